@@ -0,0 +1,307 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An implementation of the CLI protocol of the
+//! [sigstore conformance suite](https://github.com/sigstore/sigstore-conformance),
+//! built only on top of the public API of the crate.
+//!
+//! The bundles are the ones produced by `cosign sign-blob --bundle`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use clap::{Args, Parser, Subcommand};
+use sha2::{Digest, Sha256};
+use sigstore::cosign::bundle::{Bundle, SignedArtifactBundle};
+use sigstore::cosign::SigningSession;
+use sigstore::crypto::trusted_root::{TrustedRoot, ValidityPeriod};
+use sigstore::crypto::SigningScheme;
+use sigstore::fulcio::token::{IdentityToken, TokenValidator};
+use sigstore::fulcio::{FulcioClient, IdentityKind, TokenProvider, FULCIO_ROOT};
+use sigstore::rekor::apis::configuration::Configuration;
+use sigstore::rekor::apis::{entries_api, index_api};
+use sigstore::rekor::entries::IdentityPolicy;
+use sigstore::rekor::models::SearchIndex;
+use sigstore::tuf::SigstoreRepository;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, EnvFilter};
+use x509_cert::der::Decode;
+use x509_cert::Certificate;
+
+const FULCIO_STAGING_URL: &str = "https://fulcio.sigstage.dev/";
+const REKOR_STAGING_URL: &str = "https://rekor.sigstage.dev";
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+
+    /// Enable verbose mode
+    #[clap(short, long, global = true)]
+    verbose: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Sign a blob, writing the signature and the certificate
+    Sign(SignArgs),
+    /// Sign a blob, writing a bundle
+    SignBundle(SignBundleArgs),
+    /// Verify a blob with its signature and certificate
+    Verify(VerifyArgs),
+    /// Verify a blob with its bundle
+    VerifyBundle(VerifyBundleArgs),
+}
+
+#[derive(Args, Debug)]
+struct Instance {
+    /// Use the staging instances of Fulcio and Rekor
+    #[clap(long)]
+    staging: bool,
+
+    /// A `trusted_root.json` document, used instead of the trust material
+    /// distributed via TUF. Required by `--staging`.
+    #[clap(long)]
+    trusted_root: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct SignArgs {
+    /// The OIDC identity token given to Fulcio
+    #[clap(long)]
+    identity_token: String,
+
+    /// Where to write the base64 encoded signature
+    #[clap(long)]
+    signature: PathBuf,
+
+    /// Where to write the PEM encoded signing certificate
+    #[clap(long)]
+    certificate: PathBuf,
+
+    #[clap(flatten)]
+    instance: Instance,
+
+    /// The blob to sign
+    file: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct SignBundleArgs {
+    /// The OIDC identity token given to Fulcio
+    #[clap(long)]
+    identity_token: String,
+
+    /// Where to write the bundle
+    #[clap(long)]
+    bundle: PathBuf,
+
+    #[clap(flatten)]
+    instance: Instance,
+
+    /// The blob to sign
+    file: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct Identity {
+    /// The email or URI expected inside of the signing certificate
+    #[clap(long)]
+    certificate_identity: String,
+
+    /// The OIDC issuer expected inside of the signing certificate
+    #[clap(long)]
+    certificate_oidc_issuer: String,
+}
+
+#[derive(Args, Debug)]
+struct VerifyArgs {
+    /// The base64 encoded signature
+    #[clap(long)]
+    signature: PathBuf,
+
+    /// The PEM encoded signing certificate
+    #[clap(long)]
+    certificate: PathBuf,
+
+    #[clap(flatten)]
+    identity: Identity,
+
+    #[clap(flatten)]
+    instance: Instance,
+
+    /// The blob to verify
+    file: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct VerifyBundleArgs {
+    /// The bundle
+    #[clap(long)]
+    bundle: PathBuf,
+
+    #[clap(flatten)]
+    identity: Identity,
+
+    #[clap(flatten)]
+    instance: Instance,
+
+    /// The blob to verify
+    file: PathBuf,
+}
+
+impl Instance {
+    fn fulcio_url(&self) -> Result<url::Url> {
+        let url = if self.staging {
+            FULCIO_STAGING_URL
+        } else {
+            FULCIO_ROOT
+        };
+        Ok(url::Url::parse(url)?)
+    }
+
+    fn rekor_configuration(&self) -> Configuration {
+        let mut configuration = Configuration::default();
+        if self.staging {
+            configuration.base_path = REKOR_STAGING_URL.to_string();
+        }
+        configuration
+    }
+
+    async fn trusted_root(&self) -> Result<TrustedRoot> {
+        if let Some(path) = &self.trusted_root {
+            return Ok(TrustedRoot::from_json(&fs::read_to_string(path)?)?);
+        }
+        if self.staging {
+            bail!("the TUF root embedded in the crate is the production one, --staging requires --trusted-root");
+        }
+
+        let repo = tokio::task::spawn_blocking(|| SigstoreRepository::fetch(None)).await??;
+        let mut trusted_root = TrustedRoot::new();
+        trusted_root.add_rekor_pub_key(repo.rekor_pub_key(), ValidityPeriod::always())?;
+        trusted_root.add_fulcio_cert_chain(repo.fulcio_certs(), ValidityPeriod::always());
+        Ok(trusted_root)
+    }
+}
+
+impl Identity {
+    fn policy(&self) -> IdentityPolicy {
+        IdentityPolicy {
+            subject: self.certificate_identity.clone(),
+            issuer: Some(self.certificate_oidc_issuer.clone()),
+        }
+    }
+}
+
+/// Sign `file` with a certificate issued by Fulcio for `identity_token`,
+/// the signature is uploaded to Rekor
+async fn sign(
+    identity_token: &str,
+    instance: &Instance,
+    file: &Path,
+) -> Result<SignedArtifactBundle> {
+    let token = IdentityToken::from_str(identity_token)?;
+    let identity_kind = if token.email().is_some() {
+        IdentityKind::Email
+    } else {
+        IdentityKind::Uri
+    };
+    let token_provider = TokenProvider::for_identity(token.token().clone(), identity_kind)?;
+    let fulcio = FulcioClient::new(instance.fulcio_url()?, token_provider)
+        .with_token_validator(TokenValidator::default());
+
+    let session = SigningSession::new(fulcio, SigningScheme::default()).await?;
+    let blob = fs::read(file).with_context(|| format!("cannot read {}", file.display()))?;
+    Ok(session
+        .sign_blob(&instance.rekor_configuration(), &blob)
+        .await?)
+}
+
+/// Verify `bundle`, then ensure it has been signed by the expected identity
+async fn verify(
+    bundle: &SignedArtifactBundle,
+    identity: &Identity,
+    instance: &Instance,
+    file: &Path,
+) -> Result<()> {
+    let blob = fs::read(file).with_context(|| format!("cannot read {}", file.display()))?;
+    let trusted_root = instance.trusted_root().await?;
+    let certificate = bundle.verify_blob(&blob, &trusted_root)?;
+    let certificate = Certificate::from_der(&certificate)?;
+    identity.policy().check(&certificate)?;
+    Ok(())
+}
+
+/// Find the Rekor entries recording the signature of `file`, the first
+/// one that can be verified is used
+async fn verify_detached(args: &VerifyArgs) -> Result<()> {
+    let signature = fs::read_to_string(&args.signature)?.trim().to_string();
+    let cert = BASE64_STD_ENGINE.encode(fs::read(&args.certificate)?);
+    let blob = fs::read(&args.file)?;
+
+    let configuration = args.instance.rekor_configuration();
+    let query = SearchIndex::new().with_sha256_digest(&Sha256::digest(&blob));
+    let uuids = index_api::search_index(&configuration, query).await?;
+
+    let mut last_error = anyhow!("the signature has not been recorded by Rekor");
+    for uuid in uuids {
+        let entry = entries_api::get_log_entry_by_uuid(&configuration, &uuid).await?;
+        let bundle = SignedArtifactBundle {
+            base64_signature: signature.clone(),
+            cert: cert.clone(),
+            rekor_bundle: Bundle::from_log_entry(&entry)?,
+        };
+        match verify(&bundle, &args.identity, &args.instance, &args.file).await {
+            Ok(()) => return Ok(()),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+#[tokio::main]
+pub async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    // setup logging
+    let level_filter = if cli.verbose { "debug" } else { "info" };
+    let filter_layer = EnvFilter::new(level_filter);
+    tracing_subscriber::registry()
+        .with(filter_layer)
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .init();
+
+    match cli.command {
+        Commands::Sign(args) => {
+            let bundle = sign(&args.identity_token, &args.instance, &args.file).await?;
+            fs::write(&args.signature, &bundle.base64_signature)?;
+            fs::write(&args.certificate, BASE64_STD_ENGINE.decode(&bundle.cert)?)?;
+        }
+        Commands::SignBundle(args) => {
+            let bundle = sign(&args.identity_token, &args.instance, &args.file).await?;
+            fs::write(&args.bundle, serde_json::to_string(&bundle)?)?;
+        }
+        Commands::Verify(args) => verify_detached(&args).await?,
+        Commands::VerifyBundle(args) => {
+            let bundle: SignedArtifactBundle =
+                serde_json::from_str(&fs::read_to_string(&args.bundle)?)?;
+            verify(&bundle, &args.identity, &args.instance, &args.file).await?;
+        }
+    }
+    Ok(())
+}
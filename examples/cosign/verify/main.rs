@@ -19,6 +19,8 @@ use sigstore::cosign::verification_constraint::{
     PublicKeyVerifier, VerificationConstraintVec,
 };
 use sigstore::cosign::{CosignCapabilities, SignatureLayer};
+use sigstore::crypto::certificate_transparency::SctPolicy;
+use sigstore::crypto::trusted_root::{TrustedRoot, ValidityPeriod};
 use sigstore::crypto::SigningScheme;
 use sigstore::errors::SigstoreVerifyConstraintsError;
 use sigstore::registry::{ClientConfig, ClientProtocol, OciReference};
@@ -64,11 +66,13 @@ struct Cli {
     #[clap(long)]
     use_sigstore_tuf_data: bool,
 
-    /// File containing Rekor's public key (e.g.: ~/.sigstore/root/targets/rekor.pub)
+    /// File containing Rekor's public key (e.g.: ~/.sigstore/root/targets/rekor.pub).
+    /// When used with `use-sigstore-tuf-data`, the key is trusted together with the TUF one
     #[clap(long, required(false))]
     rekor_pub_key: Option<String>,
 
-    /// File containing Fulcio's certificate (e.g.: ~/.sigstore/root/targets/fulcio.crt.pem)
+    /// File containing Fulcio's certificate (e.g.: ~/.sigstore/root/targets/fulcio.crt.pem).
+    /// When used with `use-sigstore-tuf-data`, the certificate is trusted together with the TUF ones
     #[clap(long, required(false))]
     fulcio_cert: Option<String>,
 
@@ -96,6 +100,12 @@ struct Cli {
     #[clap(long)]
     enable_registry_caching: bool,
 
+    /// Do not verify the SCT of the certificates issued by Fulcio. The SCT can
+    /// be verified only with `use-sigstore-tuf-data`, which provides the keys
+    /// of the CT logs
+    #[clap(long)]
+    insecure_ignore_sct: bool,
+
     /// Number of loops to be done. Useful only for testing `enable-registry-caching`
     #[clap(long, default_value = "1")]
     loops: u32,
@@ -147,10 +157,18 @@ async fn run_app(
         client_builder = client_builder.with_fulcio_certs(&frd.fulcio_certs);
     }
 
+    if let Some(trusted_root) = frd.trusted_root.as_ref() {
+        client_builder = client_builder.with_trusted_root(trusted_root.clone());
+    }
+
     if cli.enable_registry_caching {
         client_builder = client_builder.enable_registry_caching();
     }
 
+    if cli.insecure_ignore_sct {
+        client_builder = client_builder.with_sct_policy(SctPolicy::Skip);
+    }
+
     let mut client = client_builder.build()?;
 
     // Build verification constraints
@@ -242,6 +260,7 @@ async fn run_app(
 struct FulcioAndRekorData {
     pub rekor_pub_key: Option<String>,
     pub fulcio_certs: Vec<sigstore::registry::Certificate>,
+    pub trusted_root: Option<TrustedRoot>,
 }
 
 async fn fulcio_and_rekor_data(cli: &Cli) -> anyhow::Result<FulcioAndRekorData> {
@@ -258,13 +277,17 @@ async fn fulcio_and_rekor_data(cli: &Cli) -> anyhow::Result<FulcioAndRekorData>
         let repo: SigstoreRepository = repo?;
         data.fulcio_certs = repo.fulcio_certs().into();
         data.rekor_pub_key = Some(repo.rekor_pub_key().to_string());
+        // provides the keys of the CT logs too
+        data.trusted_root = Some(TrustedRoot::try_from(&repo)?);
     };
 
     if let Some(path) = cli.rekor_pub_key.as_ref() {
-        data.rekor_pub_key = Some(
-            fs::read_to_string(path)
-                .map_err(|e| anyhow!("Error reading rekor public key from disk: {}", e))?,
-        );
+        let key = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Error reading rekor public key from disk: {}", e))?;
+        if let Some(trusted_root) = data.trusted_root.as_mut() {
+            trusted_root.add_rekor_pub_key(&key, ValidityPeriod::always())?;
+        }
+        data.rekor_pub_key = Some(key);
     }
 
     if let Some(path) = cli.fulcio_cert.as_ref() {
@@ -275,6 +298,12 @@ async fn fulcio_and_rekor_data(cli: &Cli) -> anyhow::Result<FulcioAndRekorData>
             encoding: sigstore::registry::CertificateEncoding::Pem,
             data: cert_data,
         };
+        if let Some(trusted_root) = data.trusted_root.as_mut() {
+            trusted_root.add_fulcio_cert_chain(
+                std::slice::from_ref(&certificate),
+                ValidityPeriod::always(),
+            );
+        }
         data.fulcio_certs.push(certificate);
     }
 
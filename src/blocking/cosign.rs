@@ -0,0 +1,212 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Blocking facade over the [cosign client](crate::cosign::Client).
+//!
+//! The functions verifying the layers returned by the client, like
+//! [`crate::cosign::verify_constraints`], are synchronous already and can
+//! be used as they are.
+
+use std::collections::HashMap;
+
+use tokio::runtime::Runtime;
+
+use crate::cosign::attestation::VerifiedAttestation;
+use crate::cosign::verification_cache::VerifiedImage;
+use crate::cosign::verification_constraint::VerificationConstraint;
+use crate::cosign::{AttachmentKind, CosignCapabilities, DownloadedLayer, Sbom, SignatureLayer};
+use crate::crypto::CosignVerificationKey;
+use crate::errors::Result;
+use crate::registry::{Auth, OciReference, PushResponse, Referrer};
+
+/// Blocking version of [`crate::cosign::Client`]. Each method is the
+/// blocking version of the [`CosignCapabilities`] method of the same name.
+///
+/// ```rust,no_run
+/// use sigstore::blocking::cosign::Client;
+/// use sigstore::cosign::ClientBuilder;
+/// use sigstore::registry::{Auth, OciReference};
+///
+/// # fn doc() -> sigstore::errors::Result<()> {
+/// let mut client = Client::new(ClientBuilder::default().build()?)?;
+/// let image: OciReference = "registry-testing.svc.lan/busybox".parse()?;
+/// let (cosign_image, source_image_digest) = client.triangulate(&image, &Auth::Anonymous)?;
+/// let layers =
+///     client.trusted_signature_layers(&Auth::Anonymous, &source_image_digest, &cosign_image)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Client {
+    inner: crate::cosign::Client,
+    runtime: Runtime,
+}
+
+impl Client {
+    /// Wrap the async `client`, built with a
+    /// [`ClientBuilder`](crate::cosign::ClientBuilder)
+    pub fn new(client: crate::cosign::Client) -> Result<Self> {
+        Ok(Client {
+            inner: client,
+            runtime: super::runtime()?,
+        })
+    }
+
+    /// Blocking version of [`crate::cosign::Client::resolve_digest`]
+    pub fn resolve_digest(&mut self, image: &OciReference, auth: &Auth) -> Result<String> {
+        self.runtime
+            .block_on(self.inner.resolve_digest(image, auth))
+    }
+
+    /// Blocking version of [`crate::cosign::Client::referrers`]
+    pub fn referrers(
+        &mut self,
+        auth: &Auth,
+        image: &OciReference,
+        kind: AttachmentKind,
+    ) -> Result<Vec<Referrer>> {
+        self.runtime
+            .block_on(self.inner.referrers(auth, image, kind))
+    }
+
+    /// Blocking version of [`crate::cosign::Client::discover_signature_layers`]
+    pub fn discover_signature_layers(
+        &mut self,
+        auth: &Auth,
+        image: &OciReference,
+    ) -> Result<Vec<SignatureLayer>> {
+        self.runtime
+            .block_on(self.inner.discover_signature_layers(auth, image))
+    }
+
+    /// Blocking version of [`crate::cosign::Client::discover_attestations`]
+    pub fn discover_attestations(
+        &mut self,
+        auth: &Auth,
+        image: &OciReference,
+        verification_key: Option<&CosignVerificationKey>,
+    ) -> Result<Vec<VerifiedAttestation>> {
+        self.runtime.block_on(
+            self.inner
+                .discover_attestations(auth, image, verification_key),
+        )
+    }
+
+    /// Blocking version of [`crate::cosign::Client::attach_sbom`]
+    pub fn attach_sbom(
+        &mut self,
+        auth: &Auth,
+        image: &OciReference,
+        sbom: &Sbom,
+    ) -> Result<PushResponse> {
+        self.runtime
+            .block_on(self.inner.attach_sbom(auth, image, sbom))
+    }
+
+    /// Blocking version of [`crate::cosign::Client::fetch_sboms`]
+    pub fn fetch_sboms(&mut self, auth: &Auth, image: &OciReference) -> Result<Vec<Sbom>> {
+        self.runtime.block_on(self.inner.fetch_sboms(auth, image))
+    }
+
+    /// Blocking version of [`CosignCapabilities::triangulate`]
+    pub fn triangulate(
+        &mut self,
+        image: &OciReference,
+        auth: &Auth,
+    ) -> Result<(OciReference, String)> {
+        self.runtime.block_on(self.inner.triangulate(image, auth))
+    }
+
+    /// Blocking version of [`CosignCapabilities::trusted_signature_layers`]
+    pub fn trusted_signature_layers(
+        &mut self,
+        auth: &Auth,
+        source_image_digest: &str,
+        cosign_image: &OciReference,
+    ) -> Result<Vec<SignatureLayer>> {
+        self.runtime.block_on(self.inner.trusted_signature_layers(
+            auth,
+            source_image_digest,
+            cosign_image,
+        ))
+    }
+
+    /// Blocking version of [`CosignCapabilities::push_signature`]
+    pub fn push_signature(
+        &mut self,
+        annotations: Option<HashMap<String, String>>,
+        auth: &Auth,
+        target_reference: &OciReference,
+        signature_layers: Vec<SignatureLayer>,
+    ) -> Result<PushResponse> {
+        self.runtime.block_on(self.inner.push_signature(
+            annotations,
+            auth,
+            target_reference,
+            signature_layers,
+        ))
+    }
+
+    /// Blocking version of [`CosignCapabilities::download`]
+    pub fn download(
+        &mut self,
+        auth: &Auth,
+        image: &OciReference,
+        kind: AttachmentKind,
+    ) -> Result<Vec<DownloadedLayer>> {
+        self.runtime
+            .block_on(self.inner.download(auth, image, kind))
+    }
+
+    /// Blocking version of [`CosignCapabilities::verify_attestations`]
+    pub fn verify_attestations(
+        &mut self,
+        auth: &Auth,
+        image: &OciReference,
+        verification_key: Option<&CosignVerificationKey>,
+    ) -> Result<Vec<VerifiedAttestation>> {
+        self.runtime.block_on(
+            self.inner
+                .verify_attestations(auth, image, verification_key),
+        )
+    }
+
+    /// Blocking version of [`crate::cosign::Client::verify_first_signature_layer`]
+    pub fn verify_first_signature_layer(
+        &mut self,
+        auth: &Auth,
+        source_image_digest: &str,
+        cosign_image: &OciReference,
+        constraints: &[Box<dyn VerificationConstraint>],
+    ) -> Result<SignatureLayer> {
+        self.runtime
+            .block_on(self.inner.verify_first_signature_layer(
+                auth,
+                source_image_digest,
+                cosign_image,
+                constraints,
+            ))
+    }
+
+    /// Blocking version of [`crate::cosign::Client::verify_image`]
+    pub fn verify_image(
+        &mut self,
+        auth: &Auth,
+        image: &OciReference,
+        constraints: &[Box<dyn VerificationConstraint>],
+    ) -> Result<VerifiedImage> {
+        self.runtime
+            .block_on(self.inner.verify_image(auth, image, constraints))
+    }
+}
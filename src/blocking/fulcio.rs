@@ -0,0 +1,69 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Blocking facade over the [Fulcio client](crate::fulcio::FulcioClient).
+
+use std::sync::Arc;
+
+use tokio::runtime::Runtime;
+use url::Url;
+
+use crate::crypto::signing_key::SigStoreSigner;
+use crate::crypto::SigningScheme;
+use crate::errors::Result;
+use crate::fulcio::token::TokenValidator;
+use crate::fulcio::{FulcioCert, TokenProvider};
+use crate::http::HttpClient;
+
+/// Blocking version of [`crate::fulcio::FulcioClient`]
+pub struct FulcioClient {
+    inner: crate::fulcio::FulcioClient,
+    runtime: Runtime,
+}
+
+impl FulcioClient {
+    /// Create a new Fulcio client, see [`crate::fulcio::FulcioClient::new`]
+    pub fn new(root_url: Url, token_provider: TokenProvider) -> Result<Self> {
+        Ok(FulcioClient {
+            inner: crate::fulcio::FulcioClient::new(root_url, token_provider),
+            runtime: super::runtime()?,
+        })
+    }
+
+    /// See [`crate::fulcio::FulcioClient::with_token_validator`]
+    pub fn with_token_validator(self, token_validator: TokenValidator) -> Self {
+        FulcioClient {
+            inner: self.inner.with_token_validator(token_validator),
+            ..self
+        }
+    }
+
+    /// See [`crate::fulcio::FulcioClient::with_http_client`]
+    pub fn with_http_client(self, http_client: Arc<dyn HttpClient>) -> Self {
+        FulcioClient {
+            inner: self.inner.with_http_client(http_client),
+            ..self
+        }
+    }
+
+    /// Blocking version of [`crate::fulcio::FulcioClient::request_cert`]
+    pub fn request_cert(
+        self,
+        signing_scheme: SigningScheme,
+    ) -> Result<(SigStoreSigner, FulcioCert)> {
+        self.runtime
+            .block_on(self.inner.request_cert(signing_scheme))
+    }
+}
@@ -0,0 +1,59 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Synchronous facades over the async clients of this crate, for the CLI
+//! tools and the codebases that do not use async, in the same way as
+//! `reqwest::blocking`.
+//!
+//! Each facade owns a single-threaded tokio runtime, which drives the
+//! futures of the wrapped client until they complete:
+//!
+//! ```rust,no_run
+//! use sigstore::blocking::rekor::Client;
+//! use sigstore::rekor::apis::configuration::Configuration;
+//!
+//! # fn doc() -> Result<(), Box<dyn std::error::Error>> {
+//! let rekor = Client::new(Configuration::default())?;
+//! let log_info = rekor.get_log_info()?;
+//! println!("root hash: {}", log_info.root_hash);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! **Warning:** like the ones of `reqwest::blocking`, the facades must not
+//! be used from within an async runtime, doing that panics. Use the async
+//! clients there.
+//!
+//! This module is available only when the `blocking` feature is enabled.
+
+#[cfg(feature = "cosign")]
+pub mod cosign;
+
+#[cfg(feature = "fulcio")]
+pub mod fulcio;
+
+#[cfg(feature = "rekor")]
+pub mod rekor;
+
+#[cfg(feature = "tuf")]
+pub mod tuf;
+
+/// The runtime driving the futures of a facade
+#[cfg(any(feature = "cosign", feature = "fulcio", feature = "rekor"))]
+pub(crate) fn runtime() -> crate::errors::Result<tokio::runtime::Runtime> {
+    Ok(tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?)
+}
@@ -0,0 +1,150 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Blocking facade over the [Rekor APIs](crate::rekor::apis).
+
+use tokio::runtime::Runtime;
+
+use crate::errors::Result;
+use crate::rekor::apis::configuration::Configuration;
+use crate::rekor::apis::entries_api::{
+    self, CreateLogEntryError, GetLogEntryByIndexError, GetLogEntryByUuidError, SearchLogQueryError,
+};
+use crate::rekor::apis::index_api::{self, SearchIndexError};
+use crate::rekor::apis::pubkey_api::{self, GetPublicKeyError};
+use crate::rekor::apis::tlog_api::{self, GetLogInfoError, GetLogProofError};
+use crate::rekor::apis::Error;
+use crate::rekor::models::{
+    log_entry::LogEntry, ConsistencyProof, LogInfo, ProposedEntry, SearchIndex, SearchLogQuery,
+};
+
+/// Blocking Rekor client. Each method is the blocking version of the
+/// function of the same name of the [Rekor APIs](crate::rekor::apis).
+pub struct Client {
+    configuration: Configuration,
+    runtime: Runtime,
+}
+
+impl Client {
+    /// Create a client reaching the Rekor instance of `configuration`
+    pub fn new(configuration: Configuration) -> Result<Self> {
+        Ok(Client {
+            configuration,
+            runtime: super::runtime()?,
+        })
+    }
+
+    /// The configuration of the client
+    pub fn configuration(&self) -> &Configuration {
+        &self.configuration
+    }
+
+    /// Blocking version of [`entries_api::create_log_entry`]
+    pub fn create_log_entry(
+        &self,
+        proposed_entry: ProposedEntry,
+    ) -> std::result::Result<LogEntry, Error<CreateLogEntryError>> {
+        self.runtime.block_on(entries_api::create_log_entry(
+            &self.configuration,
+            proposed_entry,
+        ))
+    }
+
+    /// Blocking version of [`entries_api::get_log_entry_by_index`]
+    pub fn get_log_entry_by_index(
+        &self,
+        log_index: i32,
+    ) -> std::result::Result<LogEntry, Error<GetLogEntryByIndexError>> {
+        self.runtime.block_on(entries_api::get_log_entry_by_index(
+            &self.configuration,
+            log_index,
+        ))
+    }
+
+    /// Blocking version of [`entries_api::get_log_entry_by_uuid`]
+    pub fn get_log_entry_by_uuid(
+        &self,
+        entry_uuid: &str,
+    ) -> std::result::Result<LogEntry, Error<GetLogEntryByUuidError>> {
+        self.runtime.block_on(entries_api::get_log_entry_by_uuid(
+            &self.configuration,
+            entry_uuid,
+        ))
+    }
+
+    /// Blocking version of [`entries_api::search_log_query_entries`]
+    pub fn search_log_query(
+        &self,
+        query: SearchLogQuery,
+    ) -> std::result::Result<Vec<LogEntry>, Error<SearchLogQueryError>> {
+        self.runtime.block_on(entries_api::search_log_query_entries(
+            &self.configuration,
+            query,
+        ))
+    }
+
+    /// Blocking version of [`index_api::search_index`]
+    pub fn search_index(
+        &self,
+        query: SearchIndex,
+    ) -> std::result::Result<Vec<String>, Error<SearchIndexError>> {
+        self.runtime
+            .block_on(index_api::search_index(&self.configuration, query))
+    }
+
+    /// Blocking version of [`pubkey_api::get_public_key`]
+    pub fn get_public_key(
+        &self,
+        tree_id: Option<&str>,
+    ) -> std::result::Result<String, Error<GetPublicKeyError>> {
+        self.runtime
+            .block_on(pubkey_api::get_public_key(&self.configuration, tree_id))
+    }
+
+    /// Blocking version of [`tlog_api::get_log_info`]
+    pub fn get_log_info(&self) -> std::result::Result<LogInfo, Error<GetLogInfoError>> {
+        self.runtime
+            .block_on(tlog_api::get_log_info(&self.configuration))
+    }
+
+    /// Blocking version of [`tlog_api::get_log_proof`]
+    pub fn get_log_proof(
+        &self,
+        last_size: i32,
+        first_size: Option<&str>,
+        tree_id: Option<&str>,
+    ) -> std::result::Result<ConsistencyProof, Error<GetLogProofError>> {
+        self.runtime.block_on(tlog_api::get_log_proof(
+            &self.configuration,
+            last_size,
+            first_size,
+            tree_id,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_client_outside_of_a_runtime() {
+        let client = Client::new(Configuration::default()).expect("cannot create the client");
+        assert_eq!(
+            client.configuration().base_path,
+            Configuration::default().base_path
+        );
+    }
+}
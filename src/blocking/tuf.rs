@@ -0,0 +1,22 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Blocking access to Sigstore's TUF repository.
+//!
+//! The TUF client is synchronous already: this module re-exports it, so
+//! that the blocking code can find all the clients of this crate under
+//! [`crate::blocking`].
+
+pub use crate::tuf::SigstoreRepository;
@@ -0,0 +1,437 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The Sigstore bundle, the `Bundle` message of the Sigstore
+//! [protobuf-specs](https://github.com/sigstore/protobuf-specs).
+//!
+//! A bundle holds everything needed to verify an artifact offline: the
+//! signature, or the DSSE envelope, the certificate or a hint about the key
+//! of the signer and the entries recorded by the transparency log. The
+//! bundles written by sigstore-python, sigstore-go, sigstore-js and by
+//! `cosign --new-bundle-format` can be read, and vice versa.
+//!
+//! The versions 0.1, 0.2 and 0.3 of the format are supported. The structs
+//! follow the JSON mapping of the protobuf messages: the binary fields are
+//! base64 encoded and the 64 bits integers are strings.
+//!
+//! Note well: reading a bundle doesn't verify it in any way, use the
+//! [`verify::Verifier`] for that.
+//!
+//! ```rust,no_run
+//! use sigstore::bundle::{Bundle, BundleContent};
+//!
+//! # fn doc() -> sigstore::errors::Result<()> {
+//! let bundle = Bundle::from_json(&std::fs::read_to_string("artifact.sigstore.json")?)?;
+//! if let BundleContent::MessageSignature(signature) = &bundle.content {
+//!     println!("signature: {}", signature.signature);
+//! }
+//! std::fs::write("copy.sigstore.json", bundle.to_json()?)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use serde::{Deserialize, Serialize};
+
+use crate::dsse::Envelope;
+use crate::errors::{Result, SigstoreError};
+
+#[cfg(all(feature = "cosign", feature = "cert", feature = "rekor"))]
+pub mod verify;
+
+/// Media type of the bundles of version 0.1
+pub const BUNDLE_V01_MEDIA_TYPE: &str = "application/vnd.dev.sigstore.bundle+json;version=0.1";
+/// Media type of the bundles of version 0.2
+pub const BUNDLE_V02_MEDIA_TYPE: &str = "application/vnd.dev.sigstore.bundle+json;version=0.2";
+/// Media type of the bundles of version 0.3
+pub const BUNDLE_V03_MEDIA_TYPE: &str = "application/vnd.dev.sigstore.bundle.v0.3+json";
+/// Alternative media type of the bundles of version 0.3, written by some
+/// clients
+pub const BUNDLE_V03_LEGACY_MEDIA_TYPE: &str =
+    "application/vnd.dev.sigstore.bundle+json;version=0.3";
+
+/// The versions of the bundle format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BundleVersion {
+    V0_1,
+    V0_2,
+    V0_3,
+}
+
+impl BundleVersion {
+    /// The version identified by the `media_type` of a bundle
+    pub fn from_media_type(media_type: &str) -> Result<Self> {
+        match media_type {
+            BUNDLE_V01_MEDIA_TYPE => Ok(BundleVersion::V0_1),
+            BUNDLE_V02_MEDIA_TYPE => Ok(BundleVersion::V0_2),
+            BUNDLE_V03_MEDIA_TYPE | BUNDLE_V03_LEGACY_MEDIA_TYPE => Ok(BundleVersion::V0_3),
+            _ => Err(SigstoreError::SigstoreBundleError(format!(
+                "unsupported media type {media_type}"
+            ))),
+        }
+    }
+
+    /// The media type of the bundles of this version
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            BundleVersion::V0_1 => BUNDLE_V01_MEDIA_TYPE,
+            BundleVersion::V0_2 => BUNDLE_V02_MEDIA_TYPE,
+            BundleVersion::V0_3 => BUNDLE_V03_MEDIA_TYPE,
+        }
+    }
+}
+
+/// A Sigstore bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bundle {
+    pub media_type: String,
+    pub verification_material: VerificationMaterial,
+    #[serde(flatten)]
+    pub content: BundleContent,
+}
+
+/// What has been signed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BundleContent {
+    /// The signature of an artifact
+    MessageSignature(MessageSignature),
+    /// A DSSE envelope, usually holding an in-toto attestation
+    DsseEnvelope(Envelope),
+}
+
+/// The signature of an artifact
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageSignature {
+    /// The digest of the artifact
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_digest: Option<HashOutput>,
+    /// The base64 encoded signature
+    pub signature: String,
+}
+
+/// A digest, e.g. the one of a signed artifact
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HashOutput {
+    /// The algorithm, e.g. `SHA2_256`
+    pub algorithm: String,
+    /// The base64 encoded digest
+    pub digest: String,
+}
+
+/// The material needed to verify the content of the bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationMaterial {
+    #[serde(flatten)]
+    pub content: VerificationMaterialContent,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tlog_entries: Vec<TransparencyLogEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_verification_data: Option<TimestampVerificationData>,
+}
+
+/// The identity of the signer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VerificationMaterialContent {
+    /// A hint about the public key of the signer, which must be provided by
+    /// the verifier
+    PublicKey(PublicKeyIdentifier),
+    /// The certificate chain of the signer, starting with the leaf
+    /// certificate. Used by the versions 0.1 and 0.2 of the format.
+    X509CertificateChain(X509CertificateChain),
+    /// The certificate of the signer. Used by the version 0.3 of the format.
+    Certificate(X509Certificate),
+}
+
+/// A hint about a public key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicKeyIdentifier {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hint: String,
+}
+
+/// A chain of certificates, starting with the leaf one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct X509CertificateChain {
+    pub certificates: Vec<X509Certificate>,
+}
+
+/// A DER encoded certificate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct X509Certificate {
+    /// The base64 encoded DER of the certificate
+    pub raw_bytes: String,
+}
+
+/// An entry of the transparency log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransparencyLogEntry {
+    #[serde(with = "int64")]
+    pub log_index: i64,
+    pub log_id: LogId,
+    pub kind_version: KindVersion,
+    /// The time of integration into the log, in seconds since the epoch
+    #[serde(with = "int64")]
+    pub integrated_time: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inclusion_promise: Option<InclusionPromise>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inclusion_proof: Option<InclusionProof>,
+    /// The base64 encoded body of the entry, as returned by Rekor
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub canonicalized_body: String,
+}
+
+/// The ID of a transparency log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogId {
+    /// The base64 encoded digest of the public key of the log
+    pub key_id: String,
+}
+
+/// The kind of a Rekor entry, e.g. `hashedrekord` version `0.0.1`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KindVersion {
+    pub kind: String,
+    pub version: String,
+}
+
+/// The promise of Rekor to integrate an entry into the log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InclusionPromise {
+    /// The base64 encoded signed entry timestamp
+    pub signed_entry_timestamp: String,
+}
+
+/// The proof of the inclusion of an entry into the log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InclusionProof {
+    #[serde(with = "int64")]
+    pub log_index: i64,
+    /// The base64 encoded root hash of the tree
+    pub root_hash: String,
+    #[serde(with = "int64")]
+    pub tree_size: i64,
+    /// The base64 encoded hashes of the path to the root
+    #[serde(default)]
+    pub hashes: Vec<String>,
+    pub checkpoint: Checkpoint,
+}
+
+/// A signed checkpoint of the log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The checkpoint, in the signed note format
+    pub envelope: String,
+}
+
+/// Timestamps proving when the signature was created
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimestampVerificationData {
+    #[serde(default)]
+    pub rfc3161_timestamps: Vec<Rfc3161SignedTimestamp>,
+}
+
+/// A RFC 3161 timestamp
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rfc3161SignedTimestamp {
+    /// The base64 encoded DER of the timestamp response
+    pub signed_timestamp: String,
+}
+
+impl Bundle {
+    /// Parse a JSON encoded bundle, of any of the supported versions
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let bundle: Bundle = serde_json::from_str(raw)
+            .map_err(|e| SigstoreError::SigstoreBundleError(format!("cannot parse bundle: {e}")))?;
+        bundle.version()?;
+        Ok(bundle)
+    }
+
+    /// Serialize the bundle to JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// The version of the format of the bundle
+    pub fn version(&self) -> Result<BundleVersion> {
+        BundleVersion::from_media_type(&self.media_type)
+    }
+
+    /// The DER encoded certificate of the signer, when the signature has been
+    /// made in keyless mode
+    pub fn signing_certificate(&self) -> Result<Option<Vec<u8>>> {
+        let certificate = match &self.verification_material.content {
+            VerificationMaterialContent::PublicKey(_) => return Ok(None),
+            VerificationMaterialContent::Certificate(certificate) => certificate,
+            VerificationMaterialContent::X509CertificateChain(chain) => {
+                chain.certificates.first().ok_or_else(|| {
+                    SigstoreError::SigstoreBundleError("empty certificate chain".to_string())
+                })?
+            }
+        };
+        Ok(Some(BASE64_STD_ENGINE.decode(&certificate.raw_bytes)?))
+    }
+}
+
+/// The protobuf JSON mapping encodes the 64 bits integers as strings, but
+/// accepts numbers too
+mod int64 {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Int64 {
+        String(String),
+        Number(i64),
+    }
+
+    pub(super) fn serialize<S: Serializer>(value: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+        match Int64::deserialize(deserializer)? {
+            Int64::String(value) => value.parse().map_err(serde::de::Error::custom),
+            Int64::Number(value) => Ok(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn tlog_entry() -> Value {
+        json!({
+            "logIndex": "25579",
+            "logId": {"keyId": "0y8wozwgNUMhsNOntRqFvMNN5b1E0+rhO8z2zBpJAGA="},
+            "kindVersion": {"kind": "hashedrekord", "version": "0.0.1"},
+            "integratedTime": "1700000000",
+            "inclusionPromise": {"signedEntryTimestamp": "c2V0"},
+            "inclusionProof": {
+                "logIndex": "25579",
+                "rootHash": "cm9vdA==",
+                "treeSize": "25580",
+                "hashes": ["aGFzaDE=", "aGFzaDI="],
+                "checkpoint": {"envelope": "rekor.sigstore.dev - 1193050959916656506\n25580\ncm9vdA==\n"}
+            },
+            "canonicalizedBody": "Ym9keQ=="
+        })
+    }
+
+    #[test]
+    fn round_trip_message_signature_bundle() {
+        let raw = json!({
+            "mediaType": BUNDLE_V03_MEDIA_TYPE,
+            "verificationMaterial": {
+                "certificate": {"rawBytes": "Y2VydGlmaWNhdGU="},
+                "tlogEntries": [tlog_entry()],
+                "timestampVerificationData": {"rfc3161Timestamps": [{"signedTimestamp": "dHM="}]}
+            },
+            "messageSignature": {
+                "messageDigest": {"algorithm": "SHA2_256", "digest": "ZGlnZXN0"},
+                "signature": "c2lnbmF0dXJl"
+            }
+        });
+
+        let bundle = Bundle::from_json(&raw.to_string()).unwrap();
+        assert_eq!(bundle.version().unwrap(), BundleVersion::V0_3);
+        assert_eq!(
+            bundle.signing_certificate().unwrap(),
+            Some(b"certificate".to_vec())
+        );
+        let entry = &bundle.verification_material.tlog_entries[0];
+        assert_eq!(entry.log_index, 25579);
+        assert_eq!(entry.integrated_time, 1_700_000_000);
+        assert!(matches!(
+            &bundle.content,
+            BundleContent::MessageSignature(s) if s.signature == "c2lnbmF0dXJl"
+        ));
+
+        let written: Value = serde_json::from_str(&bundle.to_json().unwrap()).unwrap();
+        assert_eq!(written, raw);
+    }
+
+    #[test]
+    fn round_trip_dsse_bundle() {
+        let raw = json!({
+            "mediaType": BUNDLE_V02_MEDIA_TYPE,
+            "verificationMaterial": {
+                "x509CertificateChain": {"certificates": [{"rawBytes": "bGVhZg=="}, {"rawBytes": "Y2E="}]},
+                "tlogEntries": [tlog_entry()]
+            },
+            "dsseEnvelope": {
+                "payload": "e30=",
+                "payloadType": "application/vnd.in-toto+json",
+                "signatures": [{"sig": "c2lnbmF0dXJl"}]
+            }
+        });
+
+        let bundle = Bundle::from_json(&raw.to_string()).unwrap();
+        assert_eq!(bundle.version().unwrap(), BundleVersion::V0_2);
+        assert_eq!(
+            bundle.signing_certificate().unwrap(),
+            Some(b"leaf".to_vec())
+        );
+        match &bundle.content {
+            BundleContent::DsseEnvelope(envelope) => {
+                assert_eq!(envelope.decoded_payload().unwrap(), b"{}")
+            }
+            content => panic!("unexpected content {:?}", content),
+        }
+
+        let written: Value = serde_json::from_str(&bundle.to_json().unwrap()).unwrap();
+        assert_eq!(written, raw);
+    }
+
+    #[test]
+    fn reject_unknown_versions() {
+        let mut raw = json!({
+            "mediaType": "application/vnd.dev.sigstore.bundle+json;version=0.4",
+            "verificationMaterial": {"publicKey": {"hint": "key"}},
+            "messageSignature": {"signature": "c2lnbmF0dXJl"}
+        });
+        assert!(matches!(
+            Bundle::from_json(&raw.to_string()),
+            Err(SigstoreError::SigstoreBundleError(_))
+        ));
+
+        raw["mediaType"] = Value::from(BUNDLE_V01_MEDIA_TYPE);
+        let bundle = Bundle::from_json(&raw.to_string()).unwrap();
+        assert_eq!(bundle.signing_certificate().unwrap(), None);
+        // integers written as numbers are accepted too
+        let entry: TransparencyLogEntry = serde_json::from_value(json!({
+            "logIndex": 1,
+            "logId": {"keyId": "a2V5"},
+            "kindVersion": {"kind": "dsse", "version": "0.0.1"},
+            "integratedTime": 2
+        }))
+        .unwrap();
+        assert_eq!((entry.log_index, entry.integrated_time), (1, 2));
+    }
+}
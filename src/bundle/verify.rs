@@ -0,0 +1,697 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of Sigstore bundles, the equivalent of the verifier of
+//! sigstore-go.
+//!
+//! A [`Verifier`] checks a [`Bundle`] against the trust material of a
+//! [`TrustedRoot`] and against a [`VerificationPolicy`], describing who is
+//! expected to have signed the artifact:
+//!
+//! * the entry of the transparency log must have been promised by one of the
+//!   Rekor keys in use at the time of its integration. Starting from the
+//!   version 0.2 of the format, its inclusion proof must be valid too, and
+//!   must be about a checkpoint signed by the same log
+//! * the entry must refer to the signature, or to the envelope, of the
+//!   bundle
+//! * the RFC 3161 timestamps of the signature must have been signed by one
+//!   of the timestamp authorities of the trusted root
+//! * in keyless mode, the certificate must have been issued by Fulcio, be
+//!   valid at the time of the integration into Rekor and at the time of
+//!   each timestamp and, when the trusted root knows about CT logs, carry a
+//!   valid SCT. The identity of the certificate must satisfy the policy
+//! * the signature of the artifact, or of the DSSE envelope, must be valid.
+//!   The statement of the envelope must have the artifact as subject
+//!
+//! A bundle without tlog entry is accepted when it has at least one
+//! timestamp, for the deployments relying on a timestamp authority instead
+//! of Rekor.
+//!
+//! ```rust,no_run
+//! use sigstore::bundle::verify::{VerificationPolicy, Verifier};
+//! use sigstore::bundle::Bundle;
+//! use sigstore::cosign::verification_constraint::certificate_identity_verifier::IdentityMatcher;
+//! use sigstore::crypto::trusted_root::TrustedRoot;
+//! use std::path::Path;
+//!
+//! # fn doc() -> sigstore::errors::Result<()> {
+//! let verifier = Verifier::new(TrustedRoot::from_file(Path::new("trusted_root.json"))?);
+//! let bundle = Bundle::from_json(&std::fs::read_to_string("artifact.sigstore.json")?)?;
+//! let policy = VerificationPolicy::identity(
+//!     IdentityMatcher::exact("alice@example.com"),
+//!     IdentityMatcher::exact("https://github.com/login/oauth"),
+//! );
+//!
+//! let result = verifier.verify(&bundle, &std::fs::read("artifact")?, &policy)?;
+//! println!("signed by {:?} at {:?}", result.signer, result.integrated_time);
+//! # Ok(())
+//! # }
+//! ```
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+
+use super::{Bundle, BundleContent, BundleVersion, MessageSignature, TransparencyLogEntry};
+use crate::cosign::attestation::subjects::check_artifact;
+use crate::cosign::attestation::Statement;
+use crate::cosign::bundle::{Bundle as RekorBundle, Payload};
+use crate::cosign::identity::SignerIdentity;
+use crate::cosign::signature_layers::CertificateSignature;
+use crate::cosign::verification_constraint::certificate_identity_verifier::{
+    CertificateIdentityVerifier, IdentityMatcher,
+};
+use crate::crypto::certificate_transparency::SctPolicy;
+use crate::crypto::timestamp::SignedTimestamp;
+use crate::crypto::trusted_root::TrustedRoot;
+use crate::crypto::{CosignVerificationKey, Signature};
+use crate::dsse::{Envelope, EnvelopeVerifier};
+use crate::errors::{Result, SigstoreError};
+use crate::rekor::checkpoint::Checkpoint;
+use crate::rekor::merkle::{hash_leaf, verify_inclusion};
+
+/// Who is expected to have signed the artifact
+#[derive(Debug, Clone)]
+pub enum VerificationPolicy {
+    /// The artifact has been signed in keyless mode, by the identity
+    /// accepted by the verifier
+    Identity(CertificateIdentityVerifier),
+    /// The artifact has been signed with this long-lived key
+    PublicKey(CosignVerificationKey),
+}
+
+impl VerificationPolicy {
+    /// Accept the keyless signatures of `identity`, authenticated by
+    /// `issuer`
+    pub fn identity(identity: IdentityMatcher, issuer: IdentityMatcher) -> Self {
+        VerificationPolicy::Identity(CertificateIdentityVerifier::new(identity, issuer))
+    }
+
+    /// Accept the signatures made with `key`
+    pub fn public_key(key: CosignVerificationKey) -> Self {
+        VerificationPolicy::PublicKey(key)
+    }
+}
+
+/// The outcome of a successful verification
+#[derive(Debug, Clone)]
+pub struct VerificationResult {
+    /// The identity of the signer, for keyless signatures
+    pub signer: Option<SignerIdentity>,
+    /// The time the signature has been integrated into Rekor, in seconds
+    /// since the epoch
+    pub integrated_time: Option<i64>,
+    /// The times of the verified RFC 3161 timestamps, in seconds since the
+    /// epoch
+    pub timestamps: Vec<i64>,
+    /// The in-toto statement, when the bundle holds a DSSE envelope
+    pub statement: Option<Statement>,
+}
+
+/// Verifier of Sigstore bundles
+#[derive(Debug, Clone)]
+pub struct Verifier {
+    trusted_root: TrustedRoot,
+    sct_policy: SctPolicy,
+}
+
+impl Verifier {
+    /// A verifier trusting the material of `trusted_root`
+    pub fn new(trusted_root: TrustedRoot) -> Self {
+        Verifier {
+            trusted_root,
+            sct_policy: SctPolicy::Require,
+        }
+    }
+
+    /// Whether the SCT of the signing certificate has to be verified with
+    /// the CT logs of the trusted root, defaults to [`SctPolicy::Require`]
+    pub fn with_sct_policy(mut self, policy: SctPolicy) -> Self {
+        self.sct_policy = policy;
+        self
+    }
+
+    /// Verify that `bundle` is a valid signature of `artifact`, produced
+    /// by a signer accepted by `policy`
+    pub fn verify(
+        &self,
+        bundle: &Bundle,
+        artifact: &[u8],
+        policy: &VerificationPolicy,
+    ) -> Result<VerificationResult> {
+        let version = bundle.version()?;
+        let rekor_bundle = match bundle.verification_material.tlog_entries.as_slice() {
+            [] => None,
+            [entry] => Some(self.verify_tlog_entry(entry, version)?),
+            _ => return Err(bundle_error("the bundle has more than one tlog entry")),
+        };
+        let integrated_time = rekor_bundle.as_ref().map(|b| b.payload.integrated_time);
+        let timestamps = self.verify_timestamps(bundle)?;
+        let signing_times = integrated_time
+            .iter()
+            .chain(&timestamps)
+            .copied()
+            .collect::<Vec<_>>();
+        if signing_times.is_empty() {
+            return Err(bundle_error(
+                "the bundle has neither a tlog entry nor a timestamp",
+            ));
+        }
+
+        let certificate_signature = match bundle.signing_certificate()? {
+            Some(der) => {
+                let mut verified = None;
+                for time in signing_times {
+                    verified = Some(self.verify_certificate(&der, time)?);
+                }
+                verified
+            }
+            None => None,
+        };
+        let (key, signer) = match (policy, certificate_signature) {
+            (VerificationPolicy::Identity(verifier), Some(cs)) => {
+                check_identity(verifier, &cs)?;
+                let signer = SignerIdentity::from(&cs);
+                (cs.verification_key, Some(signer))
+            }
+            (VerificationPolicy::Identity(_), None) => {
+                return Err(policy_error(
+                    "the bundle doesn't have a certificate, it has been signed with a key",
+                ))
+            }
+            (VerificationPolicy::PublicKey(key), None) => (key.clone(), None),
+            (VerificationPolicy::PublicKey(_), Some(_)) => {
+                return Err(policy_error(
+                    "the bundle has been signed in keyless mode, not with a key",
+                ))
+            }
+        };
+
+        let statement = match &bundle.content {
+            BundleContent::MessageSignature(signature) => {
+                verify_message_signature(signature, rekor_bundle.as_ref(), &key, artifact)?;
+                None
+            }
+            BundleContent::DsseEnvelope(envelope) => Some(verify_envelope(
+                envelope,
+                rekor_bundle.as_ref(),
+                &key,
+                artifact,
+            )?),
+        };
+
+        Ok(VerificationResult {
+            signer,
+            integrated_time,
+            timestamps,
+            statement,
+        })
+    }
+
+    /// Verify the RFC 3161 timestamps of the signature of `bundle`. Returns
+    /// their times.
+    fn verify_timestamps(&self, bundle: &Bundle) -> Result<Vec<i64>> {
+        let timestamps = match &bundle.verification_material.timestamp_verification_data {
+            Some(data) => &data.rfc3161_timestamps,
+            None => return Ok(Vec::new()),
+        };
+        if timestamps.is_empty() {
+            return Ok(Vec::new());
+        }
+        // the timestamps are about the raw signature
+        let signature =
+            match &bundle.content {
+                BundleContent::MessageSignature(signature) => &signature.signature,
+                BundleContent::DsseEnvelope(envelope) => match envelope.signatures.as_slice() {
+                    [signature] => &signature.sig,
+                    _ => return Err(bundle_error(
+                        "cannot verify the timestamps of an envelope without exactly one signature",
+                    )),
+                },
+            };
+        let signature = BASE64_STD_ENGINE.decode(signature)?;
+
+        timestamps
+            .iter()
+            .map(|timestamp| {
+                let response = BASE64_STD_ENGINE.decode(&timestamp.signed_timestamp)?;
+                SignedTimestamp::from_response(&response)?.verify(&signature, &self.trusted_root)
+            })
+            .collect()
+    }
+
+    /// Verify the promise, and the inclusion proof, of `entry`. Returns the
+    /// entry as a cosign bundle, whose SET has been verified.
+    fn verify_tlog_entry(
+        &self,
+        entry: &TransparencyLogEntry,
+        version: BundleVersion,
+    ) -> Result<RekorBundle> {
+        if entry.canonicalized_body.is_empty() {
+            return Err(bundle_error("the tlog entry doesn't have a body"));
+        }
+        let promise = entry
+            .inclusion_promise
+            .as_ref()
+            .ok_or_else(|| bundle_error("the tlog entry doesn't have an inclusion promise"))?;
+        let log_id = hex::encode(BASE64_STD_ENGINE.decode(&entry.log_id.key_id)?);
+
+        let rekor_bundle = RekorBundle {
+            signed_entry_timestamp: promise.signed_entry_timestamp.clone(),
+            payload: Payload {
+                body: entry.canonicalized_body.clone(),
+                integrated_time: entry.integrated_time,
+                log_index: entry.log_index,
+                log_id,
+            },
+        };
+        RekorBundle::verify_bundle_at(&rekor_bundle, &self.trusted_root)?;
+
+        match &entry.inclusion_proof {
+            Some(_) => self.verify_inclusion_proof(entry, &rekor_bundle.payload.log_id)?,
+            None if version >= BundleVersion::V0_2 => {
+                return Err(bundle_error(
+                    "the tlog entry doesn't have an inclusion proof",
+                ))
+            }
+            None => {}
+        }
+        Ok(rekor_bundle)
+    }
+
+    /// Verify the inclusion proof of `entry` against its checkpoint, which
+    /// must have been signed by the log with ID `log_id`
+    fn verify_inclusion_proof(&self, entry: &TransparencyLogEntry, log_id: &str) -> Result<()> {
+        let err = |msg: &str| SigstoreError::RekorInclusionProofError(msg.to_string());
+        let proof = entry
+            .inclusion_proof
+            .as_ref()
+            .ok_or_else(|| err("the entry doesn't have an inclusion proof"))?;
+
+        let checkpoint = Checkpoint::parse(&proof.checkpoint.envelope)?;
+        let signed = self
+            .trusted_root
+            .rekor_pub_keys_for_log_at(log_id, entry.integrated_time)
+            .into_iter()
+            .any(|key| checkpoint.verify_signature(key).is_ok());
+        if !signed {
+            return Err(err("the checkpoint has not been signed by the log"));
+        }
+
+        let root = BASE64_STD_ENGINE.decode(&proof.root_hash)?;
+        let tree_size = u64::try_from(proof.tree_size).map_err(|_| err("invalid tree size"))?;
+        if checkpoint.size != tree_size || checkpoint.hash != root {
+            return Err(err("the proof is not about the tree of the checkpoint"));
+        }
+        let index = u64::try_from(proof.log_index).map_err(|_| err("invalid log index"))?;
+        let hashes = proof
+            .hashes
+            .iter()
+            .map(|h| BASE64_STD_ENGINE.decode(h))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let body = BASE64_STD_ENGINE.decode(&entry.canonicalized_body)?;
+
+        verify_inclusion(index, tree_size, &hash_leaf(&body), &root, &hashes)
+    }
+
+    /// Verify the certificate of the signer with the Fulcio certificates in
+    /// use at `signing_time`, a verified time of the signature
+    fn verify_certificate(&self, der: &[u8], signing_time: i64) -> Result<CertificateSignature> {
+        let cert_pool = self
+            .trusted_root
+            .fulcio_cert_pool_at(signing_time)?
+            .ok_or_else(|| {
+                SigstoreError::TrustedRootError(format!(
+                    "no Fulcio certificate in use at {signing_time}"
+                ))
+            })?;
+        let pem = pem::encode(&pem::Pem {
+            tag: "CERTIFICATE".to_string(),
+            contents: der.to_vec(),
+        });
+        CertificateSignature::from_certificate(
+            pem.as_bytes(),
+            &cert_pool,
+            signing_time,
+            Some(&self.trusted_root),
+            self.sct_policy,
+        )
+    }
+}
+
+/// Ensure the identity of the certificate is accepted by `verifier`
+fn check_identity(
+    verifier: &CertificateIdentityVerifier,
+    certificate_signature: &CertificateSignature,
+) -> Result<()> {
+    if verifier.matches(certificate_signature) {
+        Ok(())
+    } else {
+        Err(policy_error(&format!(
+            "the signer {:?}, authenticated by {:?}, is not accepted",
+            certificate_signature.subject, certificate_signature.issuer
+        )))
+    }
+}
+
+fn verify_message_signature(
+    signature: &MessageSignature,
+    rekor_bundle: Option<&RekorBundle>,
+    key: &CosignVerificationKey,
+    artifact: &[u8],
+) -> Result<()> {
+    if let Some(message_digest) = &signature.message_digest {
+        let digest = BASE64_STD_ENGINE.encode(Sha256::digest(artifact));
+        if message_digest.algorithm != "SHA2_256" || message_digest.digest != digest {
+            return Err(bundle_error(
+                "the digest of the bundle is not the one of the artifact",
+            ));
+        }
+    }
+    if let Some(rekor_bundle) = rekor_bundle {
+        rekor_bundle.verify_entry(&signature.signature, artifact)?;
+    }
+    key.verify_signature(
+        Signature::Base64Encoded(signature.signature.as_bytes()),
+        artifact,
+    )
+}
+
+fn verify_envelope(
+    envelope: &Envelope,
+    rekor_bundle: Option<&RekorBundle>,
+    key: &CosignVerificationKey,
+    artifact: &[u8],
+) -> Result<Statement> {
+    if let Some(rekor_bundle) = rekor_bundle {
+        // `intoto` and `dsse` entries record the digest of the payload at
+        // different locations
+        let body: Value =
+            serde_json::from_slice(&BASE64_STD_ENGINE.decode(&rekor_bundle.payload.body)?)?;
+        let payload_hash = Value::from(hex::encode(Sha256::digest(envelope.decoded_payload()?)));
+        if body.pointer("/spec/content/payloadHash/value") != Some(&payload_hash)
+            && body.pointer("/spec/payloadHash/value") != Some(&payload_hash)
+        {
+            return Err(SigstoreError::RekorEntryMismatchError(
+                "the entry doesn't refer to the payload of the envelope".to_string(),
+            ));
+        }
+    }
+
+    EnvelopeVerifier::new()
+        .with_key(None, key.clone())
+        .verify(envelope)?;
+    let statement = envelope.statement()?;
+    check_artifact(&statement, artifact)?;
+    Ok(statement)
+}
+
+fn bundle_error(msg: &str) -> SigstoreError {
+    SigstoreError::SigstoreBundleError(msg.to_string())
+}
+
+fn policy_error(msg: &str) -> SigstoreError {
+    SigstoreError::VerificationConstraintError(msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::{
+        Checkpoint as CheckpointDocument, HashOutput, InclusionPromise, InclusionProof,
+        KindVersion, LogId, PublicKeyIdentifier, Rfc3161SignedTimestamp, TimestampVerificationData,
+        VerificationMaterial, VerificationMaterialContent, BUNDLE_V01_MEDIA_TYPE,
+        BUNDLE_V03_MEDIA_TYPE,
+    };
+    use crate::crypto::timestamp::tests::Tsa;
+    use crate::crypto::trusted_root::ValidityPeriod;
+    use crate::crypto::{SigStoreSigner, SigningScheme};
+    use crate::rekor::checkpoint::tests::sign_checkpoint;
+    use chrono::Utc;
+    use olpc_cjson::CanonicalFormatter;
+    use serde::Serialize;
+    use serde_json::json;
+
+    const ARTIFACT: &[u8] = b"hello, bundle";
+
+    struct Fixture {
+        rekor: SigStoreSigner,
+        signer: SigStoreSigner,
+        tsa: Tsa,
+        verifier: Verifier,
+    }
+
+    fn fixture() -> Fixture {
+        let rekor = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let tsa = Tsa::new();
+        let mut trusted_root = tsa.trusted_root();
+        trusted_root
+            .add_rekor_pub_key(
+                &rekor.public_key_to_pem().unwrap(),
+                ValidityPeriod::always(),
+            )
+            .unwrap();
+        Fixture {
+            rekor,
+            signer: SigningScheme::default().create_signer().unwrap(),
+            tsa,
+            verifier: Verifier::new(trusted_root),
+        }
+    }
+
+    /// The tlog entry recording `body`, the only entry of the log
+    fn tlog_entry(rekor: &SigStoreSigner, body: &Value) -> TransparencyLogEntry {
+        let body = body.to_string();
+        let key_id = Sha256::digest(rekor.public_key_to_der().unwrap());
+        let payload = Payload {
+            body: BASE64_STD_ENGINE.encode(&body),
+            integrated_time: 1_700_000_000,
+            log_index: 0,
+            log_id: hex::encode(key_id),
+        };
+        let mut canonical = Vec::new();
+        let mut ser =
+            serde_json::Serializer::with_formatter(&mut canonical, CanonicalFormatter::new());
+        payload.serialize(&mut ser).unwrap();
+
+        let root = hash_leaf(body.as_bytes());
+        TransparencyLogEntry {
+            log_index: 0,
+            log_id: LogId {
+                key_id: BASE64_STD_ENGINE.encode(key_id),
+            },
+            kind_version: KindVersion {
+                kind: body_kind(&body),
+                version: "0.0.1".to_string(),
+            },
+            integrated_time: payload.integrated_time,
+            inclusion_promise: Some(InclusionPromise {
+                signed_entry_timestamp: BASE64_STD_ENGINE.encode(rekor.sign(&canonical).unwrap()),
+            }),
+            inclusion_proof: Some(InclusionProof {
+                log_index: 0,
+                root_hash: BASE64_STD_ENGINE.encode(&root),
+                tree_size: 1,
+                hashes: Vec::new(),
+                checkpoint: CheckpointDocument {
+                    envelope: sign_checkpoint(rekor, 1, &root),
+                },
+            }),
+            canonicalized_body: payload.body,
+        }
+    }
+
+    fn body_kind(body: &str) -> String {
+        let body: Value = serde_json::from_str(body).unwrap();
+        body["kind"].as_str().unwrap().to_string()
+    }
+
+    fn message_signature_bundle(f: &Fixture) -> Bundle {
+        let signature = BASE64_STD_ENGINE.encode(f.signer.sign(ARTIFACT).unwrap());
+        let body = json!({
+            "apiVersion": "0.0.1",
+            "kind": "hashedrekord",
+            "spec": {
+                "data": {"hash": {"algorithm": "sha256", "value": hex::encode(Sha256::digest(ARTIFACT))}},
+                "signature": {"content": signature, "publicKey": {"content": "not relevant"}}
+            }
+        });
+        Bundle {
+            media_type: BUNDLE_V03_MEDIA_TYPE.to_string(),
+            verification_material: VerificationMaterial {
+                content: VerificationMaterialContent::PublicKey(PublicKeyIdentifier {
+                    hint: String::new(),
+                }),
+                tlog_entries: vec![tlog_entry(&f.rekor, &body)],
+                timestamp_verification_data: None,
+            },
+            content: BundleContent::MessageSignature(MessageSignature {
+                message_digest: Some(HashOutput {
+                    algorithm: "SHA2_256".to_string(),
+                    digest: BASE64_STD_ENGINE.encode(Sha256::digest(ARTIFACT)),
+                }),
+                signature,
+            }),
+        }
+    }
+
+    #[test]
+    fn verify_message_signature_bundle() {
+        let f = fixture();
+        let bundle = message_signature_bundle(&f);
+        let policy = VerificationPolicy::public_key(f.signer.to_verification_key().unwrap());
+
+        let result = f.verifier.verify(&bundle, ARTIFACT, &policy).unwrap();
+        assert_eq!(result.integrated_time, Some(1_700_000_000));
+        assert!(result.timestamps.is_empty());
+        assert!(result.signer.is_none());
+        assert!(result.statement.is_none());
+
+        assert!(f
+            .verifier
+            .verify(&bundle, b"another artifact", &policy)
+            .is_err());
+        let other_key = SigningScheme::default()
+            .create_signer()
+            .unwrap()
+            .to_verification_key()
+            .unwrap();
+        assert!(f
+            .verifier
+            .verify(
+                &bundle,
+                ARTIFACT,
+                &VerificationPolicy::public_key(other_key)
+            )
+            .is_err());
+        let keyless = VerificationPolicy::identity(
+            IdentityMatcher::exact("alice@example.com"),
+            IdentityMatcher::exact("https://github.com/login/oauth"),
+        );
+        assert!(matches!(
+            f.verifier.verify(&bundle, ARTIFACT, &keyless),
+            Err(SigstoreError::VerificationConstraintError(_))
+        ));
+
+        // the trusted root doesn't know about this log
+        let untrusted = Verifier::new(TrustedRoot::new());
+        assert!(untrusted.verify(&bundle, ARTIFACT, &policy).is_err());
+    }
+
+    #[test]
+    fn verify_bundle_timestamped_by_tsa() {
+        let f = fixture();
+        let policy = VerificationPolicy::public_key(f.signer.to_verification_key().unwrap());
+        let mut bundle = message_signature_bundle(&f);
+        let signature = match &bundle.content {
+            BundleContent::MessageSignature(s) => BASE64_STD_ENGINE.decode(&s.signature).unwrap(),
+            BundleContent::DsseEnvelope(_) => unreachable!(),
+        };
+        let now = Utc::now().timestamp();
+        let timestamp = |message: &[u8]| {
+            Some(TimestampVerificationData {
+                rfc3161_timestamps: vec![Rfc3161SignedTimestamp {
+                    signed_timestamp: BASE64_STD_ENGINE.encode(f.tsa.timestamp(message, None, now)),
+                }],
+            })
+        };
+
+        // the timestamp replaces the tlog entry
+        bundle.verification_material.tlog_entries.clear();
+        bundle.verification_material.timestamp_verification_data = timestamp(&signature);
+        let result = f.verifier.verify(&bundle, ARTIFACT, &policy).unwrap();
+        assert_eq!(result.integrated_time, None);
+        assert_eq!(result.timestamps, vec![now]);
+
+        bundle.verification_material.timestamp_verification_data = timestamp(b"another signature");
+        assert!(matches!(
+            f.verifier.verify(&bundle, ARTIFACT, &policy),
+            Err(SigstoreError::TimestampError(_))
+        ));
+
+        bundle.verification_material.timestamp_verification_data = None;
+        assert!(matches!(
+            f.verifier.verify(&bundle, ARTIFACT, &policy),
+            Err(SigstoreError::SigstoreBundleError(_))
+        ));
+    }
+
+    #[test]
+    fn inclusion_proof_is_required_since_v02() {
+        let f = fixture();
+        let policy = VerificationPolicy::public_key(f.signer.to_verification_key().unwrap());
+        let mut bundle = message_signature_bundle(&f);
+        bundle.verification_material.tlog_entries[0].inclusion_proof = None;
+        assert!(matches!(
+            f.verifier.verify(&bundle, ARTIFACT, &policy),
+            Err(SigstoreError::SigstoreBundleError(_))
+        ));
+
+        bundle.media_type = BUNDLE_V01_MEDIA_TYPE.to_string();
+        assert!(f.verifier.verify(&bundle, ARTIFACT, &policy).is_ok());
+
+        // a proof about another tree is rejected
+        let mut bundle = message_signature_bundle(&f);
+        let proof = bundle.verification_material.tlog_entries[0]
+            .inclusion_proof
+            .as_mut()
+            .unwrap();
+        proof.checkpoint.envelope = sign_checkpoint(&f.rekor, 1, &[0; 32]);
+        assert!(matches!(
+            f.verifier.verify(&bundle, ARTIFACT, &policy),
+            Err(SigstoreError::RekorInclusionProofError(_))
+        ));
+    }
+
+    #[test]
+    fn verify_dsse_bundle() {
+        let f = fixture();
+        let statement = json!({
+            "_type": "https://in-toto.io/Statement/v1",
+            "subject": [{"name": "artifact", "digest": {"sha256": hex::encode(Sha256::digest(ARTIFACT))}}],
+            "predicateType": "https://example.com/test",
+            "predicate": {}
+        });
+        let mut envelope = Envelope::new(
+            crate::cosign::attestation::IN_TOTO_PAYLOAD_TYPE,
+            statement.to_string().as_bytes(),
+        );
+        envelope.sign(&f.signer, None).unwrap();
+        let body = json!({
+            "apiVersion": "0.0.1",
+            "kind": "dsse",
+            "spec": {
+                "payloadHash": {"algorithm": "sha256", "value": hex::encode(Sha256::digest(statement.to_string()))},
+            }
+        });
+        let mut bundle = message_signature_bundle(&f);
+        bundle.verification_material.tlog_entries = vec![tlog_entry(&f.rekor, &body)];
+        bundle.content = BundleContent::DsseEnvelope(envelope);
+        let policy = VerificationPolicy::public_key(f.signer.to_verification_key().unwrap());
+
+        let result = f.verifier.verify(&bundle, ARTIFACT, &policy).unwrap();
+        assert_eq!(
+            result.statement.unwrap().predicate_type,
+            "https://example.com/test"
+        );
+        assert!(f
+            .verifier
+            .verify(&bundle, b"another artifact", &policy)
+            .is_err());
+    }
+}
@@ -0,0 +1,257 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Storage backends for the data cached by this crate.
+//!
+//! The [`CacheStorage`] trait abstracts where cached data is kept, so that
+//! services running several replicas can share a cache, for example by
+//! implementing the trait on top of Redis. Two implementations are
+//! provided:
+//!
+//! * [`InMemoryCache`]: a cache private to the process
+//! * [`FilesystemCache`]: a cache stored inside of a local directory
+//!
+//! The storage is used by:
+//!
+//! * [`ClientBuilder::with_cache_storage`](crate::cosign::ClientBuilder::with_cache_storage),
+//!   to cache the responses of the OCI registries
+//! * [`SigstoreRepository::fetch_with_cache`](crate::tuf::SigstoreRepository::fetch_with_cache),
+//!   to cache the TUF targets
+//! * [`VerificationCache`](crate::cosign::verification_cache::VerificationCache),
+//!   to cache the successful verifications of images
+//!
+//! ```rust,no_run
+//! use sigstore::cache::InMemoryCache;
+//! use sigstore::cosign::ClientBuilder;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! let cache = Arc::new(InMemoryCache::default());
+//! let client = ClientBuilder::default()
+//!     .with_cache_storage(cache, Duration::from_secs(60))
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::errors::Result;
+
+/// A key/value store holding cached data.
+///
+/// Implementations must be safe to share between threads. The keys are
+/// arbitrary strings, the implementations are free to encode them.
+pub trait CacheStorage: Send + Sync {
+    /// The value stored for `key`, `None` when missing or expired
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Store `value` for `key`. The value expires after `ttl`, it's kept
+    /// until replaced or removed when `ttl` is `None`.
+    fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> Result<()>;
+
+    /// Remove the value stored for `key`, if any
+    fn remove(&self, key: &str) -> Result<()>;
+}
+
+/// The values of an [`InMemoryCache`], with the instant they expire at
+type Entries = HashMap<String, (Vec<u8>, Option<Instant>)>;
+
+/// A cache kept in memory, private to the process
+#[derive(Default)]
+pub struct InMemoryCache {
+    entries: Mutex<Entries>,
+}
+
+impl InMemoryCache {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        // a panic while holding the lock can't leave the map inconsistent
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl CacheStorage for InMemoryCache {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let mut entries = self.lock();
+        match entries.get(key) {
+            Some((_, Some(expires_at))) if *expires_at <= Instant::now() => {
+                entries.remove(key);
+                Ok(None)
+            }
+            Some((value, _)) => Ok(Some(value.clone())),
+            None => Ok(None),
+        }
+    }
+
+    fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> Result<()> {
+        let expires_at = ttl.map(|ttl| Instant::now() + ttl);
+        self.lock()
+            .insert(key.to_string(), (value.to_vec(), expires_at));
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        self.lock().remove(key);
+        Ok(())
+    }
+}
+
+/// A cache stored inside of a local directory.
+///
+/// Each value is stored inside of a file named after its key, the expiration
+/// times are kept inside of the `.expires` sub-directory. Keys that can't be
+/// used as file names are hashed.
+pub struct FilesystemCache {
+    dir: PathBuf,
+}
+
+impl FilesystemCache {
+    /// Use `dir` to store the cache, the directory is created when needed
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FilesystemCache { dir: dir.into() }
+    }
+
+    fn file_name(key: &str) -> String {
+        let valid = !key.is_empty()
+            && !key.starts_with('.')
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+        if valid {
+            key.to_string()
+        } else {
+            hex::encode(Sha256::digest(key.as_bytes()))
+        }
+    }
+
+    fn expires_path(&self, file_name: &str) -> PathBuf {
+        self.dir.join(".expires").join(file_name)
+    }
+}
+
+impl CacheStorage for FilesystemCache {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let file_name = Self::file_name(key);
+        let path = self.dir.join(&file_name);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        if let Ok(expires_at) = fs::read_to_string(self.expires_path(&file_name)) {
+            let expired = expires_at
+                .trim()
+                .parse::<u64>()
+                .map_or(true, |expires_at| unix_now() >= expires_at);
+            if expired {
+                self.remove(key)?;
+                return Ok(None);
+            }
+        }
+        Ok(Some(fs::read(path)?))
+    }
+
+    fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> Result<()> {
+        let file_name = Self::file_name(key);
+        let expires_path = self.expires_path(&file_name);
+        fs::create_dir_all(&self.dir)?;
+        match ttl {
+            Some(ttl) => {
+                fs::create_dir_all(self.dir.join(".expires"))?;
+                fs::write(&expires_path, (unix_now() + ttl.as_secs()).to_string())?;
+            }
+            None if expires_path.exists() => fs::remove_file(&expires_path)?,
+            None => {}
+        }
+        fs::write(self.dir.join(file_name), value)?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        let file_name = Self::file_name(key);
+        for path in [self.dir.join(&file_name), self.expires_path(&file_name)] {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exercise(cache: &dyn CacheStorage) {
+        assert_eq!(cache.get("rekor.pub").unwrap(), None);
+
+        cache.set("rekor.pub", b"key", None).unwrap();
+        assert_eq!(cache.get("rekor.pub").unwrap(), Some(b"key".to_vec()));
+
+        cache
+            .set(
+                "registry/digest",
+                b"sha256:abcd",
+                Some(Duration::from_secs(60)),
+            )
+            .unwrap();
+        assert_eq!(
+            cache.get("registry/digest").unwrap(),
+            Some(b"sha256:abcd".to_vec())
+        );
+
+        cache
+            .set("expired", b"value", Some(Duration::from_secs(0)))
+            .unwrap();
+        assert_eq!(cache.get("expired").unwrap(), None);
+
+        cache.remove("rekor.pub").unwrap();
+        assert_eq!(cache.get("rekor.pub").unwrap(), None);
+        cache.remove("rekor.pub").unwrap();
+    }
+
+    #[test]
+    fn in_memory_cache() {
+        exercise(&InMemoryCache::default());
+    }
+
+    #[test]
+    fn filesystem_cache() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = FilesystemCache::new(dir.path().join("cache"));
+        exercise(&cache);
+
+        // plain keys are stored as they are, others are hashed
+        cache.set("fulcio.crt.pem", b"cert", None).unwrap();
+        assert!(dir.path().join("cache").join("fulcio.crt.pem").exists());
+        cache.set("../escape", b"value", None).unwrap();
+        assert!(!dir.path().join("escape").exists());
+        assert_eq!(cache.get("../escape").unwrap(), Some(b"value".to_vec()));
+    }
+}
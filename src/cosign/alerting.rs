@@ -0,0 +1,66 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hooks invoked when an image starts failing verification.
+//!
+//! Monitoring systems can implement the [`FailureHandler`] trait and register
+//! it with the [`Watcher`](crate::cosign::watcher::Watcher). The handler is
+//! invoked only when an image becomes invalid, hence there's no need to poll
+//! the results of the verification to page on newly failing images.
+
+use async_trait::async_trait;
+use std::fmt;
+
+use super::watcher::VerificationState;
+use crate::errors::Result;
+use crate::registry::OciReference;
+
+/// Details about an image that just failed verification
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationFailure {
+    /// The image that failed verification
+    pub image: OciReference,
+    /// The name of the policy the image has been verified against
+    pub policy: String,
+    /// Why verification failed. Contains either the error raised while
+    /// fetching the signatures, or the list of unsatisfied constraints.
+    pub reasons: Vec<String>,
+    /// The state of the image before the failure, `None` when the image
+    /// has been verified for the first time
+    pub previous: Option<VerificationState>,
+}
+
+impl fmt::Display for VerificationFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} failed verification against policy {}: {}",
+            self.image,
+            self.policy,
+            self.reasons.join("; ")
+        )
+    }
+}
+
+/// A trait that can be implemented to be notified about verification failures
+#[async_trait(?Send)]
+pub trait FailureHandler {
+    /// Invoked each time an image starts failing verification.
+    ///
+    /// Errors returned by the handler are logged, they do not interrupt the
+    /// verification of the other images nor the invocation of the other
+    /// handlers.
+    async fn on_failure(&self, failure: &VerificationFailure) -> Result<()>;
+}
@@ -0,0 +1,522 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Long-term archival of the evidence produced when signing a blob.
+//!
+//! An [`EvidenceArchive`] packages everything that is needed to verify a
+//! blob signed with `cosign sign-blob --bundle` years after the signature
+//! has been produced:
+//!
+//! * the signature, the signing certificate and the Rekor bundle
+//! * the Rekor checkpoint observed when the archive has been created, with
+//!   the inclusion proof of the entry in the tree it describes
+//! * a later checkpoint and the consistency proof between the two
+//! * a snapshot of the trust root (Fulcio certificates and Rekor public key)
+//!
+//! Verification doesn't require any network access, and doesn't depend on
+//! the trust root that is current at verification time.
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::archive::EvidenceArchive;
+//!
+//! let blob = std::fs::read("artifact.txt").expect("Cannot read blob");
+//! let archive_json = std::fs::read_to_string("artifact.evidence.json")
+//!     .expect("Cannot read archive");
+//!
+//! let archive = EvidenceArchive::from_json(&archive_json).expect("Invalid archive");
+//! match archive.verify(&blob) {
+//!     Ok(()) => println!("blob verified"),
+//!     Err(e) => eprintln!("verification failed: {e}"),
+//! }
+//! ```
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use chrono::{TimeZone, Utc};
+use pkcs8::der::Decode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+use x509_cert::Certificate;
+
+use super::bundle::{Bundle, SignedArtifactBundle};
+use crate::crypto::certificate_pool::CertificatePool;
+use crate::crypto::trusted_root::FreshnessPolicy;
+use crate::crypto::{self, CosignVerificationKey, Signature};
+use crate::errors::{Result, SigstoreError};
+use crate::rekor::auditor::verify_checkpoint_transition;
+use crate::rekor::checkpoint::Checkpoint;
+use crate::rekor::merkle::verify_log_entry_inclusion_at;
+use crate::rekor::models::log_entry::{InclusionProof, LogEntry, Verification};
+use crate::rekor::models::ConsistencyProof;
+
+/// The version of the archive format produced by this crate
+pub const EVIDENCE_ARCHIVE_VERSION: &str = "v1";
+
+/// A snapshot of the trust root used to verify the evidence
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustRootSnapshot {
+    /// The PEM encoded public key of Rekor
+    pub rekor_pub_key: String,
+    /// The PEM encoded Fulcio certificates, both roots and intermediates
+    pub fulcio_certs: Vec<String>,
+    /// When the snapshot has been taken, as a UNIX timestamp
+    pub captured_at: i64,
+}
+
+impl TrustRootSnapshot {
+    /// Create a snapshot of the given trust material
+    pub fn new(rekor_pub_key: &str, fulcio_certs: &[crate::registry::Certificate]) -> Result<Self> {
+        let fulcio_certs = fulcio_certs
+            .iter()
+            .map(|c| match c.encoding {
+                crate::registry::CertificateEncoding::Pem => String::from_utf8(c.data.clone())
+                    .map_err(|e| SigstoreError::from(e.utf8_error())),
+                crate::registry::CertificateEncoding::Der => Ok(pem::encode(&pem::Pem {
+                    tag: "CERTIFICATE".to_string(),
+                    contents: c.data.clone(),
+                })),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(TrustRootSnapshot {
+            rekor_pub_key: rekor_pub_key.to_string(),
+            fulcio_certs,
+            captured_at: Utc::now().timestamp(),
+        })
+    }
+
+    /// Create a snapshot of the trust material currently provided by the
+    /// Sigstore TUF repository
+    #[cfg(feature = "tuf")]
+    pub fn from_sigstore_repository(repo: &crate::tuf::SigstoreRepository) -> Result<Self> {
+        let mut snapshot = Self::new(repo.rekor_pub_key(), repo.fulcio_certs())?;
+        snapshot.captured_at = repo.fetched_at().timestamp();
+        Ok(snapshot)
+    }
+
+    /// Ensure the snapshot is not older than allowed by `policy`
+    pub fn check_freshness(&self, policy: &FreshnessPolicy) -> Result<()> {
+        let captured_at = Utc
+            .timestamp_opt(self.captured_at, 0)
+            .single()
+            .ok_or_else(|| {
+                SigstoreError::EvidenceArchiveError(format!(
+                    "invalid snapshot timestamp {}",
+                    self.captured_at
+                ))
+            })?;
+        policy.check(captured_at)
+    }
+
+    fn fulcio_cert_pool(&self) -> Result<CertificatePool> {
+        let certs: Vec<crate::registry::Certificate> = self
+            .fulcio_certs
+            .iter()
+            .map(|pem| crate::registry::Certificate {
+                encoding: crate::registry::CertificateEncoding::Pem,
+                data: pem.as_bytes().to_vec(),
+            })
+            .collect();
+        CertificatePool::from_certificates(&certs)
+    }
+}
+
+/// All the evidence needed to verify a signed blob, packaged for long-term
+/// storage
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct EvidenceArchive {
+    /// The version of the archive format
+    pub version: String,
+    /// The digest of the signed blob, in the `sha256:<hex value>` format
+    pub artifact_digest: String,
+    /// The base64 encoded signature of the blob
+    pub base64_signature: String,
+    /// The PEM encoded signing certificate
+    pub cert: String,
+    /// The Rekor bundle proving the signature has been recorded
+    pub rekor_bundle: Bundle,
+    /// The Rekor checkpoint observed when the archive has been created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<String>,
+    /// The proof that the Rekor entry is part of the tree described by
+    /// [`EvidenceArchive::checkpoint`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inclusion_proof: Option<InclusionProof>,
+    /// A checkpoint produced by Rekor after [`EvidenceArchive::checkpoint`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub later_checkpoint: Option<String>,
+    /// The consistency proof between the two checkpoints
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consistency_proof: Option<ConsistencyProof>,
+    /// The trust root used to verify the evidence
+    pub trust_root: TrustRootSnapshot,
+}
+
+impl EvidenceArchive {
+    /// Create a new archive for the blob signed with `cosign sign-blob --bundle`.
+    ///
+    /// The archive is verified before being returned.
+    pub fn new(
+        signed_bundle: &SignedArtifactBundle,
+        blob: &[u8],
+        trust_root: TrustRootSnapshot,
+    ) -> Result<Self> {
+        let cert = String::from_utf8(BASE64_STD_ENGINE.decode(&signed_bundle.cert)?)
+            .map_err(|e| SigstoreError::from(e.utf8_error()))?;
+
+        let archive = EvidenceArchive {
+            version: EVIDENCE_ARCHIVE_VERSION.to_string(),
+            artifact_digest: format!("sha256:{:x}", Sha256::digest(blob)),
+            base64_signature: signed_bundle.base64_signature.clone(),
+            cert,
+            rekor_bundle: signed_bundle.rekor_bundle.clone(),
+            checkpoint: None,
+            inclusion_proof: None,
+            later_checkpoint: None,
+            consistency_proof: None,
+            trust_root,
+        };
+        archive.verify(blob)?;
+        Ok(archive)
+    }
+
+    /// Attach the Rekor checkpoints to the archive.
+    ///
+    /// * `checkpoint`: the signed note observed when the signature has been
+    ///   recorded
+    /// * `inclusion_proof`: the proof that the Rekor entry is part of the
+    ///   tree described by `checkpoint`
+    /// * `later_checkpoint`: a signed note produced later by Rekor
+    /// * `consistency_proof`: the proof that `later_checkpoint` is an
+    ///   append-only extension of `checkpoint`
+    pub fn with_checkpoints(
+        mut self,
+        checkpoint: &str,
+        inclusion_proof: InclusionProof,
+        later_checkpoint: &str,
+        consistency_proof: ConsistencyProof,
+    ) -> Self {
+        self.checkpoint = Some(checkpoint.to_string());
+        self.inclusion_proof = Some(inclusion_proof);
+        self.later_checkpoint = Some(later_checkpoint.to_string());
+        self.consistency_proof = Some(consistency_proof);
+        self
+    }
+
+    /// Load an archive from its JSON representation
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let archive: EvidenceArchive = serde_json::from_str(raw)?;
+        if archive.version != EVIDENCE_ARCHIVE_VERSION {
+            return Err(SigstoreError::EvidenceArchiveError(format!(
+                "unsupported archive version {}",
+                archive.version
+            )));
+        }
+        Ok(archive)
+    }
+
+    /// Serialize the archive to JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Verify the given blob using only the evidence stored inside of the
+    /// archive.
+    ///
+    /// The following checks are performed:
+    /// * the blob matches the archived digest
+    /// * the Rekor bundle has been signed by the archived Rekor key, and
+    ///   refers to the archived signature and digest
+    /// * the signing certificate has been issued by the archived Fulcio
+    ///   certificates, and was valid when the signature was recorded
+    /// * the signature is valid
+    /// * the checkpoints, when present, are signed by Rekor and consistent,
+    ///   and the Rekor entry is part of the tree of the first one
+    pub fn verify(&self, blob: &[u8]) -> Result<()> {
+        let digest = format!("sha256:{:x}", Sha256::digest(blob));
+        if digest != self.artifact_digest {
+            return Err(SigstoreError::EvidenceArchiveError(format!(
+                "blob digest {digest} doesn't match archived digest {}",
+                self.artifact_digest
+            )));
+        }
+
+        let rekor_pub_key =
+            CosignVerificationKey::try_from_pem(self.trust_root.rekor_pub_key.as_bytes())?;
+        Bundle::verify_bundle(&self.rekor_bundle, &rekor_pub_key)?;
+        self.verify_bundle_body()?;
+
+        let cert_pool = self.trust_root.fulcio_cert_pool()?;
+        cert_pool.verify_pem_cert(self.cert.as_bytes())?;
+        let pem = pem::parse(self.cert.as_bytes())?;
+        let cert = Certificate::from_der(&pem.contents)
+            .map_err(|e| SigstoreError::X509Error(format!("parse from der: {e}")))?;
+        crypto::certificate::is_trusted(&cert, self.rekor_bundle.payload.integrated_time)?;
+
+        let verification_key =
+            CosignVerificationKey::try_from(&cert.tbs_certificate.subject_public_key_info)?;
+        verification_key.verify_signature(
+            Signature::Base64Encoded(self.base64_signature.as_bytes()),
+            blob,
+        )?;
+
+        self.verify_checkpoints(&rekor_pub_key)
+    }
+
+    /// Ensure the Rekor entry refers to the archived signature and digest
+    fn verify_bundle_body(&self) -> Result<()> {
+        let body: Value =
+            serde_json::from_slice(&BASE64_STD_ENGINE.decode(&self.rekor_bundle.payload.body)?)?;
+
+        let (algorithm, hash) = self
+            .artifact_digest
+            .split_once(':')
+            .expect("digest has been validated");
+        let matches = body.pointer("/spec/data/hash/algorithm") == Some(&Value::from(algorithm))
+            && body.pointer("/spec/data/hash/value") == Some(&Value::from(hash))
+            && body.pointer("/spec/signature/content")
+                == Some(&Value::from(self.base64_signature.as_str()));
+
+        if matches {
+            Ok(())
+        } else {
+            Err(SigstoreError::EvidenceArchiveError(
+                "Rekor entry doesn't refer to the archived signature".to_string(),
+            ))
+        }
+    }
+
+    fn verify_checkpoints(&self, rekor_pub_key: &CosignVerificationKey) -> Result<()> {
+        match (&self.checkpoint, &self.later_checkpoint) {
+            (None, None) => Ok(()),
+            (Some(checkpoint), Some(later_checkpoint)) => {
+                let checkpoint = Checkpoint::parse_and_verify(checkpoint, rekor_pub_key)?;
+                self.verify_inclusion(&checkpoint)?;
+                let later_checkpoint =
+                    Checkpoint::parse_and_verify(later_checkpoint, rekor_pub_key)?;
+                verify_checkpoint_transition(
+                    Some(&checkpoint),
+                    &later_checkpoint,
+                    self.consistency_proof.as_ref(),
+                )
+            }
+            _ => Err(SigstoreError::EvidenceArchiveError(
+                "both checkpoints must be provided".to_string(),
+            )),
+        }
+    }
+
+    /// Ensure the Rekor entry is part of the tree described by `checkpoint`
+    fn verify_inclusion(&self, checkpoint: &Checkpoint) -> Result<()> {
+        let inclusion_proof = self.inclusion_proof.clone().ok_or_else(|| {
+            SigstoreError::EvidenceArchiveError(
+                "the inclusion proof of the Rekor entry is missing".to_string(),
+            )
+        })?;
+        let payload = &self.rekor_bundle.payload;
+        let raw_body = BASE64_STD_ENGINE.decode(&payload.body)?;
+        let entry = LogEntry {
+            body: serde_json::from_slice(&raw_body)?,
+            raw_body,
+            integrated_time: payload.integrated_time,
+            log_i_d: payload.log_id.clone(),
+            log_index: payload.log_index,
+            verification: Verification {
+                inclusion_proof: Some(inclusion_proof),
+                signed_entry_timestamp: self.rekor_bundle.signed_entry_timestamp.clone(),
+            },
+            ..Default::default()
+        };
+        verify_log_entry_inclusion_at(&entry, checkpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::bundle::Payload;
+    use crate::crypto::tests::{generate_certificate, CertGenerationOptions};
+    use crate::crypto::{SigStoreSigner, SigningScheme};
+    use crate::rekor::checkpoint::tests::sign_checkpoint;
+    use crate::rekor::merkle::tests::{consistency_proof, inclusion_proof, root};
+    use chrono::Duration;
+    use olpc_cjson::CanonicalFormatter;
+    use openssl::hash::MessageDigest;
+    use serde_json::json;
+
+    const BLOB: &[u8] = b"hello world";
+
+    fn sign_bundle(rekor: &SigStoreSigner, payload: Payload) -> Bundle {
+        let mut buf = Vec::new();
+        let mut ser = serde_json::Serializer::with_formatter(&mut buf, CanonicalFormatter::new());
+        payload.serialize(&mut ser).expect("cannot serialize");
+        Bundle {
+            signed_entry_timestamp: BASE64_STD_ENGINE
+                .encode(rekor.sign(&buf).expect("cannot sign")),
+            payload,
+        }
+    }
+
+    fn build_archive() -> anyhow::Result<(EvidenceArchive, SigStoreSigner)> {
+        let ca_data = generate_certificate(None, CertGenerationOptions::default())?;
+        let issued_cert = generate_certificate(Some(&ca_data), CertGenerationOptions::default())?;
+
+        let mut signer =
+            openssl::sign::Signer::new(MessageDigest::sha256(), &issued_cert.private_key)?;
+        signer.update(BLOB)?;
+        let signature = BASE64_STD_ENGINE.encode(signer.sign_to_vec()?);
+
+        let body = json!({
+            "apiVersion": "0.0.1",
+            "kind": "hashedrekord",
+            "spec": {
+                "data": { "hash": { "algorithm": "sha256", "value": format!("{:x}", Sha256::digest(BLOB)) } },
+                "signature": { "content": signature }
+            }
+        });
+
+        let mut canonical_body = Vec::new();
+        let mut ser =
+            serde_json::Serializer::with_formatter(&mut canonical_body, CanonicalFormatter::new());
+        body.serialize(&mut ser)?;
+
+        let rekor = SigningScheme::ECDSA_P256_SHA256_ASN1.create_signer()?;
+        let rekor_bundle = sign_bundle(
+            &rekor,
+            Payload {
+                body: BASE64_STD_ENGINE.encode(canonical_body),
+                integrated_time: (Utc::now() - Duration::minutes(1)).timestamp(),
+                log_index: 3,
+                log_id: "log".to_string(),
+            },
+        );
+
+        let signed_bundle = SignedArtifactBundle {
+            base64_signature: signature,
+            cert: BASE64_STD_ENGINE.encode(issued_cert.cert.to_pem()?),
+            rekor_bundle,
+        };
+        let trust_root = TrustRootSnapshot::new(
+            &rekor.to_sigstore_keypair()?.public_key_to_pem()?,
+            &[crate::registry::Certificate::try_from(ca_data.cert)?],
+        )?;
+
+        let archive = EvidenceArchive::new(&signed_bundle, BLOB, trust_root)?;
+        Ok((archive, rekor))
+    }
+
+    /// The leaves of a log of 8 entries, holding the entry of `archive` at
+    /// index 3
+    fn log_leaves(archive: &EvidenceArchive) -> anyhow::Result<Vec<Vec<u8>>> {
+        let mut leaves: Vec<Vec<u8>> = (0..8).map(|i| vec![i]).collect();
+        leaves[3] = BASE64_STD_ENGINE.decode(&archive.rekor_bundle.payload.body)?;
+        Ok(leaves)
+    }
+
+    /// The inclusion proof of the leaf at index 3 in the tree made of `leaves`
+    fn entry_inclusion_proof(leaves: &[Vec<u8>]) -> InclusionProof {
+        InclusionProof {
+            hashes: inclusion_proof(3, leaves).iter().map(hex::encode).collect(),
+            log_index: 3,
+            root_hash: hex::encode(root(leaves)),
+            tree_size: leaves.len() as i64,
+        }
+    }
+
+    #[test]
+    fn archive_roundtrip_and_verify() -> anyhow::Result<()> {
+        let (archive, rekor) = build_archive()?;
+
+        let leaves = log_leaves(&archive)?;
+        let archive = archive.with_checkpoints(
+            &sign_checkpoint(&rekor, 5, &root(&leaves[..5])),
+            entry_inclusion_proof(&leaves[..5]),
+            &sign_checkpoint(&rekor, 8, &root(&leaves)),
+            ConsistencyProof::new(
+                hex::encode(root(&leaves)),
+                consistency_proof(5, &leaves)
+                    .iter()
+                    .map(hex::encode)
+                    .collect(),
+            ),
+        );
+
+        let restored = EvidenceArchive::from_json(&archive.to_json()?)?;
+        assert_eq!(restored, archive);
+        assert!(restored.verify(BLOB).is_ok());
+        assert!(restored.verify(b"another blob").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn archive_detects_tampering() -> anyhow::Result<()> {
+        let (archive, rekor) = build_archive()?;
+
+        let mut tampered = archive.clone();
+        tampered.rekor_bundle.payload.log_index = 4;
+        assert!(tampered.verify(BLOB).is_err());
+
+        let mut tampered = archive.clone();
+        tampered.trust_root.fulcio_certs = vec![];
+        assert!(tampered.verify(BLOB).is_err());
+
+        let leaves = log_leaves(&archive)?;
+        let tampered = archive.clone().with_checkpoints(
+            &sign_checkpoint(&rekor, 8, &root(&leaves)),
+            entry_inclusion_proof(&leaves),
+            &sign_checkpoint(&rekor, 5, &root(&leaves[..5])),
+            ConsistencyProof::new(String::new(), vec![]),
+        );
+        assert!(matches!(
+            tampered.verify(BLOB),
+            Err(SigstoreError::RekorLogRollbackError { .. })
+        ));
+
+        let consistency = ConsistencyProof::new(
+            hex::encode(root(&leaves)),
+            consistency_proof(5, &leaves)
+                .iter()
+                .map(hex::encode)
+                .collect(),
+        );
+        let mut tampered = archive.clone().with_checkpoints(
+            &sign_checkpoint(&rekor, 5, &root(&leaves[..5])),
+            entry_inclusion_proof(&leaves),
+            &sign_checkpoint(&rekor, 8, &root(&leaves)),
+            consistency,
+        );
+        assert!(matches!(
+            tampered.verify(BLOB),
+            Err(SigstoreError::RekorInclusionProofError(_))
+        ));
+
+        let mut other_leaves = leaves.clone();
+        other_leaves[3] = b"another entry".to_vec();
+        tampered.inclusion_proof = Some(entry_inclusion_proof(&other_leaves[..5]));
+        assert!(tampered.verify(BLOB).is_err());
+
+        tampered.inclusion_proof = None;
+        assert!(matches!(
+            tampered.verify(BLOB),
+            Err(SigstoreError::EvidenceArchiveError(_))
+        ));
+
+        Ok(())
+    }
+}
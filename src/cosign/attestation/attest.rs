@@ -0,0 +1,383 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Creation of attestations, the equivalent of the `cosign attest --key`
+//! command.
+//!
+//! The predicate is wrapped inside of an in-toto statement about the image,
+//! signed as a DSSE envelope and pushed next to the attestations already
+//! attached to the image, under its `.att` tag. The envelope can be recorded
+//! inside of Rekor too, in which case its bundle is attached to the
+//! attestation.
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::attestation::attest::{attest, AttestOptions};
+//! use sigstore::cosign::attestation::predicate::SLSA_PROVENANCE_V1_PREDICATE_TYPE;
+//! use sigstore::cosign::ClientBuilder;
+//! use sigstore::crypto::SigningScheme;
+//! use sigstore::registry::{Auth, OciReference};
+//! use sigstore::rekor::apis::configuration::Configuration;
+//!
+//! # async fn doc(provenance: serde_json::Value) -> sigstore::errors::Result<()> {
+//! let mut client = ClientBuilder::default().build()?;
+//! let signer = SigningScheme::default().create_signer()?;
+//! let image: OciReference = "registry.example.com/app:v1".parse()?;
+//!
+//! let options = AttestOptions::default().with_rekor_config(Configuration::default());
+//! attest(
+//!     &mut client,
+//!     &Auth::Anonymous,
+//!     &image,
+//!     &signer,
+//!     SLSA_PROVENANCE_V1_PREDICATE_TYPE,
+//!     &provenance,
+//!     &options,
+//! )
+//! .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "rekor")]
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing::debug;
+
+use super::{Envelope, Statement, Subject, IN_TOTO_PAYLOAD_TYPE};
+use crate::cosign::bundle::Bundle;
+use crate::cosign::client::{image_manifest, Client};
+use crate::cosign::constants::{
+    SIGSTORE_BUNDLE_ANNOTATION, SIGSTORE_DSSE_MEDIA_TYPE, SIGSTORE_PREDICATE_TYPE_ANNOTATION,
+    SIGSTORE_SIGNATURE_ANNOTATION,
+};
+use crate::cosign::{AttachmentKind, CosignCapabilities};
+use crate::crypto::SigStoreSigner;
+use crate::errors::{Result, SigstoreError};
+use crate::registry::{Auth, OciReference, PushResponse};
+#[cfg(feature = "rekor")]
+use crate::rekor::apis::{configuration::Configuration, entries_api};
+#[cfg(feature = "rekor")]
+use crate::rekor::models::{intoto, ProposedEntry};
+
+/// Options of [`attest`]
+#[derive(Debug, Clone, Default)]
+pub struct AttestOptions {
+    #[cfg(feature = "rekor")]
+    rekor_config: Option<Configuration>,
+    replace: bool,
+}
+
+impl AttestOptions {
+    /// Record the envelope inside of the Rekor instance described by
+    /// `rekor_config`. By default, nothing is uploaded to Rekor.
+    #[cfg(feature = "rekor")]
+    pub fn with_rekor_config(mut self, rekor_config: Configuration) -> Self {
+        self.rekor_config = Some(rekor_config);
+        self
+    }
+
+    /// Remove the attestations of the image having the same predicate type,
+    /// like `cosign attest --replace`. By default, they are kept.
+    pub fn with_replace(mut self, replace: bool) -> Self {
+        self.replace = replace;
+        self
+    }
+}
+
+/// Sign `statement` with `signer`, returns the DSSE envelope holding it
+pub fn sign_statement(signer: &SigStoreSigner, statement: &Statement) -> Result<Envelope> {
+    let mut envelope = Envelope::new(IN_TOTO_PAYLOAD_TYPE, &serde_json::to_vec(statement)?);
+    envelope.sign(signer, None)?;
+    Ok(envelope)
+}
+
+/// Attest `predicate`, of type `predicate_type`, about `image`, and attach
+/// the attestation to the image.
+///
+/// The subject of the statement is the manifest the image resolves to. The
+/// attestation is pushed to the signature repository of `client` when one
+/// is configured, like done by
+/// [`CosignCapabilities::verify_attestations`].
+pub async fn attest<P: Serialize>(
+    client: &mut Client,
+    auth: &Auth,
+    image: &OciReference,
+    signer: &SigStoreSigner,
+    predicate_type: &str,
+    predicate: &P,
+    options: &AttestOptions,
+) -> Result<PushResponse> {
+    let (_, manifest_digest) = client.triangulate(image, auth).await?;
+    let subject = Subject::new(
+        &format!("{}/{}", image.registry(), image.repository()),
+        &manifest_digest,
+    )?;
+    let statement = Statement::new(vec![subject], predicate_type, predicate)?;
+    let envelope = sign_statement(signer, &statement)?;
+
+    #[cfg(feature = "rekor")]
+    let bundle = match &options.rekor_config {
+        Some(rekor_config) => Some(upload(rekor_config, signer, &envelope).await?),
+        None => None,
+    };
+    #[cfg(not(feature = "rekor"))]
+    let bundle: Option<Bundle> = None;
+    let layer = attestation_layer(&envelope, predicate_type, bundle.as_ref())?;
+
+    let location = client.signature_repository.clone();
+    let target =
+        AttachmentKind::Attestation.reference(location.as_ref().unwrap_or(image), &manifest_digest);
+    let existing = existing_layers(client, auth, &target).await?;
+    let layers = merge_layers(existing, layer, options.replace);
+
+    let response = client.push_layers(None, auth, &target, layers).await?;
+    debug!(%image, %target, predicate_type, "attestation pushed");
+    Ok(response)
+}
+
+/// Record `envelope` inside of Rekor, as an `intoto` entry whose public key
+/// is the one of `signer`
+#[cfg(feature = "rekor")]
+async fn upload(
+    rekor_config: &Configuration,
+    signer: &SigStoreSigner,
+    envelope: &Envelope,
+) -> Result<Bundle> {
+    let spec = intoto::Spec::new(
+        intoto::Content::from_envelope(serde_json::to_string(envelope)?),
+        BASE64_STD_ENGINE.encode(signer.public_key_to_pem()?),
+    );
+    let entry = entries_api::create_log_entry(rekor_config, ProposedEntry::from_spec(&spec)?)
+        .await
+        .map_err(|e| SigstoreError::RekorClientError(e.to_string()))?;
+    debug!(uuid = %entry.uuid, log_index = entry.log_index, "attestation uploaded to Rekor");
+    Bundle::from_log_entry(&entry)
+}
+
+/// The layer holding `envelope`, with the annotations written by cosign
+fn attestation_layer(
+    envelope: &Envelope,
+    predicate_type: &str,
+    bundle: Option<&Bundle>,
+) -> Result<oci_distribution::client::ImageLayer> {
+    // the signatures are inside of the envelope
+    let mut annotations: HashMap<String, String> = [
+        (SIGSTORE_SIGNATURE_ANNOTATION.to_string(), String::new()),
+        (
+            SIGSTORE_PREDICATE_TYPE_ANNOTATION.to_string(),
+            predicate_type.to_string(),
+        ),
+    ]
+    .into();
+    if let Some(bundle) = bundle {
+        annotations.insert(
+            SIGSTORE_BUNDLE_ANNOTATION.to_string(),
+            serde_json::to_string(bundle)?,
+        );
+    }
+    Ok(oci_distribution::client::ImageLayer::new(
+        serde_json::to_vec(envelope)?,
+        SIGSTORE_DSSE_MEDIA_TYPE.to_string(),
+        Some(annotations),
+    ))
+}
+
+/// The attestation layers already pushed to `target`, together with their
+/// annotations. There's none when the image has not been attested yet.
+async fn existing_layers(
+    client: &mut Client,
+    auth: &Auth,
+    target: &OciReference,
+) -> Result<Vec<oci_distribution::client::ImageLayer>> {
+    let (manifest, layers) = match client
+        .fetch_manifest_and_layers(auth, target, AttachmentKind::Attestation.media_types())
+        .await
+    {
+        Ok(fetched) => fetched,
+        Err(SigstoreError::RegistryPullManifestError { .. }) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let manifest = image_manifest(manifest, target)?;
+
+    // the annotations are stored inside of the manifest, not by the layers
+    Ok(manifest
+        .layers
+        .iter()
+        .filter_map(|descriptor| {
+            layers
+                .iter()
+                .find(|layer| layer.sha256_digest() == descriptor.digest)
+                .map(|layer| {
+                    oci_distribution::client::ImageLayer::new(
+                        layer.data.clone(),
+                        descriptor.media_type.clone(),
+                        descriptor.annotations.clone(),
+                    )
+                })
+        })
+        .collect())
+}
+
+/// Append `layer` to the `existing` ones. When replacing, the existing
+/// attestations having the predicate type of `layer` are dropped.
+fn merge_layers(
+    existing: Vec<oci_distribution::client::ImageLayer>,
+    layer: oci_distribution::client::ImageLayer,
+    replace: bool,
+) -> Vec<oci_distribution::client::ImageLayer> {
+    let predicate_type = |layer: &oci_distribution::client::ImageLayer| {
+        Envelope::from_slice(&layer.data)
+            .and_then(|envelope| envelope.statement())
+            .map(|statement| statement.predicate_type)
+            .ok()
+    };
+    let new_predicate_type = predicate_type(&layer);
+
+    let mut layers: Vec<_> = existing
+        .into_iter()
+        .filter(|existing| {
+            existing.sha256_digest() != layer.sha256_digest()
+                && !(replace && predicate_type(existing) == new_predicate_type)
+        })
+        .collect();
+    layers.push(layer);
+    layers
+}
+
+#[cfg(feature = "mock-client")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::certificate_transparency::SctPolicy;
+    use crate::mock_client::test::MockOciClient;
+    use oci_distribution::client::{Config, ImageData, ImageLayer};
+    use oci_distribution::manifest::{OciDescriptor, OciImageManifest, OciManifest};
+    use serde_json::json;
+
+    const IMAGE_DIGEST: &str =
+        "sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b";
+
+    fn layer(signer: &SigStoreSigner, predicate_type: &str, result: &str) -> ImageLayer {
+        let subject = Subject::new("docker.io/library/busybox", IMAGE_DIGEST).unwrap();
+        let statement =
+            Statement::new(vec![subject], predicate_type, &json!({ "result": result })).unwrap();
+        attestation_layer(
+            &sign_statement(signer, &statement).unwrap(),
+            predicate_type,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn replace_attestations_of_the_same_type() {
+        let signer = crate::crypto::SigningScheme::default()
+            .create_signer()
+            .unwrap();
+        let vuln = layer(
+            &signer,
+            "https://cosign.sigstore.dev/attestation/vuln/v1",
+            "PASSED",
+        );
+        let old_provenance = layer(&signer, "https://slsa.dev/provenance/v1", "FAILED");
+        let new_provenance = layer(&signer, "https://slsa.dev/provenance/v1", "PASSED");
+
+        let digests = |layers: &[ImageLayer]| -> Vec<String> {
+            layers.iter().map(ImageLayer::sha256_digest).collect()
+        };
+
+        let existing = vec![vuln.clone(), old_provenance.clone()];
+        let kept = merge_layers(existing.clone(), new_provenance.clone(), false);
+        assert_eq!(
+            digests(&kept),
+            digests(&[vuln.clone(), old_provenance, new_provenance.clone()])
+        );
+
+        let replaced = merge_layers(existing, new_provenance.clone(), true);
+        assert_eq!(digests(&replaced), digests(&[vuln, new_provenance.clone()]));
+
+        // attesting twice the same statement doesn't duplicate it
+        let pushed_again = merge_layers(vec![new_provenance.clone()], new_provenance, false);
+        assert_eq!(pushed_again.len(), 1);
+
+        let annotations = pushed_again[0].annotations.as_ref().unwrap();
+        assert_eq!(
+            annotations[SIGSTORE_PREDICATE_TYPE_ANNOTATION],
+            "https://slsa.dev/provenance/v1"
+        );
+        assert!(annotations.get(SIGSTORE_BUNDLE_ANNOTATION).is_none());
+    }
+
+    #[tokio::test]
+    async fn attest_image_with_existing_attestations() {
+        let signer = crate::crypto::SigningScheme::default()
+            .create_signer()
+            .unwrap();
+        let existing = layer(&signer, "https://example.com/test", "PASSED");
+        let manifest = OciImageManifest {
+            layers: vec![OciDescriptor {
+                media_type: SIGSTORE_DSSE_MEDIA_TYPE.to_string(),
+                digest: existing.sha256_digest(),
+                size: existing.data.len() as i64,
+                annotations: existing.annotations.clone(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let mut client = Client {
+            registry_client: Box::new(MockOciClient {
+                fetch_manifest_digest_response: Some(Ok(IMAGE_DIGEST.to_string())),
+                pull_response: Some(Ok(ImageData {
+                    layers: vec![existing],
+                    digest: None,
+                    config: Config::oci_v1(b"{}".to_vec(), None),
+                    manifest: None,
+                })),
+                pull_manifest_response: Some(Ok((
+                    OciManifest::Image(manifest),
+                    "sha256:manifest".to_string(),
+                ))),
+                push_response: Some(Ok(oci_distribution::client::PushResponse {
+                    config_url: "config".to_string(),
+                    manifest_url: "manifest".to_string(),
+                })),
+            }),
+            rekor_pub_key: None,
+            fulcio_cert_pool: None,
+            trusted_root: None,
+            sct_policy: SctPolicy::Require,
+            freshness: None,
+            progress_listener: None,
+            signature_repository: None,
+            verification_concurrency: 1,
+            verification_cache: None,
+        };
+
+        let response = attest(
+            &mut client,
+            &Auth::Anonymous,
+            &"docker.io/busybox:latest".parse().unwrap(),
+            &signer,
+            "https://slsa.dev/provenance/v1",
+            &json!({"buildDefinition": {}}),
+            &AttestOptions::default(),
+        )
+        .await
+        .expect("attest failed");
+        assert_eq!(response.manifest_url, "manifest");
+    }
+}
@@ -0,0 +1,317 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of the chain of custody of an artifact.
+//!
+//! End-to-end supply chain checks look at a sequence of attestations, each
+//! one produced by a different step: source → build → scan → deploy. The
+//! steps are linked together: the output of one step is the input of the
+//! next one.
+//!
+//! A [`ChainPolicy`] describes the expected steps, the way they are linked
+//! and the assertions each statement must satisfy. The statements are
+//! expected to be verified beforehand, for example with
+//! [`Envelope::verify`](super::Envelope::verify).
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::attestation::chain::{ChainPolicy, Linkage, StepPolicy};
+//! use sigstore::cosign::attestation::Statement;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! # let (source, build, scan): (Statement, Statement, Statement) = unimplemented!();
+//! let policy = ChainPolicy::new()
+//!     .with_step(StepPolicy::new("source"))
+//!     .with_step(
+//!         StepPolicy::new("build")
+//!             .with_predicate_type("https://slsa.dev/provenance/v0.2")
+//!             .with_assertion(r#"$.predicate.buildType == "https://example.com/build@v1""#.parse()?),
+//!     )
+//!     .with_step(StepPolicy::new("scan").with_linkage(Linkage::Subject));
+//!
+//! policy.verify(&[source, build, scan], Some("sha256:..."))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeSet;
+
+use super::subjects::{check_subject, materials};
+use super::{PredicateAssertion, Statement};
+use crate::errors::{Result, SigstoreError};
+
+/// How a step is linked to the previous one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Linkage {
+    /// One of the subjects of the previous statement is a material of this
+    /// one. This is the case of a build consuming the output of the
+    /// previous step.
+    Materials,
+    /// This statement is about one of the subjects of the previous statement.
+    /// This is the case of a scan of the artifact produced by a build.
+    Subject,
+}
+
+/// The requirements of a step of the chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepPolicy {
+    name: String,
+    predicate_type: Option<String>,
+    linkage: Linkage,
+    assertions: Vec<PredicateAssertion>,
+}
+
+impl StepPolicy {
+    /// Create a step without requirements, linked to the previous one via
+    /// [`Linkage::Materials`]
+    pub fn new(name: &str) -> Self {
+        StepPolicy {
+            name: name.to_string(),
+            predicate_type: None,
+            linkage: Linkage::Materials,
+            assertions: Vec::new(),
+        }
+    }
+
+    /// Require the statement to have the given predicate type
+    pub fn with_predicate_type(mut self, predicate_type: &str) -> Self {
+        self.predicate_type = Some(predicate_type.to_string());
+        self
+    }
+
+    /// Change how the step is linked to the previous one. This is ignored
+    /// for the first step of the chain.
+    pub fn with_linkage(mut self, linkage: Linkage) -> Self {
+        self.linkage = linkage;
+        self
+    }
+
+    /// Require the statement to satisfy `assertion`. This method can be
+    /// invoked multiple times, all the assertions must be satisfied.
+    pub fn with_assertion(mut self, assertion: PredicateAssertion) -> Self {
+        self.assertions.push(assertion);
+        self
+    }
+
+    fn check(&self, statement: &Statement) -> Result<()> {
+        if let Some(predicate_type) = &self.predicate_type {
+            if &statement.predicate_type != predicate_type {
+                return Err(self.broken(format!(
+                    "expected predicate type {predicate_type}, found {}",
+                    statement.predicate_type
+                )));
+            }
+        }
+        for assertion in &self.assertions {
+            if !statement.satisfies(assertion)? {
+                return Err(self.broken(format!("assertion {assertion} not satisfied")));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_link(&self, previous: &Statement, statement: &Statement) -> Result<()> {
+        let outputs = subject_digests(previous);
+        let inputs: BTreeSet<(String, String)> = match self.linkage {
+            Linkage::Materials => materials(statement)?
+                .into_iter()
+                .flat_map(|m| m.digest.into_iter())
+                .map(|(algorithm, value)| (algorithm, value.to_lowercase()))
+                .collect(),
+            Linkage::Subject => subject_digests(statement),
+        };
+
+        if outputs.is_disjoint(&inputs) {
+            let reason = match self.linkage {
+                Linkage::Materials => "no material is a subject of the previous step",
+                Linkage::Subject => "no subject is a subject of the previous step",
+            };
+            return Err(self.broken(reason.to_string()));
+        }
+        Ok(())
+    }
+
+    fn broken(&self, reason: String) -> SigstoreError {
+        SigstoreError::AttestationChainError {
+            step: self.name.clone(),
+            reason,
+        }
+    }
+}
+
+/// The digests of all the subjects of a statement
+fn subject_digests(statement: &Statement) -> BTreeSet<(String, String)> {
+    statement
+        .subject
+        .iter()
+        .flat_map(|s| s.digest.iter())
+        .map(|(algorithm, value)| (algorithm.clone(), value.to_lowercase()))
+        .collect()
+}
+
+/// The expected steps of a chain of custody, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainPolicy {
+    steps: Vec<StepPolicy>,
+}
+
+impl ChainPolicy {
+    /// Create an empty policy
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a step to the chain
+    pub fn with_step(mut self, step: StepPolicy) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    /// Verify the chain made by `statements`, one for each step, in order.
+    ///
+    /// When provided, `artifact_digest` must be one of the subjects of the
+    /// last statement, in the `<algorithm>:<hex>` format.
+    pub fn verify(&self, statements: &[Statement], artifact_digest: Option<&str>) -> Result<()> {
+        if statements.len() != self.steps.len() {
+            return Err(SigstoreError::AttestationChainError {
+                step: "-".to_string(),
+                reason: format!(
+                    "expected {} statements, got {}",
+                    self.steps.len(),
+                    statements.len()
+                ),
+            });
+        }
+
+        let mut previous: Option<&Statement> = None;
+        for (step, statement) in self.steps.iter().zip(statements) {
+            step.check(statement)?;
+            if let Some(previous) = previous {
+                step.check_link(previous, statement)?;
+            }
+            previous = Some(statement);
+        }
+
+        if let (Some(digest), Some(step), Some(last)) =
+            (artifact_digest, self.steps.last(), statements.last())
+        {
+            check_subject(last, digest).map_err(|e| step.broken(e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SOURCE_COMMIT: &str = "a1b2c3";
+    const IMAGE_DIGEST: &str = "f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b";
+
+    fn statement(value: serde_json::Value) -> Statement {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn source() -> Statement {
+        statement(json!({
+            "_type": "https://in-toto.io/Statement/v1",
+            "predicateType": "https://example.com/source-review/v1",
+            "subject": [{"name": "repo", "digest": {"sha1": SOURCE_COMMIT}}],
+            "predicate": {"reviewers": 2}
+        }))
+    }
+
+    fn build(commit: &str) -> Statement {
+        statement(json!({
+            "_type": "https://in-toto.io/Statement/v0.1",
+            "predicateType": "https://slsa.dev/provenance/v0.2",
+            "subject": [{"name": "image", "digest": {"sha256": IMAGE_DIGEST}}],
+            "predicate": {
+                "buildType": "https://example.com/build@v1",
+                "materials": [{"uri": "git+https://example.com/repo", "digest": {"sha1": commit}}]
+            }
+        }))
+    }
+
+    fn scan(digest: &str) -> Statement {
+        statement(json!({
+            "_type": "https://in-toto.io/Statement/v1",
+            "predicateType": "https://cosign.sigstore.dev/attestation/vuln/v1",
+            "subject": [{"name": "image", "digest": {"sha256": digest}}],
+            "predicate": {"scanner": {"result": {"critical": 0}}}
+        }))
+    }
+
+    fn policy() -> ChainPolicy {
+        ChainPolicy::new()
+            .with_step(
+                StepPolicy::new("source")
+                    .with_assertion("$.predicate.reviewers == 2".parse().unwrap()),
+            )
+            .with_step(
+                StepPolicy::new("build").with_predicate_type("https://slsa.dev/provenance/v0.2"),
+            )
+            .with_step(
+                StepPolicy::new("scan")
+                    .with_linkage(Linkage::Subject)
+                    .with_assertion("$.predicate.scanner.result.critical == 0".parse().unwrap()),
+            )
+    }
+
+    #[test]
+    fn verify_complete_chain() {
+        let digest = format!("sha256:{IMAGE_DIGEST}");
+        assert!(policy()
+            .verify(
+                &[source(), build(SOURCE_COMMIT), scan(IMAGE_DIGEST)],
+                Some(&digest)
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn detect_broken_links() {
+        let error = policy()
+            .verify(&[source(), build("ffffff"), scan(IMAGE_DIGEST)], None)
+            .expect_err("broken material link accepted");
+        assert!(
+            matches!(error, SigstoreError::AttestationChainError { step, .. } if step == "build")
+        );
+
+        let error = policy()
+            .verify(&[source(), build(SOURCE_COMMIT), scan("0000")], None)
+            .expect_err("broken subject link accepted");
+        assert!(
+            matches!(error, SigstoreError::AttestationChainError { step, .. } if step == "scan")
+        );
+
+        assert!(policy()
+            .verify(
+                &[source(), build(SOURCE_COMMIT), scan(IMAGE_DIGEST)],
+                Some("sha256:0000")
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn enforce_step_requirements() {
+        assert!(policy()
+            .verify(&[source(), build(SOURCE_COMMIT)], None)
+            .is_err());
+        assert!(policy()
+            .verify(&[source(), scan(IMAGE_DIGEST), scan(IMAGE_DIGEST)], None)
+            .is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Attestations attached to container images.
+//!
+//! cosign stores attestations as [DSSE](crate::dsse) envelopes wrapping an
+//! [in-toto statement](https://github.com/in-toto/attestation/blob/main/spec/v1/statement.md).
+//! The envelopes can be obtained with
+//! [`CosignCapabilities::download`](crate::cosign::CosignCapabilities::download)
+//! and [`AttachmentKind::Attestation`](crate::cosign::AttachmentKind::Attestation).
+//!
+//! The predicate of a statement can be inspected with the
+//! [`query`] module, without having to define a dedicated struct for each
+//! kind of predicate. The most common predicates, SLSA provenance and
+//! SBOMs, can also be decoded into the structs of the [`predicate`] module.
+//!
+//! New attestations are created and attached to an image by the [`attest`]
+//! module.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use super::DownloadedLayer;
+use crate::crypto::CosignVerificationKey;
+use crate::dsse::EnvelopeVerifier;
+pub use crate::dsse::{pae, Envelope, EnvelopeSignature};
+use crate::errors::{Result, SigstoreError};
+
+pub mod attest;
+pub mod chain;
+pub mod predicate;
+pub use predicate::Predicate;
+pub mod query;
+pub use query::{JsonPath, PredicateAssertion};
+pub mod slsa;
+pub mod subjects;
+pub mod verification;
+pub use verification::VerifiedAttestation;
+
+/// The payload type used by DSSE envelopes holding an in-toto statement
+pub const IN_TOTO_PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+
+impl Envelope {
+    /// Decode the in-toto statement held by the envelope.
+    ///
+    /// Note well: this doesn't verify the signatures of the envelope.
+    pub fn statement(&self) -> Result<Statement> {
+        if self.payload_type != IN_TOTO_PAYLOAD_TYPE {
+            return Err(SigstoreError::AttestationError(format!(
+                "unexpected payload type {}",
+                self.payload_type
+            )));
+        }
+        Statement::from_slice(&self.decoded_payload()?)
+    }
+
+    /// Ensure the envelope has been signed by `verification_key`, then
+    /// decode its in-toto statement
+    pub fn verify(&self, verification_key: &CosignVerificationKey) -> Result<Statement> {
+        EnvelopeVerifier::new()
+            .with_key(None, verification_key.clone())
+            .verify(self)
+            .map_err(|_| {
+                SigstoreError::AttestationError(
+                    "no signature of the DSSE envelope has been produced by the given key"
+                        .to_string(),
+                )
+            })?;
+        self.statement()
+    }
+}
+
+impl TryFrom<&DownloadedLayer> for Envelope {
+    type Error = SigstoreError;
+
+    fn try_from(layer: &DownloadedLayer) -> Result<Self> {
+        Envelope::from_slice(&layer.data)
+    }
+}
+
+/// An in-toto statement
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Statement {
+    /// The type of the statement, e.g. `https://in-toto.io/Statement/v1`
+    #[serde(rename = "_type")]
+    pub statement_type: String,
+    /// The artifacts the statement is about
+    pub subject: Vec<Subject>,
+    /// The type of the predicate, e.g. `https://slsa.dev/provenance/v1`
+    #[serde(rename = "predicateType")]
+    pub predicate_type: String,
+    /// The predicate, left undecoded
+    #[serde(default)]
+    pub predicate: Value,
+}
+
+/// An artifact referenced by an in-toto statement
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Subject {
+    /// The name of the artifact
+    #[serde(default)]
+    pub name: String,
+    /// The digests of the artifact, indexed by algorithm (e.g. `sha256`)
+    pub digest: BTreeMap<String, String>,
+}
+
+impl Statement {
+    /// Parse a JSON encoded in-toto statement
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        serde_json::from_slice(data)
+            .map_err(|e| SigstoreError::AttestationError(format!("invalid in-toto statement: {e}")))
+    }
+
+    /// Return all the values of the statement matching `path`. The root of
+    /// the path is the whole statement, hence the predicate is found under
+    /// `$.predicate`.
+    pub fn select(&self, path: &JsonPath) -> Result<Vec<Value>> {
+        let value = serde_json::to_value(self)?;
+        Ok(path.select(&value).into_iter().cloned().collect())
+    }
+
+    /// Evaluate `assertion` against the statement
+    pub fn satisfies(&self, assertion: &PredicateAssertion) -> Result<bool> {
+        let value = serde_json::to_value(self)?;
+        Ok(assertion.evaluate(&value))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::crypto::signing_key::SigStoreSigner;
+    use crate::crypto::SigningScheme;
+    use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+    use serde_json::json;
+
+    pub(crate) fn sign_envelope(signer: &SigStoreSigner, statement: &Value) -> Envelope {
+        let mut envelope = build_envelope(statement);
+        envelope.sign(signer, None).unwrap();
+        envelope
+    }
+
+    pub(crate) fn build_envelope(statement: &Value) -> Envelope {
+        Envelope::new(IN_TOTO_PAYLOAD_TYPE, statement.to_string().as_bytes())
+    }
+
+    pub(crate) fn provenance_statement() -> Value {
+        json!({
+            "_type": "https://in-toto.io/Statement/v0.1",
+            "predicateType": "https://slsa.dev/provenance/v0.2",
+            "subject": [{
+                "name": "registry.local/busybox",
+                "digest": {"sha256": "f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b"}
+            }],
+            "predicate": {
+                "builder": {"id": "https://github.com/slsa-framework/slsa-github-generator/.github/workflows/generator_container_slsa3.yml@refs/tags/v1.5.0"},
+                "buildType": "https://github.com/slsa-framework/slsa-github-generator/container@v1",
+                "materials": [{
+                    "uri": "git+https://github.com/octocat/example-repo@refs/heads/main",
+                    "digest": {"sha1": "a1b2c3"}
+                }]
+            }
+        })
+    }
+
+    #[test]
+    fn decode_statement_from_envelope() {
+        let envelope = build_envelope(&provenance_statement());
+        let raw = serde_json::to_vec(&envelope).unwrap();
+
+        let statement = Envelope::from_slice(&raw).unwrap().statement().unwrap();
+        assert_eq!(statement.predicate_type, "https://slsa.dev/provenance/v0.2");
+        assert_eq!(statement.subject.len(), 1);
+
+        let builder_ids = statement
+            .select(&"$.predicate.builder.id".parse().unwrap())
+            .unwrap();
+        assert_eq!(builder_ids.len(), 1);
+    }
+
+    #[test]
+    fn verify_envelope_signature() {
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .expect("cannot create signer");
+        let key = signer.to_verification_key().unwrap();
+        let envelope = sign_envelope(&signer, &provenance_statement());
+        assert!(envelope.verify(&key).is_ok());
+
+        let mut tampered = envelope.clone();
+        tampered.payload = BASE64_STD_ENGINE.encode(b"{}");
+        assert!(tampered.verify(&key).is_err());
+
+        let other_key = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap()
+            .to_verification_key()
+            .unwrap();
+        assert!(envelope.verify(&other_key).is_err());
+    }
+
+    #[test]
+    fn reject_unexpected_payload_type() {
+        let mut envelope = build_envelope(&provenance_statement());
+        envelope.payload_type = "text/plain".to_string();
+        assert!(envelope.statement().is_err());
+    }
+}
@@ -0,0 +1,444 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Typed models of the most common predicates.
+//!
+//! [`Statement::predicate`] is left undecoded, as any kind of predicate can
+//! be attested. The predicates produced by the usual tools can be decoded
+//! into the structs of this module with [`Statement::typed_predicate`]:
+//!
+//! * [`SlsaProvenanceV02`] and [`SlsaProvenanceV1`]: the
+//!   [SLSA provenance](https://slsa.dev/provenance) of an artifact
+//! * [`SpdxDocument`]: an [SPDX](https://spdx.dev) SBOM
+//! * [`CycloneDxBom`]: a [CycloneDX](https://cyclonedx.org) SBOM
+//!
+//! Only the most used fields are modelled. The structs having an `extra`
+//! field keep the other ones there, as JSON.
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::attestation::predicate::Predicate;
+//! use sigstore::cosign::attestation::Statement;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! # let statement: Statement = unimplemented!();
+//! if let Predicate::CycloneDx(bom) = statement.typed_predicate()? {
+//!     for component in &bom.components {
+//!         println!("{} {}", component.name, component.version);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+use super::subjects::Material;
+use super::{Statement, Subject};
+use crate::errors::{Result, SigstoreError};
+
+/// The type of the v0.1 in-toto statements
+pub const STATEMENT_TYPE_V01: &str = "https://in-toto.io/Statement/v0.1";
+/// The type of the v1 in-toto statements
+pub const STATEMENT_TYPE_V1: &str = "https://in-toto.io/Statement/v1";
+
+/// The predicate type of the SLSA v0.2 provenance
+pub const SLSA_PROVENANCE_V02_PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v0.2";
+/// The predicate type of the SLSA v1 provenance
+pub const SLSA_PROVENANCE_V1_PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v1";
+/// The predicate type of the SPDX documents
+pub const SPDX_PREDICATE_TYPE: &str = "https://spdx.dev/Document";
+/// The predicate type of the CycloneDX BOMs
+pub const CYCLONEDX_PREDICATE_TYPE: &str = "https://cyclonedx.org/bom";
+
+/// A decoded predicate
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    SlsaProvenanceV02(SlsaProvenanceV02),
+    SlsaProvenanceV1(SlsaProvenanceV1),
+    Spdx(SpdxDocument),
+    CycloneDx(CycloneDxBom),
+    /// A predicate of another type, left undecoded
+    Other(Value),
+}
+
+/// A SLSA v0.2 provenance
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SlsaProvenanceV02 {
+    pub builder: Builder,
+    pub build_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invocation: Option<Invocation>,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub build_config: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<BuildMetadataV02>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub materials: Vec<Material>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The entity that executed the build
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Builder {
+    pub id: String,
+    /// The versions of the components of the builder, only used by SLSA v1
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub version: BTreeMap<String, String>,
+    /// The dependencies of the builder, only used by SLSA v1
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub builder_dependencies: Vec<ResourceDescriptor>,
+}
+
+/// The event that kicked off a SLSA v0.2 build
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Invocation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_source: Option<ConfigSource>,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub parameters: Value,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub environment: Value,
+}
+
+/// The recipe of a SLSA v0.2 build
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigSource {
+    #[serde(default)]
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub digest: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub entry_point: String,
+}
+
+/// The metadata of a SLSA v0.2 build. The timestamps are RFC 3339 strings.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildMetadataV02 {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_invocation_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_started_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_finished_on: Option<String>,
+    #[serde(default)]
+    pub reproducible: bool,
+}
+
+/// A SLSA v1 provenance
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SlsaProvenanceV1 {
+    pub build_definition: BuildDefinition,
+    pub run_details: RunDetails,
+}
+
+/// The inputs of a SLSA v1 build
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildDefinition {
+    pub build_type: String,
+    #[serde(default)]
+    pub external_parameters: Value,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub internal_parameters: Value,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resolved_dependencies: Vec<ResourceDescriptor>,
+}
+
+/// The details of the execution of a SLSA v1 build
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RunDetails {
+    pub builder: Builder,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<BuildMetadataV1>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub byproducts: Vec<ResourceDescriptor>,
+}
+
+/// The metadata of a SLSA v1 build. The timestamps are RFC 3339 strings.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildMetadataV1 {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invocation_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_on: Option<String>,
+}
+
+/// An in-toto resource descriptor, used by SLSA v1 to describe the
+/// dependencies and the byproducts of a build
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceDescriptor {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub digest: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub download_location: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub media_type: String,
+}
+
+/// An SPDX document
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxDocument {
+    pub spdx_version: String,
+    #[serde(rename = "SPDXID")]
+    pub spdx_id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub document_namespace: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub packages: Vec<SpdxPackage>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A package described by an SPDX document
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxPackage {
+    pub name: String,
+    #[serde(rename = "SPDXID")]
+    pub spdx_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_info: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license_concluded: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license_declared: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checksums: Vec<SpdxChecksum>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The checksum of an SPDX package, e.g. `SHA256`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxChecksum {
+    pub algorithm: String,
+    pub checksum_value: String,
+}
+
+/// A CycloneDX BOM
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxBom {
+    pub bom_format: String,
+    pub spec_version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial_number: Option<String>,
+    #[serde(default)]
+    pub version: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<CycloneDxComponent>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A component described by a CycloneDX BOM
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxComponent {
+    #[serde(rename = "type")]
+    pub component_type: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purl: Option<String>,
+    #[serde(rename = "bom-ref", default, skip_serializing_if = "Option::is_none")]
+    pub bom_ref: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hashes: Vec<CycloneDxHash>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The hash of a CycloneDX component, e.g. `SHA-256`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct CycloneDxHash {
+    pub alg: String,
+    pub content: String,
+}
+
+impl Statement {
+    /// Create a v1 statement about `subject`, holding `predicate`
+    pub fn new<P: Serialize>(
+        subject: Vec<Subject>,
+        predicate_type: &str,
+        predicate: &P,
+    ) -> Result<Self> {
+        Ok(Statement {
+            statement_type: STATEMENT_TYPE_V1.to_string(),
+            subject,
+            predicate_type: predicate_type.to_string(),
+            predicate: serde_json::to_value(predicate)?,
+        })
+    }
+
+    /// Decode the predicate into `P`, regardless of the predicate type
+    pub fn predicate_as<P: DeserializeOwned>(&self) -> Result<P> {
+        serde_json::from_value(self.predicate.clone()).map_err(|e| {
+            SigstoreError::AttestationError(format!(
+                "invalid {} predicate: {e}",
+                self.predicate_type
+            ))
+        })
+    }
+
+    /// Decode the predicate according to its type. The predicates of
+    /// unknown types are returned as [`Predicate::Other`].
+    pub fn typed_predicate(&self) -> Result<Predicate> {
+        Ok(match self.predicate_type.as_str() {
+            SLSA_PROVENANCE_V02_PREDICATE_TYPE => {
+                Predicate::SlsaProvenanceV02(self.predicate_as()?)
+            }
+            SLSA_PROVENANCE_V1_PREDICATE_TYPE => Predicate::SlsaProvenanceV1(self.predicate_as()?),
+            SPDX_PREDICATE_TYPE => Predicate::Spdx(self.predicate_as()?),
+            // cosign attests CycloneDX BOMs with a versioned predicate type
+            t if t == CYCLONEDX_PREDICATE_TYPE
+                || t.starts_with(&format!("{CYCLONEDX_PREDICATE_TYPE}/")) =>
+            {
+                Predicate::CycloneDx(self.predicate_as()?)
+            }
+            _ => Predicate::Other(self.predicate.clone()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::attestation::tests::provenance_statement;
+    use serde_json::json;
+
+    #[test]
+    fn decode_slsa_provenance() {
+        let statement: Statement = serde_json::from_value(provenance_statement()).unwrap();
+        let provenance = match statement.typed_predicate().unwrap() {
+            Predicate::SlsaProvenanceV02(provenance) => provenance,
+            other => panic!("unexpected predicate {:?}", other),
+        };
+        assert!(provenance.builder.id.ends_with("@refs/tags/v1.5.0"));
+        assert_eq!(provenance.materials.len(), 1);
+        assert_eq!(provenance.materials[0].digest["sha1"], "a1b2c3");
+
+        let statement: Statement = serde_json::from_value(json!({
+            "_type": STATEMENT_TYPE_V1,
+            "predicateType": SLSA_PROVENANCE_V1_PREDICATE_TYPE,
+            "subject": [],
+            "predicate": {
+                "buildDefinition": {
+                    "buildType": "https://example.com/build@v1",
+                    "externalParameters": {"ref": "refs/heads/main"},
+                    "resolvedDependencies": [{"name": "base-image", "digest": {"sha256": "abcd"}}]
+                },
+                "runDetails": {
+                    "builder": {"id": "https://example.com/builder", "version": {"builder": "1.2"}},
+                    "metadata": {"invocationId": "42"}
+                }
+            }
+        }))
+        .unwrap();
+        let provenance: SlsaProvenanceV1 = statement.predicate_as().unwrap();
+        assert_eq!(provenance.run_details.builder.version["builder"], "1.2");
+        assert_eq!(
+            provenance.build_definition.resolved_dependencies[0].name,
+            "base-image"
+        );
+    }
+
+    #[test]
+    fn decode_sboms() {
+        let spdx = json!({
+            "spdxVersion": "SPDX-2.3",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": "busybox",
+            "dataLicense": "CC0-1.0",
+            "packages": [{
+                "name": "musl",
+                "SPDXID": "SPDXRef-Package-musl",
+                "versionInfo": "1.2.3",
+                "checksums": [{"algorithm": "SHA256", "checksumValue": "abcd"}]
+            }]
+        });
+        let statement = Statement::new(vec![], SPDX_PREDICATE_TYPE, &spdx).unwrap();
+        match statement.typed_predicate().unwrap() {
+            Predicate::Spdx(document) => {
+                assert_eq!(document.packages[0].version_info.as_deref(), Some("1.2.3"));
+                assert_eq!(document.extra["dataLicense"], "CC0-1.0");
+                // the unknown fields are kept
+                assert_eq!(serde_json::to_value(&document).unwrap(), spdx);
+            }
+            other => panic!("unexpected predicate {:?}", other),
+        }
+
+        let bom = json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.4",
+            "version": 1,
+            "components": [{
+                "type": "library",
+                "name": "musl",
+                "version": "1.2.3",
+                "purl": "pkg:apk/alpine/musl@1.2.3",
+                "bom-ref": "musl"
+            }]
+        });
+        let statement =
+            Statement::new(vec![], &format!("{CYCLONEDX_PREDICATE_TYPE}/v1.4"), &bom).unwrap();
+        match statement.typed_predicate().unwrap() {
+            Predicate::CycloneDx(bom) => {
+                assert_eq!(bom.components[0].bom_ref.as_deref(), Some("musl"));
+            }
+            other => panic!("unexpected predicate {:?}", other),
+        }
+
+        let statement = Statement::new(vec![], "https://example.com/custom", &bom).unwrap();
+        assert!(matches!(
+            statement.typed_predicate().unwrap(),
+            Predicate::Other(_)
+        ));
+    }
+
+    #[test]
+    fn reject_invalid_predicate() {
+        let statement =
+            Statement::new(vec![], SLSA_PROVENANCE_V1_PREDICATE_TYPE, &json!({})).unwrap();
+        assert!(matches!(
+            statement.typed_predicate(),
+            Err(SigstoreError::AttestationError(_))
+        ));
+    }
+}
@@ -0,0 +1,342 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A small query language over in-toto statements.
+//!
+//! Paths use a subset of the JSONPath syntax:
+//!   * `$` is the root of the statement
+//!   * `.name` and `['name']` select a field of an object
+//!   * `[0]` selects an element of an array
+//!   * `.*` and `[*]` select all the fields of an object, or all the elements
+//!     of an array
+//!
+//! A [`PredicateAssertion`] combines a path with an optional comparison
+//! against a JSON value:
+//!   * `$.predicate.builder.id == "https://example.com/builder"` is satisfied
+//!     when at least one of the selected values is equal to the given one
+//!   * `$.predicate.buildType != "local"` is satisfied when none of the
+//!     selected values is equal to the given one
+//!   * `$.predicate.materials[0].digest.sha1` is satisfied when the path
+//!     selects at least one value
+//!
+//! ```rust
+//! use sigstore::cosign::attestation::PredicateAssertion;
+//! use serde_json::json;
+//!
+//! let assertion: PredicateAssertion =
+//!     r#"$.predicate.builder.id == "https://example.com/builder""#.parse().unwrap();
+//! let statement = json!({"predicate": {"builder": {"id": "https://example.com/builder"}}});
+//! assert!(assertion.evaluate(&statement));
+//! ```
+
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::errors::SigstoreError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Field(String),
+    Index(usize),
+    Wildcard,
+}
+
+/// A path selecting values inside of a JSON document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    raw: String,
+    segments: Vec<Segment>,
+}
+
+impl JsonPath {
+    /// Return all the values matching the path
+    pub fn select<'a>(&self, root: &'a Value) -> Vec<&'a Value> {
+        let mut current = vec![root];
+        for segment in &self.segments {
+            current = current
+                .into_iter()
+                .flat_map(|value| -> Vec<&'a Value> {
+                    match (segment, value) {
+                        (Segment::Field(name), Value::Object(map)) => {
+                            map.get(name).into_iter().collect()
+                        }
+                        (Segment::Index(index), Value::Array(items)) => {
+                            items.get(*index).into_iter().collect()
+                        }
+                        (Segment::Wildcard, Value::Object(map)) => map.values().collect(),
+                        (Segment::Wildcard, Value::Array(items)) => items.iter().collect(),
+                        _ => Vec::new(),
+                    }
+                })
+                .collect();
+        }
+        current
+    }
+
+    /// Parse a path at the beginning of `input`, returning it together with
+    /// the rest of the input
+    fn parse_prefix(input: &str) -> std::result::Result<(Self, &str), String> {
+        let trimmed = input.trim_start();
+        let mut rest = trimmed
+            .strip_prefix('$')
+            .ok_or_else(|| "path must start with `$`".to_string())?;
+        let mut segments = Vec::new();
+
+        loop {
+            if let Some(r) = rest.strip_prefix('.') {
+                if let Some(r) = r.strip_prefix('*') {
+                    segments.push(Segment::Wildcard);
+                    rest = r;
+                    continue;
+                }
+                let end = r
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                    .unwrap_or(r.len());
+                if end == 0 {
+                    return Err("empty field name".to_string());
+                }
+                segments.push(Segment::Field(r[..end].to_string()));
+                rest = &r[end..];
+            } else if let Some(r) = rest.strip_prefix('[') {
+                let (segment, r) = parse_bracket(r)?;
+                segments.push(segment);
+                rest = r;
+            } else {
+                break;
+            }
+        }
+
+        let raw = trimmed[..trimmed.len() - rest.len()].to_string();
+        Ok((JsonPath { raw, segments }, rest))
+    }
+}
+
+/// Parse the content of a `[...]` segment, the opening bracket has already
+/// been consumed
+fn parse_bracket(input: &str) -> std::result::Result<(Segment, &str), String> {
+    if let Some(r) = input.strip_prefix("*]") {
+        return Ok((Segment::Wildcard, r));
+    }
+    if let Some(quote) = input.chars().next().filter(|c| *c == '\'' || *c == '"') {
+        let body = &input[1..];
+        let end = body
+            .find(quote)
+            .ok_or_else(|| "unterminated field name".to_string())?;
+        let r = body[end + 1..]
+            .strip_prefix(']')
+            .ok_or_else(|| "expected `]`".to_string())?;
+        return Ok((Segment::Field(body[..end].to_string()), r));
+    }
+    let end = input.find(']').ok_or_else(|| "expected `]`".to_string())?;
+    let index = input[..end]
+        .trim()
+        .parse::<usize>()
+        .map_err(|e| format!("invalid array index: {e}"))?;
+    Ok((Segment::Index(index), &input[end + 1..]))
+}
+
+impl FromStr for JsonPath {
+    type Err = SigstoreError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, rest) =
+            JsonPath::parse_prefix(s).map_err(|error| SigstoreError::PredicateQueryError {
+                query: s.to_string(),
+                error,
+            })?;
+        if !rest.trim().is_empty() {
+            return Err(SigstoreError::PredicateQueryError {
+                query: s.to_string(),
+                error: format!("unexpected trailing input `{}`", rest.trim()),
+            });
+        }
+        Ok(path)
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operator {
+    Exists,
+    Equal(Value),
+    NotEqual(Value),
+}
+
+/// An assertion about the values found at a given path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PredicateAssertion {
+    path: JsonPath,
+    operator: Operator,
+}
+
+impl PredicateAssertion {
+    /// Assert that `path` selects at least one value
+    pub fn exists(path: JsonPath) -> Self {
+        PredicateAssertion {
+            path,
+            operator: Operator::Exists,
+        }
+    }
+
+    /// Assert that at least one of the values selected by `path` is equal to
+    /// `expected`
+    pub fn equal(path: JsonPath, expected: Value) -> Self {
+        PredicateAssertion {
+            path,
+            operator: Operator::Equal(expected),
+        }
+    }
+
+    /// Assert that none of the values selected by `path` is equal to
+    /// `unexpected`
+    pub fn not_equal(path: JsonPath, unexpected: Value) -> Self {
+        PredicateAssertion {
+            path,
+            operator: Operator::NotEqual(unexpected),
+        }
+    }
+
+    /// The path the assertion is about
+    pub fn path(&self) -> &JsonPath {
+        &self.path
+    }
+
+    /// Evaluate the assertion against `root`
+    pub fn evaluate(&self, root: &Value) -> bool {
+        let selected = self.path.select(root);
+        match &self.operator {
+            Operator::Exists => !selected.is_empty(),
+            Operator::Equal(expected) => selected.contains(&expected),
+            Operator::NotEqual(unexpected) => selected.iter().all(|v| *v != unexpected),
+        }
+    }
+}
+
+impl FromStr for PredicateAssertion {
+    type Err = SigstoreError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |error: String| SigstoreError::PredicateQueryError {
+            query: s.to_string(),
+            error,
+        };
+
+        let (path, rest) = JsonPath::parse_prefix(s).map_err(error)?;
+        let rest = rest.trim();
+        if rest.is_empty() {
+            return Ok(PredicateAssertion::exists(path));
+        }
+
+        let (operator, operand): (fn(Value) -> Operator, &str) =
+            if let Some(operand) = rest.strip_prefix("==") {
+                (Operator::Equal, operand)
+            } else if let Some(operand) = rest.strip_prefix("!=") {
+                (Operator::NotEqual, operand)
+            } else {
+                return Err(error(format!("unknown operator in `{rest}`")));
+            };
+        let value: Value = serde_json::from_str(operand.trim())
+            .map_err(|e| error(format!("the operand is not a JSON value: {e}")))?;
+
+        Ok(PredicateAssertion {
+            path,
+            operator: operator(value),
+        })
+    }
+}
+
+impl fmt::Display for PredicateAssertion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.operator {
+            Operator::Exists => write!(f, "{}", self.path),
+            Operator::Equal(v) => write!(f, "{} == {v}", self.path),
+            Operator::NotEqual(v) => write!(f, "{} != {v}", self.path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::attestation::tests::provenance_statement;
+    use serde_json::json;
+
+    #[test]
+    fn select_values() {
+        let statement = provenance_statement();
+
+        let path: JsonPath = "$.subject[0].digest.sha256".parse().unwrap();
+        assert_eq!(path.select(&statement).len(), 1);
+
+        let path: JsonPath = "$.predicate['builder'][\"id\"]".parse().unwrap();
+        assert_eq!(path.select(&statement).len(), 1);
+
+        let path: JsonPath = "$.predicate.materials[*].uri".parse().unwrap();
+        assert_eq!(
+            path.select(&statement),
+            vec![&json!(
+                "git+https://github.com/octocat/example-repo@refs/heads/main"
+            )]
+        );
+
+        let path: JsonPath = "$.predicate.missing[3]".parse().unwrap();
+        assert!(path.select(&statement).is_empty());
+    }
+
+    #[test]
+    fn reject_invalid_paths() {
+        for path in ["predicate", "$.", "$[x]", "$['unterminated]", "$.a b"] {
+            assert!(path.parse::<JsonPath>().is_err(), "{} accepted", path);
+        }
+    }
+
+    #[test]
+    fn evaluate_assertions() {
+        let statement = provenance_statement();
+        let cases = [
+            (
+                r#"$.predicateType == "https://slsa.dev/provenance/v0.2""#,
+                true,
+            ),
+            (
+                r#"$.predicateType == "https://slsa.dev/provenance/v1""#,
+                false,
+            ),
+            (r#"$.predicate.buildType != "local""#, true),
+            ("$.predicate.materials[0].digest.sha1", true),
+            ("$.predicate.invocation", false),
+            (r#"$.subject[*].name == "registry.local/busybox""#, true),
+        ];
+        for (raw, expected) in cases {
+            let assertion: PredicateAssertion = raw.parse().unwrap();
+            assert_eq!(assertion.evaluate(&statement), expected, "{raw}");
+            assert_eq!(
+                assertion.to_string().parse::<PredicateAssertion>().unwrap(),
+                assertion
+            );
+        }
+
+        assert!(r#"$.predicateType ~= "x""#.parse::<PredicateAssertion>().is_err());
+        assert!("$.predicateType == not-json"
+            .parse::<PredicateAssertion>()
+            .is_err());
+    }
+}
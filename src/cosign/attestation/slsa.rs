@@ -0,0 +1,270 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers for [SLSA provenance](https://slsa.dev/provenance) attestations.
+//!
+//! The most common SLSA policy ensures an artifact has been produced by a
+//! trusted builder. [`TrustedBuilder`] checks the builder ID of a verified
+//! provenance statement, optionally requiring a minimum version of the
+//! builder.
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::attestation::slsa::{TrustedBuilder, GITHUB_CONTAINER_BUILDER};
+//! use sigstore::cosign::attestation::Envelope;
+//! use sigstore::crypto::CosignVerificationKey;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! # let envelope: Envelope = unimplemented!();
+//! # let verification_key: CosignVerificationKey = unimplemented!();
+//! let statement = envelope.verify(&verification_key)?;
+//! TrustedBuilder::new(GITHUB_CONTAINER_BUILDER)
+//!     .with_min_version("v1.4.0")?
+//!     .check(&statement)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::{JsonPath, Statement};
+use crate::errors::{Result, SigstoreError};
+
+/// The prefix shared by the predicate types of all the SLSA provenance
+/// versions
+pub const SLSA_PROVENANCE_PREDICATE_TYPE_PREFIX: &str = "https://slsa.dev/provenance/";
+
+/// The SLSA 3 builder for generic artifacts, hosted on GitHub
+pub const GITHUB_GENERIC_BUILDER: &str =
+    "https://github.com/slsa-framework/slsa-github-generator/.github/workflows/generator_generic_slsa3.yml";
+
+/// The SLSA 3 builder for container images, hosted on GitHub
+pub const GITHUB_CONTAINER_BUILDER: &str =
+    "https://github.com/slsa-framework/slsa-github-generator/.github/workflows/generator_container_slsa3.yml";
+
+/// Where the builder ID is found, for the SLSA v0.x and v1 predicates
+const BUILDER_ID_PATHS: [&str; 2] = [
+    "$.predicate.builder.id",
+    "$.predicate.runDetails.builder.id",
+];
+
+/// The version of a builder, taken from the Git tag of its ID
+/// (e.g. `...@refs/tags/v1.5.0`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BuilderVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl FromStr for BuilderVersion {
+    type Err = SigstoreError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || SigstoreError::AttestationError(format!("invalid builder version {s}"));
+        let raw = s.strip_prefix('v').unwrap_or(s);
+        // ignore pre-release and build metadata
+        let raw = raw.split(['-', '+']).next().unwrap_or(raw);
+
+        let mut parts = raw.split('.');
+        let mut next = || -> Result<u64> {
+            match parts.next() {
+                None => Ok(0),
+                Some(p) => p.parse::<u64>().map_err(|_| invalid()),
+            }
+        };
+        let version = BuilderVersion {
+            major: next()?,
+            minor: next()?,
+            patch: next()?,
+        };
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(version)
+    }
+}
+
+impl fmt::Display for BuilderVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// A builder trusted to produce provenance attestations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedBuilder {
+    id: String,
+    min_version: Option<BuilderVersion>,
+}
+
+impl TrustedBuilder {
+    /// Trust the builder with the given ID. The ID is compared without the
+    /// `@<git ref>` suffix, if any.
+    pub fn new(id: &str) -> Self {
+        TrustedBuilder {
+            id: id.to_string(),
+            min_version: None,
+        }
+    }
+
+    /// Require the builder to be released with a Git tag greater or equal
+    /// to `version` (e.g. `v1.4.0`)
+    pub fn with_min_version(mut self, version: &str) -> Result<Self> {
+        self.min_version = Some(version.parse()?);
+        Ok(self)
+    }
+
+    /// Ensure `statement` is a SLSA provenance produced by the builder
+    pub fn check(&self, statement: &Statement) -> Result<()> {
+        let builder_id = builder_id(statement)?;
+        let untrusted = |reason: String| SigstoreError::UntrustedBuilder {
+            builder_id: builder_id.clone(),
+            reason,
+        };
+
+        let (id, git_ref) = match builder_id.split_once('@') {
+            Some((id, git_ref)) => (id, Some(git_ref)),
+            None => (builder_id.as_str(), None),
+        };
+        if id != self.id {
+            return Err(untrusted(format!("expected {}", self.id)));
+        }
+
+        if let Some(min_version) = &self.min_version {
+            let tag = git_ref
+                .and_then(|r| r.strip_prefix("refs/tags/"))
+                .ok_or_else(|| untrusted("the builder is not referenced by tag".to_string()))?;
+            let version: BuilderVersion = tag.parse()?;
+            if version < *min_version {
+                return Err(untrusted(format!(
+                    "version {version} is older than {min_version}"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The ID of the builder that produced a SLSA provenance statement
+pub fn builder_id(statement: &Statement) -> Result<String> {
+    if !statement
+        .predicate_type
+        .starts_with(SLSA_PROVENANCE_PREDICATE_TYPE_PREFIX)
+    {
+        return Err(SigstoreError::AttestationError(format!(
+            "{} is not a SLSA provenance predicate",
+            statement.predicate_type
+        )));
+    }
+
+    for path in BUILDER_ID_PATHS {
+        let path: JsonPath = path.parse()?;
+        if let Some(id) = statement
+            .select(&path)?
+            .into_iter()
+            .find_map(|v| v.as_str().map(|s| s.to_string()))
+        {
+            return Ok(id);
+        }
+    }
+    Err(SigstoreError::AttestationError(
+        "the provenance doesn't declare a builder ID".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::attestation::tests::provenance_statement;
+    use serde_json::json;
+
+    fn statement(value: serde_json::Value) -> Statement {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn check_builder_identity() {
+        let provenance = statement(provenance_statement());
+
+        assert!(TrustedBuilder::new(GITHUB_CONTAINER_BUILDER)
+            .check(&provenance)
+            .is_ok());
+        assert!(TrustedBuilder::new(GITHUB_CONTAINER_BUILDER)
+            .with_min_version("v1.4.0")
+            .unwrap()
+            .check(&provenance)
+            .is_ok());
+
+        let error = TrustedBuilder::new(GITHUB_CONTAINER_BUILDER)
+            .with_min_version("v1.6")
+            .unwrap()
+            .check(&provenance)
+            .expect_err("old builder accepted");
+        assert!(matches!(error, SigstoreError::UntrustedBuilder { .. }));
+
+        assert!(TrustedBuilder::new(GITHUB_GENERIC_BUILDER)
+            .check(&provenance)
+            .is_err());
+    }
+
+    #[test]
+    fn builder_identity_of_slsa_v1_provenance() {
+        let provenance = statement(json!({
+            "_type": "https://in-toto.io/Statement/v1",
+            "predicateType": "https://slsa.dev/provenance/v1",
+            "subject": [],
+            "predicate": {
+                "runDetails": {
+                    "builder": {"id": format!("{GITHUB_GENERIC_BUILDER}@refs/heads/main")}
+                }
+            }
+        }));
+
+        assert!(TrustedBuilder::new(GITHUB_GENERIC_BUILDER)
+            .check(&provenance)
+            .is_ok());
+        // branches cannot satisfy version constraints
+        assert!(TrustedBuilder::new(GITHUB_GENERIC_BUILDER)
+            .with_min_version("v1.0.0")
+            .unwrap()
+            .check(&provenance)
+            .is_err());
+    }
+
+    #[test]
+    fn reject_non_provenance_statements() {
+        let mut value = provenance_statement();
+        value["predicateType"] = json!("https://cyclonedx.org/bom");
+        assert!(builder_id(&statement(value)).is_err());
+    }
+
+    #[test]
+    fn parse_builder_versions() {
+        assert_eq!(
+            "v1.5.0".parse::<BuilderVersion>().unwrap(),
+            BuilderVersion {
+                major: 1,
+                minor: 5,
+                patch: 0
+            }
+        );
+        assert_eq!("2".parse::<BuilderVersion>().unwrap().to_string(), "v2.0.0");
+        assert!("v1.2.3-rc.1".parse::<BuilderVersion>().is_ok());
+        assert!("v1.x".parse::<BuilderVersion>().is_err());
+        assert!("1.2.3.4".parse::<BuilderVersion>().is_err());
+    }
+}
@@ -0,0 +1,279 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cross-checks between the digests declared by a statement and the actual
+//! ones.
+//!
+//! A valid attestation is worthless when it's about another artifact.
+//! [`check_subject`] ensures the artifact being verified is one of the
+//! subjects of the statement, while [`check_materials`] ensures the inputs
+//! of the build declared by a provenance are the expected ones.
+//!
+//! Digests are always expressed in the `<algorithm>:<hex>` format, like
+//! the ones used by OCI registries.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use super::{JsonPath, Statement, Subject};
+use crate::crypto::hash::HashAlgorithm;
+use crate::errors::{Result, SigstoreError};
+
+/// Where the materials are found, for the SLSA v0.x and v1 predicates
+const MATERIALS_PATHS: [&str; 2] = [
+    "$.predicate.materials[*]",
+    "$.predicate.buildDefinition.resolvedDependencies[*]",
+];
+
+/// An input of the build, as declared by a provenance
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Material {
+    /// The location of the material
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub uri: String,
+    /// The name of the material, only used by SLSA v1 dependencies
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    /// The digests of the material, indexed by algorithm
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub digest: BTreeMap<String, String>,
+}
+
+impl Material {
+    /// Returns `true` when the material is identified by `id`, which is
+    /// either its URI or its name
+    pub fn is_identified_by(&self, id: &str) -> bool {
+        (!self.uri.is_empty() && self.uri == id) || (!self.name.is_empty() && self.name == id)
+    }
+}
+
+/// Split a digest into its algorithm and its hex encoded value
+fn split_digest(digest: &str) -> Result<(&str, &str)> {
+    digest
+        .split_once(':')
+        .filter(|(algorithm, value)| !algorithm.is_empty() && !value.is_empty())
+        .ok_or_else(|| {
+            SigstoreError::AttestationError(format!(
+                "digest {digest} is not in the <algorithm>:<hex> format"
+            ))
+        })
+}
+
+/// Returns `true` when `digests` contains `digest`
+fn contains_digest(digests: &BTreeMap<String, String>, digest: &str) -> Result<bool> {
+    let (algorithm, value) = split_digest(digest)?;
+    Ok(digests
+        .get(algorithm)
+        .map(|v| v.eq_ignore_ascii_case(value))
+        .unwrap_or(false))
+}
+
+impl Subject {
+    /// The subject `name`, whose digest is `digest`
+    pub fn new(name: &str, digest: &str) -> Result<Self> {
+        let (algorithm, value) = split_digest(digest)?;
+        Ok(Subject {
+            name: name.to_string(),
+            digest: [(algorithm.to_string(), value.to_lowercase())].into(),
+        })
+    }
+
+    /// Returns `true` when the subject has the digest `digest`
+    pub fn matches(&self, digest: &str) -> Result<bool> {
+        contains_digest(&self.digest, digest)
+    }
+}
+
+/// The subjects of `statement` whose digest is `digest`
+pub fn subjects_matching<'a>(statement: &'a Statement, digest: &str) -> Result<Vec<&'a Subject>> {
+    let mut subjects = Vec::new();
+    for subject in &statement.subject {
+        if subject.matches(digest)? {
+            subjects.push(subject);
+        }
+    }
+    Ok(subjects)
+}
+
+/// Ensure `digest` belongs to one of the subjects of `statement`
+pub fn check_subject(statement: &Statement, digest: &str) -> Result<()> {
+    if subjects_matching(statement, digest)?.is_empty() {
+        return Err(SigstoreError::AttestationDigestMismatch(format!(
+            "{digest} is not a subject of the statement"
+        )));
+    }
+    Ok(())
+}
+
+/// Ensure the artifact read from `artifact` is one of the subjects of
+/// `statement`, and return its subject. The SHA-256 digest of the artifact
+/// is computed while it's read.
+pub fn check_artifact<R: Read>(statement: &Statement, artifact: R) -> Result<&Subject> {
+    let digest = format!(
+        "sha256:{}",
+        hex::encode(HashAlgorithm::Sha256.digest_reader(artifact)?)
+    );
+    subjects_matching(statement, &digest)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            SigstoreError::AttestationDigestMismatch(format!(
+                "the artifact {digest} is not a subject of the statement"
+            ))
+        })
+}
+
+/// The materials declared by a provenance statement
+pub fn materials(statement: &Statement) -> Result<Vec<Material>> {
+    let mut materials = Vec::new();
+    for path in MATERIALS_PATHS {
+        let path: JsonPath = path.parse()?;
+        for value in statement.select(&path)? {
+            let material: Material = serde_json::from_value(value)
+                .map_err(|e| SigstoreError::AttestationError(format!("invalid material: {e}")))?;
+            materials.push(material);
+        }
+    }
+    Ok(materials)
+}
+
+/// Ensure the provenance `statement` declares all the `expected` materials,
+/// indexed by URI or name, with the given digests.
+///
+/// Materials that are declared by the statement, but are not part of
+/// `expected`, are ignored.
+pub fn check_materials(statement: &Statement, expected: &BTreeMap<String, String>) -> Result<()> {
+    let declared = materials(statement)?;
+    for (uri, digest) in expected {
+        let material = declared
+            .iter()
+            .find(|m| m.is_identified_by(uri))
+            .ok_or_else(|| {
+                SigstoreError::AttestationDigestMismatch(format!("material {uri} is not declared"))
+            })?;
+        if !contains_digest(&material.digest, digest)? {
+            return Err(SigstoreError::AttestationDigestMismatch(format!(
+                "material {uri} doesn't have digest {digest}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::attestation::tests::provenance_statement;
+    use serde_json::json;
+    use sha2::Digest;
+
+    const SUBJECT_DIGEST: &str =
+        "sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b";
+    const MATERIAL_URI: &str = "git+https://github.com/octocat/example-repo@refs/heads/main";
+
+    fn statement(value: serde_json::Value) -> Statement {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn subject_digest_must_match() {
+        let statement = statement(provenance_statement());
+        assert!(check_subject(&statement, SUBJECT_DIGEST).is_ok());
+        assert!(check_subject(
+            &statement,
+            &SUBJECT_DIGEST.to_uppercase().replace("SHA256", "sha256")
+        )
+        .is_ok());
+
+        let error = check_subject(&statement, "sha256:0000").expect_err("wrong subject accepted");
+        assert!(matches!(error, SigstoreError::AttestationDigestMismatch(_)));
+        assert!(check_subject(&statement, "sha512:f3cf").is_err());
+        assert!(check_subject(&statement, "f3cfc9d0").is_err());
+    }
+
+    #[test]
+    fn artifact_must_be_a_subject() {
+        let artifact = b"hello";
+        let digest = format!("sha256:{}", hex::encode(sha2::Sha256::digest(artifact)));
+        let statement = Statement {
+            subject: vec![
+                Subject::new("other", SUBJECT_DIGEST).unwrap(),
+                Subject::new(
+                    "hello.txt",
+                    &digest.to_uppercase().replace("SHA256", "sha256"),
+                )
+                .unwrap(),
+            ],
+            ..statement(provenance_statement())
+        };
+
+        assert_eq!(subjects_matching(&statement, &digest).unwrap().len(), 1);
+        assert_eq!(
+            check_artifact(&statement, &artifact[..]).unwrap().name,
+            "hello.txt"
+        );
+        assert!(matches!(
+            check_artifact(&statement, &b"bye"[..]),
+            Err(SigstoreError::AttestationDigestMismatch(_))
+        ));
+        assert!(Subject::new("hello.txt", "f3cfc9d0").is_err());
+    }
+
+    #[test]
+    fn materials_must_match() {
+        let statement = statement(provenance_statement());
+
+        let expected: BTreeMap<String, String> =
+            [(MATERIAL_URI.to_string(), "sha1:a1b2c3".to_string())].into();
+        assert!(check_materials(&statement, &expected).is_ok());
+
+        let expected: BTreeMap<String, String> =
+            [(MATERIAL_URI.to_string(), "sha1:ffffff".to_string())].into();
+        assert!(check_materials(&statement, &expected).is_err());
+
+        let expected: BTreeMap<String, String> = [(
+            "git+https://example.com/other".to_string(),
+            "sha1:a1b2c3".to_string(),
+        )]
+        .into();
+        assert!(check_materials(&statement, &expected).is_err());
+    }
+
+    #[test]
+    fn materials_of_slsa_v1_provenance() {
+        let statement = statement(json!({
+            "_type": "https://in-toto.io/Statement/v1",
+            "predicateType": "https://slsa.dev/provenance/v1",
+            "subject": [],
+            "predicate": {
+                "buildDefinition": {
+                    "resolvedDependencies": [
+                        {"uri": MATERIAL_URI, "digest": {"gitCommit": "a1b2c3"}},
+                        {"name": "base-image", "digest": {"sha256": "abcd"}}
+                    ]
+                }
+            }
+        }));
+
+        let materials = materials(&statement).unwrap();
+        assert_eq!(materials.len(), 2);
+        assert_eq!(materials[1].name, "base-image");
+
+        let expected: BTreeMap<String, String> =
+            [("base-image".to_string(), "sha256:abcd".to_string())].into();
+        assert!(check_materials(&statement, &expected).is_ok());
+    }
+}
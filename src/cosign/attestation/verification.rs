@@ -0,0 +1,354 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of the attestations attached to an image, see
+//! [`CosignCapabilities::verify_attestations`](crate::cosign::CosignCapabilities::verify_attestations).
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use super::predicate::Predicate;
+use super::subjects::check_subject;
+use super::{Envelope, Statement};
+use crate::cosign::bundle::Bundle;
+use crate::cosign::constants::SIGSTORE_DSSE_MEDIA_TYPE;
+use crate::cosign::signature_layers::{CertificateSignature, SignatureLayer};
+use crate::crypto::certificate_pool::CertificatePool;
+use crate::crypto::certificate_transparency::SctPolicy;
+use crate::crypto::trusted_root::TrustedRoot;
+use crate::crypto::CosignVerificationKey;
+use crate::errors::{Result, SigstoreError};
+
+/// An attestation attached to an image, whose envelope has been verified
+#[derive(Clone, Debug)]
+pub struct VerifiedAttestation {
+    /// The digest of the layer holding the attestation
+    pub oci_digest: String,
+    /// The DSSE envelope of the attestation
+    pub envelope: Envelope,
+    /// The in-toto statement held by the envelope. The image is one of its
+    /// subjects.
+    pub statement: Statement,
+    /// The decoded predicate of the statement
+    pub predicate: Predicate,
+    /// The certificate of the signer, for attestations produced in keyless
+    /// mode. Like for signatures, the identity of the signer still has to be
+    /// checked by the caller.
+    pub certificate_signature: Option<CertificateSignature>,
+    /// The bundle produced by Rekor
+    pub bundle: Option<Bundle>,
+}
+
+impl VerifiedAttestation {
+    /// Verify the attestation held by `layer`.
+    ///
+    /// The envelope is verified with `verification_key` when provided,
+    /// otherwise with the certificate found inside of the annotations, which
+    /// must have been issued by Fulcio. The Rekor bundle, when present, must
+    /// refer to the payload of the envelope.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        descriptor: &oci_distribution::manifest::OciDescriptor,
+        layer: &oci_distribution::client::ImageLayer,
+        source_image_digest: &str,
+        verification_key: Option<&CosignVerificationKey>,
+        rekor_pub_key: Option<&CosignVerificationKey>,
+        fulcio_cert_pool: Option<&CertificatePool>,
+        trusted_root: Option<&TrustedRoot>,
+        sct_policy: SctPolicy,
+    ) -> Result<Self> {
+        if descriptor.media_type != SIGSTORE_DSSE_MEDIA_TYPE
+            || layer.media_type != SIGSTORE_DSSE_MEDIA_TYPE
+        {
+            return Err(SigstoreError::SigstoreMediaTypeNotFoundError);
+        }
+        if descriptor.digest != layer.sha256_digest() {
+            return Err(SigstoreError::SigstoreLayerDigestMismatchError);
+        }
+
+        let envelope = Envelope::from_slice(&layer.data)?;
+        let annotations = descriptor.annotations.clone().unwrap_or_default();
+
+        let bundle = match trusted_root {
+            Some(trusted_root) => {
+                SignatureLayer::get_bundle_from_annotations_at(&annotations, trusted_root)?
+            }
+            None => SignatureLayer::get_bundle_from_annotations(&annotations, rekor_pub_key)?,
+        };
+        if let Some(bundle) = &bundle {
+            check_log_entry(bundle, &envelope)?;
+        }
+        let fulcio_cert_pool_at_signing_time = match (trusted_root, bundle.as_ref()) {
+            (Some(trusted_root), Some(bundle)) => {
+                trusted_root.fulcio_cert_pool_at(bundle.payload.integrated_time)?
+            }
+            _ => None,
+        };
+        let certificate_signature = SignatureLayer::get_certificate_signature_from_annotations(
+            &annotations,
+            match trusted_root {
+                Some(_) => fulcio_cert_pool_at_signing_time.as_ref(),
+                None => fulcio_cert_pool,
+            },
+            bundle.as_ref(),
+            trusted_root,
+            sct_policy,
+        );
+
+        let key = verification_key
+            .or_else(|| certificate_signature.as_ref().map(|c| &c.verification_key))
+            .ok_or_else(|| {
+                SigstoreError::AttestationError(
+                    "neither a verification key nor a trusted certificate is available".to_string(),
+                )
+            })?;
+        let statement = envelope.verify(key)?;
+        check_subject(&statement, source_image_digest)?;
+        let predicate = statement.typed_predicate()?;
+
+        Ok(VerifiedAttestation {
+            oci_digest: descriptor.digest.clone(),
+            envelope,
+            statement,
+            predicate,
+            certificate_signature,
+            bundle,
+        })
+    }
+}
+
+/// Ensure the `intoto` entry recorded by Rekor refers to the payload of
+/// `envelope`
+fn check_log_entry(bundle: &Bundle, envelope: &Envelope) -> Result<()> {
+    let body: Value = serde_json::from_slice(&BASE64_STD_ENGINE.decode(&bundle.payload.body)?)?;
+    let payload_hash = hex::encode(Sha256::digest(envelope.decoded_payload()?));
+    if body.pointer("/spec/content/payloadHash/value") != Some(&Value::from(payload_hash)) {
+        return Err(SigstoreError::RekorEntryMismatchError(
+            "the entry doesn't refer to the payload of the attestation".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Creates the list of the attestations held by the layers of `manifest`
+/// that can be verified. The layers that cannot be verified are skipped.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn build_verified_attestations(
+    manifest: &oci_distribution::manifest::OciImageManifest,
+    source_image_digest: &str,
+    layers: &[oci_distribution::client::ImageLayer],
+    verification_key: Option<&CosignVerificationKey>,
+    rekor_pub_key: Option<&CosignVerificationKey>,
+    fulcio_cert_pool: Option<&CertificatePool>,
+    trusted_root: Option<&TrustedRoot>,
+    sct_policy: SctPolicy,
+) -> Result<Vec<VerifiedAttestation>> {
+    let mut attestations = Vec::new();
+
+    for descriptor in &manifest.layers {
+        let layer = match layers
+            .iter()
+            .find(|l| l.sha256_digest() == descriptor.digest)
+        {
+            Some(layer) => layer,
+            None => continue,
+        };
+        match VerifiedAttestation::new(
+            descriptor,
+            layer,
+            source_image_digest,
+            verification_key,
+            rekor_pub_key,
+            fulcio_cert_pool,
+            trusted_root,
+            sct_policy,
+        ) {
+            Ok(attestation) => attestations.push(attestation),
+            Err(e) => {
+                info!(error = ?e, digest = %descriptor.digest, "Skipping attestation because of error");
+            }
+        }
+    }
+
+    if attestations.is_empty() {
+        Err(SigstoreError::SigstoreNoVerifiedLayer)
+    } else {
+        Ok(attestations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::attestation::tests::sign_envelope;
+    use crate::cosign::constants::SIGSTORE_BUNDLE_ANNOTATION;
+    use crate::crypto::SigningScheme;
+    use oci_distribution::client::ImageLayer;
+    use oci_distribution::manifest::{OciDescriptor, OciImageManifest};
+    use serde_json::json;
+
+    const IMAGE_DIGEST: &str =
+        "sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b";
+
+    fn statement(digest: &str) -> Value {
+        json!({
+            "_type": "https://in-toto.io/Statement/v1",
+            "predicateType": "https://example.com/test",
+            "subject": [{"name": "busybox", "digest": {"sha256": digest}}],
+            "predicate": {"result": "PASSED"}
+        })
+    }
+
+    fn manifest(layers: &[&ImageLayer]) -> OciImageManifest {
+        OciImageManifest {
+            layers: layers
+                .iter()
+                .map(|layer| OciDescriptor {
+                    media_type: SIGSTORE_DSSE_MEDIA_TYPE.to_string(),
+                    digest: layer.sha256_digest(),
+                    size: layer.data.len() as i64,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn layer(envelope: &Envelope) -> ImageLayer {
+        ImageLayer::new(
+            serde_json::to_vec(envelope).unwrap(),
+            SIGSTORE_DSSE_MEDIA_TYPE.to_string(),
+            None,
+        )
+    }
+
+    #[test]
+    fn verify_attestations_signed_by_key() {
+        let signer = SigningScheme::default().create_signer().unwrap();
+        let key = signer.to_verification_key().unwrap();
+
+        let digest = IMAGE_DIGEST.strip_prefix("sha256:").unwrap();
+        let valid = layer(&sign_envelope(&signer, &statement(digest)));
+        let other_image = layer(&sign_envelope(&signer, &statement(&"0".repeat(64))));
+        let manifest = manifest(&[&valid, &other_image]);
+        let layers = [valid.clone(), other_image];
+
+        let attestations = build_verified_attestations(
+            &manifest,
+            IMAGE_DIGEST,
+            &layers,
+            Some(&key),
+            None,
+            None,
+            None,
+            SctPolicy::Require,
+        )
+        .unwrap();
+        assert_eq!(attestations.len(), 1);
+        assert_eq!(attestations[0].oci_digest, valid.sha256_digest());
+        assert_eq!(
+            attestations[0].predicate,
+            Predicate::Other(json!({"result": "PASSED"}))
+        );
+
+        let other_key = SigningScheme::default()
+            .create_signer()
+            .unwrap()
+            .to_verification_key()
+            .unwrap();
+        assert!(matches!(
+            build_verified_attestations(
+                &manifest,
+                IMAGE_DIGEST,
+                &layers,
+                Some(&other_key),
+                None,
+                None,
+                None,
+                SctPolicy::Require,
+            ),
+            Err(SigstoreError::SigstoreNoVerifiedLayer)
+        ));
+        // without a key, a trusted certificate is required
+        assert!(build_verified_attestations(
+            &manifest,
+            IMAGE_DIGEST,
+            &layers,
+            None,
+            None,
+            None,
+            None,
+            SctPolicy::Require,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn log_entry_must_refer_to_the_payload() {
+        let signer = SigningScheme::default().create_signer().unwrap();
+        let envelope = sign_envelope(&signer, &statement("abcd"));
+        let payload_hash = hex::encode(Sha256::digest(envelope.decoded_payload().unwrap()));
+
+        let bundle = |hash: &str| Bundle {
+            signed_entry_timestamp: String::new(),
+            payload: crate::cosign::bundle::Payload {
+                body: BASE64_STD_ENGINE.encode(
+                    json!({
+                        "apiVersion": "0.0.1",
+                        "kind": "intoto",
+                        "spec": {"content": {
+                            "hash": {"algorithm": "sha256", "value": "not checked"},
+                            "payloadHash": {"algorithm": "sha256", "value": hash}
+                        }}
+                    })
+                    .to_string(),
+                ),
+                integrated_time: 0,
+                log_index: 0,
+                log_id: String::new(),
+            },
+        };
+        assert!(check_log_entry(&bundle(&payload_hash), &envelope).is_ok());
+        assert!(matches!(
+            check_log_entry(&bundle("abcd"), &envelope),
+            Err(SigstoreError::RekorEntryMismatchError(_))
+        ));
+
+        // bundles that cannot be verified are rejected
+        let layer = layer(&envelope);
+        let mut descriptor = manifest(&[&layer]).layers.remove(0);
+        descriptor.annotations = Some(
+            [(
+                SIGSTORE_BUNDLE_ANNOTATION.to_string(),
+                serde_json::to_string(&bundle(&payload_hash)).unwrap(),
+            )]
+            .into(),
+        );
+        let key = signer.to_verification_key().unwrap();
+        assert!(VerifiedAttestation::new(
+            &descriptor,
+            &layer,
+            "sha256:abcd",
+            Some(&key),
+            Some(&key),
+            None,
+            None,
+            SctPolicy::Require,
+        )
+        .is_err());
+    }
+}
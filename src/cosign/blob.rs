@@ -0,0 +1,244 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signing and verification of blobs, the equivalent of the
+//! `cosign sign-blob` and `cosign verify-blob` commands.
+//!
+//! The signatures are base64 encoded, like the ones written by
+//! `cosign sign-blob --output-signature`. The bundles are the
+//! [`SignedArtifactBundle`] written by `cosign sign-blob --bundle`, hence
+//! the blobs signed by cosign can be verified here and vice versa.
+//!
+//! Keyless signatures are produced by a
+//! [`SigningSession`](crate::cosign::SigningSession). Signatures made with a
+//! long-lived key are produced by the functions of this module:
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::blob::{sign_blob_with_bundle, verify_blob_bundle};
+//! use sigstore::crypto::trusted_root::TrustedRoot;
+//! use sigstore::crypto::SigningScheme;
+//! use sigstore::rekor::apis::configuration::Configuration;
+//! use std::fs::File;
+//! use std::path::Path;
+//!
+//! # async fn doc() -> sigstore::errors::Result<()> {
+//! let signer = SigningScheme::default().create_signer()?;
+//! let bundle = sign_blob_with_bundle(
+//!     &signer,
+//!     &Configuration::default(),
+//!     File::open("artifact.tar.gz")?,
+//! )
+//! .await?;
+//! std::fs::write("artifact.bundle", serde_json::to_string(&bundle)?)?;
+//!
+//! let trusted_root = TrustedRoot::from_file(Path::new("trusted_root.json"))?;
+//! verify_blob_bundle(
+//!     File::open("artifact.tar.gz")?,
+//!     &bundle,
+//!     &trusted_root,
+//!     Some(&signer.to_verification_key()?),
+//! )?;
+//! # Ok(())
+//! # }
+//! ```
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use tracing::debug;
+
+use super::bundle::{Bundle, SignedArtifactBundle};
+use crate::crypto::trusted_root::TrustedRoot;
+use crate::crypto::{CosignVerificationKey, SigStoreSigner, Signature};
+use crate::errors::{Result, SigstoreError};
+use crate::rekor::apis::configuration::Configuration;
+use crate::rekor::apis::entries_api;
+use crate::rekor::models::hashedrekord::{AlgorithmKind, Data, Hash, PublicKey, Spec};
+use crate::rekor::models::proposed_entry::HASHEDREKORD_API_VERSION;
+use crate::rekor::models::{hashedrekord, ProposedEntry};
+
+/// Sign the contents of `blob`, like `cosign sign-blob --key`.
+///
+/// Returns the base64 encoded signature.
+pub fn sign_blob<R: Read>(signer: &SigStoreSigner, blob: R) -> Result<String> {
+    let blob = read_blob(blob)?;
+    Ok(BASE64_STD_ENGINE.encode(signer.sign(&blob)?))
+}
+
+/// Sign the contents of `blob` and upload the signature to Rekor, like
+/// `cosign sign-blob --key --bundle`.
+///
+/// The returned bundle doesn't have a certificate, the signature has to be
+/// verified with the public key of `signer`.
+pub async fn sign_blob_with_bundle<R: Read>(
+    signer: &SigStoreSigner,
+    rekor_config: &Configuration,
+    blob: R,
+) -> Result<SignedArtifactBundle> {
+    let blob = read_blob(blob)?;
+    let signature = BASE64_STD_ENGINE.encode(signer.sign(&blob)?);
+
+    let proposed_entry = ProposedEntry::Hashedrekord {
+        api_version: HASHEDREKORD_API_VERSION.to_string(),
+        spec: Spec::new(
+            hashedrekord::Signature::new(
+                signature.clone(),
+                PublicKey::new(BASE64_STD_ENGINE.encode(signer.public_key_to_pem()?)),
+            ),
+            Data::new(Hash::new(
+                AlgorithmKind::sha256,
+                hex::encode(Sha256::digest(&blob)),
+            )),
+        ),
+    };
+    let entry = entries_api::create_log_entry(rekor_config, proposed_entry)
+        .await
+        .map_err(|e| SigstoreError::RekorClientError(e.to_string()))?;
+    debug!(uuid = %entry.uuid, log_index = entry.log_index, "signature uploaded to Rekor");
+
+    Ok(SignedArtifactBundle {
+        base64_signature: signature,
+        cert: String::new(),
+        rekor_bundle: Bundle::from_log_entry(&entry)?,
+    })
+}
+
+/// Verify the base64 encoded `signature` of `blob`, like
+/// `cosign verify-blob --key --signature`.
+///
+/// The blob is hashed while it is read, see
+/// [`CosignVerificationKey::verify_signature_from_reader`].
+pub fn verify_blob<R: Read>(
+    blob: R,
+    signature: &str,
+    verification_key: &CosignVerificationKey,
+) -> Result<()> {
+    verification_key
+        .verify_signature_from_reader(Signature::Base64Encoded(signature.as_bytes()), blob)
+}
+
+/// Same as [`verify_blob`], with the signature given as raw bytes instead of
+/// being base64 encoded, like the ones already decoded from an annotation.
+pub fn verify_blob_raw<R: Read>(
+    blob: R,
+    signature: &[u8],
+    verification_key: &CosignVerificationKey,
+) -> Result<()> {
+    verification_key.verify_signature_from_reader(Signature::Raw(signature), blob)
+}
+
+/// Verify `blob` with the bundle produced by `cosign sign-blob --bundle`.
+///
+/// The bundles of keyless signatures are verified with the certificate they
+/// hold, see [`SignedArtifactBundle::verify_blob`]: the identity of the
+/// signer still has to be checked by the caller. The bundles of signatures
+/// made with a long-lived key are verified with `verification_key`.
+pub fn verify_blob_bundle<R: Read>(
+    blob: R,
+    bundle: &SignedArtifactBundle,
+    trusted_root: &TrustedRoot,
+    verification_key: Option<&CosignVerificationKey>,
+) -> Result<()> {
+    let blob = read_blob(blob)?;
+    match verification_key {
+        Some(key) => bundle.verify_blob_with_key(&blob, key, trusted_root),
+        None if bundle.cert.is_empty() => Err(SigstoreError::VerificationConstraintError(
+            "the bundle doesn't have a certificate, a verification key is required".to_string(),
+        )),
+        None => bundle.verify_blob(&blob, trusted_root).map(|_| ()),
+    }
+}
+
+fn read_blob<R: Read>(mut blob: R) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    blob.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::bundle::tests::sign_bundle;
+    use crate::crypto::trusted_root::ValidityPeriod;
+    use crate::crypto::SigningScheme;
+
+    const BLOB: &[u8] = b"hello, blob";
+
+    /// A bundle recording `signature` of `BLOB`, signed by `rekor`
+    fn build_bundle(rekor: &SigStoreSigner, signature: &str) -> SignedArtifactBundle {
+        SignedArtifactBundle {
+            base64_signature: signature.to_string(),
+            cert: String::new(),
+            rekor_bundle: sign_bundle(rekor, signature, BLOB),
+        }
+    }
+
+    #[test]
+    fn sign_and_verify_blob() {
+        let signer = SigningScheme::default().create_signer().unwrap();
+        let key = signer.to_verification_key().unwrap();
+
+        let signature = sign_blob(&signer, BLOB).unwrap();
+        assert!(verify_blob(BLOB, &signature, &key).is_ok());
+        assert!(verify_blob(&b"another blob"[..], &signature, &key).is_err());
+
+        let raw_signature = BASE64_STD_ENGINE.decode(&signature).unwrap();
+        assert!(verify_blob_raw(BLOB, &raw_signature, &key).is_ok());
+        assert!(verify_blob_raw(&b"another blob"[..], &raw_signature, &key).is_err());
+    }
+
+    #[test]
+    fn verify_bundle_of_key_signature() {
+        let rekor = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let mut trusted_root = TrustedRoot::new();
+        trusted_root
+            .add_rekor_pub_key(
+                &rekor.public_key_to_pem().unwrap(),
+                ValidityPeriod::always(),
+            )
+            .unwrap();
+
+        let signer = SigningScheme::default().create_signer().unwrap();
+        let key = signer.to_verification_key().unwrap();
+        let bundle = build_bundle(&rekor, &sign_blob(&signer, BLOB).unwrap());
+
+        // cosign omits the certificate of the bundles signed with a key
+        let raw = serde_json::to_value(&bundle).unwrap();
+        assert!(raw.get("cert").is_none());
+        let parsed: SignedArtifactBundle = serde_json::from_value(raw).unwrap();
+        assert_eq!(parsed, bundle);
+
+        assert!(verify_blob_bundle(BLOB, &bundle, &trusted_root, Some(&key)).is_ok());
+        assert!(matches!(
+            verify_blob_bundle(BLOB, &bundle, &trusted_root, None),
+            Err(SigstoreError::VerificationConstraintError(_))
+        ));
+        assert!(matches!(
+            verify_blob_bundle(&b"another blob"[..], &bundle, &trusted_root, Some(&key)),
+            Err(SigstoreError::RekorEntryMismatchError(_))
+        ));
+
+        let other_key = SigningScheme::default()
+            .create_signer()
+            .unwrap()
+            .to_verification_key()
+            .unwrap();
+        assert!(verify_blob_bundle(BLOB, &bundle, &trusted_root, Some(&other_key)).is_err());
+
+        assert!(verify_blob_bundle(BLOB, &bundle, &TrustedRoot::new(), Some(&key)).is_err());
+    }
+}
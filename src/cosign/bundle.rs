@@ -13,11 +13,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
 use olpc_cjson::CanonicalFormatter;
+use pkcs8::der::Decode;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
 use std::cmp::PartialEq;
+use std::convert::TryFrom;
+use x509_cert::Certificate;
 
-use crate::crypto::{CosignVerificationKey, Signature};
+use crate::crypto::{self, trusted_root::TrustedRoot, CosignVerificationKey, Signature};
 use crate::errors::{Result, SigstoreError};
 
 /// Struct that represents the signature bundle as generated by running a
@@ -33,6 +39,8 @@ pub struct SignedArtifactBundle {
     /// of the blob.
     pub base64_signature: String,
     /// Represents the 'cert' field which is a PEM encoded certificate.
+    /// Like cosign, it is empty when the blob has been signed with a key.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub cert: String,
     /// Represents the 'rekorBundle' field.
     pub rekor_bundle: Bundle,
@@ -49,6 +57,69 @@ impl SignedArtifactBundle {
         })?;
         Bundle::verify_bundle(&bundle.rekor_bundle, rekor_pub_key).map(|_| bundle)
     }
+
+    /// Verify that `blob` has been signed by the certificate of the bundle,
+    /// using the trust material of `trusted_root` that was in use when the
+    /// signature was recorded by Rekor.
+    ///
+    /// The following checks are performed:
+    /// * the Rekor bundle has been signed by one of the Rekor keys
+    /// * the Rekor entry refers to the signature of the bundle and to `blob`
+    /// * the certificate has been issued by Fulcio, and was valid when the
+    ///   signature was recorded
+    /// * the signature of `blob` is valid
+    ///
+    /// Returns the DER encoded signing certificate, the identity of the
+    /// signer still has to be checked by the caller.
+    pub fn verify_blob(&self, blob: &[u8], trusted_root: &TrustedRoot) -> Result<Vec<u8>> {
+        let integrated_time = self.rekor_bundle.payload.integrated_time;
+        Bundle::verify_bundle_at(&self.rekor_bundle, trusted_root)?;
+        self.rekor_bundle
+            .verify_entry(&self.base64_signature, blob)?;
+
+        let cert_pem = BASE64_STD_ENGINE.decode(&self.cert)?;
+        let cert_pool = trusted_root
+            .fulcio_cert_pool_at(integrated_time)?
+            .ok_or_else(|| {
+                SigstoreError::TrustedRootError(format!(
+                    "no Fulcio certificate in use at {integrated_time}"
+                ))
+            })?;
+        cert_pool.verify_pem_cert(&cert_pem)?;
+        let cert_der = pem::parse(&cert_pem)?.contents;
+        let cert = Certificate::from_der(&cert_der)
+            .map_err(|e| SigstoreError::X509Error(format!("parse from der: {e}")))?;
+        crypto::certificate::is_trusted(&cert, integrated_time)?;
+
+        let verification_key =
+            CosignVerificationKey::try_from(&cert.tbs_certificate.subject_public_key_info)?;
+        verification_key.verify_signature(
+            Signature::Base64Encoded(self.base64_signature.as_bytes()),
+            blob,
+        )?;
+        Ok(cert_der)
+    }
+
+    /// Verify that `blob` has been signed by `verification_key`, like
+    /// `cosign verify-blob --key --bundle`.
+    ///
+    /// The Rekor bundle is verified with the keys of `trusted_root` that
+    /// were in use when the signature was recorded, and the Rekor entry
+    /// must refer to the signature of the bundle and to `blob`.
+    pub fn verify_blob_with_key(
+        &self,
+        blob: &[u8],
+        verification_key: &CosignVerificationKey,
+        trusted_root: &TrustedRoot,
+    ) -> Result<()> {
+        Bundle::verify_bundle_at(&self.rekor_bundle, trusted_root)?;
+        self.rekor_bundle
+            .verify_entry(&self.base64_signature, blob)?;
+        verification_key.verify_signature(
+            Signature::Base64Encoded(self.base64_signature.as_bytes()),
+            blob,
+        )
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -62,14 +133,46 @@ impl Bundle {
     /// Create a new verified `Bundle`
     ///
     /// **Note well:** The bundle will be returned only if it can be verified
-    /// using the supplied `rekor_pub_key` public key.
-    pub(crate) fn new_verified(raw: &str, rekor_pub_key: &CosignVerificationKey) -> Result<Self> {
+    /// using the supplied `rekor_pub_key` public key. This is done offline,
+    /// Rekor is not contacted. The caller must then ensure the bundle refers
+    /// to the signature being verified, see [`Bundle::verify_entry`].
+    pub fn new_verified(raw: &str, rekor_pub_key: &CosignVerificationKey) -> Result<Self> {
         let bundle: Bundle = serde_json::from_str(raw).map_err(|e| {
             SigstoreError::UnexpectedError(format!("Cannot parse bundle |{raw}|: {e:?}"))
         })?;
         Self::verify_bundle(&bundle, rekor_pub_key).map(|_| bundle)
     }
 
+    /// Create a new verified `Bundle`, using the Rekor keys of `trusted_root`
+    /// that were in use when the entry was integrated into the log.
+    ///
+    /// **Note well:** The bundle will be returned only if it can be verified
+    /// by one of these keys.
+    pub(crate) fn new_verified_at(raw: &str, trusted_root: &TrustedRoot) -> Result<Self> {
+        let bundle: Bundle = serde_json::from_str(raw).map_err(|e| {
+            SigstoreError::UnexpectedError(format!("Cannot parse bundle |{raw}|: {e:?}"))
+        })?;
+        Self::verify_bundle_at(&bundle, trusted_root).map(|_| bundle)
+    }
+
+    /// Verify a `Bundle` with the keys of `trusted_root` that were used by
+    /// the log of the entry when the entry was integrated into it.
+    pub(crate) fn verify_bundle_at(bundle: &Bundle, trusted_root: &TrustedRoot) -> Result<()> {
+        let integrated_time = bundle.payload.integrated_time;
+        let verified = trusted_root
+            .rekor_pub_keys_for_log_at(&bundle.payload.log_id, integrated_time)
+            .into_iter()
+            .any(|key| Self::verify_bundle(bundle, key).is_ok());
+        if verified {
+            Ok(())
+        } else {
+            Err(SigstoreError::TrustedRootError(format!(
+                "bundle not signed by any of the keys in use at {integrated_time} by the log {}",
+                bundle.payload.log_id
+            )))
+        }
+    }
+
     /// Verify a `Bundle`.
     ///
     /// **Note well:** The bundle will be returned only if it can be verified
@@ -92,6 +195,49 @@ impl Bundle {
         )?;
         Ok(())
     }
+
+    /// Ensure the Rekor entry of the bundle records the base64 encoded
+    /// `signature` of `payload`, like the `hashedrekord` and `rekord` entries
+    /// created by cosign.
+    ///
+    /// The signed entry timestamp proves that Rekor recorded the entry, not
+    /// that the entry is about a given signature: without this check, the
+    /// bundle of any other signature would be accepted.
+    pub fn verify_entry(&self, signature: &str, payload: &[u8]) -> Result<()> {
+        let body: Value = serde_json::from_slice(&BASE64_STD_ENGINE.decode(&self.payload.body)?)?;
+
+        if body.pointer("/spec/signature/content") != Some(&Value::from(signature)) {
+            return Err(SigstoreError::RekorEntryMismatchError(
+                "the entry doesn't refer to the signature".to_string(),
+            ));
+        }
+        let digest = hex::encode(Sha256::digest(payload));
+        if body.pointer("/spec/data/hash/value") != Some(&Value::from(digest)) {
+            return Err(SigstoreError::RekorEntryMismatchError(
+                "the entry doesn't refer to the digest of the payload".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Create the `Bundle` of an entry returned by Rekor, like the one
+    /// obtained when uploading a signature.
+    ///
+    /// **Note well:** the bundle is not verified.
+    #[cfg(feature = "rekor")]
+    pub fn from_log_entry(entry: &crate::rekor::models::log_entry::LogEntry) -> Result<Self> {
+        let body = entry.canonicalized_body()?;
+
+        Ok(Bundle {
+            signed_entry_timestamp: entry.verification.signed_entry_timestamp.clone(),
+            payload: Payload {
+                body: BASE64_STD_ENGINE.encode(body),
+                integrated_time: entry.integrated_time,
+                log_index: entry.log_index,
+                log_id: entry.log_i_d.clone(),
+            },
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -105,12 +251,41 @@ pub struct Payload {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use serde_json::json;
 
     use crate::cosign::tests::get_rekor_public_key;
-    use crate::crypto::SigningScheme;
+    use crate::crypto::{SigStoreSigner, SigningScheme};
+
+    /// A bundle of the `hashedrekord` entry recording the base64 encoded
+    /// `signature` of `payload`, signed by `rekor`
+    pub(crate) fn sign_bundle(rekor: &SigStoreSigner, signature: &str, payload: &[u8]) -> Bundle {
+        let body = json!({
+            "apiVersion": "0.0.1",
+            "kind": "hashedrekord",
+            "spec": {
+                "data": { "hash": { "algorithm": "sha256", "value": hex::encode(Sha256::digest(payload)) } },
+                "signature": { "content": signature, "publicKey": { "content": "not relevant" } }
+            }
+        });
+        let payload = Payload {
+            body: BASE64_STD_ENGINE.encode(body.to_string()),
+            integrated_time: 1_700_000_000,
+            log_index: 1,
+            // the ID of a log is the digest of its key
+            log_id: hex::encode(Sha256::digest(rekor.public_key_to_der().unwrap())),
+        };
+        let mut canonical = Vec::new();
+        let mut ser =
+            serde_json::Serializer::with_formatter(&mut canonical, CanonicalFormatter::new());
+        payload.serialize(&mut ser).unwrap();
+
+        Bundle {
+            signed_entry_timestamp: BASE64_STD_ENGINE.encode(rekor.sign(&canonical).unwrap()),
+            payload,
+        }
+    }
 
     fn build_correct_bundle() -> String {
         let bundle_json = json!({
@@ -151,6 +326,59 @@ OSWS1X9vPavpiQOoTTGC0xX57OojUadxF1cdQmrsiReWg2Wn4FneJfa8xw==
         assert!(bundle.is_err());
     }
 
+    #[test]
+    fn bundle_new_verified_at_uses_key_valid_at_integration_time() {
+        use crate::cosign::tests::REKOR_PUB_KEY;
+        use crate::crypto::trusted_root::ValidityPeriod;
+
+        let rotated_key = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap()
+            .to_sigstore_keypair()
+            .unwrap()
+            .public_key_to_pem()
+            .unwrap();
+        let bundle_json = build_correct_bundle();
+
+        // the bundle was integrated at 1634714179, before the key rotation
+        let mut trusted_root = TrustedRoot::new();
+        trusted_root
+            .add_rekor_pub_key(
+                REKOR_PUB_KEY,
+                ValidityPeriod {
+                    start: 1600000000,
+                    end: Some(1640000000),
+                },
+            )
+            .unwrap();
+        trusted_root
+            .add_rekor_pub_key(
+                &rotated_key,
+                ValidityPeriod {
+                    start: 1640000000,
+                    end: None,
+                },
+            )
+            .unwrap();
+        assert!(Bundle::new_verified_at(&bundle_json, &trusted_root).is_ok());
+
+        // the old key was retired before the bundle was integrated
+        let mut trusted_root = TrustedRoot::new();
+        trusted_root
+            .add_rekor_pub_key(
+                REKOR_PUB_KEY,
+                ValidityPeriod {
+                    start: 1600000000,
+                    end: Some(1630000000),
+                },
+            )
+            .unwrap();
+        assert!(matches!(
+            Bundle::new_verified_at(&bundle_json, &trusted_root),
+            Err(SigstoreError::TrustedRootError(_))
+        ));
+    }
+
     #[test]
     fn signedartifactbundle_new_verified_success() {
         // Bundle as generated by running the following command, and taking the
@@ -164,5 +392,44 @@ OSWS1X9vPavpiQOoTTGC0xX57OojUadxF1cdQmrsiReWg2Wn4FneJfa8xw==
         assert!(result.is_ok());
         let bundle = result.unwrap();
         assert_eq!(bundle.rekor_bundle.payload.log_index, 7810348);
+
+        // the Rekor entry refers to another artifact
+        let mut trusted_root = TrustedRoot::new();
+        trusted_root
+            .add_rekor_pub_key(
+                crate::cosign::tests::REKOR_PUB_KEY,
+                crate::crypto::trusted_root::ValidityPeriod::always(),
+            )
+            .unwrap();
+        assert!(matches!(
+            bundle.verify_blob(b"not the signed artifact", &trusted_root),
+            Err(SigstoreError::RekorEntryMismatchError(_))
+        ));
+
+        // the Rekor entry must be signed by a key in use at integration time
+        assert!(matches!(
+            bundle.verify_blob(b"not the signed artifact", &TrustedRoot::new()),
+            Err(SigstoreError::TrustedRootError(_))
+        ));
+    }
+
+    #[test]
+    fn bundle_must_record_the_signature() {
+        let rekor = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let bundle = sign_bundle(&rekor, "c2lnbmF0dXJl", b"payload");
+        let raw = serde_json::to_string(&bundle).unwrap();
+
+        let verified = Bundle::new_verified(&raw, &rekor.to_verification_key().unwrap()).unwrap();
+        assert!(verified.verify_entry("c2lnbmF0dXJl", b"payload").is_ok());
+        assert!(matches!(
+            verified.verify_entry("YW5vdGhlciBzaWduYXR1cmU=", b"payload"),
+            Err(SigstoreError::RekorEntryMismatchError(_))
+        ));
+        assert!(matches!(
+            verified.verify_entry("c2lnbmF0dXJl", b"another payload"),
+            Err(SigstoreError::RekorEntryMismatchError(_))
+        ));
     }
 }
@@ -14,22 +14,39 @@
 // limitations under the License.
 
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::ops::Add;
+use std::sync::Arc;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use oci_distribution::manifest::OCI_IMAGE_MEDIA_TYPE;
 use tracing::warn;
 
-use super::constants::{SIGSTORE_OCI_MEDIA_TYPE, SIGSTORE_SIGNATURE_ANNOTATION};
-use super::{CosignCapabilities, SignatureLayer};
-use crate::cosign::signature_layers::build_signature_layers;
+use super::attestation::verification::{build_verified_attestations, VerifiedAttestation};
+use super::constants::SIGSTORE_OCI_MEDIA_TYPE;
+use super::sbom::Sbom;
+use super::{AttachmentKind, CosignCapabilities, DownloadedLayer, SignatureLayer};
+use crate::cosign::download::build_downloaded_layers;
+use crate::cosign::signature_layers::{
+    build_signature_layers_concurrently, candidate_layers, verify_candidate_layers,
+};
+use crate::cosign::verification_cache::{VerificationCache, VerifiedImage};
+use crate::cosign::verification_constraint::VerificationConstraint;
 use crate::crypto::CosignVerificationKey;
-use crate::registry::{Auth, OciReference, PushResponse};
+use crate::registry::progress::ProgressTracker;
+use crate::registry::{
+    Auth, OciReference, ProgressListener, PushResponse, Referrer, TransferDirection,
+};
 use crate::{
-    crypto::certificate_pool::CertificatePool,
-    errors::{Result, SigstoreError},
+    crypto::{
+        certificate_pool::CertificatePool,
+        certificate_transparency::SctPolicy,
+        trusted_root::{FreshnessPolicy, TrustedRoot},
+    },
+    errors::{LayerVerificationFailure, Result, SigstoreError, VerificationErrors},
 };
-use tracing::debug;
+use tracing::{debug, info};
 
 /// Used to generate an empty [OCI Configuration](https://github.com/opencontainers/image-spec/blob/v1.0.0/config.md).
 pub const CONFIG_DATA: &str = "{}";
@@ -41,6 +58,13 @@ pub struct Client {
     pub(crate) registry_client: Box<dyn crate::registry::ClientCapabilities>,
     pub(crate) rekor_pub_key: Option<CosignVerificationKey>,
     pub(crate) fulcio_cert_pool: Option<CertificatePool>,
+    pub(crate) trusted_root: Option<TrustedRoot>,
+    pub(crate) sct_policy: SctPolicy,
+    pub(crate) freshness: Option<(FreshnessPolicy, DateTime<Utc>)>,
+    pub(crate) progress_listener: Option<Arc<dyn ProgressListener>>,
+    pub(crate) signature_repository: Option<OciReference>,
+    pub(crate) verification_concurrency: usize,
+    pub(crate) verification_cache: Option<VerificationCache>,
 }
 
 #[async_trait(?Send)]
@@ -50,14 +74,14 @@ impl CosignCapabilities for Client {
         image: &OciReference,
         auth: &Auth,
     ) -> Result<(OciReference, String)> {
-        let manifest_digest = self
-            .registry_client
-            .fetch_manifest_digest(&image.oci_reference, &auth.into())
-            .await?;
+        let manifest_digest = self.resolve_digest(image, auth).await?;
 
+        // signatures can be stored inside of another repository, like
+        // cosign does when `COSIGN_REPOSITORY` is set
+        let location = self.signature_repository.as_ref().unwrap_or(image);
         let reference = OciReference::with_tag(
-            image.registry().to_string(),
-            image.repository().to_string(),
+            location.registry().to_string(),
+            location.repository().to_string(),
             manifest_digest.replace(':', "-").add(".sig"),
         );
 
@@ -70,23 +94,24 @@ impl CosignCapabilities for Client {
         source_image_digest: &str,
         cosign_image: &OciReference,
     ) -> Result<Vec<SignatureLayer>> {
-        let (manifest, layers) = self.fetch_manifest_and_layers(auth, cosign_image).await?;
-        let image_manifest = match manifest {
-            oci_distribution::manifest::OciManifest::Image(im) => im,
-            oci_distribution::manifest::OciManifest::ImageIndex(_) => {
-                return Err(SigstoreError::RegistryPullManifestError {
-                    image: cosign_image.to_string(),
-                    error: "Found a OciImageIndex instead of a OciImageManifest".to_string(),
-                });
-            }
-        };
+        if let Some((policy, fetched_at)) = &self.freshness {
+            policy.check(*fetched_at)?;
+        }
+
+        let (manifest, layers) = self
+            .fetch_manifest_and_layers(auth, cosign_image, vec![SIGSTORE_OCI_MEDIA_TYPE])
+            .await?;
+        let image_manifest = image_manifest(manifest, cosign_image)?;
 
-        let sl = build_signature_layers(
+        let sl = build_signature_layers_concurrently(
             &image_manifest,
             source_image_digest,
             &layers,
             self.rekor_pub_key.as_ref(),
             self.fulcio_cert_pool.as_ref(),
+            self.trusted_root.as_ref(),
+            self.sct_policy,
+            self.verification_concurrency,
         )?;
 
         debug!(signature_layers=?sl, ?cosign_image, "trusted signature layers");
@@ -100,51 +125,529 @@ impl CosignCapabilities for Client {
         target_reference: &OciReference,
         signature_layers: Vec<SignatureLayer>,
     ) -> Result<PushResponse> {
-        let layers: Vec<oci_distribution::client::ImageLayer> = signature_layers
+        let layers = signature_layers
             .iter()
-            .filter_map(|sl| {
-                match serde_json::to_vec(&sl.simple_signing) {
-                    Ok(data) => {
-                        let annotations = match &sl.signature {
-                            Some(sig) => [(SIGSTORE_SIGNATURE_ANNOTATION.into(), sig.clone())].into(),
-                            None => HashMap::new(),
-                        };
-                        let image_layer = oci_distribution::client::ImageLayer::new(data, SIGSTORE_OCI_MEDIA_TYPE.into(), Some(annotations));
-                        Some(image_layer)
-                    }
-                    Err(e) => {
-                        warn!(error = ?e, signaturelayer = ?sl, "Skipping SignatureLayer because serialization failed");
-                        None
-                    }
+            .filter_map(|sl| match sl.to_image_layer() {
+                Ok(image_layer) => Some(image_layer),
+                Err(e) => {
+                    warn!(error = ?e, signaturelayer = ?sl, "Skipping SignatureLayer because serialization failed");
+                    None
                 }
             })
             .collect();
 
+        self.push_layers(annotations, auth, target_reference, layers)
+            .await
+    }
+
+    async fn download(
+        &mut self,
+        auth: &Auth,
+        image: &OciReference,
+        kind: AttachmentKind,
+    ) -> Result<Vec<DownloadedLayer>> {
+        let (_, manifest_digest) = self.triangulate(image, auth).await?;
+        let reference = kind.reference(image, &manifest_digest);
+
+        let (manifest, layers) = self
+            .fetch_manifest_and_layers(auth, &reference, kind.media_types())
+            .await?;
+        let image_manifest = image_manifest(manifest, &reference)?;
+
+        let downloaded = build_downloaded_layers(&image_manifest, &layers);
+        debug!(%kind, ?reference, layers = downloaded.len(), "downloaded layers");
+        Ok(downloaded)
+    }
+
+    async fn verify_attestations(
+        &mut self,
+        auth: &Auth,
+        image: &OciReference,
+        verification_key: Option<&CosignVerificationKey>,
+    ) -> Result<Vec<VerifiedAttestation>> {
+        if let Some((policy, fetched_at)) = &self.freshness {
+            policy.check(*fetched_at)?;
+        }
+
+        let (_, manifest_digest) = self.triangulate(image, auth).await?;
+        // attestations are stored next to the signatures
+        let location = self.signature_repository.clone();
+        let reference = AttachmentKind::Attestation
+            .reference(location.as_ref().unwrap_or(image), &manifest_digest);
+
+        let (manifest, layers) = self
+            .fetch_manifest_and_layers(auth, &reference, AttachmentKind::Attestation.media_types())
+            .await?;
+        let image_manifest = image_manifest(manifest, &reference)?;
+
+        let attestations = build_verified_attestations(
+            &image_manifest,
+            &manifest_digest,
+            &layers,
+            verification_key,
+            self.rekor_pub_key.as_ref(),
+            self.fulcio_cert_pool.as_ref(),
+            self.trusted_root.as_ref(),
+            self.sct_policy,
+        )?;
+        debug!(
+            ?reference,
+            attestations = attestations.len(),
+            "verified attestations"
+        );
+        Ok(attestations)
+    }
+}
+
+/// Internal helper that ensures the given manifest is an image manifest
+pub(crate) fn image_manifest(
+    manifest: oci_distribution::manifest::OciManifest,
+    reference: &OciReference,
+) -> Result<oci_distribution::manifest::OciImageManifest> {
+    match manifest {
+        oci_distribution::manifest::OciManifest::Image(im) => Ok(im),
+        oci_distribution::manifest::OciManifest::ImageIndex(_) => {
+            Err(SigstoreError::RegistryPullManifestError {
+                image: reference.to_string(),
+                error: "Found a OciImageIndex instead of a OciImageManifest".to_string(),
+            })
+        }
+    }
+}
+
+impl Client {
+    /// Returns the digest of the manifest referenced by `image`.
+    ///
+    /// Tags are resolved by the registry, with a `HEAD` request accepting
+    /// both image manifests and manifest lists: the digest of a
+    /// multi-platform image is the one of its manifest list, like for
+    /// `cosign sign`. The digest of references that already have one is
+    /// returned without contacting the registry.
+    pub async fn resolve_digest(&mut self, image: &OciReference, auth: &Auth) -> Result<String> {
+        if let Some(digest) = image.digest() {
+            return Ok(digest.to_string());
+        }
+
+        let digest = self
+            .registry_client
+            .fetch_manifest_digest(&image.oci_reference, &auth.into())
+            .await?;
+        debug!(?image, %digest, "resolved image digest");
+        Ok(digest)
+    }
+
+    /// Returns the objects of the given `kind` attached to `image` through
+    /// the referrers API of the OCI distribution spec 1.1, like cosign does
+    /// with `--registry-referrers-mode=oci-1-1`.
+    ///
+    /// The registries that do not support the API are queried with the
+    /// referrers tag schema instead: the type of the referrers found this way
+    /// is unknown, hence they are all returned.
+    pub async fn referrers(
+        &mut self,
+        auth: &Auth,
+        image: &OciReference,
+        kind: AttachmentKind,
+    ) -> Result<Vec<Referrer>> {
+        let digest = self.resolve_digest(image, auth).await?;
+        self.registry_client
+            .fetch_referrers(
+                &image.pinned(&digest).oci_reference,
+                &auth.into(),
+                Some(kind.artifact_type()),
+            )
+            .await
+    }
+
+    /// Returns the trusted signature layers of `image`, like
+    /// [`CosignCapabilities::trusted_signature_layers`].
+    ///
+    /// The signatures are discovered through the referrers API, see
+    /// [`Client::referrers`]. When no signature refers to the image, they
+    /// are read from the `sha256-<digest>.sig` tag used by default by cosign.
+    pub async fn discover_signature_layers(
+        &mut self,
+        auth: &Auth,
+        image: &OciReference,
+    ) -> Result<Vec<SignatureLayer>> {
+        if let Some((policy, fetched_at)) = &self.freshness {
+            policy.check(*fetched_at)?;
+        }
+
+        let digest = self.resolve_digest(image, auth).await?;
+        let referrers = self
+            .fetch_referrer_layers(auth, image, &digest, AttachmentKind::Signature)
+            .await?;
+        if referrers.is_empty() {
+            debug!(?image, "no signature referrer, using the signature tag");
+            let location = self.signature_repository.clone();
+            let cosign_image =
+                AttachmentKind::Signature.reference(location.as_ref().unwrap_or(image), &digest);
+            return self
+                .trusted_signature_layers(auth, &digest, &cosign_image)
+                .await;
+        }
+
+        let mut signature_layers = Vec::new();
+        for (manifest, layers) in referrers {
+            signature_layers.extend(build_signature_layers_concurrently(
+                &manifest,
+                &digest,
+                &layers,
+                self.rekor_pub_key.as_ref(),
+                self.fulcio_cert_pool.as_ref(),
+                self.trusted_root.as_ref(),
+                self.sct_policy,
+                self.verification_concurrency,
+            )?);
+        }
+        debug!(
+            ?signature_layers,
+            ?image,
+            "trusted signature layers of the referrers"
+        );
+        Ok(signature_layers)
+    }
+
+    /// Returns the verified attestations of `image`, like
+    /// [`CosignCapabilities::verify_attestations`].
+    ///
+    /// The attestations are discovered through the referrers API, see
+    /// [`Client::referrers`]. When no attestation refers to the image, they
+    /// are read from the `sha256-<digest>.att` tag used by default by cosign.
+    pub async fn discover_attestations(
+        &mut self,
+        auth: &Auth,
+        image: &OciReference,
+        verification_key: Option<&CosignVerificationKey>,
+    ) -> Result<Vec<VerifiedAttestation>> {
+        if let Some((policy, fetched_at)) = &self.freshness {
+            policy.check(*fetched_at)?;
+        }
+
+        let digest = self.resolve_digest(image, auth).await?;
+        let referrers = self
+            .fetch_referrer_layers(auth, image, &digest, AttachmentKind::Attestation)
+            .await?;
+        if referrers.is_empty() {
+            debug!(?image, "no attestation referrer, using the attestation tag");
+            return self
+                .verify_attestations(auth, image, verification_key)
+                .await;
+        }
+
+        let mut attestations = Vec::new();
+        for (manifest, layers) in referrers {
+            attestations.extend(build_verified_attestations(
+                &manifest,
+                &digest,
+                &layers,
+                verification_key,
+                self.rekor_pub_key.as_ref(),
+                self.fulcio_cert_pool.as_ref(),
+                self.trusted_root.as_ref(),
+                self.sct_policy,
+            )?);
+        }
+        debug!(
+            ?image,
+            attestations = attestations.len(),
+            "verified attestations of the referrers"
+        );
+        Ok(attestations)
+    }
+
+    /// Attach `sbom` to `image`, like `cosign attach sbom`.
+    ///
+    /// The SBOM is pushed to the `sha256-<digest>.sbom` tag, replacing the
+    /// SBOM already attached to the image, if any. The referrers API is not
+    /// used: the client of `oci-distribution` cannot set the subject of the
+    /// pushed manifests.
+    pub async fn attach_sbom(
+        &mut self,
+        auth: &Auth,
+        image: &OciReference,
+        sbom: &Sbom,
+    ) -> Result<PushResponse> {
+        let digest = self.resolve_digest(image, auth).await?;
+        let reference = AttachmentKind::Sbom.reference(image, &digest);
+        debug!(?reference, format = %sbom.format, "attaching SBOM");
+
+        self.push_layers(None, auth, &reference, vec![sbom.to_image_layer()])
+            .await
+    }
+
+    /// Returns the SBOMs attached to `image`, like `cosign download sbom`.
+    ///
+    /// The SBOMs are discovered through the referrers API, see
+    /// [`Client::referrers`]. When no SBOM refers to the image, they are read
+    /// from the `sha256-<digest>.sbom` tag.
+    ///
+    /// **Note well:** the SBOMs are not verified in any way.
+    pub async fn fetch_sboms(&mut self, auth: &Auth, image: &OciReference) -> Result<Vec<Sbom>> {
+        let digest = self.resolve_digest(image, auth).await?;
+        let mut referrers = self
+            .fetch_referrer_layers(auth, image, &digest, AttachmentKind::Sbom)
+            .await?;
+        if referrers.is_empty() {
+            debug!(?image, "no SBOM referrer, using the SBOM tag");
+            let reference = AttachmentKind::Sbom.reference(image, &digest);
+            let (manifest, layers) = self
+                .fetch_manifest_and_layers(auth, &reference, AttachmentKind::Sbom.media_types())
+                .await?;
+            referrers.push((image_manifest(manifest, &reference)?, layers));
+        }
+
+        let mut sboms = Vec::new();
+        for (manifest, layers) in referrers {
+            for layer in build_downloaded_layers(&manifest, &layers) {
+                sboms.push(Sbom::try_from(layer)?);
+            }
+        }
+        debug!(?image, sboms = sboms.len(), "fetched SBOMs");
+        Ok(sboms)
+    }
+
+    /// Fetch the manifests and the layers of the referrers of the manifest
+    /// `digest` holding objects of the given `kind`. The referrers that
+    /// cannot be fetched are skipped.
+    async fn fetch_referrer_layers(
+        &mut self,
+        auth: &Auth,
+        image: &OciReference,
+        digest: &str,
+        kind: AttachmentKind,
+    ) -> Result<
+        Vec<(
+            oci_distribution::manifest::OciImageManifest,
+            Vec<oci_distribution::client::ImageLayer>,
+        )>,
+    > {
+        let referrers = self
+            .registry_client
+            .fetch_referrers(
+                &image.pinned(digest).oci_reference,
+                &auth.into(),
+                Some(kind.artifact_type()),
+            )
+            .await?;
+
+        let media_types = kind.media_types();
+        let mut fetched = Vec::new();
+        for referrer in referrers {
+            let reference = image.pinned(&referrer.digest);
+            let (manifest, layers) = match self
+                .fetch_manifest_and_layers(auth, &reference, media_types.clone())
+                .await
+                .and_then(|(manifest, layers)| Ok((image_manifest(manifest, &reference)?, layers)))
+            {
+                Ok(fetched) => fetched,
+                Err(e) => {
+                    warn!(error = ?e, ?reference, "Skipping referrer that cannot be fetched");
+                    continue;
+                }
+            };
+
+            // the referrers found through the tag schema have no type
+            if manifest
+                .layers
+                .iter()
+                .any(|layer| media_types.contains(&layer.media_type.as_str()))
+            {
+                fetched.push((manifest, layers));
+            }
+        }
+        Ok(fetched)
+    }
+
+    /// Returns the first trusted signature layer of `cosign_image` that
+    /// satisfies all the `constraints`, see
+    /// [`CosignCapabilities::trusted_signature_layers`] for how the layers are
+    /// trusted.
+    ///
+    /// The layers are verified concurrently when the client has been built
+    /// with [`ClientBuilder::with_verification_concurrency`](crate::cosign::ClientBuilder::with_verification_concurrency),
+    /// and the remaining ones are skipped as soon as a satisfying layer is
+    /// found. Unlike [`verify_constraints`](crate::cosign::verify_constraints),
+    /// a single layer must satisfy all the constraints.
+    ///
+    /// Returns [`SigstoreError::VerificationErrors`] when no layer satisfies
+    /// them, recording why each layer failed.
+    pub async fn verify_first_signature_layer(
+        &mut self,
+        auth: &Auth,
+        source_image_digest: &str,
+        cosign_image: &OciReference,
+        constraints: &[Box<dyn VerificationConstraint>],
+    ) -> Result<SignatureLayer> {
+        if let Some((policy, fetched_at)) = &self.freshness {
+            policy.check(*fetched_at)?;
+        }
+
+        let (manifest, layers) = self
+            .fetch_manifest_and_layers(auth, cosign_image, vec![SIGSTORE_OCI_MEDIA_TYPE])
+            .await?;
+        let image_manifest = image_manifest(manifest, cosign_image)?;
+
+        let candidates = candidate_layers(&image_manifest, &layers);
+        let mut found = None;
+        let mut errors = VerificationErrors::default();
+        verify_candidate_layers(
+            &candidates,
+            source_image_digest,
+            self.rekor_pub_key.as_ref(),
+            self.fulcio_cert_pool.as_ref(),
+            self.trusted_root.as_ref(),
+            self.sct_policy,
+            self.verification_concurrency,
+            |index, signature_layer| {
+                let layer_digest = &candidates[index].0.digest;
+                let signature_layer = match signature_layer {
+                    Ok(sl) => sl,
+                    Err(e) => {
+                        info!(error = ?e, "Skipping OCI layer because of error");
+                        errors.push(layer_digest, vec![LayerVerificationFailure::Untrusted(e)]);
+                        return false;
+                    }
+                };
+
+                let failures: Vec<LayerVerificationFailure> = constraints
+                    .iter()
+                    .filter_map(|c| match c.verify(&signature_layer) {
+                        Ok(true) => None,
+                        Ok(false) => Some(LayerVerificationFailure::UnsatisfiedConstraint(
+                            format!("{c:?}"),
+                        )),
+                        Err(e) => {
+                            warn!(error = ?e, constraint = ?c, "Skipping layer because constraint verification returned an error");
+                            Some(LayerVerificationFailure::ConstraintError {
+                                constraint: format!("{c:?}"),
+                                error: e,
+                            })
+                        }
+                    })
+                    .collect();
+                if failures.is_empty() {
+                    found = Some(signature_layer);
+                    true
+                } else {
+                    errors.push(layer_digest, failures);
+                    false
+                }
+            },
+        );
+
+        debug!(signature_layer=?found, ?cosign_image, "first satisfying signature layer");
+        found.ok_or(SigstoreError::VerificationErrors(errors))
+    }
+
+    /// Verify that `image` has a signature satisfying all the
+    /// `constraints`, like [`Client::verify_first_signature_layer`] does.
+    ///
+    /// When the client has a [`VerificationCache`], see
+    /// [`ClientBuilder::with_verification_cache`](crate::cosign::ClientBuilder::with_verification_cache),
+    /// a cached verification of the image digest by the same constraints
+    /// is returned without reaching Rekor nor the registry, which is not
+    /// reached at all when `image` holds a digest. Successful verifications
+    /// are added to the cache.
+    pub async fn verify_image(
+        &mut self,
+        auth: &Auth,
+        image: &OciReference,
+        constraints: &[Box<dyn VerificationConstraint>],
+    ) -> Result<VerifiedImage> {
+        let policy_hash = VerificationCache::policy_hash(constraints);
+        if let (Some(cache), Some(image_digest)) = (&self.verification_cache, image.digest()) {
+            if let Some(verified_image) = cache.get(image_digest, &policy_hash)? {
+                debug!(?image, "verification found in cache");
+                return Ok(verified_image);
+            }
+        }
+
+        let (cosign_image, image_digest) = self.triangulate(image, auth).await?;
+        if let Some(cache) = &self.verification_cache {
+            if let Some(verified_image) = cache.get(&image_digest, &policy_hash)? {
+                debug!(?image, "verification found in cache");
+                return Ok(verified_image);
+            }
+        }
+
+        let signature_layer = self
+            .verify_first_signature_layer(auth, &image_digest, &cosign_image, constraints)
+            .await?;
+        let verified_image = VerifiedImage {
+            image_digest,
+            signature_digest: signature_layer.oci_digest,
+            verified_at: Utc::now(),
+        };
+        if let Some(cache) = &self.verification_cache {
+            cache.insert(&policy_hash, &verified_image)?;
+        }
+        Ok(verified_image)
+    }
+
+    /// Internal helper method used to push the given layers, inside of an
+    /// image manifest, to `target_reference`
+    pub(crate) async fn push_layers(
+        &mut self,
+        annotations: Option<HashMap<String, String>>,
+        auth: &Auth,
+        target_reference: &OciReference,
+        layers: Vec<oci_distribution::client::ImageLayer>,
+    ) -> Result<PushResponse> {
         // TODO: Do we need to support OCI Image Configuration?
         let config =
             oci_distribution::client::Config::oci_v1(CONFIG_DATA.as_bytes().to_vec(), None);
         let mut manifest =
             oci_distribution::manifest::OciImageManifest::build(&layers[..], &config, annotations);
         manifest.media_type = Some(OCI_IMAGE_MEDIA_TYPE.to_string());
-        self.registry_client
+
+        let total_bytes =
+            layers.iter().map(|l| l.data.len() as u64).sum::<u64>() + config.data.len() as u64;
+        let mut progress = ProgressTracker::new(
+            self.progress_listener.as_ref(),
+            target_reference.whole(),
+            TransferDirection::Push,
+            total_bytes,
+        );
+        progress.start()?;
+
+        let response = self
+            .registry_client
             .push(
                 &target_reference.oci_reference,
                 &layers[..],
-                config,
+                config.clone(),
                 &auth.into(),
                 Some(manifest),
             )
-            .await
-            .map(|r| r.into())
+            .await?;
+
+        // the image has been pushed at once: the listener can no longer
+        // abort the transfer, its errors are only logged
+        let transferred = layers
+            .iter()
+            .map(|layer| (layer.sha256_digest(), layer.data.len()))
+            .chain(std::iter::once((config.sha256_digest(), config.data.len())));
+        for (digest, bytes) in transferred {
+            if let Err(e) = progress.layer_transferred(&digest, bytes as u64) {
+                warn!(error = ?e, reference = ?target_reference, "progress listener failed after the push");
+            }
+        }
+
+        Ok(response.into())
     }
-}
 
-impl Client {
-    /// Internal helper method used to fetch data from an OCI registry
-    async fn fetch_manifest_and_layers(
+    /// Internal helper method used to fetch data from an OCI registry.
+    ///
+    /// The size of the layers is announced to the progress listener, which
+    /// can refuse the transfer, before any layer is pulled. The layers are
+    /// then pulled one by one.
+    pub(crate) async fn fetch_manifest_and_layers(
         &mut self,
         auth: &Auth,
         cosign_image: &OciReference,
+        accepted_media_types: Vec<&str>,
     ) -> Result<(
         oci_distribution::manifest::OciManifest,
         Vec<oci_distribution::client::ImageLayer>,
@@ -155,28 +658,57 @@ impl Client {
             .registry_client
             .pull_manifest(&cosign_image.oci_reference, &oci_auth)
             .await?;
-        let image_data = self
-            .registry_client
-            .pull(
-                &cosign_image.oci_reference,
-                &oci_auth,
-                vec![SIGSTORE_OCI_MEDIA_TYPE],
-            )
-            .await?;
+        let descriptors = match &manifest {
+            oci_distribution::manifest::OciManifest::Image(im) => im.layers.clone(),
+            oci_distribution::manifest::OciManifest::ImageIndex(_) => {
+                return Err(SigstoreError::RegistryPullManifestError {
+                    image: cosign_image.to_string(),
+                    error: "Found a OciImageIndex instead of a OciImageManifest".to_string(),
+                })
+            }
+        };
+        if let Some(descriptor) = descriptors
+            .iter()
+            .find(|d| !accepted_media_types.contains(&d.media_type.as_str()))
+        {
+            return Err(SigstoreError::RegistryPullError {
+                image: cosign_image.whole(),
+                error: format!("Incompatible layer media type: {}", descriptor.media_type),
+            });
+        }
+
+        let total_bytes = descriptors.iter().map(|d| d.size.max(0) as u64).sum();
+        let mut progress = ProgressTracker::new(
+            self.progress_listener.as_ref(),
+            cosign_image.whole(),
+            TransferDirection::Pull,
+            total_bytes,
+        );
+        progress.start()?;
+
+        let mut layers = Vec::with_capacity(descriptors.len());
+        for descriptor in &descriptors {
+            let layer = self
+                .registry_client
+                .pull_layer(&cosign_image.oci_reference, &oci_auth, descriptor)
+                .await?;
+            progress.layer_transferred(&descriptor.digest, layer.data.len() as u64)?;
+            layers.push(layer);
+        }
 
-        Ok((manifest, image_data.layers))
+        Ok((manifest, layers))
     }
 }
 
 #[cfg(feature = "mock-client")]
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use crate::cosign::tests::{get_fulcio_cert_pool, REKOR_PUB_KEY};
     use crate::crypto::SigningScheme;
     use crate::mock_client::test::MockOciClient;
 
-    fn build_test_client(mock_client: MockOciClient) -> Client {
+    pub(crate) fn build_test_client(mock_client: MockOciClient) -> Client {
         let rekor_pub_key =
             CosignVerificationKey::from_pem(REKOR_PUB_KEY.as_bytes(), &SigningScheme::default())
                 .expect("Cannot create CosignVerificationKey");
@@ -185,6 +717,13 @@ mod tests {
             registry_client: Box::new(mock_client),
             rekor_pub_key: Some(rekor_pub_key),
             fulcio_cert_pool: Some(get_fulcio_cert_pool()),
+            trusted_root: None,
+            sct_policy: SctPolicy::Require,
+            freshness: None,
+            progress_listener: None,
+            signature_repository: None,
+            verification_concurrency: 1,
+            verification_cache: None,
         }
     }
 
@@ -209,4 +748,339 @@ mod tests {
         assert!(reference.is_ok());
         assert_eq!(reference.unwrap(), (expected_image, image_digest));
     }
+
+    #[tokio::test]
+    async fn triangulate_with_signature_repository() {
+        let image = "docker.io/busybox:latest".parse().unwrap();
+        let image_digest =
+            String::from("sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b");
+        let expected_image = "ghcr.io/octocat/signatures:sha256-f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b.sig".parse().unwrap();
+        let mock_client = MockOciClient {
+            fetch_manifest_digest_response: Some(Ok(image_digest.clone())),
+            pull_response: None,
+            pull_manifest_response: None,
+            push_response: None,
+        };
+        let mut cosign_client = build_test_client(mock_client);
+        cosign_client.signature_repository = Some("ghcr.io/octocat/signatures".parse().unwrap());
+
+        let reference = cosign_client
+            .triangulate(&image, &crate::registry::Auth::Anonymous)
+            .await;
+
+        assert_eq!(reference.unwrap(), (expected_image, image_digest));
+    }
+
+    #[tokio::test]
+    async fn resolve_digest() {
+        let digest =
+            String::from("sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b");
+        let mock_client = MockOciClient {
+            fetch_manifest_digest_response: Some(Ok(digest.clone())),
+            pull_response: None,
+            pull_manifest_response: None,
+            push_response: None,
+        };
+        let mut cosign_client = build_test_client(mock_client);
+        let image = "docker.io/busybox:latest".parse().unwrap();
+        assert_eq!(
+            cosign_client
+                .resolve_digest(&image, &crate::registry::Auth::Anonymous)
+                .await
+                .unwrap(),
+            digest
+        );
+
+        // pinned references are not resolved by the registry
+        let mock_client = MockOciClient {
+            fetch_manifest_digest_response: None,
+            pull_response: None,
+            pull_manifest_response: None,
+            push_response: None,
+        };
+        let mut cosign_client = build_test_client(mock_client);
+        let image = format!("docker.io/busybox:latest@{digest}")
+            .parse()
+            .unwrap();
+        assert_eq!(
+            cosign_client
+                .resolve_digest(&image, &crate::registry::Auth::Anonymous)
+                .await
+                .unwrap(),
+            digest
+        );
+    }
+
+    #[tokio::test]
+    async fn referrers_from_tag_schema() {
+        use oci_distribution::manifest::{ImageIndexEntry, OciImageIndex, OciManifest};
+
+        let digest =
+            String::from("sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b");
+        let index = OciImageIndex {
+            schema_version: 2,
+            media_type: Some(crate::registry::referrers::OCI_IMAGE_INDEX_MEDIA_TYPE.to_string()),
+            manifests: vec![ImageIndexEntry {
+                media_type: OCI_IMAGE_MEDIA_TYPE.to_string(),
+                digest: String::from(
+                    "sha256:5f481572d088dc4023afb35fced9530ced3d9b03bf7299c6f492163cb9f0452e",
+                ),
+                size: 527,
+                platform: None,
+                annotations: None,
+            }],
+            annotations: None,
+        };
+        let mock_client = MockOciClient {
+            fetch_manifest_digest_response: Some(Ok(digest.clone())),
+            pull_response: None,
+            pull_manifest_response: Some(Ok((OciManifest::ImageIndex(index), digest))),
+            push_response: None,
+        };
+        let mut cosign_client = build_test_client(mock_client);
+        let image = "docker.io/busybox:latest".parse().unwrap();
+
+        let referrers = cosign_client
+            .referrers(
+                &crate::registry::Auth::Anonymous,
+                &image,
+                AttachmentKind::Signature,
+            )
+            .await
+            .unwrap();
+        assert_eq!(referrers.len(), 1);
+        assert_eq!(
+            referrers[0].digest,
+            "sha256:5f481572d088dc4023afb35fced9530ced3d9b03bf7299c6f492163cb9f0452e"
+        );
+        // the type of the referrers is unknown with the tag schema
+        assert_eq!(referrers[0].artifact_type, None);
+    }
+
+    #[tokio::test]
+    async fn attach_and_fetch_sbom() {
+        use crate::cosign::sbom::SbomFormat;
+        use oci_distribution::client::{Config, ImageData};
+        use oci_distribution::manifest::{OciDescriptor, OciImageManifest, OciManifest};
+
+        let image = "docker.io/busybox:latest".parse().unwrap();
+        let image_digest =
+            String::from("sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b");
+        let sbom =
+            Sbom::new(br#"{"bomFormat":"CycloneDX","specVersion":"1.5","version":1}"#.to_vec())
+                .unwrap();
+        let layer = sbom.to_image_layer();
+        let manifest = OciImageManifest {
+            layers: vec![OciDescriptor {
+                media_type: layer.media_type.clone(),
+                digest: layer.sha256_digest(),
+                size: layer.data.len() as i64,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let mock_client = MockOciClient {
+            fetch_manifest_digest_response: Some(Ok(image_digest.clone())),
+            pull_response: Some(Ok(ImageData {
+                layers: vec![layer],
+                digest: None,
+                config: Config::oci_v1(CONFIG_DATA.as_bytes().to_vec(), None),
+                manifest: None,
+            })),
+            // no referrers index: the manifest of the SBOM is returned instead
+            pull_manifest_response: Some(Ok((OciManifest::Image(manifest), image_digest))),
+            push_response: Some(Ok(oci_distribution::client::PushResponse {
+                config_url: "config".to_string(),
+                manifest_url: "manifest".to_string(),
+            })),
+        };
+        let mut cosign_client = build_test_client(mock_client);
+
+        let response = cosign_client
+            .attach_sbom(&crate::registry::Auth::Anonymous, &image, &sbom)
+            .await
+            .unwrap();
+        assert_eq!(response.manifest_url, "manifest");
+
+        let sboms = cosign_client
+            .fetch_sboms(&crate::registry::Auth::Anonymous, &image)
+            .await
+            .unwrap();
+        assert_eq!(sboms, vec![sbom]);
+        assert_eq!(sboms[0].format, SbomFormat::CycloneDxJson);
+    }
+
+    #[tokio::test]
+    async fn transfer_budget_is_enforced_before_pulling() {
+        use crate::registry::TransferBudget;
+        use oci_distribution::manifest::{OciDescriptor, OciImageManifest, OciManifest};
+
+        let image: OciReference = "docker.io/library/busybox:sha256-f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b.sig".parse().unwrap();
+        let manifest = OciImageManifest {
+            layers: vec![OciDescriptor {
+                media_type: SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+                digest: "sha256:5f481572d088dc4023afb35fced9530ced3d9b03bf7299c6f492163cb9f0452e"
+                    .to_string(),
+                size: 2048,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        // pulling the layers would fail, no pull response is provided
+        let mock_client = MockOciClient {
+            fetch_manifest_digest_response: None,
+            pull_response: None,
+            pull_manifest_response: Some(Ok((
+                OciManifest::Image(manifest),
+                "sha256:manifest".to_string(),
+            ))),
+            push_response: None,
+        };
+        let mut cosign_client = build_test_client(mock_client);
+        cosign_client.progress_listener = Some(Arc::new(TransferBudget { max_bytes: 1024 }));
+
+        let result = cosign_client
+            .fetch_manifest_and_layers(
+                &crate::registry::Auth::Anonymous,
+                &image,
+                vec![SIGSTORE_OCI_MEDIA_TYPE],
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(SigstoreError::RegistryTransferBudgetExceeded { size: 2048, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn stale_trust_material_is_rejected() {
+        use chrono::Duration;
+
+        let image: OciReference = "docker.io/library/busybox:sha256-f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b.sig".parse().unwrap();
+        let mock_client = MockOciClient {
+            fetch_manifest_digest_response: None,
+            pull_response: None,
+            pull_manifest_response: None,
+            push_response: None,
+        };
+        let mut cosign_client = build_test_client(mock_client);
+        cosign_client.freshness = Some((
+            FreshnessPolicy::new(Duration::days(1)),
+            Utc::now() - Duration::days(2),
+        ));
+
+        let error = cosign_client
+            .trusted_signature_layers(
+                &crate::registry::Auth::Anonymous,
+                "sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b",
+                &image,
+            )
+            .await
+            .expect_err("stale trust material accepted");
+        assert!(matches!(error, SigstoreError::StaleTrustMaterial { .. }));
+    }
+
+    #[tokio::test]
+    async fn download_attestations_without_verification() {
+        use crate::cosign::constants::{SIGSTORE_DSSE_MEDIA_TYPE, SIGSTORE_SIGNATURE_ANNOTATION};
+        use oci_distribution::client::{Config, ImageData, ImageLayer};
+        use oci_distribution::manifest::{OciDescriptor, OciImageManifest, OciManifest};
+
+        let image = "docker.io/busybox:latest".parse().unwrap();
+        let image_digest =
+            String::from("sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b");
+
+        let layer = ImageLayer::new(
+            br#"{"payloadType":"application/vnd.in-toto+json"}"#.to_vec(),
+            SIGSTORE_DSSE_MEDIA_TYPE.to_string(),
+            None,
+        );
+        let manifest = OciImageManifest {
+            layers: vec![OciDescriptor {
+                media_type: SIGSTORE_DSSE_MEDIA_TYPE.to_string(),
+                digest: layer.sha256_digest(),
+                size: layer.data.len() as i64,
+                annotations: Some(
+                    [(
+                        SIGSTORE_SIGNATURE_ANNOTATION.to_string(),
+                        "not verified".to_string(),
+                    )]
+                    .into(),
+                ),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let mock_client = MockOciClient {
+            fetch_manifest_digest_response: Some(Ok(image_digest)),
+            pull_response: Some(Ok(ImageData {
+                layers: vec![layer.clone()],
+                digest: None,
+                config: Config::oci_v1(CONFIG_DATA.as_bytes().to_vec(), None),
+                manifest: None,
+            })),
+            pull_manifest_response: Some(Ok((
+                OciManifest::Image(manifest),
+                "sha256:manifest".to_string(),
+            ))),
+            push_response: None,
+        };
+        let mut cosign_client = build_test_client(mock_client);
+
+        let downloaded = cosign_client
+            .download(
+                &crate::registry::Auth::Anonymous,
+                &image,
+                AttachmentKind::Attestation,
+            )
+            .await
+            .expect("download failed");
+
+        assert_eq!(downloaded.len(), 1);
+        assert_eq!(downloaded[0].data, layer.data);
+        assert_eq!(downloaded[0].signature(), Some("not verified"));
+    }
+
+    #[tokio::test]
+    async fn verify_image_from_cache() {
+        let image_digest =
+            "sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b";
+        let image: OciReference = format!("docker.io/busybox@{image_digest}").parse().unwrap();
+        let mock_client = MockOciClient {
+            fetch_manifest_digest_response: None,
+            pull_response: None,
+            pull_manifest_response: None,
+            push_response: None,
+        };
+        let mut cosign_client = build_test_client(mock_client);
+        let cache = VerificationCache::in_memory(std::time::Duration::from_secs(60));
+        cosign_client.verification_cache = Some(cache.clone());
+
+        // the mock client fails every request, hence the registry is not
+        // reached when the verification is cached
+        let constraints: Vec<Box<dyn VerificationConstraint>> = Vec::new();
+        assert!(cosign_client
+            .verify_image(&crate::registry::Auth::Anonymous, &image, &constraints)
+            .await
+            .is_err());
+
+        let verified_image = VerifiedImage {
+            image_digest: image_digest.to_string(),
+            signature_digest: "sha256:a1b2".to_string(),
+            verified_at: Utc::now(),
+        };
+        cache
+            .insert(
+                &VerificationCache::policy_hash(&constraints),
+                &verified_image,
+            )
+            .unwrap();
+        let cached = cosign_client
+            .verify_image(&crate::registry::Auth::Anonymous, &image, &constraints)
+            .await
+            .expect("the verification is not cached");
+        assert_eq!(cached, verified_image);
+    }
 }
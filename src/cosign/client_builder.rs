@@ -13,13 +13,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use chrono::{DateTime, Utc};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::info;
 
 use super::client::Client;
+use super::verification_cache::VerificationCache;
+use crate::cache::CacheStorage;
 use crate::crypto::SigningScheme;
-use crate::crypto::{certificate_pool::CertificatePool, CosignVerificationKey};
+use crate::crypto::{
+    certificate_pool::CertificatePool,
+    certificate_transparency::SctPolicy,
+    revocation::{RevocationChecker, RevocationPolicy},
+    trusted_root::{FreshnessPolicy, TrustedRoot},
+    CosignVerificationKey,
+};
 use crate::errors::Result;
-use crate::registry::{Certificate, ClientConfig};
+use crate::registry::recording::{Recorder, Recording, RecordingClient, ReplayClient};
+use crate::registry::referrers::ReferrersApi;
+use crate::registry::StorageCachingClient;
+use crate::registry::{Certificate, ClientConfig, OciReference, ProgressListener};
 
 /// A builder that generates Client objects.
 ///
@@ -36,6 +51,11 @@ use crate::registry::{Certificate, ClientConfig};
 /// Fulcio integration can be enabled by specifying Fulcio's certificate.
 /// This can be provided via the [`ClientBuilder::with_fulcio_cert`] method.
 ///
+/// The certificates issued by Fulcio are trusted only once their SCT has
+/// been verified with the keys of the CT logs of a
+/// [trusted root](ClientBuilder::with_trusted_root). This requirement can be
+/// dropped via the [`ClientBuilder::with_sct_policy`] method.
+///
 /// > Note well: the [`tuf`](crate::tuf) module provides helper structs and methods
 /// > to obtain this data from the official TUF repository of the Sigstore project.
 ///
@@ -49,11 +69,33 @@ use crate::registry::{Certificate, ClientConfig};
 /// the [`ClientBuilder::enable_registry_caching`] method.
 ///
 /// Each cached entry will automatically expire after 60 seconds.
+///
+/// The responses can also be cached inside of a [`CacheStorage`], which can be
+/// shared between several processes. This is enabled via the
+/// [`ClientBuilder::with_cache_storage`] method.
+///
+/// ## Progress reporting
+///
+/// A [`ProgressListener`] can be registered via the
+/// [`ClientBuilder::with_progress_listener`] method. The listener is notified
+/// about the amount of data exchanged with the registry and can abort
+/// transfers, for example by using a [`TransferBudget`](crate::registry::TransferBudget).
 #[derive(Default)]
 pub struct ClientBuilder {
     oci_client_config: ClientConfig,
     rekor_pub_key: Option<String>,
     fulcio_certs: Vec<Certificate>,
+    trusted_root: Option<TrustedRoot>,
+    sct_policy: SctPolicy,
+    freshness: Option<(FreshnessPolicy, DateTime<Utc>)>,
+    recorder: Option<Recorder>,
+    replay: Option<Recording>,
+    progress_listener: Option<Arc<dyn ProgressListener>>,
+    signature_repository: Option<String>,
+    verification_concurrency: Option<usize>,
+    verification_cache: Option<VerificationCache>,
+    revocation: Option<(Arc<dyn RevocationChecker>, RevocationPolicy)>,
+    cache_storage: Option<(Arc<dyn CacheStorage>, Duration)>,
     #[cfg(feature = "cached-client")]
     enable_registry_caching: bool,
 }
@@ -66,6 +108,13 @@ impl ClientBuilder {
         self
     }
 
+    /// Cache the data returned from remote OCI registries inside of
+    /// `storage`, each entry expires after `ttl`.
+    pub fn with_cache_storage(mut self, storage: Arc<dyn CacheStorage>, ttl: Duration) -> Self {
+        self.cache_storage = Some((storage, ttl));
+        self
+    }
+
     /// Specify the public key used by Rekor.
     ///
     /// The public key can be obtained by using the helper methods under the
@@ -109,6 +158,38 @@ impl ClientBuilder {
         self
     }
 
+    /// Specify the trust material used by Sigstore over the time, together
+    /// with its validity windows.
+    ///
+    /// This allows old signatures to be verified with the Rekor key and the
+    /// Fulcio certificates that were in use when they were produced, even
+    /// after these have been rotated.
+    ///
+    /// When provided, it takes precedence over the values given to
+    /// [`ClientBuilder::with_rekor_pub_key`] and
+    /// [`ClientBuilder::with_fulcio_certs`].
+    pub fn with_trusted_root(mut self, trusted_root: TrustedRoot) -> Self {
+        self.trusted_root = Some(trusted_root);
+        self
+    }
+
+    /// Optional - reject verification once the trust material is older than
+    /// allowed by `policy`.
+    ///
+    /// `fetched_at` is when the Rekor and Fulcio material given to the builder
+    /// has been obtained, for example via
+    /// [`SigstoreRepository::fetched_at`](crate::tuf::SigstoreRepository::fetched_at).
+    /// Long-running processes are expected to build a new client with fresh
+    /// material before the policy kicks in.
+    pub fn with_freshness_policy(
+        mut self,
+        policy: FreshnessPolicy,
+        fetched_at: DateTime<Utc>,
+    ) -> Self {
+        self.freshness = Some((policy, fetched_at));
+        self
+    }
+
     /// Optional - the configuration to be used by the OCI client.
     ///
     /// This can be used when dealing with registries that are not using
@@ -118,7 +199,97 @@ impl ClientBuilder {
         self
     }
 
-    pub fn build(self) -> Result<Client> {
+    /// Optional - a listener notified about the progress of the transfers
+    /// performed against the OCI registry.
+    pub fn with_progress_listener(mut self, listener: Arc<dyn ProgressListener>) -> Self {
+        self.progress_listener = Some(listener);
+        self
+    }
+
+    /// Optional - record the trust material and all the registry responses
+    /// used by the client, see [`recording`](crate::registry::recording).
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Optional - serve all the registry requests from `recording`, without
+    /// reaching the network. The trust material found inside of the recording
+    /// replaces the one given to the builder.
+    pub fn with_replay(mut self, recording: Recording) -> Self {
+        self.replay = Some(recording);
+        self
+    }
+
+    /// Optional - the repository where the signatures are stored, instead
+    /// of the repository of the signed images. This is the equivalent of
+    /// cosign's `COSIGN_REPOSITORY` environment variable.
+    ///
+    /// `repository` is a repository name, like `ghcr.io/octocat/signatures`
+    pub fn with_signature_repository(mut self, repository: &str) -> Self {
+        self.signature_repository = Some(repository.to_string());
+        self
+    }
+
+    /// Optional - verify up to `limit` signature layers at the same time,
+    /// using that many threads. By default the layers are verified one
+    /// after the other.
+    ///
+    /// This speeds up the verification of the images having many
+    /// signatures, like the ones signed by several signers or across key
+    /// rotations. See [`Client::verify_first_signature_layer`] to stop at
+    /// the first satisfying signature.
+    pub fn with_verification_concurrency(mut self, limit: usize) -> Self {
+        self.verification_concurrency = Some(limit.max(1));
+        self
+    }
+
+    /// Optional - remember the successful verifications done by
+    /// [`Client::verify_image`] inside of `cache`, to skip the registry and
+    /// Rekor when the same image is verified again with the same policy
+    pub fn with_verification_cache(mut self, cache: VerificationCache) -> Self {
+        self.verification_cache = Some(cache);
+        self
+    }
+
+    /// Optional - check the revocation status of the certificates issued by
+    /// Fulcio with `checker`, see [`CertificatePool::with_revocation_checker`].
+    /// Useful with private Fulcio deployments issuing long-lived
+    /// certificates.
+    pub fn with_revocation_checker(
+        mut self,
+        checker: Arc<dyn RevocationChecker>,
+        policy: RevocationPolicy,
+    ) -> Self {
+        self.revocation = Some((checker, policy));
+        self
+    }
+
+    /// Optional - whether the SCT of the certificates issued by Fulcio has
+    /// to be verified, defaults to [`SctPolicy::Require`].
+    ///
+    /// The SCT is verified with the keys of the CT logs of the
+    /// [trusted root](ClientBuilder::with_trusted_root). Without them, the
+    /// certificates are rejected unless `policy` is [`SctPolicy::Skip`].
+    pub fn with_sct_policy(mut self, policy: SctPolicy) -> Self {
+        self.sct_policy = policy;
+        self
+    }
+
+    pub fn build(mut self) -> Result<Client> {
+        if let Some(recording) = &self.replay {
+            if let Some(key) = recording.rekor_pub_key() {
+                self.rekor_pub_key = Some(key.to_string());
+            }
+            let fulcio_certs = recording.fulcio_certs()?;
+            if !fulcio_certs.is_empty() {
+                self.fulcio_certs = fulcio_certs;
+            }
+        }
+        if let Some(recorder) = &self.recorder {
+            recorder.set_trust_material(self.rekor_pub_key.as_deref(), &self.fulcio_certs);
+        }
+
         let rekor_pub_key = match self.rekor_pub_key {
             None => {
                 info!("Rekor public key not provided. Rekor integration disabled");
@@ -134,12 +305,22 @@ impl ClientBuilder {
             info!("No Fulcio cert has been provided. Fulcio integration disabled");
             None
         } else {
-            let cert_pool = CertificatePool::from_certificates(&self.fulcio_certs)?;
+            let mut cert_pool = CertificatePool::from_certificates(&self.fulcio_certs)?;
+            if let Some((checker, policy)) = self.revocation {
+                cert_pool = cert_pool.with_revocation_checker(checker, policy);
+            }
             Some(cert_pool)
         };
 
+        let signature_repository = self
+            .signature_repository
+            .as_deref()
+            .map(OciReference::from_str)
+            .transpose()?;
+
         let oci_client =
             oci_distribution::client::Client::new(self.oci_client_config.clone().into());
+        let referrers_api = ReferrersApi::new(&self.oci_client_config)?;
 
         let registry_client: Box<dyn crate::registry::ClientCapabilities> = {
             cfg_if::cfg_if! {
@@ -147,24 +328,54 @@ impl ClientBuilder {
                     if self.enable_registry_caching {
                         Box::new(crate::registry::OciCachingClient {
                             registry_client: oci_client,
+                            referrers_api,
                         }) as Box<dyn crate::registry::ClientCapabilities>
                     } else {
                         Box::new(crate::registry::OciClient {
                             registry_client: oci_client,
+                            referrers_api,
                         }) as Box<dyn crate::registry::ClientCapabilities>
                     }
                 } else {
                     Box::new(crate::registry::OciClient {
                         registry_client: oci_client,
+                        referrers_api,
                     }) as Box<dyn crate::registry::ClientCapabilities>
                 }
             }
         };
 
+        let registry_client = match self.cache_storage {
+            Some((storage, ttl)) => Box::new(StorageCachingClient {
+                inner: registry_client,
+                storage,
+                ttl,
+            }),
+            None => registry_client,
+        };
+
+        let registry_client = match (self.replay, self.recorder) {
+            (Some(recording), _) => {
+                Box::new(ReplayClient { recording }) as Box<dyn crate::registry::ClientCapabilities>
+            }
+            (None, Some(recorder)) => Box::new(RecordingClient {
+                inner: registry_client,
+                recorder,
+            }),
+            (None, None) => registry_client,
+        };
+
         Ok(Client {
             registry_client,
             rekor_pub_key,
             fulcio_cert_pool,
+            trusted_root: self.trusted_root,
+            sct_policy: self.sct_policy,
+            freshness: self.freshness,
+            progress_listener: self.progress_listener,
+            signature_repository,
+            verification_concurrency: self.verification_concurrency.unwrap_or(1),
+            verification_cache: self.verification_cache,
         })
     }
 }
@@ -0,0 +1,51 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use crate::{
+    cosign::countersign::{countersignature_target, COUNTERSIGNATURE_ANNOTATION},
+    cosign::SignatureLayer,
+    errors::Result,
+};
+
+use super::{AnnotationMarker, Constraint};
+
+/// Marks a [`SignatureLayer`] as the countersignature of another one.
+///
+/// The marker must be applied before the layer is signed, see the
+/// [`countersign`](crate::cosign::countersign) module.
+#[derive(Debug)]
+pub struct CountersignatureMarker {
+    target: String,
+}
+
+impl CountersignatureMarker {
+    /// Create a marker for the countersignature of `original`, which must
+    /// be signed already
+    pub fn new(original: &SignatureLayer) -> Result<Self> {
+        Ok(Self {
+            target: countersignature_target(original)?,
+        })
+    }
+}
+
+impl Constraint for CountersignatureMarker {
+    fn add_constraint(&self, signature_layer: &mut SignatureLayer) -> Result<bool> {
+        let annotations: HashMap<String, String> =
+            [(COUNTERSIGNATURE_ANNOTATION.to_string(), self.target.clone())].into();
+        AnnotationMarker::new(annotations).add_constraint(signature_layer)
+    }
+}
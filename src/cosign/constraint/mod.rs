@@ -69,5 +69,8 @@ pub trait Constraint: std::fmt::Debug {
 pub mod annotation;
 pub use annotation::AnnotationMarker;
 
+pub mod countersign;
+pub use countersign::CountersignatureMarker;
+
 pub mod signature;
 pub use self::signature::PrivateKeySigner;
@@ -0,0 +1,243 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Countersignatures, used by two-stage release approval workflows.
+//!
+//! A countersignature is a regular cosign signature of the image whose
+//! payload references another signature (and its Rekor bundle, if any). For
+//! example, the release pipeline signs an image, then the security team
+//! approves the release by countersigning that signature. Both signatures
+//! are stored next to each other.
+//!
+//! The countersignature is created by applying a
+//! [`CountersignatureMarker`](crate::cosign::constraint::CountersignatureMarker)
+//! before signing the layer:
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::constraint::{Constraint, CountersignatureMarker, PrivateKeySigner};
+//! use sigstore::cosign::SignatureLayer;
+//! use sigstore::crypto::SigningScheme;
+//! use sigstore::registry::OciReference;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! # let original: SignatureLayer = unimplemented!();
+//! # let image: OciReference = unimplemented!();
+//! # let manifest_digest = "sha256:...";
+//! let mut countersignature = SignatureLayer::new_unsigned(&image, manifest_digest)?;
+//! CountersignatureMarker::new(&original)?.add_constraint(&mut countersignature)?;
+//! let signer = SigningScheme::default().create_signer()?;
+//! PrivateKeySigner::new_with_signer(signer).add_constraint(&mut countersignature)?;
+//! // push `countersignature` together with `original`
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! At verification time, [`verify_countersigned`] requires a trusted
+//! signature satisfying the constraint of the original signer, countersigned
+//! by a trusted signature satisfying the constraint of the approver.
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use super::verification_constraint::VerificationConstraint;
+use super::SignatureLayer;
+use crate::errors::{Result, SigstoreError};
+
+/// The annotation of the Simple Signing payload holding the reference to
+/// the countersigned signature
+pub const COUNTERSIGNATURE_ANNOTATION: &str = "dev.sigstore.cosign/countersigns";
+
+/// Compute the value that identifies the signature of `layer`, and its Rekor
+/// bundle when available, inside of a countersignature.
+///
+/// This is the SHA-256 of the raw signature, followed by the Signed Entry
+/// Timestamp of the bundle.
+pub fn countersignature_target(layer: &SignatureLayer) -> Result<String> {
+    let signature = layer.signature.as_ref().ok_or_else(|| {
+        SigstoreError::CountersignatureError("the layer is not signed".to_string())
+    })?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(BASE64_STD_ENGINE.decode(signature).map_err(|e| {
+        SigstoreError::CountersignatureError(format!("invalid signature encoding: {e}"))
+    })?);
+    if let Some(bundle) = &layer.bundle {
+        hasher.update(
+            BASE64_STD_ENGINE
+                .decode(&bundle.signed_entry_timestamp)
+                .map_err(|e| {
+                    SigstoreError::CountersignatureError(format!(
+                        "invalid signed entry timestamp encoding: {e}"
+                    ))
+                })?,
+        );
+    }
+    Ok(format!("sha256:{:x}", hasher.finalize()))
+}
+
+/// The target of the countersignature held by `layer`, if any
+fn countersigned_target(layer: &SignatureLayer) -> Option<&str> {
+    layer
+        .simple_signing
+        .optional
+        .as_ref()
+        .and_then(|o| o.extra.get(COUNTERSIGNATURE_ANNOTATION))
+        .and_then(Value::as_str)
+}
+
+/// Ensure `signature_layers` contain a signature satisfying `original`,
+/// countersigned by a signature satisfying `countersigner`.
+///
+/// The layers are expected to be trusted, like the ones returned by
+/// [`CosignCapabilities::trusted_signature_layers`](crate::cosign::CosignCapabilities::trusted_signature_layers).
+/// The countersignature must be about the same image of the original
+/// signature.
+pub fn verify_countersigned(
+    signature_layers: &[SignatureLayer],
+    original: &dyn VerificationConstraint,
+    countersigner: &dyn VerificationConstraint,
+) -> Result<()> {
+    let countersignatures: Vec<&SignatureLayer> = signature_layers
+        .iter()
+        .filter(|sl| countersigned_target(sl).is_some())
+        .filter(|sl| countersigner.verify(sl).unwrap_or(false))
+        .collect();
+
+    let originals = signature_layers
+        .iter()
+        .filter(|sl| countersigned_target(sl).is_none())
+        .filter(|sl| original.verify(sl).unwrap_or(false));
+
+    for sl in originals {
+        let target = match countersignature_target(sl) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        let countersigned = countersignatures.iter().any(|cs| {
+            countersigned_target(cs) == Some(target.as_str())
+                && cs.simple_signing.critical.image.docker_manifest_digest
+                    == sl.simple_signing.critical.image.docker_manifest_digest
+        });
+        if countersigned {
+            return Ok(());
+        }
+    }
+
+    Err(SigstoreError::CountersignatureError(
+        "no trusted signature has been countersigned by a trusted countersigner".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::constraint::{Constraint, CountersignatureMarker, PrivateKeySigner};
+    use crate::cosign::signature_layers::tests::build_correct_signature_layer_without_bundle;
+    use crate::cosign::verification_constraint::PublicKeyVerifier;
+    use crate::crypto::tests::PUBLIC_KEY;
+    use crate::crypto::SigningScheme;
+    use crate::registry::OciReference;
+
+    fn countersign(original: &SignatureLayer) -> (SignatureLayer, PublicKeyVerifier) {
+        let image: OciReference = original
+            .simple_signing
+            .critical
+            .identity
+            .docker_reference
+            .parse()
+            .unwrap();
+        let mut countersignature = SignatureLayer::new_unsigned(
+            &image,
+            &original
+                .simple_signing
+                .critical
+                .image
+                .docker_manifest_digest,
+        )
+        .unwrap();
+        assert!(CountersignatureMarker::new(original)
+            .unwrap()
+            .add_constraint(&mut countersignature)
+            .unwrap());
+
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let public_key = signer
+            .to_sigstore_keypair()
+            .unwrap()
+            .public_key_to_pem()
+            .unwrap();
+        assert!(PrivateKeySigner::new_with_signer(signer)
+            .add_constraint(&mut countersignature)
+            .unwrap());
+
+        (
+            countersignature,
+            PublicKeyVerifier::try_from(public_key.as_bytes()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn verify_countersigned_signature() {
+        let (original, _) = build_correct_signature_layer_without_bundle();
+        let original_verifier = PublicKeyVerifier::try_from(PUBLIC_KEY.as_bytes()).unwrap();
+        let (countersignature, countersigner) = countersign(&original);
+
+        assert!(verify_countersigned(
+            &[original.clone(), countersignature.clone()],
+            &original_verifier,
+            &countersigner
+        )
+        .is_ok());
+
+        // the approval alone is not enough
+        assert!(
+            verify_countersigned(&[original.clone()], &original_verifier, &countersigner).is_err()
+        );
+
+        // the countersignature must be produced by the approver
+        let (_, other_countersigner) = countersign(&original);
+        assert!(verify_countersigned(
+            &[original, countersignature],
+            &original_verifier,
+            &other_countersigner
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn countersignature_is_bound_to_the_original_signature() {
+        let (original, _) = build_correct_signature_layer_without_bundle();
+        let original_verifier = PublicKeyVerifier::try_from(PUBLIC_KEY.as_bytes()).unwrap();
+        let (countersignature, countersigner) = countersign(&original);
+
+        let mut other = original.clone();
+        other.signature = Some(BASE64_STD_ENGINE.encode(b"another signature"));
+        assert_ne!(
+            countersignature_target(&original).unwrap(),
+            countersignature_target(&other).unwrap()
+        );
+        // `other` doesn't pass the original verifier, hence it cannot be
+        // used to satisfy the policy
+        assert!(verify_countersigned(
+            &[other, countersignature],
+            &original_verifier,
+            &countersigner
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,280 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Structs used to download the Sigstore objects attached to an image,
+//! without verifying them.
+//!
+//! This is the library equivalent of the `cosign download signature`,
+//! `cosign download attestation` and `cosign download sbom` commands.
+//! The data returned by [`CosignCapabilities::download`](crate::cosign::CosignCapabilities::download)
+//! is **not** verified in any way, and must be considered untrusted. It is meant
+//! to be consumed by forensics and debugging tools.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use super::constants::{
+    SIGSTORE_BUNDLE_ANNOTATION, SIGSTORE_CERT_ANNOTATION, SIGSTORE_CHAIN_ANNOTATION,
+    SIGSTORE_DSSE_MEDIA_TYPE, SIGSTORE_OCI_MEDIA_TYPE, SIGSTORE_SBOM_MEDIA_TYPES,
+    SIGSTORE_SIGNATURE_ANNOTATION,
+};
+use super::payload::simple_signing::SimpleSigning;
+use crate::errors::{Result, SigstoreError};
+use crate::registry::OciReference;
+
+/// The kinds of objects cosign attaches to an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachmentKind {
+    /// Signatures, stored under the `sha256-<digest>.sig` tag
+    Signature,
+    /// Attestations, stored under the `sha256-<digest>.att` tag
+    Attestation,
+    /// SBOMs, stored under the `sha256-<digest>.sbom` tag
+    Sbom,
+}
+
+impl AttachmentKind {
+    /// The suffix of the tag used by cosign to store this kind of object
+    pub fn tag_suffix(&self) -> &'static str {
+        match self {
+            AttachmentKind::Signature => "sig",
+            AttachmentKind::Attestation => "att",
+            AttachmentKind::Sbom => "sbom",
+        }
+    }
+
+    /// The artifact type of the manifests attached by cosign to the image
+    /// through the OCI 1.1 referrers API
+    pub fn artifact_type(&self) -> &'static str {
+        match self {
+            AttachmentKind::Signature => "application/vnd.dev.cosign.artifact.sig.v1+json",
+            AttachmentKind::Attestation => "application/vnd.dev.cosign.artifact.att.v1+json",
+            AttachmentKind::Sbom => "application/vnd.dev.cosign.artifact.sbom.v1+json",
+        }
+    }
+
+    /// The media types of the layers holding this kind of object
+    pub fn media_types(&self) -> Vec<&'static str> {
+        match self {
+            AttachmentKind::Signature => vec![SIGSTORE_OCI_MEDIA_TYPE],
+            AttachmentKind::Attestation => vec![SIGSTORE_DSSE_MEDIA_TYPE],
+            AttachmentKind::Sbom => SIGSTORE_SBOM_MEDIA_TYPES.to_vec(),
+        }
+    }
+
+    /// Calculate the location where cosign stores this kind of object for the
+    /// image with the given manifest digest.
+    pub fn reference(&self, image: &OciReference, manifest_digest: &str) -> OciReference {
+        OciReference::with_tag(
+            image.registry().to_string(),
+            image.repository().to_string(),
+            format!(
+                "{}.{}",
+                manifest_digest.replace(':', "-"),
+                self.tag_suffix()
+            ),
+        )
+    }
+}
+
+impl fmt::Display for AttachmentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AttachmentKind::Signature => "signature",
+            AttachmentKind::Attestation => "attestation",
+            AttachmentKind::Sbom => "sbom",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A layer downloaded from the registry, together with the annotations
+/// found inside of the manifest.
+///
+/// **Note well:** the contents of this struct have not been verified.
+#[derive(Clone, Debug, Serialize)]
+pub struct DownloadedLayer {
+    /// The digest of the layer
+    pub digest: String,
+    /// The media type of the layer
+    pub media_type: String,
+    /// The annotations associated with the layer inside of the manifest
+    pub annotations: HashMap<String, String>,
+    /// The raw contents of the layer. For signatures this is the Simple
+    /// Signing payload, for attestations the DSSE envelope and for SBOMs the
+    /// SBOM document itself.
+    #[serde(skip_serializing)]
+    pub data: Vec<u8>,
+}
+
+impl DownloadedLayer {
+    /// The base64 encoded signature, if any
+    pub fn signature(&self) -> Option<&str> {
+        self.annotation(SIGSTORE_SIGNATURE_ANNOTATION)
+    }
+
+    /// The PEM encoded certificate of the signer, if any
+    pub fn certificate(&self) -> Option<&str> {
+        self.annotation(SIGSTORE_CERT_ANNOTATION)
+    }
+
+    /// The PEM encoded certificate chain of the signer, if any
+    pub fn chain(&self) -> Option<&str> {
+        self.annotation(SIGSTORE_CHAIN_ANNOTATION)
+    }
+
+    /// The raw Rekor bundle, if any
+    pub fn bundle(&self) -> Option<&str> {
+        self.annotation(SIGSTORE_BUNDLE_ANNOTATION)
+    }
+
+    /// Parse the Simple Signing payload held by a signature layer
+    pub fn simple_signing(&self) -> Result<SimpleSigning> {
+        if self.media_type != SIGSTORE_OCI_MEDIA_TYPE {
+            return Err(SigstoreError::SigstoreMediaTypeNotFoundError);
+        }
+        serde_json::from_slice(&self.data).map_err(|e| {
+            SigstoreError::UnexpectedError(format!(
+                "Cannot convert layer data into SimpleSigning object: {e:?}"
+            ))
+        })
+    }
+
+    fn annotation(&self, key: &str) -> Option<&str> {
+        self.annotations.get(key).map(|v| v.as_str())
+    }
+}
+
+/// Pairs the layers described inside of the manifest with the data
+/// downloaded from the registry.
+///
+/// Layers that are described inside of the manifest, but that have not been
+/// downloaded, are ignored.
+pub(crate) fn build_downloaded_layers(
+    manifest: &oci_distribution::manifest::OciImageManifest,
+    layers: &[oci_distribution::client::ImageLayer],
+) -> Vec<DownloadedLayer> {
+    manifest
+        .layers
+        .iter()
+        .filter_map(|descriptor| {
+            layers
+                .iter()
+                .find(|l| l.sha256_digest() == descriptor.digest)
+                .map(|layer| DownloadedLayer {
+                    digest: descriptor.digest.clone(),
+                    media_type: descriptor.media_type.clone(),
+                    annotations: descriptor.annotations.clone().unwrap_or_default(),
+                    data: layer.data.clone(),
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oci_distribution::client::ImageLayer;
+    use oci_distribution::manifest::{OciDescriptor, OciImageManifest};
+
+    #[test]
+    fn attachment_reference() {
+        let image: OciReference = "registry.local/busybox:latest".parse().unwrap();
+        let digest = "sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b";
+
+        let reference = AttachmentKind::Attestation.reference(&image, digest);
+        assert_eq!(
+            reference.whole(),
+            "registry.local/busybox:sha256-f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b.att"
+        );
+
+        let reference = AttachmentKind::Sbom.reference(&image, digest);
+        assert_eq!(
+            reference.tag(),
+            Some("sha256-f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b.sbom")
+        );
+    }
+
+    #[test]
+    fn downloaded_layers_keep_annotations() {
+        let layer = ImageLayer::new(
+            b"payload".to_vec(),
+            SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+            None,
+        );
+        let missing_layer = ImageLayer::new(
+            b"not downloaded".to_vec(),
+            SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+            None,
+        );
+
+        let annotations: HashMap<String, String> = [
+            (SIGSTORE_SIGNATURE_ANNOTATION.to_string(), "sig".to_string()),
+            (SIGSTORE_CERT_ANNOTATION.to_string(), "cert".to_string()),
+        ]
+        .into();
+
+        let manifest = OciImageManifest {
+            layers: vec![
+                OciDescriptor {
+                    media_type: SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+                    digest: layer.sha256_digest(),
+                    size: layer.data.len() as i64,
+                    annotations: Some(annotations),
+                    ..Default::default()
+                },
+                OciDescriptor {
+                    media_type: SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+                    digest: missing_layer.sha256_digest(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let downloaded = build_downloaded_layers(&manifest, &[layer]);
+        assert_eq!(downloaded.len(), 1);
+        let dl = &downloaded[0];
+        assert_eq!(dl.data, b"payload".to_vec());
+        assert_eq!(dl.signature(), Some("sig"));
+        assert_eq!(dl.certificate(), Some("cert"));
+        assert_eq!(dl.chain(), None);
+        assert_eq!(dl.bundle(), None);
+        assert!(dl.simple_signing().is_err());
+    }
+
+    #[test]
+    fn parse_simple_signing_payload() {
+        let payload = r#"{"critical":{"identity":{"docker-reference":"registry.local/busybox"},"image":{"docker-manifest-digest":"sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b"},"type":"cosign container image signature"},"optional":null}"#;
+        let mut layer = DownloadedLayer {
+            digest: "sha256:abc".to_string(),
+            media_type: SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+            annotations: HashMap::new(),
+            data: payload.as_bytes().to_vec(),
+        };
+        let simple_signing = layer.simple_signing().unwrap();
+        assert!(simple_signing.satisfies_manifest_digest(
+            "sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b"
+        ));
+
+        layer.media_type = SIGSTORE_DSSE_MEDIA_TYPE.to_string();
+        assert!(matches!(
+            layer.simple_signing(),
+            Err(SigstoreError::SigstoreMediaTypeNotFoundError)
+        ));
+    }
+}
@@ -0,0 +1,243 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Configuration read from the environment variables of the cosign CLI.
+//!
+//! Tools replacing cosign with this crate can honor the variables already
+//! set inside of existing pipelines. Nothing is read unless asked to:
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::env::CosignEnv;
+//! use sigstore::cosign::ClientBuilder;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! let env = CosignEnv::from_env();
+//! let client = env.configure(ClientBuilder::default())?.build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The variables understood are:
+//!
+//! * `COSIGN_REPOSITORY`: the repository where the signatures are stored
+//! * `COSIGN_EXPERIMENTAL`: whether the experimental features are enabled
+//! * `SIGSTORE_REKOR_URL`: the URL of the Rekor instance
+//! * `SIGSTORE_FULCIO_URL`: the URL of the Fulcio instance
+//! * `SIGSTORE_REKOR_PUBLIC_KEY`: the path of a PEM file with the public key
+//!   of Rekor, used instead of the one distributed via TUF
+//! * `SIGSTORE_ROOT_FILE`: the path of a PEM file with the Fulcio
+//!   certificates, used instead of the ones distributed via TUF
+//! * `SIGSTORE_NO_CACHE`: whether the TUF metadata must not be cached on disk
+//! * `TUF_ROOT`: the directory where the TUF metadata is cached, defaults to
+//!   `$HOME/.sigstore/root`
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use super::ClientBuilder;
+use crate::errors::Result;
+use crate::registry::{Certificate, CertificateEncoding};
+
+pub const COSIGN_REPOSITORY: &str = "COSIGN_REPOSITORY";
+pub const COSIGN_EXPERIMENTAL: &str = "COSIGN_EXPERIMENTAL";
+pub const SIGSTORE_REKOR_URL: &str = "SIGSTORE_REKOR_URL";
+pub const SIGSTORE_FULCIO_URL: &str = "SIGSTORE_FULCIO_URL";
+pub const SIGSTORE_REKOR_PUBLIC_KEY: &str = "SIGSTORE_REKOR_PUBLIC_KEY";
+pub const SIGSTORE_ROOT_FILE: &str = "SIGSTORE_ROOT_FILE";
+pub const SIGSTORE_NO_CACHE: &str = "SIGSTORE_NO_CACHE";
+pub const TUF_ROOT: &str = "TUF_ROOT";
+
+/// The cosign CLI configuration found inside of the environment
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CosignEnv {
+    pub repository: Option<String>,
+    pub experimental: bool,
+    pub rekor_url: Option<String>,
+    pub fulcio_url: Option<String>,
+    pub rekor_public_key: Option<PathBuf>,
+    pub root_file: Option<PathBuf>,
+    pub no_cache: bool,
+    pub tuf_root: Option<PathBuf>,
+}
+
+impl CosignEnv {
+    /// Read the configuration from the environment of the process
+    pub fn from_env() -> Self {
+        Self::from_vars(std::env::vars())
+    }
+
+    /// Read the configuration from the given environment variables
+    pub fn from_vars<I, K, V>(vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let vars: HashMap<String, String> = vars
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .filter(|(_, v)| !v.is_empty())
+            .collect();
+        let tuf_root = vars.get(TUF_ROOT).map(PathBuf::from).or_else(|| {
+            vars.get("HOME")
+                .map(|home| PathBuf::from(home).join(".sigstore").join("root"))
+        });
+
+        CosignEnv {
+            repository: vars.get(COSIGN_REPOSITORY).cloned(),
+            experimental: vars.get(COSIGN_EXPERIMENTAL).is_some_and(|v| parse_bool(v)),
+            rekor_url: vars.get(SIGSTORE_REKOR_URL).cloned(),
+            fulcio_url: vars.get(SIGSTORE_FULCIO_URL).cloned(),
+            rekor_public_key: vars.get(SIGSTORE_REKOR_PUBLIC_KEY).map(PathBuf::from),
+            root_file: vars.get(SIGSTORE_ROOT_FILE).map(PathBuf::from),
+            no_cache: vars.get(SIGSTORE_NO_CACHE).is_some_and(|v| parse_bool(v)),
+            tuf_root,
+        }
+    }
+
+    /// Apply the configuration to `builder`.
+    ///
+    /// The Rekor key and the Fulcio certificates read from the files given
+    /// by the environment replace the ones already given to the builder.
+    pub fn configure(&self, mut builder: ClientBuilder) -> Result<ClientBuilder> {
+        if let Some(repository) = &self.repository {
+            builder = builder.with_signature_repository(repository);
+        }
+        if let Some(path) = &self.rekor_public_key {
+            builder = builder.with_rekor_pub_key(&std::fs::read_to_string(path)?);
+        }
+        if let Some(path) = &self.root_file {
+            let certs: Vec<Certificate> = pem::parse_many(std::fs::read(path)?)?
+                .iter()
+                .map(|cert| Certificate {
+                    encoding: CertificateEncoding::Pem,
+                    data: pem::encode(cert).into_bytes(),
+                })
+                .collect();
+            builder = builder.with_fulcio_certs(&certs);
+        }
+        Ok(builder)
+    }
+
+    /// The directory to be given to
+    /// [`SigstoreRepository::fetch`](crate::tuf::SigstoreRepository::fetch),
+    /// `None` when the TUF metadata must not be cached
+    pub fn tuf_checkout_dir(&self) -> Option<PathBuf> {
+        if self.no_cache {
+            None
+        } else {
+            self.tuf_root.clone()
+        }
+    }
+
+    /// The configuration of the Rekor client, pointing to the Rekor
+    /// instance of the environment
+    #[cfg(feature = "rekor")]
+    pub fn rekor_configuration(&self) -> crate::rekor::apis::configuration::Configuration {
+        let mut configuration = crate::rekor::apis::configuration::Configuration::default();
+        if let Some(url) = &self.rekor_url {
+            configuration.base_path = url.trim_end_matches('/').to_string();
+        }
+        configuration
+    }
+
+    /// The URL of the Fulcio instance of the environment, defaults to
+    /// [`FULCIO_ROOT`](crate::fulcio::FULCIO_ROOT)
+    #[cfg(feature = "fulcio")]
+    pub fn fulcio_url(&self) -> Result<url::Url> {
+        Ok(url::Url::parse(
+            self.fulcio_url
+                .as_deref()
+                .unwrap_or(crate::fulcio::FULCIO_ROOT),
+        )?)
+    }
+}
+
+/// Parse a boolean the way cosign does
+fn parse_bool(value: &str) -> bool {
+    matches!(value, "1" | "t" | "T" | "true" | "TRUE" | "True")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::tests::{FULCIO_CRT_1_PEM, FULCIO_CRT_2_PEM, REKOR_PUB_KEY};
+    use std::io::Write;
+
+    #[test]
+    fn read_cosign_variables() {
+        let env = CosignEnv::from_vars([
+            (COSIGN_REPOSITORY, "ghcr.io/octocat/signatures"),
+            (COSIGN_EXPERIMENTAL, "1"),
+            (SIGSTORE_REKOR_URL, "https://rekor.example.com/"),
+            (SIGSTORE_NO_CACHE, "false"),
+            (SIGSTORE_ROOT_FILE, ""),
+            ("HOME", "/home/octocat"),
+        ]);
+        assert_eq!(
+            env,
+            CosignEnv {
+                repository: Some("ghcr.io/octocat/signatures".to_string()),
+                experimental: true,
+                rekor_url: Some("https://rekor.example.com/".to_string()),
+                tuf_root: Some(PathBuf::from("/home/octocat/.sigstore/root")),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            env.tuf_checkout_dir(),
+            Some(PathBuf::from("/home/octocat/.sigstore/root"))
+        );
+
+        let env = CosignEnv::from_vars([
+            (TUF_ROOT, "/var/cache/tuf"),
+            (SIGSTORE_NO_CACHE, "true"),
+            ("HOME", "/home/octocat"),
+        ]);
+        assert_eq!(env.tuf_root, Some(PathBuf::from("/var/cache/tuf")));
+        assert_eq!(env.tuf_checkout_dir(), None);
+    }
+
+    #[test]
+    fn configure_client_builder() {
+        let mut rekor_key = tempfile::NamedTempFile::new().unwrap();
+        rekor_key.write_all(REKOR_PUB_KEY.as_bytes()).unwrap();
+        let mut root_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(root_file, "{FULCIO_CRT_1_PEM}\n{FULCIO_CRT_2_PEM}").unwrap();
+
+        let env = CosignEnv::from_vars([
+            (COSIGN_REPOSITORY, "ghcr.io/octocat/signatures".to_string()),
+            (
+                SIGSTORE_REKOR_PUBLIC_KEY,
+                rekor_key.path().display().to_string(),
+            ),
+            (SIGSTORE_ROOT_FILE, root_file.path().display().to_string()),
+        ]);
+        let client = env
+            .configure(ClientBuilder::default())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(client.rekor_pub_key.is_some());
+        assert!(client.fulcio_cert_pool.is_some());
+        assert_eq!(
+            client.signature_repository.unwrap().repository(),
+            "octocat/signatures"
+        );
+
+        let env = CosignEnv::from_vars([(SIGSTORE_REKOR_PUBLIC_KEY, "/does/not/exist")]);
+        assert!(env.configure(ClientBuilder::default()).is_err());
+    }
+}
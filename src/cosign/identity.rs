@@ -0,0 +1,153 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The identity of the signer of a verified signature.
+//!
+//! Verification constraints decide whether an artifact can be trusted.
+//! Applications that have their own authorization system can instead take
+//! the [`SignerIdentity`] of each trusted signature and feed it to it,
+//! without having to parse the certificate of the signer again.
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::SignatureLayer;
+//!
+//! # fn main() {
+//! # let trusted_layers: Vec<SignatureLayer> = vec![];
+//! for identity in trusted_layers.iter().filter_map(|sl| sl.signer_identity()) {
+//!     println!(
+//!         "signed by {} ({:?}), key {}",
+//!         identity.san.value(),
+//!         identity.issuer,
+//!         identity.key_fingerprint
+//!     );
+//! }
+//! # }
+//! ```
+
+use serde::Serialize;
+
+use super::signature_layers::{CertificateSignature, CertificateSubject};
+
+/// The normalized identity of a signer, taken from its verified certificate
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignerIdentity {
+    /// The Subject Alternative Name of the certificate
+    pub san: SubjectAlternativeName,
+    /// The OIDC issuer that authenticated the signer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    /// The claims about the CI workflow that produced the signature, when
+    /// the signature was done from a CI system
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ci: Option<CiClaims>,
+    /// The SHA-256 fingerprint of the signer's public key, in the
+    /// `sha256:<hex>` format
+    pub key_fingerprint: String,
+}
+
+/// The Subject Alternative Name of a signing certificate
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum SubjectAlternativeName {
+    /// An email address, used by signers authenticated with their account
+    Email(String),
+    /// A URI, used for example by workload identities like GitHub Actions
+    Uri(String),
+}
+
+impl SubjectAlternativeName {
+    /// The value of the SAN, regardless of its type
+    pub fn value(&self) -> &str {
+        match self {
+            SubjectAlternativeName::Email(v) | SubjectAlternativeName::Uri(v) => v,
+        }
+    }
+}
+
+impl From<&CertificateSubject> for SubjectAlternativeName {
+    fn from(subject: &CertificateSubject) -> Self {
+        match subject {
+            CertificateSubject::Email(e) => SubjectAlternativeName::Email(e.clone()),
+            CertificateSubject::Uri(u) => SubjectAlternativeName::Uri(u.clone()),
+        }
+    }
+}
+
+/// Claims about the CI workflow that requested the signing certificate
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CiClaims {
+    /// The event that triggered the workflow (e.g. `push`)
+    pub trigger: Option<String>,
+    /// The commit the workflow ran against
+    pub sha: Option<String>,
+    /// The name of the workflow
+    pub workflow_name: Option<String>,
+    /// The repository that owns the workflow (e.g. `octocat/example-repo`)
+    pub repository: Option<String>,
+    /// The Git ref the workflow ran against (e.g. `refs/tags/v0.9.9`)
+    pub git_ref: Option<String>,
+}
+
+impl From<&CertificateSignature> for SignerIdentity {
+    fn from(cs: &CertificateSignature) -> Self {
+        let ci = CiClaims {
+            trigger: cs.github_workflow_trigger.clone(),
+            sha: cs.github_workflow_sha.clone(),
+            workflow_name: cs.github_workflow_name.clone(),
+            repository: cs.github_workflow_repository.clone(),
+            git_ref: cs.github_workflow_ref.clone(),
+        };
+
+        SignerIdentity {
+            san: SubjectAlternativeName::from(&cs.subject),
+            issuer: cs.issuer.clone(),
+            ci: if ci == CiClaims::default() {
+                None
+            } else {
+                Some(ci)
+            },
+            key_fingerprint: cs.key_fingerprint.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::signature_layers::tests::{
+        build_correct_signature_layer_with_certificate,
+        build_correct_signature_layer_without_bundle,
+    };
+
+    #[test]
+    fn signer_identity_from_keyless_signature() {
+        let sl = build_correct_signature_layer_with_certificate();
+        let identity = sl.signer_identity().expect("identity not found");
+        let cs = sl.certificate_signature.unwrap();
+
+        assert_eq!(identity.issuer, cs.issuer);
+        assert_eq!(identity.san, SubjectAlternativeName::from(&cs.subject));
+        assert!(identity.key_fingerprint.starts_with("sha256:"));
+        assert_eq!(identity.key_fingerprint.len(), "sha256:".len() + 64);
+    }
+
+    #[test]
+    fn no_signer_identity_without_certificate() {
+        let (sl, _) = build_correct_signature_layer_without_bundle();
+        assert!(sl.signer_identity().is_none());
+    }
+}
@@ -0,0 +1,325 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Comparison of the cosign objects attached to an image inside of two
+//! registries.
+//!
+//! Promotion pipelines copy images from one registry to another (for example
+//! from a staging registry to a production mirror). When the signatures or
+//! the attestations are not copied too, verification starts failing only
+//! once the image is deployed. [`diff_inventories`] catches these issues
+//! earlier, by reporting the objects that are missing or that differ between
+//! the two registries.
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::inventory::diff_inventories;
+//! use sigstore::cosign::{AttachmentKind, ClientBuilder};
+//! use sigstore::registry::{Auth, OciReference};
+//!
+//! # async fn run() -> sigstore::errors::Result<()> {
+//! let mut staging = ClientBuilder::default().build()?;
+//! let mut prod = ClientBuilder::default().build()?;
+//! let staging_image: OciReference = "staging.registry.local/app:v1".parse()?;
+//! let prod_image: OciReference = "prod.registry.local/app:v1".parse()?;
+//!
+//! let diff = diff_inventories(
+//!     (&mut staging, &Auth::Anonymous, &staging_image),
+//!     (&mut prod, &Auth::Anonymous, &prod_image),
+//!     &[AttachmentKind::Signature, AttachmentKind::Attestation],
+//! )
+//! .await?;
+//! for difference in &diff.differences {
+//!     println!("{difference}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::constants::SIGSTORE_SIGNATURE_ANNOTATION;
+use super::{AttachmentKind, Client, CosignCapabilities, DownloadedLayer};
+use crate::errors::{Result, SigstoreError};
+use crate::registry::{Auth, OciReference};
+
+/// A registry to inspect: the client used to reach it, the credentials and
+/// the image
+pub type InventorySource<'a> = (&'a mut Client, &'a Auth, &'a OciReference);
+
+/// A difference between the objects attached to the image inside of the
+/// source registry and the ones inside of the target registry
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum InventoryDifference {
+    /// The two images do not have the same manifest digest, hence their
+    /// attachments have not been compared
+    #[serde(rename_all = "camelCase")]
+    DigestMismatch {
+        source_digest: String,
+        target_digest: String,
+    },
+    /// The object exists only inside of the source registry
+    #[serde(rename_all = "camelCase")]
+    Missing { kind: String, layer_digest: String },
+    /// The object exists only inside of the target registry
+    #[serde(rename_all = "camelCase")]
+    Unexpected { kind: String, layer_digest: String },
+    /// The object exists inside of both registries, but some of its
+    /// annotations (like the certificate or the Rekor bundle) differ
+    #[serde(rename_all = "camelCase")]
+    Divergent {
+        kind: String,
+        layer_digest: String,
+        annotations: Vec<String>,
+    },
+}
+
+impl fmt::Display for InventoryDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InventoryDifference::DigestMismatch {
+                source_digest,
+                target_digest,
+            } => write!(
+                f,
+                "image digest mismatch: source {source_digest}, target {target_digest}"
+            ),
+            InventoryDifference::Missing { kind, layer_digest } => {
+                write!(f, "{kind} {layer_digest} missing from target")
+            }
+            InventoryDifference::Unexpected { kind, layer_digest } => {
+                write!(f, "{kind} {layer_digest} found only inside of target")
+            }
+            InventoryDifference::Divergent {
+                kind,
+                layer_digest,
+                annotations,
+            } => write!(
+                f,
+                "{kind} {layer_digest} has different annotations: {}",
+                annotations.join(", ")
+            ),
+        }
+    }
+}
+
+/// The outcome of [`diff_inventories`]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct InventoryDiff {
+    /// The manifest digest of the image inside of the source registry
+    pub source_digest: String,
+    /// The manifest digest of the image inside of the target registry
+    pub target_digest: String,
+    /// All the differences that have been found
+    pub differences: Vec<InventoryDifference>,
+}
+
+impl InventoryDiff {
+    /// Returns `true` when the two registries hold the same objects
+    pub fn is_consistent(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+/// Compare the objects of the given `kinds` attached to the image inside of
+/// the `source` and the `target` registries.
+///
+/// Objects are identified by the digest of their layer and, for signatures,
+/// by their signature: the same payload can be signed more than once.
+///
+/// A registry that cannot provide the manifest of the objects of a given
+/// kind is considered to have none of them, since that's how registries
+/// report missing tags. Any other error is returned.
+pub async fn diff_inventories(
+    source: InventorySource<'_>,
+    target: InventorySource<'_>,
+    kinds: &[AttachmentKind],
+) -> Result<InventoryDiff> {
+    let (source_client, source_auth, source_image) = source;
+    let (target_client, target_auth, target_image) = target;
+
+    let (_, source_digest) = source_client.triangulate(source_image, source_auth).await?;
+    let (_, target_digest) = target_client.triangulate(target_image, target_auth).await?;
+
+    let mut differences = Vec::new();
+    if source_digest != target_digest {
+        differences.push(InventoryDifference::DigestMismatch {
+            source_digest: source_digest.clone(),
+            target_digest: target_digest.clone(),
+        });
+        return Ok(InventoryDiff {
+            source_digest,
+            target_digest,
+            differences,
+        });
+    }
+
+    for kind in kinds {
+        let source_layers =
+            download_or_empty(source_client, source_auth, source_image, *kind).await?;
+        let target_layers =
+            download_or_empty(target_client, target_auth, target_image, *kind).await?;
+        differences.extend(compare_layers(*kind, &source_layers, &target_layers));
+    }
+
+    Ok(InventoryDiff {
+        source_digest,
+        target_digest,
+        differences,
+    })
+}
+
+async fn download_or_empty(
+    client: &mut Client,
+    auth: &Auth,
+    image: &OciReference,
+    kind: AttachmentKind,
+) -> Result<Vec<DownloadedLayer>> {
+    match client.download(auth, image, kind).await {
+        Ok(layers) => Ok(layers),
+        Err(SigstoreError::RegistryPullManifestError { .. }) => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Index the layers by their digest and signature
+fn index_layers(
+    layers: &[DownloadedLayer],
+) -> BTreeMap<(String, Option<String>), &DownloadedLayer> {
+    layers
+        .iter()
+        .map(|l| {
+            let signature = l.annotations.get(SIGSTORE_SIGNATURE_ANNOTATION).cloned();
+            ((l.digest.clone(), signature), l)
+        })
+        .collect()
+}
+
+fn compare_layers(
+    kind: AttachmentKind,
+    source: &[DownloadedLayer],
+    target: &[DownloadedLayer],
+) -> Vec<InventoryDifference> {
+    let source = index_layers(source);
+    let target = index_layers(target);
+    let mut differences = Vec::new();
+
+    for (key, source_layer) in &source {
+        match target.get(key) {
+            None => differences.push(InventoryDifference::Missing {
+                kind: kind.to_string(),
+                layer_digest: key.0.clone(),
+            }),
+            Some(target_layer) => {
+                let mut annotations: Vec<String> = source_layer
+                    .annotations
+                    .keys()
+                    .chain(target_layer.annotations.keys())
+                    .filter(|k| {
+                        source_layer.annotations.get(*k) != target_layer.annotations.get(*k)
+                    })
+                    .cloned()
+                    .collect();
+                annotations.sort();
+                annotations.dedup();
+                if !annotations.is_empty() {
+                    differences.push(InventoryDifference::Divergent {
+                        kind: kind.to_string(),
+                        layer_digest: key.0.clone(),
+                        annotations,
+                    });
+                }
+            }
+        }
+    }
+
+    for key in target.keys().filter(|k| !source.contains_key(*k)) {
+        differences.push(InventoryDifference::Unexpected {
+            kind: kind.to_string(),
+            layer_digest: key.0.clone(),
+        });
+    }
+
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::constants::{SIGSTORE_BUNDLE_ANNOTATION, SIGSTORE_OCI_MEDIA_TYPE};
+    use std::collections::HashMap;
+
+    fn layer(data: &str, annotations: &[(&str, &str)]) -> DownloadedLayer {
+        DownloadedLayer {
+            digest: format!("sha256:{data}"),
+            media_type: SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+            annotations: annotations
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<String, String>>(),
+            data: data.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn compare_identical_layers() {
+        let layers = vec![layer("a", &[(SIGSTORE_SIGNATURE_ANNOTATION, "sig-a")])];
+        assert!(compare_layers(AttachmentKind::Signature, &layers, &layers).is_empty());
+    }
+
+    #[test]
+    fn compare_missing_unexpected_and_divergent_layers() {
+        let source = vec![
+            layer("a", &[(SIGSTORE_SIGNATURE_ANNOTATION, "sig-a")]),
+            layer(
+                "b",
+                &[
+                    (SIGSTORE_SIGNATURE_ANNOTATION, "sig-b"),
+                    (SIGSTORE_BUNDLE_ANNOTATION, "bundle"),
+                ],
+            ),
+            // same payload, signed twice
+            layer("a", &[(SIGSTORE_SIGNATURE_ANNOTATION, "sig-a2")]),
+        ];
+        let target = vec![
+            layer("a", &[(SIGSTORE_SIGNATURE_ANNOTATION, "sig-a")]),
+            layer("b", &[(SIGSTORE_SIGNATURE_ANNOTATION, "sig-b")]),
+            layer("c", &[(SIGSTORE_SIGNATURE_ANNOTATION, "sig-c")]),
+        ];
+
+        let differences = compare_layers(AttachmentKind::Signature, &source, &target);
+        assert_eq!(
+            differences,
+            vec![
+                InventoryDifference::Missing {
+                    kind: "signature".to_string(),
+                    layer_digest: "sha256:a".to_string(),
+                },
+                InventoryDifference::Divergent {
+                    kind: "signature".to_string(),
+                    layer_digest: "sha256:b".to_string(),
+                    annotations: vec![SIGSTORE_BUNDLE_ANNOTATION.to_string()],
+                },
+                InventoryDifference::Unexpected {
+                    kind: "signature".to_string(),
+                    layer_digest: "sha256:c".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -28,6 +28,7 @@
 //!   * Verify using a given key
 //!   * Verify bundle produced by transparency log (Rekor)
 //!   * Verify signature produced in keyless mode, using Fulcio Web-PKI
+//!   * Verify the in-toto attestations attached to an image
 //!
 //! Signature annotations and certificate email can be provided at verification time.
 //!
@@ -53,6 +54,8 @@ use x509_cert::Certificate;
 
 pub mod bundle;
 pub(crate) mod constants;
+pub mod download;
+pub use download::{AttachmentKind, DownloadedLayer};
 pub mod signature_layers;
 pub use signature_layers::SignatureLayer;
 
@@ -71,6 +74,34 @@ use crate::registry::oci_reference::OciReference;
 pub use payload::simple_signing;
 
 pub mod constraint;
+
+pub mod alerting;
+#[cfg(feature = "rekor")]
+pub mod archive;
+pub mod attestation;
+#[cfg(feature = "rekor")]
+pub mod blob;
+pub mod countersign;
+pub mod env;
+pub mod identity;
+pub use identity::SignerIdentity;
+pub mod inventory;
+pub mod offline;
+pub mod policy;
+pub mod report;
+pub mod sbom;
+pub use sbom::{Sbom, SbomFormat};
+#[cfg(all(feature = "fulcio", feature = "rekor"))]
+pub mod signing_session;
+#[cfg(all(feature = "fulcio", feature = "rekor"))]
+pub use signing_session::SigningSession;
+pub mod tenancy;
+pub mod threshold;
+pub use threshold::ThresholdVerifier;
+pub mod verification_cache;
+pub mod watcher;
+pub use watcher::Watcher;
+
 #[async_trait(?Send)]
 /// Cosign Abilities that have to be implemented by a
 /// Cosign client
@@ -150,6 +181,69 @@ pub trait CosignCapabilities {
         signature_layers: Vec<SignatureLayer>,
     ) -> Result<PushResponse>;
 
+    /// Download the signatures, attestations or SBOMs attached to the given
+    /// image, without verifying them.
+    ///
+    /// This is the equivalent of the `cosign download signature`,
+    /// `cosign download attestation` and `cosign download sbom` commands.
+    ///
+    /// The parameters:
+    /// - `auth`: Credential used to access the registry
+    /// - `image`: the image the objects are attached to
+    /// - `kind`: the kind of objects to download
+    ///
+    /// **Warning:** the returned [`DownloadedLayer`] objects are **not**
+    /// verified. Use [`CosignCapabilities::trusted_signature_layers`] to
+    /// obtain data that can be trusted.
+    ///
+    /// The default implementation returns a
+    /// [`SigstoreError::CosignCapabilityNotSupported`] error.
+    async fn download(
+        &mut self,
+        _auth: &Auth,
+        _image: &OciReference,
+        _kind: AttachmentKind,
+    ) -> Result<Vec<DownloadedLayer>> {
+        Err(SigstoreError::CosignCapabilityNotSupported("download"))
+    }
+
+    /// Returns the attestations attached to the given image that can be
+    /// verified. This is the equivalent of the `cosign verify-attestation`
+    /// command.
+    ///
+    /// Each attestation is verified this way:
+    ///
+    /// * the DSSE envelope must be signed by `verification_key`. When no key
+    ///   is provided, the envelope must be signed by the certificate embedded
+    ///   into the layer, which must be trusted. See
+    ///   [`CosignCapabilities::trusted_signature_layers`] for the requirements
+    /// * the Rekor bundle, when present, must be valid and must refer to the
+    ///   payload of the envelope
+    /// * the image must be one of the subjects of the in-toto statement
+    ///
+    /// The attestations that cannot be verified are skipped. The returned
+    /// [`VerifiedAttestation`](crate::cosign::attestation::VerifiedAttestation)
+    /// objects hold the decoded predicates, ready to be evaluated by a policy.
+    ///
+    /// The parameters:
+    /// - `auth`: Credential used to access the registry
+    /// - `image`: the image the attestations are attached to
+    /// - `verification_key`: the key that signed the attestations, `None`
+    ///   for attestations produced in keyless mode
+    ///
+    /// The default implementation returns a
+    /// [`SigstoreError::CosignCapabilityNotSupported`] error.
+    async fn verify_attestations(
+        &mut self,
+        _auth: &Auth,
+        _image: &OciReference,
+        _verification_key: Option<&CosignVerificationKey>,
+    ) -> Result<Vec<attestation::VerifiedAttestation>> {
+        Err(SigstoreError::CosignCapabilityNotSupported(
+            "attestation verification",
+        ))
+    }
+
     /// Verifies the signature produced by cosign when signing the given blob via the `cosign sign-blob` command
     ///
     /// The parameters:
@@ -159,6 +253,12 @@ pub trait CosignCapabilities {
     ///
     /// This function returns `Ok())` when the given signature has been verified, otherwise returns an `Err`.
     fn verify_blob(cert: &str, signature: &str, blob: &[u8]) -> Result<()> {
+        Self::verify_blob_raw(cert, &BASE64_STD_ENGINE.decode(signature)?, blob)
+    }
+
+    /// Same as [`verify_blob`](Self::verify_blob), with the signature
+    /// given as raw bytes instead of being base64 encoded
+    fn verify_blob_raw(cert: &str, signature: &[u8], blob: &[u8]) -> Result<()> {
         let cert = BASE64_STD_ENGINE.decode(cert)?;
         let pem = pem::parse(cert)?;
         let cert = Certificate::from_der(&pem.contents).map_err(|e| {
@@ -166,8 +266,7 @@ pub trait CosignCapabilities {
         })?;
         let spki = cert.tbs_certificate.subject_public_key_info;
         let ver_key = CosignVerificationKey::try_from(&spki)?;
-        let signature = Signature::Base64Encoded(signature.as_bytes());
-        ver_key.verify_signature(signature, blob)?;
+        ver_key.verify_signature(Signature::Raw(signature), blob)?;
         Ok(())
     }
 
@@ -181,9 +280,22 @@ pub trait CosignCapabilities {
     ///
     /// This function returns `Ok())` when the given signature has been verified, otherwise returns an `Err`.
     fn verify_blob_with_public_key(public_key: &str, signature: &str, blob: &[u8]) -> Result<()> {
+        Self::verify_blob_raw_with_public_key(
+            public_key,
+            &BASE64_STD_ENGINE.decode(signature)?,
+            blob,
+        )
+    }
+
+    /// Same as [`verify_blob_with_public_key`](Self::verify_blob_with_public_key),
+    /// with the signature given as raw bytes instead of being base64 encoded
+    fn verify_blob_raw_with_public_key(
+        public_key: &str,
+        signature: &[u8],
+        blob: &[u8],
+    ) -> Result<()> {
         let ver_key = CosignVerificationKey::try_from_pem(public_key.as_bytes())?;
-        let signature = Signature::Base64Encoded(signature.as_bytes());
-        ver_key.verify_signature(signature, blob)?;
+        ver_key.verify_signature(Signature::Raw(signature), blob)?;
         Ok(())
     }
 }
@@ -279,7 +391,7 @@ where
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use serde_json::json;
     use std::collections::HashMap;
 
@@ -306,7 +418,7 @@ MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE2G2Y+2tabdTV5BcGiBIx0a9fAFwr
 kBbmLSGtks4L3qX6yYY0zufBnhC8Ur/iy55GhWP/9A/bY2LhC30M9+RYtw==
 -----END PUBLIC KEY-----"#;
 
-    const FULCIO_CRT_1_PEM: &str = r#"-----BEGIN CERTIFICATE-----
+    pub(crate) const FULCIO_CRT_1_PEM: &str = r#"-----BEGIN CERTIFICATE-----
 MIIB+DCCAX6gAwIBAgITNVkDZoCiofPDsy7dfm6geLbuhzAKBggqhkjOPQQDAzAq
 MRUwEwYDVQQKEwxzaWdzdG9yZS5kZXYxETAPBgNVBAMTCHNpZ3N0b3JlMB4XDTIx
 MDMwNzAzMjAyOVoXDTMxMDIyMzAzMjAyOVowKjEVMBMGA1UEChMMc2lnc3RvcmUu
@@ -320,7 +432,7 @@ Ve/83WrFomwmNf056y1X48F9c4m3a3ozXAIxAKjRay5/aj/jsKKGIkmQatjI8uup
 Hr/+CxFvaJWmpYqNkLDGRU+9orzh5hI2RrcuaQ==
 -----END CERTIFICATE-----"#;
 
-    const FULCIO_CRT_2_PEM: &str = r#"-----BEGIN CERTIFICATE-----
+    pub(crate) const FULCIO_CRT_2_PEM: &str = r#"-----BEGIN CERTIFICATE-----
 MIIB9zCCAXygAwIBAgIUALZNAPFdxHPwjeDloDwyYChAO/4wCgYIKoZIzj0EAwMw
 KjEVMBMGA1UEChMMc2lnc3RvcmUuZGV2MREwDwYDVQQDEwhzaWdzdG9yZTAeFw0y
 MTEwMDcxMzU2NTlaFw0zMTEwMDUxMzU2NThaMCoxFTATBgNVBAoTDHNpZ3N0b3Jl
@@ -0,0 +1,281 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of container images inside of air-gapped environments.
+//!
+//! [`export_verification_material`] runs on a connected machine: it fetches
+//! the signatures of the given images, together with their certificates and
+//! Rekor bundles, plus a snapshot of the trust material. Everything is stored
+//! inside of an [`OfflineArchive`].
+//!
+//! The archive is then copied to the disconnected machine, where
+//! [`verify_offline`] evaluates the verification constraints without
+//! reaching the network.
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::offline::{export_verification_material, verify_offline};
+//! use sigstore::cosign::verification_constraint::VerificationConstraintVec;
+//! use sigstore::cosign::ClientBuilder;
+//! use sigstore::registry::{Auth, OciReference};
+//! use sigstore::tuf::SigstoreRepository;
+//!
+//! # async fn run() -> sigstore::errors::Result<()> {
+//! // on the connected machine
+//! let repo = SigstoreRepository::fetch(None)?;
+//! let builder = ClientBuilder::default()
+//!     .with_rekor_pub_key(repo.rekor_pub_key())
+//!     .with_fulcio_certs(repo.fulcio_certs());
+//! let images: Vec<OciReference> = vec!["ghcr.io/sigstore/cosign/cosign:v2.0.0".parse()?];
+//! let archive = export_verification_material(builder, &Auth::Anonymous, &images).await?;
+//! std::fs::write("material.json", archive.to_json()?)?;
+//!
+//! // on the disconnected machine
+//! let archive = sigstore::cosign::offline::OfflineArchive::from_json(
+//!     &std::fs::read_to_string("material.json")?,
+//! )?;
+//! let constraints: VerificationConstraintVec = vec![];
+//! for report in verify_offline(&archive, &constraints).await? {
+//!     println!("{}: {}", report.artifact.reference, report.verified);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use super::report::VerificationReport;
+use super::verification_constraint::VerificationConstraintVec;
+use super::{Client, ClientBuilder, CosignCapabilities};
+use crate::errors::{Result, SigstoreError};
+use crate::registry::recording::{Recorder, Recording};
+use crate::registry::{Auth, OciReference};
+
+/// The version of the archive format produced by this crate
+pub const OFFLINE_ARCHIVE_VERSION: &str = "v1";
+
+/// All the material required to verify a set of images offline
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OfflineArchive {
+    /// The version of the archive format
+    pub version: String,
+    /// The images covered by the archive
+    pub images: Vec<String>,
+    /// The signatures, the trust material and all the other data used
+    /// during verification
+    pub recording: Recording,
+}
+
+impl OfflineArchive {
+    /// Serialize the archive to JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(|e| {
+            SigstoreError::OfflineArchiveError(format!("cannot serialize archive: {e}"))
+        })
+    }
+
+    /// Load an archive produced by [`OfflineArchive::to_json`]
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let archive: OfflineArchive = serde_json::from_str(raw).map_err(|e| {
+            SigstoreError::OfflineArchiveError(format!("cannot parse archive: {e}"))
+        })?;
+        if archive.version != OFFLINE_ARCHIVE_VERSION {
+            return Err(SigstoreError::OfflineArchiveError(format!(
+                "unsupported archive version {}",
+                archive.version
+            )));
+        }
+        Ok(archive)
+    }
+}
+
+/// Fetch everything needed to verify `images` offline.
+///
+/// `builder` must be configured with the trust material (Rekor public key
+/// and Fulcio certificates) to be used at verification time. The signatures
+/// of each image are fetched and verified against it, the export fails when
+/// one of the images cannot be processed.
+pub async fn export_verification_material(
+    builder: ClientBuilder,
+    auth: &Auth,
+    images: &[OciReference],
+) -> Result<OfflineArchive> {
+    let recorder = Recorder::new();
+    let mut client = builder.with_recorder(recorder.clone()).build()?;
+    export_with_client(&mut client, &recorder, auth, images).await
+}
+
+async fn export_with_client(
+    client: &mut Client,
+    recorder: &Recorder,
+    auth: &Auth,
+    images: &[OciReference],
+) -> Result<OfflineArchive> {
+    for image in images {
+        let (cosign_image, source_image_digest) = client.triangulate(image, auth).await?;
+        client
+            .trusted_signature_layers(auth, &source_image_digest, &cosign_image)
+            .await?;
+    }
+
+    Ok(OfflineArchive {
+        version: OFFLINE_ARCHIVE_VERSION.to_string(),
+        images: images.iter().map(|i| i.whole()).collect(),
+        recording: recorder.recording(),
+    })
+}
+
+/// Verify all the images of `archive` against `constraints`, without
+/// reaching the network.
+///
+/// A [`VerificationReport`] is returned for each image, in the same order
+/// used at export time.
+pub async fn verify_offline(
+    archive: &OfflineArchive,
+    constraints: &VerificationConstraintVec,
+) -> Result<Vec<VerificationReport>> {
+    let mut client = ClientBuilder::default()
+        .with_replay(archive.recording.clone())
+        .build()?;
+
+    let mut reports = Vec::with_capacity(archive.images.len());
+    for image in &archive.images {
+        let image: OciReference = image.parse()?;
+        let report = match client.triangulate(&image, &Auth::Anonymous).await {
+            Ok((cosign_image, source_image_digest)) => match client
+                .trusted_signature_layers(&Auth::Anonymous, &source_image_digest, &cosign_image)
+                .await
+            {
+                Ok(layers) => {
+                    VerificationReport::new(&image, &source_image_digest, &layers, constraints)
+                }
+                Err(e) => VerificationReport::failed(&image, Some(&source_image_digest), &e),
+            },
+            Err(e) => VerificationReport::failed(&image, None, &e),
+        };
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+#[cfg(feature = "mock-client")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::constants::{SIGSTORE_OCI_MEDIA_TYPE, SIGSTORE_SIGNATURE_ANNOTATION};
+    use crate::cosign::signature_layers::tests::build_correct_signature_layer_without_bundle;
+    use crate::cosign::verification_constraint::PublicKeyVerifier;
+    use crate::crypto::certificate_transparency::SctPolicy;
+    use crate::crypto::tests::PUBLIC_KEY;
+    use crate::mock_client::test::MockOciClient;
+    use crate::registry::recording::RecordingClient;
+    use oci_distribution::client::{Config, ImageData, ImageLayer};
+    use oci_distribution::manifest::{OciDescriptor, OciImageManifest, OciManifest};
+
+    fn build_recording_client(recorder: &Recorder) -> Client {
+        let (sl, _) = build_correct_signature_layer_without_bundle();
+        let annotations: std::collections::HashMap<String, String> = [(
+            SIGSTORE_SIGNATURE_ANNOTATION.to_string(),
+            sl.signature.clone().unwrap(),
+        )]
+        .into();
+        let layer = ImageLayer::new(
+            sl.raw_data.clone(),
+            SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+            Some(annotations.clone()),
+        );
+        let manifest = OciImageManifest {
+            layers: vec![OciDescriptor {
+                media_type: SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+                digest: layer.sha256_digest(),
+                size: layer.data.len() as i64,
+                annotations: Some(annotations),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let mock_client = MockOciClient {
+            fetch_manifest_digest_response: Some(Ok(sl
+                .simple_signing
+                .critical
+                .image
+                .docker_manifest_digest
+                .clone())),
+            pull_response: Some(Ok(ImageData {
+                layers: vec![layer],
+                digest: None,
+                config: Config::oci_v1(b"{}".to_vec(), None),
+                manifest: None,
+            })),
+            pull_manifest_response: Some(Ok((
+                OciManifest::Image(manifest),
+                "sha256:manifest".to_string(),
+            ))),
+            push_response: None,
+        };
+
+        Client {
+            registry_client: Box::new(RecordingClient {
+                inner: Box::new(mock_client),
+                recorder: recorder.clone(),
+            }),
+            rekor_pub_key: None,
+            fulcio_cert_pool: None,
+            trusted_root: None,
+            sct_policy: SctPolicy::Require,
+            freshness: None,
+            progress_listener: None,
+            signature_repository: None,
+            verification_concurrency: 1,
+            verification_cache: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn export_and_verify_offline() {
+        let image: OciReference = "registry.local/busybox:latest".parse().unwrap();
+        let recorder = Recorder::new();
+        let mut client = build_recording_client(&recorder);
+
+        let archive =
+            export_with_client(&mut client, &recorder, &Auth::Anonymous, &[image.clone()])
+                .await
+                .expect("export failed");
+        let archive = OfflineArchive::from_json(&archive.to_json().unwrap()).unwrap();
+
+        let constraints: VerificationConstraintVec = vec![Box::new(
+            PublicKeyVerifier::try_from(PUBLIC_KEY.as_bytes()).unwrap(),
+        )];
+        let reports = verify_offline(&archive, &constraints).await.unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].artifact.reference, image.whole());
+        assert!(reports[0].verified);
+        assert_eq!(reports[0].signatures.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn verify_offline_reports_missing_material() {
+        let archive = OfflineArchive {
+            version: OFFLINE_ARCHIVE_VERSION.to_string(),
+            images: vec!["registry.local/busybox:latest".to_string()],
+            recording: Recording::default(),
+        };
+
+        let reports = verify_offline(&archive, &Vec::new()).await.unwrap();
+        assert!(!reports[0].verified);
+        assert!(reports[0].error.is_some());
+    }
+}
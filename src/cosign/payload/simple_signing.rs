@@ -17,8 +17,10 @@
 //! the Container signature format described
 //! [here](https://github.com/containers/image/blob/a5061e5a5f00333ea3a92e7103effd11c6e2f51d/docs/containers-signature.5.md#json-data-format).
 
+use crate::errors::Result;
 use crate::registry::OciReference;
 
+use olpc_cjson::CanonicalFormatter;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::{collections::HashMap, fmt};
@@ -64,6 +66,51 @@ impl SimpleSigning {
         }
     }
 
+    /// Add the given annotations to the `optional` section, like
+    /// `cosign sign -a key=value` does
+    pub fn with_annotations(self, annotations: HashMap<String, String>) -> Self {
+        let mut optional = self.optional.unwrap_or_default();
+        optional.extra.extend(
+            annotations
+                .into_iter()
+                .map(|(key, value)| (key, Value::String(value))),
+        );
+        Self {
+            critical: self.critical,
+            optional: Some(optional),
+        }
+    }
+
+    /// Set the `creator` of the `optional` section
+    pub fn with_creator(self, creator: &str) -> Self {
+        let mut optional = self.optional.unwrap_or_default();
+        optional.creator = Some(creator.to_string());
+        Self {
+            critical: self.critical,
+            optional: Some(optional),
+        }
+    }
+
+    /// Set the `timestamp` of the `optional` section, as a UNIX timestamp
+    pub fn with_timestamp(self, timestamp: i64) -> Self {
+        let mut optional = self.optional.unwrap_or_default();
+        optional.timestamp = Some(timestamp);
+        Self {
+            critical: self.critical,
+            optional: Some(optional),
+        }
+    }
+
+    /// The payload to be signed: the canonical JSON serialization of the
+    /// object, with sorted keys and without whitespaces, identical to the
+    /// payloads produced by cosign
+    pub fn to_canonical_json(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        let mut ser = serde_json::Serializer::with_formatter(&mut buf, CanonicalFormatter::new());
+        self.serialize(&mut ser)?;
+        Ok(buf)
+    }
+
     /// Checks whether all the provided `annotations` are satisfied
     pub fn satisfies_annotations(&self, annotations: &HashMap<String, String>) -> bool {
         if annotations.is_empty() {
@@ -206,6 +253,31 @@ mod tests {
     use super::*;
     use serde_json::json;
 
+    #[test]
+    fn build_simple_signing_payload() {
+        let image_ref: OciReference = "registry.foo.bar/busybox:latest".parse().unwrap();
+        let ss = SimpleSigning::new(&image_ref, "sha256:something");
+        assert_eq!(
+            String::from_utf8(ss.to_canonical_json().unwrap()).unwrap(),
+            r#"{"critical":{"identity":{"docker-reference":"registry.foo.bar/busybox:latest"},"image":{"docker-manifest-digest":"sha256:something"},"type":"cosign container image signature"},"optional":null}"#
+        );
+
+        let annotations: HashMap<String, String> = [
+            ("env".to_string(), "prod".to_string()),
+            ("app".to_string(), "web".to_string()),
+        ]
+        .into();
+        let ss = ss
+            .with_annotations(annotations.clone())
+            .with_creator("sigstore-rs")
+            .with_timestamp(1_700_000_000);
+        assert!(ss.satisfies_annotations(&annotations));
+        assert_eq!(
+            String::from_utf8(ss.to_canonical_json().unwrap()).unwrap(),
+            r#"{"critical":{"identity":{"docker-reference":"registry.foo.bar/busybox:latest"},"image":{"docker-manifest-digest":"sha256:something"},"type":"cosign container image signature"},"optional":{"app":"web","creator":"sigstore-rs","env":"prod","timestamp":1700000000}}"#
+        );
+    }
+
     #[test]
     fn simple_signing_does_not_satisfy_annotations_when_optional_is_none() {
         let ss_json = json!({
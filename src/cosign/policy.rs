@@ -0,0 +1,643 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Declarative verification policies.
+//!
+//! A [`Policy`] is read from a document modeled after the `ClusterImagePolicy`
+//! of the Sigstore [policy-controller](https://docs.sigstore.dev/policy-controller/overview/),
+//! encoded in JSON, or in YAML when the `policy-yaml` feature is enabled:
+//!
+//! ```yaml
+//! apiVersion: policy.sigstore.dev/v1beta1
+//! kind: ClusterImagePolicy
+//! metadata:
+//!   name: release-images
+//! spec:
+//!   images:
+//!     - glob: "registry.example.com/release/**"
+//!   authorities:
+//!     - name: release-key
+//!       key:
+//!         data: |
+//!           -----BEGIN PUBLIC KEY-----
+//!           ...
+//!           -----END PUBLIC KEY-----
+//!       annotations:
+//!         env: prod
+//!     - name: release-workflow
+//!       keyless:
+//!         identities:
+//!           - issuer: https://token.actions.githubusercontent.com
+//!             subjectRegExp: ^https://github\.com/octocat/hello-world/
+//!       requireRekorBundle: true
+//!       attestations:
+//!         - name: provenance
+//!           predicateType: https://slsa.dev/provenance/v0.2
+//!   threshold: 1
+//! ```
+//!
+//! An authority is satisfied by a trusted signature produced by its signer,
+//! or, when it lists attestations, by trusted attestations of each of the
+//! required predicate types. The image is allowed when `threshold`
+//! authorities are satisfied, all of them by default.
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::policy::Policy;
+//! use sigstore::cosign::ClientBuilder;
+//! use sigstore::registry::{Auth, OciReference};
+//!
+//! # async fn run() -> sigstore::errors::Result<()> {
+//! let policy = Policy::from_json(&std::fs::read_to_string("policy.json")?)?;
+//!
+//! let mut client = ClientBuilder::default().build()?;
+//! let image: OciReference = "registry.example.com/release/app:v1".parse()?;
+//! if policy.applies_to(&image) {
+//!     let evaluation = policy.verify(&mut client, &Auth::Anonymous, &image).await?;
+//!     println!("allowed: {}", evaluation.is_allowed());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use super::attestation::verification::VerifiedAttestation;
+use super::verification_constraint::certificate_identity_verifier::{
+    CertificateIdentityVerifier, IdentityMatcher,
+};
+use super::{Client, CosignCapabilities, SignatureLayer};
+use crate::crypto::CosignVerificationKey;
+use crate::errors::{Result, SigstoreError};
+use crate::registry::{Auth, OciReference};
+
+/// A policy document, like a `ClusterImagePolicy`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyDocument {
+    #[serde(default)]
+    pub api_version: Option<String>,
+    #[serde(default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub metadata: PolicyMetadata,
+    pub spec: PolicySpec,
+}
+
+/// The metadata of a [`PolicyDocument`]
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct PolicyMetadata {
+    #[serde(default)]
+    pub name: String,
+}
+
+/// The images covered by a policy, and the authorities they must be
+/// signed by
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicySpec {
+    /// The images covered by the policy, all of them when empty
+    #[serde(default)]
+    pub images: Vec<ImagePattern>,
+    pub authorities: Vec<Authority>,
+    /// The number of authorities that must be satisfied, all of them when
+    /// missing
+    #[serde(default)]
+    pub threshold: Option<usize>,
+}
+
+/// A glob matching image references. `*` matches any sequence of
+/// characters but `/`, `**` matches any sequence of characters.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ImagePattern {
+    pub glob: String,
+}
+
+/// A signer trusted by a policy, either a key or keyless identities
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Authority {
+    /// The name of the authority, `authority-<index>` when missing
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub key: Option<KeyAuthority>,
+    #[serde(default)]
+    pub keyless: Option<KeylessAuthority>,
+    /// The annotations the signatures must have
+    #[serde(default)]
+    pub annotations: HashMap<String, String>,
+    /// Accept only the signatures and attestations recorded by Rekor
+    #[serde(default)]
+    pub require_rekor_bundle: bool,
+    /// The attestations that must have been produced by the authority,
+    /// instead of a signature
+    #[serde(default)]
+    pub attestations: Vec<AttestationRequirement>,
+}
+
+/// A signer using a long-lived key
+#[derive(Deserialize, Debug, Clone)]
+pub struct KeyAuthority {
+    /// The PEM encoded public key
+    pub data: String,
+}
+
+/// A signer using Fulcio certificates
+#[derive(Deserialize, Debug, Clone)]
+pub struct KeylessAuthority {
+    pub identities: Vec<Identity>,
+}
+
+/// An identity certified by Fulcio. The subject and the issuer are each
+/// given either exactly or as a regular expression.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Identity {
+    #[serde(default)]
+    pub issuer: Option<String>,
+    #[serde(default)]
+    pub subject: Option<String>,
+    #[serde(default)]
+    pub issuer_reg_exp: Option<String>,
+    #[serde(default)]
+    pub subject_reg_exp: Option<String>,
+}
+
+/// An attestation required by an authority
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AttestationRequirement {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The predicate type of the attestation, e.g. `https://slsa.dev/provenance/v0.2`
+    pub predicate_type: String,
+}
+
+/// A verification policy, ready to be evaluated
+#[derive(Debug)]
+pub struct Policy {
+    name: String,
+    images: Vec<Regex>,
+    authorities: Vec<PolicyAuthority>,
+    threshold: usize,
+}
+
+#[derive(Debug)]
+struct PolicyAuthority {
+    name: String,
+    signer: Signer,
+    annotations: HashMap<String, String>,
+    require_rekor_bundle: bool,
+    predicate_types: Vec<String>,
+}
+
+#[derive(Debug)]
+enum Signer {
+    Key(CosignVerificationKey),
+    Keyless(Vec<CertificateIdentityVerifier>),
+}
+
+/// The outcome of the evaluation of a [`Policy`]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyEvaluation {
+    /// The name of the policy
+    pub policy: String,
+    pub satisfied_authorities: Vec<String>,
+    pub unsatisfied_authorities: Vec<String>,
+    /// The number of authorities that must be satisfied
+    pub threshold: usize,
+}
+
+impl PolicyEvaluation {
+    /// Returns `true` when enough authorities are satisfied
+    pub fn is_allowed(&self) -> bool {
+        self.satisfied_authorities.len() >= self.threshold
+    }
+}
+
+impl Policy {
+    /// Compile the policy described by `document`, reading its keys and
+    /// regular expressions
+    pub fn new(document: PolicyDocument) -> Result<Self> {
+        let spec = document.spec;
+        if spec.authorities.is_empty() {
+            return Err(policy_error("the policy has no authority"));
+        }
+        let threshold = spec.threshold.unwrap_or(spec.authorities.len());
+        if threshold == 0 || threshold > spec.authorities.len() {
+            return Err(policy_error(&format!(
+                "invalid threshold {threshold} for {} authorities",
+                spec.authorities.len()
+            )));
+        }
+
+        let images = spec
+            .images
+            .iter()
+            .map(|image| glob_to_regex(&image.glob))
+            .collect::<Result<Vec<_>>>()?;
+        let authorities = spec
+            .authorities
+            .into_iter()
+            .enumerate()
+            .map(|(index, authority)| PolicyAuthority::new(index, authority))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Policy {
+            name: document.metadata.name,
+            images,
+            authorities,
+            threshold,
+        })
+    }
+
+    /// Read the JSON encoded policy document
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let document: PolicyDocument = serde_json::from_str(raw)
+            .map_err(|e| policy_error(&format!("cannot parse the policy: {e}")))?;
+        Self::new(document)
+    }
+
+    /// Read the YAML encoded policy document
+    #[cfg(feature = "policy-yaml")]
+    pub fn from_yaml(raw: &str) -> Result<Self> {
+        let document: PolicyDocument = serde_yaml::from_str(raw)
+            .map_err(|e| policy_error(&format!("cannot parse the policy: {e}")))?;
+        Self::new(document)
+    }
+
+    /// The name of the policy
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns `true` when `image` is covered by the policy
+    pub fn applies_to(&self, image: &OciReference) -> bool {
+        let image = image.whole();
+        self.images.is_empty() || self.images.iter().any(|glob| glob.is_match(&image))
+    }
+
+    /// Evaluate the policy against the trusted signatures and attestations
+    /// of an image
+    pub fn evaluate(
+        &self,
+        signature_layers: &[SignatureLayer],
+        attestations: &[VerifiedAttestation],
+    ) -> PolicyEvaluation {
+        let (satisfied, unsatisfied): (Vec<_>, Vec<_>) = self
+            .authorities
+            .iter()
+            .partition(|authority| authority.is_satisfied(signature_layers, attestations));
+
+        let evaluation = PolicyEvaluation {
+            policy: self.name.clone(),
+            satisfied_authorities: satisfied.iter().map(|a| a.name.clone()).collect(),
+            unsatisfied_authorities: unsatisfied.iter().map(|a| a.name.clone()).collect(),
+            threshold: self.threshold,
+        };
+        debug!(?evaluation, "policy evaluated");
+        evaluation
+    }
+
+    /// Fetch the trusted signatures and attestations of `image`, then
+    /// evaluate the policy against them.
+    ///
+    /// The signatures and attestations that cannot be fetched are
+    /// considered missing, hence the authorities relying on them are not
+    /// satisfied.
+    pub async fn verify(
+        &self,
+        client: &mut Client,
+        auth: &Auth,
+        image: &OciReference,
+    ) -> Result<PolicyEvaluation> {
+        let (cosign_image, source_image_digest) = client.triangulate(image, auth).await?;
+
+        let mut signature_layers = Vec::new();
+        if self
+            .authorities
+            .iter()
+            .any(|authority| authority.predicate_types.is_empty())
+        {
+            match client
+                .trusted_signature_layers(auth, &source_image_digest, &cosign_image)
+                .await
+            {
+                Ok(layers) => signature_layers = layers,
+                Err(e) => warn!(error = ?e, ?image, "cannot fetch the signatures"),
+            }
+        }
+
+        let mut attestations = Vec::new();
+        let attesters: Vec<&PolicyAuthority> = self
+            .authorities
+            .iter()
+            .filter(|authority| !authority.predicate_types.is_empty())
+            .collect();
+        if !attesters.is_empty() {
+            // the attestations signed with a key are verified only with it
+            let mut keys = vec![None];
+            keys.extend(
+                attesters
+                    .iter()
+                    .filter_map(|authority| match &authority.signer {
+                        Signer::Key(key) => Some(Some(key)),
+                        Signer::Keyless(_) => None,
+                    }),
+            );
+            for key in keys {
+                match client.verify_attestations(auth, image, key).await {
+                    Ok(found) => attestations.extend(found),
+                    Err(e) => warn!(error = ?e, ?image, "cannot fetch the attestations"),
+                }
+            }
+        }
+
+        Ok(self.evaluate(&signature_layers, &attestations))
+    }
+}
+
+impl PolicyAuthority {
+    fn new(index: usize, authority: Authority) -> Result<Self> {
+        let name = authority
+            .name
+            .unwrap_or_else(|| format!("authority-{index}"));
+        let signer = match (authority.key, authority.keyless) {
+            (Some(key), None) => Signer::Key(
+                CosignVerificationKey::try_from_pem(key.data.as_bytes()).map_err(|e| {
+                    policy_error(&format!("invalid key of the authority {name}: {e}"))
+                })?,
+            ),
+            (None, Some(keyless)) => {
+                if keyless.identities.is_empty() {
+                    return Err(policy_error(&format!(
+                        "the authority {name} has no keyless identity"
+                    )));
+                }
+                Signer::Keyless(
+                    keyless
+                        .identities
+                        .iter()
+                        .map(|identity| identity_verifier(&name, identity))
+                        .collect::<Result<Vec<_>>>()?,
+                )
+            }
+            _ => {
+                return Err(policy_error(&format!(
+                    "the authority {name} must have either a key or keyless identities"
+                )))
+            }
+        };
+
+        Ok(PolicyAuthority {
+            name,
+            signer,
+            annotations: authority.annotations,
+            require_rekor_bundle: authority.require_rekor_bundle,
+            predicate_types: authority
+                .attestations
+                .into_iter()
+                .map(|attestation| attestation.predicate_type)
+                .collect(),
+        })
+    }
+
+    fn is_satisfied(
+        &self,
+        signature_layers: &[SignatureLayer],
+        attestations: &[VerifiedAttestation],
+    ) -> bool {
+        if self.predicate_types.is_empty() {
+            return signature_layers.iter().any(|layer| {
+                (!self.require_rekor_bundle || layer.bundle.is_some())
+                    && self.signer.signed_layer(layer)
+                    && layer
+                        .simple_signing
+                        .satisfies_annotations(&self.annotations)
+            });
+        }
+
+        self.predicate_types.iter().all(|predicate_type| {
+            attestations.iter().any(|attestation| {
+                attestation.statement.predicate_type == *predicate_type
+                    && (!self.require_rekor_bundle || attestation.bundle.is_some())
+                    && self.signer.signed_attestation(attestation)
+            })
+        })
+    }
+}
+
+impl Signer {
+    fn signed_layer(&self, layer: &SignatureLayer) -> bool {
+        match self {
+            Signer::Key(key) => layer.is_signed_by_key(key),
+            Signer::Keyless(identities) => match &layer.certificate_signature {
+                Some(signature) => identities
+                    .iter()
+                    .any(|identity| identity.matches(signature)),
+                None => false,
+            },
+        }
+    }
+
+    fn signed_attestation(&self, attestation: &VerifiedAttestation) -> bool {
+        match self {
+            Signer::Key(key) => attestation.envelope.verify(key).is_ok(),
+            Signer::Keyless(identities) => match &attestation.certificate_signature {
+                Some(signature) => identities
+                    .iter()
+                    .any(|identity| identity.matches(signature)),
+                None => false,
+            },
+        }
+    }
+}
+
+fn identity_verifier(authority: &str, identity: &Identity) -> Result<CertificateIdentityVerifier> {
+    let matcher = |exact: &Option<String>, regex: &Option<String>, field: &str| match (exact, regex)
+    {
+        (Some(exact), None) => Ok(IdentityMatcher::exact(exact)),
+        (None, Some(regex)) => IdentityMatcher::regex(regex),
+        _ => Err(policy_error(&format!(
+            "the identities of the authority {authority} must have either {field} or {field}RegExp"
+        ))),
+    };
+
+    Ok(CertificateIdentityVerifier::new(
+        matcher(&identity.subject, &identity.subject_reg_exp, "subject")?,
+        matcher(&identity.issuer, &identity.issuer_reg_exp, "issuer")?,
+    ))
+}
+
+/// Convert the glob of an [`ImagePattern`] to an anchored regular expression
+fn glob_to_regex(glob: &str) -> Result<Regex> {
+    let mut pattern = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                pattern.push_str(".*");
+            }
+            '*' => pattern.push_str("[^/]*"),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+
+    Regex::new(&pattern).map_err(|e| policy_error(&format!("invalid image glob {glob}: {e}")))
+}
+
+fn policy_error(message: &str) -> SigstoreError {
+    SigstoreError::PolicyError(message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::signature_layers::tests::{
+        build_correct_signature_layer_with_certificate,
+        build_correct_signature_layer_without_bundle,
+    };
+    use crate::cosign::signature_layers::CertificateSubject;
+    use serde_json::json;
+
+    const WORKFLOW: &str =
+        "https://github.com/octocat/hello-world/.github/workflows/release.yml@refs/tags/v1.0";
+    const GITHUB_ISSUER: &str = "https://token.actions.githubusercontent.com";
+
+    fn keyless_layer() -> SignatureLayer {
+        let mut layer = build_correct_signature_layer_with_certificate();
+        if let Some(signature) = layer.certificate_signature.as_mut() {
+            signature.subject = CertificateSubject::Uri(WORKFLOW.to_string());
+            signature.issuer = Some(GITHUB_ISSUER.to_string());
+        }
+        layer
+    }
+
+    #[test]
+    fn evaluate_policy() {
+        let (key_layer, key) = build_correct_signature_layer_without_bundle();
+        let policy = Policy::from_json(
+            &json!({
+                "apiVersion": "policy.sigstore.dev/v1beta1",
+                "kind": "ClusterImagePolicy",
+                "metadata": {"name": "release"},
+                "spec": {
+                    "authorities": [
+                        {"name": "release-key", "key": {"data": key.to_pem().unwrap()}},
+                        {
+                            "keyless": {"identities": [{
+                                "issuer": GITHUB_ISSUER,
+                                "subjectRegExp": r"^https://github\.com/octocat/hello-world/",
+                            }]},
+                        },
+                    ],
+                },
+            })
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(policy.name(), "release");
+
+        let evaluation = policy.evaluate(&[key_layer.clone(), keyless_layer()], &[]);
+        assert!(evaluation.is_allowed());
+        assert_eq!(
+            evaluation.satisfied_authorities,
+            vec!["release-key", "authority-1"]
+        );
+
+        // both authorities are required by default
+        let evaluation = policy.evaluate(&[key_layer], &[]);
+        assert!(!evaluation.is_allowed());
+        assert_eq!(evaluation.unsatisfied_authorities, vec!["authority-1"]);
+    }
+
+    #[test]
+    fn evaluate_authority_requirements() {
+        let (layer, key) = build_correct_signature_layer_without_bundle();
+        let policy = |mut authority: serde_json::Value| {
+            authority["key"] = json!({"data": key.to_pem().unwrap()});
+            Policy::from_json(&json!({"spec": {"authorities": [authority]}}).to_string()).unwrap()
+        };
+
+        assert!(policy(json!({}))
+            .evaluate(&[layer.clone()], &[])
+            .is_allowed());
+        assert!(!policy(json!({"annotations": {"env": "prod"}}))
+            .evaluate(&[layer.clone()], &[])
+            .is_allowed());
+        assert!(!policy(json!({"requireRekorBundle": true}))
+            .evaluate(&[layer.clone()], &[])
+            .is_allowed());
+        assert!(!policy(json!({
+            "attestations": [{"predicateType": "https://slsa.dev/provenance/v0.2"}],
+        }))
+        .evaluate(&[layer], &[])
+        .is_allowed());
+    }
+
+    #[test]
+    fn reject_invalid_policies() {
+        let (_, key) = build_correct_signature_layer_without_bundle();
+        let key = json!({"data": key.to_pem().unwrap()});
+        let identity = json!({"issuer": GITHUB_ISSUER, "subject": WORKFLOW});
+
+        for spec in [
+            json!({"authorities": []}),
+            json!({"authorities": [{}]}),
+            json!({"authorities": [{"key": key, "keyless": {"identities": [identity]}}]}),
+            json!({"authorities": [{"key": {"data": "not a key"}}]}),
+            json!({"authorities": [{"keyless": {"identities": []}}]}),
+            json!({"authorities": [{"keyless": {"identities": [{"issuer": GITHUB_ISSUER}]}}]}),
+            json!({"authorities": [{"keyless": {"identities": [{
+                "issuer": GITHUB_ISSUER,
+                "subjectRegExp": "(unclosed",
+            }]}}]}),
+            json!({"authorities": [{"key": key}], "threshold": 0}),
+            json!({"authorities": [{"key": key}], "threshold": 2}),
+        ] {
+            assert!(
+                Policy::from_json(&json!({ "spec": spec }).to_string()).is_err(),
+                "{} should be rejected",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn match_images() {
+        let (_, key) = build_correct_signature_layer_without_bundle();
+        let policy = Policy::from_json(
+            &json!({"spec": {
+                "images": [{"glob": "registry.example.com/release/*"}, {"glob": "ghcr.io/octocat/**"}],
+                "authorities": [{"key": {"data": key.to_pem().unwrap()}}],
+            }})
+            .to_string(),
+        )
+        .unwrap();
+
+        let applies = |image: &str| policy.applies_to(&image.parse().unwrap());
+        assert!(applies("registry.example.com/release/app:v1"));
+        assert!(!applies("registry.example.com/release/team/app:v1"));
+        assert!(applies("ghcr.io/octocat/team/app:v1"));
+        assert!(!applies("ghcr.io/other/app:v1"));
+    }
+}
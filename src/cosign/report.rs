@@ -0,0 +1,300 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Machine-readable reports about the verification of an artifact.
+//!
+//! A [`VerificationReport`] describes the verified artifact, the trusted
+//! signatures with the identities of their signers and their transparency log
+//! entries, plus the outcome of each verification constraint.
+//!
+//! The JSON serialization of the report is versioned: fields are never
+//! removed or renamed within the same [`VERIFICATION_REPORT_VERSION`], hence
+//! CI gates can safely parse it.
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::report::VerificationReport;
+//! use sigstore::cosign::SignatureLayer;
+//! use sigstore::cosign::verification_constraint::VerificationConstraintVec;
+//! use sigstore::registry::OciReference;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! # let image: OciReference = "registry.local/busybox:latest".parse()?;
+//! # let source_image_digest = "sha256:digest";
+//! # let trusted_layers: Vec<SignatureLayer> = vec![];
+//! # let constraints: VerificationConstraintVec = vec![];
+//! let report = VerificationReport::new(
+//!     &image,
+//!     source_image_digest,
+//!     &trusted_layers,
+//!     &constraints,
+//! );
+//! println!("{}", report.to_json()?);
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use super::signature_layers::CertificateSubject;
+use super::verification_constraint::VerificationConstraintVec;
+use super::SignatureLayer;
+use crate::errors::{Result, SigstoreError};
+use crate::registry::OciReference;
+
+/// The version of the report format produced by this crate
+pub const VERIFICATION_REPORT_VERSION: &str = "1";
+
+/// The outcome of the verification of an artifact
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationReport {
+    /// The version of the report format
+    pub version: String,
+    /// The verified artifact
+    pub artifact: ArtifactReport,
+    /// `true` when all the verification constraints are satisfied
+    pub verified: bool,
+    /// The error that prevented the verification, if any
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub error: Option<String>,
+    /// The trusted signatures of the artifact
+    pub signatures: Vec<SignatureReport>,
+    /// The outcome of each verification constraint
+    pub policy: Vec<ConstraintReport>,
+}
+
+/// The artifact being verified
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArtifactReport {
+    /// The reference to the artifact, as provided by the user
+    pub reference: String,
+    /// The digest of the artifact, when it could be resolved
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub digest: Option<String>,
+}
+
+/// A trusted signature
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureReport {
+    /// The digest of the OCI layer holding the signature
+    pub layer_digest: String,
+    /// The identity of the signer, available for keyless signatures
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub identity: Option<IdentityReport>,
+    /// The transparency log entry of the signature
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub tlog_entry: Option<TlogEntryReport>,
+}
+
+/// The identity of a signer, taken from its Fulcio certificate
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityReport {
+    /// Either `email` or `uri`
+    pub subject_type: String,
+    /// The subject of the certificate
+    pub subject: String,
+    /// The OIDC issuer that authenticated the signer
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub issuer: Option<String>,
+}
+
+/// A reference to a Rekor entry
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TlogEntryReport {
+    /// The index of the entry inside of the log
+    pub log_index: i64,
+    /// The ID of the log
+    pub log_id: String,
+    /// When the entry was integrated into the log, as a UNIX timestamp
+    pub integrated_time: i64,
+}
+
+/// The outcome of a verification constraint
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConstraintReport {
+    /// A description of the constraint
+    pub constraint: String,
+    /// `true` when at least one signature satisfies the constraint
+    pub satisfied: bool,
+    /// The digests of the layers satisfying the constraint
+    pub satisfied_by: Vec<String>,
+}
+
+impl From<&SignatureLayer> for SignatureReport {
+    fn from(sl: &SignatureLayer) -> Self {
+        let identity = sl.certificate_signature.as_ref().map(|cs| {
+            let (subject_type, subject) = match &cs.subject {
+                CertificateSubject::Email(e) => ("email", e.clone()),
+                CertificateSubject::Uri(u) => ("uri", u.clone()),
+            };
+            IdentityReport {
+                subject_type: subject_type.to_string(),
+                subject,
+                issuer: cs.issuer.clone(),
+            }
+        });
+        let tlog_entry = sl.bundle.as_ref().map(|b| TlogEntryReport {
+            log_index: b.payload.log_index,
+            log_id: b.payload.log_id.clone(),
+            integrated_time: b.payload.integrated_time,
+        });
+
+        SignatureReport {
+            layer_digest: sl.oci_digest.clone(),
+            identity,
+            tlog_entry,
+        }
+    }
+}
+
+impl VerificationReport {
+    /// Build the report by evaluating `constraints` against the trusted
+    /// signature layers of the artifact.
+    ///
+    /// The outcome of the verification matches the one of
+    /// [`verify_constraints`](crate::cosign::verify_constraints).
+    pub fn new(
+        image: &OciReference,
+        source_image_digest: &str,
+        trusted_layers: &[SignatureLayer],
+        constraints: &VerificationConstraintVec,
+    ) -> Self {
+        let policy: Vec<ConstraintReport> = constraints
+            .iter()
+            .map(|c| {
+                let satisfied_by: Vec<String> = trusted_layers
+                    .iter()
+                    .filter(|sl| c.verify(sl).unwrap_or(false))
+                    .map(|sl| sl.oci_digest.clone())
+                    .collect();
+                ConstraintReport {
+                    constraint: format!("{c:?}"),
+                    satisfied: !satisfied_by.is_empty(),
+                    satisfied_by,
+                }
+            })
+            .collect();
+
+        VerificationReport {
+            version: VERIFICATION_REPORT_VERSION.to_string(),
+            artifact: ArtifactReport {
+                reference: image.whole(),
+                digest: Some(source_image_digest.to_string()),
+            },
+            verified: policy.iter().all(|c| c.satisfied),
+            error: None,
+            signatures: trusted_layers.iter().map(SignatureReport::from).collect(),
+            policy,
+        }
+    }
+
+    /// Build the report of a verification that could not be performed, for
+    /// example because the signatures could not be fetched
+    pub fn failed(image: &OciReference, digest: Option<&str>, error: &SigstoreError) -> Self {
+        VerificationReport {
+            version: VERIFICATION_REPORT_VERSION.to_string(),
+            artifact: ArtifactReport {
+                reference: image.whole(),
+                digest: digest.map(|d| d.to_string()),
+            },
+            verified: false,
+            error: Some(error.to_string()),
+            signatures: Vec::new(),
+            policy: Vec::new(),
+        }
+    }
+
+    /// Serialize the report to JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| {
+            SigstoreError::UnexpectedError(format!("Cannot serialize verification report: {e}"))
+        })
+    }
+
+    /// Load a report produced by [`VerificationReport::to_json`]
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let report: VerificationReport = serde_json::from_str(raw).map_err(|e| {
+            SigstoreError::UnexpectedError(format!("Cannot parse verification report: {e}"))
+        })?;
+        if report.version != VERIFICATION_REPORT_VERSION {
+            return Err(SigstoreError::UnexpectedError(format!(
+                "Unsupported verification report version: {}",
+                report.version
+            )));
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::signature_layers::tests::{
+        build_correct_signature_layer_with_certificate,
+        build_correct_signature_layer_without_bundle,
+    };
+    use crate::cosign::verification_constraint::{AnnotationVerifier, PublicKeyVerifier};
+    use crate::crypto::tests::PUBLIC_KEY;
+    use serde_json::Value;
+
+    #[test]
+    fn report_constraint_outcomes() {
+        let image: OciReference = "registry.local/busybox:latest".parse().unwrap();
+        let (sl_with_key, _) = build_correct_signature_layer_without_bundle();
+        let sl_with_cert = build_correct_signature_layer_with_certificate();
+        let layers = vec![sl_with_key.clone(), sl_with_cert.clone()];
+
+        let constraints: VerificationConstraintVec = vec![
+            Box::new(PublicKeyVerifier::try_from(PUBLIC_KEY.as_bytes()).unwrap()),
+            Box::new(AnnotationVerifier {
+                annotations: [("env".to_string(), "prod".to_string())].into(),
+            }),
+        ];
+
+        let report = VerificationReport::new(&image, "sha256:digest", &layers, &constraints);
+        assert!(!report.verified);
+        assert_eq!(report.signatures.len(), 2);
+        assert!(report.signatures[0].identity.is_none());
+        assert!(report.signatures[1].identity.is_some());
+        assert!(report.signatures[1].tlog_entry.is_some());
+        assert_eq!(report.policy[0].satisfied_by, vec![sl_with_key.oci_digest]);
+        assert!(!report.policy[1].satisfied);
+
+        let json = report.to_json().unwrap();
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], VERIFICATION_REPORT_VERSION);
+        assert_eq!(value["artifact"]["digest"], "sha256:digest");
+        assert!(value.get("error").is_none());
+        assert_eq!(VerificationReport::from_json(&json).unwrap(), report);
+    }
+
+    #[test]
+    fn report_failed_verification() {
+        let image: OciReference = "registry.local/busybox:latest".parse().unwrap();
+        let report =
+            VerificationReport::failed(&image, None, &SigstoreError::SigstoreNoVerifiedLayer);
+        assert!(!report.verified);
+        assert!(report.error.is_some());
+
+        let mut value: Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        value["version"] = Value::String("0".to_string());
+        assert!(VerificationReport::from_json(&value.to_string()).is_err());
+    }
+}
@@ -0,0 +1,288 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! SBOMs attached to images, like the ones of `cosign attach sbom`.
+//!
+//! The SBOM documents are stored as the single layer of the image tagged
+//! `sha256-<digest>.sbom`, next to the image they describe. The media type
+//! of the layer tells the format of the document: [`Sbom::new`] detects it
+//! from the contents of the document, hence supply-chain tools can push the
+//! files produced by `syft` or `trivy` as they are.
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::sbom::Sbom;
+//! use sigstore::registry::{Auth, OciReference};
+//!
+//! # async fn doc() -> sigstore::errors::Result<()> {
+//! let mut client = sigstore::cosign::ClientBuilder::default().build()?;
+//! let image: OciReference = "registry.local/busybox:latest".parse()?;
+//!
+//! let sbom = Sbom::new(std::fs::read("sbom.spdx.json")?)?;
+//! client.attach_sbom(&Auth::Anonymous, &image, &sbom).await?;
+//!
+//! for sbom in client.fetch_sboms(&Auth::Anonymous, &image).await? {
+//!     println!("{}", sbom.format);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! **Note well:** the SBOMs are not signed. Use attestations to get SBOMs
+//! that can be trusted.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use oci_distribution::client::ImageLayer;
+use serde_json::Value;
+
+use super::attestation::predicate::{CycloneDxBom, SpdxDocument};
+use super::DownloadedLayer;
+use crate::errors::{Result, SigstoreError};
+
+/// The formats of the SBOM documents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbomFormat {
+    /// SPDX, tag-value encoded
+    Spdx,
+    /// SPDX, JSON encoded
+    SpdxJson,
+    /// SPDX, XML encoded
+    SpdxXml,
+    /// CycloneDX, JSON encoded
+    CycloneDxJson,
+    /// CycloneDX, XML encoded
+    CycloneDxXml,
+    /// The native format of `syft`
+    SyftJson,
+}
+
+impl SbomFormat {
+    /// The media type of the layers holding documents of this format
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            SbomFormat::Spdx => "text/spdx",
+            SbomFormat::SpdxJson => "text/spdx+json",
+            SbomFormat::SpdxXml => "text/spdx+xml",
+            SbomFormat::CycloneDxJson => "application/vnd.cyclonedx+json",
+            SbomFormat::CycloneDxXml => "application/vnd.cyclonedx+xml",
+            SbomFormat::SyftJson => "application/vnd.syft+json",
+        }
+    }
+
+    /// The format of the layers with the given media type, if any.
+    /// `application/vnd.cyclonedx`, used by old versions of cosign, is the
+    /// XML encoding of CycloneDX.
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "text/spdx" => Some(SbomFormat::Spdx),
+            "text/spdx+json" => Some(SbomFormat::SpdxJson),
+            "text/spdx+xml" => Some(SbomFormat::SpdxXml),
+            "application/vnd.cyclonedx+json" => Some(SbomFormat::CycloneDxJson),
+            "application/vnd.cyclonedx" | "application/vnd.cyclonedx+xml" => {
+                Some(SbomFormat::CycloneDxXml)
+            }
+            "application/vnd.syft+json" => Some(SbomFormat::SyftJson),
+            _ => None,
+        }
+    }
+
+    /// Detect the format of `document` from its contents
+    pub fn detect(document: &[u8]) -> Result<Self> {
+        let text = std::str::from_utf8(document)
+            .map_err(|_| SigstoreError::SbomError("the document is not UTF-8".to_string()))?
+            .trim_start_matches('\u{feff}')
+            .trim_start();
+
+        if text.starts_with('{') {
+            let json: Value = serde_json::from_str(text)
+                .map_err(|e| SigstoreError::SbomError(format!("invalid JSON document: {e}")))?;
+            if json.get("spdxVersion").is_some() {
+                return Ok(SbomFormat::SpdxJson);
+            }
+            if json.get("bomFormat").and_then(Value::as_str) == Some("CycloneDX") {
+                return Ok(SbomFormat::CycloneDxJson);
+            }
+            if json.pointer("/descriptor/name").and_then(Value::as_str) == Some("syft") {
+                return Ok(SbomFormat::SyftJson);
+            }
+        } else if text.starts_with('<') {
+            if text.contains("cyclonedx.org/schema/bom") {
+                return Ok(SbomFormat::CycloneDxXml);
+            }
+            if text.contains("spdx") {
+                return Ok(SbomFormat::SpdxXml);
+            }
+        } else if text.lines().any(|line| line.starts_with("SPDXVersion:")) {
+            return Ok(SbomFormat::Spdx);
+        }
+
+        Err(SigstoreError::SbomError(
+            "unknown SBOM format, expected SPDX, CycloneDX or syft".to_string(),
+        ))
+    }
+}
+
+impl fmt::Display for SbomFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SbomFormat::Spdx => "spdx",
+            SbomFormat::SpdxJson => "spdx-json",
+            SbomFormat::SpdxXml => "spdx-xml",
+            SbomFormat::CycloneDxJson => "cyclonedx-json",
+            SbomFormat::CycloneDxXml => "cyclonedx-xml",
+            SbomFormat::SyftJson => "syft-json",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// An SBOM document, together with its format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sbom {
+    /// The format of the document
+    pub format: SbomFormat,
+    /// The raw document
+    pub data: Vec<u8>,
+}
+
+impl Sbom {
+    /// Create an SBOM from `document`, detecting its format
+    pub fn new(document: Vec<u8>) -> Result<Self> {
+        Ok(Sbom {
+            format: SbomFormat::detect(&document)?,
+            data: document,
+        })
+    }
+
+    /// Create an SBOM from `document`, which has the given format
+    pub fn with_format(format: SbomFormat, document: Vec<u8>) -> Self {
+        Sbom {
+            format,
+            data: document,
+        }
+    }
+
+    /// Decode the document, which must be a JSON encoded SPDX document
+    pub fn spdx(&self) -> Result<SpdxDocument> {
+        self.decode(SbomFormat::SpdxJson)
+    }
+
+    /// Decode the document, which must be a JSON encoded CycloneDX BOM
+    pub fn cyclonedx(&self) -> Result<CycloneDxBom> {
+        self.decode(SbomFormat::CycloneDxJson)
+    }
+
+    fn decode<T: serde::de::DeserializeOwned>(&self, expected: SbomFormat) -> Result<T> {
+        if self.format != expected {
+            return Err(SigstoreError::SbomError(format!(
+                "expected a {expected} document, found a {} one",
+                self.format
+            )));
+        }
+        serde_json::from_slice(&self.data)
+            .map_err(|e| SigstoreError::SbomError(format!("invalid {expected} document: {e}")))
+    }
+
+    /// The layer holding the document
+    pub(crate) fn to_image_layer(&self) -> ImageLayer {
+        ImageLayer::new(
+            self.data.clone(),
+            self.format.media_type().to_string(),
+            None,
+        )
+    }
+}
+
+impl TryFrom<DownloadedLayer> for Sbom {
+    type Error = SigstoreError;
+
+    /// The format is the one of the media type of the layer, or the one
+    /// detected when the media type is not an SBOM one
+    fn try_from(layer: DownloadedLayer) -> Result<Self> {
+        match SbomFormat::from_media_type(&layer.media_type) {
+            Some(format) => Ok(Sbom::with_format(format, layer.data)),
+            None => Sbom::new(layer.data),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::constants::SIGSTORE_SBOM_MEDIA_TYPES;
+    use std::collections::HashMap;
+
+    #[test]
+    fn detect_formats() {
+        let spdx = br#"{"spdxVersion":"SPDX-2.3","SPDXID":"SPDXRef-DOCUMENT","name":"busybox"}"#;
+        let cyclonedx = br#"{"bomFormat":"CycloneDX","specVersion":"1.5","version":1}"#;
+        let syft = br#"{"artifacts":[],"descriptor":{"name":"syft","version":"0.98.0"}}"#;
+        let cyclonedx_xml = br#"<?xml version="1.0"?><bom xmlns="http://cyclonedx.org/schema/bom/1.5" version="1"/>"#;
+        let spdx_tag_value = b"SPDXVersion: SPDX-2.3\nDataLicense: CC0-1.0\n";
+
+        assert_eq!(SbomFormat::detect(spdx).unwrap(), SbomFormat::SpdxJson);
+        assert_eq!(
+            SbomFormat::detect(cyclonedx).unwrap(),
+            SbomFormat::CycloneDxJson
+        );
+        assert_eq!(SbomFormat::detect(syft).unwrap(), SbomFormat::SyftJson);
+        assert_eq!(
+            SbomFormat::detect(cyclonedx_xml).unwrap(),
+            SbomFormat::CycloneDxXml
+        );
+        assert_eq!(
+            SbomFormat::detect(spdx_tag_value).unwrap(),
+            SbomFormat::Spdx
+        );
+        assert!(matches!(
+            SbomFormat::detect(br#"{"name":"busybox"}"#),
+            Err(SigstoreError::SbomError(_))
+        ));
+
+        let sbom = Sbom::new(spdx.to_vec()).unwrap();
+        assert_eq!(sbom.spdx().unwrap().name, "busybox");
+        assert!(sbom.cyclonedx().is_err());
+    }
+
+    #[test]
+    fn media_types_round_trip() {
+        for media_type in SIGSTORE_SBOM_MEDIA_TYPES {
+            let format = SbomFormat::from_media_type(media_type).unwrap();
+            if media_type != "application/vnd.cyclonedx" {
+                assert_eq!(format.media_type(), media_type);
+            }
+        }
+    }
+
+    #[test]
+    fn sbom_from_downloaded_layer() {
+        let cyclonedx = br#"{"bomFormat":"CycloneDX","specVersion":"1.5","version":1}"#;
+        let layer = DownloadedLayer {
+            digest: "sha256:abc".to_string(),
+            media_type: "application/octet-stream".to_string(),
+            annotations: HashMap::new(),
+            data: cyclonedx.to_vec(),
+        };
+
+        let sbom = Sbom::try_from(layer).unwrap();
+        assert_eq!(sbom.format, SbomFormat::CycloneDxJson);
+        assert_eq!(sbom.cyclonedx().unwrap().spec_version, "1.5");
+        assert_eq!(
+            sbom.to_image_layer().media_type,
+            "application/vnd.cyclonedx+json"
+        );
+    }
+}
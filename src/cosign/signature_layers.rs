@@ -13,12 +13,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use const_oid::ObjectIdentifier;
 use digest::Digest;
 use oci_distribution::client::ImageLayer;
-use pkcs8::der::Decode;
+use pkcs8::der::{Decode, Encode};
 use serde::Serialize;
 use std::convert::TryFrom;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
 use std::{collections::HashMap, fmt};
 use tracing::{debug, info, warn};
 use x509_cert::ext::pkix::name::GeneralName;
@@ -27,16 +29,18 @@ use x509_cert::Certificate;
 
 use super::bundle::Bundle;
 use super::constants::{
-    SIGSTORE_BUNDLE_ANNOTATION, SIGSTORE_CERT_ANNOTATION, SIGSTORE_GITHUB_WORKFLOW_NAME_OID,
-    SIGSTORE_GITHUB_WORKFLOW_REF_OID, SIGSTORE_GITHUB_WORKFLOW_REPOSITORY_OID,
-    SIGSTORE_GITHUB_WORKFLOW_SHA_OID, SIGSTORE_GITHUB_WORKFLOW_TRIGGER_OID, SIGSTORE_ISSUER_OID,
-    SIGSTORE_OCI_MEDIA_TYPE, SIGSTORE_SIGNATURE_ANNOTATION,
+    SIGSTORE_BUNDLE_ANNOTATION, SIGSTORE_CERT_ANNOTATION, SIGSTORE_OCI_MEDIA_TYPE,
+    SIGSTORE_SIGNATURE_ANNOTATION,
 };
+use super::identity::SignerIdentity;
+use crate::crypto::certificate_extensions::CertificateExtensions;
 use crate::crypto::certificate_pool::CertificatePool;
+use crate::crypto::certificate_transparency::SctPolicy;
+use crate::crypto::trusted_root::TrustedRoot;
 use crate::registry::oci_reference::OciReference;
 use crate::{
     cosign::simple_signing::SimpleSigning,
-    crypto::{self, CosignVerificationKey, Signature},
+    crypto::{self, certificate_transparency, CosignVerificationKey, Signature},
     errors::{Result, SigstoreError},
 };
 
@@ -61,6 +65,15 @@ pub struct CertificateSignature {
     pub github_workflow_repository: Option<String>,
     /// The Git ref of the commit that triggered the GitHub workflow (e.g. `refs/tags/v0.9.9`)
     pub github_workflow_ref: Option<String>,
+    /// All the Fulcio extensions of the certificate, including the ones
+    /// describing the CI build that produced the signature
+    pub extensions: CertificateExtensions,
+    /// The SHA-256 fingerprint of the public key embedded into the
+    /// certificate, in the `sha256:<hex>` format
+    pub key_fingerprint: String,
+    /// The PEM encoded certificate
+    #[serde(skip_serializing)]
+    pub certificate: String,
 }
 
 impl fmt::Display for CertificateSignature {
@@ -211,7 +224,7 @@ impl SignatureLayer {
     pub fn new_unsigned(image_ref: &OciReference, manifest_digest: &str) -> Result<Self> {
         let simple_signing = SimpleSigning::new(image_ref, manifest_digest);
 
-        let payload = serde_json::to_vec(&simple_signing)?;
+        let payload = simple_signing.to_canonical_json()?;
         let digest = format!("sha256:{:x}", sha2::Sha256::digest(&payload));
         Ok(SignatureLayer {
             simple_signing,
@@ -223,6 +236,42 @@ impl SignatureLayer {
         })
     }
 
+    /// Build the layer of the signature image holding this signature, with
+    /// the annotations written by cosign: the signature, the certificate of
+    /// the signer and the Rekor bundle, when available.
+    ///
+    /// The payload is the one that has been signed, when known, otherwise
+    /// the serialization of the Simple Signing object.
+    pub fn to_image_layer(&self) -> Result<ImageLayer> {
+        let mut annotations = HashMap::new();
+        if let Some(signature) = &self.signature {
+            annotations.insert(SIGSTORE_SIGNATURE_ANNOTATION.to_string(), signature.clone());
+        }
+        if let Some(certificate_signature) = &self.certificate_signature {
+            annotations.insert(
+                SIGSTORE_CERT_ANNOTATION.to_string(),
+                certificate_signature.certificate.clone(),
+            );
+        }
+        if let Some(bundle) = &self.bundle {
+            annotations.insert(
+                SIGSTORE_BUNDLE_ANNOTATION.to_string(),
+                serde_json::to_string(bundle)?,
+            );
+        }
+
+        let data = if self.raw_data.is_empty() {
+            serde_json::to_vec(&self.simple_signing)?
+        } else {
+            self.raw_data.clone()
+        };
+        Ok(ImageLayer::new(
+            data,
+            SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+            Some(annotations),
+        ))
+    }
+
     /// Create a SignatureLayer that can be considered trusted.
     ///
     /// Params:
@@ -232,9 +281,18 @@ impl SignatureLayer {
     ///   * `source_image_digest`: the digest of the object that we're trying
     ///      to verify. This is **not** the digest of the signature itself.
     ///   * `rekor_pub_key`: the public key of Rekor, used to verify `bundle`
-    ///     entries
+    ///     entries offline. The entry recorded by the bundle must be the one
+    ///     of the signature of the layer
     ///   * `fulcio_pub_key`: the public key provided by Fulcio's certificate.
     ///     Used to verify the `certificate` entries
+    ///   * `trusted_root`: the trust material with its validity windows. When
+    ///     provided, it takes precedence over `rekor_pub_key` and
+    ///     `fulcio_cert_pool`: the bundle and the certificate are verified with
+    ///     the material that was in use when the signature was integrated into
+    ///     Rekor. The SCT embedded into the certificate is verified with its CT
+    ///     log keys
+    ///   * `sct_policy`: whether the certificates lacking a verifiable SCT
+    ///     are rejected
     ///
     /// **Note well:** the certificate and bundle added to the final SignatureLayer
     /// object are to be considered **trusted** and **verified**, according to
@@ -245,6 +303,8 @@ impl SignatureLayer {
         source_image_digest: &str,
         rekor_pub_key: Option<&CosignVerificationKey>,
         fulcio_cert_pool: Option<&CertificatePool>,
+        trusted_root: Option<&TrustedRoot>,
+        sct_policy: SctPolicy,
     ) -> Result<SignatureLayer> {
         if descriptor.media_type != SIGSTORE_OCI_MEDIA_TYPE {
             return Err(SigstoreError::SigstoreMediaTypeNotFoundError);
@@ -274,11 +334,29 @@ impl SignatureLayer {
         let annotations = descriptor.annotations.clone().unwrap_or_default();
 
         let signature = Self::get_signature_from_annotations(&annotations)?;
-        let bundle = Self::get_bundle_from_annotations(&annotations, rekor_pub_key)?;
+        let bundle = match trusted_root {
+            Some(trusted_root) => Self::get_bundle_from_annotations_at(&annotations, trusted_root)?,
+            None => Self::get_bundle_from_annotations(&annotations, rekor_pub_key)?,
+        };
+        // the bundle must be about this very signature
+        if let Some(bundle) = &bundle {
+            bundle.verify_entry(&signature, &layer.data)?;
+        }
+        let fulcio_cert_pool_at_signing_time = match (trusted_root, bundle.as_ref()) {
+            (Some(trusted_root), Some(bundle)) => {
+                trusted_root.fulcio_cert_pool_at(bundle.payload.integrated_time)?
+            }
+            _ => None,
+        };
         let certificate_signature = Self::get_certificate_signature_from_annotations(
             &annotations,
-            fulcio_cert_pool,
+            match trusted_root {
+                Some(_) => fulcio_cert_pool_at_signing_time.as_ref(),
+                None => fulcio_cert_pool,
+            },
             bundle.as_ref(),
+            trusted_root,
+            sct_policy,
         );
 
         Ok(SignatureLayer {
@@ -299,7 +377,7 @@ impl SignatureLayer {
         Ok(signature)
     }
 
-    fn get_bundle_from_annotations(
+    pub(crate) fn get_bundle_from_annotations(
         annotations: &HashMap<String, String>,
         rekor_pub_key: Option<&CosignVerificationKey>,
     ) -> Result<Option<Bundle>> {
@@ -316,10 +394,22 @@ impl SignatureLayer {
         Ok(bundle)
     }
 
-    fn get_certificate_signature_from_annotations(
+    pub(crate) fn get_bundle_from_annotations_at(
+        annotations: &HashMap<String, String>,
+        trusted_root: &TrustedRoot,
+    ) -> Result<Option<Bundle>> {
+        annotations
+            .get(SIGSTORE_BUNDLE_ANNOTATION)
+            .map(|value| Bundle::new_verified_at(value, trusted_root))
+            .transpose()
+    }
+
+    pub(crate) fn get_certificate_signature_from_annotations(
         annotations: &HashMap<String, String>,
         fulcio_cert_pool: Option<&CertificatePool>,
         bundle: Option<&Bundle>,
+        trusted_root: Option<&TrustedRoot>,
+        sct_policy: SctPolicy,
     ) -> Option<CertificateSignature> {
         let cert_raw = match annotations.get(SIGSTORE_CERT_ANNOTATION) {
             Some(value) => value,
@@ -348,8 +438,13 @@ impl SignatureLayer {
             }
         };
 
-        match CertificateSignature::from_certificate(cert_raw.as_bytes(), fulcio_cert_pool, bundle)
-        {
+        match CertificateSignature::from_certificate(
+            cert_raw.as_bytes(),
+            fulcio_cert_pool,
+            bundle.payload.integrated_time,
+            trusted_root,
+            sct_policy,
+        ) {
             Ok(certificate_signature) => Some(certificate_signature),
             Err(e) => {
                 info!(reason=?e, "Ignoring certificate annotation");
@@ -358,6 +453,14 @@ impl SignatureLayer {
         }
     }
 
+    /// The normalized identity of the signer, available when the layer holds
+    /// a verified certificate
+    pub fn signer_identity(&self) -> Option<SignerIdentity> {
+        self.certificate_signature
+            .as_ref()
+            .map(SignerIdentity::from)
+    }
+
     /// Given a Cosign public key, check whether this Signature Layer has been
     /// signed by it
     pub(crate) fn is_signed_by_key(&self, verification_key: &CosignVerificationKey) -> bool {
@@ -393,126 +496,234 @@ pub(crate) fn build_signature_layers(
     layers: &[oci_distribution::client::ImageLayer],
     rekor_pub_key: Option<&CosignVerificationKey>,
     fulcio_cert_pool: Option<&CertificatePool>,
+    trusted_root: Option<&TrustedRoot>,
+    sct_policy: SctPolicy,
 ) -> Result<Vec<SignatureLayer>> {
-    let mut signature_layers: Vec<SignatureLayer> = Vec::new();
+    build_signature_layers_concurrently(
+        manifest,
+        source_image_digest,
+        layers,
+        rekor_pub_key,
+        fulcio_cert_pool,
+        trusted_root,
+        sct_policy,
+        1,
+    )
+}
 
-    for manifest_layer in &manifest.layers {
-        let matching_layer: Option<&oci_distribution::client::ImageLayer> =
-            layers.iter().find(|l| {
-                let tmp: ImageLayer = (*l).clone();
-                tmp.sha256_digest() == manifest_layer.digest
-            });
-        if let Some(layer) = matching_layer {
-            match SignatureLayer::new(
-                manifest_layer,
-                layer,
-                source_image_digest,
-                rekor_pub_key,
-                fulcio_cert_pool,
-            ) {
-                Ok(sl) => signature_layers.push(sl),
-                Err(e) => {
-                    info!(error = ?e, "Skipping OCI layer because of error");
-                }
+/// Like [`build_signature_layers`], verifying up to `concurrency` layers at
+/// the same time. The layers are returned in the order of the manifest.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn build_signature_layers_concurrently(
+    manifest: &oci_distribution::manifest::OciImageManifest,
+    source_image_digest: &str,
+    layers: &[oci_distribution::client::ImageLayer],
+    rekor_pub_key: Option<&CosignVerificationKey>,
+    fulcio_cert_pool: Option<&CertificatePool>,
+    trusted_root: Option<&TrustedRoot>,
+    sct_policy: SctPolicy,
+    concurrency: usize,
+) -> Result<Vec<SignatureLayer>> {
+    let mut signature_layers: Vec<(usize, SignatureLayer)> = Vec::new();
+    verify_candidate_layers(
+        &candidate_layers(manifest, layers),
+        source_image_digest,
+        rekor_pub_key,
+        fulcio_cert_pool,
+        trusted_root,
+        sct_policy,
+        concurrency,
+        |index, signature_layer| {
+            match signature_layer {
+                Ok(sl) => signature_layers.push((index, sl)),
+                Err(e) => info!(error = ?e, "Skipping OCI layer because of error"),
             }
-        }
-    }
+            false
+        },
+    );
 
     if signature_layers.is_empty() {
         Err(SigstoreError::SigstoreNoVerifiedLayer)
     } else {
-        Ok(signature_layers)
+        signature_layers.sort_by_key(|(index, _)| *index);
+        Ok(signature_layers.into_iter().map(|(_, sl)| sl).collect())
+    }
+}
+
+/// The layers of the manifest, paired with their descriptor
+pub(crate) fn candidate_layers<'a>(
+    manifest: &'a oci_distribution::manifest::OciImageManifest,
+    layers: &'a [oci_distribution::client::ImageLayer],
+) -> Vec<(
+    &'a oci_distribution::manifest::OciDescriptor,
+    &'a oci_distribution::client::ImageLayer,
+)> {
+    manifest
+        .layers
+        .iter()
+        .filter_map(|manifest_layer| {
+            layers
+                .iter()
+                .find(|l| {
+                    let tmp: ImageLayer = (*l).clone();
+                    tmp.sha256_digest() == manifest_layer.digest
+                })
+                .map(|layer| (manifest_layer, layer))
+        })
+        .collect()
+}
+
+/// Verify the `candidates` and hand the outcome of each verification,
+/// together with the index of the candidate, to `visit`, until `visit`
+/// returns `true`.
+///
+/// When `concurrency` is greater than one, the candidates are verified by
+/// that many threads, and the outcomes are handed over in the order their
+/// verification completes. The threads stop picking new candidates
+/// once `visit` returned `true`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn verify_candidate_layers<F>(
+    candidates: &[(
+        &oci_distribution::manifest::OciDescriptor,
+        &oci_distribution::client::ImageLayer,
+    )],
+    source_image_digest: &str,
+    rekor_pub_key: Option<&CosignVerificationKey>,
+    fulcio_cert_pool: Option<&CertificatePool>,
+    trusted_root: Option<&TrustedRoot>,
+    sct_policy: SctPolicy,
+    concurrency: usize,
+    mut visit: F,
+) where
+    F: FnMut(usize, Result<SignatureLayer>) -> bool,
+{
+    let verify = |index: usize| {
+        let (descriptor, layer) = candidates[index];
+        SignatureLayer::new(
+            descriptor,
+            layer,
+            source_image_digest,
+            rekor_pub_key,
+            fulcio_cert_pool,
+            trusted_root,
+            sct_policy,
+        )
+    };
+
+    if concurrency <= 1 || candidates.len() <= 1 {
+        for index in 0..candidates.len() {
+            if visit(index, verify(index)) {
+                return;
+            }
+        }
+        return;
     }
+
+    let next = AtomicUsize::new(0);
+    let done = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..concurrency.min(candidates.len()) {
+            let sender = sender.clone();
+            let (next, done, verify) = (&next, &done, &verify);
+            scope.spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= candidates.len() {
+                        break;
+                    }
+                    if sender.send((index, verify(index))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        for (index, result) in receiver {
+            if visit(index, result) {
+                done.store(true, Ordering::Relaxed);
+                break;
+            }
+        }
+    });
 }
 
 impl CertificateSignature {
     /// Ensures the given certificate can be trusted, then extracts
     /// its details and returns them as a `CertificateSignature` object
+    ///
+    /// The certificate must have been valid at `integrated_time`, the
+    /// trusted time of the signature: the integrated time of its verified
+    /// Rekor bundle, or the time of a verified RFC 3161 timestamp.
+    ///
+    /// The embedded SCT of the certificate is verified with the keys of the
+    /// CT logs of `trusted_root`, the certificate is rejected when they are
+    /// not available, unless `sct_policy` is [`SctPolicy::Skip`].
     pub(crate) fn from_certificate(
         cert_raw: &[u8],
         fulcio_cert_pool: &CertificatePool,
-        trusted_bundle: &Bundle,
+        integrated_time: i64,
+        trusted_root: Option<&TrustedRoot>,
+        sct_policy: SctPolicy,
     ) -> Result<Self> {
         let pem = pem::parse(cert_raw)?;
         let cert = Certificate::from_der(&pem.contents)
             .map_err(|e| SigstoreError::X509Error(format!("parse from der: {e}")))?;
-        let integrated_time = trusted_bundle.payload.integrated_time;
 
         // ensure the certificate has been issued by Fulcio
-        fulcio_cert_pool.verify_pem_cert(cert_raw)?;
+        let issuer_der = fulcio_cert_pool.verify_pem_cert_issuer(cert_raw)?;
 
         crypto::certificate::is_trusted(&cert, integrated_time)?;
 
+        // ensure the certificate has been logged by a CT log
+        if sct_policy == SctPolicy::Require {
+            let trusted_root = trusted_root.filter(|tr| tr.has_ct_logs()).ok_or_else(|| {
+                SigstoreError::SctVerificationError(
+                    "the keys of the CT logs are not available".to_string(),
+                )
+            })?;
+            let issuer = Certificate::from_der(&issuer_der)
+                .map_err(|e| SigstoreError::X509Error(format!("parse from der: {e}")))?;
+            let issuer_spki = issuer
+                .tbs_certificate
+                .subject_public_key_info
+                .to_vec()
+                .map_err(|e| SigstoreError::X509Error(format!("encode public key: {e}")))?;
+            certificate_transparency::verify_embedded_sct(&cert, &issuer_spki, trusted_root)?;
+        }
+
         let subject = CertificateSubject::from_certificate(&cert)?;
         let verification_key =
             CosignVerificationKey::try_from(&cert.tbs_certificate.subject_public_key_info)?;
+        let key_fingerprint = key_fingerprint(&cert)?;
 
-        let issuer = get_cert_extension_by_oid(&cert, SIGSTORE_ISSUER_OID, "Issuer")?;
-
-        let github_workflow_trigger = get_cert_extension_by_oid(
-            &cert,
-            SIGSTORE_GITHUB_WORKFLOW_TRIGGER_OID,
-            "GitHub Workflow trigger",
-        )?;
-
-        let github_workflow_sha = get_cert_extension_by_oid(
-            &cert,
-            SIGSTORE_GITHUB_WORKFLOW_SHA_OID,
-            "GitHub Workflow sha",
-        )?;
-
-        let github_workflow_name = get_cert_extension_by_oid(
-            &cert,
-            SIGSTORE_GITHUB_WORKFLOW_NAME_OID,
-            "GitHub Workflow name",
-        )?;
-
-        let github_workflow_repository = get_cert_extension_by_oid(
-            &cert,
-            SIGSTORE_GITHUB_WORKFLOW_REPOSITORY_OID,
-            "GitHub Workflow repository",
-        )?;
-
-        let github_workflow_ref = get_cert_extension_by_oid(
-            &cert,
-            SIGSTORE_GITHUB_WORKFLOW_REF_OID,
-            "GitHub Workflow ref",
-        )?;
+        let extensions = CertificateExtensions::from_certificate(&cert)?;
 
         Ok(CertificateSignature {
             verification_key,
-            issuer,
-            github_workflow_trigger,
-            github_workflow_sha,
-            github_workflow_name,
-            github_workflow_repository,
-            github_workflow_ref,
+            issuer: extensions.issuer.clone(),
+            github_workflow_trigger: extensions.github_workflow_trigger.clone(),
+            github_workflow_sha: extensions.github_workflow_sha.clone(),
+            github_workflow_name: extensions.github_workflow_name.clone(),
+            github_workflow_repository: extensions.github_workflow_repository.clone(),
+            github_workflow_ref: extensions.github_workflow_ref.clone(),
+            extensions,
+            key_fingerprint,
             subject,
+            certificate: String::from_utf8_lossy(cert_raw).to_string(),
         })
     }
 }
 
-fn get_cert_extension_by_oid(
-    cert: &Certificate,
-    ext_oid: ObjectIdentifier,
-    ext_oid_name: &str,
-) -> Result<Option<String>> {
-    cert.tbs_certificate
-        .extensions
-        .as_ref()
-        .ok_or(SigstoreError::X509Error(
-            "Certificate's extension is empty".to_string(),
-        ))?
-        .iter()
-        .find(|ext| ext.extn_id == ext_oid)
-        .map(|ext| {
-            String::from_utf8(ext.extn_value.to_vec()).map_err(|_| {
-                SigstoreError::X509Error(format!(
-                    "Certificate's extension Sigstore {ext_oid_name} is not UTF8 compatible"
-                ))
-            })
-        })
-        .transpose()
+/// Compute the SHA-256 fingerprint of the public key of `cert`
+fn key_fingerprint(cert: &Certificate) -> Result<String> {
+    let spki_der = cert
+        .tbs_certificate
+        .subject_public_key_info
+        .to_vec()
+        .map_err(|e| SigstoreError::X509Error(format!("encode public key: {e}")))?;
+    Ok(format!("sha256:{:x}", sha2::Sha256::digest(spki_der)))
 }
 
 impl CertificateSubject {
@@ -632,9 +843,14 @@ oXqqo/C9QnOHTto=
 -----END CERTIFICATE-----"#;
 
         let fulcio_cert_pool = get_fulcio_cert_pool();
-        let certificate_signature =
-            CertificateSignature::from_certificate(cert_raw.as_bytes(), &fulcio_cert_pool, &bundle)
-                .expect("Cannot create certificate signature");
+        let certificate_signature = CertificateSignature::from_certificate(
+            cert_raw.as_bytes(),
+            &fulcio_cert_pool,
+            bundle.payload.integrated_time,
+            None,
+            SctPolicy::Skip,
+        )
+        .expect("Cannot create certificate signature");
 
         SignatureLayer {
             simple_signing: serde_json::from_value(ss_value.clone()).unwrap(),
@@ -646,6 +862,32 @@ oXqqo/C9QnOHTto=
         }
     }
 
+    #[test]
+    fn signature_layer_to_image_layer() {
+        let signature_layer = build_correct_signature_layer_with_certificate();
+        let image_layer = signature_layer.to_image_layer().unwrap();
+        assert_eq!(image_layer.data, signature_layer.raw_data);
+        assert_eq!(image_layer.media_type, SIGSTORE_OCI_MEDIA_TYPE);
+
+        let annotations = image_layer.annotations.unwrap();
+        assert_eq!(
+            annotations.get(SIGSTORE_SIGNATURE_ANNOTATION),
+            signature_layer.signature.as_ref()
+        );
+        assert!(annotations[SIGSTORE_CERT_ANNOTATION].starts_with("-----BEGIN CERTIFICATE-----"));
+        let bundle: Bundle =
+            serde_json::from_str(&annotations[SIGSTORE_BUNDLE_ANNOTATION]).unwrap();
+        assert_eq!(Some(bundle), signature_layer.bundle);
+
+        let (signature_layer, _) = build_correct_signature_layer_without_bundle();
+        let annotations = signature_layer
+            .to_image_layer()
+            .unwrap()
+            .annotations
+            .unwrap();
+        assert_eq!(annotations.len(), 1);
+    }
+
     #[test]
     fn is_signed_by_key_fails_when_signature_is_not_valid() {
         let (signature_layer, _) = build_correct_signature_layer_without_bundle();
@@ -685,6 +927,8 @@ JsB89BPhZYch0U0hKANx5TY+ncrm0s8bfJxxHoenAEFhwhuXeb4PqIrtoQ==
             "source_image_digest is not relevant now",
             Some(&rekor_pub_key),
             Some(&fulcio_cert_pool),
+            None,
+            SctPolicy::Require,
         )
         .expect_err("Didn't get an error");
 
@@ -717,6 +961,8 @@ JsB89BPhZYch0U0hKANx5TY+ncrm0s8bfJxxHoenAEFhwhuXeb4PqIrtoQ==
             "source_image_digest is not relevant now",
             Some(&rekor_pub_key),
             Some(&fulcio_cert_pool),
+            None,
+            SctPolicy::Require,
         )
         .expect_err("Didn't get an error");
 
@@ -750,6 +996,8 @@ JsB89BPhZYch0U0hKANx5TY+ncrm0s8bfJxxHoenAEFhwhuXeb4PqIrtoQ==
             "source_image_digest is not relevant now",
             Some(&rekor_pub_key),
             Some(&fulcio_cert_pool),
+            None,
+            SctPolicy::Require,
         )
         .expect_err("Didn't get an error");
 
@@ -760,6 +1008,165 @@ JsB89BPhZYch0U0hKANx5TY+ncrm0s8bfJxxHoenAEFhwhuXeb4PqIrtoQ==
         assert!(found, "Got a different error type: {}", error);
     }
 
+    #[test]
+    fn new_signature_layer_verifies_bundle_offline() {
+        use crate::cosign::bundle::tests::sign_bundle;
+        use crate::crypto::SigningScheme;
+
+        let image_digest =
+            "sha256:5f481572d088dc4023afb35fced9530ced3d9b03bf7299c6f492163cb9f0452e";
+        let image: OciReference = "registry.example.com/app:v1".parse().unwrap();
+        let payload = SignatureLayer::new_unsigned(&image, image_digest)
+            .unwrap()
+            .raw_data;
+        let layer = oci_distribution::client::ImageLayer::new(
+            payload.clone(),
+            SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+            None,
+        );
+        let rekor = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let rekor_pub_key = rekor.to_verification_key().unwrap();
+
+        let descriptor =
+            |signature: &str, bundle: &Bundle| oci_distribution::manifest::OciDescriptor {
+                media_type: SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+                digest: layer.sha256_digest(),
+                annotations: Some(
+                    [
+                        (
+                            SIGSTORE_SIGNATURE_ANNOTATION.to_string(),
+                            signature.to_string(),
+                        ),
+                        (
+                            SIGSTORE_BUNDLE_ANNOTATION.to_string(),
+                            serde_json::to_string(bundle).unwrap(),
+                        ),
+                    ]
+                    .into(),
+                ),
+                ..Default::default()
+            };
+
+        let bundle = sign_bundle(&rekor, "c2lnbmF0dXJl", &payload);
+        let signature_layer = SignatureLayer::new(
+            &descriptor("c2lnbmF0dXJl", &bundle),
+            &layer,
+            image_digest,
+            Some(&rekor_pub_key),
+            None,
+            None,
+            SctPolicy::Require,
+        )
+        .expect("the bundle cannot be verified");
+        assert_eq!(signature_layer.bundle, Some(bundle.clone()));
+
+        // a genuine bundle, but of another signature
+        let error = SignatureLayer::new(
+            &descriptor("YW5vdGhlciBzaWduYXR1cmU=", &bundle),
+            &layer,
+            image_digest,
+            Some(&rekor_pub_key),
+            None,
+            None,
+            SctPolicy::Require,
+        )
+        .expect_err("Didn't get an error");
+        assert!(matches!(error, SigstoreError::RekorEntryMismatchError(_)));
+
+        // a bundle not signed by Rekor
+        let error = SignatureLayer::new(
+            &descriptor("c2lnbmF0dXJl", &bundle),
+            &layer,
+            image_digest,
+            Some(&get_rekor_public_key()),
+            None,
+            None,
+            SctPolicy::Require,
+        );
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn build_signature_layers_concurrently_keeps_manifest_order() {
+        let image_digest =
+            "sha256:5f481572d088dc4023afb35fced9530ced3d9b03bf7299c6f492163cb9f0452e";
+        let mut layers: Vec<oci_distribution::client::ImageLayer> = (0..6)
+            .map(|i| {
+                let image: OciReference = format!("registry.example.com/app:v{i}").parse().unwrap();
+                oci_distribution::client::ImageLayer::new(
+                    SignatureLayer::new_unsigned(&image, image_digest)
+                        .unwrap()
+                        .raw_data,
+                    SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+                    Some(
+                        [(
+                            SIGSTORE_SIGNATURE_ANNOTATION.to_string(),
+                            "c2lnbmF0dXJl".to_string(),
+                        )]
+                        .into(),
+                    ),
+                )
+            })
+            .collect();
+        // a layer that cannot be trusted, which is skipped
+        layers.push(oci_distribution::client::ImageLayer::new(
+            "not a signature".as_bytes().to_vec(),
+            "text/plain".to_string(),
+            None,
+        ));
+        let config = oci_distribution::client::Config::oci_v1("{}".as_bytes().to_vec(), None);
+        let manifest = oci_distribution::manifest::OciImageManifest::build(&layers, &config, None);
+
+        let serial = build_signature_layers(
+            &manifest,
+            image_digest,
+            &layers,
+            None,
+            None,
+            None,
+            SctPolicy::Require,
+        )
+        .unwrap();
+        let concurrent = build_signature_layers_concurrently(
+            &manifest,
+            image_digest,
+            &layers,
+            None,
+            None,
+            None,
+            SctPolicy::Require,
+            4,
+        )
+        .unwrap();
+        assert_eq!(serial.len(), 6);
+        assert_eq!(
+            serial.iter().map(|sl| &sl.oci_digest).collect::<Vec<_>>(),
+            concurrent
+                .iter()
+                .map(|sl| &sl.oci_digest)
+                .collect::<Vec<_>>()
+        );
+
+        // the verification stops at the first accepted layer
+        let mut visited = 0;
+        verify_candidate_layers(
+            &candidate_layers(&manifest, &layers),
+            image_digest,
+            None,
+            None,
+            None,
+            SctPolicy::Require,
+            4,
+            |_, _| {
+                visited += 1;
+                true
+            },
+        );
+        assert_eq!(visited, 1);
+    }
+
     #[test]
     fn get_signature_from_annotations_success() {
         let mut annotations: HashMap<String, String> = HashMap::new();
@@ -803,6 +1210,8 @@ JsB89BPhZYch0U0hKANx5TY+ncrm0s8bfJxxHoenAEFhwhuXeb4PqIrtoQ==
             &annotations,
             Some(&fulcio_cert_pool),
             None,
+            None,
+            SctPolicy::Require,
         );
 
         assert!(actual.is_none());
@@ -821,6 +1230,8 @@ JsB89BPhZYch0U0hKANx5TY+ncrm0s8bfJxxHoenAEFhwhuXeb4PqIrtoQ==
             &annotations,
             Some(&fulcio_cert_pool),
             None,
+            None,
+            SctPolicy::Require,
         );
         assert!(cert.is_none());
     }
@@ -838,6 +1249,8 @@ JsB89BPhZYch0U0hKANx5TY+ncrm0s8bfJxxHoenAEFhwhuXeb4PqIrtoQ==
             &annotations,
             None,
             Some(&bundle),
+            None,
+            SctPolicy::Require,
         );
         assert!(cert.is_none());
     }
@@ -865,7 +1278,6 @@ JsB89BPhZYch0U0hKANx5TY+ncrm0s8bfJxxHoenAEFhwhuXeb4PqIrtoQ==
     }
 
     // Testing CertificateSignature
-    use crate::cosign::bundle::Payload;
     use crate::crypto::tests::{generate_certificate, CertGenerationOptions};
     use crate::crypto::SigningScheme;
     use chrono::{Duration, Utc};
@@ -899,19 +1311,14 @@ JsB89BPhZYch0U0hKANx5TY+ncrm0s8bfJxxHoenAEFhwhuXeb4PqIrtoQ==
         let cert_pool = CertificatePool::from_certificates(&certs).unwrap();
 
         let integrated_time = Utc::now().checked_sub_signed(Duration::minutes(1)).unwrap();
-        let bundle = Bundle {
-            signed_entry_timestamp: "not relevant".to_string(),
-            payload: Payload {
-                body: "not relevant".to_string(),
-                integrated_time: integrated_time.timestamp(),
-                log_index: 0,
-                log_id: "not relevant".to_string(),
-            },
-        };
-
-        let certificate_signature =
-            CertificateSignature::from_certificate(&issued_cert_pem, &cert_pool, &bundle)
-                .expect("Didn't expect an error");
+        let certificate_signature = CertificateSignature::from_certificate(
+            &issued_cert_pem,
+            &cert_pool,
+            integrated_time.timestamp(),
+            None,
+            SctPolicy::Skip,
+        )
+        .expect("Didn't expect an error");
 
         let expected_issuer = match certificate_signature.subject.clone() {
             CertificateSubject::Email(mail) => mail == expected_email,
@@ -926,6 +1333,30 @@ JsB89BPhZYch0U0hKANx5TY+ncrm0s8bfJxxHoenAEFhwhuXeb4PqIrtoQ==
         Ok(())
     }
 
+    #[test]
+    fn certificate_signature_from_certificate_requires_sct() -> anyhow::Result<()> {
+        let ca_data = generate_certificate(None, CertGenerationOptions::default())?;
+        let issued_cert = generate_certificate(Some(&ca_data), CertGenerationOptions::default())?;
+        let issued_cert_pem = issued_cert.cert.to_pem()?;
+
+        let certs = vec![crate::registry::Certificate::try_from(ca_data.cert).unwrap()];
+        let cert_pool = CertificatePool::from_certificates(&certs).unwrap();
+
+        // the keys of the CT logs are not known
+        let integrated_time = Utc::now().checked_sub_signed(Duration::minutes(1)).unwrap();
+        let error = CertificateSignature::from_certificate(
+            &issued_cert_pem,
+            &cert_pool,
+            integrated_time.timestamp(),
+            None,
+            SctPolicy::Require,
+        )
+        .expect_err("Didn't get an error");
+        assert!(matches!(error, SigstoreError::SctVerificationError(_)));
+
+        Ok(())
+    }
+
     #[test]
     fn certificate_signature_from_certificate_using_uri() -> anyhow::Result<()> {
         let expected_url = "https://sigstore.dev/test".to_string();
@@ -946,19 +1377,14 @@ JsB89BPhZYch0U0hKANx5TY+ncrm0s8bfJxxHoenAEFhwhuXeb4PqIrtoQ==
         let cert_pool = CertificatePool::from_certificates(&certs).unwrap();
 
         let integrated_time = Utc::now().checked_sub_signed(Duration::minutes(1)).unwrap();
-        let bundle = Bundle {
-            signed_entry_timestamp: "not relevant".to_string(),
-            payload: Payload {
-                body: "not relevant".to_string(),
-                integrated_time: integrated_time.timestamp(),
-                log_index: 0,
-                log_id: "not relevant".to_string(),
-            },
-        };
-
-        let certificate_signature =
-            CertificateSignature::from_certificate(&issued_cert_pem, &cert_pool, &bundle)
-                .expect("Didn't expect an error");
+        let certificate_signature = CertificateSignature::from_certificate(
+            &issued_cert_pem,
+            &cert_pool,
+            integrated_time.timestamp(),
+            None,
+            SctPolicy::Skip,
+        )
+        .expect("Didn't expect an error");
 
         let expected_issuer = match certificate_signature.subject.clone() {
             CertificateSubject::Uri(url) => url == expected_url,
@@ -992,18 +1418,14 @@ JsB89BPhZYch0U0hKANx5TY+ncrm0s8bfJxxHoenAEFhwhuXeb4PqIrtoQ==
         let cert_pool = CertificatePool::from_certificates(&certs).unwrap();
 
         let integrated_time = Utc::now().checked_sub_signed(Duration::minutes(1)).unwrap();
-        let bundle = Bundle {
-            signed_entry_timestamp: "not relevant".to_string(),
-            payload: Payload {
-                body: "not relevant".to_string(),
-                integrated_time: integrated_time.timestamp(),
-                log_index: 0,
-                log_id: "not relevant".to_string(),
-            },
-        };
-
-        let error = CertificateSignature::from_certificate(&issued_cert_pem, &cert_pool, &bundle)
-            .expect_err("Didn't get an error");
+        let error = CertificateSignature::from_certificate(
+            &issued_cert_pem,
+            &cert_pool,
+            integrated_time.timestamp(),
+            None,
+            SctPolicy::Skip,
+        )
+        .expect_err("Didn't get an error");
         assert!(matches!(
             error,
             SigstoreError::CertificateWithoutSubjectAlternativeName
@@ -0,0 +1,850 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Keyless signing sessions.
+//!
+//! Signing an image in keyless mode is done in three steps: Fulcio issues a
+//! short lived certificate for an ephemeral key, the signature is uploaded
+//! to Rekor and then pushed to the registry. A [`SigningSession`] holds the
+//! ephemeral key and its certificate, so that a signing operation
+//! interrupted by a failure of Rekor or of the registry can be resumed,
+//! while the certificate is still valid, without asking Fulcio for a new
+//! certificate.
+//!
+//! The same session can sign many artifacts while its certificate is valid,
+//! each signature being recorded by its own Rekor entry. This avoids a round
+//! trip to Fulcio per artifact when signing hundreds of images inside of a
+//! single CI run.
+//!
+//! During a migration between trust models, [`SigningSession::sign_image_with_key`]
+//! attaches both a keyless signature and a signature made with a long-lived
+//! key to an image, so that it can be verified by the consumers of either
+//! model.
+//!
+//! Keyless signatures permanently publish the identity of the signer inside
+//! of Rekor. Interactive tools can register an [`UploadConfirmation`], to
+//! warn the user and abort when the user declines, before anything is
+//! uploaded.
+//!
+//! The state of a session can be persisted, with the private key encrypted,
+//! to resume the operation later on, even from another process:
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::signing_session::{SigningSession, SigningSessionState};
+//! use sigstore::cosign::ClientBuilder;
+//! use sigstore::crypto::SigningScheme;
+//! use sigstore::fulcio::{FulcioClient, TokenProvider, FULCIO_ROOT};
+//! use sigstore::registry::{Auth, OciReference};
+//! use sigstore::rekor::apis::configuration::Configuration;
+//!
+//! # async fn doc(token_provider: TokenProvider) -> sigstore::errors::Result<()> {
+//! let mut client = ClientBuilder::default().build()?;
+//! let rekor_config = Configuration::default();
+//! let fulcio = FulcioClient::new(url::Url::parse(FULCIO_ROOT)?, token_provider);
+//!
+//! let mut session = SigningSession::new(fulcio, SigningScheme::default()).await?;
+//! for tag in ["v1", "v2", "v3"] {
+//!     let image: OciReference = format!("registry.example.com/app:{tag}").parse()?;
+//!     if let Err(e) = session
+//!         .sign_image(&mut client, &Auth::Anonymous, &rekor_config, &image)
+//!         .await
+//!     {
+//!         println!("cannot sign {image}: {e}");
+//!     }
+//! }
+//!
+//! if !session.pending().is_empty() {
+//!     // keep the work done so far
+//!     let state = serde_json::to_string(&session.to_state(b"password")?)?;
+//!
+//!     // ...later on
+//!     let state: SigningSessionState = serde_json::from_str(&state)?;
+//!     let mut session = SigningSession::from_state(state, b"password")?;
+//!     session
+//!         .resume(&mut client, &Auth::Anonymous, &rekor_config)
+//!         .await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use chrono::{DateTime, Utc};
+use pkcs8::der::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use tracing::debug;
+use x509_cert::Certificate;
+
+use super::bundle::{Bundle, SignedArtifactBundle};
+use super::client::Client;
+use super::constants::{
+    SIGSTORE_BUNDLE_ANNOTATION, SIGSTORE_CERT_ANNOTATION, SIGSTORE_CHAIN_ANNOTATION,
+    SIGSTORE_OCI_MEDIA_TYPE, SIGSTORE_SIGNATURE_ANNOTATION,
+};
+use super::{CosignCapabilities, SignatureLayer};
+use crate::crypto::signing_key::SigStoreKeyPair;
+use crate::crypto::{SigStoreSigner, SigningScheme};
+use crate::errors::{Result, SigstoreError};
+use crate::fulcio::FulcioClient;
+use crate::registry::{Auth, OciReference, PushResponse};
+use crate::rekor::apis::configuration::Configuration;
+use crate::rekor::apis::entries_api;
+use crate::rekor::entries::identity::certificate_subjects;
+use crate::rekor::models::hashedrekord::{AlgorithmKind, Data, Hash, PublicKey, Signature, Spec};
+use crate::rekor::models::proposed_entry::HASHEDREKORD_API_VERSION;
+use crate::rekor::models::ProposedEntry;
+
+const PEM_CERTIFICATE_END: &str = "-----END CERTIFICATE-----";
+
+/// The warning shown by cosign before uploading a keyless signature, can be
+/// presented to the users by the [`UploadConfirmation`] implementations
+pub const TRANSPARENCY_LOG_NOTICE: &str = "Note that there may be personally identifiable information associated with this signed artifact. \
+This may include the email address associated with the account with which you authenticate. \
+This information will be used for signing this artifact and will be stored in public transparency logs and cannot be removed later.";
+
+/// What is about to be published inside of the transparency log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadRequest {
+    /// The identities of the signer, taken from the certificate issued by
+    /// Fulcio, e.g. an email address
+    pub identities: Vec<String>,
+    /// The signed artifact: the reference of an image, or the digest of a
+    /// blob
+    pub artifact: String,
+    /// The URL of the transparency log
+    pub log_url: String,
+}
+
+impl fmt::Display for UploadRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the signature of {} by {} is about to be published to {}",
+            self.artifact,
+            self.identities.join(", "),
+            self.log_url
+        )
+    }
+}
+
+/// A trait that can be implemented to confirm the uploads to the
+/// transparency log
+#[async_trait(?Send)]
+pub trait UploadConfirmation {
+    /// Invoked before a signature is uploaded to the transparency log.
+    ///
+    /// The upload is aborted, with a
+    /// [`TransparencyLogUploadDeclined`](SigstoreError::TransparencyLogUploadDeclined)
+    /// error, when `false` is returned. Errors abort the upload too.
+    async fn confirm(&self, request: &UploadRequest) -> Result<bool>;
+}
+
+/// An ephemeral key, together with the certificate issued by Fulcio for it
+pub struct SigningSession {
+    signer: SigStoreSigner,
+    signing_scheme: SigningScheme,
+    /// The PEM encoded certificates returned by Fulcio, starting from the
+    /// certificate of the ephemeral key
+    certificates: Vec<String>,
+    not_after: DateTime<Utc>,
+    /// The identities certified by the certificate of the ephemeral key
+    identities: Vec<String>,
+    pending: Vec<PendingSignature>,
+    confirmation: Option<Box<dyn UploadConfirmation>>,
+}
+
+/// A signature that has not been pushed to the registry yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingSignature {
+    /// The signed image
+    pub image: String,
+    /// Where the signature is pushed, e.g.
+    /// `registry.example.com/app:sha256-<digest>.sig`
+    pub target: String,
+    /// The signed simple signing payload
+    pub payload: String,
+    /// The base64 encoded signature of `payload`
+    pub signature: String,
+    /// The bundle of the Rekor entry, `None` until the signature has been
+    /// uploaded to Rekor
+    pub bundle: Option<Bundle>,
+    /// The base64 encoded signature of `payload` made with a long-lived key,
+    /// pushed together with the keyless one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_signature: Option<String>,
+}
+
+/// The state of a [`SigningSession`], which can be persisted to resume the
+/// session later on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SigningSessionState {
+    /// The signing scheme of the ephemeral key, e.g. `ECDSA_P256_SHA256_ASN1`
+    pub signing_scheme: String,
+    /// The ephemeral private key, as an encrypted PEM
+    pub encrypted_private_key: String,
+    /// The PEM encoded certificate chain issued by Fulcio
+    pub certificate_chain: String,
+    /// The signatures that were not pushed when the state was saved
+    #[serde(default)]
+    pub pending: Vec<PendingSignature>,
+}
+
+impl SigningSession {
+    /// Start a new session, asking Fulcio for the certificate of a new
+    /// ephemeral key
+    pub async fn new(fulcio: FulcioClient, signing_scheme: SigningScheme) -> Result<Self> {
+        let (signer, cert) = fulcio.request_cert(signing_scheme).await?;
+        Self::from_parts(signer, signing_scheme, &cert.to_string())
+    }
+
+    /// Create a session from an ephemeral key and the PEM encoded
+    /// certificate chain issued for it. The first certificate of the chain
+    /// must be the one of the key.
+    pub fn from_parts(
+        signer: SigStoreSigner,
+        signing_scheme: SigningScheme,
+        certificate_chain: &str,
+    ) -> Result<Self> {
+        let certificates = split_certificates(certificate_chain);
+        let leaf = certificates
+            .first()
+            .ok_or_else(|| session_error("the certificate chain is empty"))?;
+        let leaf = pem::parse(leaf)?;
+        let leaf = Certificate::from_der(&leaf.contents)
+            .map_err(|e| SigstoreError::X509Error(e.to_string()))?;
+
+        let certified_key = leaf
+            .tbs_certificate
+            .subject_public_key_info
+            .to_vec()
+            .map_err(|e| SigstoreError::X509Error(e.to_string()))?;
+        if signer.public_key_to_der()? != certified_key {
+            return Err(session_error(
+                "the certificate doesn't certify the ephemeral key",
+            ));
+        }
+
+        let identities = certificate_subjects(&leaf)?;
+        Ok(SigningSession {
+            signer,
+            signing_scheme,
+            certificates,
+            identities,
+            not_after: leaf
+                .tbs_certificate
+                .validity
+                .not_after
+                .to_system_time()
+                .into(),
+            pending: Vec::new(),
+            confirmation: None,
+        })
+    }
+
+    /// Resume a session from its persisted state
+    pub fn from_state(state: SigningSessionState, password: &[u8]) -> Result<Self> {
+        let signing_scheme = SigningScheme::try_from(state.signing_scheme.as_str())
+            .map_err(SigstoreError::SigningSessionError)?;
+        let signer =
+            SigStoreKeyPair::from_encrypted_pem(state.encrypted_private_key.as_bytes(), password)?
+                .to_sigstore_signer(&signing_scheme)?;
+        let mut session = Self::from_parts(signer, signing_scheme, &state.certificate_chain)?;
+        session.pending = state.pending;
+        Ok(session)
+    }
+
+    /// The state of the session, the private key is encrypted with
+    /// `password`
+    pub fn to_state(&self, password: &[u8]) -> Result<SigningSessionState> {
+        let encrypted_private_key = self
+            .signer
+            .to_sigstore_keypair()?
+            .private_key_to_encrypted_pem(password)?;
+        Ok(SigningSessionState {
+            signing_scheme: self.signing_scheme.to_string(),
+            encrypted_private_key: encrypted_private_key.to_string(),
+            certificate_chain: self.certificates.concat(),
+            pending: self.pending.clone(),
+        })
+    }
+
+    /// Ask `confirmation` before uploading each signature to the
+    /// transparency log
+    pub fn with_upload_confirmation(self, confirmation: Box<dyn UploadConfirmation>) -> Self {
+        Self {
+            confirmation: Some(confirmation),
+            ..self
+        }
+    }
+
+    /// The identities of the signer, taken from the certificate issued by
+    /// Fulcio
+    pub fn identities(&self) -> &[String] {
+        &self.identities
+    }
+
+    /// When the certificate of the ephemeral key expires
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.not_after
+    }
+
+    /// Whether the certificate of the ephemeral key can still be used to
+    /// create new signatures
+    pub fn is_valid(&self) -> bool {
+        Utc::now() < self.not_after
+    }
+
+    /// The signatures that have not been pushed yet
+    pub fn pending(&self) -> &[PendingSignature] {
+        &self.pending
+    }
+
+    /// Sign `image`, upload the signature to Rekor and push it to the
+    /// registry.
+    ///
+    /// When either Rekor or the registry fail, the signature is kept by the
+    /// session and can be completed with [`SigningSession::resume`]. Other
+    /// images can be signed in the meantime.
+    pub async fn sign_image(
+        &mut self,
+        client: &mut Client,
+        auth: &Auth,
+        rekor_config: &Configuration,
+        image: &OciReference,
+    ) -> Result<PushResponse> {
+        self.sign_image_internal(client, auth, rekor_config, image, None)
+            .await
+    }
+
+    /// Sign `image` both keylessly and with the long-lived key of `signer`,
+    /// then push the two signatures together. The keyless signature is
+    /// uploaded to Rekor, the other one is not.
+    ///
+    /// The image can be verified either with the identity of the signer or
+    /// with the public key of `signer`, which helps migrating from one
+    /// trust model to the other. Failures are handled like done by
+    /// [`SigningSession::sign_image`].
+    pub async fn sign_image_with_key(
+        &mut self,
+        client: &mut Client,
+        auth: &Auth,
+        rekor_config: &Configuration,
+        image: &OciReference,
+        signer: &SigStoreSigner,
+    ) -> Result<PushResponse> {
+        self.sign_image_internal(client, auth, rekor_config, image, Some(signer))
+            .await
+    }
+
+    async fn sign_image_internal(
+        &mut self,
+        client: &mut Client,
+        auth: &Auth,
+        rekor_config: &Configuration,
+        image: &OciReference,
+        key_signer: Option<&SigStoreSigner>,
+    ) -> Result<PushResponse> {
+        self.ensure_valid()?;
+
+        let (target, digest) = client.triangulate(image, auth).await?;
+        let layer = SignatureLayer::new_unsigned(image, &digest)?;
+        let signature = self.signer.sign(&layer.raw_data)?;
+        let key_signature = key_signer
+            .map(|signer| signer.sign(&layer.raw_data))
+            .transpose()?;
+        self.pending.push(PendingSignature {
+            image: image.whole(),
+            target: target.whole(),
+            payload: std::str::from_utf8(&layer.raw_data)?.to_string(),
+            signature: BASE64_STD_ENGINE.encode(signature),
+            bundle: None,
+            key_signature: key_signature.map(|signature| BASE64_STD_ENGINE.encode(signature)),
+        });
+
+        self.complete(client, auth, rekor_config, self.pending.len() - 1)
+            .await
+    }
+
+    /// Sign `blob` and upload the signature to Rekor, like done by
+    /// `cosign sign-blob --bundle`
+    pub async fn sign_blob(
+        &self,
+        rekor_config: &Configuration,
+        blob: &[u8],
+    ) -> Result<SignedArtifactBundle> {
+        self.ensure_valid()?;
+
+        let signature = BASE64_STD_ENGINE.encode(self.signer.sign(blob)?);
+        let artifact = format!("sha256:{}", hex::encode(Sha256::digest(blob)));
+        let rekor_bundle = self
+            .upload(rekor_config, &artifact, &signature, blob)
+            .await?;
+        Ok(SignedArtifactBundle {
+            base64_signature: signature,
+            cert: BASE64_STD_ENGINE.encode(&self.certificates[0]),
+            rekor_bundle,
+        })
+    }
+
+    /// Complete the pending signatures: upload them to Rekor, unless already
+    /// done, then push them to the registry.
+    ///
+    /// Stops at the first failure, the signatures that are not completed are
+    /// kept by the session.
+    pub async fn resume(
+        &mut self,
+        client: &mut Client,
+        auth: &Auth,
+        rekor_config: &Configuration,
+    ) -> Result<Vec<PushResponse>> {
+        let mut responses = Vec::with_capacity(self.pending.len());
+        while !self.pending.is_empty() {
+            responses.push(self.complete(client, auth, rekor_config, 0).await?);
+        }
+        Ok(responses)
+    }
+
+    /// Upload to Rekor and push the pending signature at `index`, the
+    /// signature is removed from the pending ones once pushed
+    async fn complete(
+        &mut self,
+        client: &mut Client,
+        auth: &Auth,
+        rekor_config: &Configuration,
+        index: usize,
+    ) -> Result<PushResponse> {
+        if self.pending[index].bundle.is_none() {
+            let uploaded = self
+                .upload(
+                    rekor_config,
+                    &self.pending[index].image,
+                    &self.pending[index].signature,
+                    self.pending[index].payload.as_bytes(),
+                )
+                .await;
+            let bundle = match uploaded {
+                Err(SigstoreError::TransparencyLogUploadDeclined) => {
+                    // there's nothing to resume
+                    self.pending.remove(index);
+                    return Err(SigstoreError::TransparencyLogUploadDeclined);
+                }
+                uploaded => uploaded?,
+            };
+            self.pending[index].bundle = Some(bundle);
+        }
+
+        // once uploaded, the bundle proves the signature was created while
+        // the certificate was valid: pushing doesn't require a valid
+        // certificate
+        let pending = &self.pending[index];
+        let target = OciReference::from_str(&pending.target)?;
+        let layers = signature_image_layers(pending, &self.certificates)?;
+        let response = client.push_layers(None, auth, &target, layers).await?;
+        debug!(image = %pending.image, target = %pending.target, "signature pushed");
+
+        self.pending.remove(index);
+        Ok(response)
+    }
+
+    /// Record the base64 encoded `signature` of `artifact` inside of Rekor,
+    /// returns the bundle of the new entry
+    async fn upload(
+        &self,
+        rekor_config: &Configuration,
+        artifact_name: &str,
+        signature: &str,
+        artifact: &[u8],
+    ) -> Result<Bundle> {
+        // Rekor rejects the certificates that are expired at the time the
+        // entry is integrated
+        self.ensure_valid()?;
+
+        if let Some(confirmation) = &self.confirmation {
+            let request = UploadRequest {
+                identities: self.identities.clone(),
+                artifact: artifact_name.to_string(),
+                log_url: rekor_config.base_path.clone(),
+            };
+            if !confirmation.confirm(&request).await? {
+                return Err(SigstoreError::TransparencyLogUploadDeclined);
+            }
+        }
+
+        let entry = entries_api::create_log_entry(
+            rekor_config,
+            proposed_entry(signature, artifact, &self.certificates[0]),
+        )
+        .await
+        .map_err(|e| SigstoreError::RekorClientError(e.to_string()))?;
+        debug!(uuid = %entry.uuid, log_index = entry.log_index, "signature uploaded to Rekor");
+        Bundle::from_log_entry(&entry)
+    }
+
+    fn ensure_valid(&self) -> Result<()> {
+        if self.is_valid() {
+            Ok(())
+        } else {
+            Err(session_error("the certificate of the session has expired"))
+        }
+    }
+}
+
+fn session_error(msg: &str) -> SigstoreError {
+    SigstoreError::SigningSessionError(msg.to_string())
+}
+
+/// Split a PEM encoded certificate chain into the PEM of each certificate
+fn split_certificates(chain: &str) -> Vec<String> {
+    chain
+        .split_inclusive(PEM_CERTIFICATE_END)
+        .map(|cert| cert.trim_start())
+        .filter(|cert| cert.ends_with(PEM_CERTIFICATE_END))
+        .map(|cert| format!("{cert}\n"))
+        .collect()
+}
+
+/// The `hashedrekord` entry recording the `signature` of `artifact`, the
+/// public key of the entry is the certificate of the ephemeral key
+fn proposed_entry(signature: &str, artifact: &[u8], certificate: &str) -> ProposedEntry {
+    ProposedEntry::Hashedrekord {
+        api_version: HASHEDREKORD_API_VERSION.to_string(),
+        spec: Spec::new(
+            Signature::new(
+                signature.to_string(),
+                PublicKey::new(BASE64_STD_ENGINE.encode(certificate)),
+            ),
+            Data::new(Hash::new(
+                AlgorithmKind::sha256,
+                hex::encode(Sha256::digest(artifact)),
+            )),
+        ),
+    }
+}
+
+/// The layers of the signature image, with the annotations written by cosign
+/// for keyless signatures and, when signed with a key too, for signatures
+/// made with a key
+fn signature_image_layers(
+    signature: &PendingSignature,
+    certificates: &[String],
+) -> Result<Vec<oci_distribution::client::ImageLayer>> {
+    let mut annotations: HashMap<String, String> = [
+        (
+            SIGSTORE_SIGNATURE_ANNOTATION.to_string(),
+            signature.signature.clone(),
+        ),
+        (
+            SIGSTORE_CERT_ANNOTATION.to_string(),
+            certificates[0].clone(),
+        ),
+        (
+            SIGSTORE_CHAIN_ANNOTATION.to_string(),
+            certificates[1..].concat(),
+        ),
+    ]
+    .into();
+    if let Some(bundle) = &signature.bundle {
+        annotations.insert(
+            SIGSTORE_BUNDLE_ANNOTATION.to_string(),
+            serde_json::to_string(bundle)?,
+        );
+    }
+
+    let mut layers = vec![oci_distribution::client::ImageLayer::new(
+        signature.payload.as_bytes().to_vec(),
+        SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+        Some(annotations),
+    )];
+    if let Some(key_signature) = &signature.key_signature {
+        layers.push(oci_distribution::client::ImageLayer::new(
+            signature.payload.as_bytes().to_vec(),
+            SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+            Some(
+                [(
+                    SIGSTORE_SIGNATURE_ANNOTATION.to_string(),
+                    key_signature.clone(),
+                )]
+                .into(),
+            ),
+        ));
+    }
+    Ok(layers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::bundle::Payload;
+    use crate::crypto::certificate_transparency::SctPolicy;
+    use crate::crypto::tests::{generate_certificate, CertGenerationOptions};
+    use crate::mock_client::test::MockOciClient;
+    use openssl::pkey::PKey;
+
+    fn build_session() -> (SigningSession, String) {
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let public_key = signer
+            .to_sigstore_keypair()
+            .unwrap()
+            .public_key_to_pem()
+            .unwrap();
+
+        let ca_data = generate_certificate(None, CertGenerationOptions::default()).unwrap();
+        let issued = generate_certificate(
+            Some(&ca_data),
+            CertGenerationOptions {
+                subject_email: Some("tests@sigstore-rs.dev".to_string()),
+                public_key: PKey::public_key_from_pem(public_key.as_bytes()).unwrap(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let chain = format!(
+            "{}{}",
+            String::from_utf8(issued.cert.to_pem().unwrap()).unwrap(),
+            String::from_utf8(ca_data.cert.to_pem().unwrap()).unwrap()
+        );
+
+        let session =
+            SigningSession::from_parts(signer, SigningScheme::ECDSA_P256_SHA256_ASN1, &chain)
+                .unwrap();
+        (session, chain)
+    }
+
+    fn client(
+        push_response: Option<anyhow::Result<oci_distribution::client::PushResponse>>,
+    ) -> Client {
+        Client {
+            registry_client: Box::new(MockOciClient {
+                fetch_manifest_digest_response: None,
+                pull_response: None,
+                pull_manifest_response: None,
+                push_response,
+            }),
+            rekor_pub_key: None,
+            fulcio_cert_pool: None,
+            trusted_root: None,
+            sct_policy: SctPolicy::Require,
+            freshness: None,
+            progress_listener: None,
+            signature_repository: None,
+            verification_concurrency: 1,
+            verification_cache: None,
+        }
+    }
+
+    fn uploaded_signature(tag: &str) -> PendingSignature {
+        PendingSignature {
+            image: format!("registry.example.com/app:{tag}"),
+            target: format!("registry.example.com/app:sha256-{tag}.sig"),
+            payload: "{}".to_string(),
+            signature: "c2lnbmF0dXJl".to_string(),
+            bundle: Some(Bundle {
+                signed_entry_timestamp: "c2V0".to_string(),
+                payload: Payload {
+                    body: "Ym9keQ==".to_string(),
+                    integrated_time: 1_700_000_000,
+                    log_index: 42,
+                    log_id: "log".to_string(),
+                },
+            }),
+            key_signature: None,
+        }
+    }
+
+    #[test]
+    fn reject_certificate_of_another_key() {
+        let (_, chain) = build_session();
+        let other = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let error =
+            SigningSession::from_parts(other, SigningScheme::ECDSA_P256_SHA256_ASN1, &chain)
+                .err()
+                .expect("certificate of another key accepted");
+        assert!(matches!(error, SigstoreError::SigningSessionError(_)));
+    }
+
+    #[test]
+    fn persist_and_restore_state() {
+        let (mut session, chain) = build_session();
+        assert!(session.is_valid());
+        assert_eq!(session.certificates.len(), 2);
+        session.pending = vec![uploaded_signature("v1")];
+
+        let state = serde_json::to_string(&session.to_state(b"password").unwrap()).unwrap();
+        let state: SigningSessionState = serde_json::from_str(&state).unwrap();
+        assert_eq!(
+            split_certificates(&state.certificate_chain),
+            split_certificates(&chain)
+        );
+        assert!(SigningSession::from_state(state.clone(), b"wrong password").is_err());
+
+        let restored = SigningSession::from_state(state, b"password").unwrap();
+        assert_eq!(restored.pending(), &[uploaded_signature("v1")]);
+        assert_eq!(restored.expires_at(), session.expires_at());
+
+        // the restored key is the one certified by Fulcio
+        let signature = restored.signer.sign(b"payload").unwrap();
+        session
+            .signer
+            .to_verification_key()
+            .unwrap()
+            .verify_signature(crate::crypto::Signature::Raw(&signature), b"payload")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn resume_push_of_uploaded_signatures() {
+        let (mut session, _) = build_session();
+        session.pending = vec![uploaded_signature("v1"), uploaded_signature("v2")];
+        let rekor_config = Configuration::default();
+
+        // the registry fails, the signatures are kept
+        let mut failing = client(Some(Err(anyhow::anyhow!("registry down"))));
+        assert!(session
+            .resume(&mut failing, &Auth::Anonymous, &rekor_config)
+            .await
+            .is_err());
+        assert_eq!(session.pending().len(), 2);
+
+        // Rekor isn't contacted again: the bundle is already there
+        let mut working = client(Some(Ok(oci_distribution::client::PushResponse {
+            config_url: "config".to_string(),
+            manifest_url: "manifest".to_string(),
+        })));
+        let responses = session
+            .resume(&mut working, &Auth::Anonymous, &rekor_config)
+            .await
+            .unwrap();
+        assert_eq!(responses.len(), 2);
+        assert!(session.pending().is_empty());
+        assert!(session
+            .resume(&mut working, &Auth::Anonymous, &rekor_config)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    struct Decline(std::cell::RefCell<Vec<UploadRequest>>);
+
+    #[async_trait(?Send)]
+    impl UploadConfirmation for std::rc::Rc<Decline> {
+        async fn confirm(&self, request: &UploadRequest) -> Result<bool> {
+            self.0.borrow_mut().push(request.clone());
+            Ok(false)
+        }
+    }
+
+    #[tokio::test]
+    async fn declined_uploads_are_not_published() {
+        let decline = std::rc::Rc::new(Decline(Default::default()));
+        let (session, _) = build_session();
+        let mut session = session.with_upload_confirmation(Box::new(decline.clone()));
+        assert_eq!(session.identities(), ["tests@sigstore-rs.dev"]);
+        let rekor_config = Configuration::default();
+
+        let error = session
+            .sign_blob(&rekor_config, b"hello world")
+            .await
+            .expect_err("declined upload performed");
+        assert!(matches!(
+            error,
+            SigstoreError::TransparencyLogUploadDeclined
+        ));
+
+        // a declined image signature is dropped
+        let mut pending = uploaded_signature("v1");
+        pending.bundle = None;
+        session.pending = vec![pending];
+        let mut registry = client(None);
+        assert!(session
+            .resume(&mut registry, &Auth::Anonymous, &rekor_config)
+            .await
+            .is_err());
+        assert!(session.pending().is_empty());
+
+        let requests = decline.0.borrow();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[0].artifact,
+            "sha256:b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+        assert_eq!(requests[0].identities, ["tests@sigstore-rs.dev"]);
+        assert_eq!(requests[0].log_url, rekor_config.base_path);
+        assert_eq!(requests[1].artifact, "registry.example.com/app:v1");
+    }
+
+    #[test]
+    fn signature_layer_annotations() {
+        let (session, _) = build_session();
+        let layers =
+            signature_image_layers(&uploaded_signature("v1"), &session.certificates).unwrap();
+        assert_eq!(layers.len(), 1);
+        let annotations = layers[0].annotations.clone().unwrap();
+        assert_eq!(
+            annotations[SIGSTORE_CERT_ANNOTATION],
+            session.certificates[0]
+        );
+        assert_eq!(
+            annotations[SIGSTORE_CHAIN_ANNOTATION],
+            session.certificates[1]
+        );
+        let bundle: Bundle =
+            serde_json::from_str(&annotations[SIGSTORE_BUNDLE_ANNOTATION]).unwrap();
+        assert_eq!(bundle.payload.log_index, 42);
+    }
+
+    #[test]
+    fn dual_signature_layers() {
+        let (session, _) = build_session();
+        let key = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let mut signature = uploaded_signature("v1");
+        signature.key_signature =
+            Some(BASE64_STD_ENGINE.encode(key.sign(signature.payload.as_bytes()).unwrap()));
+
+        let layers = signature_image_layers(&signature, &session.certificates).unwrap();
+        assert_eq!(layers.len(), 2);
+        assert!(layers[0]
+            .annotations
+            .as_ref()
+            .unwrap()
+            .contains_key(SIGSTORE_CERT_ANNOTATION));
+
+        // the second layer can be verified with the public key alone
+        let annotations = layers[1].annotations.as_ref().unwrap();
+        assert_eq!(annotations.len(), 1);
+        key.to_verification_key()
+            .unwrap()
+            .verify_signature(
+                crate::crypto::Signature::Base64Encoded(
+                    annotations[SIGSTORE_SIGNATURE_ANNOTATION].as_bytes(),
+                ),
+                &layers[1].data,
+            )
+            .unwrap();
+    }
+}
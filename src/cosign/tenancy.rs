@@ -0,0 +1,362 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of an image under multiple trust configurations.
+//!
+//! Multi-tenant services, like admission controllers, often have to verify
+//! the same image under the policies of different tenants (for example one
+//! per Kubernetes namespace). Each tenant can trust different Sigstore
+//! instances and require different signers.
+//!
+//! A [`TenantPolicies`] holds one named [`TrustConfiguration`] per tenant.
+//! The signatures of the image are fetched only once, then they are verified
+//! and evaluated under each trust configuration.
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::tenancy::{TenantPolicies, TrustConfiguration};
+//! use sigstore::cosign::verification_constraint::{PublicKeyVerifier, VerificationConstraintVec};
+//! use sigstore::cosign::ClientBuilder;
+//! use sigstore::registry::{Auth, OciReference};
+//!
+//! # async fn run() -> sigstore::errors::Result<()> {
+//! # let team_a_key = "";
+//! # let team_b_key = "";
+//! let mut policies = TenantPolicies::new();
+//! let constraints: VerificationConstraintVec = vec![Box::new(
+//!     PublicKeyVerifier::try_from(team_a_key.as_bytes())?,
+//! )];
+//! policies.insert("team-a", TrustConfiguration::new(None, &[], constraints)?);
+//! let constraints: VerificationConstraintVec = vec![Box::new(
+//!     PublicKeyVerifier::try_from(team_b_key.as_bytes())?,
+//! )];
+//! policies.insert("team-b", TrustConfiguration::new(None, &[], constraints)?);
+//!
+//! let mut client = ClientBuilder::default().build()?;
+//! let image: OciReference = "registry.local/app:v1".parse()?;
+//! let reports = policies.evaluate(&mut client, &Auth::Anonymous, &image).await?;
+//! for (tenant, report) in &reports {
+//!     println!("{tenant}: {}", report.verified);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use tracing::debug;
+
+use super::client::image_manifest;
+use super::constants::SIGSTORE_OCI_MEDIA_TYPE;
+use super::report::VerificationReport;
+use super::signature_layers::build_signature_layers;
+use super::verification_constraint::VerificationConstraintVec;
+use super::{Client, CosignCapabilities};
+use crate::crypto::certificate_pool::CertificatePool;
+use crate::crypto::certificate_transparency::SctPolicy;
+use crate::crypto::trusted_root::TrustedRoot;
+use crate::crypto::{CosignVerificationKey, SigningScheme};
+use crate::errors::{Result, SigstoreError};
+use crate::registry::{Auth, Certificate, OciReference};
+
+/// The trust material and the verification constraints of a tenant
+pub struct TrustConfiguration {
+    rekor_pub_key: Option<CosignVerificationKey>,
+    fulcio_cert_pool: Option<CertificatePool>,
+    trusted_root: Option<TrustedRoot>,
+    sct_policy: SctPolicy,
+    constraints: VerificationConstraintVec,
+}
+
+impl TrustConfiguration {
+    /// Create a new trust configuration.
+    ///
+    /// `rekor_pub_key` is the PEM encoded public key of Rekor, `fulcio_certs`
+    /// the certificates used by Fulcio. They have the same meaning of the
+    /// values given to [`ClientBuilder`](crate::cosign::ClientBuilder).
+    pub fn new(
+        rekor_pub_key: Option<&str>,
+        fulcio_certs: &[Certificate],
+        constraints: VerificationConstraintVec,
+    ) -> Result<Self> {
+        let rekor_pub_key = rekor_pub_key
+            .map(|key| CosignVerificationKey::from_pem(key.as_bytes(), &SigningScheme::default()))
+            .transpose()?;
+        let fulcio_cert_pool = if fulcio_certs.is_empty() {
+            None
+        } else {
+            Some(CertificatePool::from_certificates(fulcio_certs)?)
+        };
+
+        Ok(TrustConfiguration {
+            rekor_pub_key,
+            fulcio_cert_pool,
+            trusted_root: None,
+            sct_policy: SctPolicy::Require,
+            constraints,
+        })
+    }
+
+    /// Use the trust material of `trusted_root`, which takes precedence over
+    /// the Rekor key and the Fulcio certificates given to
+    /// [`TrustConfiguration::new`]
+    pub fn with_trusted_root(mut self, trusted_root: TrustedRoot) -> Self {
+        self.trusted_root = Some(trusted_root);
+        self
+    }
+
+    /// Whether the SCT of the certificates issued by Fulcio has to be
+    /// verified, see [`ClientBuilder::with_sct_policy`](crate::cosign::ClientBuilder::with_sct_policy)
+    pub fn with_sct_policy(mut self, policy: SctPolicy) -> Self {
+        self.sct_policy = policy;
+        self
+    }
+
+    /// The verification constraints of the tenant
+    pub fn constraints(&self) -> &VerificationConstraintVec {
+        &self.constraints
+    }
+}
+
+/// A set of named trust configurations
+#[derive(Default)]
+pub struct TenantPolicies {
+    tenants: BTreeMap<String, TrustConfiguration>,
+}
+
+impl TenantPolicies {
+    /// Create an empty set of policies
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the trust configuration of a tenant, replacing the previous one
+    /// with the same name
+    pub fn insert(&mut self, name: impl Into<String>, configuration: TrustConfiguration) {
+        self.tenants.insert(name.into(), configuration);
+    }
+
+    /// Remove the trust configuration of a tenant
+    pub fn remove(&mut self, name: &str) -> Option<TrustConfiguration> {
+        self.tenants.remove(name)
+    }
+
+    /// The names of all the tenants
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.tenants.keys().map(|n| n.as_str())
+    }
+
+    /// Verify `image` under the trust configuration of every tenant.
+    ///
+    /// A [`VerificationReport`] is returned for each tenant. An error is
+    /// returned only when the image or its signatures cannot be fetched,
+    /// since that affects all the tenants.
+    pub async fn evaluate(
+        &self,
+        client: &mut Client,
+        auth: &Auth,
+        image: &OciReference,
+    ) -> Result<BTreeMap<String, VerificationReport>> {
+        let names: Vec<&str> = self.names().collect();
+        self.evaluate_tenants(client, auth, image, &names).await
+    }
+
+    /// Like [`TenantPolicies::evaluate`], but only for the tenants listed
+    /// inside of `names`. Unknown tenants are reported as an error.
+    pub async fn evaluate_tenants(
+        &self,
+        client: &mut Client,
+        auth: &Auth,
+        image: &OciReference,
+        names: &[&str],
+    ) -> Result<BTreeMap<String, VerificationReport>> {
+        let tenants = names
+            .iter()
+            .map(|name| {
+                self.tenants
+                    .get(*name)
+                    .map(|tc| (*name, tc))
+                    .ok_or_else(|| SigstoreError::UnexpectedError(format!("unknown tenant {name}")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if let Some((policy, fetched_at)) = &client.freshness {
+            policy.check(*fetched_at)?;
+        }
+
+        let (cosign_image, source_image_digest) = client.triangulate(image, auth).await?;
+        let (manifest, layers) = client
+            .fetch_manifest_and_layers(auth, &cosign_image, vec![SIGSTORE_OCI_MEDIA_TYPE])
+            .await?;
+        let image_manifest = image_manifest(manifest, &cosign_image)?;
+
+        let mut reports = BTreeMap::new();
+        for (name, tc) in tenants {
+            let report = match build_signature_layers(
+                &image_manifest,
+                &source_image_digest,
+                &layers,
+                tc.rekor_pub_key.as_ref(),
+                tc.fulcio_cert_pool.as_ref(),
+                tc.trusted_root.as_ref(),
+                tc.sct_policy,
+            ) {
+                Ok(trusted_layers) => VerificationReport::new(
+                    image,
+                    &source_image_digest,
+                    &trusted_layers,
+                    &tc.constraints,
+                ),
+                Err(e) => VerificationReport::failed(image, Some(&source_image_digest), &e),
+            };
+            debug!(
+                tenant = name,
+                verified = report.verified,
+                "tenant evaluated"
+            );
+            reports.insert(name.to_string(), report);
+        }
+
+        Ok(reports)
+    }
+}
+
+#[cfg(feature = "mock-client")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::constants::SIGSTORE_SIGNATURE_ANNOTATION;
+    use crate::cosign::signature_layers::tests::build_correct_signature_layer_without_bundle;
+    use crate::cosign::verification_constraint::{AnnotationVerifier, PublicKeyVerifier};
+    use crate::crypto::tests::PUBLIC_KEY;
+    use crate::mock_client::test::MockOciClient;
+    use oci_distribution::client::{Config, ImageData, ImageLayer};
+    use oci_distribution::manifest::{OciDescriptor, OciImageManifest, OciManifest};
+
+    fn build_test_client() -> Client {
+        let (sl, _) = build_correct_signature_layer_without_bundle();
+        let annotations: std::collections::HashMap<String, String> = [(
+            SIGSTORE_SIGNATURE_ANNOTATION.to_string(),
+            sl.signature.clone().unwrap(),
+        )]
+        .into();
+        let layer = ImageLayer::new(
+            sl.raw_data.clone(),
+            SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+            Some(annotations.clone()),
+        );
+        let manifest = OciImageManifest {
+            layers: vec![OciDescriptor {
+                media_type: SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+                digest: layer.sha256_digest(),
+                size: layer.data.len() as i64,
+                annotations: Some(annotations),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let mock_client = MockOciClient {
+            fetch_manifest_digest_response: Some(Ok(sl
+                .simple_signing
+                .critical
+                .image
+                .docker_manifest_digest
+                .clone())),
+            pull_response: Some(Ok(ImageData {
+                layers: vec![layer],
+                digest: None,
+                config: Config::oci_v1(b"{}".to_vec(), None),
+                manifest: None,
+            })),
+            pull_manifest_response: Some(Ok((
+                OciManifest::Image(manifest),
+                "sha256:manifest".to_string(),
+            ))),
+            push_response: None,
+        };
+
+        Client {
+            registry_client: Box::new(mock_client),
+            rekor_pub_key: None,
+            fulcio_cert_pool: None,
+            trusted_root: None,
+            sct_policy: SctPolicy::Require,
+            freshness: None,
+            progress_listener: None,
+            signature_repository: None,
+            verification_concurrency: 1,
+            verification_cache: None,
+        }
+    }
+
+    fn build_policies() -> TenantPolicies {
+        let mut policies = TenantPolicies::new();
+        policies.insert(
+            "team-a",
+            TrustConfiguration::new(
+                None,
+                &[],
+                vec![Box::new(
+                    PublicKeyVerifier::try_from(PUBLIC_KEY.as_bytes()).unwrap(),
+                )],
+            )
+            .unwrap(),
+        );
+        policies.insert(
+            "team-b",
+            TrustConfiguration::new(
+                None,
+                &[],
+                vec![Box::new(AnnotationVerifier {
+                    annotations: [("env".to_string(), "prod".to_string())].into(),
+                })],
+            )
+            .unwrap(),
+        );
+        policies
+    }
+
+    #[tokio::test]
+    async fn evaluate_all_tenants() {
+        let image: OciReference = "registry.local/busybox:latest".parse().unwrap();
+        let mut client = build_test_client();
+
+        let reports = build_policies()
+            .evaluate(&mut client, &Auth::Anonymous, &image)
+            .await
+            .unwrap();
+        assert_eq!(reports.len(), 2);
+        assert!(reports["team-a"].verified);
+        assert!(!reports["team-b"].verified);
+        assert!(reports["team-b"].error.is_none());
+    }
+
+    #[tokio::test]
+    async fn evaluate_selected_tenants() {
+        let image: OciReference = "registry.local/busybox:latest".parse().unwrap();
+        let mut client = build_test_client();
+        let policies = build_policies();
+
+        let reports = policies
+            .evaluate_tenants(&mut client, &Auth::Anonymous, &image, &["team-b"])
+            .await
+            .unwrap();
+        assert_eq!(reports.keys().collect::<Vec<_>>(), vec!["team-b"]);
+
+        assert!(policies
+            .evaluate_tenants(&mut client, &Auth::Anonymous, &image, &["team-c"])
+            .await
+            .is_err());
+    }
+}
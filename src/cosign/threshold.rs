@@ -0,0 +1,283 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! "k of n" verification: require valid signatures from at least `k` of a
+//! set of `n` trusted signers.
+//!
+//! Each signer is described by a list of
+//! [`VerificationConstraint`](crate::cosign::verification_constraint::VerificationConstraint),
+//! like a [`PublicKeyVerifier`](crate::cosign::verification_constraint::PublicKeyVerifier)
+//! or a [`CertificateIdentityVerifier`](crate::cosign::verification_constraint::CertificateIdentityVerifier).
+//! A signer is satisfied by a signature layer meeting all of its
+//! constraints. A layer satisfies at most one signer: two signers matching
+//! the same signature are not two valid signatures.
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::threshold::ThresholdVerifier;
+//! use sigstore::cosign::verification_constraint::certificate_identity_verifier::{
+//!     CertificateIdentityVerifier, IdentityMatcher,
+//! };
+//! use sigstore::cosign::SignatureLayer;
+//!
+//! # fn doc(signature_layers: &[SignatureLayer]) -> sigstore::errors::Result<()> {
+//! let mut verifier = ThresholdVerifier::new(2);
+//! for maintainer in ["alice", "bob", "carol", "dave", "eve"] {
+//!     verifier = verifier.with_signer(
+//!         maintainer,
+//!         vec![Box::new(CertificateIdentityVerifier::new(
+//!             IdentityMatcher::exact(&format!("{maintainer}@example.com")),
+//!             IdentityMatcher::exact("https://github.com/login/oauth"),
+//!         ))],
+//!     );
+//! }
+//!
+//! let signers = verifier.verify(signature_layers)?;
+//! println!("signed by {}", signers.join(", "));
+//! # Ok(())
+//! # }
+//! ```
+
+use tracing::warn;
+
+use super::verification_constraint::VerificationConstraintVec;
+use super::SignatureLayer;
+use crate::errors::{Result, SigstoreError};
+
+/// Verifier requiring the signatures of a minimum number of trusted signers
+#[derive(Debug)]
+pub struct ThresholdVerifier {
+    threshold: usize,
+    signers: Vec<(String, VerificationConstraintVec)>,
+}
+
+impl ThresholdVerifier {
+    /// Require the signatures of at least `threshold` of the signers
+    pub fn new(threshold: usize) -> Self {
+        ThresholdVerifier {
+            threshold,
+            signers: Vec::new(),
+        }
+    }
+
+    /// Trust the signer named `name`, whose signatures satisfy all the
+    /// `constraints`
+    pub fn with_signer(mut self, name: &str, constraints: VerificationConstraintVec) -> Self {
+        self.signers.push((name.to_string(), constraints));
+        self
+    }
+
+    /// Returns the names of the signers satisfied by the trusted
+    /// `signature_layers`, in the order they were added.
+    ///
+    /// Fails with [`SigstoreError::SigstoreThresholdNotMet`] when fewer than
+    /// `threshold` signers are satisfied.
+    pub fn verify(&self, signature_layers: &[SignatureLayer]) -> Result<Vec<String>> {
+        if self.threshold == 0 || self.threshold > self.signers.len() {
+            return Err(SigstoreError::VerificationConstraintError(format!(
+                "invalid threshold {} for {} signers",
+                self.threshold,
+                self.signers.len()
+            )));
+        }
+
+        let candidates: Vec<Vec<usize>> = self
+            .signers
+            .iter()
+            .map(|(name, constraints)| {
+                signature_layers
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, layer)| {
+                        constraints.iter().all(|c| match c.verify(layer) {
+                            Ok(verified) => verified,
+                            Err(e) => {
+                                warn!(error = ?e, signer = name, constraint = ?c, "Skipping layer because constraint verification returned an error");
+                                false
+                            }
+                        })
+                    })
+                    .map(|(index, _)| index)
+                    .collect()
+            })
+            .collect();
+
+        // assign distinct layers to as many signers as possible
+        let mut owners: Vec<Option<usize>> = vec![None; signature_layers.len()];
+        for signer in 0..self.signers.len() {
+            let mut visited = vec![false; signature_layers.len()];
+            assign(signer, &candidates, &mut owners, &mut visited);
+        }
+
+        let mut satisfied: Vec<usize> = owners.into_iter().flatten().collect();
+        satisfied.sort_unstable();
+        let satisfied: Vec<String> = satisfied
+            .into_iter()
+            .map(|signer| self.signers[signer].0.clone())
+            .collect();
+
+        if satisfied.len() < self.threshold {
+            return Err(SigstoreError::SigstoreThresholdNotMet {
+                threshold: self.threshold,
+                satisfied,
+            });
+        }
+        Ok(satisfied)
+    }
+}
+
+/// Find a layer for `signer` among its `candidates`, taking it from the
+/// signer owning it when that signer can be given another layer
+fn assign(
+    signer: usize,
+    candidates: &[Vec<usize>],
+    owners: &mut [Option<usize>],
+    visited: &mut [bool],
+) -> bool {
+    for &layer in &candidates[signer] {
+        if visited[layer] {
+            continue;
+        }
+        visited[layer] = true;
+        let available = match owners[layer] {
+            Some(owner) => assign(owner, candidates, owners, visited),
+            None => true,
+        };
+        if available {
+            owners[layer] = Some(signer);
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::signature_layers::tests::{
+        build_correct_signature_layer_with_certificate,
+        build_correct_signature_layer_without_bundle,
+    };
+    use crate::cosign::signature_layers::CertificateSubject;
+    use crate::cosign::verification_constraint::certificate_identity_verifier::{
+        CertificateIdentityVerifier, IdentityMatcher,
+    };
+    use crate::cosign::verification_constraint::{
+        AnnotationVerifier, PublicKeyVerifier, VerificationConstraint,
+    };
+    use std::collections::HashMap;
+
+    const ISSUER: &str = "https://github.com/login/oauth";
+
+    fn layer_signed_by(email: &str) -> SignatureLayer {
+        let mut layer = build_correct_signature_layer_with_certificate();
+        if let Some(signature) = layer.certificate_signature.as_mut() {
+            signature.subject = CertificateSubject::Email(email.to_string());
+            signature.issuer = Some(ISSUER.to_string());
+        }
+        layer
+    }
+
+    fn identity(pattern: &str) -> Box<dyn VerificationConstraint> {
+        Box::new(CertificateIdentityVerifier::new(
+            IdentityMatcher::regex(pattern).unwrap(),
+            IdentityMatcher::exact(ISSUER),
+        ))
+    }
+
+    fn maintainers(threshold: usize) -> ThresholdVerifier {
+        ["alice", "bob", "carol", "dave", "eve"].iter().fold(
+            ThresholdVerifier::new(threshold),
+            |verifier, name| {
+                verifier.with_signer(name, vec![identity(&format!("^{name}@example.com$"))])
+            },
+        )
+    }
+
+    #[test]
+    fn verify_threshold() {
+        let layers = vec![
+            layer_signed_by("carol@example.com"),
+            layer_signed_by("mallory@example.com"),
+            layer_signed_by("alice@example.com"),
+        ];
+
+        assert_eq!(
+            maintainers(2).verify(&layers).unwrap(),
+            vec!["alice", "carol"]
+        );
+
+        match maintainers(3).verify(&layers) {
+            Err(SigstoreError::SigstoreThresholdNotMet {
+                threshold,
+                satisfied,
+            }) => {
+                assert_eq!(threshold, 3);
+                assert_eq!(satisfied, vec!["alice", "carol"]);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn count_each_signature_once() {
+        let (key_layer, key) = build_correct_signature_layer_without_bundle();
+        let key_signer = || -> VerificationConstraintVec {
+            vec![Box::new(
+                PublicKeyVerifier::try_from(key.to_pem().unwrap().as_bytes()).unwrap(),
+            )]
+        };
+
+        // both signers are satisfied by the only signature
+        let verifier = ThresholdVerifier::new(2)
+            .with_signer("release", key_signer())
+            .with_signer("backup", key_signer());
+        assert!(verifier.verify(&[key_layer.clone()]).is_err());
+
+        // the signature of alice, first taken by the signer accepting
+        // anyone, is given back to alice
+        let verifier = ThresholdVerifier::new(2)
+            .with_signer("anyone", vec![identity("@example.com$")])
+            .with_signer("alice", vec![identity("^alice@")]);
+        let layers = vec![
+            layer_signed_by("alice@example.com"),
+            layer_signed_by("bob@example.com"),
+        ];
+        assert_eq!(verifier.verify(&layers).unwrap(), vec!["anyone", "alice"]);
+
+        // all the constraints of a signer must be satisfied by the same layer
+        let verifier = ThresholdVerifier::new(1).with_signer(
+            "release",
+            vec![
+                key_signer().remove(0),
+                Box::new(AnnotationVerifier {
+                    annotations: HashMap::from([("env".to_string(), "prod".to_string())]),
+                }),
+            ],
+        );
+        assert!(verifier.verify(&[key_layer]).is_err());
+    }
+
+    #[test]
+    fn reject_invalid_threshold() {
+        assert!(matches!(
+            maintainers(0).verify(&[]),
+            Err(SigstoreError::VerificationConstraintError(_))
+        ));
+        assert!(matches!(
+            maintainers(6).verify(&[]),
+            Err(SigstoreError::VerificationConstraintError(_))
+        ));
+    }
+}
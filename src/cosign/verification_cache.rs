@@ -0,0 +1,215 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cache of the successful verifications of images.
+//!
+//! Admission controllers verify the same images over and over. A
+//! [`VerificationCache`] remembers, for each image digest and policy, that
+//! the image has been verified, so that
+//! [`Client::verify_image`](crate::cosign::Client::verify_image) can skip
+//! the registry and Rekor until the entry expires:
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::verification_cache::VerificationCache;
+//! use sigstore::cosign::ClientBuilder;
+//! use std::time::Duration;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! let client = ClientBuilder::default()
+//!     .with_verification_cache(VerificationCache::in_memory(Duration::from_secs(300)))
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Only the successful verifications are cached: an image failing
+//! verification is verified again the next time, since it may have been
+//! signed in the meantime.
+//!
+//! The policy is identified by a hash of its constraints, see
+//! [`VerificationCache::policy_hash`]. The entries don't depend on the trust
+//! material of the client: a storage shared by clients trusting different
+//! Rekor or Fulcio instances must use a distinct `namespace` for each of
+//! them.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::verification_constraint::VerificationConstraint;
+use crate::cache::{CacheStorage, InMemoryCache};
+use crate::errors::Result;
+
+/// The outcome of a successful verification
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifiedImage {
+    /// The digest of the verified image
+    pub image_digest: String,
+    /// The digest of the signature layer that satisfied the policy
+    pub signature_digest: String,
+    /// When the image has been verified
+    pub verified_at: DateTime<Utc>,
+}
+
+/// Cache of the successful verifications, keyed by image digest and policy
+#[derive(Clone)]
+pub struct VerificationCache {
+    storage: Arc<dyn CacheStorage>,
+    ttl: Duration,
+    namespace: String,
+}
+
+impl VerificationCache {
+    /// Keep the verifications inside of `storage`, each one expires after
+    /// `ttl`
+    pub fn new(storage: Arc<dyn CacheStorage>, ttl: Duration) -> Self {
+        VerificationCache {
+            storage,
+            ttl,
+            namespace: "default".to_string(),
+        }
+    }
+
+    /// Keep the verifications in memory, private to the process
+    pub fn in_memory(ttl: Duration) -> Self {
+        Self::new(Arc::new(InMemoryCache::default()), ttl)
+    }
+
+    /// Prefix the keys of the entries with `namespace`, to share a storage
+    /// between clients with distinct trust material
+    pub fn with_namespace(mut self, namespace: &str) -> Self {
+        self.namespace = namespace.to_string();
+        self
+    }
+
+    /// The hash identifying a policy, computed from the debug
+    /// representation of its constraints. The order of the constraints
+    /// matters, and the constraints holding maps, like an
+    /// [`AnnotationVerifier`](crate::cosign::verification_constraint::AnnotationVerifier)
+    /// with several annotations, can produce distinct hashes for the same
+    /// policy: this only causes cache misses.
+    pub fn policy_hash(constraints: &[Box<dyn VerificationConstraint>]) -> String {
+        let mut hasher = Sha256::new();
+        for constraint in constraints {
+            hasher.update(format!("{constraint:?}").as_bytes());
+            hasher.update([0]);
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    fn key(&self, image_digest: &str, policy_hash: &str) -> String {
+        format!(
+            "verification-{}-{}-{}",
+            self.namespace,
+            image_digest.replace(':', "-"),
+            policy_hash
+        )
+    }
+
+    /// The cached verification of the image by the policy, if any
+    pub fn get(&self, image_digest: &str, policy_hash: &str) -> Result<Option<VerifiedImage>> {
+        let value = self.storage.get(&self.key(image_digest, policy_hash))?;
+        // an entry that can't be read is handled as missing
+        Ok(value.and_then(|value| serde_json::from_slice(&value).ok()))
+    }
+
+    /// Remember that the image has been verified by the policy
+    pub fn insert(&self, policy_hash: &str, verified_image: &VerifiedImage) -> Result<()> {
+        self.storage.set(
+            &self.key(&verified_image.image_digest, policy_hash),
+            &serde_json::to_vec(verified_image)?,
+            Some(self.ttl),
+        )
+    }
+
+    /// Forget the verification of the image by the policy, for example
+    /// after a key has been revoked
+    pub fn invalidate(&self, image_digest: &str, policy_hash: &str) -> Result<()> {
+        self.storage.remove(&self.key(image_digest, policy_hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::verification_constraint::AnnotationVerifier;
+
+    fn verified_image() -> VerifiedImage {
+        VerifiedImage {
+            image_digest: "sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b"
+                .to_string(),
+            signature_digest: "sha256:a1b2".to_string(),
+            verified_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn cache_verifications_per_policy() {
+        let cache = VerificationCache::in_memory(Duration::from_secs(60));
+        let verified_image = verified_image();
+
+        cache.insert("policy-a", &verified_image).unwrap();
+        assert_eq!(
+            cache.get(&verified_image.image_digest, "policy-a").unwrap(),
+            Some(verified_image.clone())
+        );
+        assert_eq!(
+            cache.get(&verified_image.image_digest, "policy-b").unwrap(),
+            None
+        );
+
+        cache
+            .invalidate(&verified_image.image_digest, "policy-a")
+            .unwrap();
+        assert_eq!(
+            cache.get(&verified_image.image_digest, "policy-a").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn expire_verifications() {
+        let cache = VerificationCache::in_memory(Duration::from_secs(0));
+        let verified_image = verified_image();
+
+        cache.insert("policy", &verified_image).unwrap();
+        assert_eq!(
+            cache.get(&verified_image.image_digest, "policy").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn policy_hash_depends_on_constraints() {
+        let annotations = |value: &str| -> Vec<Box<dyn VerificationConstraint>> {
+            vec![Box::new(AnnotationVerifier {
+                annotations: [("env".to_string(), value.to_string())].into(),
+            })]
+        };
+
+        assert_eq!(
+            VerificationCache::policy_hash(&annotations("prod")),
+            VerificationCache::policy_hash(&annotations("prod"))
+        );
+        assert_ne!(
+            VerificationCache::policy_hash(&annotations("prod")),
+            VerificationCache::policy_hash(&annotations("dev"))
+        );
+    }
+}
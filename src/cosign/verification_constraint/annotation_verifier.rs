@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use super::VerificationConstraint;
 use crate::cosign::signature_layers::SignatureLayer;
-use crate::errors::Result;
+use crate::errors::{Result, SigstoreError};
 
 /// Verification Constraint for the annotations added by `cosign sign`
 ///
@@ -19,6 +19,37 @@ pub struct AnnotationVerifier {
     pub annotations: HashMap<String, String>,
 }
 
+impl AnnotationVerifier {
+    /// Require all the given annotations
+    pub fn new(annotations: HashMap<String, String>) -> Self {
+        AnnotationVerifier { annotations }
+    }
+
+    /// Require the annotations given as `key=value` strings, the format
+    /// used by `cosign verify -a`
+    pub fn from_key_values<I, S>(key_values: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let annotations = key_values
+            .into_iter()
+            .map(|key_value| {
+                let key_value = key_value.as_ref();
+                match key_value.split_once('=') {
+                    Some((key, value)) if !key.is_empty() => {
+                        Ok((key.to_string(), value.to_string()))
+                    }
+                    _ => Err(SigstoreError::VerificationConstraintError(format!(
+                        "invalid annotation {key_value}, expected key=value"
+                    ))),
+                }
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        Ok(Self::new(annotations))
+    }
+}
+
 impl VerificationConstraint for AnnotationVerifier {
     fn verify(&self, signature_layer: &SignatureLayer) -> Result<bool> {
         let verified = signature_layer
@@ -26,4 +57,47 @@ impl VerificationConstraint for AnnotationVerifier {
             .satisfies_annotations(&self.annotations);
         Ok(verified)
     }
+
+    fn description(&self) -> String {
+        let mut annotations: Vec<String> = self
+            .annotations
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        annotations.sort();
+        format!("annotated with {}", annotations.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::signature_layers::tests::build_correct_signature_layer_without_bundle;
+
+    #[test]
+    fn verify_annotations() {
+        let (mut signature_layer, _) = build_correct_signature_layer_without_bundle();
+        let vf = AnnotationVerifier::from_key_values(["env=prod", "team=a=b"]).unwrap();
+        assert_eq!(vf.annotations["team"], "a=b");
+        assert!(!vf.verify(&signature_layer).unwrap());
+
+        signature_layer.simple_signing = signature_layer.simple_signing.with_annotations(
+            [
+                ("env".to_string(), "prod".to_string()),
+                ("team".to_string(), "a=b".to_string()),
+                ("extra".to_string(), "ignored".to_string()),
+            ]
+            .into(),
+        );
+        assert!(vf.verify(&signature_layer).unwrap());
+
+        let vf = AnnotationVerifier::from_key_values(["env=staging"]).unwrap();
+        assert!(!vf.verify(&signature_layer).unwrap());
+
+        assert!(matches!(
+            AnnotationVerifier::from_key_values(["env"]),
+            Err(SigstoreError::VerificationConstraintError(_))
+        ));
+        assert!(AnnotationVerifier::from_key_values(["=prod"]).is_err());
+    }
 }
@@ -117,6 +117,13 @@ impl VerificationConstraint for CertSubjectEmailVerifier {
         };
         Ok(verified)
     }
+
+    fn description(&self) -> String {
+        match &self.issuer {
+            Some(issuer) => format!("signed by {} authenticated by {issuer}", self.email),
+            None => format!("signed by {}", self.email),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -65,6 +65,10 @@ impl VerificationConstraint for CertSubjectUrlVerifier {
         };
         Ok(verified)
     }
+
+    fn description(&self) -> String {
+        format!("signed by {} authenticated by {}", self.url, self.issuer)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,212 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use regex::Regex;
+use std::fmt;
+
+use super::VerificationConstraint;
+use crate::cosign::signature_layers::{CertificateSignature, CertificateSubject, SignatureLayer};
+use crate::errors::{Result, SigstoreError};
+
+/// How a value found inside of the certificate is compared with the
+/// expected one
+#[derive(Debug, Clone)]
+pub enum IdentityMatcher {
+    /// The value must be exactly the given string
+    Exact(String),
+    /// The value must match the regular expression. Like cosign, the
+    /// expression is not anchored: use `^` and `$` to match the whole value
+    Regex(Regex),
+}
+
+impl IdentityMatcher {
+    /// Match the values equal to `value`
+    pub fn exact(value: &str) -> Self {
+        IdentityMatcher::Exact(value.to_string())
+    }
+
+    /// Match the values satisfying the regular expression `pattern`
+    pub fn regex(pattern: &str) -> Result<Self> {
+        Regex::new(pattern)
+            .map(IdentityMatcher::Regex)
+            .map_err(|e| {
+                SigstoreError::VerificationConstraintError(format!(
+                    "invalid regular expression {pattern}: {e}"
+                ))
+            })
+    }
+
+    pub(crate) fn matches(&self, value: &str) -> bool {
+        match self {
+            IdentityMatcher::Exact(expected) => expected == value,
+            IdentityMatcher::Regex(re) => re.is_match(value),
+        }
+    }
+}
+
+impl fmt::Display for IdentityMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdentityMatcher::Exact(value) => write!(f, "{value}"),
+            IdentityMatcher::Regex(re) => write!(f, "an identity matching {re}"),
+        }
+    }
+}
+
+/// Verification Constraint for signatures produced in keyless mode, the
+/// equivalent of the `--certificate-identity` and `--certificate-oidc-issuer`
+/// flags of `cosign verify`, together with their `-regexp` variants.
+///
+/// The identity is compared with the SAN of the certificate, regardless of
+/// it being an email address or a URI. The issuer is compared with the OIDC
+/// issuer extension of the certificate: signatures without it are rejected.
+///
+/// ```rust
+/// use sigstore::cosign::verification_constraint::certificate_identity_verifier::{
+///     CertificateIdentityVerifier, IdentityMatcher,
+/// };
+///
+/// # fn doc() -> sigstore::errors::Result<()> {
+/// // Signatures produced by alice, authenticated via GitHub
+/// let vc_alice = CertificateIdentityVerifier::new(
+///     IdentityMatcher::exact("alice@example.com"),
+///     IdentityMatcher::exact("https://github.com/login/oauth"),
+/// );
+///
+/// // Signatures produced by any workflow of the `octocat/hello-world` repository
+/// let vc_workflows = CertificateIdentityVerifier::new(
+///     IdentityMatcher::regex(r"^https://github\.com/octocat/hello-world/\.github/workflows/")?,
+///     IdentityMatcher::exact("https://token.actions.githubusercontent.com"),
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CertificateIdentityVerifier {
+    pub identity: IdentityMatcher,
+    pub issuer: IdentityMatcher,
+}
+
+impl CertificateIdentityVerifier {
+    /// Accept the certificates issued for `identity` by `issuer`
+    pub fn new(identity: IdentityMatcher, issuer: IdentityMatcher) -> Self {
+        CertificateIdentityVerifier { identity, issuer }
+    }
+
+    /// Returns `true` when the certificate has been issued for the
+    /// expected identity by the expected issuer
+    pub(crate) fn matches(&self, signature: &CertificateSignature) -> bool {
+        let identity = match &signature.subject {
+            CertificateSubject::Email(email) => email.as_str(),
+            CertificateSubject::Uri(uri) => uri.as_str(),
+        };
+        let issuer_matches = match &signature.issuer {
+            Some(issuer) => self.issuer.matches(issuer),
+            None => false,
+        };
+
+        self.identity.matches(identity) && issuer_matches
+    }
+}
+
+impl VerificationConstraint for CertificateIdentityVerifier {
+    fn verify(&self, signature_layer: &SignatureLayer) -> Result<bool> {
+        let verified = match &signature_layer.certificate_signature {
+            Some(signature) => self.matches(signature),
+            None => false,
+        };
+        Ok(verified)
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "signed by {} authenticated by {}",
+            self.identity, self.issuer
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::signature_layers::tests::{
+        build_correct_signature_layer_with_certificate,
+        build_correct_signature_layer_without_bundle,
+    };
+
+    const WORKFLOW: &str =
+        "https://github.com/octocat/hello-world/.github/workflows/release.yml@refs/tags/v1.0";
+    const GITHUB_ISSUER: &str = "https://token.actions.githubusercontent.com";
+
+    #[test]
+    fn certificate_identity_verifier() {
+        let mut sl = build_correct_signature_layer_with_certificate();
+        let mut cert_signature = sl.certificate_signature.unwrap();
+        cert_signature.subject = CertificateSubject::Uri(WORKFLOW.to_string());
+        cert_signature.issuer = Some(GITHUB_ISSUER.to_string());
+        sl.certificate_signature = Some(cert_signature);
+
+        let vc = CertificateIdentityVerifier::new(
+            IdentityMatcher::exact(WORKFLOW),
+            IdentityMatcher::exact(GITHUB_ISSUER),
+        );
+        assert!(vc.verify(&sl).unwrap());
+
+        let vc = CertificateIdentityVerifier::new(
+            IdentityMatcher::regex(r"^https://github\.com/octocat/hello-world/").unwrap(),
+            IdentityMatcher::regex(r"^https://token\.actions\.githubusercontent\.com$").unwrap(),
+        );
+        assert!(vc.verify(&sl).unwrap());
+
+        let vc = CertificateIdentityVerifier::new(
+            IdentityMatcher::regex(r"^https://github\.com/octocat/other/").unwrap(),
+            IdentityMatcher::exact(GITHUB_ISSUER),
+        );
+        assert!(!vc.verify(&sl).unwrap());
+
+        let vc = CertificateIdentityVerifier::new(
+            IdentityMatcher::exact(WORKFLOW),
+            IdentityMatcher::exact("https://accounts.google.com"),
+        );
+        assert!(!vc.verify(&sl).unwrap());
+    }
+
+    #[test]
+    fn certificate_identity_verifier_email_without_issuer() {
+        let email = "alice@example.com";
+        let mut sl = build_correct_signature_layer_with_certificate();
+        let mut cert_signature = sl.certificate_signature.unwrap();
+        cert_signature.subject = CertificateSubject::Email(email.to_string());
+        cert_signature.issuer = None;
+        sl.certificate_signature = Some(cert_signature);
+
+        let vc = CertificateIdentityVerifier::new(
+            IdentityMatcher::exact(email),
+            IdentityMatcher::regex(".*").unwrap(),
+        );
+        assert!(!vc.verify(&sl).unwrap());
+
+        let (sl, _) = build_correct_signature_layer_without_bundle();
+        assert!(!vc.verify(&sl).unwrap());
+    }
+
+    #[test]
+    fn invalid_regex() {
+        assert!(matches!(
+            IdentityMatcher::regex("(unclosed"),
+            Err(SigstoreError::VerificationConstraintError(_))
+        ));
+    }
+}
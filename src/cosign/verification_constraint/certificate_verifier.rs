@@ -53,6 +53,23 @@ impl CertificateVerifier {
         cert_bytes: &[u8],
         require_rekor_bundle: bool,
         cert_chain: Option<&[crate::registry::Certificate]>,
+    ) -> Result<Self> {
+        let cert_pool = cert_chain
+            .map(CertificatePool::from_certificates)
+            .transpose()?;
+        Self::from_der_with_pool(cert_bytes, require_rekor_bundle, cert_pool.as_ref())
+    }
+
+    /// Create a new instance of `CertificateVerifier` using the DER encoded
+    /// certificate, like [`CertificateVerifier::from_der`] does.
+    ///
+    /// * `cert_pool`: the trust store used to verify the provided
+    ///   certificate, which can be shared by several verifiers. When not
+    ///   specified, the certificate is assumed to be trusted
+    pub fn from_der_with_pool(
+        cert_bytes: &[u8],
+        require_rekor_bundle: bool,
+        cert_pool: Option<&CertificatePool>,
     ) -> Result<Self> {
         let cert = Certificate::from_der(cert_bytes)
             .map_err(|e| SigstoreError::X509Error(format!("parse from der {e}")))?;
@@ -60,8 +77,7 @@ impl CertificateVerifier {
         crate::crypto::certificate::verify_has_san(&cert)?;
         crate::crypto::certificate::verify_validity(&cert)?;
 
-        if let Some(certs) = cert_chain {
-            let cert_pool = CertificatePool::from_certificates(certs)?;
+        if let Some(cert_pool) = cert_pool {
             cert_pool.verify_der_cert(cert_bytes)?;
         }
 
@@ -121,6 +137,10 @@ impl VerificationConstraint for CertificateVerifier {
             }
         }
     }
+
+    fn description(&self) -> String {
+        "signed by the key of the trusted certificate".to_string()
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,282 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::certificate_identity_verifier::IdentityMatcher;
+use super::VerificationConstraint;
+use crate::cosign::signature_layers::{CertificateSignature, CertificateSubject, SignatureLayer};
+use crate::errors::Result;
+
+/// The OIDC issuer of the tokens of GitHub Actions
+pub const GITHUB_ACTIONS_ISSUER: &str = "https://token.actions.githubusercontent.com";
+
+/// Verification Constraint for signatures produced in keyless mode by a
+/// GitHub Actions workflow.
+///
+/// The repository, the path of the workflow, the Git ref and the event that
+/// triggered the workflow are read from the Fulcio extensions of the
+/// certificate, instead of being matched with regular expressions over the
+/// SAN URI. The certificates issued before Fulcio added the
+/// `1.3.6.1.4.1.57264.1.12` and following extensions are checked against
+/// the deprecated GitHub ones.
+///
+/// The workflow is the top-level one, found inside of the repository: when
+/// it calls a reusable workflow, the SAN of the certificate refers to the
+/// reusable workflow instead.
+///
+/// ```rust
+/// use sigstore::cosign::verification_constraint::certificate_identity_verifier::IdentityMatcher;
+/// use sigstore::cosign::verification_constraint::GitHubWorkflowVerifier;
+///
+/// # fn doc() -> sigstore::errors::Result<()> {
+/// // Signatures produced by the release workflow of `octocat/hello-world`,
+/// // when a tag is pushed
+/// let vc = GitHubWorkflowVerifier::new("octocat/hello-world")
+///     .with_workflow(".github/workflows/release.yml")
+///     .with_ref(IdentityMatcher::regex(r"^refs/tags/v")?)
+///     .with_trigger("push");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct GitHubWorkflowVerifier {
+    repository: String,
+    workflow: Option<String>,
+    git_ref: Option<IdentityMatcher>,
+    triggers: Vec<String>,
+    issuer: String,
+}
+
+impl GitHubWorkflowVerifier {
+    /// Accept the signatures produced by any workflow of `repository`,
+    /// e.g. `octocat/hello-world`
+    pub fn new(repository: &str) -> Self {
+        GitHubWorkflowVerifier {
+            repository: repository.to_string(),
+            workflow: None,
+            git_ref: None,
+            triggers: Vec::new(),
+            issuer: GITHUB_ACTIONS_ISSUER.to_string(),
+        }
+    }
+
+    /// Accept only the signatures produced by the workflow at `path`, e.g.
+    /// `.github/workflows/release.yml`
+    pub fn with_workflow(mut self, path: &str) -> Self {
+        self.workflow = Some(path.trim_start_matches('/').to_string());
+        self
+    }
+
+    /// Accept only the signatures produced by workflows running against a
+    /// matching Git ref, e.g. `refs/heads/main`
+    pub fn with_ref(mut self, git_ref: IdentityMatcher) -> Self {
+        self.git_ref = Some(git_ref);
+        self
+    }
+
+    /// Accept the signatures produced by workflows triggered by `trigger`,
+    /// e.g. `push`. When called multiple times, any of the triggers is
+    /// accepted.
+    pub fn with_trigger(mut self, trigger: &str) -> Self {
+        self.triggers.push(trigger.to_string());
+        self
+    }
+
+    /// Expect the tokens issued by `issuer`, like the one of a GitHub
+    /// Enterprise Server, instead of [`GITHUB_ACTIONS_ISSUER`]
+    pub fn with_issuer(mut self, issuer: &str) -> Self {
+        self.issuer = issuer.to_string();
+        self
+    }
+
+    fn matches(&self, signature: &CertificateSignature) -> bool {
+        if signature.issuer.as_deref() != Some(self.issuer.as_str()) {
+            return false;
+        }
+
+        let extensions = &signature.extensions;
+        let repository = extensions
+            .source_repository_uri
+            .as_deref()
+            .and_then(uri_path)
+            .or(signature.github_workflow_repository.as_deref());
+        if !repository.is_some_and(|r| r.eq_ignore_ascii_case(&self.repository)) {
+            return false;
+        }
+
+        if let Some(workflow) = &self.workflow {
+            let workflow_uri = match (&extensions.build_config_uri, &signature.subject) {
+                (Some(uri), _) | (None, CertificateSubject::Uri(uri)) => uri.as_str(),
+                (None, CertificateSubject::Email(_)) => return false,
+            };
+            if workflow_path(workflow_uri, &self.repository) != Some(workflow.as_str()) {
+                return false;
+            }
+        }
+
+        if let Some(git_ref) = &self.git_ref {
+            let actual = extensions
+                .source_repository_ref
+                .as_deref()
+                .or(signature.github_workflow_ref.as_deref());
+            if !actual.is_some_and(|r| git_ref.matches(r)) {
+                return false;
+            }
+        }
+
+        if !self.triggers.is_empty() {
+            let actual = extensions
+                .build_trigger
+                .as_deref()
+                .or(signature.github_workflow_trigger.as_deref());
+            if !actual.is_some_and(|t| self.triggers.iter().any(|expected| expected == t)) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl VerificationConstraint for GitHubWorkflowVerifier {
+    fn verify(&self, signature_layer: &SignatureLayer) -> Result<bool> {
+        let verified = match &signature_layer.certificate_signature {
+            Some(signature) => self.matches(signature),
+            None => false,
+        };
+        Ok(verified)
+    }
+
+    fn description(&self) -> String {
+        let mut description = match &self.workflow {
+            Some(workflow) => format!("signed by the {workflow} workflow of {}", self.repository),
+            None => format!("signed by a workflow of {}", self.repository),
+        };
+        if let Some(git_ref) = &self.git_ref {
+            description.push_str(&format!(" running against {git_ref}"));
+        }
+        if !self.triggers.is_empty() {
+            description.push_str(&format!(" triggered by {}", self.triggers.join(" or ")));
+        }
+        description
+    }
+}
+
+/// The path of `uri`, without the leading `/`: the `owner/name` of a
+/// repository URI like `https://github.com/owner/name`
+fn uri_path(uri: &str) -> Option<&str> {
+    let (_, rest) = uri.split_once("://")?;
+    let (_, path) = rest.split_once('/')?;
+    Some(path.trim_end_matches('/'))
+}
+
+/// The path of the workflow of a URI like
+/// `https://github.com/owner/name/.github/workflows/release.yml@refs/heads/main`,
+/// when the workflow belongs to `repository`
+fn workflow_path<'a>(uri: &'a str, repository: &str) -> Option<&'a str> {
+    let path = uri_path(uri)?;
+    let (path, _git_ref) = path.split_once('@').unwrap_or((path, ""));
+    let owner = path.get(..repository.len())?;
+    if !owner.eq_ignore_ascii_case(repository) {
+        return None;
+    }
+    path[repository.len()..].strip_prefix('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::signature_layers::tests::{
+        build_correct_signature_layer_with_certificate,
+        build_correct_signature_layer_without_bundle,
+    };
+
+    fn workflow_layer() -> SignatureLayer {
+        let mut sl = build_correct_signature_layer_with_certificate();
+        let mut cs = sl.certificate_signature.unwrap();
+        cs.issuer = Some(GITHUB_ACTIONS_ISSUER.to_string());
+        // signed by a reusable workflow of another repository
+        cs.subject = CertificateSubject::Uri(
+            "https://github.com/octocat/workflows/.github/workflows/sign.yml@refs/heads/main"
+                .to_string(),
+        );
+        cs.extensions.source_repository_uri =
+            Some("https://github.com/octocat/hello-world".to_string());
+        cs.extensions.build_config_uri = Some(
+            "https://github.com/octocat/hello-world/.github/workflows/release.yml@refs/tags/v1.0"
+                .to_string(),
+        );
+        cs.extensions.source_repository_ref = Some("refs/tags/v1.0".to_string());
+        cs.extensions.build_trigger = Some("push".to_string());
+        sl.certificate_signature = Some(cs);
+        sl
+    }
+
+    #[test]
+    fn github_workflow_verifier() {
+        let sl = workflow_layer();
+
+        let vc = GitHubWorkflowVerifier::new("octocat/hello-world")
+            .with_workflow(".github/workflows/release.yml")
+            .with_ref(IdentityMatcher::regex(r"^refs/tags/v").unwrap())
+            .with_trigger("workflow_dispatch")
+            .with_trigger("push");
+        assert!(vc.verify(&sl).unwrap());
+        assert!(GitHubWorkflowVerifier::new("Octocat/Hello-World")
+            .verify(&sl)
+            .unwrap());
+
+        for vc in [
+            GitHubWorkflowVerifier::new("octocat/hello"),
+            GitHubWorkflowVerifier::new("octocat/workflows"),
+            GitHubWorkflowVerifier::new("octocat/hello-world")
+                .with_workflow(".github/workflows/sign.yml"),
+            GitHubWorkflowVerifier::new("octocat/hello-world")
+                .with_ref(IdentityMatcher::exact("refs/heads/main")),
+            GitHubWorkflowVerifier::new("octocat/hello-world").with_trigger("pull_request"),
+            GitHubWorkflowVerifier::new("octocat/hello-world")
+                .with_issuer("https://token.actions.example.com"),
+        ] {
+            assert!(!vc.verify(&sl).unwrap(), "{:?} should not match", vc);
+        }
+    }
+
+    #[test]
+    fn github_workflow_verifier_deprecated_extensions() {
+        let mut sl = build_correct_signature_layer_with_certificate();
+        let mut cs = sl.certificate_signature.unwrap();
+        cs.issuer = Some(GITHUB_ACTIONS_ISSUER.to_string());
+        cs.subject = CertificateSubject::Uri(
+            "https://github.com/octocat/hello-world/.github/workflows/release.yml@refs/heads/main"
+                .to_string(),
+        );
+        cs.github_workflow_repository = Some("octocat/hello-world".to_string());
+        cs.github_workflow_ref = Some("refs/heads/main".to_string());
+        cs.github_workflow_trigger = Some("push".to_string());
+        sl.certificate_signature = Some(cs);
+
+        let vc = GitHubWorkflowVerifier::new("octocat/hello-world")
+            .with_workflow("/.github/workflows/release.yml")
+            .with_ref(IdentityMatcher::exact("refs/heads/main"))
+            .with_trigger("push");
+        assert!(vc.verify(&sl).unwrap());
+    }
+
+    #[test]
+    fn github_workflow_verifier_no_certificate() {
+        let (sl, _) = build_correct_signature_layer_without_bundle();
+        let vc = GitHubWorkflowVerifier::new("octocat/hello-world");
+        assert!(!vc.verify(&sl).unwrap());
+    }
+}
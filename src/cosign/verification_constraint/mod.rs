@@ -24,6 +24,11 @@
 //! * [`CertSubjectUrlVerifier`]: ensure a signature has been produced in keyless mode,
 //!   plus the certificate SAN has a specific URI inside of it. This can be used to verify
 //!   signatures produced by GitHub Actions.
+//! * [`CertificateIdentityVerifier`]: ensure a signature has been produced in keyless mode
+//!   by a specific identity and OIDC issuer, matched either exactly or with regular
+//!   expressions, like cosign's `--certificate-identity` and `--certificate-oidc-issuer`
+//! * [`GitHubWorkflowVerifier`]: ensure a signature has been produced in keyless mode
+//!   by a GitHub Actions workflow, checking its repository, path, Git ref and trigger
 //!
 //! Developers can define ad-hoc validation logic by creating a Struct that implements
 //! the [`VerificationConstraintVec`] trait.
@@ -64,6 +69,13 @@ pub trait VerificationConstraint: std::fmt::Debug {
     ///   }
     /// }
     fn verify(&self, signature_layer: &SignatureLayer) -> Result<bool>;
+
+    /// A human readable description of the constraint, used to report why
+    /// a signature layer has been rejected. Defaults to the `Debug`
+    /// representation of the constraint.
+    fn description(&self) -> String {
+        format!("{self:?}")
+    }
 }
 
 pub mod certificate_verifier;
@@ -80,3 +92,9 @@ pub use cert_subject_url_verifier::CertSubjectUrlVerifier;
 
 pub mod annotation_verifier;
 pub use annotation_verifier::AnnotationVerifier;
+
+pub mod certificate_identity_verifier;
+pub use certificate_identity_verifier::CertificateIdentityVerifier;
+
+pub mod github_workflow_verifier;
+pub use github_workflow_verifier::GitHubWorkflowVerifier;
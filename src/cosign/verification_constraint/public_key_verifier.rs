@@ -25,6 +25,7 @@ impl PublicKeyVerifier {
     /// * `RSA public key`: `RSA_PKCS1_SHA256`
     /// * `EC public key with P-256 curve`: `ECDSA_P256_SHA256_ASN1`
     /// * `EC public key with P-384 curve`: `ECDSA_P384_SHA384_ASN1`
+    /// * `EC public key with P-521 curve`: `ECDSA_P521_SHA512`
     /// * `Ed25519 public key`: `Ed25519`
     pub fn try_from(key_raw: &[u8]) -> Result<Self> {
         let key = CosignVerificationKey::try_from_pem(key_raw)?;
@@ -36,6 +37,10 @@ impl VerificationConstraint for PublicKeyVerifier {
     fn verify(&self, signature_layer: &SignatureLayer) -> Result<bool> {
         Ok(signature_layer.is_signed_by_key(&self.key))
     }
+
+    fn description(&self) -> String {
+        "signed by the trusted public key".to_string()
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,539 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Continuous verification of a set of images.
+//!
+//! The [`Watcher`] keeps re-verifying a list of images against the same
+//! set of [`VerificationConstraint`](crate::cosign::verification_constraint::VerificationConstraint)
+//! objects, and reports each time the verification outcome of an image changes.
+//! This is the building block of drift-detection daemons.
+//!
+//! Verification can be triggered on demand via [`Watcher::check`], on a
+//! schedule via [`Watcher::run`], or when the trust root changes via
+//! [`Watcher::update_client`].
+//!
+//! [`FailureHandler`] objects can be registered to be notified each time an
+//! image starts failing verification.
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::watcher::Watcher;
+//! use sigstore::cosign::verification_constraint::{PublicKeyVerifier, VerificationConstraintVec};
+//! use sigstore::crypto::SigningScheme;
+//! use std::time::Duration;
+//!
+//! #[tokio::main]
+//! pub async fn main() {
+//!   let client = sigstore::cosign::ClientBuilder::default()
+//!     .build()
+//!     .expect("Unexpected failure while building Client");
+//!
+//!   let verification_key = std::fs::read("~/cosign.pub")
+//!     .expect("Cannot read contents of cosign public key");
+//!   let constraints: VerificationConstraintVec = vec![Box::new(
+//!     PublicKeyVerifier::new(&verification_key, &SigningScheme::default())
+//!       .expect("Could not create verifier"),
+//!   )];
+//!
+//!   let mut watcher = Watcher::new(
+//!     client,
+//!     sigstore::registry::Auth::Anonymous,
+//!     vec!["registry-testing.svc.lan/busybox:latest".parse().unwrap()],
+//!     constraints,
+//!   );
+//!
+//!   watcher.run(Duration::from_secs(300), |transition| {
+//!     println!("{transition}");
+//!   }).await;
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info, warn};
+
+use super::alerting::{FailureHandler, VerificationFailure};
+use super::verification_constraint::VerificationConstraintVec;
+use super::{verify_constraints, CosignCapabilities, SignatureLayer};
+use crate::errors::Result;
+use crate::metrics::{FailureReason, MetricsRecorder};
+use crate::registry::{Auth, OciReference};
+
+/// The outcome of the verification of an image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationState {
+    /// All the verification constraints are satisfied
+    Verified,
+    /// The image failed verification, the reason is provided
+    Failed(String),
+}
+
+impl VerificationState {
+    /// Returns `true` when the image passed verification
+    pub fn is_verified(&self) -> bool {
+        matches!(self, VerificationState::Verified)
+    }
+}
+
+impl fmt::Display for VerificationState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationState::Verified => write!(f, "verified"),
+            VerificationState::Failed(reason) => write!(f, "failed ({reason})"),
+        }
+    }
+}
+
+/// A change in the verification outcome of an image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    /// The image that changed state
+    pub image: OciReference,
+    /// The previous state, `None` when the image is verified for the first time
+    pub previous: Option<VerificationState>,
+    /// The current state
+    pub current: VerificationState,
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.previous {
+            Some(previous) => write!(f, "{}: {} -> {}", self.image, previous, self.current),
+            None => write!(f, "{}: {}", self.image, self.current),
+        }
+    }
+}
+
+/// Re-verifies a set of images and reports changes of their verification state.
+pub struct Watcher<C: CosignCapabilities> {
+    client: C,
+    auth: Auth,
+    images: Vec<OciReference>,
+    constraints: VerificationConstraintVec,
+    policy_name: String,
+    failure_handlers: Vec<Arc<dyn FailureHandler>>,
+    metrics: Option<Arc<dyn MetricsRecorder>>,
+    states: HashMap<String, VerificationState>,
+}
+
+impl<C: CosignCapabilities> Watcher<C> {
+    /// Create a new `Watcher`.
+    ///
+    /// * `client`: the client used to verify the images. It defines the
+    ///   trust root (Rekor and Fulcio material) used at verification time
+    /// * `auth`: the credentials used to access the registry
+    /// * `images`: the images to watch
+    /// * `constraints`: the constraints each image must satisfy
+    pub fn new(
+        client: C,
+        auth: Auth,
+        images: Vec<OciReference>,
+        constraints: VerificationConstraintVec,
+    ) -> Self {
+        Watcher {
+            client,
+            auth,
+            images,
+            constraints,
+            policy_name: "default".to_string(),
+            failure_handlers: Vec::new(),
+            metrics: None,
+            states: HashMap::new(),
+        }
+    }
+
+    /// Set the name of the policy enforced by the watcher. The name is
+    /// reported to the [`FailureHandler`] objects. Defaults to `default`.
+    pub fn with_policy_name(mut self, name: &str) -> Self {
+        self.policy_name = name.to_string();
+        self
+    }
+
+    /// Register a handler invoked each time an image starts failing
+    /// verification
+    pub fn add_failure_handler(&mut self, handler: Arc<dyn FailureHandler>) {
+        self.failure_handlers.push(handler);
+    }
+
+    /// Report the outcome and the duration of each verification to the given
+    /// [`MetricsRecorder`]
+    pub fn with_metrics_recorder(mut self, recorder: Arc<dyn MetricsRecorder>) -> Self {
+        self.metrics = Some(recorder);
+        self
+    }
+
+    /// Add an image to the set of watched ones
+    pub fn watch(&mut self, image: OciReference) {
+        if !self.images.contains(&image) {
+            self.images.push(image);
+        }
+    }
+
+    /// Stop watching an image
+    pub fn unwatch(&mut self, image: &OciReference) {
+        self.images.retain(|i| i != image);
+        self.states.remove(&image.whole());
+    }
+
+    /// The last known verification state of an image
+    pub fn state(&self, image: &OciReference) -> Option<&VerificationState> {
+        self.states.get(&image.whole())
+    }
+
+    /// Verify all the watched images once, and return the images whose
+    /// verification state changed since the previous check.
+    ///
+    /// Images verified for the first time are always reported. The
+    /// registered [`FailureHandler`] objects are invoked for each image that
+    /// transitioned to the failed state.
+    pub async fn check(&mut self) -> Vec<Transition> {
+        let mut transitions = Vec::new();
+
+        for image in self.images.clone() {
+            let (current, reasons) = match self.verify(&image).await {
+                Ok(()) => (VerificationState::Verified, Vec::new()),
+                Err(reasons) => (VerificationState::Failed(reasons.join("; ")), reasons),
+            };
+            debug!(%image, state = %current, "image verified");
+
+            let previous = self.states.insert(image.whole(), current.clone());
+            if previous.as_ref() != Some(&current) {
+                if !current.is_verified() {
+                    self.notify_failure(VerificationFailure {
+                        image: image.clone(),
+                        policy: self.policy_name.clone(),
+                        reasons,
+                        previous: previous.clone(),
+                    })
+                    .await;
+                }
+                transitions.push(Transition {
+                    image,
+                    previous,
+                    current,
+                });
+            }
+        }
+
+        transitions
+    }
+
+    /// Replace the client used to perform verification, then re-verify all
+    /// the watched images.
+    ///
+    /// This should be invoked when the trust root changes, for example after
+    /// new Fulcio certificates or a new Rekor key have been obtained from
+    /// the TUF repository.
+    pub async fn update_client(&mut self, client: C) -> Vec<Transition> {
+        info!("trust root changed, verifying all the watched images");
+        self.client = client;
+        self.check().await
+    }
+
+    /// Verify the watched images every `interval`, invoking `on_transition`
+    /// each time the state of an image changes.
+    ///
+    /// The first verification happens immediately. This method never returns.
+    pub async fn run<F>(&mut self, interval: Duration, mut on_transition: F)
+    where
+        F: FnMut(&Transition),
+    {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            for transition in self.check().await {
+                on_transition(&transition);
+            }
+        }
+    }
+
+    async fn notify_failure(&self, failure: VerificationFailure) {
+        for handler in &self.failure_handlers {
+            if let Err(e) = handler.on_failure(&failure).await {
+                warn!(image = %failure.image, error = %e, "failure handler error");
+            }
+        }
+    }
+
+    async fn verify(&mut self, image: &OciReference) -> std::result::Result<(), Vec<String>> {
+        if let Some(metrics) = &self.metrics {
+            metrics.verification_attempted();
+        }
+        let start = Instant::now();
+
+        let outcome = match self.fetch_signature_layers(image).await {
+            Ok(layers) => verify_constraints(&layers, self.constraints.iter()).map_err(|e| {
+                let reasons = e
+                    .unsatisfied_constraints
+                    .iter()
+                    .map(|c| format!("no signature layer is {}", c.description()))
+                    .collect();
+                (FailureReason::ConstraintsNotSatisfied, reasons)
+            }),
+            Err(e) => Err((FailureReason::from_error(&e), vec![e.to_string()])),
+        };
+
+        match outcome {
+            Ok(()) => {
+                if let Some(metrics) = &self.metrics {
+                    metrics.verification_succeeded(start.elapsed());
+                }
+                Ok(())
+            }
+            Err((reason, reasons)) => {
+                if let Some(metrics) = &self.metrics {
+                    metrics.verification_failed(reason, start.elapsed());
+                }
+                Err(reasons)
+            }
+        }
+    }
+
+    async fn fetch_signature_layers(
+        &mut self,
+        image: &OciReference,
+    ) -> Result<Vec<SignatureLayer>> {
+        let (cosign_image, source_image_digest) =
+            self.client.triangulate(image, &self.auth).await?;
+        self.client
+            .trusted_signature_layers(&self.auth, &source_image_digest, &cosign_image)
+            .await
+    }
+}
+
+#[cfg(feature = "mock-client")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::client::tests::build_test_client;
+    use crate::cosign::client::CONFIG_DATA;
+    use crate::cosign::constants::{SIGSTORE_OCI_MEDIA_TYPE, SIGSTORE_SIGNATURE_ANNOTATION};
+    use crate::cosign::signature_layers::tests::build_correct_signature_layer_without_bundle;
+    use crate::cosign::verification_constraint::VerificationConstraint;
+    use crate::cosign::Client;
+    use crate::metrics::tests::CountingRecorder;
+    use crate::mock_client::test::MockOciClient;
+    use async_trait::async_trait;
+    use oci_distribution::client::{Config, ImageData, ImageLayer};
+    use oci_distribution::manifest::{OciDescriptor, OciImageManifest, OciManifest};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    const IMAGE_DIGEST: &str =
+        "sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b";
+
+    /// A client serving a single image signed with the key of
+    /// `build_correct_signature_layer_without_bundle`, unless the registry
+    /// is not available
+    fn signed_image_client(registry_available: bool) -> Client {
+        let (signature_layer, _) = build_correct_signature_layer_without_bundle();
+        let layer = ImageLayer::new(
+            signature_layer.raw_data,
+            SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+            None,
+        );
+        let manifest = OciImageManifest {
+            layers: vec![OciDescriptor {
+                media_type: SIGSTORE_OCI_MEDIA_TYPE.to_string(),
+                digest: layer.sha256_digest(),
+                size: layer.data.len() as i64,
+                annotations: Some(
+                    [(
+                        SIGSTORE_SIGNATURE_ANNOTATION.to_string(),
+                        signature_layer.signature.unwrap(),
+                    )]
+                    .into(),
+                ),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let image_digest = if registry_available {
+            Ok(IMAGE_DIGEST.to_string())
+        } else {
+            Err(anyhow::anyhow!("registry not available"))
+        };
+
+        build_test_client(MockOciClient {
+            fetch_manifest_digest_response: Some(image_digest),
+            pull_response: Some(Ok(ImageData {
+                layers: vec![layer],
+                digest: None,
+                config: Config::oci_v1(CONFIG_DATA.as_bytes().to_vec(), None),
+                manifest: None,
+            })),
+            pull_manifest_response: Some(Ok((
+                OciManifest::Image(manifest),
+                "sha256:manifest".to_string(),
+            ))),
+            push_response: None,
+        })
+    }
+
+    #[derive(Debug)]
+    struct SwitchConstraint {
+        satisfied: Arc<AtomicBool>,
+    }
+
+    impl VerificationConstraint for SwitchConstraint {
+        fn verify(&self, _signature_layer: &SignatureLayer) -> Result<bool> {
+            Ok(self.satisfied.load(Ordering::SeqCst))
+        }
+
+        fn description(&self) -> String {
+            "switched on".to_string()
+        }
+    }
+
+    #[tokio::test]
+    async fn watcher_reports_transitions() {
+        let satisfied = Arc::new(AtomicBool::new(true));
+        let image: OciReference = "registry.local/busybox:latest".parse().unwrap();
+
+        let mut watcher = Watcher::new(
+            signed_image_client(true),
+            Auth::Anonymous,
+            vec![image.clone()],
+            vec![Box::new(SwitchConstraint {
+                satisfied: satisfied.clone(),
+            })],
+        );
+
+        // first check: the image is always reported
+        let transitions = watcher.check().await;
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].previous, None);
+        assert_eq!(transitions[0].current, VerificationState::Verified);
+
+        // nothing changed
+        assert!(watcher.check().await.is_empty());
+
+        // the policy is no longer satisfied
+        satisfied.store(false, Ordering::SeqCst);
+        let transitions = watcher.check().await;
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].previous, Some(VerificationState::Verified));
+        assert!(!transitions[0].current.is_verified());
+        assert!(!watcher.state(&image).unwrap().is_verified());
+    }
+
+    #[tokio::test]
+    async fn watcher_reverifies_on_client_update() {
+        let image: OciReference = "registry.local/busybox:latest".parse().unwrap();
+        let mut watcher = Watcher::new(
+            signed_image_client(true),
+            Auth::Anonymous,
+            vec![image.clone()],
+            vec![Box::new(SwitchConstraint {
+                satisfied: Arc::new(AtomicBool::new(true)),
+            })],
+        );
+        watcher.check().await;
+
+        let transitions = watcher.update_client(signed_image_client(false)).await;
+        assert_eq!(transitions.len(), 1);
+        assert!(matches!(
+            transitions[0].current,
+            VerificationState::Failed(_)
+        ));
+
+        watcher.unwatch(&image);
+        assert!(watcher.state(&image).is_none());
+        assert!(watcher.check().await.is_empty());
+    }
+
+    #[derive(Default)]
+    struct RecordingHandler {
+        failures: Mutex<Vec<VerificationFailure>>,
+    }
+
+    #[async_trait(?Send)]
+    impl FailureHandler for RecordingHandler {
+        async fn on_failure(&self, failure: &VerificationFailure) -> Result<()> {
+            self.failures.lock().unwrap().push(failure.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn watcher_invokes_failure_handlers() {
+        let satisfied = Arc::new(AtomicBool::new(true));
+        let image: OciReference = "registry.local/busybox:latest".parse().unwrap();
+        let handler = Arc::new(RecordingHandler::default());
+
+        let mut watcher = Watcher::new(
+            signed_image_client(true),
+            Auth::Anonymous,
+            vec![image.clone()],
+            vec![Box::new(SwitchConstraint {
+                satisfied: satisfied.clone(),
+            })],
+        )
+        .with_policy_name("production");
+        watcher.add_failure_handler(handler.clone());
+
+        watcher.check().await;
+        assert!(handler.failures.lock().unwrap().is_empty());
+
+        satisfied.store(false, Ordering::SeqCst);
+        watcher.check().await;
+        // the image is still failing, the handler must not be invoked again
+        watcher.check().await;
+
+        let failures = handler.failures.lock().unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].image, image);
+        assert_eq!(failures[0].policy, "production");
+        assert_eq!(failures[0].previous, Some(VerificationState::Verified));
+        assert_eq!(failures[0].reasons.len(), 1);
+        assert_eq!(
+            failures[0].reasons,
+            vec!["no signature layer is switched on"]
+        );
+    }
+
+    #[tokio::test]
+    async fn watcher_records_metrics() {
+        let satisfied = Arc::new(AtomicBool::new(true));
+        let image: OciReference = "registry.local/busybox:latest".parse().unwrap();
+        let recorder = Arc::new(CountingRecorder::default());
+
+        let mut watcher = Watcher::new(
+            signed_image_client(true),
+            Auth::Anonymous,
+            vec![image],
+            vec![Box::new(SwitchConstraint {
+                satisfied: satisfied.clone(),
+            })],
+        )
+        .with_metrics_recorder(recorder.clone());
+
+        watcher.check().await;
+        satisfied.store(false, Ordering::SeqCst);
+        watcher.check().await;
+        watcher.update_client(signed_image_client(false)).await;
+
+        assert_eq!(*recorder.attempted.lock().unwrap(), 3);
+        assert_eq!(*recorder.succeeded.lock().unwrap(), 1);
+        let failed = recorder.failed.lock().unwrap();
+        assert_eq!(
+            failed.get(&FailureReason::ConstraintsNotSatisfied),
+            Some(&1)
+        );
+        assert_eq!(failed.get(&FailureReason::Registry), Some(&1));
+    }
+}
@@ -0,0 +1,375 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The extensions added by Fulcio to the certificates it issues.
+//!
+//! Fulcio copies the claims of the OIDC token of the signer inside of
+//! the certificate, under the `1.3.6.1.4.1.57264.1` arc. For signatures
+//! produced by a CI system they describe the build that produced them, and
+//! can be used by policy engines to make decisions about its provenance:
+//!
+//! ```rust,no_run
+//! use sigstore::crypto::certificate_extensions::CertificateExtensions;
+//! use x509_cert::Certificate;
+//! use x509_cert::der::Decode;
+//!
+//! # fn doc(der: &[u8]) -> sigstore::errors::Result<()> {
+//! let certificate = Certificate::from_der(der).expect("invalid certificate");
+//! let extensions = CertificateExtensions::from_certificate(&certificate)?;
+//! if extensions.source_repository_ref.as_deref() != Some("refs/heads/main") {
+//!     println!("not built from the main branch");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The extensions are described by the
+//! [Fulcio documentation](https://github.com/sigstore/fulcio/blob/main/docs/oid-info.md).
+
+use const_oid::ObjectIdentifier;
+use der::asn1::Utf8StringRef;
+use der::Decode;
+use serde::Serialize;
+use x509_cert::Certificate;
+
+use crate::errors::{Result, SigstoreError};
+
+/// The OIDC issuer, stored as a raw string. Deprecated by [`ISSUER_V2_OID`]
+const ISSUER_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.1");
+const GITHUB_WORKFLOW_TRIGGER_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.2");
+const GITHUB_WORKFLOW_SHA_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.3");
+const GITHUB_WORKFLOW_NAME_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.4");
+const GITHUB_WORKFLOW_REPOSITORY_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.5");
+const GITHUB_WORKFLOW_REF_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.6");
+/// The OIDC issuer, stored as a DER encoded UTF8String like all the
+/// extensions that follow
+const ISSUER_V2_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.8");
+const BUILD_SIGNER_URI_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.9");
+const BUILD_SIGNER_DIGEST_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.10");
+const RUNNER_ENVIRONMENT_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.11");
+const SOURCE_REPOSITORY_URI_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.12");
+const SOURCE_REPOSITORY_DIGEST_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.13");
+const SOURCE_REPOSITORY_REF_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.14");
+const SOURCE_REPOSITORY_IDENTIFIER_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.15");
+const SOURCE_REPOSITORY_OWNER_URI_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.16");
+const SOURCE_REPOSITORY_OWNER_IDENTIFIER_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.17");
+const BUILD_CONFIG_URI_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.18");
+const BUILD_CONFIG_DIGEST_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.19");
+const BUILD_TRIGGER_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.20");
+const RUN_INVOCATION_URI_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.21");
+const SOURCE_REPOSITORY_VISIBILITY_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.22");
+
+/// The Fulcio extensions found inside of a certificate. Extensions missing
+/// from the certificate are `None`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateExtensions {
+    /// The OIDC issuer that authenticated the signer, taken from the
+    /// `1.3.6.1.4.1.57264.1.8` extension or, for older certificates, from
+    /// the deprecated `1.3.6.1.4.1.57264.1.1` one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    /// The trigger of the GitHub workflow (e.g. `push`). Deprecated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub github_workflow_trigger: Option<String>,
+    /// The commit ID that triggered the GitHub workflow. Deprecated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub github_workflow_sha: Option<String>,
+    /// The name of the GitHub workflow. Deprecated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub github_workflow_name: Option<String>,
+    /// The repository that owns the GitHub workflow. Deprecated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub github_workflow_repository: Option<String>,
+    /// The Git ref that triggered the GitHub workflow. Deprecated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub github_workflow_ref: Option<String>,
+    /// The URI of the build instructions that signed the artifact, like
+    /// a reusable workflow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_signer_uri: Option<String>,
+    /// The digest of the build instructions that signed the artifact
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_signer_digest: Option<String>,
+    /// Whether the build ran on `github-hosted` or `self-hosted` runners
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runner_environment: Option<String>,
+    /// The URI of the source repository
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_repository_uri: Option<String>,
+    /// The commit digest of the source that was built
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_repository_digest: Option<String>,
+    /// The Git ref of the source that was built (e.g. `refs/tags/v1.0.0`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_repository_ref: Option<String>,
+    /// The immutable identifier of the source repository
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_repository_identifier: Option<String>,
+    /// The URI of the owner of the source repository
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_repository_owner_uri: Option<String>,
+    /// The immutable identifier of the owner of the source repository
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_repository_owner_identifier: Option<String>,
+    /// The URI of the top-level build instructions, like the workflow file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_config_uri: Option<String>,
+    /// The digest of the top-level build instructions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_config_digest: Option<String>,
+    /// The event that triggered the build (e.g. `push`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_trigger: Option<String>,
+    /// The URI of the run that produced the signature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run_invocation_uri: Option<String>,
+    /// The visibility of the source repository when the artifact was
+    /// signed (e.g. `public`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_repository_visibility_at_signing: Option<String>,
+}
+
+impl CertificateExtensions {
+    /// Extract the Fulcio extensions of `certificate`
+    pub fn from_certificate(certificate: &Certificate) -> Result<Self> {
+        let mut extensions = CertificateExtensions::default();
+        let mut legacy_issuer = None;
+
+        for ext in certificate.tbs_certificate.extensions.iter().flatten() {
+            let oid = ext.extn_id;
+            let (field, value) = match oid {
+                ISSUER_OID => (&mut legacy_issuer, raw_string(oid, ext.extn_value)?),
+                GITHUB_WORKFLOW_TRIGGER_OID => (
+                    &mut extensions.github_workflow_trigger,
+                    raw_string(oid, ext.extn_value)?,
+                ),
+                GITHUB_WORKFLOW_SHA_OID => (
+                    &mut extensions.github_workflow_sha,
+                    raw_string(oid, ext.extn_value)?,
+                ),
+                GITHUB_WORKFLOW_NAME_OID => (
+                    &mut extensions.github_workflow_name,
+                    raw_string(oid, ext.extn_value)?,
+                ),
+                GITHUB_WORKFLOW_REPOSITORY_OID => (
+                    &mut extensions.github_workflow_repository,
+                    raw_string(oid, ext.extn_value)?,
+                ),
+                GITHUB_WORKFLOW_REF_OID => (
+                    &mut extensions.github_workflow_ref,
+                    raw_string(oid, ext.extn_value)?,
+                ),
+                ISSUER_V2_OID => (&mut extensions.issuer, der_string(oid, ext.extn_value)?),
+                BUILD_SIGNER_URI_OID => (
+                    &mut extensions.build_signer_uri,
+                    der_string(oid, ext.extn_value)?,
+                ),
+                BUILD_SIGNER_DIGEST_OID => (
+                    &mut extensions.build_signer_digest,
+                    der_string(oid, ext.extn_value)?,
+                ),
+                RUNNER_ENVIRONMENT_OID => (
+                    &mut extensions.runner_environment,
+                    der_string(oid, ext.extn_value)?,
+                ),
+                SOURCE_REPOSITORY_URI_OID => (
+                    &mut extensions.source_repository_uri,
+                    der_string(oid, ext.extn_value)?,
+                ),
+                SOURCE_REPOSITORY_DIGEST_OID => (
+                    &mut extensions.source_repository_digest,
+                    der_string(oid, ext.extn_value)?,
+                ),
+                SOURCE_REPOSITORY_REF_OID => (
+                    &mut extensions.source_repository_ref,
+                    der_string(oid, ext.extn_value)?,
+                ),
+                SOURCE_REPOSITORY_IDENTIFIER_OID => (
+                    &mut extensions.source_repository_identifier,
+                    der_string(oid, ext.extn_value)?,
+                ),
+                SOURCE_REPOSITORY_OWNER_URI_OID => (
+                    &mut extensions.source_repository_owner_uri,
+                    der_string(oid, ext.extn_value)?,
+                ),
+                SOURCE_REPOSITORY_OWNER_IDENTIFIER_OID => (
+                    &mut extensions.source_repository_owner_identifier,
+                    der_string(oid, ext.extn_value)?,
+                ),
+                BUILD_CONFIG_URI_OID => (
+                    &mut extensions.build_config_uri,
+                    der_string(oid, ext.extn_value)?,
+                ),
+                BUILD_CONFIG_DIGEST_OID => (
+                    &mut extensions.build_config_digest,
+                    der_string(oid, ext.extn_value)?,
+                ),
+                BUILD_TRIGGER_OID => (
+                    &mut extensions.build_trigger,
+                    der_string(oid, ext.extn_value)?,
+                ),
+                RUN_INVOCATION_URI_OID => (
+                    &mut extensions.run_invocation_uri,
+                    der_string(oid, ext.extn_value)?,
+                ),
+                SOURCE_REPOSITORY_VISIBILITY_OID => (
+                    &mut extensions.source_repository_visibility_at_signing,
+                    der_string(oid, ext.extn_value)?,
+                ),
+                _ => continue,
+            };
+            *field = Some(value);
+        }
+
+        if extensions.issuer.is_none() {
+            extensions.issuer = legacy_issuer;
+        }
+        Ok(extensions)
+    }
+}
+
+/// Decode the value of the extensions that store the raw bytes of a string
+fn raw_string(oid: ObjectIdentifier, value: &[u8]) -> Result<String> {
+    String::from_utf8(value.to_vec()).map_err(|_| {
+        SigstoreError::X509Error(format!(
+            "Certificate's extension {oid} is not UTF8 compatible"
+        ))
+    })
+}
+
+/// Decode the value of the extensions that store a DER encoded UTF8String
+fn der_string(oid: ObjectIdentifier, value: &[u8]) -> Result<String> {
+    Utf8StringRef::from_der(value)
+        .map(String::from)
+        .map_err(|e| {
+            SigstoreError::X509Error(format!(
+                "Certificate's extension {oid} is not a UTF8String: {e}"
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::tests::{generate_certificate, CertGenerationOptions};
+    use der::Encode;
+    use x509_cert::ext::Extension;
+
+    fn der_utf8(value: &str) -> Vec<u8> {
+        Utf8StringRef::new(value).unwrap().to_vec().unwrap()
+    }
+
+    #[test]
+    fn extract_fulcio_extensions() {
+        let ca = generate_certificate(None, CertGenerationOptions::default()).unwrap();
+        let issued = generate_certificate(Some(&ca), CertGenerationOptions::default()).unwrap();
+        let der = issued.cert.to_der().unwrap();
+        let mut certificate = Certificate::from_der(&der).unwrap();
+
+        // no Fulcio extensions inside of the generated certificate
+        assert_eq!(
+            CertificateExtensions::from_certificate(&certificate).unwrap(),
+            CertificateExtensions::default()
+        );
+
+        let issuer = der_utf8("https://token.actions.githubusercontent.com");
+        let source_ref = der_utf8("refs/tags/v1.0.0");
+        let visibility = der_utf8("public");
+        let fulcio_extensions = [
+            (ISSUER_OID, b"https://legacy.example.com".as_slice()),
+            (GITHUB_WORKFLOW_TRIGGER_OID, b"push".as_slice()),
+            (ISSUER_V2_OID, issuer.as_slice()),
+            (SOURCE_REPOSITORY_REF_OID, source_ref.as_slice()),
+            (SOURCE_REPOSITORY_VISIBILITY_OID, visibility.as_slice()),
+        ];
+        let extensions = certificate
+            .tbs_certificate
+            .extensions
+            .get_or_insert_with(Vec::new);
+        for (extn_id, extn_value) in fulcio_extensions {
+            extensions.push(Extension {
+                extn_id,
+                critical: false,
+                extn_value,
+            });
+        }
+
+        let extensions = CertificateExtensions::from_certificate(&certificate).unwrap();
+        assert_eq!(
+            extensions.issuer.as_deref(),
+            Some("https://token.actions.githubusercontent.com")
+        );
+        assert_eq!(extensions.github_workflow_trigger.as_deref(), Some("push"));
+        assert_eq!(
+            extensions.source_repository_ref.as_deref(),
+            Some("refs/tags/v1.0.0")
+        );
+        assert_eq!(
+            extensions
+                .source_repository_visibility_at_signing
+                .as_deref(),
+            Some("public")
+        );
+        assert_eq!(extensions.build_signer_uri, None);
+
+        // the legacy issuer is used when the new one is missing
+        certificate
+            .tbs_certificate
+            .extensions
+            .as_mut()
+            .unwrap()
+            .retain(|ext| ext.extn_id != ISSUER_V2_OID);
+        let extensions = CertificateExtensions::from_certificate(&certificate).unwrap();
+        assert_eq!(
+            extensions.issuer.as_deref(),
+            Some("https://legacy.example.com")
+        );
+
+        // values of the new extensions must be DER encoded
+        certificate
+            .tbs_certificate
+            .extensions
+            .as_mut()
+            .unwrap()
+            .push(Extension {
+                extn_id: BUILD_TRIGGER_OID,
+                critical: false,
+                extn_value: b"push",
+            });
+        assert!(matches!(
+            CertificateExtensions::from_certificate(&certificate),
+            Err(SigstoreError::X509Error(_))
+        ));
+    }
+}
@@ -13,6 +13,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use pkcs8::der::Decode;
+use x509_cert::ext::pkix::{AuthorityKeyIdentifier, SubjectKeyIdentifier};
+
+use super::revocation::{check_revocation, RevocationChecker, RevocationPolicy};
 use crate::{
     errors::{Result, SigstoreError},
     registry::Certificate,
@@ -20,7 +27,7 @@ use crate::{
 
 // The untrusted intermediate CA certificate, used for chain building
 // TODO: Remove once this is bundled in TUF metadata.
-const FULCIO_INTERMEDIATE_V1: &str = "-----BEGIN CERTIFICATE-----
+pub(crate) const FULCIO_INTERMEDIATE_V1: &str = "-----BEGIN CERTIFICATE-----
 MIICGjCCAaGgAwIBAgIUALnViVfnU0brJasmRkHrn/UnfaQwCgYIKoZIzj0EAwMw
 KjEVMBMGA1UEChMMc2lnc3RvcmUuZGV2MREwDwYDVQQDEwhzaWdzdG9yZTAeFw0y
 MjA0MTMyMDA2MTVaFw0zMTEwMDUxMzU2NThaMDcxFTATBgNVBAoTDHNpZ3N0b3Jl
@@ -35,19 +42,51 @@ nZU84/9DZdnFvvxmAjBOt6QpBlc4J/0DxvkTCqpclvziL6BCCPnjdlIB3Pu3BxsP
 mygUY7Ii2zbdCdliiow=
 -----END CERTIFICATE-----";
 
-/// A collection of trusted root certificates
+/// The maximum number of intermediates inside of a chain
+const MAX_INTERMEDIATES: usize = 4;
+
+/// A trust store holding trusted root certificates and the intermediates
+/// used to build the chains leading to them.
+///
+/// The certificates are indexed by their Subject Key Identifier: the
+/// issuers of a certificate are found through its Authority Key Identifier,
+/// instead of being looked up among all the certificates of the pool. The
+/// certificates lacking these extensions are still handled, by comparing
+/// them to every certificate of the pool.
+///
+/// ```rust,no_run
+/// use sigstore::crypto::CertificatePool;
+/// use sigstore::registry::{Certificate, CertificateEncoding};
+///
+/// # fn main() -> sigstore::errors::Result<()> {
+/// let pool = CertificatePool::from_certificates(&[Certificate {
+///     encoding: CertificateEncoding::Pem,
+///     data: std::fs::read("fulcio-root.pem")?,
+/// }])?;
+/// let chain = pool.build_chain(&std::fs::read("signing-cert.der")?)?;
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Default, Debug)]
-pub(crate) struct CertificatePool {
+pub struct CertificatePool {
     trusted_roots: Vec<picky::x509::Cert>,
     intermediates: Vec<picky::x509::Cert>,
+    /// The certificates of the pool, by Subject Key Identifier
+    by_subject_key_id: HashMap<Vec<u8>, Vec<PoolEntry>>,
+    revocation: Option<(Arc<dyn RevocationChecker>, RevocationPolicy)>,
+}
+
+/// The position of a certificate inside of the pool
+#[derive(Clone, Copy, Debug)]
+enum PoolEntry {
+    Root(usize),
+    Intermediate(usize),
 }
 
 impl CertificatePool {
     /// Build a `CertificatePool` instance using the provided list of [`Certificate`]
-    pub(crate) fn from_certificates(certs: &[Certificate]) -> Result<Self> {
-        let mut trusted_roots = vec![];
-        let mut intermediates = vec![];
-
+    pub fn from_certificates(certs: &[Certificate]) -> Result<Self> {
+        let mut pool = CertificatePool::default();
         for c in certs {
             let pc = match c.encoding {
                 crate::registry::CertificateEncoding::Pem => {
@@ -58,31 +97,78 @@ impl CertificatePool {
                 }
                 crate::registry::CertificateEncoding::Der => picky::x509::Cert::from_der(&c.data),
             }?;
+            pool.add(pc)?;
+        }
 
-            match pc.ty() {
-                picky::x509::certificate::CertType::Root => {
-                    trusted_roots.push(pc);
-                }
-                picky::x509::certificate::CertType::Intermediate => {
-                    intermediates.push(pc);
-                }
-                _ => {
-                    return Err(SigstoreError::CertificatePoolError(
-                        "Cannot add a certificate that is no root or intermediate".to_string(),
-                    ));
-                }
+        // TODO: Remove once FULCIO_INTERMEDIATE_V1 is bundled in TUF metadata.
+        if pool.intermediates.is_empty() {
+            pool.add(picky::x509::Cert::from_pem_str(FULCIO_INTERMEDIATE_V1)?)?;
+        }
+
+        Ok(pool)
+    }
+
+    /// Check the revocation status of the verified certificates and of
+    /// the intermediates of their chains with `checker`. The statuses that
+    /// cannot be determined are handled according to `policy`.
+    ///
+    /// See the [`revocation`](crate::crypto::revocation) module.
+    pub fn with_revocation_checker(
+        mut self,
+        checker: Arc<dyn RevocationChecker>,
+        policy: RevocationPolicy,
+    ) -> Self {
+        self.revocation = Some((checker, policy));
+        self
+    }
+
+    /// Add a root or intermediate certificate to the pool
+    fn add(&mut self, cert: picky::x509::Cert) -> Result<()> {
+        let (subject_key_id, _) = key_identifiers(&cert)?;
+        let entry = match cert.ty() {
+            picky::x509::certificate::CertType::Root => {
+                self.trusted_roots.push(cert);
+                PoolEntry::Root(self.trusted_roots.len() - 1)
             }
+            picky::x509::certificate::CertType::Intermediate => {
+                self.intermediates.push(cert);
+                PoolEntry::Intermediate(self.intermediates.len() - 1)
+            }
+            _ => {
+                return Err(SigstoreError::CertificatePoolError(
+                    "Cannot add a certificate that is no root or intermediate".to_string(),
+                ));
+            }
+        };
+        if let Some(subject_key_id) = subject_key_id {
+            self.by_subject_key_id
+                .entry(subject_key_id)
+                .or_default()
+                .push(entry);
         }
+        Ok(())
+    }
 
-        // TODO: Remove once FULCIO_INTERMEDIATE_V1 is bundled in TUF metadata.
-        if intermediates.is_empty() {
-            intermediates.push(picky::x509::Cert::from_pem_str(FULCIO_INTERMEDIATE_V1)?);
+    fn get(&self, entry: PoolEntry) -> (&picky::x509::Cert, bool) {
+        match entry {
+            PoolEntry::Root(i) => (&self.trusted_roots[i], true),
+            PoolEntry::Intermediate(i) => (&self.intermediates[i], false),
         }
+    }
 
-        Ok(CertificatePool {
-            trusted_roots,
-            intermediates,
-        })
+    /// The certificates of the pool that can have issued `cert`, with a flag
+    /// telling whether they are trusted roots
+    fn issuer_candidates(&self, cert: &picky::x509::Cert) -> Vec<(&picky::x509::Cert, bool)> {
+        let authority_key_id = key_identifiers(cert).ok().and_then(|(_, aki)| aki);
+        match authority_key_id.and_then(|aki| self.by_subject_key_id.get(&aki)) {
+            Some(entries) => entries.iter().map(|entry| self.get(*entry)).collect(),
+            None => self
+                .trusted_roots
+                .iter()
+                .map(|c| (c, true))
+                .chain(self.intermediates.iter().map(|c| (c, false)))
+                .collect(),
+        }
     }
 
     /// Ensures the given certificate has been issued by one of the trusted root certificates
@@ -93,12 +179,22 @@ impl CertificatePool {
     /// Because of that the validity checks performed by this method are more
     /// relaxed. The validity checks are done inside of
     /// [`crate::crypto::verify_validity`] and [`crate::crypto::verify_expiration`].
-    pub(crate) fn verify_pem_cert(&self, cert_pem: &[u8]) -> Result<()> {
+    pub fn verify_pem_cert(&self, cert_pem: &[u8]) -> Result<()> {
         let cert_pem_str = std::str::from_utf8(cert_pem).map_err(|_| {
             SigstoreError::UnexpectedError("Cannot convert cert back to string".to_string())
         })?;
         let cert = picky::x509::Cert::from_pem_str(cert_pem_str)?;
-        self.verify(&cert)
+        self.verify(&cert).map(|_| ())
+    }
+
+    /// Like [`CertificatePool::verify_pem_cert`], returns the DER encoded
+    /// certificate that issued the given one
+    pub(crate) fn verify_pem_cert_issuer(&self, cert_pem: &[u8]) -> Result<Vec<u8>> {
+        let cert_pem_str = std::str::from_utf8(cert_pem).map_err(|_| {
+            SigstoreError::UnexpectedError("Cannot convert cert back to string".to_string())
+        })?;
+        let cert = picky::x509::Cert::from_pem_str(cert_pem_str)?;
+        Ok(self.verify(&cert)?[0].to_der()?)
     }
 
     /// Ensures the given certificate has been issued by one of the trusted root certificates
@@ -109,45 +205,280 @@ impl CertificatePool {
     /// Because of that the validity checks performed by this method are more
     /// relaxed. The validity checks are done inside of
     /// [`crate::crypto::verify_validity`] and [`crate::crypto::verify_expiration`].
-    pub(crate) fn verify_der_cert(&self, bytes: &[u8]) -> Result<()> {
+    pub fn verify_der_cert(&self, bytes: &[u8]) -> Result<()> {
         let cert = picky::x509::Cert::from_der(bytes)?;
-        self.verify(&cert)
+        self.verify(&cert).map(|_| ())
+    }
+
+    /// Build the verified chain of the given DER encoded certificate, which
+    /// must be issued by one of the trusted roots. The DER encoded
+    /// certificates of the chain are returned starting from the issuer of
+    /// the given one, the trusted root being the last one.
+    pub fn build_chain(&self, cert_der: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let cert = picky::x509::Cert::from_der(cert_der)?;
+        self.verify(&cert)?
+            .into_iter()
+            .map(|c| c.to_der().map_err(SigstoreError::from))
+            .collect()
     }
 
-    fn verify(&self, cert: &picky::x509::Cert) -> Result<()> {
-        let verified = self
-            .create_chains_for_all_certificates()
-            .iter()
-            .any(|chain| {
+    /// Returns the verified chain of `cert`, with its issuer first
+    fn verify(&self, cert: &picky::x509::Cert) -> Result<Vec<&picky::x509::Cert>> {
+        let mut chains = vec![];
+        self.build_chains(cert, &mut vec![], &mut chains);
+        let chain = chains
+            .into_iter()
+            .find(|chain| {
                 cert.verifier()
                     .chain(chain.iter().copied())
                     .exact_date(&cert.valid_not_before())
                     .verify()
                     .is_ok()
-            });
+            })
+            .ok_or_else(|| {
+                SigstoreError::CertificateValidityError("Not issued by a trusted root".to_string())
+            })?;
 
-        if verified {
-            Ok(())
-        } else {
-            Err(SigstoreError::CertificateValidityError(
-                "Not issued by a trusted root".to_string(),
-            ))
+        if let Some((checker, policy)) = &self.revocation {
+            // the trusted root, last of the chain, can't be revoked
+            let mut subject = cert;
+            for issuer in chain.iter().copied() {
+                check_revocation(
+                    checker.as_ref(),
+                    *policy,
+                    &subject.to_der()?,
+                    &issuer.to_der()?,
+                )?;
+                subject = issuer;
+            }
         }
+        Ok(chain)
     }
 
-    fn create_chains_for_all_certificates(&self) -> Vec<Vec<&picky::x509::Cert>> {
-        let mut chains: Vec<Vec<&picky::x509::Cert>> = vec![];
-        self.trusted_roots.iter().for_each(|trusted_root| {
-            chains.push([trusted_root].to_vec());
-        });
-        self.intermediates.iter().for_each(|intermediate| {
-            for root in self.trusted_roots.iter() {
-                if root.is_parent_of(intermediate).is_ok() {
-                    chains.push([intermediate, root].to_vec());
-                }
+    /// Add to `chains` all the chains leading `cert` to a trusted root, with
+    /// the certificate closest to the leaf first. `path` holds the issuers
+    /// found so far. Intermediates can be issued by other intermediates, as
+    /// happens when the intermediates of Fulcio are rotated.
+    fn build_chains<'a>(
+        &'a self,
+        cert: &picky::x509::Cert,
+        path: &mut Vec<&'a picky::x509::Cert>,
+        chains: &mut Vec<Vec<&'a picky::x509::Cert>>,
+    ) {
+        for (issuer, is_root) in self.issuer_candidates(cert) {
+            let in_path = path.iter().any(|c| std::ptr::eq(*c, issuer));
+            if in_path || issuer.is_parent_of(cert).is_err() {
+                continue;
             }
-        });
+            path.push(issuer);
+            if is_root {
+                chains.push(path.clone());
+            } else if path.len() <= MAX_INTERMEDIATES {
+                self.build_chains(issuer, path, chains);
+            }
+            path.pop();
+        }
+    }
+}
+
+/// A Subject Key Identifier and the key identifier of an Authority Key
+/// Identifier, each of them is optional
+type KeyIdentifiers = (Option<Vec<u8>>, Option<Vec<u8>>);
+
+/// The Subject Key Identifier and the key identifier of the Authority Key
+/// Identifier of `cert`
+fn key_identifiers(cert: &picky::x509::Cert) -> Result<KeyIdentifiers> {
+    let der = cert.to_der()?;
+    let cert = x509_cert::Certificate::from_der(&der)
+        .map_err(|e| SigstoreError::X509Error(format!("parse from der: {e}")))?;
+    let subject_key_id = cert
+        .tbs_certificate
+        .get::<SubjectKeyIdentifier>()
+        .ok()
+        .flatten()
+        .map(|(_, ski)| ski.0.as_bytes().to_vec());
+    let authority_key_id = cert
+        .tbs_certificate
+        .get::<AuthorityKeyIdentifier>()
+        .ok()
+        .flatten()
+        .and_then(|(_, aki)| aki.key_identifier)
+        .map(|key_id| key_id.as_bytes().to_vec());
+    Ok((subject_key_id, authority_key_id))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::crypto::tests::generate_ecdsa_p256_keypair;
+    use crate::registry::CertificateEncoding;
+    use openssl::asn1::{Asn1Integer, Asn1Time};
+    use openssl::bn::BigNum;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::{PKey, Private};
+    use openssl::x509::extension::{
+        AuthorityKeyIdentifier, BasicConstraints, KeyUsage, SubjectAlternativeName,
+        SubjectKeyIdentifier,
+    };
+    use openssl::x509::{X509NameBuilder, X509};
+
+    pub(crate) struct Issued {
+        pub(crate) cert: X509,
+        pub(crate) private_key: PKey<Private>,
+    }
+
+    impl Issued {
+        pub(crate) fn to_certificate(&self) -> Certificate {
+            Certificate {
+                encoding: CertificateEncoding::Pem,
+                data: self.cert.to_pem().unwrap(),
+            }
+        }
+    }
+
+    /// Issue a certificate named `name`, self-signed when `issuer` is `None`
+    pub(crate) fn issue(name: &str, issuer: Option<&Issued>, ca: bool) -> Issued {
+        let (private_key, public_key) = generate_ecdsa_p256_keypair();
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_text("O", "tests").unwrap();
+        subject.append_entry_by_text("CN", name).unwrap();
+        let subject = subject.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        let serial = Asn1Integer::from_bn(&BigNum::from_u32(rand::random()).unwrap()).unwrap();
+        builder.set_serial_number(&serial).unwrap();
+        builder.set_subject_name(&subject).unwrap();
+        builder.set_pubkey(&public_key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        let issuer_name = issuer.map_or(subject.as_ref(), |i| i.cert.subject_name());
+        builder.set_issuer_name(issuer_name).unwrap();
+
+        let context = builder.x509v3_context(issuer.map(|i| i.cert.as_ref()), None);
+        let mut extensions = vec![SubjectKeyIdentifier::new().build(&context).unwrap()];
+        if issuer.is_some() {
+            extensions.push(
+                AuthorityKeyIdentifier::new()
+                    .keyid(true)
+                    .build(&context)
+                    .unwrap(),
+            );
+        }
+        if ca {
+            extensions.push(BasicConstraints::new().critical().ca().build().unwrap());
+            extensions.push(
+                KeyUsage::new()
+                    .critical()
+                    .key_cert_sign()
+                    .crl_sign()
+                    .build()
+                    .unwrap(),
+            );
+        } else {
+            extensions.push(
+                KeyUsage::new()
+                    .critical()
+                    .digital_signature()
+                    .build()
+                    .unwrap(),
+            );
+            extensions.push(
+                SubjectAlternativeName::new()
+                    .email("tests@sigstore-rs.dev")
+                    .build(&context)
+                    .unwrap(),
+            );
+        }
+        for extension in extensions {
+            builder.append_extension(extension).unwrap();
+        }
+
+        let signing_key = issuer.map_or(&private_key, |i| &i.private_key);
+        builder.sign(signing_key, MessageDigest::sha256()).unwrap();
+        Issued {
+            cert: builder.build(),
+            private_key,
+        }
+    }
+
+    #[test]
+    fn verify_chain_with_rotated_intermediates() {
+        let root = issue("root", None, true);
+        let intermediate = issue("intermediate", Some(&root), true);
+        let rotated = issue("rotated intermediate", Some(&intermediate), true);
+        let leaf = issue("leaf", Some(&rotated), false);
+
+        let pool = CertificatePool::from_certificates(&[
+            rotated.to_certificate(),
+            root.to_certificate(),
+            intermediate.to_certificate(),
+        ])
+        .unwrap();
+        let issuer = pool
+            .verify_pem_cert_issuer(&leaf.cert.to_pem().unwrap())
+            .unwrap();
+        assert_eq!(issuer, rotated.cert.to_der().unwrap());
+
+        // the chain is broken without the intermediate in the middle
+        let pool =
+            CertificatePool::from_certificates(&[rotated.to_certificate(), root.to_certificate()])
+                .unwrap();
+        assert!(pool.verify_pem_cert(&leaf.cert.to_pem().unwrap()).is_err());
+    }
+
+    #[test]
+    fn verify_chain_with_multiple_roots() {
+        let old_root = issue("old root", None, true);
+        let old_intermediate = issue("old intermediate", Some(&old_root), true);
+        let new_root = issue("new root", None, true);
+        let new_intermediate = issue("new intermediate", Some(&new_root), true);
+
+        let pool = CertificatePool::from_certificates(&[
+            old_intermediate.to_certificate(),
+            old_root.to_certificate(),
+            new_intermediate.to_certificate(),
+            new_root.to_certificate(),
+        ])
+        .unwrap();
+        for intermediate in [&old_intermediate, &new_intermediate] {
+            let leaf = issue("leaf", Some(intermediate), false);
+            assert!(pool.verify_der_cert(&leaf.cert.to_der().unwrap()).is_ok());
+        }
+
+        let untrusted_root = issue("untrusted root", None, true);
+        let leaf = issue("leaf", Some(&untrusted_root), false);
+        assert!(matches!(
+            pool.verify_der_cert(&leaf.cert.to_der().unwrap()),
+            Err(SigstoreError::CertificateValidityError(_))
+        ));
+    }
+
+    #[test]
+    fn build_chain_through_key_identifiers() {
+        let root = issue("root", None, true);
+        let intermediate = issue("intermediate", Some(&root), true);
+        // same subject as the genuine intermediate, but another key
+        let decoy = issue("intermediate", Some(&root), true);
+        let leaf = issue("leaf", Some(&intermediate), false);
 
-        chains
+        let pool = CertificatePool::from_certificates(&[
+            decoy.to_certificate(),
+            root.to_certificate(),
+            intermediate.to_certificate(),
+        ])
+        .unwrap();
+        let chain = pool.build_chain(&leaf.cert.to_der().unwrap()).unwrap();
+        assert_eq!(
+            chain,
+            vec![
+                intermediate.cert.to_der().unwrap(),
+                root.cert.to_der().unwrap()
+            ]
+        );
     }
 }
@@ -0,0 +1,510 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of the Signed Certificate Timestamps (SCTs) of Fulcio
+//! certificates.
+//!
+//! Fulcio submits each certificate it issues to a Certificate Transparency
+//! log. The log answers with a promise of inclusion, the SCT, described by
+//! [RFC 6962](https://www.rfc-editor.org/rfc/rfc6962#section-3.2).
+//! The SCT is usually embedded inside of the certificate, older Fulcio
+//! instances return it next to the certificate instead.
+//!
+//! A valid SCT proves the certificate is publicly auditable: a certificate
+//! issued by a compromised Fulcio without being logged is rejected.
+//!
+//! ```rust,no_run
+//! use sigstore::crypto::certificate_transparency::verify_embedded_sct;
+//! use sigstore::crypto::trusted_root::TrustedRoot;
+//! use std::path::Path;
+//! use x509_cert::der::Decode;
+//! use x509_cert::Certificate;
+//!
+//! # fn doc(cert_der: &[u8], issuer_spki_der: &[u8]) -> sigstore::errors::Result<()> {
+//! let trusted_root = TrustedRoot::from_file(Path::new("trusted_root.json"))?;
+//! let certificate = Certificate::from_der(cert_der).expect("invalid certificate");
+//! verify_embedded_sct(&certificate, issuer_spki_der, &trusted_root)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use const_oid::ObjectIdentifier;
+use der::asn1::OctetStringRef;
+use der::{Decode, Encode};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::convert::{TryFrom, TryInto};
+use tracing::debug;
+use x509_cert::Certificate;
+
+use super::trusted_root::TrustedRoot;
+use super::Signature;
+use crate::errors::{Result, SigstoreError};
+
+/// The extension holding the SCTs embedded inside of a certificate
+const SCT_LIST_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.11129.2.4.2");
+
+/// The only version of SCT defined by RFC 6962
+const SCT_VERSION_V1: u8 = 0;
+/// The `certificate_timestamp` signature type
+const SIGNATURE_TYPE_CERTIFICATE_TIMESTAMP: u8 = 0;
+/// The `sha256` hash algorithm of TLS
+const HASH_ALGORITHM_SHA256: u8 = 4;
+
+/// The type of the entry logged by the CT log
+const ENTRY_TYPE_X509: u16 = 0;
+const ENTRY_TYPE_PRECERT: u16 = 1;
+
+/// A Signed Certificate Timestamp, issued by a CT log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedCertificateTimestamp {
+    pub version: u8,
+    /// The SHA-256 digest of the DER encoded key of the log
+    pub log_id: [u8; 32],
+    /// Milliseconds since the UNIX epoch
+    pub timestamp: u64,
+    pub extensions: Vec<u8>,
+    /// The TLS identifier of the hash algorithm used by the signature
+    pub hash_algorithm: u8,
+    /// The TLS identifier of the signature algorithm
+    pub signature_algorithm: u8,
+    pub signature: Vec<u8>,
+}
+
+/// The JSON representation of a detached SCT, as returned by the
+/// `add-chain` endpoint of the CT logs and by Fulcio
+#[derive(Deserialize)]
+struct DetachedSct {
+    sct_version: u8,
+    id: String,
+    timestamp: u64,
+    #[serde(default)]
+    extensions: String,
+    signature: String,
+}
+
+/// The entry of the log covered by the signature of a SCT
+enum LogEntry<'a> {
+    /// The certificate itself, for detached SCTs
+    X509(&'a [u8]),
+    /// The certificate, before the SCT was embedded into it
+    Precert {
+        issuer_key_hash: [u8; 32],
+        tbs_certificate: Vec<u8>,
+    },
+}
+
+impl SignedCertificateTimestamp {
+    /// Parse a TLS encoded SCT
+    pub fn from_tls(bytes: &[u8]) -> Result<Self> {
+        let mut reader = TlsReader(bytes);
+        let version = reader.read_u8()?;
+        let log_id = reader.read(32)?.try_into().expect("read returns 32 bytes");
+        let timestamp = reader.read_u64()?;
+        let extensions = reader.read_vec_u16()?.to_vec();
+        let hash_algorithm = reader.read_u8()?;
+        let signature_algorithm = reader.read_u8()?;
+        let signature = reader.read_vec_u16()?.to_vec();
+        reader.finish()?;
+
+        Ok(SignedCertificateTimestamp {
+            version,
+            log_id,
+            timestamp,
+            extensions,
+            hash_algorithm,
+            signature_algorithm,
+            signature,
+        })
+    }
+
+    /// Parse a detached SCT, encoded as JSON like the responses of the
+    /// `add-chain` endpoint of the CT logs
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let detached: DetachedSct = serde_json::from_str(raw)
+            .map_err(|e| SigstoreError::SctVerificationError(format!("invalid SCT: {e}")))?;
+        let log_id = BASE64_STD_ENGINE
+            .decode(&detached.id)?
+            .try_into()
+            .map_err(|_| SigstoreError::SctVerificationError("invalid log ID".to_string()))?;
+
+        let signature = BASE64_STD_ENGINE.decode(&detached.signature)?;
+        let mut reader = TlsReader(&signature);
+        let hash_algorithm = reader.read_u8()?;
+        let signature_algorithm = reader.read_u8()?;
+        let signature = reader.read_vec_u16()?.to_vec();
+        reader.finish()?;
+
+        Ok(SignedCertificateTimestamp {
+            version: detached.sct_version,
+            log_id,
+            timestamp: detached.timestamp,
+            extensions: BASE64_STD_ENGINE.decode(&detached.extensions)?,
+            hash_algorithm,
+            signature_algorithm,
+            signature,
+        })
+    }
+
+    /// The hex encoded ID of the log that issued the SCT
+    pub fn log_id_hex(&self) -> String {
+        hex::encode(self.log_id)
+    }
+
+    /// Verify the SCT with the keys of the CT logs known by `trusted_root`
+    fn verify(&self, entry: &LogEntry<'_>, trusted_root: &TrustedRoot) -> Result<()> {
+        if self.version != SCT_VERSION_V1 {
+            return Err(SigstoreError::SctVerificationError(format!(
+                "unsupported SCT version {}",
+                self.version
+            )));
+        }
+        if self.hash_algorithm != HASH_ALGORITHM_SHA256 {
+            return Err(SigstoreError::SctVerificationError(format!(
+                "unsupported hash algorithm {}",
+                self.hash_algorithm
+            )));
+        }
+
+        let log_id = self.log_id_hex();
+        let time = (self.timestamp / 1000) as i64;
+        let keys = trusted_root.ct_log_pub_keys_for_log_at(&log_id, time);
+        if keys.is_empty() {
+            return Err(SigstoreError::SctVerificationError(format!(
+                "no trusted CT log with ID {log_id} at {time}"
+            )));
+        }
+
+        let signed_data = self.signed_data(entry)?;
+        if keys.iter().any(|key| {
+            key.verify_signature(Signature::Raw(&self.signature), &signed_data)
+                .is_ok()
+        }) {
+            Ok(())
+        } else {
+            Err(SigstoreError::SctVerificationError(format!(
+                "invalid signature of CT log {log_id}"
+            )))
+        }
+    }
+
+    /// The data signed by the log, the `digitally-signed` struct of RFC 6962
+    fn signed_data(&self, entry: &LogEntry<'_>) -> Result<Vec<u8>> {
+        let mut data = vec![self.version, SIGNATURE_TYPE_CERTIFICATE_TIMESTAMP];
+        data.extend_from_slice(&self.timestamp.to_be_bytes());
+        match entry {
+            LogEntry::X509(certificate) => {
+                data.extend_from_slice(&ENTRY_TYPE_X509.to_be_bytes());
+                write_vec_u24(&mut data, certificate)?;
+            }
+            LogEntry::Precert {
+                issuer_key_hash,
+                tbs_certificate,
+            } => {
+                data.extend_from_slice(&ENTRY_TYPE_PRECERT.to_be_bytes());
+                data.extend_from_slice(issuer_key_hash);
+                write_vec_u24(&mut data, tbs_certificate)?;
+            }
+        }
+        let extensions_len = u16::try_from(self.extensions.len()).map_err(|_| {
+            SigstoreError::SctVerificationError("SCT extensions are too long".to_string())
+        })?;
+        data.extend_from_slice(&extensions_len.to_be_bytes());
+        data.extend_from_slice(&self.extensions);
+        Ok(data)
+    }
+}
+
+/// Whether the SCT of the certificates issued by Fulcio has to be verified
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SctPolicy {
+    /// Reject the certificates whose SCT cannot be verified, including when
+    /// the keys of the CT logs are not known
+    #[default]
+    Require,
+    /// Do not verify the SCT. Certificates issued by a compromised Fulcio
+    /// without being logged are accepted
+    Skip,
+}
+
+/// The SCTs embedded inside of `certificate`
+pub fn embedded_scts(certificate: &Certificate) -> Result<Vec<SignedCertificateTimestamp>> {
+    let extension = match certificate
+        .tbs_certificate
+        .extensions
+        .iter()
+        .flatten()
+        .find(|ext| ext.extn_id == SCT_LIST_OID)
+    {
+        Some(extension) => extension,
+        None => return Ok(Vec::new()),
+    };
+
+    let list = OctetStringRef::from_der(extension.extn_value)
+        .map_err(|e| SigstoreError::SctVerificationError(format!("invalid SCT list: {e}")))?;
+    let mut reader = TlsReader(list.as_bytes());
+    let mut scts = TlsReader(reader.read_vec_u16()?);
+    reader.finish()?;
+
+    let mut result = Vec::new();
+    while !scts.0.is_empty() {
+        result.push(SignedCertificateTimestamp::from_tls(scts.read_vec_u16()?)?);
+    }
+    Ok(result)
+}
+
+/// Ensure at least one of the SCTs embedded inside of `certificate` has
+/// been issued by a CT log of `trusted_root`.
+///
+/// `issuer_spki_der` is the DER encoded public key of the certificate
+/// authority that issued `certificate`.
+pub fn verify_embedded_sct(
+    certificate: &Certificate,
+    issuer_spki_der: &[u8],
+    trusted_root: &TrustedRoot,
+) -> Result<()> {
+    let scts = embedded_scts(certificate)?;
+    if scts.is_empty() {
+        return Err(SigstoreError::SctVerificationError(
+            "the certificate doesn't have embedded SCTs".to_string(),
+        ));
+    }
+
+    // the log signed the certificate before the SCTs were added to it
+    let mut tbs_certificate = certificate.tbs_certificate.clone();
+    if let Some(extensions) = tbs_certificate.extensions.as_mut() {
+        extensions.retain(|ext| ext.extn_id != SCT_LIST_OID);
+    }
+    let entry = LogEntry::Precert {
+        issuer_key_hash: Sha256::digest(issuer_spki_der).into(),
+        tbs_certificate: tbs_certificate
+            .to_vec()
+            .map_err(|e| SigstoreError::X509Error(format!("encode certificate: {e}")))?,
+    };
+
+    verify_any(&scts, &entry, trusted_root)
+}
+
+/// Ensure the detached `sct` has been issued for the DER encoded
+/// `certificate` by a CT log of `trusted_root`
+pub fn verify_detached_sct(
+    sct: &SignedCertificateTimestamp,
+    certificate: &[u8],
+    trusted_root: &TrustedRoot,
+) -> Result<()> {
+    verify_any(
+        std::slice::from_ref(sct),
+        &LogEntry::X509(certificate),
+        trusted_root,
+    )
+}
+
+fn verify_any(
+    scts: &[SignedCertificateTimestamp],
+    entry: &LogEntry<'_>,
+    trusted_root: &TrustedRoot,
+) -> Result<()> {
+    let mut last_error = None;
+    for sct in scts {
+        match sct.verify(entry, trusted_root) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                debug!(log_id = %sct.log_id_hex(), error = %e, "Cannot verify SCT");
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error
+        .unwrap_or_else(|| SigstoreError::SctVerificationError("no SCT to verify".to_string())))
+}
+
+fn write_vec_u24(data: &mut Vec<u8>, value: &[u8]) -> Result<()> {
+    if value.len() >= 1 << 24 {
+        return Err(SigstoreError::SctVerificationError(
+            "the logged entry is too long".to_string(),
+        ));
+    }
+    data.extend_from_slice(&(value.len() as u32).to_be_bytes()[1..]);
+    data.extend_from_slice(value);
+    Ok(())
+}
+
+/// Reads the TLS encoded structures of RFC 6962
+struct TlsReader<'a>(&'a [u8]);
+
+impl<'a> TlsReader<'a> {
+    fn read(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(SigstoreError::SctVerificationError(
+                "truncated SCT".to_string(),
+            ));
+        }
+        let (value, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(value)
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read(1)?[0])
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(
+            self.read(8)?.try_into().expect("read returns 8 bytes"),
+        ))
+    }
+
+    /// Read a vector prefixed by its length, stored in two bytes
+    fn read_vec_u16(&mut self) -> Result<&'a [u8]> {
+        let len = u16::from_be_bytes(self.read(2)?.try_into().expect("read returns 2 bytes"));
+        self.read(len as usize)
+    }
+
+    fn finish(&self) -> Result<()> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(SigstoreError::SctVerificationError(
+                "trailing data after SCT".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::crypto::certificate_pool::FULCIO_INTERMEDIATE_V1;
+    use crate::crypto::trusted_root::ValidityPeriod;
+    use crate::crypto::SigningScheme;
+
+    /// The key of the production CT log of Sigstore, used since 2022
+    pub(crate) const CTFE_2022_PUB_KEY: &str = r#"-----BEGIN PUBLIC KEY-----
+MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEiPSlFi0CmFTfEjCUqF9HuCEcYXNK
+AaYalIJmBZ8yyezPjTqhxrKBpMnaocVtLJBI1eM3uXnQzQGAJdJ4gs9Fyw==
+-----END PUBLIC KEY-----"#;
+
+    /// A certificate issued by the production instance of Fulcio, with an
+    /// SCT of the 2022 CT log
+    pub(crate) const FULCIO_LEAF_CERT: &str = r#"-----BEGIN CERTIFICATE-----
+MIICqTCCAi6gAwIBAgIUc4soYChsRq4lWUu990I7GrErO9IwCgYIKoZIzj0EAwMw
+NzEVMBMGA1UEChMMc2lnc3RvcmUuZGV2MR4wHAYDVQQDExVzaWdzdG9yZS1pbnRl
+cm1lZGlhdGUwHhcNMjMwMzEzMTEzOTIwWhcNMjMwMzEzMTE0OTIwWjAAMFkwEwYH
+KoZIzj0CAQYIKoZIzj0DAQcDQgAE7zhgP7vhI8QzXm0nMC6wvj1c/82sRx4ozvIB
+6od9xfiNofmjlDJtdG+IrObrxONhAXffZWDB2N8SmjAcHVz85qOCAU0wggFJMA4G
+A1UdDwEB/wQEAwIHgDATBgNVHSUEDDAKBggrBgEFBQcDAzAdBgNVHQ4EFgQU3U2j
+b80jcAEZIXWnZgIjGEJ39EcwHwYDVR0jBBgwFoAU39Ppz1YkEZb5qNjpKFWixi4Y
+ZD8wJwYDVR0RAQH/BB0wG4EZZGFuaWVsLmJldmVuaXVzQGdtYWlsLmNvbTAsBgor
+BgEEAYO/MAEBBB5odHRwczovL2dpdGh1Yi5jb20vbG9naW4vb2F1dGgwgYoGCisG
+AQQB1nkCBAIEfAR6AHgAdgDdPTBqxscRMmMZHhyZZzcCokpeuN48rf+HinKALynu
+jgAAAYbaxJHJAAAEAwBHMEUCIFsrHqZF6pqZotjvHvvSPxk7jdtWkAPLn55APKmj
+lD72AiEA3807EnFi2HLZcoP+85fmCH5awXDX1KLPUW7kibOwKpAwCgYIKoZIzj0E
+AwMDaQAwZgIxAOf6C68qm6r7Rovurc7j+JQkki8hsoWd68vC+VvSazSFMpCxrvm7
+HlrW7oMAzjlCzwIxANQWgC60eNi7QNeqlMlo/UraZz8xFho2d0Fr5fa0ZfALBE82
+I9TvCXsVua7/ERp+eQ==
+-----END CERTIFICATE-----"#;
+
+    fn intermediate_spki() -> Vec<u8> {
+        let der = pem::parse(FULCIO_INTERMEDIATE_V1).unwrap().contents;
+        Certificate::from_der(&der)
+            .unwrap()
+            .tbs_certificate
+            .subject_public_key_info
+            .to_vec()
+            .unwrap()
+    }
+
+    #[test]
+    fn verify_sct_embedded_by_fulcio() {
+        let der = pem::parse(FULCIO_LEAF_CERT).unwrap().contents;
+        let certificate = Certificate::from_der(&der).unwrap();
+
+        let scts = embedded_scts(&certificate).unwrap();
+        assert_eq!(scts.len(), 1);
+        assert_eq!(
+            scts[0].log_id_hex(),
+            "dd3d306ac6c7113263191e1c99673702a24a5eb8de3cadff878a72802f29ee8e"
+        );
+        assert_eq!(scts[0].timestamp, 1678707560905);
+
+        let mut trusted_root = TrustedRoot::new();
+        assert!(matches!(
+            verify_embedded_sct(&certificate, &intermediate_spki(), &trusted_root),
+            Err(SigstoreError::SctVerificationError(_))
+        ));
+
+        trusted_root
+            .add_ct_log_pub_key(CTFE_2022_PUB_KEY, ValidityPeriod::always())
+            .unwrap();
+        assert!(verify_embedded_sct(&certificate, &intermediate_spki(), &trusted_root).is_ok());
+
+        // the SCT covers the key of the issuer
+        let other_spki = certificate
+            .tbs_certificate
+            .subject_public_key_info
+            .to_vec()
+            .unwrap();
+        assert!(verify_embedded_sct(&certificate, &other_spki, &trusted_root).is_err());
+    }
+
+    #[test]
+    fn verify_detached_sct_signature() {
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let log_key = signer.public_key_to_pem().unwrap();
+        let mut trusted_root = TrustedRoot::new();
+        trusted_root
+            .add_ct_log_pub_key(&log_key, ValidityPeriod::always())
+            .unwrap();
+
+        let certificate = b"not relevant";
+        let mut sct = SignedCertificateTimestamp {
+            version: SCT_VERSION_V1,
+            log_id: Sha256::digest(pem::parse(&log_key).unwrap().contents).into(),
+            timestamp: 1678707560905,
+            extensions: Vec::new(),
+            hash_algorithm: HASH_ALGORITHM_SHA256,
+            signature_algorithm: 3,
+            signature: Vec::new(),
+        };
+        sct.signature = signer
+            .sign(
+                &sct.signed_data(&LogEntry::X509(certificate.as_slice()))
+                    .unwrap(),
+            )
+            .unwrap();
+
+        let mut signature = vec![sct.hash_algorithm, sct.signature_algorithm];
+        signature.extend_from_slice(&u16::try_from(sct.signature.len()).unwrap().to_be_bytes());
+        signature.extend_from_slice(&sct.signature);
+        let detached = SignedCertificateTimestamp::from_json(
+            &serde_json::json!({
+                "sct_version": 0,
+                "id": BASE64_STD_ENGINE.encode(sct.log_id),
+                "timestamp": sct.timestamp,
+                "extensions": "",
+                "signature": BASE64_STD_ENGINE.encode(signature),
+            })
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(detached, sct);
+
+        assert!(verify_detached_sct(&detached, certificate, &trusted_root).is_ok());
+        assert!(verify_detached_sct(&detached, b"another certificate", &trusted_root).is_err());
+    }
+}
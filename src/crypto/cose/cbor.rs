@@ -0,0 +1,309 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The subset of [CBOR](https://www.rfc-editor.org/rfc/rfc8949.html) needed
+//! to handle COSE structures.
+//!
+//! Only definite length items are supported, floating point numbers are
+//! rejected.
+
+use std::convert::TryFrom;
+
+use crate::errors::{Result, SigstoreError};
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+
+const SIMPLE_FALSE: u8 = 20;
+const SIMPLE_TRUE: u8 = 21;
+const SIMPLE_NULL: u8 = 22;
+
+/// Nesting limit, protects against stack exhaustion
+const MAX_DEPTH: usize = 16;
+
+/// A CBOR data item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Value {
+    Unsigned(u64),
+    /// A negative integer, holding `-1 - n`
+    Negative(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    Tag(u64, Box<Value>),
+    Bool(bool),
+    Null,
+}
+
+impl Value {
+    /// Build an integer value
+    pub(crate) fn integer(i: i64) -> Value {
+        if i < 0 {
+            Value::Negative((-1 - i) as u64)
+        } else {
+            Value::Unsigned(i as u64)
+        }
+    }
+
+    /// The value as a signed integer, when it fits
+    pub(crate) fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Unsigned(n) => i64::try_from(*n).ok(),
+            Value::Negative(n) => i64::try_from(*n).ok().map(|n| -1 - n),
+            _ => None,
+        }
+    }
+
+    /// Look up the entry of a map with an integer key
+    pub(crate) fn map_get(&self, key: i64) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| k.as_integer() == Some(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Serialize the value
+    pub(crate) fn to_vec(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Value::Unsigned(n) => write_head(out, MAJOR_UNSIGNED, *n),
+            Value::Negative(n) => write_head(out, MAJOR_NEGATIVE, *n),
+            Value::Bytes(b) => {
+                write_head(out, MAJOR_BYTES, b.len() as u64);
+                out.extend_from_slice(b);
+            }
+            Value::Text(t) => {
+                write_head(out, MAJOR_TEXT, t.len() as u64);
+                out.extend_from_slice(t.as_bytes());
+            }
+            Value::Array(items) => {
+                write_head(out, MAJOR_ARRAY, items.len() as u64);
+                items.iter().for_each(|i| i.encode(out));
+            }
+            Value::Map(entries) => {
+                write_head(out, MAJOR_MAP, entries.len() as u64);
+                for (k, v) in entries {
+                    k.encode(out);
+                    v.encode(out);
+                }
+            }
+            Value::Tag(tag, value) => {
+                write_head(out, MAJOR_TAG, *tag);
+                value.encode(out);
+            }
+            Value::Bool(false) => out.push(MAJOR_SIMPLE << 5 | SIMPLE_FALSE),
+            Value::Bool(true) => out.push(MAJOR_SIMPLE << 5 | SIMPLE_TRUE),
+            Value::Null => out.push(MAJOR_SIMPLE << 5 | SIMPLE_NULL),
+        }
+    }
+
+    /// Parse a single data item, which must span the whole input
+    pub(crate) fn from_slice(data: &[u8]) -> Result<Value> {
+        let mut decoder = Decoder { data, pos: 0 };
+        let value = decoder.decode(0)?;
+        if decoder.pos != data.len() {
+            return Err(cbor_error("trailing data after CBOR item"));
+        }
+        Ok(value)
+    }
+}
+
+/// Write the initial byte of an item, followed by its argument, using the
+/// shortest encoding as required by the deterministic encoding rules
+fn write_head(out: &mut Vec<u8>, major: u8, arg: u64) {
+    let major = major << 5;
+    if arg < 24 {
+        out.push(major | arg as u8);
+    } else if arg <= u8::MAX as u64 {
+        out.push(major | 24);
+        out.push(arg as u8);
+    } else if arg <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(arg as u16).to_be_bytes());
+    } else if arg <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(arg as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&arg.to_be_bytes());
+    }
+}
+
+fn cbor_error(msg: &str) -> SigstoreError {
+    SigstoreError::CoseError(format!("invalid CBOR: {msg}"))
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| cbor_error("unexpected end of data"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn read_arg(&mut self, info: u8) -> Result<u64> {
+        let size = match info {
+            0..=23 => return Ok(info as u64),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return Err(cbor_error("indefinite lengths are not supported")),
+        };
+        Ok(self
+            .take(size)?
+            .iter()
+            .fold(0u64, |acc, b| acc << 8 | *b as u64))
+    }
+
+    fn read_len(&mut self, info: u8) -> Result<usize> {
+        let len =
+            usize::try_from(self.read_arg(info)?).map_err(|_| cbor_error("length out of range"))?;
+        // every item takes at least one byte, reject lengths that cannot
+        // be satisfied before allocating
+        if len > self.data.len() - self.pos {
+            return Err(cbor_error("unexpected end of data"));
+        }
+        Ok(len)
+    }
+
+    fn decode(&mut self, depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            return Err(cbor_error("nesting too deep"));
+        }
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        match major {
+            MAJOR_UNSIGNED => Ok(Value::Unsigned(self.read_arg(info)?)),
+            MAJOR_NEGATIVE => Ok(Value::Negative(self.read_arg(info)?)),
+            MAJOR_BYTES => {
+                let len = self.read_len(info)?;
+                Ok(Value::Bytes(self.take(len)?.to_vec()))
+            }
+            MAJOR_TEXT => {
+                let len = self.read_len(info)?;
+                let text = std::str::from_utf8(self.take(len)?)
+                    .map_err(|_| cbor_error("text is not valid UTF-8"))?;
+                Ok(Value::Text(text.to_string()))
+            }
+            MAJOR_ARRAY => {
+                let len = self.read_len(info)?;
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push(self.decode(depth + 1)?);
+                }
+                Ok(Value::Array(items))
+            }
+            MAJOR_MAP => {
+                let len = self.read_len(info)?;
+                let mut entries = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let key = self.decode(depth + 1)?;
+                    let value = self.decode(depth + 1)?;
+                    entries.push((key, value));
+                }
+                Ok(Value::Map(entries))
+            }
+            MAJOR_TAG => {
+                let tag = self.read_arg(info)?;
+                Ok(Value::Tag(tag, Box::new(self.decode(depth + 1)?)))
+            }
+            _ => match info {
+                SIMPLE_FALSE => Ok(Value::Bool(false)),
+                SIMPLE_TRUE => Ok(Value::Bool(true)),
+                SIMPLE_NULL => Ok(Value::Null),
+                _ => Err(cbor_error("unsupported simple value")),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_rfc8949_examples() {
+        assert_eq!(Value::integer(10).to_vec(), vec![0x0a]);
+        assert_eq!(Value::integer(100).to_vec(), vec![0x18, 0x64]);
+        assert_eq!(Value::integer(1000).to_vec(), vec![0x19, 0x03, 0xe8]);
+        assert_eq!(Value::integer(-7).to_vec(), vec![0x26]);
+        assert_eq!(Value::integer(-1000).to_vec(), vec![0x39, 0x03, 0xe7]);
+        assert_eq!(
+            Value::Text("IETF".to_string()).to_vec(),
+            vec![0x64, 0x49, 0x45, 0x54, 0x46]
+        );
+        assert_eq!(
+            Value::Array(vec![Value::integer(1), Value::Null]).to_vec(),
+            vec![0x82, 0x01, 0xf6]
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let value = Value::Tag(
+            18,
+            Box::new(Value::Array(vec![
+                Value::Bytes(vec![0xa1, 0x01, 0x26]),
+                Value::Map(vec![(Value::integer(4), Value::Bytes(b"kid".to_vec()))]),
+                Value::Bytes(vec![0; 300]),
+                Value::Bool(true),
+            ])),
+        );
+        let decoded = Value::from_slice(&value.to_vec()).unwrap();
+        assert_eq!(decoded, value);
+        assert_eq!(decoded.to_vec(), value.to_vec());
+    }
+
+    #[test]
+    fn reject_malformed_input() {
+        // truncated byte string
+        assert!(Value::from_slice(&[0x45, 0x01]).is_err());
+        // trailing data
+        assert!(Value::from_slice(&[0x01, 0x02]).is_err());
+        // indefinite length array
+        assert!(Value::from_slice(&[0x9f, 0x01, 0xff]).is_err());
+        // huge declared length
+        assert!(
+            Value::from_slice(&[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).is_err()
+        );
+        // deep nesting
+        assert!(Value::from_slice(&[0x81; 64]).is_err());
+    }
+}
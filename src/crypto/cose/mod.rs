@@ -0,0 +1,488 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Creation and verification of
+//! [COSE_Sign1](https://www.rfc-editor.org/rfc/rfc9052.html#section-4.2)
+//! envelopes.
+//!
+//! COSE is the CBOR counterpart of the JSON based signature formats, and is
+//! preferred by constrained devices. Envelopes can be recorded inside of
+//! Rekor using the `cose` entry type, see
+//! [`rekor::entries::cose`](crate::rekor::entries::cose).
+//!
+//! ```rust,no_run
+//! use sigstore::crypto::cose::CoseSign1;
+//! use sigstore::crypto::SigningScheme;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! let signer = SigningScheme::ECDSA_P256_SHA256_ASN1.create_signer()?;
+//! let envelope = CoseSign1::sign(&signer, b"firmware image", b"")?;
+//! let encoded = envelope.to_vec();
+//!
+//! let envelope = CoseSign1::from_slice(&encoded)?;
+//! envelope.verify(&signer.to_verification_key()?, b"")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use crate::errors::{Result, SigstoreError};
+
+use super::{CosignVerificationKey, SigStoreSigner, Signature, SignatureFormat, SigningScheme};
+
+mod cbor;
+
+use cbor::Value;
+
+/// The CBOR tag of COSE_Sign1 structures
+const COSE_SIGN1_TAG: u64 = 18;
+
+/// The label of the algorithm inside of a header map
+const HEADER_ALGORITHM: i64 = 1;
+
+/// The context string of the signature structure of COSE_Sign1
+const SIGNATURE1_CONTEXT: &str = "Signature1";
+
+/// The [COSE algorithms](https://www.iana.org/assignments/cose/cose.xhtml#algorithms)
+/// matching the signing schemes supported by the crate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoseAlgorithm {
+    /// ECDSA using P-256 and SHA-256
+    ES256,
+    /// ECDSA using P-384 and SHA-384
+    ES384,
+    /// ECDSA using P-521 and SHA-512
+    ES512,
+    /// EdDSA, only Ed25519 is supported
+    EdDSA,
+    /// RSASSA-PSS using SHA-256
+    PS256,
+    /// RSASSA-PSS using SHA-384
+    PS384,
+    /// RSASSA-PSS using SHA-512
+    PS512,
+    /// RSASSA-PKCS1-v1_5 using SHA-256
+    RS256,
+    /// RSASSA-PKCS1-v1_5 using SHA-384
+    RS384,
+    /// RSASSA-PKCS1-v1_5 using SHA-512
+    RS512,
+}
+
+impl CoseAlgorithm {
+    /// The identifier of the algorithm, as registered by IANA
+    pub fn id(&self) -> i64 {
+        match self {
+            CoseAlgorithm::ES256 => -7,
+            CoseAlgorithm::ES384 => -35,
+            CoseAlgorithm::ES512 => -36,
+            CoseAlgorithm::EdDSA => -8,
+            CoseAlgorithm::PS256 => -37,
+            CoseAlgorithm::PS384 => -38,
+            CoseAlgorithm::PS512 => -39,
+            CoseAlgorithm::RS256 => -257,
+            CoseAlgorithm::RS384 => -258,
+            CoseAlgorithm::RS512 => -259,
+        }
+    }
+
+    /// Look up an algorithm by its IANA identifier
+    pub fn from_id(id: i64) -> Result<Self> {
+        Ok(match id {
+            -7 => CoseAlgorithm::ES256,
+            -35 => CoseAlgorithm::ES384,
+            -36 => CoseAlgorithm::ES512,
+            -8 => CoseAlgorithm::EdDSA,
+            -37 => CoseAlgorithm::PS256,
+            -38 => CoseAlgorithm::PS384,
+            -39 => CoseAlgorithm::PS512,
+            -257 => CoseAlgorithm::RS256,
+            -258 => CoseAlgorithm::RS384,
+            -259 => CoseAlgorithm::RS512,
+            _ => {
+                return Err(SigstoreError::CoseError(format!(
+                    "unsupported COSE algorithm {id}"
+                )))
+            }
+        })
+    }
+
+    /// Returns `true` when `key` can verify signatures produced with this
+    /// algorithm
+    fn is_compatible_with(&self, key: &CosignVerificationKey) -> bool {
+        matches!(
+            (self, key),
+            (
+                CoseAlgorithm::ES256,
+                CosignVerificationKey::ECDSA_P256_SHA256_ASN1(_)
+            ) | (
+                CoseAlgorithm::ES384,
+                CosignVerificationKey::ECDSA_P384_SHA384_ASN1(_)
+            ) | (
+                CoseAlgorithm::ES512,
+                CosignVerificationKey::ECDSA_P521_SHA512(_)
+            ) | (CoseAlgorithm::EdDSA, CosignVerificationKey::ED25519(_))
+                | (
+                    CoseAlgorithm::PS256,
+                    CosignVerificationKey::RSA_PSS_SHA256(_)
+                )
+                | (
+                    CoseAlgorithm::PS384,
+                    CosignVerificationKey::RSA_PSS_SHA384(_)
+                )
+                | (
+                    CoseAlgorithm::PS512,
+                    CosignVerificationKey::RSA_PSS_SHA512(_)
+                )
+                | (
+                    CoseAlgorithm::RS256,
+                    CosignVerificationKey::RSA_PKCS1_SHA256(_)
+                )
+                | (
+                    CoseAlgorithm::RS384,
+                    CosignVerificationKey::RSA_PKCS1_SHA384(_)
+                )
+                | (
+                    CoseAlgorithm::RS512,
+                    CosignVerificationKey::RSA_PKCS1_SHA512(_)
+                )
+        )
+    }
+}
+
+impl From<&SigningScheme> for CoseAlgorithm {
+    fn from(scheme: &SigningScheme) -> Self {
+        match scheme {
+            SigningScheme::ECDSA_P256_SHA256_ASN1 => CoseAlgorithm::ES256,
+            SigningScheme::ECDSA_P384_SHA384_ASN1 => CoseAlgorithm::ES384,
+            SigningScheme::ECDSA_P521_SHA512 => CoseAlgorithm::ES512,
+            SigningScheme::ED25519 => CoseAlgorithm::EdDSA,
+            SigningScheme::RSA_PSS_SHA256(_) => CoseAlgorithm::PS256,
+            SigningScheme::RSA_PSS_SHA384(_) => CoseAlgorithm::PS384,
+            SigningScheme::RSA_PSS_SHA512(_) => CoseAlgorithm::PS512,
+            SigningScheme::RSA_PKCS1_SHA256(_) => CoseAlgorithm::RS256,
+            SigningScheme::RSA_PKCS1_SHA384(_) => CoseAlgorithm::RS384,
+            SigningScheme::RSA_PKCS1_SHA512(_) => CoseAlgorithm::RS512,
+        }
+    }
+}
+
+impl From<&SigStoreSigner> for CoseAlgorithm {
+    fn from(signer: &SigStoreSigner) -> Self {
+        match signer {
+            SigStoreSigner::ECDSA_P256_SHA256_ASN1(_) => CoseAlgorithm::ES256,
+            SigStoreSigner::ECDSA_P384_SHA384_ASN1(_) => CoseAlgorithm::ES384,
+            SigStoreSigner::ED25519(_) => CoseAlgorithm::EdDSA,
+            SigStoreSigner::RSA_PSS_SHA256(_) => CoseAlgorithm::PS256,
+            SigStoreSigner::RSA_PSS_SHA384(_) => CoseAlgorithm::PS384,
+            SigStoreSigner::RSA_PSS_SHA512(_) => CoseAlgorithm::PS512,
+            SigStoreSigner::RSA_PKCS1_SHA256(_) => CoseAlgorithm::RS256,
+            SigStoreSigner::RSA_PKCS1_SHA384(_) => CoseAlgorithm::RS384,
+            SigStoreSigner::RSA_PKCS1_SHA512(_) => CoseAlgorithm::RS512,
+        }
+    }
+}
+
+impl fmt::Display for CoseAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// A COSE_Sign1 envelope: a payload signed by a single signer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoseSign1 {
+    /// The serialized protected header, covered by the signature
+    protected: Vec<u8>,
+    /// The unprotected header, kept only to serialize the envelope again
+    unprotected: Value,
+    /// The signed payload, `None` when the payload is detached
+    pub payload: Option<Vec<u8>>,
+    /// The raw signature. ECDSA signatures use the fixed size `r || s`
+    /// encoding mandated by COSE, not ASN.1
+    pub signature: Vec<u8>,
+}
+
+impl CoseSign1 {
+    /// Sign `payload` with `signer`. The `external_aad` is covered by the
+    /// signature without being part of the envelope, it must be provided
+    /// again at verification time. Use an empty slice when not needed.
+    pub fn sign(signer: &SigStoreSigner, payload: &[u8], external_aad: &[u8]) -> Result<Self> {
+        let algorithm = CoseAlgorithm::from(signer);
+        let protected = Value::Map(vec![(
+            Value::integer(HEADER_ALGORITHM),
+            Value::integer(algorithm.id()),
+        )])
+        .to_vec();
+
+        let to_be_signed = sig_structure(&protected, external_aad, payload);
+        let signature = signer.sign(&to_be_signed)?;
+        let signature = match algorithm {
+            CoseAlgorithm::ES256 => ecdsa::Signature::<p256::NistP256>::from_der(&signature)?
+                .to_bytes()
+                .to_vec(),
+            CoseAlgorithm::ES384 => ecdsa::Signature::<p384::NistP384>::from_der(&signature)?
+                .to_bytes()
+                .to_vec(),
+            _ => signature,
+        };
+
+        Ok(CoseSign1 {
+            protected,
+            unprotected: Value::Map(Vec::new()),
+            payload: Some(payload.to_vec()),
+            signature,
+        })
+    }
+
+    /// Remove the payload from the envelope, which must then be verified
+    /// with [`CoseSign1::verify_detached`]
+    pub fn detach(mut self) -> Self {
+        self.payload = None;
+        self
+    }
+
+    /// Parse a COSE_Sign1 envelope, either tagged or untagged
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        let value = match Value::from_slice(data)? {
+            Value::Tag(COSE_SIGN1_TAG, value) => *value,
+            Value::Tag(tag, _) => {
+                return Err(SigstoreError::CoseError(format!(
+                    "unexpected CBOR tag {tag}, not a COSE_Sign1"
+                )))
+            }
+            value => value,
+        };
+
+        let mut items = match value {
+            Value::Array(items) if items.len() == 4 => items.into_iter(),
+            _ => {
+                return Err(SigstoreError::CoseError(
+                    "COSE_Sign1 must be an array of 4 items".to_string(),
+                ))
+            }
+        };
+        // the length has been checked above
+        let (protected, unprotected, payload, signature) = (
+            items.next().unwrap_or(Value::Null),
+            items.next().unwrap_or(Value::Null),
+            items.next().unwrap_or(Value::Null),
+            items.next().unwrap_or(Value::Null),
+        );
+
+        let envelope = CoseSign1 {
+            protected: match protected {
+                Value::Bytes(b) => b,
+                _ => return Err(malformed("the protected header is not a byte string")),
+            },
+            unprotected: match unprotected {
+                Value::Map(m) => Value::Map(m),
+                _ => return Err(malformed("the unprotected header is not a map")),
+            },
+            payload: match payload {
+                Value::Bytes(b) => Some(b),
+                Value::Null => None,
+                _ => return Err(malformed("the payload is neither a byte string nor nil")),
+            },
+            signature: match signature {
+                Value::Bytes(b) => b,
+                _ => return Err(malformed("the signature is not a byte string")),
+            },
+        };
+        // ensure the protected header can be used
+        envelope.algorithm()?;
+        Ok(envelope)
+    }
+
+    /// Serialize the envelope, tagged as COSE_Sign1
+    pub fn to_vec(&self) -> Vec<u8> {
+        Value::Tag(
+            COSE_SIGN1_TAG,
+            Box::new(Value::Array(vec![
+                Value::Bytes(self.protected.clone()),
+                self.unprotected.clone(),
+                self.payload
+                    .as_ref()
+                    .map(|p| Value::Bytes(p.clone()))
+                    .unwrap_or(Value::Null),
+                Value::Bytes(self.signature.clone()),
+            ])),
+        )
+        .to_vec()
+    }
+
+    /// The algorithm declared by the protected header
+    pub fn algorithm(&self) -> Result<CoseAlgorithm> {
+        if self.protected.is_empty() {
+            return Err(malformed("the protected header is empty"));
+        }
+        let header = Value::from_slice(&self.protected)?;
+        let id = header
+            .map_get(HEADER_ALGORITHM)
+            .and_then(Value::as_integer)
+            .ok_or_else(|| malformed("the protected header doesn't declare the algorithm"))?;
+        CoseAlgorithm::from_id(id)
+    }
+
+    /// Verify the envelope, which must embed its payload
+    pub fn verify(&self, key: &CosignVerificationKey, external_aad: &[u8]) -> Result<()> {
+        let payload = self
+            .payload
+            .as_ref()
+            .ok_or_else(|| SigstoreError::CoseError("the payload is detached".to_string()))?;
+        self.verify_signature(key, payload, external_aad)
+    }
+
+    /// Verify the envelope against a detached `payload`
+    pub fn verify_detached(
+        &self,
+        key: &CosignVerificationKey,
+        payload: &[u8],
+        external_aad: &[u8],
+    ) -> Result<()> {
+        if self.payload.is_some() {
+            return Err(SigstoreError::CoseError(
+                "the payload is not detached".to_string(),
+            ));
+        }
+        self.verify_signature(key, payload, external_aad)
+    }
+
+    fn verify_signature(
+        &self,
+        key: &CosignVerificationKey,
+        payload: &[u8],
+        external_aad: &[u8],
+    ) -> Result<()> {
+        let algorithm = self.algorithm()?;
+        if !algorithm.is_compatible_with(key) {
+            return Err(SigstoreError::CoseError(format!(
+                "the key cannot verify {algorithm} signatures"
+            )));
+        }
+
+        let to_be_signed = sig_structure(&self.protected, external_aad, payload);
+        key.verify_signature_with_format(
+            Signature::Raw(&self.signature),
+            &to_be_signed,
+            SignatureFormat::Raw,
+        )
+    }
+}
+
+/// Build the `Sig_structure` covered by the signature of a COSE_Sign1
+fn sig_structure(protected: &[u8], external_aad: &[u8], payload: &[u8]) -> Vec<u8> {
+    Value::Array(vec![
+        Value::Text(SIGNATURE1_CONTEXT.to_string()),
+        Value::Bytes(protected.to_vec()),
+        Value::Bytes(external_aad.to_vec()),
+        Value::Bytes(payload.to_vec()),
+    ])
+    .to_vec()
+}
+
+fn malformed(msg: &str) -> SigstoreError {
+    SigstoreError::CoseError(format!("malformed COSE_Sign1: {msg}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_and_verify() {
+        for scheme in [
+            SigningScheme::ECDSA_P256_SHA256_ASN1,
+            SigningScheme::ECDSA_P384_SHA384_ASN1,
+            SigningScheme::ED25519,
+        ] {
+            let signer = scheme.create_signer().unwrap();
+            let key = signer.to_verification_key().unwrap();
+
+            let envelope = CoseSign1::sign(&signer, b"firmware", b"device-42").unwrap();
+            let envelope = CoseSign1::from_slice(&envelope.to_vec()).unwrap();
+            assert_eq!(envelope.algorithm().unwrap(), CoseAlgorithm::from(&scheme));
+            assert!(envelope.verify(&key, b"device-42").is_ok());
+            assert!(envelope.verify(&key, b"device-43").is_err());
+
+            let mut tampered = envelope.clone();
+            tampered.payload = Some(b"malware".to_vec());
+            assert!(tampered.verify(&key, b"device-42").is_err());
+        }
+    }
+
+    #[test]
+    fn ecdsa_signatures_use_raw_encoding() {
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let envelope = CoseSign1::sign(&signer, b"payload", b"").unwrap();
+        assert_eq!(envelope.signature.len(), 64);
+    }
+
+    #[test]
+    fn verify_detached_payload() {
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let key = signer.to_verification_key().unwrap();
+
+        let envelope = CoseSign1::sign(&signer, b"payload", b"").unwrap().detach();
+        let envelope = CoseSign1::from_slice(&envelope.to_vec()).unwrap();
+        assert!(envelope.payload.is_none());
+        assert!(envelope.verify(&key, b"").is_err());
+        assert!(envelope.verify_detached(&key, b"payload", b"").is_ok());
+        assert!(envelope.verify_detached(&key, b"other", b"").is_err());
+    }
+
+    #[test]
+    fn reject_algorithm_confusion() {
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let other_key = SigningScheme::ED25519
+            .create_signer()
+            .unwrap()
+            .to_verification_key()
+            .unwrap();
+
+        let envelope = CoseSign1::sign(&signer, b"payload", b"").unwrap();
+        let error = envelope
+            .verify(&other_key, b"")
+            .expect_err("wrong key type accepted");
+        assert!(matches!(error, SigstoreError::CoseError(_)));
+    }
+
+    #[test]
+    fn reject_malformed_envelopes() {
+        // not an array
+        assert!(CoseSign1::from_slice(&Value::integer(1).to_vec()).is_err());
+        // COSE_Sign (tag 98)
+        let signer = SigningScheme::ED25519.create_signer().unwrap();
+        let mut data = CoseSign1::sign(&signer, b"payload", b"").unwrap().to_vec();
+        data[0] = 0xd8;
+        data.insert(1, 98);
+        assert!(CoseSign1::from_slice(&data).is_err());
+        // unknown algorithm
+        let envelope = Value::Array(vec![
+            Value::Bytes(Value::Map(vec![(Value::integer(1), Value::integer(-1))]).to_vec()),
+            Value::Map(Vec::new()),
+            Value::Null,
+            Value::Bytes(Vec::new()),
+        ]);
+        assert!(CoseSign1::from_slice(&envelope.to_vec()).is_err());
+    }
+}
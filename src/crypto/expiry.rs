@@ -0,0 +1,317 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reports about the expiration of trust material and signer credentials.
+//!
+//! Verification starts failing once a Fulcio intermediate, a timestamp
+//! authority certificate or a signing certificate expires. The
+//! [`ExpiryInspector`] collects all these credentials and produces an
+//! [`ExpiryReport`] listing the ones that are about to expire, giving
+//! operators the time to rotate them.
+//!
+//! ```rust,no_run
+//! use chrono::Duration;
+//! use sigstore::crypto::expiry::ExpiryInspector;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! # let fulcio_certs: Vec<sigstore::registry::Certificate> = vec![];
+//! let mut inspector = ExpiryInspector::new();
+//! inspector.add_fulcio_certs(&fulcio_certs)?;
+//!
+//! for notice in inspector.report(Duration::days(30)).notices() {
+//!     println!("{notice}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use pkcs8::der::Decode;
+use std::fmt;
+use x509_cert::Certificate;
+
+use super::trusted_root::TrustedRoot;
+use crate::errors::{Result, SigstoreError};
+use crate::registry::CertificateEncoding;
+
+/// The kind of credential that is expiring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CredentialKind {
+    /// A Fulcio root or intermediate certificate
+    FulcioCertificate,
+    /// A Rekor public key, retired at the end of its validity window
+    RekorKey,
+    /// The certificate of a timestamp authority
+    TimestampAuthorityCertificate,
+    /// A certificate used by a signer, for example one bound to a BYO key
+    SignerCertificate,
+}
+
+impl fmt::Display for CredentialKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            CredentialKind::FulcioCertificate => "Fulcio certificate",
+            CredentialKind::RekorKey => "Rekor key",
+            CredentialKind::TimestampAuthorityCertificate => "timestamp authority certificate",
+            CredentialKind::SignerCertificate => "signer certificate",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+/// A credential that is about to expire, or that already expired
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpiryNotice {
+    /// The kind of credential
+    pub kind: CredentialKind,
+    /// A human readable description of the credential, like the subject of
+    /// a certificate
+    pub subject: String,
+    /// When the credential expires
+    pub not_after: DateTime<Utc>,
+    /// How long before the credential expires. Negative when the credential
+    /// already expired.
+    pub expires_in: Duration,
+}
+
+impl ExpiryNotice {
+    /// Returns `true` when the credential already expired
+    pub fn is_expired(&self) -> bool {
+        self.expires_in < Duration::zero()
+    }
+}
+
+impl fmt::Display for ExpiryNotice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_expired() {
+            write!(
+                f,
+                "{} {} expired on {}",
+                self.kind,
+                self.subject,
+                self.not_after.to_rfc3339()
+            )
+        } else {
+            write!(
+                f,
+                "{} {} expires on {} (in {} days)",
+                self.kind,
+                self.subject,
+                self.not_after.to_rfc3339(),
+                self.expires_in.num_days()
+            )
+        }
+    }
+}
+
+/// The credentials that are about to expire, sorted by expiration date
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExpiryReport {
+    notices: Vec<ExpiryNotice>,
+}
+
+impl ExpiryReport {
+    /// All the notices, the credentials expiring first come first
+    pub fn notices(&self) -> &[ExpiryNotice] {
+        &self.notices
+    }
+
+    /// The credentials that already expired
+    pub fn expired(&self) -> impl Iterator<Item = &ExpiryNotice> {
+        self.notices.iter().filter(|n| n.is_expired())
+    }
+
+    /// Returns `true` when no credential is about to expire
+    pub fn is_empty(&self) -> bool {
+        self.notices.is_empty()
+    }
+}
+
+/// Collects trust material and signer credentials, then reports the ones
+/// that are about to expire
+#[derive(Debug, Clone, Default)]
+pub struct ExpiryInspector {
+    credentials: Vec<(CredentialKind, String, DateTime<Utc>)>,
+}
+
+impl ExpiryInspector {
+    /// Create an empty `ExpiryInspector`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the certificates used by Fulcio
+    pub fn add_fulcio_certs(&mut self, certs: &[crate::registry::Certificate]) -> Result<()> {
+        self.add_certificates(CredentialKind::FulcioCertificate, certs)
+    }
+
+    /// Add the certificates used by a timestamp authority
+    pub fn add_timestamp_authority_certs(
+        &mut self,
+        certs: &[crate::registry::Certificate],
+    ) -> Result<()> {
+        self.add_certificates(CredentialKind::TimestampAuthorityCertificate, certs)
+    }
+
+    /// Add a certificate used by a signer
+    pub fn add_signer_cert(&mut self, cert: &crate::registry::Certificate) -> Result<()> {
+        self.add_certificates(
+            CredentialKind::SignerCertificate,
+            std::slice::from_ref(cert),
+        )
+    }
+
+    /// Add all the Fulcio certificates and the Rekor keys of a
+    /// [`TrustedRoot`]. Rekor keys are reported when their validity window
+    /// is about to close.
+    pub fn add_trusted_root(&mut self, trusted_root: &TrustedRoot) -> Result<()> {
+        self.add_fulcio_certs(&trusted_root.all_fulcio_certs())?;
+        for (index, validity) in trusted_root.rekor_key_validity_periods().enumerate() {
+            if let Some(end) = validity.end {
+                let not_after = Utc.timestamp_opt(end, 0).single().ok_or_else(|| {
+                    SigstoreError::TrustedRootError(format!("invalid timestamp {end}"))
+                })?;
+                self.credentials
+                    .push((CredentialKind::RekorKey, format!("#{index}"), not_after));
+            }
+        }
+        Ok(())
+    }
+
+    /// Report the credentials that expire within `window` from now,
+    /// including the ones that already expired
+    pub fn report(&self, window: Duration) -> ExpiryReport {
+        self.report_at(window, Utc::now())
+    }
+
+    /// Report the credentials that expire within `window` from `now`,
+    /// including the ones that already expired
+    pub fn report_at(&self, window: Duration, now: DateTime<Utc>) -> ExpiryReport {
+        let mut notices: Vec<ExpiryNotice> = self
+            .credentials
+            .iter()
+            .filter(|(_, _, not_after)| *not_after - now <= window)
+            .map(|(kind, subject, not_after)| ExpiryNotice {
+                kind: *kind,
+                subject: subject.clone(),
+                not_after: *not_after,
+                expires_in: *not_after - now,
+            })
+            .collect();
+        notices.sort_by_key(|n| n.not_after);
+        ExpiryReport { notices }
+    }
+
+    fn add_certificates(
+        &mut self,
+        kind: CredentialKind,
+        certs: &[crate::registry::Certificate],
+    ) -> Result<()> {
+        for c in certs {
+            let der = match c.encoding {
+                CertificateEncoding::Pem => pem::parse(&c.data)?.contents,
+                CertificateEncoding::Der => c.data.clone(),
+            };
+            let cert = Certificate::from_der(&der)
+                .map_err(|e| SigstoreError::X509Error(format!("parse from der: {e}")))?;
+            let not_after: DateTime<Utc> = cert
+                .tbs_certificate
+                .validity
+                .not_after
+                .to_system_time()
+                .into();
+            self.credentials
+                .push((kind, cert.tbs_certificate.subject.to_string(), not_after));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::tests::{generate_certificate, CertGenerationOptions};
+    use crate::crypto::trusted_root::ValidityPeriod;
+    use crate::registry::Certificate as RegistryCertificate;
+
+    fn certificate_expiring_at(not_after: DateTime<Utc>) -> RegistryCertificate {
+        let cert = generate_certificate(
+            None,
+            CertGenerationOptions {
+                not_before: not_after - Duration::days(365),
+                not_after,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .cert;
+        RegistryCertificate {
+            encoding: CertificateEncoding::Pem,
+            data: cert.to_pem().unwrap(),
+        }
+    }
+
+    #[test]
+    fn report_upcoming_expirations() {
+        let now = Utc::now();
+        let mut inspector = ExpiryInspector::new();
+        inspector
+            .add_fulcio_certs(&[
+                certificate_expiring_at(now + Duration::days(365)),
+                certificate_expiring_at(now + Duration::days(10)),
+            ])
+            .unwrap();
+        inspector
+            .add_signer_cert(&certificate_expiring_at(now - Duration::days(1)))
+            .unwrap();
+
+        let report = inspector.report_at(Duration::days(30), now);
+        assert_eq!(report.notices().len(), 2);
+        assert_eq!(report.notices()[0].kind, CredentialKind::SignerCertificate);
+        assert!(report.notices()[0].is_expired());
+        assert_eq!(report.notices()[1].kind, CredentialKind::FulcioCertificate);
+        assert!(!report.notices()[1].is_expired());
+        assert_eq!(report.expired().count(), 1);
+
+        assert!(inspector
+            .report_at(Duration::days(30), now - Duration::days(100))
+            .is_empty());
+    }
+
+    #[test]
+    fn report_rekor_key_rotation() {
+        let now = Utc::now();
+        let mut trusted_root = TrustedRoot::new();
+        trusted_root
+            .add_rekor_pub_key(
+                crate::crypto::tests::PUBLIC_KEY,
+                ValidityPeriod::new(now - Duration::days(100), Some(now + Duration::days(5))),
+            )
+            .unwrap();
+        trusted_root
+            .add_rekor_pub_key(
+                crate::crypto::tests::PUBLIC_KEY,
+                ValidityPeriod::new(now, None),
+            )
+            .unwrap();
+
+        let mut inspector = ExpiryInspector::new();
+        inspector.add_trusted_root(&trusted_root).unwrap();
+
+        let report = inspector.report_at(Duration::days(7), now);
+        assert_eq!(report.notices().len(), 1);
+        assert_eq!(report.notices()[0].kind, CredentialKind::RekorKey);
+    }
+}
@@ -0,0 +1,166 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Incremental computation of the digest of a message.
+//!
+//! The messages are read from a [`Read`] or an [`AsyncRead`] chunk by chunk,
+//! hence artifacts of several GB can be hashed without being loaded in
+//! memory. The digests can then be verified with
+//! [`CosignVerificationKey::verify_prehash`](crate::crypto::CosignVerificationKey::verify_prehash).
+
+use const_oid::db::rfc5912::{
+    ECDSA_WITH_SHA_256, ECDSA_WITH_SHA_384, ECDSA_WITH_SHA_512, SHA_256_WITH_RSA_ENCRYPTION,
+    SHA_384_WITH_RSA_ENCRYPTION, SHA_512_WITH_RSA_ENCRYPTION,
+};
+use const_oid::ObjectIdentifier;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::io::Read;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::errors::Result;
+
+/// Size of the chunks read from the messages
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Digest algorithms used to hash the messages before they are signed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// The digest algorithm of the X.509 signature algorithm `oid`, like the
+    /// one of a certificate or of a CRL. `None` when the algorithm is not
+    /// an ECDSA or RSA PKCS1 one, or uses another digest.
+    pub fn from_signature_algorithm(oid: &ObjectIdentifier) -> Option<Self> {
+        match *oid {
+            ECDSA_WITH_SHA_256 | SHA_256_WITH_RSA_ENCRYPTION => Some(HashAlgorithm::Sha256),
+            ECDSA_WITH_SHA_384 | SHA_384_WITH_RSA_ENCRYPTION => Some(HashAlgorithm::Sha384),
+            ECDSA_WITH_SHA_512 | SHA_512_WITH_RSA_ENCRYPTION => Some(HashAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    /// Size of the digests, in bytes
+    pub fn output_size(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 => Sha256::output_size(),
+            HashAlgorithm::Sha384 => Sha384::output_size(),
+            HashAlgorithm::Sha512 => Sha512::output_size(),
+        }
+    }
+
+    /// Compute the digest of `message`
+    pub fn digest(&self, message: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha256 => Sha256::digest(message).to_vec(),
+            HashAlgorithm::Sha384 => Sha384::digest(message).to_vec(),
+            HashAlgorithm::Sha512 => Sha512::digest(message).to_vec(),
+        }
+    }
+
+    /// Compute the digest of the message read from `reader`, without loading
+    /// it in memory
+    pub fn digest_reader<R: Read>(&self, reader: R) -> Result<Vec<u8>> {
+        match self {
+            HashAlgorithm::Sha256 => digest_reader::<Sha256, R>(reader),
+            HashAlgorithm::Sha384 => digest_reader::<Sha384, R>(reader),
+            HashAlgorithm::Sha512 => digest_reader::<Sha512, R>(reader),
+        }
+    }
+
+    /// Compute the digest of the message read from `reader`, without loading
+    /// it in memory
+    pub async fn digest_async_reader<R: AsyncRead + Unpin>(&self, reader: R) -> Result<Vec<u8>> {
+        match self {
+            HashAlgorithm::Sha256 => digest_async_reader::<Sha256, R>(reader).await,
+            HashAlgorithm::Sha384 => digest_async_reader::<Sha384, R>(reader).await,
+            HashAlgorithm::Sha512 => digest_async_reader::<Sha512, R>(reader).await,
+        }
+    }
+}
+
+fn digest_reader<D: Digest, R: Read>(mut reader: R) -> Result<Vec<u8>> {
+    let mut hasher = D::new();
+    let mut chunk = vec![0; CHUNK_SIZE];
+    loop {
+        match reader.read(&mut chunk) {
+            Ok(0) => return Ok(hasher.finalize().to_vec()),
+            Ok(read) => hasher.update(&chunk[..read]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+async fn digest_async_reader<D: Digest, R: AsyncRead + Unpin>(mut reader: R) -> Result<Vec<u8>> {
+    let mut hasher = D::new();
+    let mut chunk = vec![0; CHUNK_SIZE];
+    loop {
+        match reader.read(&mut chunk).await? {
+            0 => return Ok(hasher.finalize().to_vec()),
+            read => hasher.update(&chunk[..read]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn streaming_digest_matches_digest() {
+        // Larger than a chunk, and not a multiple of its size
+        let message: Vec<u8> = (0..3 * CHUNK_SIZE + 17).map(|i| i as u8).collect();
+
+        for algorithm in [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha384,
+            HashAlgorithm::Sha512,
+        ] {
+            let expected = algorithm.digest(&message);
+            assert_eq!(expected.len(), algorithm.output_size());
+            assert_eq!(
+                algorithm.digest_reader(message.as_slice()).unwrap(),
+                expected
+            );
+            assert_eq!(
+                algorithm
+                    .digest_async_reader(message.as_slice())
+                    .await
+                    .unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn hash_algorithm_of_signature_algorithms() {
+        assert_eq!(
+            HashAlgorithm::from_signature_algorithm(&ECDSA_WITH_SHA_384),
+            Some(HashAlgorithm::Sha384)
+        );
+        assert_eq!(
+            HashAlgorithm::from_signature_algorithm(&SHA_512_WITH_RSA_ENCRYPTION),
+            Some(HashAlgorithm::Sha512)
+        );
+        assert_eq!(
+            HashAlgorithm::from_signature_algorithm(&const_oid::db::rfc5912::ID_SHA_256),
+            None
+        );
+    }
+}
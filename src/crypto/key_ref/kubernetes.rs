@@ -0,0 +1,159 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Provider of the `k8s://[NAMESPACE]/[SECRET]` keys, stored inside of
+//! Kubernetes secrets like the ones created by
+//! `cosign generate-key-pair k8s://[NAMESPACE]/[SECRET]`.
+//!
+//! The secret holds the encrypted private key in its `cosign.key` field, the
+//! public key in its `cosign.pub` field and the password of the private key
+//! in its `cosign.password` field.
+//!
+//! The secrets are read through the API server the process is configured
+//! for: the in-cluster service account when running inside of a pod, the
+//! current context of the kubeconfig otherwise. Hence admission controllers
+//! and operators can verify images with the keys of the cluster, as long as
+//! their service account is allowed to `get` the secrets.
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use k8s_openapi::api::core::v1::Secret;
+use k8s_openapi::ByteString;
+use kube::{Api, Client};
+
+use super::{signer_from_pem, verification_key_from_pem, KeyProvider, KeyRef};
+use crate::crypto::signing_key::kms::KmsSigner;
+use crate::crypto::CosignVerificationKey;
+use crate::errors::{Result, SigstoreError};
+
+/// The field of the secret holding the encrypted private key
+pub const PRIVATE_KEY_FIELD: &str = "cosign.key";
+/// The field of the secret holding the public key
+pub const PUBLIC_KEY_FIELD: &str = "cosign.pub";
+/// The field of the secret holding the password of the private key
+pub const PASSWORD_FIELD: &str = "cosign.password";
+
+/// Provider of the keys stored inside of Kubernetes secrets
+#[derive(Clone, Default)]
+pub struct KubernetesKeyProvider {
+    client: Option<Client>,
+}
+
+impl KubernetesKeyProvider {
+    /// Create a provider connecting to the API server the process is
+    /// configured for, when a key is first resolved
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a provider using the given client, like the one already used
+    /// by a controller
+    pub fn with_client(client: Client) -> Self {
+        KubernetesKeyProvider {
+            client: Some(client),
+        }
+    }
+
+    async fn secret_data(&self, key_ref: &KeyRef) -> Result<BTreeMap<String, ByteString>> {
+        let (namespace, name) = match key_ref {
+            KeyRef::Kubernetes { namespace, name } => (namespace, name),
+            other => {
+                return Err(SigstoreError::KeyRefError(format!(
+                    "{} keys are not stored inside of Kubernetes secrets",
+                    other.scheme()
+                )))
+            }
+        };
+        let client = match &self.client {
+            Some(client) => client.clone(),
+            None => Client::try_default().await.map_err(|e| {
+                SigstoreError::KeyRefError(format!("cannot connect to Kubernetes: {e}"))
+            })?,
+        };
+
+        let secret = Api::<Secret>::namespaced(client, namespace)
+            .get(name)
+            .await
+            .map_err(|e| {
+                SigstoreError::KeyRefError(format!("cannot get the secret {namespace}/{name}: {e}"))
+            })?;
+        Ok(secret.data.unwrap_or_default())
+    }
+}
+
+/// The value of a field of the secret
+fn field<'a>(data: &'a BTreeMap<String, ByteString>, name: &str) -> Result<&'a [u8]> {
+    data.get(name)
+        .map(|value| value.0.as_slice())
+        .ok_or_else(|| SigstoreError::KeyRefError(format!("the secret has no {name} field")))
+}
+
+#[async_trait(?Send)]
+impl KeyProvider for KubernetesKeyProvider {
+    /// The signer of the private key of the secret. When `password` is
+    /// missing, the one of the secret is used.
+    async fn signer(
+        &self,
+        key_ref: &KeyRef,
+        password: Option<&[u8]>,
+    ) -> Result<Box<dyn KmsSigner>> {
+        let data = self.secret_data(key_ref).await?;
+        let password = match password {
+            Some(password) => Some(password),
+            None => data.get(PASSWORD_FIELD).map(|value| value.0.as_slice()),
+        };
+        Ok(Box::new(signer_from_pem(
+            field(&data, PRIVATE_KEY_FIELD)?,
+            password,
+        )?))
+    }
+
+    async fn verification_key(&self, key_ref: &KeyRef) -> Result<CosignVerificationKey> {
+        let data = self.secret_data(key_ref).await?;
+        verification_key_from_pem(field(&data, PUBLIC_KEY_FIELD)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_secret_fields() {
+        let data = BTreeMap::from([(
+            PUBLIC_KEY_FIELD.to_string(),
+            ByteString(b"-----BEGIN PUBLIC KEY-----".to_vec()),
+        )]);
+        assert_eq!(
+            field(&data, PUBLIC_KEY_FIELD).unwrap(),
+            b"-----BEGIN PUBLIC KEY-----"
+        );
+        assert!(matches!(
+            field(&data, PRIVATE_KEY_FIELD),
+            Err(SigstoreError::KeyRefError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn reject_other_key_refs() {
+        let provider = KubernetesKeyProvider::new();
+        let key_ref = KeyRef::Env("COSIGN_KEY".to_string());
+        assert!(matches!(
+            provider.verification_key(&key_ref).await,
+            Err(SigstoreError::KeyRefError(_))
+        ));
+    }
+}
@@ -0,0 +1,483 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Resolution of the key references accepted by the `--key` flag of cosign.
+//!
+//! A key reference is one of:
+//!
+//! * the path of a PEM file, holding a public key or a private key, like the
+//!   `cosign.key` and `cosign.pub` files of `cosign generate-key-pair`
+//! * `env://[NAME]`: an environment variable holding such PEM
+//! * `k8s://[NAMESPACE]/[SECRET]`: a Kubernetes secret, resolved when the
+//!   `k8s` feature is enabled
+//! * a reference to a key kept by a KMS or a hardware token, like
+//!   `awskms://…`, `gcpkms://…`, `azurekms://…`, `hashivault://…` and
+//!   `pkcs11:…`
+//!
+//! The references are parsed into [`KeyRef`], then resolved by the
+//! [`KeyProvider`] registered for their scheme with a [`KeyRefResolver`].
+//! The resolver comes with the providers of the local keys and of the KMS
+//! backends enabled by the cargo features; applications can register their
+//! own providers, for new schemes or to replace the built-in ones.
+//!
+//! ```rust,no_run
+//! use sigstore::crypto::key_ref::KeyRefResolver;
+//! use sigstore::crypto::signing_key::kms::KmsSigner;
+//!
+//! # async fn doc() -> sigstore::errors::Result<()> {
+//! let resolver = KeyRefResolver::new();
+//! let signer = resolver
+//!     .signer("cosign.key", Some(b"password"))
+//!     .await?;
+//! let signature = signer.sign(b"hello").await?;
+//!
+//! let verification_key = resolver.verification_key("awskms:///alias/my-key").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+
+use super::signing_key::kms::KmsSigner;
+use super::signing_key::{SigStoreKeyPair, SigStoreSigner};
+use super::CosignVerificationKey;
+use crate::crypto::signing_key::{
+    COSIGN_PRIVATE_KEY_PEM_LABEL, PUBLIC_KEY_PEM_LABEL, SIGSTORE_PRIVATE_KEY_PEM_LABEL,
+};
+use crate::errors::{Result, SigstoreError};
+
+#[cfg(feature = "k8s")]
+pub mod kubernetes;
+
+/// Scheme of the keys stored inside of files
+pub const FILE_SCHEME: &str = "file";
+/// Scheme of the keys stored inside of environment variables
+pub const ENV_SCHEME: &str = "env";
+/// Scheme of the keys stored inside of Kubernetes secrets
+pub const K8S_SCHEME: &str = "k8s";
+/// Scheme of the keys of PKCS#11 tokens, whose URIs have no `//`
+pub const PKCS11_SCHEME: &str = "pkcs11";
+
+/// A parsed key reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyRef {
+    /// The path of a PEM file
+    File(PathBuf),
+    /// The name of an environment variable holding a PEM document
+    Env(String),
+    /// A Kubernetes secret
+    Kubernetes {
+        /// The namespace of the secret
+        namespace: String,
+        /// The name of the secret
+        name: String,
+    },
+    /// A key kept by a KMS, by a hardware token, or by any other provider
+    Remote {
+        /// The scheme of the reference, like `awskms`
+        scheme: String,
+        /// The whole reference, as expected by the provider
+        uri: String,
+    },
+}
+
+impl KeyRef {
+    /// Parse a key reference. The references without scheme are file paths.
+    pub fn parse(key_ref: &str) -> Result<Self> {
+        let invalid =
+            |expected: &str| SigstoreError::KeyRefError(format!("{key_ref}, expected {expected}"));
+
+        if let Some(name) = key_ref.strip_prefix("env://") {
+            if name.is_empty() {
+                return Err(invalid("env://[NAME]"));
+            }
+            return Ok(KeyRef::Env(name.to_string()));
+        }
+        if let Some(secret) = key_ref.strip_prefix("k8s://") {
+            return match secret.split('/').collect::<Vec<_>>().as_slice() {
+                [namespace, name] if !namespace.is_empty() && !name.is_empty() => {
+                    Ok(KeyRef::Kubernetes {
+                        namespace: namespace.to_string(),
+                        name: name.to_string(),
+                    })
+                }
+                _ => Err(invalid("k8s://[NAMESPACE]/[SECRET]")),
+            };
+        }
+        if key_ref.starts_with("pkcs11:") {
+            return Ok(KeyRef::Remote {
+                scheme: PKCS11_SCHEME.to_string(),
+                uri: key_ref.to_string(),
+            });
+        }
+        if let Some((scheme, _)) = key_ref.split_once("://") {
+            if scheme.is_empty() {
+                return Err(invalid("[SCHEME]://[KEY]"));
+            }
+            return Ok(KeyRef::Remote {
+                scheme: scheme.to_string(),
+                uri: key_ref.to_string(),
+            });
+        }
+        if key_ref.is_empty() {
+            return Err(invalid("a path or [SCHEME]://[KEY]"));
+        }
+        Ok(KeyRef::File(PathBuf::from(key_ref)))
+    }
+
+    /// The scheme of the reference, used to find its provider
+    pub fn scheme(&self) -> &str {
+        match self {
+            KeyRef::File(_) => FILE_SCHEME,
+            KeyRef::Env(_) => ENV_SCHEME,
+            KeyRef::Kubernetes { .. } => K8S_SCHEME,
+            KeyRef::Remote { scheme, .. } => scheme,
+        }
+    }
+}
+
+/// Resolves the references of a scheme into signers and verification keys
+#[async_trait(?Send)]
+pub trait KeyProvider {
+    /// The signer of the referenced key. `password` decrypts the private
+    /// keys stored encrypted, the providers of remote keys ignore it.
+    async fn signer(&self, key_ref: &KeyRef, password: Option<&[u8]>)
+        -> Result<Box<dyn KmsSigner>>;
+
+    /// The key verifying the signatures of the referenced key
+    async fn verification_key(&self, key_ref: &KeyRef) -> Result<CosignVerificationKey> {
+        self.signer(key_ref, None).await?.to_verification_key()
+    }
+}
+
+/// Resolves key references through the provider registered for their scheme
+pub struct KeyRefResolver {
+    providers: HashMap<String, Box<dyn KeyProvider>>,
+}
+
+impl Default for KeyRefResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeyRefResolver {
+    /// Create a resolver with the providers of the local keys, and of the KMS
+    /// backends enabled by the cargo features
+    pub fn new() -> Self {
+        let mut resolver = KeyRefResolver {
+            providers: HashMap::new(),
+        }
+        .with_provider(FILE_SCHEME, LocalKeyProvider)
+        .with_provider(ENV_SCHEME, LocalKeyProvider);
+        for scheme in KmsKeyProvider::SCHEMES {
+            resolver = resolver.with_provider(scheme, KmsKeyProvider);
+        }
+        #[cfg(feature = "k8s")]
+        {
+            resolver = resolver.with_provider(K8S_SCHEME, kubernetes::KubernetesKeyProvider::new());
+        }
+        resolver
+    }
+
+    /// Resolve the references of `scheme` with `provider`, instead of the
+    /// provider previously registered for it
+    pub fn with_provider(mut self, scheme: &str, provider: impl KeyProvider + 'static) -> Self {
+        self.providers
+            .insert(scheme.to_string(), Box::new(provider));
+        self
+    }
+
+    /// The signer of the referenced key. `password` decrypts the private
+    /// keys stored encrypted.
+    pub async fn signer(
+        &self,
+        key_ref: &str,
+        password: Option<&[u8]>,
+    ) -> Result<Box<dyn KmsSigner>> {
+        let key_ref = KeyRef::parse(key_ref)?;
+        self.provider(&key_ref)?.signer(&key_ref, password).await
+    }
+
+    /// The key verifying the signatures of the referenced key
+    pub async fn verification_key(&self, key_ref: &str) -> Result<CosignVerificationKey> {
+        let key_ref = KeyRef::parse(key_ref)?;
+        self.provider(&key_ref)?.verification_key(&key_ref).await
+    }
+
+    fn provider(&self, key_ref: &KeyRef) -> Result<&dyn KeyProvider> {
+        self.providers
+            .get(key_ref.scheme())
+            .map(|provider| &**provider)
+            .ok_or_else(|| {
+                SigstoreError::KeyRefError(format!(
+                    "no provider for the {} scheme, is its cargo feature enabled?",
+                    key_ref.scheme()
+                ))
+            })
+    }
+}
+
+/// Provider of the PEM keys stored inside of files and of environment
+/// variables
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalKeyProvider;
+
+impl LocalKeyProvider {
+    fn read_pem(key_ref: &KeyRef) -> Result<Vec<u8>> {
+        match key_ref {
+            KeyRef::File(path) => std::fs::read(path).map_err(|e| {
+                SigstoreError::KeyRefError(format!("cannot read {}: {e}", path.display()))
+            }),
+            KeyRef::Env(name) => std::env::var(name).map(String::into_bytes).map_err(|_| {
+                SigstoreError::KeyRefError(format!("the {name} environment variable is not set"))
+            }),
+            other => Err(SigstoreError::KeyRefError(format!(
+                "{} keys are not local",
+                other.scheme()
+            ))),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl KeyProvider for LocalKeyProvider {
+    async fn signer(
+        &self,
+        key_ref: &KeyRef,
+        password: Option<&[u8]>,
+    ) -> Result<Box<dyn KmsSigner>> {
+        let pem_data = Self::read_pem(key_ref)?;
+        Ok(Box::new(signer_from_pem(&pem_data, password)?))
+    }
+
+    async fn verification_key(&self, key_ref: &KeyRef) -> Result<CosignVerificationKey> {
+        let pem_data = Self::read_pem(key_ref)?;
+        verification_key_from_pem(&pem_data)
+    }
+}
+
+/// The signer of a PEM private key: cosign keys are decrypted with
+/// `password`, the other keys are handled like
+/// [`SigStoreKeyPair::from_pkcs8_pem`] does.
+pub(crate) fn signer_from_pem(pem_data: &[u8], password: Option<&[u8]>) -> Result<SigStoreSigner> {
+    let tag = pem::parse(pem_data)?.tag;
+    match tag.as_str() {
+        COSIGN_PRIVATE_KEY_PEM_LABEL | SIGSTORE_PRIVATE_KEY_PEM_LABEL => {
+            SigStoreSigner::from_encrypted_pem(pem_data, password.unwrap_or_default())
+        }
+        PUBLIC_KEY_PEM_LABEL => Err(SigstoreError::KeyRefError(
+            "the key is a public key, it cannot sign".to_string(),
+        )),
+        _ => SigStoreKeyPair::from_pkcs8_pem(pem_data, password)?.to_default_sigstore_signer(),
+    }
+}
+
+/// The verification key of a PEM public key. Unencrypted private keys are
+/// accepted too, since their public key can be derived.
+pub(crate) fn verification_key_from_pem(pem_data: &[u8]) -> Result<CosignVerificationKey> {
+    let tag = pem::parse(pem_data)?.tag;
+    match tag.as_str() {
+        PUBLIC_KEY_PEM_LABEL => CosignVerificationKey::try_from_pem(pem_data),
+        COSIGN_PRIVATE_KEY_PEM_LABEL | SIGSTORE_PRIVATE_KEY_PEM_LABEL => {
+            Err(SigstoreError::KeyRefError(
+                "the private key is encrypted, the public key must be given instead".to_string(),
+            ))
+        }
+        _ => signer_from_pem(pem_data, None)?.to_verification_key(),
+    }
+}
+
+/// Provider of the keys of the KMS backends enabled by the cargo features
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KmsKeyProvider;
+
+impl KmsKeyProvider {
+    /// The schemes handled by the enabled backends
+    pub const SCHEMES: &'static [&'static str] = &[
+        #[cfg(feature = "aws-kms")]
+        "awskms",
+        #[cfg(feature = "gcp-kms")]
+        "gcpkms",
+        #[cfg(feature = "azure-kms")]
+        "azurekms",
+        #[cfg(feature = "hashivault")]
+        "hashivault",
+        #[cfg(feature = "pkcs11")]
+        PKCS11_SCHEME,
+    ];
+}
+
+#[async_trait(?Send)]
+impl KeyProvider for KmsKeyProvider {
+    async fn signer(
+        &self,
+        key_ref: &KeyRef,
+        _password: Option<&[u8]>,
+    ) -> Result<Box<dyn KmsSigner>> {
+        #[allow(unused_variables)]
+        let (scheme, uri) = match key_ref {
+            KeyRef::Remote { scheme, uri } => (scheme.as_str(), uri.as_str()),
+            other => {
+                return Err(SigstoreError::KeyRefError(format!(
+                    "{} keys are not kept by a KMS",
+                    other.scheme()
+                )))
+            }
+        };
+        match scheme {
+            #[cfg(feature = "aws-kms")]
+            "awskms" => Ok(Box::new(
+                super::signing_key::kms::aws::AwsKmsSigner::new(uri).await?,
+            )),
+            #[cfg(feature = "gcp-kms")]
+            "gcpkms" => Ok(Box::new(
+                super::signing_key::kms::gcp::GcpKmsSigner::new(uri).await?,
+            )),
+            #[cfg(feature = "azure-kms")]
+            "azurekms" => Ok(Box::new(
+                super::signing_key::kms::azure::AzureKmsSigner::new(uri).await?,
+            )),
+            #[cfg(feature = "hashivault")]
+            "hashivault" => Ok(Box::new(
+                super::signing_key::kms::hashivault::HashiVaultSigner::new(uri).await?,
+            )),
+            #[cfg(feature = "pkcs11")]
+            PKCS11_SCHEME => Ok(Box::new(
+                super::signing_key::kms::pkcs11::Pkcs11Signer::new(uri)?,
+            )),
+            other => Err(SigstoreError::KeyRefError(format!(
+                "no KMS backend for the {other} scheme, is its cargo feature enabled?"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{Signature, SigningScheme};
+    use rstest::rstest;
+    use std::io::Write;
+
+    #[rstest]
+    #[case("cosign.pub", KeyRef::File(PathBuf::from("cosign.pub")))]
+    #[case(
+        "/etc/keys/cosign.key",
+        KeyRef::File(PathBuf::from("/etc/keys/cosign.key"))
+    )]
+    #[case("env://COSIGN_KEY", KeyRef::Env("COSIGN_KEY".to_string()))]
+    #[case(
+        "k8s://sigstore/cosign-keys",
+        KeyRef::Kubernetes { namespace: "sigstore".to_string(), name: "cosign-keys".to_string() }
+    )]
+    #[case(
+        "awskms:///alias/my-key",
+        KeyRef::Remote { scheme: "awskms".to_string(), uri: "awskms:///alias/my-key".to_string() }
+    )]
+    #[case(
+        "pkcs11:object=my-key",
+        KeyRef::Remote { scheme: "pkcs11".to_string(), uri: "pkcs11:object=my-key".to_string() }
+    )]
+    fn parse_key_ref(#[case] key_ref: &str, #[case] expected: KeyRef) {
+        assert_eq!(KeyRef::parse(key_ref).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("env://")]
+    #[case("k8s://sigstore")]
+    #[case("k8s://sigstore/cosign/keys")]
+    #[case("://my-key")]
+    fn reject_invalid_key_ref(#[case] key_ref: &str) {
+        assert!(matches!(
+            KeyRef::parse(key_ref),
+            Err(SigstoreError::KeyRefError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolve_file_and_env_keys() {
+        let key_pair = SigStoreKeyPair::generate(&SigningScheme::ECDSA_P256_SHA256_ASN1).unwrap();
+        let private_key = key_pair.private_key_to_encrypted_pem(b"password").unwrap();
+        let mut private_key_file = tempfile::NamedTempFile::new().unwrap();
+        private_key_file.write_all(private_key.as_bytes()).unwrap();
+        std::env::set_var(
+            "SIGSTORE_KEY_REF_TEST_PUB",
+            key_pair.public_key_to_pem().unwrap(),
+        );
+
+        let resolver = KeyRefResolver::new();
+        let signer = resolver
+            .signer(private_key_file.path().to_str().unwrap(), Some(b"password"))
+            .await
+            .expect("the signer should be resolved");
+        let signature = signer.sign(b"hello").await.unwrap();
+
+        resolver
+            .verification_key("env://SIGSTORE_KEY_REF_TEST_PUB")
+            .await
+            .expect("the verification key should be resolved")
+            .verify_signature(Signature::Raw(&signature), b"hello")
+            .expect("the signature should verify");
+
+        assert!(resolver
+            .signer(private_key_file.path().to_str().unwrap(), Some(b"wrong"))
+            .await
+            .is_err());
+        assert!(resolver
+            .signer("env://SIGSTORE_KEY_REF_TEST_PUB", None)
+            .await
+            .is_err());
+    }
+
+    struct StaticProvider(SigStoreKeyPair);
+
+    #[async_trait(?Send)]
+    impl KeyProvider for StaticProvider {
+        async fn signer(
+            &self,
+            _key_ref: &KeyRef,
+            _password: Option<&[u8]>,
+        ) -> Result<Box<dyn KmsSigner>> {
+            Ok(Box::new(self.0.to_default_sigstore_signer()?))
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_with_custom_provider() {
+        let key_pair = SigStoreKeyPair::generate(&SigningScheme::ED25519).unwrap();
+        let signature = key_pair
+            .to_default_sigstore_signer()
+            .unwrap()
+            .sign(b"hello")
+            .unwrap();
+        let resolver = KeyRefResolver::new().with_provider("mykms", StaticProvider(key_pair));
+
+        resolver
+            .verification_key("mykms://my-key")
+            .await
+            .expect("the verification key should be resolved")
+            .verify_signature(Signature::Raw(&signature), b"hello")
+            .expect("the signature should verify");
+        assert!(matches!(
+            resolver.signer("otherkms://my-key", None).await,
+            Err(SigstoreError::KeyRefError(_))
+        ));
+    }
+}
@@ -42,6 +42,9 @@ pub use verification_key::CosignVerificationKey;
 /// is the default signing scheme.
 /// * `ECDSA_P384_SHA384_ASN1`: ASN.1 DER-encoded ECDSA
 /// signatures using the P-384 curve and SHA-384.
+/// * `ECDSA_P521_SHA512`: ASN.1 DER-encoded ECDSA
+///   signatures using the P-521 curve and SHA-512. It can
+///   only be used to verify signatures.
 /// * `ED25519`: ECDSA signature using SHA2-512
 /// as the digest function and curve edwards25519. The
 /// signature format please refer
@@ -57,6 +60,7 @@ pub enum SigningScheme {
     RSA_PKCS1_SHA512(usize),
     ECDSA_P256_SHA256_ASN1,
     ECDSA_P384_SHA384_ASN1,
+    ECDSA_P521_SHA512,
     ED25519,
 }
 
@@ -71,6 +75,7 @@ impl ToString for SigningScheme {
             SigningScheme::RSA_PKCS1_SHA512(_) => "RSA_PKCS1_SHA512",
             SigningScheme::ECDSA_P256_SHA256_ASN1 => "ECDSA_P256_SHA256_ASN1",
             SigningScheme::ECDSA_P384_SHA384_ASN1 => "ECDSA_P384_SHA384_ASN1",
+            SigningScheme::ECDSA_P521_SHA512 => "ECDSA_P521_SHA512",
             SigningScheme::ED25519 => "ED25519",
         };
         String::from(str)
@@ -84,6 +89,7 @@ impl TryFrom<&str> for SigningScheme {
         match value {
             "ECDSA_P256_SHA256_ASN1" => Ok(Self::ECDSA_P256_SHA256_ASN1),
             "ECDSA_P384_SHA384_ASN1" => Ok(Self::ECDSA_P384_SHA384_ASN1),
+            "ECDSA_P521_SHA512" => Ok(Self::ECDSA_P521_SHA512),
             "ED25519" => Ok(Self::ED25519),
             "RSA_PSS_SHA256" => Ok(Self::RSA_PSS_SHA256(DEFAULT_KEY_SIZE)),
             "RSA_PSS_SHA384" => Ok(Self::RSA_PSS_SHA384(DEFAULT_KEY_SIZE)),
@@ -106,6 +112,11 @@ impl SigningScheme {
             SigningScheme::ECDSA_P384_SHA384_ASN1 => SigStoreSigner::ECDSA_P384_SHA384_ASN1(
                 EcdsaSigner::<_, Sha384>::from_ecdsa_keys(&EcdsaKeys::<p384::NistP384>::new()?)?,
             ),
+            SigningScheme::ECDSA_P521_SHA512 => {
+                return Err(SigstoreError::PublicKeyUnsupportedAlgorithmError(
+                    "P-521 keys can only be used to verify signatures".to_string(),
+                ))
+            }
             SigningScheme::ED25519 => {
                 SigStoreSigner::ED25519(Ed25519Signer::from_ed25519_keys(&Ed25519Keys::new()?)?)
             }
@@ -173,13 +184,43 @@ pub enum Signature<'a> {
     Base64Encoded(&'a [u8]),
 }
 
+/// The encoding of the ECDSA signatures. The signatures of the other keys
+/// have a single encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignatureFormat {
+    /// The ASN.1 DER encoded sequence of `r` and `s`, used by cosign
+    #[default]
+    Asn1Der,
+    /// The fixed size concatenation `r || s`, used by JWS, COSE and most
+    /// hardware tokens
+    Raw,
+}
+
 #[cfg(feature = "cert")]
 pub(crate) mod certificate;
 #[cfg(feature = "cert")]
-pub(crate) mod certificate_pool;
+pub mod certificate_extensions;
+#[cfg(feature = "cert")]
+pub mod certificate_pool;
+#[cfg(feature = "cert")]
+pub use certificate_pool::CertificatePool;
+#[cfg(feature = "cert")]
+pub mod certificate_transparency;
+#[cfg(feature = "cert")]
+pub mod expiry;
+#[cfg(feature = "cert")]
+pub mod revocation;
+#[cfg(feature = "cert")]
+pub mod timestamp;
+#[cfg(feature = "cert")]
+pub mod trusted_root;
 
+pub mod hash;
+pub mod key_ref;
 pub mod verification_key;
 
+pub mod cose;
+
 use self::signing_key::{
     ecdsa::ec::{EcdsaKeys, EcdsaSigner},
     ed25519::{Ed25519Keys, Ed25519Signer},
@@ -217,6 +258,7 @@ OSWS1X9vPavpiQOoTTGC0xX57OojUadxF1cdQmrsiReWg2Wn4FneJfa8xw==
     pub(crate) struct CertGenerationOptions {
         pub digital_signature_key_usage: bool,
         pub code_signing_extended_key_usage: bool,
+        pub time_stamping_extended_key_usage: bool,
         pub subject_email: Option<String>,
         pub subject_url: Option<String>,
         //TODO: remove macro once https://github.com/sfackler/rust-openssl/issues/1411
@@ -243,6 +285,7 @@ OSWS1X9vPavpiQOoTTGC0xX57OojUadxF1cdQmrsiReWg2Wn4FneJfa8xw==
             CertGenerationOptions {
                 digital_signature_key_usage: true,
                 code_signing_extended_key_usage: true,
+                time_stamping_extended_key_usage: false,
                 subject_email: Some(String::from("tests@sigstore-rs.dev")),
                 subject_issuer: Some(String::from("https://sigstore.dev/oauth")),
                 subject_url: None,
@@ -385,9 +428,16 @@ OSWS1X9vPavpiQOoTTGC0xX57OojUadxF1cdQmrsiReWg2Wn4FneJfa8xw==
                 extensions.push(key_usage);
             }
 
-            if settings.code_signing_extended_key_usage {
-                let extended_key_usage = ExtendedKeyUsage::new().code_signing().build()?;
-                extensions.push(extended_key_usage);
+            if settings.code_signing_extended_key_usage || settings.time_stamping_extended_key_usage
+            {
+                let mut extended_key_usage = ExtendedKeyUsage::new();
+                if settings.code_signing_extended_key_usage {
+                    extended_key_usage.code_signing();
+                }
+                if settings.time_stamping_extended_key_usage {
+                    extended_key_usage.time_stamping();
+                }
+                extensions.push(extended_key_usage.build()?);
             }
         } else {
             let key_usage = KeyUsage::new()
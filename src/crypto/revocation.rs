@@ -0,0 +1,437 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Revocation checking of the certificates verified by a
+//! [`CertificatePool`](crate::crypto::CertificatePool).
+//!
+//! The certificates issued by the public instance of Fulcio are valid for
+//! a few minutes, hence they are never revoked. Private deployments can
+//! issue longer-lived certificates, which must be checked against the
+//! revocation lists of their CA:
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use sigstore::crypto::revocation::{Crl, CrlChecker, RevocationPolicy};
+//! use sigstore::crypto::CertificatePool;
+//! use sigstore::registry::{Certificate, CertificateEncoding};
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! let ca = std::fs::read("ca.der")?;
+//! let crl = Crl::from_der(&std::fs::read("ca.crl")?, &ca)?;
+//! let pool = CertificatePool::from_certificates(&[Certificate {
+//!     encoding: CertificateEncoding::Der,
+//!     data: ca,
+//! }])?
+//! .with_revocation_checker(Arc::new(CrlChecker::new(vec![crl])), RevocationPolicy::HardFail);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The CRLs can be downloaded from the distribution points of the
+//! certificates with [`Crl::fetch`]. Other revocation mechanisms, like
+//! OCSP, are plugged in by implementing the [`RevocationChecker`] trait.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use chrono::{DateTime, Utc};
+use pkcs8::der::{Decode, Encode};
+use tracing::warn;
+use x509_cert::crl::CertificateList;
+use x509_cert::ext::pkix::name::{DistributionPointName, GeneralName};
+use x509_cert::ext::pkix::CrlDistributionPoints;
+use x509_cert::Certificate;
+
+use super::{hash::HashAlgorithm, CosignVerificationKey, Signature};
+use crate::errors::{Result, SigstoreError};
+
+/// The revocation status of a certificate
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RevocationStatus {
+    /// The certificate is not revoked
+    Good,
+    /// The certificate has been revoked at the given time
+    Revoked(DateTime<Utc>),
+    /// The status cannot be determined, for the given reason
+    Unknown(String),
+}
+
+/// What to do when the revocation status of a certificate cannot be
+/// determined, for example because its CRL cannot be found or is stale
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RevocationPolicy {
+    /// Trust the certificate, logging a warning
+    SoftFail,
+    /// Reject the certificate
+    HardFail,
+}
+
+/// A source of revocation statuses, like CRLs or an OCSP responder.
+pub trait RevocationChecker: Send + Sync {
+    /// The revocation status of the DER encoded certificate `cert`, issued
+    /// by the DER encoded certificate `issuer`
+    fn check(&self, cert: &[u8], issuer: &[u8]) -> Result<RevocationStatus>;
+}
+
+impl std::fmt::Debug for dyn RevocationChecker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RevocationChecker")
+    }
+}
+
+/// Check the revocation of the DER encoded `cert`, issued by `issuer`,
+/// according to `policy`
+pub(crate) fn check_revocation(
+    checker: &dyn RevocationChecker,
+    policy: RevocationPolicy,
+    cert: &[u8],
+    issuer: &[u8],
+) -> Result<()> {
+    let reason = match checker.check(cert, issuer) {
+        Ok(RevocationStatus::Good) => return Ok(()),
+        Ok(RevocationStatus::Revoked(revoked_at)) => {
+            return Err(SigstoreError::CertificateRevokedError(format!(
+                "revoked at {revoked_at}"
+            )))
+        }
+        Ok(RevocationStatus::Unknown(reason)) => reason,
+        Err(e) => e.to_string(),
+    };
+    match policy {
+        RevocationPolicy::SoftFail => {
+            warn!(
+                reason,
+                "cannot check the revocation status of the certificate, trusting it"
+            );
+            Ok(())
+        }
+        RevocationPolicy::HardFail => Err(SigstoreError::RevocationCheckError(reason)),
+    }
+}
+
+/// A verified Certificate Revocation List
+#[derive(Clone, Debug)]
+pub struct Crl {
+    /// The DER encoded name of the issuer
+    issuer: Vec<u8>,
+    next_update: Option<DateTime<Utc>>,
+    /// The revocation times, by serial number
+    revoked: HashMap<Vec<u8>, DateTime<Utc>>,
+}
+
+impl Crl {
+    /// Parse the DER encoded CRL, which must be signed by the DER encoded
+    /// certificate `issuer`
+    pub fn from_der(der: &[u8], issuer: &[u8]) -> Result<Self> {
+        let crl = CertificateList::from_der(der).map_err(crl_error)?;
+        let issuer = Certificate::from_der(issuer)
+            .map_err(|e| SigstoreError::X509Error(format!("parse from der: {e}")))?;
+
+        let issuer_name = issuer.tbs_certificate.subject.to_vec().map_err(crl_error)?;
+        if crl.tbs_cert_list.issuer.to_vec().map_err(crl_error)? != issuer_name {
+            return Err(SigstoreError::RevocationCheckError(
+                "the CRL has not been issued by the given certificate".to_string(),
+            ));
+        }
+        let key = CosignVerificationKey::try_from(&issuer.tbs_certificate.subject_public_key_info)?;
+        let signature = Signature::Raw(crl.signature.raw_bytes());
+        let tbs_cert_list = crl.tbs_cert_list.to_vec().map_err(crl_error)?;
+        match HashAlgorithm::from_signature_algorithm(&crl.signature_algorithm.oid) {
+            Some(algorithm) => {
+                key.verify_signature_with_hash_algorithm(signature, &tbs_cert_list, algorithm)?
+            }
+            None => key.verify_signature(signature, &tbs_cert_list)?,
+        }
+
+        let revoked = crl
+            .tbs_cert_list
+            .revoked_certificates
+            .unwrap_or_default()
+            .iter()
+            .map(|revoked| {
+                (
+                    revoked.serial_number.as_bytes().to_vec(),
+                    revoked.revocation_date.to_system_time().into(),
+                )
+            })
+            .collect();
+        Ok(Crl {
+            issuer: issuer_name,
+            next_update: crl
+                .tbs_cert_list
+                .next_update
+                .map(|time| time.to_system_time().into()),
+            revoked,
+        })
+    }
+
+    /// Parse the PEM encoded CRL, which must be signed by the DER encoded
+    /// certificate `issuer`
+    pub fn from_pem(pem: &[u8], issuer: &[u8]) -> Result<Self> {
+        Self::from_der(&pem::parse(pem)?.contents, issuer)
+    }
+
+    /// Download the CRL published at `url`, which must be signed by the DER
+    /// encoded certificate `issuer`
+    #[cfg(any(feature = "fulcio", feature = "rekor"))]
+    pub async fn fetch(
+        http_client: &dyn crate::http::HttpClient,
+        url: &str,
+        issuer: &[u8],
+    ) -> Result<Self> {
+        let request = http::Request::get(url)
+            .body(Vec::new())
+            .map_err(|e| SigstoreError::HttpError(e.to_string()))?;
+        let response = http_client.execute(request).await?;
+        if !response.status().is_success() {
+            return Err(SigstoreError::RevocationCheckError(format!(
+                "cannot download the CRL at {url}: {}",
+                response.status()
+            )));
+        }
+        let body = response.into_body();
+        if body.starts_with(b"-----BEGIN") {
+            Self::from_pem(&body, issuer)
+        } else {
+            Self::from_der(&body, issuer)
+        }
+    }
+
+    /// Whether the CRL should have been replaced by a newer one at `time`
+    pub fn is_stale(&self, time: DateTime<Utc>) -> bool {
+        self.next_update
+            .is_some_and(|next_update| next_update < time)
+    }
+}
+
+/// The URLs of the CRL distribution points of the DER encoded certificate
+pub fn distribution_points(cert: &[u8]) -> Result<Vec<String>> {
+    let cert = Certificate::from_der(cert)
+        .map_err(|e| SigstoreError::X509Error(format!("parse from der: {e}")))?;
+    let distribution_points = match cert
+        .tbs_certificate
+        .get::<CrlDistributionPoints>()
+        .map_err(|e| SigstoreError::X509Error(e.to_string()))?
+    {
+        Some((_, distribution_points)) => distribution_points,
+        None => return Ok(Vec::new()),
+    };
+
+    Ok(distribution_points
+        .0
+        .iter()
+        .filter_map(|dp| match &dp.distribution_point {
+            Some(DistributionPointName::FullName(names)) => Some(names),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|name| match name {
+            GeneralName::UniformResourceIdentifier(uri) => Some(uri.as_str().to_string()),
+            _ => None,
+        })
+        .collect())
+}
+
+/// A [`RevocationChecker`] looking up the certificates inside of CRLs
+#[derive(Clone, Debug, Default)]
+pub struct CrlChecker {
+    crls: Vec<Crl>,
+}
+
+impl CrlChecker {
+    /// Check the certificates against `crls`
+    pub fn new(crls: Vec<Crl>) -> Self {
+        CrlChecker { crls }
+    }
+}
+
+impl RevocationChecker for CrlChecker {
+    fn check(&self, cert: &[u8], _issuer: &[u8]) -> Result<RevocationStatus> {
+        let cert = Certificate::from_der(cert)
+            .map_err(|e| SigstoreError::X509Error(format!("parse from der: {e}")))?;
+        let issuer = cert.tbs_certificate.issuer.to_vec().map_err(crl_error)?;
+        let serial_number = cert.tbs_certificate.serial_number.as_bytes();
+
+        let crl = match self.crls.iter().find(|crl| crl.issuer == issuer) {
+            Some(crl) => crl,
+            None => {
+                return Ok(RevocationStatus::Unknown(
+                    "no CRL of the issuer of the certificate".to_string(),
+                ))
+            }
+        };
+        if let Some(revoked_at) = crl.revoked.get(serial_number) {
+            return Ok(RevocationStatus::Revoked(*revoked_at));
+        }
+        if crl.is_stale(Utc::now()) {
+            return Ok(RevocationStatus::Unknown(
+                "the CRL of the issuer is stale".to_string(),
+            ));
+        }
+        Ok(RevocationStatus::Good)
+    }
+}
+
+fn crl_error(e: pkcs8::der::Error) -> SigstoreError {
+    SigstoreError::RevocationCheckError(format!("invalid CRL: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::certificate_pool::tests::{issue, Issued};
+    use crate::crypto::CertificatePool;
+    use const_oid::db::rfc5912::ECDSA_WITH_SHA_256;
+    use openssl::hash::MessageDigest;
+    use openssl::sign::Signer;
+    use pkcs8::der::asn1::{BitStringRef, UIntRef, UtcTime};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use x509_cert::crl::{RevokedCert, TbsCertList};
+    use x509_cert::spki::AlgorithmIdentifier;
+    use x509_cert::time::Time;
+    use x509_cert::Version;
+
+    fn utc_time(time: SystemTime) -> Time {
+        let seconds = time.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        Time::UtcTime(UtcTime::from_unix_duration(Duration::from_secs(seconds)).unwrap())
+    }
+
+    /// A CRL signed by `issuer`, revoking the certificates with the given
+    /// serial numbers and expiring at `next_update`
+    fn sign_crl(issuer: &Issued, revoked_serials: &[Vec<u8>], next_update: SystemTime) -> Vec<u8> {
+        let issuer_der = issuer.cert.to_der().unwrap();
+        let issuer_cert = Certificate::from_der(&issuer_der).unwrap();
+        let algorithm = || AlgorithmIdentifier {
+            oid: ECDSA_WITH_SHA_256,
+            parameters: None,
+        };
+        let revoked = revoked_serials
+            .iter()
+            .map(|serial| RevokedCert {
+                serial_number: UIntRef::new(serial).unwrap(),
+                revocation_date: utc_time(SystemTime::now()),
+                crl_entry_extensions: None,
+            })
+            .collect();
+        let tbs_cert_list = TbsCertList {
+            version: Version::V2,
+            signature: algorithm(),
+            issuer: issuer_cert.tbs_certificate.subject.clone(),
+            this_update: utc_time(SystemTime::now()),
+            next_update: Some(utc_time(next_update)),
+            revoked_certificates: Some(revoked),
+            crl_extensions: None,
+        };
+
+        let mut signer = Signer::new(MessageDigest::sha256(), &issuer.private_key).unwrap();
+        let signature = signer
+            .sign_oneshot_to_vec(&tbs_cert_list.to_vec().unwrap())
+            .unwrap();
+        CertificateList {
+            tbs_cert_list,
+            signature_algorithm: algorithm(),
+            signature: BitStringRef::from_bytes(&signature).unwrap(),
+        }
+        .to_vec()
+        .unwrap()
+    }
+
+    fn serial_number(issued: &Issued) -> Vec<u8> {
+        issued.cert.serial_number().to_bn().unwrap().to_vec()
+    }
+
+    #[test]
+    fn reject_revoked_certificates() {
+        let root = issue("root", None, true);
+        let intermediate = issue("intermediate", Some(&root), true);
+        let leaf = issue("leaf", Some(&intermediate), false);
+        let revoked_leaf = issue("revoked leaf", Some(&intermediate), false);
+
+        let tomorrow = SystemTime::now() + Duration::from_secs(86400);
+        let root_der = root.cert.to_der().unwrap();
+        let intermediate_der = intermediate.cert.to_der().unwrap();
+        let crls = vec![
+            Crl::from_der(&sign_crl(&root, &[], tomorrow), &root_der).unwrap(),
+            Crl::from_der(
+                &sign_crl(&intermediate, &[serial_number(&revoked_leaf)], tomorrow),
+                &intermediate_der,
+            )
+            .unwrap(),
+        ];
+        let pool = CertificatePool::from_certificates(&[
+            root.to_certificate(),
+            intermediate.to_certificate(),
+        ])
+        .unwrap()
+        .with_revocation_checker(Arc::new(CrlChecker::new(crls)), RevocationPolicy::HardFail);
+
+        assert!(pool.verify_der_cert(&leaf.cert.to_der().unwrap()).is_ok());
+        assert!(matches!(
+            pool.verify_der_cert(&revoked_leaf.cert.to_der().unwrap()),
+            Err(SigstoreError::CertificateRevokedError(_))
+        ));
+    }
+
+    #[test]
+    fn apply_policy_when_status_is_unknown() {
+        let root = issue("root", None, true);
+        let intermediate = issue("intermediate", Some(&root), true);
+        let leaf = issue("leaf", Some(&intermediate), false);
+        let leaf_der = leaf.cert.to_der().unwrap();
+
+        // a stale CRL
+        let yesterday = SystemTime::now() - Duration::from_secs(86400);
+        let crl = Crl::from_der(
+            &sign_crl(&intermediate, &[], yesterday),
+            &intermediate.cert.to_der().unwrap(),
+        )
+        .unwrap();
+        let checker = CrlChecker::new(vec![crl]);
+        assert!(matches!(
+            checker.check(&leaf_der, &[]).unwrap(),
+            RevocationStatus::Unknown(_)
+        ));
+
+        let pool = |policy| {
+            CertificatePool::from_certificates(&[
+                root.to_certificate(),
+                intermediate.to_certificate(),
+            ])
+            .unwrap()
+            .with_revocation_checker(Arc::new(checker.clone()), policy)
+        };
+        assert!(pool(RevocationPolicy::SoftFail)
+            .verify_der_cert(&leaf_der)
+            .is_ok());
+        assert!(matches!(
+            pool(RevocationPolicy::HardFail).verify_der_cert(&leaf_der),
+            Err(SigstoreError::RevocationCheckError(_))
+        ));
+    }
+
+    #[test]
+    fn reject_crl_of_another_issuer() {
+        let root = issue("root", None, true);
+        let other = issue("other", None, true);
+        let tomorrow = SystemTime::now() + Duration::from_secs(86400);
+
+        assert!(Crl::from_der(
+            &sign_crl(&other, &[], tomorrow),
+            &root.cert.to_der().unwrap()
+        )
+        .is_err());
+    }
+}
@@ -189,6 +189,17 @@ where
         Self::from_private_key(ec_seckey)
     }
 
+    /// Builds a `EcdsaKeys` from a SEC1 asn.1 private key, like the ones
+    /// inside of the `EC PRIVATE KEY` PEM files written by `openssl ecparam`.
+    pub fn from_sec1_der(private_key: &[u8]) -> Result<Self> {
+        let ec_seckey = SecretKey::<C>::from_sec1_der(private_key).map_err(|e| {
+            SigstoreError::KeyParseError(format!(
+                "Convert from sec1 der to ecdsa private key failed: {e}"
+            ))
+        })?;
+        Self::from_private_key(ec_seckey)
+    }
+
     /// Builds a `EcdsaKeys` from a private key.
     fn from_private_key(ec_seckey: SecretKey<C>) -> Result<Self> {
         let public_key = ec_seckey.public_key();
@@ -158,6 +158,14 @@ impl ECDSAKeys {
         iterate_on_curves!(from_der(private_key), "Ecdsa keys from DER private key")
     }
 
+    /// Builds a `EcdsaKeys` from a SEC1 asn.1 private key.
+    pub fn from_sec1_der(private_key: &[u8]) -> Result<Self> {
+        iterate_on_curves!(
+            from_sec1_der(private_key),
+            "Ecdsa keys from SEC1 DER private key"
+        )
+    }
+
     /// `to_sigstore_signer` will create the [`SigStoreSigner`] using
     /// this Ecdsa private key. This function does not receive any parameter
     /// to indicate the digest algorthm, because the common signing schemes
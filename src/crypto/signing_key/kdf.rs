@@ -174,7 +174,7 @@ impl SecretBoxCipher {
         let mut cipher = xsalsa20poly1305::XSalsa20Poly1305::new(key);
         cipher
             .decrypt(nonce, ciphertext)
-            .map_err(|e| SigstoreError::PrivateKeyEncryptError(e.to_string()))
+            .map_err(|e| SigstoreError::PrivateKeyDecryptError(e.to_string()))
     }
 }
 
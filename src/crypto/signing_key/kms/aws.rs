@@ -0,0 +1,266 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signer backed by an asymmetric key of AWS KMS.
+//!
+//! The keys are referenced like cosign does, with the
+//! `awskms://[ENDPOINT]/[ID|ALIAS|ARN]` format:
+//!
+//! * `awskms:///1234abcd-12ab-34cd-56ef-1234567890ab`
+//! * `awskms:///alias/my-signing-key`
+//! * `awskms:///arn:aws:kms:us-east-2:111122223333:key/1234abcd-12ab-34cd-56ef-1234567890ab`
+//! * `awskms://localhost:4566/alias/my-signing-key`, for a KMS listening on
+//!   a custom endpoint
+//!
+//! The credentials and the region are loaded from the environment, like the
+//! AWS CLI does. The region of an ARN takes precedence over the one of the
+//! environment.
+//!
+//! ```rust,no_run
+//! use sigstore::crypto::signing_key::kms::aws::AwsKmsSigner;
+//! use sigstore::crypto::signing_key::kms::KmsSigner;
+//! use sigstore::crypto::Signature;
+//!
+//! # async fn doc() -> sigstore::errors::Result<()> {
+//! let signer = AwsKmsSigner::new("awskms:///alias/my-signing-key").await?;
+//! let signature = signer.sign(b"hello").await?;
+//!
+//! signer
+//!     .to_verification_key()?
+//!     .verify_signature(Signature::Raw(&signature), b"hello")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use aws_config::SdkConfig;
+use aws_sdk_kms::config::Region;
+use aws_sdk_kms::primitives::Blob;
+use aws_sdk_kms::types::{KeySpec, MessageType, SigningAlgorithmSpec};
+use aws_sdk_kms::Client;
+
+use super::{hash_algorithm, KmsSigner};
+use crate::crypto::SigningScheme;
+use crate::errors::{Result, SigstoreError};
+
+/// Scheme of the references to AWS KMS keys
+pub const AWS_KMS_SCHEME: &str = "awskms://";
+
+/// A reference to an AWS KMS key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwsKmsKeyRef {
+    /// The endpoint of the KMS, when not the default one of the region
+    pub endpoint: Option<String>,
+    /// The ID, the alias or the ARN of the key
+    pub key_id: String,
+}
+
+impl AwsKmsKeyRef {
+    /// Parse a `awskms://[ENDPOINT]/[ID|ALIAS|ARN]` reference
+    pub fn parse(key_ref: &str) -> Result<Self> {
+        let invalid = || {
+            SigstoreError::KmsError(format!(
+                "invalid AWS KMS key reference {key_ref}, expected {AWS_KMS_SCHEME}[ENDPOINT]/[ID|ALIAS|ARN]"
+            ))
+        };
+        let (endpoint, key_id) = key_ref
+            .strip_prefix(AWS_KMS_SCHEME)
+            .and_then(|rest| rest.split_once('/'))
+            .ok_or_else(invalid)?;
+        if key_id.is_empty() {
+            return Err(invalid());
+        }
+        Ok(AwsKmsKeyRef {
+            endpoint: Some(endpoint).filter(|e| !e.is_empty()).map(str::to_string),
+            key_id: key_id.to_string(),
+        })
+    }
+
+    /// The region of the key, when it is referenced by its ARN
+    pub fn region(&self) -> Option<&str> {
+        // arn:aws:kms:<region>:<account>:key/<id>
+        let mut parts = self.key_id.split(':');
+        match (parts.next(), parts.nth(2)) {
+            (Some("arn"), Some(region)) if !region.is_empty() => Some(region),
+            _ => None,
+        }
+    }
+}
+
+/// Signer backed by an asymmetric AWS KMS key, whose key usage is
+/// `SIGN_VERIFY`
+#[derive(Debug, Clone)]
+pub struct AwsKmsSigner {
+    client: Client,
+    key_id: String,
+    signing_scheme: SigningScheme,
+    public_key: Vec<u8>,
+}
+
+impl AwsKmsSigner {
+    /// Create a signer for the key referenced by `key_ref`, using the AWS
+    /// configuration of the environment.
+    ///
+    /// The public key of the key is fetched once, to find out its signing
+    /// scheme.
+    pub async fn new(key_ref: &str) -> Result<Self> {
+        let config = aws_config::load_from_env().await;
+        Self::from_config(&config, key_ref).await
+    }
+
+    /// Create a signer for the key referenced by `key_ref`, using the given
+    /// AWS configuration
+    pub async fn from_config(config: &SdkConfig, key_ref: &str) -> Result<Self> {
+        let key_ref = AwsKmsKeyRef::parse(key_ref)?;
+        let mut builder = aws_sdk_kms::config::Builder::from(config);
+        if let Some(region) = key_ref.region() {
+            builder = builder.region(Region::new(region.to_string()));
+        }
+        if let Some(endpoint) = &key_ref.endpoint {
+            builder = builder.endpoint_url(format!("https://{endpoint}"));
+        }
+        let client = Client::from_conf(builder.build());
+
+        let output = client
+            .get_public_key()
+            .key_id(&key_ref.key_id)
+            .send()
+            .await
+            .map_err(|e| SigstoreError::KmsError(format!("cannot fetch the public key: {e}")))?;
+        let public_key = output
+            .public_key()
+            .ok_or_else(|| SigstoreError::KmsError("the key has no public key".to_string()))?
+            .as_ref()
+            .to_vec();
+        let key_spec = output
+            .key_spec()
+            .ok_or_else(|| SigstoreError::KmsError("the key spec is unknown".to_string()))?;
+
+        Ok(AwsKmsSigner {
+            client,
+            key_id: key_ref.key_id,
+            signing_scheme: signing_scheme(key_spec)?,
+            public_key,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl KmsSigner for AwsKmsSigner {
+    fn signing_scheme(&self) -> SigningScheme {
+        self.signing_scheme
+    }
+
+    fn public_key_to_der(&self) -> Result<Vec<u8>> {
+        Ok(self.public_key.clone())
+    }
+
+    async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let digest = hash_algorithm(&self.signing_scheme)?.digest(msg);
+        let output = self
+            .client
+            .sign()
+            .key_id(&self.key_id)
+            .message(Blob::new(digest))
+            .message_type(MessageType::Digest)
+            .signing_algorithm(signing_algorithm(&self.signing_scheme)?)
+            .send()
+            .await
+            .map_err(|e| SigstoreError::KmsError(format!("cannot sign: {e}")))?;
+        output
+            .signature()
+            .map(|signature| signature.as_ref().to_vec())
+            .ok_or_else(|| SigstoreError::KmsError("KMS returned no signature".to_string()))
+    }
+}
+
+/// The signing scheme used with keys of the given spec. Like cosign, RSA
+/// keys produce PKCS#1 v1.5 signatures over a SHA-256 digest.
+fn signing_scheme(key_spec: &KeySpec) -> Result<SigningScheme> {
+    match key_spec {
+        KeySpec::EccNistP256 => Ok(SigningScheme::ECDSA_P256_SHA256_ASN1),
+        KeySpec::EccNistP384 => Ok(SigningScheme::ECDSA_P384_SHA384_ASN1),
+        KeySpec::Rsa2048 => Ok(SigningScheme::RSA_PKCS1_SHA256(2048)),
+        KeySpec::Rsa3072 => Ok(SigningScheme::RSA_PKCS1_SHA256(3072)),
+        KeySpec::Rsa4096 => Ok(SigningScheme::RSA_PKCS1_SHA256(4096)),
+        other => Err(SigstoreError::KmsError(format!(
+            "unsupported key spec {}",
+            other.as_str()
+        ))),
+    }
+}
+
+fn signing_algorithm(signing_scheme: &SigningScheme) -> Result<SigningAlgorithmSpec> {
+    match signing_scheme {
+        SigningScheme::ECDSA_P256_SHA256_ASN1 => Ok(SigningAlgorithmSpec::EcdsaSha256),
+        SigningScheme::ECDSA_P384_SHA384_ASN1 => Ok(SigningAlgorithmSpec::EcdsaSha384),
+        SigningScheme::RSA_PKCS1_SHA256(_) => Ok(SigningAlgorithmSpec::RsassaPkcs1V15Sha256),
+        SigningScheme::RSA_PKCS1_SHA384(_) => Ok(SigningAlgorithmSpec::RsassaPkcs1V15Sha384),
+        SigningScheme::RSA_PKCS1_SHA512(_) => Ok(SigningAlgorithmSpec::RsassaPkcs1V15Sha512),
+        SigningScheme::RSA_PSS_SHA256(_) => Ok(SigningAlgorithmSpec::RsassaPssSha256),
+        SigningScheme::RSA_PSS_SHA384(_) => Ok(SigningAlgorithmSpec::RsassaPssSha384),
+        SigningScheme::RSA_PSS_SHA512(_) => Ok(SigningAlgorithmSpec::RsassaPssSha512),
+        SigningScheme::ED25519 => Err(SigstoreError::KmsError(
+            "AWS KMS doesn't support ED25519 keys".to_string(),
+        )),
+        SigningScheme::ECDSA_P521_SHA512 => Err(SigstoreError::KmsError(
+            "P-521 keys can only be used to verify signatures".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("awskms:///alias/my-key", None, "alias/my-key")]
+    #[case(
+        "awskms:///1234abcd-12ab-34cd-56ef-1234567890ab",
+        None,
+        "1234abcd-12ab-34cd-56ef-1234567890ab"
+    )]
+    #[case(
+        "awskms://localhost:4566/arn:aws:kms:us-east-2:111122223333:key/1234",
+        Some("localhost:4566"),
+        "arn:aws:kms:us-east-2:111122223333:key/1234"
+    )]
+    fn parse_key_ref(#[case] key_ref: &str, #[case] endpoint: Option<&str>, #[case] key_id: &str) {
+        let parsed = AwsKmsKeyRef::parse(key_ref).unwrap();
+        assert_eq!(parsed.endpoint.as_deref(), endpoint);
+        assert_eq!(parsed.key_id, key_id);
+    }
+
+    #[rstest]
+    #[case("awskms://")]
+    #[case("awskms:///")]
+    #[case("gcpkms:///alias/my-key")]
+    fn reject_invalid_key_ref(#[case] key_ref: &str) {
+        assert!(matches!(
+            AwsKmsKeyRef::parse(key_ref),
+            Err(SigstoreError::KmsError(_))
+        ));
+    }
+
+    #[test]
+    fn region_of_arn() {
+        let arn =
+            AwsKmsKeyRef::parse("awskms:///arn:aws:kms:us-east-2:111122223333:key/1234").unwrap();
+        assert_eq!(arn.region(), Some("us-east-2"));
+        let alias = AwsKmsKeyRef::parse("awskms:///alias/my-key").unwrap();
+        assert_eq!(alias.region(), None);
+    }
+}
@@ -0,0 +1,407 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signer backed by a key of Azure Key Vault.
+//!
+//! The keys are referenced like cosign does, with the
+//! `azurekms://[VAULT_NAME][VAULT_URI]/[KEY]` format:
+//!
+//! * `azurekms://my-vault.vault.azure.net/my-key`
+//! * `azurekms://my-vault/my-key`, for a vault of the Azure public cloud
+//! * `azurekms://my-vault.vault.azure.net/my-key/[VERSION]`, to pin a
+//!   version of the key
+//!
+//! The requests are authenticated with the service principal whose
+//! credentials are set by the `AZURE_TENANT_ID`, `AZURE_CLIENT_ID` and
+//! `AZURE_CLIENT_SECRET` environment variables. An access token obtained
+//! by other means, like `az account get-access-token --resource https://vault.azure.net`,
+//! can be given to [`AzureKmsSigner::with_access_token`] instead.
+//!
+//! ```rust,no_run
+//! use sigstore::crypto::signing_key::kms::azure::AzureKmsSigner;
+//! use sigstore::crypto::signing_key::kms::KmsSigner;
+//!
+//! # async fn doc() -> sigstore::errors::Result<()> {
+//! let signer = AzureKmsSigner::new("azurekms://my-vault.vault.azure.net/my-key").await?;
+//! let signature = signer.sign(b"hello").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL_ENGINE, Engine as _};
+use pkcs8::EncodePublicKey;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{ecdsa_signature_to_der, hash_algorithm, KmsSigner};
+use crate::crypto::SigningScheme;
+use crate::errors::{Result, SigstoreError};
+
+/// Scheme of the references to Azure Key Vault keys
+pub const AZURE_KMS_SCHEME: &str = "azurekms://";
+
+/// The version of the Key Vault REST API
+const API_VERSION: &str = "7.4";
+/// The suffix of the vaults of the Azure public cloud
+const VAULT_DOMAIN: &str = "vault.azure.net";
+/// The OAuth scope granting access to Key Vault
+const VAULT_SCOPE: &str = "https://vault.azure.net/.default";
+
+/// A reference to an Azure Key Vault key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AzureKmsKeyRef {
+    /// The URL of the vault, like `https://my-vault.vault.azure.net`
+    pub vault_url: String,
+    /// The name of the key
+    pub key_name: String,
+    /// The version of the key, the current one when missing
+    pub version: Option<String>,
+}
+
+impl AzureKmsKeyRef {
+    /// Parse a `azurekms://[VAULT_NAME][VAULT_URI]/[KEY][/VERSION]` reference
+    pub fn parse(key_ref: &str) -> Result<Self> {
+        let invalid = || {
+            SigstoreError::KmsError(format!(
+                "invalid Azure Key Vault key reference {key_ref}, expected {AZURE_KMS_SCHEME}[VAULT_NAME][VAULT_URI]/[KEY]"
+            ))
+        };
+        let parts = key_ref
+            .strip_prefix(AZURE_KMS_SCHEME)
+            .ok_or_else(invalid)?
+            .split('/')
+            .collect::<Vec<_>>();
+        if parts.iter().any(|part| part.is_empty()) {
+            return Err(invalid());
+        }
+        let (vault, key_name, version) = match parts.as_slice() {
+            [vault, key_name] => (*vault, *key_name, None),
+            [vault, key_name, version] => (*vault, *key_name, Some(version.to_string())),
+            _ => return Err(invalid()),
+        };
+        let vault_url = if vault.contains('.') {
+            format!("https://{vault}")
+        } else {
+            format!("https://{vault}.{VAULT_DOMAIN}")
+        };
+
+        Ok(AzureKmsKeyRef {
+            vault_url,
+            key_name: key_name.to_string(),
+            version,
+        })
+    }
+}
+
+/// Signer backed by an Azure Key Vault key, whose allowed operations
+/// include `sign`
+#[derive(Debug, Clone)]
+pub struct AzureKmsSigner {
+    client: reqwest::Client,
+    access_token: String,
+    /// The identifier of the version of the key, which is its URL
+    key_id: String,
+    signing_scheme: SigningScheme,
+    public_key: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct KeyBundle {
+    key: JsonWebKey,
+}
+
+/// The public part of a key, as a JSON web key (RFC 7517)
+#[derive(Deserialize)]
+struct JsonWebKey {
+    kid: String,
+    kty: String,
+    crv: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SignResponse {
+    value: String,
+}
+
+impl AzureKmsSigner {
+    /// Create a signer for the key referenced by `key_ref`, authenticated
+    /// with the service principal of the environment.
+    ///
+    /// The public key of the key is fetched once, to find out its signing
+    /// scheme.
+    pub async fn new(key_ref: &str) -> Result<Self> {
+        let env = |name: &str| {
+            std::env::var(name).map_err(|_| SigstoreError::KmsError(format!("{name} is not set")))
+        };
+        let tenant_id = env("AZURE_TENANT_ID")?;
+        let client_id = env("AZURE_CLIENT_ID")?;
+        let client_secret = env("AZURE_CLIENT_SECRET")?;
+
+        let response: TokenResponse = reqwest::Client::new()
+            .post(format!(
+                "https://login.microsoftonline.com/{tenant_id}/oauth2/v2.0/token"
+            ))
+            .form(&[
+                ("grant_type", "client_credentials"),
+                ("client_id", client_id.as_str()),
+                ("client_secret", client_secret.as_str()),
+                ("scope", VAULT_SCOPE),
+            ])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| SigstoreError::KmsError(format!("cannot get an Azure token: {e}")))?
+            .json()
+            .await
+            .map_err(|e| SigstoreError::KmsError(format!("invalid Azure token response: {e}")))?;
+        Self::with_access_token(key_ref, &response.access_token).await
+    }
+
+    /// Create a signer for the key referenced by `key_ref`, authenticated
+    /// with the given OAuth access token
+    pub async fn with_access_token(key_ref: &str, access_token: &str) -> Result<Self> {
+        let key_ref = AzureKmsKeyRef::parse(key_ref)?;
+        let client = reqwest::Client::new();
+
+        let mut url = format!("{}/keys/{}", key_ref.vault_url, key_ref.key_name);
+        if let Some(version) = &key_ref.version {
+            url = format!("{url}/{version}");
+        }
+        let bundle: KeyBundle = client
+            .get(url)
+            .query(&[("api-version", API_VERSION)])
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| SigstoreError::KmsError(format!("cannot fetch the public key: {e}")))?
+            .json()
+            .await
+            .map_err(|e| SigstoreError::KmsError(format!("invalid Key Vault response: {e}")))?;
+        let (signing_scheme, public_key) = bundle.key.to_public_key()?;
+
+        Ok(AzureKmsSigner {
+            client,
+            access_token: access_token.to_string(),
+            key_id: bundle.key.kid,
+            signing_scheme,
+            public_key,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl KmsSigner for AzureKmsSigner {
+    fn signing_scheme(&self) -> SigningScheme {
+        self.signing_scheme
+    }
+
+    fn public_key_to_der(&self) -> Result<Vec<u8>> {
+        Ok(self.public_key.clone())
+    }
+
+    async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let digest = hash_algorithm(&self.signing_scheme)?.digest(msg);
+        let body = json!({
+            "alg": signing_algorithm(&self.signing_scheme)?,
+            "value": BASE64_URL_ENGINE.encode(digest),
+        });
+        let response: SignResponse = self
+            .client
+            .post(format!("{}/sign", self.key_id))
+            .query(&[("api-version", API_VERSION)])
+            .bearer_auth(&self.access_token)
+            .json(&body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| SigstoreError::KmsError(format!("cannot sign: {e}")))?
+            .json()
+            .await
+            .map_err(|e| SigstoreError::KmsError(format!("invalid Key Vault response: {e}")))?;
+        // ECDSA signatures are returned as the concatenation of r and s
+        let signature = BASE64_URL_ENGINE.decode(response.value)?;
+        ecdsa_signature_to_der(&self.signing_scheme, &signature)
+    }
+}
+
+impl JsonWebKey {
+    /// The signing scheme of the key, and its asn.1 PKIX public key. Like
+    /// cosign, RSA keys produce PKCS#1 v1.5 signatures over a SHA-256
+    /// digest.
+    fn to_public_key(&self) -> Result<(SigningScheme, Vec<u8>)> {
+        let field = |value: &Option<String>, name: &str| -> Result<Vec<u8>> {
+            let value = value.as_ref().ok_or_else(|| {
+                SigstoreError::KmsError(format!("the {} key has no {name}", self.kty))
+            })?;
+            Ok(BASE64_URL_ENGINE.decode(value)?)
+        };
+        let spki_error = |e: pkcs8::spki::Error| SigstoreError::PKCS8SpkiError(e.to_string());
+
+        match (self.kty.as_str(), self.crv.as_deref()) {
+            ("EC" | "EC-HSM", Some(crv @ ("P-256" | "P-384"))) => {
+                let mut point = vec![0x04];
+                point.extend(field(&self.x, "x")?);
+                point.extend(field(&self.y, "y")?);
+                let (scheme, der) = if crv == "P-256" {
+                    let key = p256::PublicKey::from_sec1_bytes(&point)?;
+                    (
+                        SigningScheme::ECDSA_P256_SHA256_ASN1,
+                        key.to_public_key_der().map_err(spki_error)?,
+                    )
+                } else {
+                    let key = p384::PublicKey::from_sec1_bytes(&point)?;
+                    (
+                        SigningScheme::ECDSA_P384_SHA384_ASN1,
+                        key.to_public_key_der().map_err(spki_error)?,
+                    )
+                };
+                Ok((scheme, der.to_vec()))
+            }
+            ("RSA" | "RSA-HSM", _) => {
+                let n = rsa::BigUint::from_bytes_be(&field(&self.n, "n")?);
+                let e = rsa::BigUint::from_bytes_be(&field(&self.e, "e")?);
+                let bits = n.bits();
+                let key = rsa::RsaPublicKey::new(n, e)?;
+                Ok((
+                    SigningScheme::RSA_PKCS1_SHA256(bits),
+                    key.to_public_key_der().map_err(spki_error)?.to_vec(),
+                ))
+            }
+            (kty, crv) => Err(SigstoreError::KmsError(format!(
+                "unsupported key type {kty} {}",
+                crv.unwrap_or_default()
+            ))),
+        }
+    }
+}
+
+/// The JSON web algorithm used to sign with the given scheme
+fn signing_algorithm(signing_scheme: &SigningScheme) -> Result<&'static str> {
+    match signing_scheme {
+        SigningScheme::ECDSA_P256_SHA256_ASN1 => Ok("ES256"),
+        SigningScheme::ECDSA_P384_SHA384_ASN1 => Ok("ES384"),
+        SigningScheme::RSA_PKCS1_SHA256(_) => Ok("RS256"),
+        SigningScheme::RSA_PKCS1_SHA384(_) => Ok("RS384"),
+        SigningScheme::RSA_PKCS1_SHA512(_) => Ok("RS512"),
+        SigningScheme::RSA_PSS_SHA256(_) => Ok("PS256"),
+        SigningScheme::RSA_PSS_SHA384(_) => Ok("PS384"),
+        SigningScheme::RSA_PSS_SHA512(_) => Ok("PS512"),
+        SigningScheme::ED25519 => Err(SigstoreError::KmsError(
+            "Azure Key Vault doesn't support ED25519 keys".to_string(),
+        )),
+        SigningScheme::ECDSA_P521_SHA512 => Err(SigstoreError::KmsError(
+            "P-521 keys can only be used to verify signatures".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Signature;
+    use p256::elliptic_curve::sec1::ToEncodedPoint;
+    use rstest::rstest;
+    use signature::Signer;
+
+    #[rstest]
+    #[case(
+        "azurekms://my-vault.vault.azure.net/my-key",
+        "https://my-vault.vault.azure.net",
+        None
+    )]
+    #[case("azurekms://my-vault/my-key", "https://my-vault.vault.azure.net", None)]
+    #[case(
+        "azurekms://my-vault.vault.azure.cn/my-key/0123abcd",
+        "https://my-vault.vault.azure.cn",
+        Some("0123abcd")
+    )]
+    fn parse_key_ref(
+        #[case] key_ref: &str,
+        #[case] vault_url: &str,
+        #[case] version: Option<&str>,
+    ) {
+        let parsed = AzureKmsKeyRef::parse(key_ref).unwrap();
+        assert_eq!(parsed.vault_url, vault_url);
+        assert_eq!(parsed.key_name, "my-key");
+        assert_eq!(parsed.version.as_deref(), version);
+    }
+
+    #[rstest]
+    #[case("azurekms://my-vault")]
+    #[case("azurekms://my-vault/")]
+    #[case("azurekms:///my-key")]
+    #[case("azurekms://my-vault/my-key/1/2")]
+    #[case("awskms:///alias/my-key")]
+    fn reject_invalid_key_ref(#[case] key_ref: &str) {
+        assert!(matches!(
+            AzureKmsKeyRef::parse(key_ref),
+            Err(SigstoreError::KmsError(_))
+        ));
+    }
+
+    #[test]
+    fn ecdsa_signature_from_key_vault() {
+        let msg = b"hello";
+        let signing_key = p256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        let point = signing_key.verifying_key().to_encoded_point(false);
+        let jwk = JsonWebKey {
+            kid: "https://my-vault.vault.azure.net/keys/my-key/1".to_string(),
+            kty: "EC-HSM".to_string(),
+            crv: Some("P-256".to_string()),
+            x: point.x().map(|x| BASE64_URL_ENGINE.encode(x)),
+            y: point.y().map(|y| BASE64_URL_ENGINE.encode(y)),
+            n: None,
+            e: None,
+        };
+        let (scheme, der) = jwk.to_public_key().unwrap();
+        assert_eq!(scheme, SigningScheme::ECDSA_P256_SHA256_ASN1);
+
+        // Key Vault signatures are the raw r || s pair
+        let raw: p256::ecdsa::Signature = signing_key.sign(msg);
+        let signature = ecdsa_signature_to_der(&scheme, &raw.to_bytes()).unwrap();
+        crate::crypto::CosignVerificationKey::from_der(&der, &scheme)
+            .unwrap()
+            .verify_signature(Signature::Raw(&signature), msg)
+            .expect("the converted signature should verify");
+    }
+
+    #[test]
+    fn reject_unsupported_key_type() {
+        let jwk = JsonWebKey {
+            kid: "https://my-vault.vault.azure.net/keys/my-key/1".to_string(),
+            kty: "oct-HSM".to_string(),
+            crv: None,
+            x: None,
+            y: None,
+            n: None,
+            e: None,
+        };
+        assert!(matches!(
+            jwk.to_public_key(),
+            Err(SigstoreError::KmsError(_))
+        ));
+    }
+}
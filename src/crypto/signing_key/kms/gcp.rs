@@ -0,0 +1,343 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signer backed by an asymmetric key of Google Cloud KMS.
+//!
+//! The keys are referenced like cosign does:
+//!
+//! ```text
+//! gcpkms://projects/[PROJECT]/locations/[LOCATION]/keyRings/[KEYRING]/cryptoKeys/[KEY]
+//! gcpkms://projects/[PROJECT]/locations/[LOCATION]/keyRings/[KEYRING]/cryptoKeys/[KEY]/cryptoKeyVersions/[VERSION]
+//! ```
+//!
+//! Without version, the most recent enabled version of the key is used.
+//!
+//! The requests are authenticated with the application default
+//! credentials, like the ones of `gcloud auth application-default login`
+//! or of the service account of the workload. An access token obtained
+//! by other means can be given to [`GcpKmsSigner::with_access_token`]
+//! instead.
+//!
+//! ```rust,no_run
+//! use sigstore::crypto::signing_key::kms::gcp::GcpKmsSigner;
+//! use sigstore::crypto::signing_key::kms::KmsSigner;
+//!
+//! # async fn doc() -> sigstore::errors::Result<()> {
+//! let signer = GcpKmsSigner::new(
+//!     "gcpkms://projects/my-project/locations/global/keyRings/my-ring/cryptoKeys/my-key",
+//! )
+//! .await?;
+//! let signature = signer.sign(b"hello").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{hash_algorithm, KmsSigner};
+use crate::crypto::hash::HashAlgorithm;
+use crate::crypto::SigningScheme;
+use crate::errors::{Result, SigstoreError};
+
+/// Scheme of the references to Google Cloud KMS keys
+pub const GCP_KMS_SCHEME: &str = "gcpkms://";
+
+/// The endpoint of the Cloud KMS API
+const CLOUD_KMS_URL: &str = "https://cloudkms.googleapis.com/v1";
+/// The OAuth scope granting access to Cloud KMS
+const CLOUD_KMS_SCOPE: &str = "https://www.googleapis.com/auth/cloudkms";
+
+/// A reference to a Google Cloud KMS key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GcpKmsKeyRef {
+    /// The resource name of the key,
+    /// `projects/[PROJECT]/locations/[LOCATION]/keyRings/[KEYRING]/cryptoKeys/[KEY]`
+    pub key_name: String,
+    /// The version of the key, the most recent enabled one when missing
+    pub version: Option<String>,
+}
+
+impl GcpKmsKeyRef {
+    /// Parse a `gcpkms://projects/[PROJECT]/locations/[LOCATION]/keyRings/[KEYRING]/cryptoKeys/[KEY][/cryptoKeyVersions/[VERSION]]`
+    /// reference
+    pub fn parse(key_ref: &str) -> Result<Self> {
+        let invalid = || {
+            SigstoreError::KmsError(format!(
+                "invalid GCP KMS key reference {key_ref}, expected {GCP_KMS_SCHEME}projects/[PROJECT]/locations/[LOCATION]/keyRings/[KEYRING]/cryptoKeys/[KEY][/cryptoKeyVersions/[VERSION]]"
+            ))
+        };
+        let parts = key_ref
+            .strip_prefix(GCP_KMS_SCHEME)
+            .ok_or_else(invalid)?
+            .split('/')
+            .collect::<Vec<_>>();
+        let labels_match = |labels: &[&str]| {
+            parts
+                .iter()
+                .step_by(2)
+                .zip(labels)
+                .all(|(part, label)| part == label)
+                && parts
+                    .iter()
+                    .skip(1)
+                    .step_by(2)
+                    .all(|value| !value.is_empty())
+        };
+        const KEY_LABELS: [&str; 4] = ["projects", "locations", "keyRings", "cryptoKeys"];
+
+        match parts.len() {
+            8 if labels_match(&KEY_LABELS) => Ok(GcpKmsKeyRef {
+                key_name: parts.join("/"),
+                version: None,
+            }),
+            10 if labels_match(&[&KEY_LABELS[..], &["cryptoKeyVersions"][..]].concat()) => {
+                Ok(GcpKmsKeyRef {
+                    key_name: parts[..8].join("/"),
+                    version: Some(parts[9].to_string()),
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Signer backed by an asymmetric Cloud KMS key, whose purpose is
+/// `ASYMMETRIC_SIGN`
+#[derive(Debug, Clone)]
+pub struct GcpKmsSigner {
+    client: reqwest::Client,
+    access_token: String,
+    /// The resource name of the version of the key
+    version_name: String,
+    signing_scheme: SigningScheme,
+    public_key: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct PublicKeyResponse {
+    pem: String,
+    algorithm: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListVersionsResponse {
+    #[serde(default)]
+    crypto_key_versions: Vec<CryptoKeyVersion>,
+}
+
+#[derive(Deserialize)]
+struct CryptoKeyVersion {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct AsymmetricSignResponse {
+    signature: String,
+}
+
+impl GcpKmsSigner {
+    /// Create a signer for the key referenced by `key_ref`, authenticated
+    /// with the application default credentials.
+    ///
+    /// The public key of the key is fetched once, to find out its signing
+    /// scheme.
+    pub async fn new(key_ref: &str) -> Result<Self> {
+        let token = gcp_auth::AuthenticationManager::new()
+            .await
+            .map_err(|e| SigstoreError::KmsError(format!("cannot find GCP credentials: {e}")))?
+            .get_token(&[CLOUD_KMS_SCOPE])
+            .await
+            .map_err(|e| SigstoreError::KmsError(format!("cannot get a GCP token: {e}")))?;
+        Self::with_access_token(key_ref, token.as_str()).await
+    }
+
+    /// Create a signer for the key referenced by `key_ref`, authenticated
+    /// with the given OAuth access token
+    pub async fn with_access_token(key_ref: &str, access_token: &str) -> Result<Self> {
+        let key_ref = GcpKmsKeyRef::parse(key_ref)?;
+        let mut signer = GcpKmsSigner {
+            client: reqwest::Client::new(),
+            access_token: access_token.to_string(),
+            version_name: String::new(),
+            signing_scheme: SigningScheme::default(),
+            public_key: Vec::new(),
+        };
+
+        signer.version_name = match &key_ref.version {
+            Some(version) => format!("{}/cryptoKeyVersions/{version}", key_ref.key_name),
+            None => signer.latest_version(&key_ref.key_name).await?,
+        };
+        let response: PublicKeyResponse = signer
+            .get(&format!(
+                "{CLOUD_KMS_URL}/{}/publicKey",
+                signer.version_name
+            ))
+            .await?;
+        signer.signing_scheme = signing_scheme(&response.algorithm)?;
+        signer.public_key = pem::parse(&response.pem)?.contents;
+        Ok(signer)
+    }
+
+    /// The name of the most recent enabled version of the key
+    async fn latest_version(&self, key_name: &str) -> Result<String> {
+        let response: ListVersionsResponse = self
+            .get(&format!(
+                "{CLOUD_KMS_URL}/{key_name}/cryptoKeyVersions?filter=state%3DENABLED"
+            ))
+            .await?;
+        response
+            .crypto_key_versions
+            .into_iter()
+            .max_by_key(|version| {
+                version
+                    .name
+                    .rsplit('/')
+                    .next()
+                    .and_then(|v| v.parse::<u64>().ok())
+            })
+            .map(|version| version.name)
+            .ok_or_else(|| {
+                SigstoreError::KmsError(format!("the key {key_name} has no enabled version"))
+            })
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T> {
+        self.client
+            .get(url)
+            .bearer_auth(&self.access_token)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| SigstoreError::KmsError(format!("GCP KMS request failed: {e}")))?
+            .json()
+            .await
+            .map_err(|e| SigstoreError::KmsError(format!("invalid GCP KMS response: {e}")))
+    }
+}
+
+#[async_trait(?Send)]
+impl KmsSigner for GcpKmsSigner {
+    fn signing_scheme(&self) -> SigningScheme {
+        self.signing_scheme
+    }
+
+    fn public_key_to_der(&self) -> Result<Vec<u8>> {
+        Ok(self.public_key.clone())
+    }
+
+    async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let algorithm = hash_algorithm(&self.signing_scheme)?;
+        let digest_name = match algorithm {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha384 => "sha384",
+            HashAlgorithm::Sha512 => "sha512",
+        };
+        let body = json!({
+            "digest": { digest_name: BASE64_STD_ENGINE.encode(algorithm.digest(msg)) }
+        });
+        let response: AsymmetricSignResponse = self
+            .client
+            .post(format!(
+                "{CLOUD_KMS_URL}/{}:asymmetricSign",
+                self.version_name
+            ))
+            .bearer_auth(&self.access_token)
+            .json(&body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| SigstoreError::KmsError(format!("cannot sign: {e}")))?
+            .json()
+            .await
+            .map_err(|e| SigstoreError::KmsError(format!("invalid GCP KMS response: {e}")))?;
+        Ok(BASE64_STD_ENGINE.decode(response.signature)?)
+    }
+}
+
+/// The signing scheme of a `CryptoKeyVersionAlgorithm`, like
+/// `EC_SIGN_P256_SHA256` or `RSA_SIGN_PKCS1_2048_SHA256`
+fn signing_scheme(algorithm: &str) -> Result<SigningScheme> {
+    let unsupported = || SigstoreError::KmsError(format!("unsupported key algorithm {algorithm}"));
+    match algorithm {
+        "EC_SIGN_P256_SHA256" => return Ok(SigningScheme::ECDSA_P256_SHA256_ASN1),
+        "EC_SIGN_P384_SHA384" => return Ok(SigningScheme::ECDSA_P384_SHA384_ASN1),
+        _ => {}
+    }
+
+    let parts = algorithm.split('_').collect::<Vec<_>>();
+    let (padding, bits, digest) = match parts.as_slice() {
+        ["RSA", "SIGN", padding, bits, digest] => (*padding, *bits, *digest),
+        _ => return Err(unsupported()),
+    };
+    let bits = bits.parse::<usize>().map_err(|_| unsupported())?;
+    match (padding, digest) {
+        ("PKCS1", "SHA256") => Ok(SigningScheme::RSA_PKCS1_SHA256(bits)),
+        ("PKCS1", "SHA384") => Ok(SigningScheme::RSA_PKCS1_SHA384(bits)),
+        ("PKCS1", "SHA512") => Ok(SigningScheme::RSA_PKCS1_SHA512(bits)),
+        ("PSS", "SHA256") => Ok(SigningScheme::RSA_PSS_SHA256(bits)),
+        ("PSS", "SHA384") => Ok(SigningScheme::RSA_PSS_SHA384(bits)),
+        ("PSS", "SHA512") => Ok(SigningScheme::RSA_PSS_SHA512(bits)),
+        _ => Err(unsupported()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const KEY: &str = "projects/p/locations/global/keyRings/ring/cryptoKeys/key";
+
+    #[test]
+    fn parse_key_ref() {
+        let key_ref = GcpKmsKeyRef::parse(&format!("gcpkms://{KEY}")).unwrap();
+        assert_eq!(key_ref.key_name, KEY);
+        assert_eq!(key_ref.version, None);
+
+        let key_ref = GcpKmsKeyRef::parse(&format!("gcpkms://{KEY}/cryptoKeyVersions/3")).unwrap();
+        assert_eq!(key_ref.key_name, KEY);
+        assert_eq!(key_ref.version.as_deref(), Some("3"));
+    }
+
+    #[rstest]
+    #[case("awskms:///alias/key")]
+    #[case("gcpkms://projects/p/locations/global/keyRings/ring")]
+    #[case("gcpkms://projects/p/locations/global/keyRings/ring/cryptoKeys/")]
+    #[case("gcpkms://projects/p/locations/global/keyRings/ring/keys/key")]
+    #[case("gcpkms://projects/p/locations/global/keyRings/ring/cryptoKeys/key/versions/1")]
+    fn reject_invalid_key_ref(#[case] key_ref: &str) {
+        assert!(GcpKmsKeyRef::parse(key_ref).is_err());
+    }
+
+    #[rstest]
+    #[case("EC_SIGN_P256_SHA256", SigningScheme::ECDSA_P256_SHA256_ASN1)]
+    #[case("EC_SIGN_P384_SHA384", SigningScheme::ECDSA_P384_SHA384_ASN1)]
+    #[case("RSA_SIGN_PKCS1_2048_SHA256", SigningScheme::RSA_PKCS1_SHA256(2048))]
+    #[case("RSA_SIGN_PKCS1_4096_SHA512", SigningScheme::RSA_PKCS1_SHA512(4096))]
+    #[case("RSA_SIGN_PSS_3072_SHA256", SigningScheme::RSA_PSS_SHA256(3072))]
+    fn signing_scheme_of_algorithm(#[case] algorithm: &str, #[case] expected: SigningScheme) {
+        assert_eq!(signing_scheme(algorithm).unwrap(), expected);
+    }
+
+    #[test]
+    fn unsupported_algorithm() {
+        assert!(signing_scheme("RSA_SIGN_RAW_PKCS1_2048").is_err());
+        assert!(signing_scheme("GOOGLE_SYMMETRIC_ENCRYPTION").is_err());
+    }
+}
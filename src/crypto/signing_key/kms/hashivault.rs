@@ -0,0 +1,479 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signer backed by a key of the transit secrets engine of HashiCorp Vault.
+//!
+//! The keys are referenced like cosign does, with the `hashivault://[KEY]`
+//! format. The Vault server is configured by the same environment variables
+//! as the ones of cosign and of the Vault CLI:
+//!
+//! * `VAULT_ADDR`: the address of the server, like `https://vault.example.com:8200`
+//! * `VAULT_TOKEN`: the token used to authenticate
+//! * `VAULT_ROLE_ID` and `VAULT_SECRET_ID`: the AppRole credentials used
+//!   to log in, when there is no token
+//! * `VAULT_NAMESPACE`: the namespace of the engine, on Vault Enterprise
+//! * `TRANSIT_SECRET_ENGINE_PATH`: the path where the transit engine is
+//!   mounted, `transit` by default
+//!
+//! ```rust,no_run
+//! use sigstore::crypto::signing_key::kms::hashivault::HashiVaultSigner;
+//! use sigstore::crypto::signing_key::kms::KmsSigner;
+//!
+//! # async fn doc() -> sigstore::errors::Result<()> {
+//! let signer = HashiVaultSigner::new("hashivault://my-signing-key").await?;
+//! let signature = signer.sign(b"hello").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use ed25519_dalek::pkcs8::EncodePublicKey;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{hash_algorithm, KmsSigner};
+use crate::crypto::hash::HashAlgorithm;
+use crate::crypto::SigningScheme;
+use crate::errors::{Result, SigstoreError};
+
+/// Scheme of the references to keys of HashiCorp Vault
+pub const HASHIVAULT_SCHEME: &str = "hashivault://";
+
+/// The default mount path of the transit secrets engine
+const DEFAULT_TRANSIT_PATH: &str = "transit";
+
+/// A reference to a key of the transit secrets engine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashiVaultKeyRef {
+    /// The name of the key
+    pub key_name: String,
+}
+
+impl HashiVaultKeyRef {
+    /// Parse a `hashivault://[KEY]` reference
+    pub fn parse(key_ref: &str) -> Result<Self> {
+        match key_ref.strip_prefix(HASHIVAULT_SCHEME) {
+            Some(key_name) if !key_name.is_empty() && !key_name.contains('/') => {
+                Ok(HashiVaultKeyRef {
+                    key_name: key_name.to_string(),
+                })
+            }
+            _ => Err(SigstoreError::KmsError(format!(
+                "invalid HashiCorp Vault key reference {key_ref}, expected {HASHIVAULT_SCHEME}[KEY]"
+            ))),
+        }
+    }
+}
+
+/// How to authenticate to Vault
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HashiVaultAuth {
+    /// A token obtained beforehand
+    Token(String),
+    /// The credentials of an AppRole, exchanged for a token at login
+    AppRole {
+        /// The ID of the role
+        role_id: String,
+        /// The secret ID issued for the role
+        secret_id: String,
+    },
+}
+
+/// The location of the transit secrets engine, and the credentials used to
+/// access it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashiVaultConfig {
+    /// The address of the server, like `https://vault.example.com:8200`
+    pub address: String,
+    /// How to authenticate to the server
+    pub auth: HashiVaultAuth,
+    /// The path where the transit engine is mounted
+    pub transit_path: String,
+    /// The namespace of the engine, on Vault Enterprise
+    pub namespace: Option<String>,
+}
+
+impl HashiVaultConfig {
+    /// Create a configuration for the transit engine mounted at its default
+    /// path on the given server
+    pub fn new(address: &str, auth: HashiVaultAuth) -> Self {
+        HashiVaultConfig {
+            address: address.trim_end_matches('/').to_string(),
+            auth,
+            transit_path: DEFAULT_TRANSIT_PATH.to_string(),
+            namespace: None,
+        }
+    }
+
+    /// Read the configuration from the environment variables used by the
+    /// Vault CLI
+    pub fn from_env() -> Result<Self> {
+        let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let address = env("VAULT_ADDR")
+            .ok_or_else(|| SigstoreError::KmsError("VAULT_ADDR is not set".to_string()))?;
+        let auth = match (
+            env("VAULT_TOKEN"),
+            env("VAULT_ROLE_ID"),
+            env("VAULT_SECRET_ID"),
+        ) {
+            (Some(token), _, _) => HashiVaultAuth::Token(token),
+            (None, Some(role_id), Some(secret_id)) => {
+                HashiVaultAuth::AppRole { role_id, secret_id }
+            }
+            _ => {
+                return Err(SigstoreError::KmsError(
+                    "either VAULT_TOKEN, or VAULT_ROLE_ID and VAULT_SECRET_ID must be set"
+                        .to_string(),
+                ))
+            }
+        };
+
+        let mut config = Self::new(&address, auth);
+        if let Some(transit_path) = env("TRANSIT_SECRET_ENGINE_PATH") {
+            config = config.with_transit_path(&transit_path);
+        }
+        config.namespace = env("VAULT_NAMESPACE");
+        Ok(config)
+    }
+
+    /// Use the transit engine mounted at the given path
+    pub fn with_transit_path(mut self, transit_path: &str) -> Self {
+        self.transit_path = transit_path.trim_matches('/').to_string();
+        self
+    }
+
+    /// Use the engine of the given namespace
+    pub fn with_namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+}
+
+/// Signer backed by an asymmetric key of the transit secrets engine
+#[derive(Debug, Clone)]
+pub struct HashiVaultSigner {
+    client: reqwest::Client,
+    config: HashiVaultConfig,
+    token: String,
+    key_name: String,
+    key_version: u64,
+    signing_scheme: SigningScheme,
+    public_key: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct VaultResponse<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct LoginResponse {
+    auth: LoginAuth,
+}
+
+#[derive(Deserialize)]
+struct LoginAuth {
+    client_token: String,
+}
+
+#[derive(Deserialize)]
+struct KeyData {
+    #[serde(rename = "type")]
+    key_type: String,
+    latest_version: u64,
+    keys: HashMap<String, KeyVersion>,
+}
+
+#[derive(Deserialize)]
+struct KeyVersion {
+    public_key: String,
+}
+
+#[derive(Deserialize)]
+struct SignData {
+    signature: String,
+}
+
+impl HashiVaultSigner {
+    /// Create a signer for the key referenced by `key_ref`, using the Vault
+    /// configuration of the environment.
+    ///
+    /// The public key of the latest version of the key is fetched once, to
+    /// find out its signing scheme. The signatures are produced by this
+    /// version, even if the key is rotated afterwards.
+    pub async fn new(key_ref: &str) -> Result<Self> {
+        Self::from_config(HashiVaultConfig::from_env()?, key_ref).await
+    }
+
+    /// Create a signer for the key referenced by `key_ref`, using the given
+    /// Vault configuration
+    pub async fn from_config(config: HashiVaultConfig, key_ref: &str) -> Result<Self> {
+        let key_ref = HashiVaultKeyRef::parse(key_ref)?;
+        let client = reqwest::Client::new();
+
+        let token = match &config.auth {
+            HashiVaultAuth::Token(token) => token.clone(),
+            HashiVaultAuth::AppRole { role_id, secret_id } => {
+                let response: LoginResponse = send(
+                    with_namespace(
+                        client.post(format!("{}/v1/auth/approle/login", config.address)),
+                        &config,
+                    )
+                    .json(&json!({ "role_id": role_id, "secret_id": secret_id })),
+                    "cannot log in with the AppRole",
+                )
+                .await?;
+                response.auth.client_token
+            }
+        };
+
+        let url = format!(
+            "{}/v1/{}/keys/{}",
+            config.address, config.transit_path, key_ref.key_name
+        );
+        let response: VaultResponse<KeyData> = send(
+            with_namespace(client.get(url), &config).header("X-Vault-Token", &token),
+            "cannot fetch the public key",
+        )
+        .await?;
+        let key = response.data;
+        let signing_scheme = signing_scheme(&key.key_type)?;
+        let public_key = key
+            .keys
+            .get(&key.latest_version.to_string())
+            .ok_or_else(|| {
+                SigstoreError::KmsError(format!(
+                    "the version {} of the key is missing",
+                    key.latest_version
+                ))
+            })
+            .and_then(|version| public_key_to_der(&signing_scheme, &version.public_key))?;
+
+        Ok(HashiVaultSigner {
+            client,
+            config,
+            token,
+            key_name: key_ref.key_name,
+            key_version: key.latest_version,
+            signing_scheme,
+            public_key,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl KmsSigner for HashiVaultSigner {
+    fn signing_scheme(&self) -> SigningScheme {
+        self.signing_scheme
+    }
+
+    fn public_key_to_der(&self) -> Result<Vec<u8>> {
+        Ok(self.public_key.clone())
+    }
+
+    async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let mut body = json!({
+            "key_version": self.key_version,
+            "marshaling_algorithm": "asn1",
+        });
+        let url = if self.signing_scheme == SigningScheme::ED25519 {
+            // ED25519 signatures are computed over the whole message
+            body["input"] = BASE64_STD_ENGINE.encode(msg).into();
+            format!(
+                "{}/v1/{}/sign/{}",
+                self.config.address, self.config.transit_path, self.key_name
+            )
+        } else {
+            let algorithm = hash_algorithm(&self.signing_scheme)?;
+            body["input"] = BASE64_STD_ENGINE.encode(algorithm.digest(msg)).into();
+            body["prehashed"] = true.into();
+            body["signature_algorithm"] = match self.signing_scheme {
+                SigningScheme::RSA_PSS_SHA256(_)
+                | SigningScheme::RSA_PSS_SHA384(_)
+                | SigningScheme::RSA_PSS_SHA512(_) => "pss",
+                _ => "pkcs1v15",
+            }
+            .into();
+            let hash_name = match algorithm {
+                HashAlgorithm::Sha256 => "sha2-256",
+                HashAlgorithm::Sha384 => "sha2-384",
+                HashAlgorithm::Sha512 => "sha2-512",
+            };
+            format!(
+                "{}/v1/{}/sign/{}/{hash_name}",
+                self.config.address, self.config.transit_path, self.key_name
+            )
+        };
+
+        let response: VaultResponse<SignData> = send(
+            with_namespace(self.client.post(url), &self.config)
+                .header("X-Vault-Token", &self.token)
+                .json(&body),
+            "cannot sign",
+        )
+        .await?;
+        parse_signature(&response.data.signature)
+    }
+}
+
+fn with_namespace(
+    request: reqwest::RequestBuilder,
+    config: &HashiVaultConfig,
+) -> reqwest::RequestBuilder {
+    match &config.namespace {
+        Some(namespace) => request.header("X-Vault-Namespace", namespace),
+        None => request,
+    }
+}
+
+async fn send<T: serde::de::DeserializeOwned>(
+    request: reqwest::RequestBuilder,
+    context: &str,
+) -> Result<T> {
+    request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| SigstoreError::KmsError(format!("{context}: {e}")))?
+        .json()
+        .await
+        .map_err(|e| SigstoreError::KmsError(format!("invalid Vault response: {e}")))
+}
+
+/// The signing scheme of a transit key type. Like cosign, RSA keys produce
+/// PKCS#1 v1.5 signatures over a SHA-256 digest.
+fn signing_scheme(key_type: &str) -> Result<SigningScheme> {
+    match key_type {
+        "ecdsa-p256" => Ok(SigningScheme::ECDSA_P256_SHA256_ASN1),
+        "ecdsa-p384" => Ok(SigningScheme::ECDSA_P384_SHA384_ASN1),
+        "ed25519" => Ok(SigningScheme::ED25519),
+        "rsa-2048" => Ok(SigningScheme::RSA_PKCS1_SHA256(2048)),
+        "rsa-3072" => Ok(SigningScheme::RSA_PKCS1_SHA256(3072)),
+        "rsa-4096" => Ok(SigningScheme::RSA_PKCS1_SHA256(4096)),
+        other => Err(SigstoreError::KmsError(format!(
+            "unsupported key type {other}"
+        ))),
+    }
+}
+
+/// Transit returns the PEM-encoded public key, except for ED25519 keys whose
+/// raw public key is base64 encoded
+fn public_key_to_der(signing_scheme: &SigningScheme, public_key: &str) -> Result<Vec<u8>> {
+    match signing_scheme {
+        SigningScheme::ED25519 => {
+            let raw: [u8; 32] = BASE64_STD_ENGINE
+                .decode(public_key)?
+                .try_into()
+                .map_err(|_| SigstoreError::KmsError("invalid ED25519 public key".to_string()))?;
+            let key = ed25519_dalek::VerifyingKey::from_bytes(&raw)
+                .map_err(|e| SigstoreError::KmsError(format!("invalid ED25519 public key: {e}")))?;
+            Ok(key
+                .to_public_key_der()
+                .map_err(|e| SigstoreError::PKCS8SpkiError(e.to_string()))?
+                .to_vec())
+        }
+        _ => Ok(pem::parse(public_key)?.contents),
+    }
+}
+
+/// Transit signatures look like `vault:v1:<base64 signature>`
+fn parse_signature(signature: &str) -> Result<Vec<u8>> {
+    let encoded = signature
+        .strip_prefix("vault:v")
+        .and_then(|rest| rest.split_once(':'))
+        .map(|(_, encoded)| encoded)
+        .ok_or_else(|| {
+            SigstoreError::KmsError(format!("unexpected Vault signature {signature}"))
+        })?;
+    Ok(BASE64_STD_ENGINE.decode(encoded)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Signature;
+    use rstest::rstest;
+    use signature::Signer;
+
+    #[test]
+    fn parse_key_ref() {
+        let key_ref = HashiVaultKeyRef::parse("hashivault://my-key").unwrap();
+        assert_eq!(key_ref.key_name, "my-key");
+    }
+
+    #[rstest]
+    #[case("hashivault://")]
+    #[case("hashivault://transit/my-key")]
+    #[case("awskms:///my-key")]
+    fn reject_invalid_key_ref(#[case] key_ref: &str) {
+        assert!(matches!(
+            HashiVaultKeyRef::parse(key_ref),
+            Err(SigstoreError::KmsError(_))
+        ));
+    }
+
+    #[test]
+    fn config_builder() {
+        let config = HashiVaultConfig::new(
+            "https://vault.example.com:8200/",
+            HashiVaultAuth::Token("s.token".to_string()),
+        )
+        .with_transit_path("/signing/")
+        .with_namespace("team");
+        assert_eq!(config.address, "https://vault.example.com:8200");
+        assert_eq!(config.transit_path, "signing");
+        assert_eq!(config.namespace.as_deref(), Some("team"));
+    }
+
+    #[rstest]
+    #[case("ecdsa-p256", SigningScheme::ECDSA_P256_SHA256_ASN1)]
+    #[case("ed25519", SigningScheme::ED25519)]
+    #[case("rsa-3072", SigningScheme::RSA_PKCS1_SHA256(3072))]
+    fn signing_scheme_of_key_type(#[case] key_type: &str, #[case] expected: SigningScheme) {
+        assert_eq!(signing_scheme(key_type).unwrap(), expected);
+    }
+
+    #[test]
+    fn reject_symmetric_key() {
+        assert!(signing_scheme("aes256-gcm96").is_err());
+    }
+
+    #[test]
+    fn ed25519_key_and_signature() {
+        let msg = b"hello";
+        let signing_key = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+        let public_key = BASE64_STD_ENGINE.encode(signing_key.verifying_key().as_bytes());
+        let der = public_key_to_der(&SigningScheme::ED25519, &public_key).unwrap();
+
+        let signature = format!(
+            "vault:v1:{}",
+            BASE64_STD_ENGINE.encode(signing_key.sign(msg).to_bytes())
+        );
+        let signature = parse_signature(&signature).unwrap();
+        crate::crypto::CosignVerificationKey::from_der(&der, &SigningScheme::ED25519)
+            .unwrap()
+            .verify_signature(Signature::Raw(&signature), msg)
+            .expect("the signature should verify");
+    }
+
+    #[test]
+    fn reject_unexpected_signature() {
+        assert!(parse_signature("MEUCIQ==").is_err());
+    }
+}
@@ -0,0 +1,168 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signers whose private key is kept by a key management service (KMS), or
+//! by a hardware token.
+//!
+//! The private key never leaves the service: the messages are hashed
+//! locally, then the digest is sent to the KMS to be signed. Hence, unlike
+//! the ones of [`SigStoreSigner`](super::SigStoreSigner), the signing
+//! operations are asynchronous.
+//!
+//! Each backend lives behind its own cargo feature:
+//!
+//! * `aws-kms`: [`aws::AwsKmsSigner`], for the `awskms://` keys of cosign
+//! * `gcp-kms`: [`gcp::GcpKmsSigner`], for the `gcpkms://` keys of cosign
+//! * `azure-kms`: [`azure::AzureKmsSigner`], for the `azurekms://` keys of
+//!   cosign
+//! * `hashivault`: [`hashivault::HashiVaultSigner`], for the `hashivault://`
+//!   keys of cosign, stored by the transit engine of HashiCorp Vault
+//! * `pkcs11`: [`pkcs11::Pkcs11Signer`], for the `pkcs11:` URIs of cosign,
+//!   referencing keys of HSMs or of YubiKeys
+//!
+//! The signatures produced are the same that would be produced by the key
+//! if it was stored locally, hence they are verified with the
+//! [`CosignVerificationKey`] returned by [`KmsSigner::to_verification_key`].
+
+#[cfg(any(feature = "azure-kms", feature = "pkcs11"))]
+use std::convert::TryFrom;
+
+use async_trait::async_trait;
+
+#[cfg(any(
+    feature = "aws-kms",
+    feature = "azure-kms",
+    feature = "gcp-kms",
+    feature = "hashivault",
+    feature = "pkcs11"
+))]
+use crate::crypto::hash::HashAlgorithm;
+use crate::crypto::{CosignVerificationKey, SigningScheme};
+use crate::errors::Result;
+#[cfg(any(
+    feature = "aws-kms",
+    feature = "azure-kms",
+    feature = "gcp-kms",
+    feature = "hashivault",
+    feature = "pkcs11"
+))]
+use crate::errors::SigstoreError;
+
+use super::{SigStoreSigner, PUBLIC_KEY_PEM_LABEL};
+
+#[cfg(feature = "aws-kms")]
+pub mod aws;
+#[cfg(feature = "azure-kms")]
+pub mod azure;
+#[cfg(feature = "gcp-kms")]
+pub mod gcp;
+#[cfg(feature = "hashivault")]
+pub mod hashivault;
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
+
+/// A signer backed by a key stored inside of a key management service
+#[async_trait(?Send)]
+pub trait KmsSigner {
+    /// The signing scheme of the key
+    fn signing_scheme(&self) -> SigningScheme;
+
+    /// The asn.1 PKIX public key of the key
+    fn public_key_to_der(&self) -> Result<Vec<u8>>;
+
+    /// The PEM-encoded public key of the key
+    fn public_key_to_pem(&self) -> Result<String> {
+        Ok(pem::encode(&pem::Pem {
+            tag: PUBLIC_KEY_PEM_LABEL.to_string(),
+            contents: self.public_key_to_der()?,
+        }))
+    }
+
+    /// The key verifying the signatures produced by the signer
+    fn to_verification_key(&self) -> Result<CosignVerificationKey> {
+        CosignVerificationKey::from_der(&self.public_key_to_der()?, &self.signing_scheme())
+    }
+
+    /// Sign `msg` with the key, and return the signature
+    async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Local keys sign like the ones of a KMS, so that both can be used where a
+/// [`KmsSigner`] is expected
+#[async_trait(?Send)]
+impl KmsSigner for SigStoreSigner {
+    fn signing_scheme(&self) -> SigningScheme {
+        SigStoreSigner::signing_scheme(self)
+    }
+
+    fn public_key_to_der(&self) -> Result<Vec<u8>> {
+        SigStoreSigner::public_key_to_der(self)
+    }
+
+    fn to_verification_key(&self) -> Result<CosignVerificationKey> {
+        SigStoreSigner::to_verification_key(self)
+    }
+
+    async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        SigStoreSigner::sign(self, msg)
+    }
+}
+
+/// The algorithm used to hash the messages before they are sent to the
+/// KMS, for the schemes that sign a digest
+#[cfg(any(
+    feature = "aws-kms",
+    feature = "azure-kms",
+    feature = "gcp-kms",
+    feature = "hashivault",
+    feature = "pkcs11"
+))]
+pub(crate) fn hash_algorithm(signing_scheme: &SigningScheme) -> Result<HashAlgorithm> {
+    match signing_scheme {
+        SigningScheme::ECDSA_P256_SHA256_ASN1
+        | SigningScheme::RSA_PSS_SHA256(_)
+        | SigningScheme::RSA_PKCS1_SHA256(_) => Ok(HashAlgorithm::Sha256),
+        SigningScheme::ECDSA_P384_SHA384_ASN1
+        | SigningScheme::RSA_PSS_SHA384(_)
+        | SigningScheme::RSA_PKCS1_SHA384(_) => Ok(HashAlgorithm::Sha384),
+        SigningScheme::ECDSA_P521_SHA512
+        | SigningScheme::RSA_PSS_SHA512(_)
+        | SigningScheme::RSA_PKCS1_SHA512(_) => Ok(HashAlgorithm::Sha512),
+        SigningScheme::ED25519 => Err(SigstoreError::KmsError(
+            "ED25519 signatures cannot be computed over a digest".to_string(),
+        )),
+    }
+}
+
+/// Convert an ECDSA signature made of the concatenation of `r` and `s`, as
+/// returned by some backends, to the asn.1 encoding expected by the
+/// `ECDSA_*_ASN1` schemes. Other signatures are returned as is.
+#[cfg(any(feature = "azure-kms", feature = "pkcs11"))]
+pub(crate) fn ecdsa_signature_to_der(
+    signing_scheme: &SigningScheme,
+    signature: &[u8],
+) -> Result<Vec<u8>> {
+    match signing_scheme {
+        SigningScheme::ECDSA_P256_SHA256_ASN1 => Ok(p256::ecdsa::Signature::try_from(signature)?
+            .to_der()
+            .as_bytes()
+            .to_vec()),
+        SigningScheme::ECDSA_P384_SHA384_ASN1 => Ok(p384::ecdsa::Signature::try_from(signature)?
+            .to_der()
+            .as_bytes()
+            .to_vec()),
+        _ => Ok(signature.to_vec()),
+    }
+}
@@ -0,0 +1,472 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signer backed by a key stored inside of a hardware token, like an HSM or
+//! a YubiKey, that is accessed through its PKCS#11 module.
+//!
+//! The keys are referenced like cosign does, with a PKCS#11 URI
+//! ([RFC 7512](https://www.rfc-editor.org/rfc/rfc7512)):
+//!
+//! ```text
+//! pkcs11:token=YubiKey%20PIV;slot-id=0;object=SIGN%20key;id=%02?module-path=/usr/lib/libykcs11.so&pin-value=123456
+//! ```
+//!
+//! * the token is selected by its `token` label, or by its `slot-id`
+//! * the key is selected by its `object` label, or by its `id`
+//! * the module is set by the `module-path` query attribute, or by the
+//!   `COSIGN_PKCS11_MODULE_PATH` environment variable
+//! * the user PIN is set by the `pin-value` query attribute, or by the
+//!   `COSIGN_PKCS11_PIN` environment variable. There's no login without PIN.
+//!
+//! YubiKeys are accessed through the `libykcs11` module shipped with
+//! yubico-piv-tool, which exposes the PIV slots used by `cosign --sk`.
+//!
+//! ```rust,no_run
+//! use sigstore::crypto::signing_key::kms::pkcs11::Pkcs11Signer;
+//! use sigstore::crypto::signing_key::kms::KmsSigner;
+//!
+//! # async fn doc() -> sigstore::errors::Result<()> {
+//! let signer = Pkcs11Signer::new(
+//!     "pkcs11:token=YubiKey%20PIV;object=SIGN%20key?module-path=/usr/lib/libykcs11.so",
+//! )?;
+//! let signature = signer.sign(b"hello").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use const_oid::db::rfc5912::{SECP_256_R_1, SECP_384_R_1};
+use const_oid::ObjectIdentifier;
+use cryptoki::context::{CInitializeArgs, Pkcs11};
+use cryptoki::mechanism::rsa::{PkcsMgfType, PkcsPssParams};
+use cryptoki::mechanism::{Mechanism, MechanismType};
+use cryptoki::object::{Attribute, AttributeType, KeyType, ObjectClass, ObjectHandle};
+use cryptoki::session::{Session, UserType};
+use cryptoki::types::AuthPin;
+use der::asn1::OctetStringRef;
+use der::Decode;
+use pkcs8::EncodePublicKey;
+
+use super::{ecdsa_signature_to_der, hash_algorithm, KmsSigner};
+use crate::crypto::SigningScheme;
+use crate::errors::{Result, SigstoreError};
+
+/// Scheme of the PKCS#11 URIs
+pub const PKCS11_SCHEME: &str = "pkcs11:";
+
+/// The environment variable setting the module, when the URI doesn't
+const MODULE_PATH_ENV: &str = "COSIGN_PKCS11_MODULE_PATH";
+/// The environment variable setting the PIN, when the URI doesn't
+const PIN_ENV: &str = "COSIGN_PKCS11_PIN";
+
+/// A PKCS#11 URI referencing a key
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pkcs11KeyRef {
+    /// The path of the PKCS#11 module
+    pub module_path: Option<String>,
+    /// The label of the token
+    pub token: Option<String>,
+    /// The ID of the slot of the token
+    pub slot_id: Option<u64>,
+    /// The label of the key
+    pub object: Option<String>,
+    /// The ID of the key
+    pub id: Option<Vec<u8>>,
+    /// The user PIN
+    pub pin: Option<String>,
+}
+
+impl Pkcs11KeyRef {
+    /// Parse a `pkcs11:` URI. The attributes not used to find a key are
+    /// ignored.
+    pub fn parse(key_ref: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            SigstoreError::KmsError(format!("invalid PKCS#11 URI {key_ref}: {reason}"))
+        };
+        let rest = key_ref
+            .strip_prefix(PKCS11_SCHEME)
+            .ok_or_else(|| invalid("missing pkcs11: scheme"))?;
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+        let mut parsed = Pkcs11KeyRef::default();
+        let attributes = path
+            .split(';')
+            .map(|attribute| (attribute, true))
+            .chain(query.split('&').map(|attribute| (attribute, false)))
+            .filter(|(attribute, _)| !attribute.is_empty());
+        for (attribute, in_path) in attributes {
+            let (name, value) = attribute
+                .split_once('=')
+                .ok_or_else(|| invalid("attribute without value"))?;
+            let value = percent_decode(value).ok_or_else(|| invalid("invalid percent-encoding"))?;
+            let text = || String::from_utf8(value.clone()).map_err(|_| invalid("non UTF-8 value"));
+            match (name, in_path) {
+                ("token", true) => parsed.token = Some(text()?),
+                ("slot-id", true) => {
+                    parsed.slot_id = Some(text()?.parse().map_err(|_| invalid("invalid slot-id"))?)
+                }
+                ("object", true) => parsed.object = Some(text()?),
+                ("id", true) => parsed.id = Some(value),
+                ("module-path", false) => parsed.module_path = Some(text()?),
+                ("pin-value", false) => parsed.pin = Some(text()?),
+                _ => {}
+            }
+        }
+
+        if parsed.object.is_none() && parsed.id.is_none() {
+            return Err(invalid("either object or id must be set"));
+        }
+        Ok(parsed)
+    }
+}
+
+/// Decode the `%XX` sequences of an URI component
+fn percent_decode(value: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            decoded.push(byte);
+        }
+    }
+    Some(decoded)
+}
+
+/// Signer backed by a private key of a PKCS#11 token
+pub struct Pkcs11Signer {
+    session: Session,
+    key: ObjectHandle,
+    signing_scheme: SigningScheme,
+    public_key: Vec<u8>,
+}
+
+impl Pkcs11Signer {
+    /// Load the module of the URI, open a session with the token holding
+    /// the key, and log in when a PIN is known.
+    ///
+    /// The public key is read from the public key object having the same
+    /// label and ID as the private key. RSA keys produce PKCS#1 v1.5
+    /// signatures over a SHA-256 digest, unless another scheme is chosen
+    /// with [`Pkcs11Signer::with_signing_scheme`].
+    pub fn new(key_ref: &str) -> Result<Self> {
+        let key_ref = Pkcs11KeyRef::parse(key_ref)?;
+        let module_path = key_ref
+            .module_path
+            .clone()
+            .or_else(|| std::env::var(MODULE_PATH_ENV).ok())
+            .ok_or_else(|| {
+                SigstoreError::KmsError(format!(
+                    "the PKCS#11 module is set by neither module-path nor {MODULE_PATH_ENV}"
+                ))
+            })?;
+
+        let pkcs11 = Pkcs11::new(module_path).map_err(pkcs11_error)?;
+        pkcs11
+            .initialize(CInitializeArgs::OsThreads)
+            .map_err(pkcs11_error)?;
+        let mut slot = None;
+        for candidate in pkcs11.get_slots_with_token().map_err(pkcs11_error)? {
+            let label = pkcs11
+                .get_token_info(candidate)
+                .map_err(pkcs11_error)?
+                .label()
+                .trim()
+                .to_string();
+            let slot_matches = key_ref.slot_id.map_or(true, |id| id == candidate.id());
+            let token_matches = key_ref.token.as_ref().map_or(true, |token| *token == label);
+            if slot_matches && token_matches {
+                slot = Some(candidate);
+                break;
+            }
+        }
+        let slot = slot.ok_or_else(|| {
+            SigstoreError::KmsError("no token matches the PKCS#11 URI".to_string())
+        })?;
+
+        let session = pkcs11.open_ro_session(slot).map_err(pkcs11_error)?;
+        if let Some(pin) = key_ref.pin.clone().or_else(|| std::env::var(PIN_ENV).ok()) {
+            session
+                .login(UserType::User, Some(&AuthPin::new(pin)))
+                .map_err(pkcs11_error)?;
+        }
+
+        let key = find_object(&session, &key_ref, ObjectClass::PRIVATE_KEY)?;
+        let public_key = find_object(&session, &key_ref, ObjectClass::PUBLIC_KEY)?;
+        let (signing_scheme, public_key) = read_public_key(&session, public_key)?;
+
+        Ok(Pkcs11Signer {
+            session,
+            key,
+            signing_scheme,
+            public_key,
+        })
+    }
+
+    /// Sign with the given scheme, instead of the default one of the key.
+    /// Only RSA keys support several schemes, and the key size must match
+    /// the one of the key.
+    pub fn with_signing_scheme(mut self, signing_scheme: SigningScheme) -> Result<Self> {
+        let compatible = match (self.signing_scheme, signing_scheme) {
+            (SigningScheme::RSA_PKCS1_SHA256(bits), other) => matches!(
+                other,
+                SigningScheme::RSA_PKCS1_SHA256(b)
+                | SigningScheme::RSA_PKCS1_SHA384(b)
+                | SigningScheme::RSA_PKCS1_SHA512(b)
+                | SigningScheme::RSA_PSS_SHA256(b)
+                | SigningScheme::RSA_PSS_SHA384(b)
+                | SigningScheme::RSA_PSS_SHA512(b) if b == bits
+            ),
+            (current, other) => current == other,
+        };
+        if !compatible {
+            return Err(SigstoreError::KmsError(format!(
+                "the key cannot sign with {}",
+                signing_scheme.to_string()
+            )));
+        }
+        self.signing_scheme = signing_scheme;
+        Ok(self)
+    }
+}
+
+#[async_trait(?Send)]
+impl KmsSigner for Pkcs11Signer {
+    fn signing_scheme(&self) -> SigningScheme {
+        self.signing_scheme
+    }
+
+    fn public_key_to_der(&self) -> Result<Vec<u8>> {
+        Ok(self.public_key.clone())
+    }
+
+    async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let (mechanism, data) = match self.signing_scheme {
+            // The token signs the digest computed here, and returns r || s
+            SigningScheme::ECDSA_P256_SHA256_ASN1 | SigningScheme::ECDSA_P384_SHA384_ASN1 => (
+                Mechanism::Ecdsa,
+                hash_algorithm(&self.signing_scheme)?.digest(msg),
+            ),
+            // The token hashes the message itself
+            SigningScheme::RSA_PKCS1_SHA256(_) => (Mechanism::Sha256RsaPkcs, msg.to_vec()),
+            SigningScheme::RSA_PKCS1_SHA384(_) => (Mechanism::Sha384RsaPkcs, msg.to_vec()),
+            SigningScheme::RSA_PKCS1_SHA512(_) => (Mechanism::Sha512RsaPkcs, msg.to_vec()),
+            SigningScheme::RSA_PSS_SHA256(_) => (
+                Mechanism::Sha256RsaPkcsPss(PkcsPssParams {
+                    hash_alg: MechanismType::SHA256,
+                    mgf: PkcsMgfType::MGF1_SHA256,
+                    s_len: 32.into(),
+                }),
+                msg.to_vec(),
+            ),
+            SigningScheme::RSA_PSS_SHA384(_) => (
+                Mechanism::Sha384RsaPkcsPss(PkcsPssParams {
+                    hash_alg: MechanismType::SHA384,
+                    mgf: PkcsMgfType::MGF1_SHA384,
+                    s_len: 48.into(),
+                }),
+                msg.to_vec(),
+            ),
+            SigningScheme::RSA_PSS_SHA512(_) => (
+                Mechanism::Sha512RsaPkcsPss(PkcsPssParams {
+                    hash_alg: MechanismType::SHA512,
+                    mgf: PkcsMgfType::MGF1_SHA512,
+                    s_len: 64.into(),
+                }),
+                msg.to_vec(),
+            ),
+            SigningScheme::ED25519 => {
+                return Err(SigstoreError::KmsError(
+                    "ED25519 keys of PKCS#11 tokens are not supported".to_string(),
+                ))
+            }
+            SigningScheme::ECDSA_P521_SHA512 => {
+                return Err(SigstoreError::KmsError(
+                    "P-521 keys can only be used to verify signatures".to_string(),
+                ))
+            }
+        };
+        let signature = self
+            .session
+            .sign(&mechanism, self.key, &data)
+            .map_err(pkcs11_error)?;
+        ecdsa_signature_to_der(&self.signing_scheme, &signature)
+    }
+}
+
+fn pkcs11_error(error: cryptoki::error::Error) -> SigstoreError {
+    SigstoreError::KmsError(format!("PKCS#11 error: {error}"))
+}
+
+/// Find the object of the given class having the label and the ID of the URI
+fn find_object(
+    session: &Session,
+    key_ref: &Pkcs11KeyRef,
+    class: ObjectClass,
+) -> Result<ObjectHandle> {
+    let mut template = vec![Attribute::Class(class)];
+    if let Some(object) = &key_ref.object {
+        template.push(Attribute::Label(object.as_bytes().to_vec()));
+    }
+    if let Some(id) = &key_ref.id {
+        template.push(Attribute::Id(id.clone()));
+    }
+    let kind = if class == ObjectClass::PRIVATE_KEY {
+        "private key"
+    } else {
+        "public key"
+    };
+    session
+        .find_objects(&template)
+        .map_err(pkcs11_error)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| SigstoreError::KmsError(format!("no {kind} matches the PKCS#11 URI")))
+}
+
+/// The default signing scheme of the public key, and its asn.1 PKIX
+/// encoding
+fn read_public_key(session: &Session, key: ObjectHandle) -> Result<(SigningScheme, Vec<u8>)> {
+    let attributes = session
+        .get_attributes(
+            key,
+            &[
+                AttributeType::KeyType,
+                AttributeType::EcParams,
+                AttributeType::EcPoint,
+                AttributeType::Modulus,
+                AttributeType::PublicExponent,
+            ],
+        )
+        .map_err(pkcs11_error)?;
+
+    let mut key_type = None;
+    let (mut ec_params, mut ec_point, mut modulus, mut exponent) = (None, None, None, None);
+    for attribute in attributes {
+        match attribute {
+            Attribute::KeyType(value) => key_type = Some(value),
+            Attribute::EcParams(value) => ec_params = Some(value),
+            Attribute::EcPoint(value) => ec_point = Some(value),
+            Attribute::Modulus(value) => modulus = Some(value),
+            Attribute::PublicExponent(value) => exponent = Some(value),
+            _ => {}
+        }
+    }
+
+    match (key_type, ec_params, ec_point, modulus, exponent) {
+        (Some(KeyType::EC), Some(params), Some(point), _, _) => ec_public_key(&params, &point),
+        (Some(KeyType::RSA), _, _, Some(modulus), Some(exponent)) => {
+            let n = rsa::BigUint::from_bytes_be(&modulus);
+            let e = rsa::BigUint::from_bytes_be(&exponent);
+            let bits = n.bits();
+            let der = rsa::RsaPublicKey::new(n, e)?
+                .to_public_key_der()
+                .map_err(|e| SigstoreError::PKCS8SpkiError(e.to_string()))?;
+            Ok((SigningScheme::RSA_PKCS1_SHA256(bits), der.to_vec()))
+        }
+        (key_type, ..) => Err(SigstoreError::KmsError(format!(
+            "unsupported PKCS#11 key type {key_type:?}"
+        ))),
+    }
+}
+
+/// The public key of an EC key, from its `CKA_EC_PARAMS`, the OID of the
+/// curve, and its `CKA_EC_POINT`, the SEC1 point wrapped in an octet string
+fn ec_public_key(params: &[u8], point: &[u8]) -> Result<(SigningScheme, Vec<u8>)> {
+    let curve = ObjectIdentifier::from_der(params)
+        .map_err(|e| SigstoreError::KmsError(format!("invalid EC parameters: {e}")))?;
+    let point = OctetStringRef::from_der(point)
+        .map_err(|e| SigstoreError::KmsError(format!("invalid EC point: {e}")))?;
+    let spki_error = |e: pkcs8::spki::Error| SigstoreError::PKCS8SpkiError(e.to_string());
+
+    if curve == SECP_256_R_1 {
+        let key = p256::PublicKey::from_sec1_bytes(point.as_bytes())?;
+        Ok((
+            SigningScheme::ECDSA_P256_SHA256_ASN1,
+            key.to_public_key_der().map_err(spki_error)?.to_vec(),
+        ))
+    } else if curve == SECP_384_R_1 {
+        let key = p384::PublicKey::from_sec1_bytes(point.as_bytes())?;
+        Ok((
+            SigningScheme::ECDSA_P384_SHA384_ASN1,
+            key.to_public_key_der().map_err(spki_error)?.to_vec(),
+        ))
+    } else {
+        Err(SigstoreError::KmsError(format!(
+            "unsupported curve {curve}"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use der::Encode;
+    use p256::elliptic_curve::sec1::ToEncodedPoint;
+    use rstest::rstest;
+
+    #[test]
+    fn parse_key_ref() {
+        let key_ref = Pkcs11KeyRef::parse(
+            "pkcs11:token=YubiKey%20PIV;slot-id=0;object=SIGN%20key;id=%02?module-path=/usr/lib/libykcs11.so&pin-value=123456",
+        )
+        .unwrap();
+        assert_eq!(
+            key_ref,
+            Pkcs11KeyRef {
+                module_path: Some("/usr/lib/libykcs11.so".to_string()),
+                token: Some("YubiKey PIV".to_string()),
+                slot_id: Some(0),
+                object: Some("SIGN key".to_string()),
+                id: Some(vec![2]),
+                pin: Some("123456".to_string()),
+            }
+        );
+
+        let key_ref = Pkcs11KeyRef::parse("pkcs11:object=my-key;manufacturer=SoftHSM").unwrap();
+        assert_eq!(key_ref.object.as_deref(), Some("my-key"));
+        assert_eq!(key_ref.token, None);
+    }
+
+    #[rstest]
+    #[case("pkcs11:token=my-token")]
+    #[case("pkcs11:object")]
+    #[case("pkcs11:object=%zz")]
+    #[case("pkcs11:slot-id=first;object=my-key")]
+    #[case("awskms:///my-key")]
+    fn reject_invalid_key_ref(#[case] key_ref: &str) {
+        assert!(matches!(
+            Pkcs11KeyRef::parse(key_ref),
+            Err(SigstoreError::KmsError(_))
+        ));
+    }
+
+    #[test]
+    fn public_key_of_ec_attributes() {
+        let key = p256::SecretKey::random(&mut rand::rngs::OsRng).public_key();
+        let params = SECP_256_R_1.to_vec().unwrap();
+        let point = key.to_encoded_point(false);
+        let point = OctetStringRef::new(point.as_bytes())
+            .unwrap()
+            .to_vec()
+            .unwrap();
+
+        let (scheme, der) = ec_public_key(&params, &point).unwrap();
+        assert_eq!(scheme, SigningScheme::ECDSA_P256_SHA256_ASN1);
+        assert_eq!(der, key.to_public_key_der().unwrap().to_vec());
+    }
+}
@@ -0,0 +1,770 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! RFC 3161 timestamps, issued by a timestamp authority (TSA).
+//!
+//! A timestamp proves that a signature existed at a given time, like the
+//! integrated time of a Rekor entry does. Sigstore deployments that don't
+//! rely on a transparency log, or that don't trust it for this purpose, ask
+//! a TSA to countersign the signature instead.
+//!
+//! A [`TimestampRequest`] is sent to the TSA with a `POST` request, its
+//! body having the [`TIMESTAMP_QUERY_MEDIA_TYPE`] media type. The response
+//! is parsed with [`SignedTimestamp::from_response`], then verified with
+//! the certificate chains of the timestamp authorities of a
+//! [`TrustedRoot`]:
+//!
+//! ```rust,no_run
+//! use sigstore::crypto::timestamp::{SignedTimestamp, TimestampRequest};
+//! use sigstore::crypto::trusted_root::TrustedRoot;
+//!
+//! # fn doc(signature: &[u8], trusted_root: &TrustedRoot, post: impl Fn(&[u8]) -> Vec<u8>) -> sigstore::errors::Result<()> {
+//! let request = TimestampRequest::new(signature);
+//! let response = post(&request.to_der()?);
+//!
+//! let timestamp = SignedTimestamp::from_response(&response)?;
+//! request.check_response(&timestamp)?;
+//! let signed_at = timestamp.verify(signature, trusted_root)?;
+//! println!("signed before {signed_at}");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Only the certificate chains of the trusted root are used: the
+//! certificates embedded inside of the response are ignored.
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use const_oid::db::rfc5912::{ID_KP_TIME_STAMPING, RSA_ENCRYPTION};
+use const_oid::ObjectIdentifier;
+use der::asn1::{AnyRef, OctetStringRef, UIntRef};
+use der::{Decode, Encode, Header, Reader, SliceReader, Tag, TagNumber, Tagged};
+use std::convert::TryFrom;
+use x509_cert::ext::pkix::ExtendedKeyUsage;
+use x509_cert::Certificate;
+
+use super::certificate_pool::CertificatePool;
+use super::hash::HashAlgorithm;
+use super::trusted_root::TrustedRoot;
+use super::{CosignVerificationKey, Signature};
+use crate::errors::{Result, SigstoreError};
+use crate::registry::{Certificate as RegistryCertificate, CertificateEncoding};
+
+/// Media type of the timestamp requests sent to a TSA
+pub const TIMESTAMP_QUERY_MEDIA_TYPE: &str = "application/timestamp-query";
+/// Media type of the responses of a TSA
+pub const TIMESTAMP_REPLY_MEDIA_TYPE: &str = "application/timestamp-reply";
+
+const ID_SHA_256: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.1");
+const ID_SHA_384: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.2");
+const ID_SHA_512: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.3");
+/// The CMS content holding the signature of the TSA
+const ID_SIGNED_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.2");
+/// The content signed by the TSA, a `TSTInfo`
+const ID_CT_TST_INFO: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.16.1.4");
+/// The signed attribute holding the digest of the signed content
+const ID_MESSAGE_DIGEST: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.4");
+
+/// The `granted` and `grantedWithMods` statuses of a response
+const GRANTED_STATUSES: [u8; 2] = [0, 1];
+
+/// A request for a timestamp of a message, usually a signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimestampRequest {
+    /// The SHA-256 digest of the message
+    digest: Vec<u8>,
+    nonce: u64,
+}
+
+impl TimestampRequest {
+    /// A request for a timestamp of `message`, with a random nonce
+    pub fn new(message: &[u8]) -> Self {
+        TimestampRequest {
+            digest: HashAlgorithm::Sha256.digest(message),
+            // nonces are positive integers
+            nonce: rand::random::<u64>() >> 1,
+        }
+    }
+
+    /// The nonce of the request, that the response must echo
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    /// The DER encoded `TimeStampReq`. The certificate of the TSA is
+    /// requested too.
+    pub fn to_der(&self) -> Result<Vec<u8>> {
+        let algorithm = encode_sequence(&[ID_SHA_256.to_vec().map_err(der_error)?])?;
+        let message_imprint = encode_sequence(&[
+            algorithm,
+            OctetStringRef::new(&self.digest)
+                .and_then(|digest| digest.to_vec())
+                .map_err(der_error)?,
+        ])?;
+        encode_sequence(&[
+            1u8.to_vec().map_err(der_error)?,
+            message_imprint,
+            self.nonce.to_vec().map_err(der_error)?,
+            true.to_vec().map_err(der_error)?,
+        ])
+    }
+
+    /// Ensure `timestamp` has been issued in response to this request
+    pub fn check_response(&self, timestamp: &SignedTimestamp) -> Result<()> {
+        if timestamp.hash_algorithm != HashAlgorithm::Sha256
+            || timestamp.hashed_message != self.digest
+        {
+            return Err(timestamp_error("the timestamp is about another message"));
+        }
+        let nonce = self.nonce.to_be_bytes();
+        let nonce = &nonce[nonce.iter().take_while(|b| **b == 0).count()..];
+        if timestamp.nonce.as_deref() != Some(nonce) {
+            return Err(timestamp_error("the nonce of the response doesn't match"));
+        }
+        Ok(())
+    }
+}
+
+/// A timestamp token, signed by a TSA
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTimestamp {
+    /// The time of the timestamp, in seconds since the epoch
+    pub gen_time: i64,
+    /// The algorithm used to hash the timestamped message
+    pub hash_algorithm: HashAlgorithm,
+    /// The digest of the timestamped message
+    pub hashed_message: Vec<u8>,
+    /// The nonce of the request, without leading zeros
+    pub nonce: Option<Vec<u8>>,
+    /// The DER encoded `TSTInfo`, the content signed by the TSA
+    tst_info: Vec<u8>,
+    signer_info: SignerInfo,
+}
+
+/// The parts of the `SignerInfo` of the token used to verify it
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SignerInfo {
+    /// The digest algorithm of the signature, and of the signed attributes
+    digest_algorithm: HashAlgorithm,
+    /// The DER encoded signed attributes, tagged as a `SET OF`, and their
+    /// `messageDigest` attribute
+    signed_attrs: Option<(Vec<u8>, Vec<u8>)>,
+    signature: Vec<u8>,
+}
+
+impl SignedTimestamp {
+    /// Parse a DER encoded `TimeStampResp`, the response of a TSA. This is
+    /// the format of the timestamps stored inside of Sigstore bundles.
+    pub fn from_response(der: &[u8]) -> Result<Self> {
+        let mut response = Elements::parse(decode_any(der)?)?;
+        let mut status = Elements::parse(response.next("status")?)?;
+        let status = status
+            .next("status")?
+            .decode_into::<u8>()
+            .map_err(der_error)?;
+        if !GRANTED_STATUSES.contains(&status) {
+            return Err(timestamp_error(&format!(
+                "the timestamp has not been granted, status {status}"
+            )));
+        }
+        let token = response.next("timeStampToken")?;
+        response.finish()?;
+        Self::from_token_any(token)
+    }
+
+    /// Parse a DER encoded `TimeStampToken`, the CMS `ContentInfo` holding
+    /// the signed timestamp
+    pub fn from_token(der: &[u8]) -> Result<Self> {
+        Self::from_token_any(decode_any(der)?)
+    }
+
+    fn from_token_any(token: AnyRef<'_>) -> Result<Self> {
+        let mut content_info = Elements::parse(token)?;
+        if content_info.next("contentType")?.oid().map_err(der_error)? != ID_SIGNED_DATA {
+            return Err(timestamp_error("the token is not a CMS SignedData"));
+        }
+        let content = Elements::parse(content_info.next("content")?)?.single("content")?;
+
+        let mut signed_data = Elements::parse(content)?;
+        signed_data.next("version")?;
+        signed_data.next("digestAlgorithms")?;
+        let mut encap_content = Elements::parse(signed_data.next("encapContentInfo")?)?;
+        if encap_content
+            .next("eContentType")?
+            .oid()
+            .map_err(der_error)?
+            != ID_CT_TST_INFO
+        {
+            return Err(timestamp_error("the token doesn't hold a TSTInfo"));
+        }
+        let tst_info = Elements::parse(encap_content.next("eContent")?)?
+            .single("eContent")?
+            .octet_string()
+            .map_err(der_error)?
+            .as_bytes()
+            .to_vec();
+        // the certificates and the CRLs, ignored
+        signed_data.next_if(context_tag(0, true));
+        signed_data.next_if(context_tag(1, true));
+        let signer_infos = Elements::parse(signed_data.next("signerInfos")?)?;
+        signed_data.finish()?;
+        let signer_info = SignerInfo::parse(signer_infos.single("signerInfo")?)?;
+
+        let mut info = Elements::parse(decode_any(&tst_info)?)?;
+        info.next("version")?;
+        info.next("policy")?;
+        let mut message_imprint = Elements::parse(info.next("messageImprint")?)?;
+        let hash_algorithm = parse_hash_algorithm(message_imprint.next("hashAlgorithm")?)?;
+        let hashed_message = message_imprint
+            .next("hashedMessage")?
+            .octet_string()
+            .map_err(der_error)?
+            .as_bytes()
+            .to_vec();
+        info.next("serialNumber")?;
+        let gen_time = parse_generalized_time(info.next("genTime")?)?;
+        info.next_if(Tag::Sequence); // accuracy
+        info.next_if(Tag::Boolean); // ordering
+        let nonce = info
+            .next_if(Tag::Integer)
+            .map(|nonce| nonce.decode_into::<UIntRef<'_>>().map_err(der_error))
+            .transpose()?
+            .map(|nonce| nonce.as_bytes().to_vec());
+
+        Ok(SignedTimestamp {
+            gen_time,
+            hash_algorithm,
+            hashed_message,
+            nonce,
+            tst_info,
+            signer_info,
+        })
+    }
+
+    /// Verify the timestamp is about `message` and has been signed by one
+    /// of the timestamp authorities of `trusted_root` that were in use at
+    /// the time of the timestamp.
+    ///
+    /// The leaf certificate of the chain of the authority must be valid at
+    /// that time, have the `timeStamping` extended key usage and be issued
+    /// by the rest of the chain.
+    ///
+    /// Returns the time of the timestamp, in seconds since the epoch.
+    pub fn verify(&self, message: &[u8], trusted_root: &TrustedRoot) -> Result<i64> {
+        if self.hash_algorithm.digest(message) != self.hashed_message {
+            return Err(timestamp_error("the timestamp is about another message"));
+        }
+        let signed_content = match &self.signer_info.signed_attrs {
+            Some((signed_attrs, message_digest)) => {
+                if self.signer_info.digest_algorithm.digest(&self.tst_info) != *message_digest {
+                    return Err(timestamp_error(
+                        "the signed attributes are about another content",
+                    ));
+                }
+                signed_attrs
+            }
+            None => &self.tst_info,
+        };
+
+        let chains = trusted_root.timestamp_authority_chains_at(self.gen_time);
+        if chains.is_empty() {
+            return Err(timestamp_error(&format!(
+                "no timestamp authority in use at {}",
+                self.gen_time
+            )));
+        }
+        let mut errors = Vec::new();
+        for chain in chains {
+            match self.verify_with_chain(signed_content, &chain) {
+                Ok(()) => return Ok(self.gen_time),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        Err(timestamp_error(&format!(
+            "the timestamp has not been signed by a trusted authority: {}",
+            errors.join(", ")
+        )))
+    }
+
+    fn verify_with_chain(
+        &self,
+        signed_content: &[u8],
+        chain: &[RegistryCertificate],
+    ) -> Result<()> {
+        let (leaf, issuers) = chain
+            .split_first()
+            .ok_or_else(|| timestamp_error("empty certificate chain"))?;
+        let leaf_der = certificate_der(leaf)?;
+        if !issuers.is_empty() {
+            CertificatePool::from_certificates(issuers)?.verify_der_cert(&leaf_der)?;
+        }
+        let leaf = Certificate::from_der(&leaf_der)
+            .map_err(|e| SigstoreError::X509Error(format!("parse from der: {e}")))?;
+
+        let (_, usages) = leaf
+            .tbs_certificate
+            .get::<ExtendedKeyUsage>()
+            .map_err(|e| SigstoreError::X509Error(e.to_string()))?
+            .ok_or_else(|| timestamp_error("the TSA certificate has no extended key usage"))?;
+        if !usages.0.contains(&ID_KP_TIME_STAMPING) {
+            return Err(timestamp_error(
+                "the TSA certificate cannot be used for timestamping",
+            ));
+        }
+        let validity = &leaf.tbs_certificate.validity;
+        let not_before = DateTime::<Utc>::from(validity.not_before.to_system_time()).timestamp();
+        let not_after = DateTime::<Utc>::from(validity.not_after.to_system_time()).timestamp();
+        if self.gen_time < not_before || self.gen_time > not_after {
+            return Err(timestamp_error(
+                "the TSA certificate was not valid at the time of the timestamp",
+            ));
+        }
+
+        let key = CosignVerificationKey::try_from(&leaf.tbs_certificate.subject_public_key_info)?;
+        key.verify_signature_with_hash_algorithm(
+            Signature::Raw(&self.signer_info.signature),
+            signed_content,
+            self.signer_info.digest_algorithm,
+        )
+    }
+}
+
+impl SignerInfo {
+    fn parse(signer_info: AnyRef<'_>) -> Result<Self> {
+        let mut elements = Elements::parse(signer_info)?;
+        elements.next("version")?;
+        elements.next("sid")?;
+        let digest_algorithm = parse_hash_algorithm(elements.next("digestAlgorithm")?)?;
+        let signed_attrs = elements
+            .next_if(context_tag(0, true))
+            .map(parse_signed_attrs)
+            .transpose()?;
+        check_signature_algorithm(elements.next("signatureAlgorithm")?, digest_algorithm)?;
+        let signature = elements
+            .next("signature")?
+            .octet_string()
+            .map_err(der_error)?
+            .as_bytes()
+            .to_vec();
+
+        Ok(SignerInfo {
+            digest_algorithm,
+            signed_attrs,
+            signature,
+        })
+    }
+}
+
+/// Returns the signed attributes encoded as a `SET OF`, the way they are
+/// signed, and their `messageDigest` attribute
+fn parse_signed_attrs(signed_attrs: AnyRef<'_>) -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut message_digest = None;
+    let mut attributes = Elements::parse(signed_attrs)?;
+    while let Some(attribute) = attributes.next_if(Tag::Sequence) {
+        let mut attribute = Elements::parse(attribute)?;
+        if attribute.next("attrType")?.oid().map_err(der_error)? == ID_MESSAGE_DIGEST {
+            let value = Elements::parse(attribute.next("attrValues")?)?
+                .single("messageDigest")?
+                .octet_string()
+                .map_err(der_error)?;
+            message_digest = Some(value.as_bytes().to_vec());
+        }
+    }
+    attributes.finish()?;
+
+    let encoded = AnyRef::new(Tag::Set, signed_attrs.value())
+        .and_then(|set| set.to_vec())
+        .map_err(der_error)?;
+    let message_digest = message_digest
+        .ok_or_else(|| timestamp_error("the signed attributes have no message digest"))?;
+    Ok((encoded, message_digest))
+}
+
+fn parse_hash_algorithm(algorithm: AnyRef<'_>) -> Result<HashAlgorithm> {
+    let oid = Elements::parse(algorithm)?
+        .next("algorithm")?
+        .oid()
+        .map_err(der_error)?;
+    match oid {
+        ID_SHA_256 => Ok(HashAlgorithm::Sha256),
+        ID_SHA_384 => Ok(HashAlgorithm::Sha384),
+        ID_SHA_512 => Ok(HashAlgorithm::Sha512),
+        _ => Err(timestamp_error(&format!(
+            "unsupported hash algorithm {oid}"
+        ))),
+    }
+}
+
+/// Ensure the `signatureAlgorithm` of a `SignerInfo` is an ECDSA or RSA one
+/// using `digest_algorithm`. `rsaEncryption`, which doesn't name any digest,
+/// relies on `digest_algorithm` alone.
+fn check_signature_algorithm(algorithm: AnyRef<'_>, digest_algorithm: HashAlgorithm) -> Result<()> {
+    let oid = Elements::parse(algorithm)?
+        .next("algorithm")?
+        .oid()
+        .map_err(der_error)?;
+    match HashAlgorithm::from_signature_algorithm(&oid) {
+        Some(algorithm) if algorithm == digest_algorithm => Ok(()),
+        Some(algorithm) => Err(timestamp_error(&format!(
+            "the signature algorithm uses {algorithm:?}, the digest algorithm is {digest_algorithm:?}"
+        ))),
+        None if oid == RSA_ENCRYPTION => Ok(()),
+        None => Err(timestamp_error(&format!(
+            "unsupported signature algorithm {oid}"
+        ))),
+    }
+}
+
+/// Parse a `GeneralizedTime`. Unlike the ones of the certificates, the
+/// times of the timestamps can have fractions of seconds, which are
+/// dropped.
+fn parse_generalized_time(time: AnyRef<'_>) -> Result<i64> {
+    time.tag()
+        .assert_eq(Tag::GeneralizedTime)
+        .map_err(der_error)?;
+    let value = std::str::from_utf8(time.value())?;
+    let seconds = value
+        .strip_suffix('Z')
+        .map(|v| v.split('.').next().unwrap_or(v))
+        .ok_or_else(|| timestamp_error(&format!("invalid time {value}")))?;
+    NaiveDateTime::parse_from_str(seconds, "%Y%m%d%H%M%S")
+        .map(|time| Utc.from_utc_datetime(&time).timestamp())
+        .map_err(|e| timestamp_error(&format!("invalid time {value}: {e}")))
+}
+
+fn certificate_der(certificate: &RegistryCertificate) -> Result<Vec<u8>> {
+    match certificate.encoding {
+        CertificateEncoding::Der => Ok(certificate.data.clone()),
+        CertificateEncoding::Pem => Ok(pem::parse(&certificate.data)?.contents),
+    }
+}
+
+/// The elements of a DER encoded `SEQUENCE`, `SET` or constructed
+/// context-specific value, consumed in order
+struct Elements<'a>(std::iter::Peekable<std::vec::IntoIter<AnyRef<'a>>>);
+
+impl<'a> Elements<'a> {
+    fn parse(value: AnyRef<'a>) -> Result<Self> {
+        let mut reader = SliceReader::new(value.value()).map_err(der_error)?;
+        let mut elements = Vec::new();
+        while !reader.is_finished() {
+            elements.push(reader.decode::<AnyRef<'a>>().map_err(der_error)?);
+        }
+        Ok(Elements(elements.into_iter().peekable()))
+    }
+
+    fn next(&mut self, name: &str) -> Result<AnyRef<'a>> {
+        self.0
+            .next()
+            .ok_or_else(|| timestamp_error(&format!("missing {name}")))
+    }
+
+    /// The next element, when it has the given tag
+    fn next_if(&mut self, tag: Tag) -> Option<AnyRef<'a>> {
+        self.0.next_if(|element| element.tag() == tag)
+    }
+
+    /// The only element left
+    fn single(mut self, name: &str) -> Result<AnyRef<'a>> {
+        let element = self.next(name)?;
+        self.finish()?;
+        Ok(element)
+    }
+
+    fn finish(mut self) -> Result<()> {
+        match self.0.next() {
+            None => Ok(()),
+            Some(element) => Err(timestamp_error(&format!(
+                "unexpected element with tag {}",
+                element.tag()
+            ))),
+        }
+    }
+}
+
+fn decode_any(der: &[u8]) -> Result<AnyRef<'_>> {
+    AnyRef::from_der(der).map_err(der_error)
+}
+
+fn encode_sequence(elements: &[Vec<u8>]) -> Result<Vec<u8>> {
+    let content = elements.concat();
+    let mut der = Header::new(Tag::Sequence, content.len())
+        .and_then(|header| header.to_vec())
+        .map_err(der_error)?;
+    der.extend_from_slice(&content);
+    Ok(der)
+}
+
+fn context_tag(number: u8, constructed: bool) -> Tag {
+    Tag::ContextSpecific {
+        constructed,
+        number: TagNumber::new(number),
+    }
+}
+
+fn der_error(e: der::Error) -> SigstoreError {
+    timestamp_error(&format!("invalid DER: {e}"))
+}
+
+fn timestamp_error(msg: &str) -> SigstoreError {
+    SigstoreError::TimestampError(msg.to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::crypto::tests::{generate_certificate, CertData, CertGenerationOptions};
+    use crate::crypto::trusted_root::ValidityPeriod;
+    use chrono::Duration;
+    use const_oid::db::rfc5912::{ECDSA_WITH_SHA_256, ECDSA_WITH_SHA_384};
+    use openssl::hash::MessageDigest;
+
+    const MESSAGE: &[u8] = b"the signature to timestamp";
+
+    /// A TSA: its root certificate and the leaf one, used to sign
+    pub(crate) struct Tsa {
+        pub root: CertData,
+        pub leaf: CertData,
+    }
+
+    impl Tsa {
+        pub(crate) fn new() -> Self {
+            let root = generate_certificate(None, CertGenerationOptions::default()).unwrap();
+            let leaf = generate_certificate(
+                Some(&root),
+                CertGenerationOptions {
+                    code_signing_extended_key_usage: false,
+                    time_stamping_extended_key_usage: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            Tsa { root, leaf }
+        }
+
+        pub(crate) fn trusted_root(&self) -> TrustedRoot {
+            let chain = [&self.leaf, &self.root]
+                .iter()
+                .map(|c| RegistryCertificate {
+                    encoding: CertificateEncoding::Der,
+                    data: c.cert.to_der().unwrap(),
+                })
+                .collect::<Vec<_>>();
+            let mut trusted_root = TrustedRoot::new();
+            trusted_root.add_timestamp_authority_cert_chain(&chain, ValidityPeriod::always());
+            trusted_root
+        }
+
+        /// The DER encoded `TimeStampResp` of the timestamp of `message`
+        pub(crate) fn timestamp(
+            &self,
+            message: &[u8],
+            nonce: Option<u64>,
+            gen_time: i64,
+        ) -> Vec<u8> {
+            self.timestamp_with_digest(
+                message,
+                nonce,
+                gen_time,
+                HashAlgorithm::Sha256,
+                ECDSA_WITH_SHA_256,
+            )
+        }
+
+        /// The DER encoded `TimeStampResp` of the timestamp of `message`,
+        /// signed over a `digest` of the signed attributes. `signature_algorithm`
+        /// is the one announced by the `SignerInfo`.
+        pub(crate) fn timestamp_with_digest(
+            &self,
+            message: &[u8],
+            nonce: Option<u64>,
+            gen_time: i64,
+            digest: HashAlgorithm,
+            signature_algorithm: ObjectIdentifier,
+        ) -> Vec<u8> {
+            let any = |tag, value: &[u8]| AnyRef::new(tag, value).unwrap().to_vec().unwrap();
+            let sha256 = encode_sequence(&[ID_SHA_256.to_vec().unwrap()]).unwrap();
+            let (digest_oid, message_digest) = match digest {
+                HashAlgorithm::Sha256 => (ID_SHA_256, MessageDigest::sha256()),
+                HashAlgorithm::Sha384 => (ID_SHA_384, MessageDigest::sha384()),
+                HashAlgorithm::Sha512 => (ID_SHA_512, MessageDigest::sha512()),
+            };
+            let digest_algorithm = encode_sequence(&[digest_oid.to_vec().unwrap()]).unwrap();
+            let octets = |value: &[u8]| OctetStringRef::new(value).unwrap().to_vec().unwrap();
+            let time = Utc
+                .timestamp_opt(gen_time, 0)
+                .unwrap()
+                .format("%Y%m%d%H%M%S.123Z")
+                .to_string();
+
+            let mut tst_info = vec![
+                1u8.to_vec().unwrap(),
+                ObjectIdentifier::new_unwrap("1.2.3.4").to_vec().unwrap(),
+                encode_sequence(&[
+                    sha256.clone(),
+                    octets(&HashAlgorithm::Sha256.digest(message)),
+                ])
+                .unwrap(),
+                42u8.to_vec().unwrap(),
+                any(Tag::GeneralizedTime, time.as_bytes()),
+            ];
+            if let Some(nonce) = nonce {
+                tst_info.push(nonce.to_vec().unwrap());
+            }
+            let tst_info = encode_sequence(&tst_info).unwrap();
+
+            let attribute = |oid: ObjectIdentifier, value: Vec<u8>| {
+                encode_sequence(&[oid.to_vec().unwrap(), any(Tag::Set, &value)]).unwrap()
+            };
+            let signed_attrs = [
+                attribute(
+                    ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.3"),
+                    ID_CT_TST_INFO.to_vec().unwrap(),
+                ),
+                attribute(ID_MESSAGE_DIGEST, octets(&digest.digest(&tst_info))),
+            ]
+            .concat();
+            let mut signer =
+                openssl::sign::Signer::new(message_digest, &self.leaf.private_key).unwrap();
+            signer.update(&any(Tag::Set, &signed_attrs)).unwrap();
+            let signature = signer.sign_to_vec().unwrap();
+
+            let signer_info = encode_sequence(&[
+                1u8.to_vec().unwrap(),
+                encode_sequence(&[]).unwrap(),
+                digest_algorithm.clone(),
+                any(context_tag(0, true), &signed_attrs),
+                encode_sequence(&[signature_algorithm.to_vec().unwrap()]).unwrap(),
+                octets(&signature),
+            ])
+            .unwrap();
+            let signed_data = encode_sequence(&[
+                3u8.to_vec().unwrap(),
+                any(Tag::Set, &digest_algorithm),
+                encode_sequence(&[
+                    ID_CT_TST_INFO.to_vec().unwrap(),
+                    any(context_tag(0, true), &octets(&tst_info)),
+                ])
+                .unwrap(),
+                any(Tag::Set, &signer_info),
+            ])
+            .unwrap();
+            let token = encode_sequence(&[
+                ID_SIGNED_DATA.to_vec().unwrap(),
+                any(context_tag(0, true), &signed_data),
+            ])
+            .unwrap();
+
+            encode_sequence(&[encode_sequence(&[0u8.to_vec().unwrap()]).unwrap(), token]).unwrap()
+        }
+    }
+
+    #[test]
+    fn verify_timestamp() {
+        let tsa = Tsa::new();
+        let now = Utc::now().timestamp();
+        let request = TimestampRequest::new(MESSAGE);
+        let response = tsa.timestamp(MESSAGE, Some(request.nonce()), now);
+
+        let timestamp = SignedTimestamp::from_response(&response).unwrap();
+        request.check_response(&timestamp).unwrap();
+        assert_eq!(timestamp.verify(MESSAGE, &tsa.trusted_root()).unwrap(), now);
+
+        assert!(timestamp
+            .verify(b"another message", &tsa.trusted_root())
+            .is_err());
+        assert!(TimestampRequest::new(MESSAGE)
+            .check_response(&timestamp)
+            .is_err());
+        assert!(timestamp
+            .verify(MESSAGE, &Tsa::new().trusted_root())
+            .is_err());
+        assert!(timestamp.verify(MESSAGE, &TrustedRoot::new()).is_err());
+    }
+
+    #[test]
+    fn verify_timestamp_signed_over_another_digest() {
+        // a P-256 key signing a SHA-384 digest
+        let tsa = Tsa::new();
+        let now = Utc::now().timestamp();
+        let response = tsa.timestamp_with_digest(
+            MESSAGE,
+            None,
+            now,
+            HashAlgorithm::Sha384,
+            ECDSA_WITH_SHA_384,
+        );
+        let timestamp = SignedTimestamp::from_response(&response).unwrap();
+        assert_eq!(timestamp.verify(MESSAGE, &tsa.trusted_root()).unwrap(), now);
+
+        // the signature algorithm must use the digest algorithm
+        let response = tsa.timestamp_with_digest(
+            MESSAGE,
+            None,
+            now,
+            HashAlgorithm::Sha384,
+            ECDSA_WITH_SHA_256,
+        );
+        assert!(matches!(
+            SignedTimestamp::from_response(&response),
+            Err(SigstoreError::TimestampError(_))
+        ));
+
+        let response = tsa.timestamp_with_digest(
+            MESSAGE,
+            None,
+            now,
+            HashAlgorithm::Sha384,
+            ObjectIdentifier::new_unwrap("1.3.101.112"),
+        );
+        assert!(matches!(
+            SignedTimestamp::from_response(&response),
+            Err(SigstoreError::TimestampError(_))
+        ));
+    }
+
+    #[test]
+    fn reject_timestamp_outside_of_certificate_validity() {
+        let tsa = Tsa::new();
+        let gen_time = (Utc::now() - Duration::days(2)).timestamp();
+        let timestamp =
+            SignedTimestamp::from_response(&tsa.timestamp(MESSAGE, None, gen_time)).unwrap();
+        assert!(matches!(
+            timestamp.verify(MESSAGE, &tsa.trusted_root()),
+            Err(SigstoreError::TimestampError(_))
+        ));
+    }
+
+    #[test]
+    fn timestamp_request_to_der() {
+        let request = TimestampRequest::new(MESSAGE);
+        let der = request.to_der().unwrap();
+        let mut elements = Elements::parse(decode_any(&der).unwrap()).unwrap();
+        assert_eq!(elements.next("version").unwrap().value(), &[1]);
+        let mut imprint = Elements::parse(elements.next("messageImprint").unwrap()).unwrap();
+        assert_eq!(
+            parse_hash_algorithm(imprint.next("hashAlgorithm").unwrap()).unwrap(),
+            HashAlgorithm::Sha256
+        );
+        assert_eq!(
+            imprint.next("hashedMessage").unwrap().value(),
+            HashAlgorithm::Sha256.digest(MESSAGE)
+        );
+        assert_eq!(
+            u64::from_der(elements.next("nonce").unwrap().to_vec().unwrap().as_slice()).unwrap(),
+            request.nonce()
+        );
+        assert_eq!(elements.single("certReq").unwrap().tag(), Tag::Boolean);
+    }
+}
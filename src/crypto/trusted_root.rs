@@ -0,0 +1,765 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Trust material annotated with validity windows.
+//!
+//! Sigstore rotates its keys over the time: Rekor signing keys are replaced
+//! and Fulcio intermediates expire. A signature produced in the past must be
+//! verified with the material that was valid when the signature was
+//! integrated into the transparency log, not with the current one.
+//!
+//! The [`TrustedRoot`] keeps track of all the Rekor keys and Fulcio
+//! certificate chains, together with the time window during which they were
+//! in use. It can be built programmatically or loaded from a Sigstore
+//! `trusted_root.json` document, the `TrustedRoot` message of the
+//! [protobuf-specs](https://github.com/sigstore/protobuf-specs) distributed
+//! by the other Sigstore clients. The document itself is modeled by
+//! [`TrustedRootDocument`].
+//!
+//! Loading a `TrustedRoot` never requires network access, which makes it
+//! suitable for air-gapped environments. The trust material can be shipped
+//! next to the application, or embedded inside of it:
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::ClientBuilder;
+//! use sigstore::crypto::trusted_root::TrustedRoot;
+//! use std::path::Path;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! // a `trusted_root.json` document copied from a connected machine
+//! let trusted_root = TrustedRoot::from_file(Path::new("/etc/sigstore/trusted_root.json"))?;
+//!
+//! // or the files written by `SigstoreRepository::fetch` into its checkout dir
+//! let trusted_root = TrustedRoot::from_checkout_dir(Path::new("/etc/sigstore/targets"))?;
+//!
+//! let client = ClientBuilder::default()
+//!     .with_trusted_root(trusted_root)
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The [`FreshnessPolicy`] prevents long-running processes from silently
+//! verifying against trust material that has not been refreshed for too long.
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use super::certificate_pool::CertificatePool;
+use super::CosignVerificationKey;
+use crate::errors::{Result, SigstoreError};
+use crate::registry::{Certificate, CertificateEncoding};
+
+/// The time window during which a piece of trust material was in use.
+///
+/// Both the boundaries are expressed as UNIX timestamps and are inclusive.
+/// A period without `end` is still ongoing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidityPeriod {
+    pub start: i64,
+    pub end: Option<i64>,
+}
+
+impl ValidityPeriod {
+    /// Create a new `ValidityPeriod`
+    pub fn new(start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> Self {
+        ValidityPeriod {
+            start: start.timestamp(),
+            end: end.map(|e| e.timestamp()),
+        }
+    }
+
+    /// A period that has always been valid, and will never expire
+    pub fn always() -> Self {
+        ValidityPeriod {
+            start: i64::MIN,
+            end: None,
+        }
+    }
+
+    /// Returns `true` when `time` falls inside of the period
+    pub fn contains(&self, time: i64) -> bool {
+        time >= self.start && self.end.is_none_or(|end| time <= end)
+    }
+}
+
+/// The key of a transparency log, either Rekor or a CT log
+#[derive(Debug, Clone)]
+struct LogKey {
+    key: CosignVerificationKey,
+    /// hex encoded ID of the log
+    log_id: String,
+    validity: ValidityPeriod,
+}
+
+impl LogKey {
+    fn from_pem(pem: &str, validity: ValidityPeriod) -> Result<Self> {
+        let der = pem::parse(pem)?.contents;
+        Ok(LogKey {
+            key: CosignVerificationKey::try_from_der(&der)?,
+            log_id: hex::encode(Sha256::digest(&der)),
+            validity,
+        })
+    }
+
+    fn from_instance(instance: &TransparencyLogInstance) -> Result<Self> {
+        let der = BASE64_STD_ENGINE.decode(&instance.public_key.raw_bytes)?;
+        // the ID of the log defaults to the digest of its key
+        let log_id = match &instance.log_id {
+            Some(log_id) => hex::encode(BASE64_STD_ENGINE.decode(&log_id.key_id)?),
+            None => hex::encode(Sha256::digest(&der)),
+        };
+        Ok(LogKey {
+            key: CosignVerificationKey::try_from_der(&der)?,
+            log_id,
+            validity: instance.public_key.valid_for.to_period()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CertificateAuthority {
+    cert_chain: Vec<Certificate>,
+    validity: ValidityPeriod,
+}
+
+impl CertificateAuthority {
+    fn from_document(document: &CertificateAuthorityDocument) -> Result<Self> {
+        let cert_chain = document
+            .cert_chain
+            .certificates
+            .iter()
+            .map(|c| {
+                Ok(Certificate {
+                    encoding: CertificateEncoding::Der,
+                    data: BASE64_STD_ENGINE.decode(&c.raw_bytes)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(CertificateAuthority {
+            cert_chain,
+            validity: document.valid_for.to_period()?,
+        })
+    }
+}
+
+/// The sorted paths of the files of `dir` with the given prefix and suffix
+fn files_matching(dir: &Path, prefix: &str, suffix: &str) -> Result<Vec<PathBuf>> {
+    let mut paths = std::fs::read_dir(dir)
+        .map_err(|e| {
+            SigstoreError::TrustedRootError(format!("cannot read {}: {e}", dir.display()))
+        })?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(prefix) && name.ends_with(suffix))
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+    Ok(paths)
+}
+
+/// All the trust material used by a Sigstore instance over the time
+#[derive(Debug, Clone, Default)]
+pub struct TrustedRoot {
+    rekor_keys: Vec<LogKey>,
+    ct_log_keys: Vec<LogKey>,
+    certificate_authorities: Vec<CertificateAuthority>,
+    timestamp_authorities: Vec<CertificateAuthority>,
+}
+
+impl TrustedRoot {
+    /// Create an empty `TrustedRoot`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a `TrustedRoot` from a Sigstore `trusted_root.json` document.
+    ///
+    /// See [`TrustedRoot::from_document`] for the entries consumed.
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let document: TrustedRootDocument = serde_json::from_str(raw)
+            .map_err(|e| SigstoreError::TrustedRootError(format!("cannot parse document: {e}")))?;
+        Self::from_document(&document)
+    }
+
+    /// Build a `TrustedRoot` from a parsed `trusted_root.json` document.
+    ///
+    /// The transparency logs, the CT logs, the certificate authorities and
+    /// the timestamp authorities are consumed.
+    pub fn from_document(document: &TrustedRootDocument) -> Result<Self> {
+        let mut trusted_root = TrustedRoot::new();
+        for tlog in &document.tlogs {
+            trusted_root.rekor_keys.push(LogKey::from_instance(tlog)?);
+        }
+        for ctlog in &document.ctlogs {
+            trusted_root.ct_log_keys.push(LogKey::from_instance(ctlog)?);
+        }
+        for ca in &document.certificate_authorities {
+            trusted_root
+                .certificate_authorities
+                .push(CertificateAuthority::from_document(ca)?);
+        }
+        for tsa in &document.timestamp_authorities {
+            trusted_root
+                .timestamp_authorities
+                .push(CertificateAuthority::from_document(tsa)?);
+        }
+
+        Ok(trusted_root)
+    }
+
+    /// Load a `TrustedRoot` from a `trusted_root.json` document stored on
+    /// the local filesystem
+    pub fn from_file(path: &Path) -> Result<Self> {
+        Self::from_json(&read_to_string(path)?)
+    }
+
+    /// Load a `TrustedRoot` from a directory holding the targets of the
+    /// Sigstore TUF repository, like the `checkout_dir` populated by
+    /// [`SigstoreRepository::fetch`](crate::tuf::SigstoreRepository::fetch).
+    ///
+    /// The directory must contain Rekor's public key, `rekor.pub`, and at
+    /// least one Fulcio certificate, `fulcio.crt.pem` or
+    /// `fulcio_v<N>.crt.pem`. The keys of the CT logs, `ctfe.pub` or
+    /// `ctfe_<N>.pub`, are loaded when available. The files don't carry any
+    /// validity window, hence all the material is considered to be always
+    /// valid.
+    pub fn from_checkout_dir(dir: &Path) -> Result<Self> {
+        let mut trusted_root = TrustedRoot::new();
+        trusted_root.add_rekor_pub_key(
+            &read_to_string(&dir.join("rekor.pub"))?,
+            ValidityPeriod::always(),
+        )?;
+
+        for path in files_matching(dir, "ctfe", ".pub")? {
+            trusted_root.add_ct_log_pub_key(&read_to_string(&path)?, ValidityPeriod::always())?;
+        }
+
+        let fulcio_paths = files_matching(dir, "fulcio", ".crt.pem")?;
+        if fulcio_paths.is_empty() {
+            return Err(SigstoreError::TrustedRootError(format!(
+                "no Fulcio certificate found inside of {}",
+                dir.display()
+            )));
+        }
+        for path in fulcio_paths {
+            let cert = Certificate {
+                encoding: CertificateEncoding::Pem,
+                data: read_to_string(&path)?.into_bytes(),
+            };
+            trusted_root.add_fulcio_cert_chain(&[cert], ValidityPeriod::always());
+        }
+
+        Ok(trusted_root)
+    }
+
+    /// Add a Rekor public key, used during the given period.
+    ///
+    /// `key` is a PEM encoded public key
+    pub fn add_rekor_pub_key(&mut self, key: &str, validity: ValidityPeriod) -> Result<()> {
+        self.rekor_keys.push(LogKey::from_pem(key, validity)?);
+        Ok(())
+    }
+
+    /// Add the public key of a CT log, used during the given period.
+    ///
+    /// `key` is a PEM encoded public key
+    pub fn add_ct_log_pub_key(&mut self, key: &str, validity: ValidityPeriod) -> Result<()> {
+        self.ct_log_keys.push(LogKey::from_pem(key, validity)?);
+        Ok(())
+    }
+
+    /// Add a Fulcio certificate chain, used during the given period
+    pub fn add_fulcio_cert_chain(&mut self, cert_chain: &[Certificate], validity: ValidityPeriod) {
+        self.certificate_authorities.push(CertificateAuthority {
+            cert_chain: cert_chain.to_vec(),
+            validity,
+        });
+    }
+
+    /// Add the certificate chain of a timestamp authority, used during the
+    /// given period. The chain starts with the certificate signing the
+    /// timestamps.
+    pub fn add_timestamp_authority_cert_chain(
+        &mut self,
+        cert_chain: &[Certificate],
+        validity: ValidityPeriod,
+    ) {
+        self.timestamp_authorities.push(CertificateAuthority {
+            cert_chain: cert_chain.to_vec(),
+            validity,
+        });
+    }
+
+    /// The Rekor keys that were in use at the given time
+    pub fn rekor_pub_keys_at(&self, time: i64) -> Vec<&CosignVerificationKey> {
+        self.rekor_keys
+            .iter()
+            .filter(|k| k.validity.contains(time))
+            .map(|k| &k.key)
+            .collect()
+    }
+
+    /// The keys of the Rekor instance with the hex encoded `log_id` that
+    /// were in use at the given time
+    pub fn rekor_pub_keys_for_log_at(
+        &self,
+        log_id: &str,
+        time: i64,
+    ) -> Vec<&CosignVerificationKey> {
+        self.rekor_keys
+            .iter()
+            .filter(|k| k.log_id.eq_ignore_ascii_case(log_id) && k.validity.contains(time))
+            .map(|k| &k.key)
+            .collect()
+    }
+
+    /// The keys of the CT logs that were in use at the given time
+    pub fn ct_log_pub_keys_at(&self, time: i64) -> Vec<&CosignVerificationKey> {
+        self.ct_log_keys
+            .iter()
+            .filter(|k| k.validity.contains(time))
+            .map(|k| &k.key)
+            .collect()
+    }
+
+    /// The keys of the CT log with the hex encoded `log_id` that were in
+    /// use at the given time
+    pub fn ct_log_pub_keys_for_log_at(
+        &self,
+        log_id: &str,
+        time: i64,
+    ) -> Vec<&CosignVerificationKey> {
+        self.ct_log_keys
+            .iter()
+            .filter(|k| k.log_id.eq_ignore_ascii_case(log_id) && k.validity.contains(time))
+            .map(|k| &k.key)
+            .collect()
+    }
+
+    /// Returns `true` when the keys of some CT logs are known. The SCTs of
+    /// the Fulcio certificates are verified only in that case.
+    pub fn has_ct_logs(&self) -> bool {
+        !self.ct_log_keys.is_empty()
+    }
+
+    /// The Fulcio certificates that were in use at the given time
+    pub fn fulcio_certs_at(&self, time: i64) -> Vec<Certificate> {
+        self.certificate_authorities
+            .iter()
+            .filter(|ca| ca.validity.contains(time))
+            .flat_map(|ca| ca.cert_chain.iter().cloned())
+            .collect()
+    }
+
+    /// The certificate chains of the timestamp authorities that were in use
+    /// at the given time
+    pub fn timestamp_authority_chains_at(&self, time: i64) -> Vec<Vec<Certificate>> {
+        self.timestamp_authorities
+            .iter()
+            .filter(|tsa| tsa.validity.contains(time))
+            .map(|tsa| tsa.cert_chain.clone())
+            .collect()
+    }
+
+    /// Returns `true` when some timestamp authorities are known
+    pub fn has_timestamp_authorities(&self) -> bool {
+        !self.timestamp_authorities.is_empty()
+    }
+
+    /// All the Fulcio certificates, regardless of their validity window
+    pub(crate) fn all_fulcio_certs(&self) -> Vec<Certificate> {
+        self.certificate_authorities
+            .iter()
+            .flat_map(|ca| ca.cert_chain.iter().cloned())
+            .collect()
+    }
+
+    /// The validity windows of the Rekor keys
+    pub(crate) fn rekor_key_validity_periods(&self) -> impl Iterator<Item = &ValidityPeriod> {
+        self.rekor_keys.iter().map(|k| &k.validity)
+    }
+
+    /// Build a [`CertificatePool`] with the Fulcio certificates that were in
+    /// use at the given time. Returns `None` when no certificate authority
+    /// was active at that time.
+    pub(crate) fn fulcio_cert_pool_at(&self, time: i64) -> Result<Option<CertificatePool>> {
+        let certs = self.fulcio_certs_at(time);
+        if certs.is_empty() {
+            return Ok(None);
+        }
+        CertificatePool::from_certificates(&certs).map(Some)
+    }
+}
+
+/// Rejects trust material that is older than a configured age
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreshnessPolicy {
+    max_age: Duration,
+}
+
+impl FreshnessPolicy {
+    /// Create a new `FreshnessPolicy`. Trust material fetched more than
+    /// `max_age` ago is considered stale.
+    pub fn new(max_age: Duration) -> Self {
+        FreshnessPolicy { max_age }
+    }
+
+    /// The maximum age allowed for the trust material
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    /// Ensure the trust material fetched at `fetched_at` is not stale
+    pub fn check(&self, fetched_at: DateTime<Utc>) -> Result<()> {
+        self.check_at(fetched_at, Utc::now())
+    }
+
+    /// Ensure the trust material fetched at `fetched_at` is not stale at the
+    /// given point in time
+    pub fn check_at(&self, fetched_at: DateTime<Utc>, now: DateTime<Utc>) -> Result<()> {
+        if now - fetched_at > self.max_age {
+            return Err(SigstoreError::StaleTrustMaterial {
+                fetched_at: fetched_at.to_rfc3339(),
+                max_age_seconds: self.max_age.num_seconds(),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn read_to_string(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|e| {
+        SigstoreError::TrustedRootError(format!("cannot read {}: {e}", path.display()))
+    })
+}
+
+/// A `trusted_root.json` document, the `TrustedRoot` message of the Sigstore
+/// protobuf-specs.
+///
+/// The binary fields are base64 encoded, the timestamps are RFC 3339
+/// strings.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustedRootDocument {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(default)]
+    pub tlogs: Vec<TransparencyLogInstance>,
+    #[serde(default)]
+    pub certificate_authorities: Vec<CertificateAuthorityDocument>,
+    #[serde(default)]
+    pub ctlogs: Vec<TransparencyLogInstance>,
+    #[serde(default)]
+    pub timestamp_authorities: Vec<CertificateAuthorityDocument>,
+}
+
+/// A transparency log: a Rekor instance or a CT log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransparencyLogInstance {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_algorithm: Option<String>,
+    pub public_key: PublicKeyDocument,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_id: Option<LogIdDocument>,
+}
+
+/// A DER encoded public key, with the time window during which it was used
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyDocument {
+    pub raw_bytes: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_details: Option<String>,
+    pub valid_for: TimeRange,
+}
+
+/// The ID of a transparency log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogIdDocument {
+    pub key_id: String,
+}
+
+/// A certificate authority, like Fulcio, or a timestamp authority
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateAuthorityDocument {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<DistinguishedNameDocument>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    pub cert_chain: CertChainDocument,
+    pub valid_for: TimeRange,
+}
+
+/// The subject of a certificate authority
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DistinguishedNameDocument {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub common_name: Option<String>,
+}
+
+/// A chain of certificates, starting with the leaf one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CertChainDocument {
+    pub certificates: Vec<CertificateDocument>,
+}
+
+/// A DER encoded certificate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateDocument {
+    pub raw_bytes: String,
+}
+
+/// A time window, a missing `end` means the window is still open
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+}
+
+impl TimeRange {
+    /// Parse the boundaries of the window
+    pub fn to_period(&self) -> Result<ValidityPeriod> {
+        let parse = |t: &str| {
+            DateTime::parse_from_rfc3339(t)
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|e| SigstoreError::TrustedRootError(format!("invalid time {t}: {e}")))
+        };
+        Ok(ValidityPeriod::new(
+            parse(&self.start)?,
+            self.end.as_deref().map(parse).transpose()?,
+        ))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::cosign::tests::REKOR_PUB_KEY;
+    use crate::crypto::SigningScheme;
+    use serde_json::json;
+
+    /// Strip the PEM armor, returning the base64 encoded DER contents
+    pub(crate) fn pem_body(pem: &str) -> String {
+        pem.lines().filter(|l| !l.starts_with("-----")).collect()
+    }
+
+    #[test]
+    fn validity_period_boundaries() {
+        let period = ValidityPeriod {
+            start: 10,
+            end: Some(20),
+        };
+        assert!(!period.contains(9));
+        assert!(period.contains(10));
+        assert!(period.contains(20));
+        assert!(!period.contains(21));
+
+        let ongoing = ValidityPeriod {
+            start: 10,
+            end: None,
+        };
+        assert!(ongoing.contains(i64::MAX));
+        assert!(ValidityPeriod::always().contains(0));
+    }
+
+    #[test]
+    fn load_trusted_root_document() {
+        let rotated_key = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap()
+            .to_sigstore_keypair()
+            .unwrap()
+            .public_key_to_pem()
+            .unwrap();
+
+        let document = json!({
+            "mediaType": "application/vnd.dev.sigstore.trustedroot+json;version=0.1",
+            "tlogs": [
+                {
+                    "baseUrl": "https://rekor.sigstore.dev",
+                    "publicKey": {
+                        "rawBytes": pem_body(REKOR_PUB_KEY),
+                        "keyDetails": "PKIX_ECDSA_P256_SHA_256",
+                        "validFor": {
+                            "start": "2021-01-12T11:53:27.000Z",
+                            "end": "2022-01-01T00:00:00.000Z"
+                        }
+                    }
+                },
+                {
+                    "baseUrl": "https://rekor.sigstore.dev",
+                    "publicKey": {
+                        "rawBytes": pem_body(&rotated_key),
+                        "keyDetails": "PKIX_ECDSA_P256_SHA_256",
+                        "validFor": {
+                            "start": "2022-01-01T00:00:00.000Z"
+                        }
+                    }
+                }
+            ],
+            "certificateAuthorities": [],
+            "ctlogs": [
+                {
+                    "baseUrl": "https://ctfe.sigstore.dev/test",
+                    "hashAlgorithm": "SHA2_256",
+                    "publicKey": {
+                        "rawBytes": pem_body(&rotated_key),
+                        "keyDetails": "PKIX_ECDSA_P256_SHA_256",
+                        "validFor": {
+                            "start": "2021-03-14T00:00:00.000Z"
+                        }
+                    },
+                    "logId": {
+                        "keyId": "CGCS8ChS/2hF0dFrJ4ScRWcYrBY9wzjSbea8IgY2b3I="
+                    }
+                }
+            ],
+            "timestampAuthorities": []
+        });
+
+        let parsed: TrustedRootDocument = serde_json::from_value(document.clone()).unwrap();
+        assert_eq!(parsed.ctlogs.len(), 1);
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), document);
+
+        let trusted_root = TrustedRoot::from_json(&document.to_string()).unwrap();
+
+        // 2021-10-20
+        assert_eq!(trusted_root.rekor_pub_keys_at(1634714179).len(), 1);
+        // 2020-01-01, before Rekor went live
+        assert!(trusted_root.rekor_pub_keys_at(1577836800).is_empty());
+        // 2022-01-01T00:00:00Z, both keys are accepted
+        assert_eq!(trusted_root.rekor_pub_keys_at(1640995200).len(), 2);
+        // 2023-01-01
+        assert_eq!(trusted_root.rekor_pub_keys_at(1672531200).len(), 1);
+        // the log ID defaults to the digest of the key
+        assert_eq!(
+            trusted_root
+                .rekor_pub_keys_for_log_at(
+                    "C0D23D6AD406973F9559F3BA2D1CA01F84147D8FFC5B8445C224F98B9591801D",
+                    1634714179
+                )
+                .len(),
+            1
+        );
+        assert!(trusted_root
+            .rekor_pub_keys_for_log_at(&"00".repeat(32), 1634714179)
+            .is_empty());
+        assert_eq!(trusted_root.ct_log_pub_keys_at(1672531200).len(), 1);
+        assert!(trusted_root.ct_log_pub_keys_at(1577836800).is_empty());
+        assert!(trusted_root
+            .fulcio_cert_pool_at(1672531200)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn load_from_local_files() {
+        let targets = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("data")
+            .join("repository")
+            .join("targets");
+        let checkout_dir = tempfile::TempDir::new().unwrap();
+        for name in [
+            "rekor.pub",
+            "fulcio.crt.pem",
+            "fulcio_v1.crt.pem",
+            "ctfe.pub",
+        ] {
+            std::fs::copy(targets.join(name), checkout_dir.path().join(name)).unwrap();
+        }
+
+        let trusted_root = TrustedRoot::from_checkout_dir(checkout_dir.path()).unwrap();
+        assert_eq!(trusted_root.rekor_pub_keys_at(0).len(), 1);
+        assert_eq!(trusted_root.fulcio_certs_at(0).len(), 2);
+        assert_eq!(trusted_root.ct_log_pub_keys_at(0).len(), 1);
+
+        std::fs::remove_file(checkout_dir.path().join("fulcio.crt.pem")).unwrap();
+        std::fs::remove_file(checkout_dir.path().join("fulcio_v1.crt.pem")).unwrap();
+        assert!(matches!(
+            TrustedRoot::from_checkout_dir(checkout_dir.path()),
+            Err(SigstoreError::TrustedRootError(_))
+        ));
+
+        let document = json!({
+            "tlogs": [
+                {
+                    "publicKey": {
+                        "rawBytes": pem_body(REKOR_PUB_KEY),
+                        "validFor": { "start": "2021-01-12T11:53:27.000Z" }
+                    }
+                }
+            ]
+        });
+        let path = checkout_dir.path().join("trusted_root.json");
+        std::fs::write(&path, document.to_string()).unwrap();
+        let trusted_root = TrustedRoot::from_file(&path).unwrap();
+        assert_eq!(trusted_root.rekor_pub_keys_at(1634714179).len(), 1);
+        assert!(matches!(
+            TrustedRoot::from_file(&checkout_dir.path().join("missing.json")),
+            Err(SigstoreError::TrustedRootError(_))
+        ));
+    }
+
+    #[test]
+    fn freshness_policy_rejects_stale_material() {
+        let policy = FreshnessPolicy::new(Duration::hours(24));
+        let now = Utc::now();
+
+        assert!(policy.check_at(now - Duration::hours(1), now).is_ok());
+        assert!(policy.check_at(now - Duration::hours(24), now).is_ok());
+        assert!(matches!(
+            policy.check_at(now - Duration::hours(25), now),
+            Err(SigstoreError::StaleTrustMaterial {
+                max_age_seconds: 86400,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn reject_invalid_document() {
+        let document = json!({
+            "tlogs": [
+                {
+                    "publicKey": {
+                        "rawBytes": pem_body(REKOR_PUB_KEY),
+                        "validFor": { "start": "yesterday" }
+                    }
+                }
+            ]
+        });
+        assert!(matches!(
+            TrustedRoot::from_json(&document.to_string()),
+            Err(SigstoreError::TrustedRootError(_))
+        ));
+    }
+}
@@ -14,16 +14,25 @@
 // limitations under the License.
 
 use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
-use const_oid::db::rfc5912::{ID_EC_PUBLIC_KEY, RSA_ENCRYPTION};
-use pkcs8::{DecodePublicKey, SubjectPublicKeyInfo};
+use const_oid::db::rfc5912::{
+    ID_EC_PUBLIC_KEY, ID_RSASSA_PSS, ID_SHA_256, ID_SHA_384, ID_SHA_512, RSA_ENCRYPTION,
+    SECP_256_R_1, SECP_384_R_1, SECP_521_R_1,
+};
+use p521::pkcs8::DecodePublicKey as _;
+use pkcs8::der::Decode;
+use pkcs8::{DecodePublicKey, EncodePublicKey, SubjectPublicKeyInfo};
+use rsa::pkcs1::{DecodeRsaPublicKey, RsaPssParams};
 use rsa::{pkcs1v15, pss};
 use sha2::{Digest, Sha256, Sha384};
-use signature::{DigestVerifier, Verifier};
+use signature::{hazmat::PrehashVerifier, DigestVerifier, Verifier};
 use std::convert::TryFrom;
+use std::io::Read;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 use super::{
+    hash::HashAlgorithm,
     signing_key::{KeyPair, SigStoreSigner},
-    Signature, SigningScheme,
+    Signature, SignatureFormat, SigningScheme,
 };
 
 use crate::errors::*;
@@ -44,6 +53,10 @@ use crate::cosign::constants::ED25519;
 ///   * Ed25519 keys, and SHA-512 as the digest algorithm
 ///   * ECDSA keys, ASN.1 DER-encoded, using the P-256 curve and SHA-256 as digest algorithm
 ///   * ECDSA keys, ASN.1 DER-encoded, using the P-384 curve and SHA-384 as digest algorithm
+///
+/// The keys are read from, and exported to, PEM or DER encoded SPKI. They can
+/// also be read from the x509 certificates holding them, see
+/// [`from_certificate_pem`](Self::from_certificate_pem).
 #[allow(non_camel_case_types)]
 #[derive(Debug, Clone)]
 pub enum CosignVerificationKey {
@@ -55,16 +68,21 @@ pub enum CosignVerificationKey {
     RSA_PKCS1_SHA512(pkcs1v15::VerifyingKey<sha2::Sha512>),
     ECDSA_P256_SHA256_ASN1(ecdsa::VerifyingKey<p256::NistP256>),
     ECDSA_P384_SHA384_ASN1(ecdsa::VerifyingKey<p384::NistP384>),
+    ECDSA_P521_SHA512(p521::PublicKey),
     ED25519(ed25519_dalek::VerifyingKey),
 }
 
 /// Attempts to convert a [x509 Subject Public Key Info](SubjectPublicKeyInfo) object into
 /// a `CosignVerificationKey` one.
 ///
+/// The curve of the ECDSA keys is the named curve of the key parameters.
 /// Currently can convert only the following types of keys:
 ///   * ECDSA P-256: assumes the SHA-256 digest algorithm is used
 ///   * ECDSA P-384: assumes the SHA-384 digest algorithm is used
-///   * RSA: assumes PKCS1 padding is used
+///   * ECDSA P-521: assumes the SHA-512 digest algorithm is used
+///   * RSA: assumes PKCS1 padding and the SHA-256 digest algorithm are used
+///   * RSA restricted to RSASSA-PSS: uses PSS padding and the digest algorithm
+///     of the key parameters, SHA-256 when the key has no parameters
 impl<'a> TryFrom<&SubjectPublicKeyInfo<'a>> for CosignVerificationKey {
     type Error = SigstoreError;
 
@@ -72,27 +90,40 @@ impl<'a> TryFrom<&SubjectPublicKeyInfo<'a>> for CosignVerificationKey {
         let algorithm = subject_pub_key_info.algorithm.oid;
         let public_key_der = subject_pub_key_info.subject_public_key;
         match algorithm {
-            ID_EC_PUBLIC_KEY => match public_key_der.len() {
-                65 => Ok(CosignVerificationKey::ECDSA_P256_SHA256_ASN1(
-                    ecdsa::VerifyingKey::try_from(*subject_pub_key_info).map_err(|e| {
-                        SigstoreError::PKCS8SpkiError(format!(
-                            "Ecdsa-P256 from der bytes to public key failed: {e}"
-                        ))
-                    })?,
-                )),
-                97 => Ok(CosignVerificationKey::ECDSA_P384_SHA384_ASN1(
-                    ecdsa::VerifyingKey::try_from(*subject_pub_key_info).map_err(|e| {
-                        SigstoreError::PKCS8SpkiError(format!(
-                            "Ecdsa-P384 from der bytes to public key failed: {e}"
-                        ))
-                    })?,
-                )),
-                _ => Err(SigstoreError::PublicKeyUnsupportedAlgorithmError(format!(
-                    "EC with size {} is not supported",
-                    // asn.1 encode caused different length
-                    (public_key_der.len() - 1) * 4
-                ))),
-            },
+            ID_EC_PUBLIC_KEY => {
+                let curve = subject_pub_key_info
+                    .algorithm
+                    .parameters_oid()
+                    .map_err(|e| {
+                        SigstoreError::PKCS8SpkiError(format!("EC key without named curve: {e}"))
+                    })?;
+                match curve {
+                    SECP_256_R_1 => Ok(CosignVerificationKey::ECDSA_P256_SHA256_ASN1(
+                        ecdsa::VerifyingKey::try_from(*subject_pub_key_info).map_err(|e| {
+                            SigstoreError::PKCS8SpkiError(format!(
+                                "Ecdsa-P256 from der bytes to public key failed: {e}"
+                            ))
+                        })?,
+                    )),
+                    SECP_384_R_1 => Ok(CosignVerificationKey::ECDSA_P384_SHA384_ASN1(
+                        ecdsa::VerifyingKey::try_from(*subject_pub_key_info).map_err(|e| {
+                            SigstoreError::PKCS8SpkiError(format!(
+                                "Ecdsa-P384 from der bytes to public key failed: {e}"
+                            ))
+                        })?,
+                    )),
+                    SECP_521_R_1 => Ok(CosignVerificationKey::ECDSA_P521_SHA512(
+                        p521::PublicKey::from_sec1_bytes(public_key_der).map_err(|e| {
+                            SigstoreError::PKCS8SpkiError(format!(
+                                "Ecdsa-P521 from der bytes to public key failed: {e}"
+                            ))
+                        })?,
+                    )),
+                    _ => Err(SigstoreError::PublicKeyUnsupportedAlgorithmError(format!(
+                        "EC with curve OID {curve} is not supported"
+                    ))),
+                }
+            }
             RSA_ENCRYPTION => {
                 let pubkey = rsa::RsaPublicKey::try_from(*subject_pub_key_info).map_err(|e| {
                     SigstoreError::PKCS8SpkiError(format!(
@@ -100,9 +131,10 @@ impl<'a> TryFrom<&SubjectPublicKeyInfo<'a>> for CosignVerificationKey {
                     ))
                 })?;
                 Ok(CosignVerificationKey::RSA_PKCS1_SHA256(
-                    pkcs1v15::VerifyingKey::<sha2::Sha256>::from(pubkey),
+                    pkcs1v15::VerifyingKey::<sha2::Sha256>::new_with_prefix(pubkey),
                 ))
             }
+            ID_RSASSA_PSS => rsa_pss_verification_key(subject_pub_key_info),
             //
             #[cfg(feature = "cosign")]
             ED25519 => Ok(CosignVerificationKey::ED25519(
@@ -116,6 +148,43 @@ impl<'a> TryFrom<&SubjectPublicKeyInfo<'a>> for CosignVerificationKey {
     }
 }
 
+/// Build the verification key of an RSA key whose SubjectPublicKeyInfo uses
+/// the `id-RSASSA-PSS` algorithm, as described by RFC 4055
+fn rsa_pss_verification_key(
+    subject_pub_key_info: &SubjectPublicKeyInfo<'_>,
+) -> Result<CosignVerificationKey> {
+    let pubkey = rsa::RsaPublicKey::from_pkcs1_der(subject_pub_key_info.subject_public_key)
+        .map_err(|e| {
+            SigstoreError::PKCS8SpkiError(format!("RSA from der bytes to public key failed: {e}"))
+        })?;
+    let digest_algorithm = match subject_pub_key_info.algorithm.parameters {
+        Some(parameters) => {
+            parameters
+                .decode_into::<RsaPssParams>()
+                .map_err(|e| {
+                    SigstoreError::PKCS8SpkiError(format!("invalid RSASSA-PSS parameters: {e}"))
+                })?
+                .hash
+                .oid
+        }
+        None => ID_SHA_256,
+    };
+    match digest_algorithm {
+        ID_SHA_256 => Ok(CosignVerificationKey::RSA_PSS_SHA256(
+            pss::VerifyingKey::new(pubkey),
+        )),
+        ID_SHA_384 => Ok(CosignVerificationKey::RSA_PSS_SHA384(
+            pss::VerifyingKey::new(pubkey),
+        )),
+        ID_SHA_512 => Ok(CosignVerificationKey::RSA_PSS_SHA512(
+            pss::VerifyingKey::new(pubkey),
+        )),
+        oid => Err(SigstoreError::PublicKeyUnsupportedAlgorithmError(format!(
+            "RSASSA-PSS with digest algorithm OID {oid} is not supported"
+        ))),
+    }
+}
+
 impl CosignVerificationKey {
     /// Builds a [`CosignVerificationKey`] from DER-encoded data. The methods takes care
     /// of extracting the SubjectPublicKeyInfo from the DER-encoded data.
@@ -189,6 +258,13 @@ impl CosignVerificationKey {
                     ))
                 })?,
             ),
+            SigningScheme::ECDSA_P521_SHA512 => CosignVerificationKey::ECDSA_P521_SHA512(
+                p521::PublicKey::from_public_key_der(der_data).map_err(|e| {
+                    SigstoreError::PKCS8SpkiError(format!(
+                        "Ecdsa-P521 from der bytes to public key failed: {e}"
+                    ))
+                })?,
+            ),
             SigningScheme::ED25519 => CosignVerificationKey::ED25519(
                 ed25519_dalek::VerifyingKey::from_public_key_der(der_data)?,
             ),
@@ -200,12 +276,15 @@ impl CosignVerificationKey {
     /// * `RSA public key`: `RSA_PKCS1_SHA256`
     /// * `EC public key with P-256 curve`: `ECDSA_P256_SHA256_ASN1`
     /// * `EC public key with P-384 curve`: `ECDSA_P384_SHA384_ASN1`
+    /// * `EC public key with P-521 curve`: `ECDSA_P521_SHA512`
     /// * `Ed25519 public key`: `Ed25519`
     pub fn try_from_der(der_data: &[u8]) -> Result<Self> {
         if let Ok(p256vk) = ecdsa::VerifyingKey::from_public_key_der(der_data) {
             Ok(Self::ECDSA_P256_SHA256_ASN1(p256vk))
         } else if let Ok(p384vk) = ecdsa::VerifyingKey::from_public_key_der(der_data) {
             Ok(Self::ECDSA_P384_SHA384_ASN1(p384vk))
+        } else if let Ok(p521pk) = p521::PublicKey::from_public_key_der(der_data) {
+            Ok(Self::ECDSA_P521_SHA512(p521pk))
         } else if let Ok(ed25519bytes) =
             ed25519::pkcs8::PublicKeyBytes::from_public_key_der(der_data)
         {
@@ -236,6 +315,7 @@ impl CosignVerificationKey {
     /// * `RSA public key`: `RSA_PKCS1_SHA256`
     /// * `EC public key with P-256 curve`: `ECDSA_P256_SHA256_ASN1`
     /// * `EC public key with P-384 curve`: `ECDSA_P384_SHA384_ASN1`
+    /// * `EC public key with P-521 curve`: `ECDSA_P521_SHA512`
     /// * `Ed25519 public key`: `Ed25519`
     pub fn try_from_pem(pem_data: &[u8]) -> Result<Self> {
         let key_pem = pem::parse(pem_data)?;
@@ -254,13 +334,334 @@ impl CosignVerificationKey {
         signer.to_verification_key(signing_scheme)
     }
 
+    /// Builds a [`CosignVerificationKey`] from the public key of the
+    /// DER-encoded x509 certificate. The verification algorithm is set as
+    /// for the [SubjectPublicKeyInfo] of the certificates, see the
+    /// implementation of `TryFrom<&SubjectPublicKeyInfo>`.
+    pub fn from_certificate_der(cert_der: &[u8]) -> Result<Self> {
+        let cert = x509_cert::Certificate::from_der(cert_der)
+            .map_err(|e| SigstoreError::X509Error(format!("parse from der: {e}")))?;
+        Self::try_from(&cert.tbs_certificate.subject_public_key_info)
+    }
+
+    /// Builds a [`CosignVerificationKey`] from the public key of the
+    /// PEM-encoded x509 certificate, see [`from_certificate_der`](Self::from_certificate_der)
+    pub fn from_certificate_pem(cert_pem: &[u8]) -> Result<Self> {
+        let cert_pem = pem::parse(cert_pem)?;
+        Self::from_certificate_der(cert_pem.contents.as_slice())
+    }
+
+    /// Export the key in DER-encoded SPKI format. The RSA keys are encoded
+    /// as `rsaEncryption` keys, whatever their padding.
+    pub fn to_der(&self) -> Result<Vec<u8>> {
+        let der = match self {
+            CosignVerificationKey::RSA_PSS_SHA256(inner) => inner.as_ref().to_public_key_der(),
+            CosignVerificationKey::RSA_PSS_SHA384(inner) => inner.as_ref().to_public_key_der(),
+            CosignVerificationKey::RSA_PSS_SHA512(inner) => inner.as_ref().to_public_key_der(),
+            CosignVerificationKey::RSA_PKCS1_SHA256(inner) => inner.as_ref().to_public_key_der(),
+            CosignVerificationKey::RSA_PKCS1_SHA384(inner) => inner.as_ref().to_public_key_der(),
+            CosignVerificationKey::RSA_PKCS1_SHA512(inner) => inner.as_ref().to_public_key_der(),
+            CosignVerificationKey::ECDSA_P256_SHA256_ASN1(inner) => inner.to_public_key_der(),
+            CosignVerificationKey::ECDSA_P384_SHA384_ASN1(inner) => inner.to_public_key_der(),
+            CosignVerificationKey::ECDSA_P521_SHA512(inner) => {
+                return p521::pkcs8::EncodePublicKey::to_public_key_der(inner)
+                    .map(|der| der.as_bytes().to_vec())
+                    .map_err(|e| SigstoreError::PKCS8SpkiError(e.to_string()))
+            }
+            CosignVerificationKey::ED25519(inner) => inner.to_public_key_der(),
+        };
+        Ok(der
+            .map_err(|e| SigstoreError::PKCS8SpkiError(e.to_string()))?
+            .to_vec())
+    }
+
+    /// Export the key in PEM-encoded SPKI format, see [`to_der`](Self::to_der)
+    pub fn to_pem(&self) -> Result<String> {
+        let line_ending = pkcs8::LineEnding::LF;
+        let pem = match self {
+            CosignVerificationKey::RSA_PSS_SHA256(inner) => {
+                inner.as_ref().to_public_key_pem(line_ending)
+            }
+            CosignVerificationKey::RSA_PSS_SHA384(inner) => {
+                inner.as_ref().to_public_key_pem(line_ending)
+            }
+            CosignVerificationKey::RSA_PSS_SHA512(inner) => {
+                inner.as_ref().to_public_key_pem(line_ending)
+            }
+            CosignVerificationKey::RSA_PKCS1_SHA256(inner) => {
+                inner.as_ref().to_public_key_pem(line_ending)
+            }
+            CosignVerificationKey::RSA_PKCS1_SHA384(inner) => {
+                inner.as_ref().to_public_key_pem(line_ending)
+            }
+            CosignVerificationKey::RSA_PKCS1_SHA512(inner) => {
+                inner.as_ref().to_public_key_pem(line_ending)
+            }
+            CosignVerificationKey::ECDSA_P256_SHA256_ASN1(inner) => {
+                inner.to_public_key_pem(line_ending)
+            }
+            CosignVerificationKey::ECDSA_P384_SHA384_ASN1(inner) => {
+                inner.to_public_key_pem(line_ending)
+            }
+            CosignVerificationKey::ECDSA_P521_SHA512(inner) => {
+                return p521::pkcs8::EncodePublicKey::to_public_key_pem(
+                    inner,
+                    p521::pkcs8::LineEnding::LF,
+                )
+                .map_err(|e| SigstoreError::PKCS8SpkiError(e.to_string()))
+            }
+            CosignVerificationKey::ED25519(inner) => inner.to_public_key_pem(line_ending),
+        };
+        pem.map_err(|e| SigstoreError::PKCS8SpkiError(e.to_string()))
+    }
+
+    /// The ID of the key: the SHA-256 digest of its DER encoded SPKI.
+    ///
+    /// This is the ID of the keys of the transparency logs, and the one
+    /// of the `keyid` of the DSSE signatures produced by sigstore.
+    pub fn key_id(&self) -> Result<Vec<u8>> {
+        Ok(Sha256::digest(self.to_der()?).to_vec())
+    }
+
+    /// The hex encoded [`key_id`](Self::key_id), like the log IDs of the
+    /// Rekor entries
+    pub fn key_id_hex(&self) -> Result<String> {
+        Ok(hex::encode(self.key_id()?))
+    }
+
+    /// The base64 encoded [`key_id`](Self::key_id), like the `keyId` of the
+    /// log IDs of the trusted roots and of the bundles
+    pub fn key_id_base64(&self) -> Result<String> {
+        Ok(BASE64_STD_ENGINE.encode(self.key_id()?))
+    }
+
+    /// The digest algorithm used to hash the messages before verifying their
+    /// signature, `None` for Ed25519 keys: Ed25519 signatures are computed
+    /// over the whole message.
+    pub fn hash_algorithm(&self) -> Option<HashAlgorithm> {
+        match self {
+            CosignVerificationKey::RSA_PSS_SHA256(_)
+            | CosignVerificationKey::RSA_PKCS1_SHA256(_)
+            | CosignVerificationKey::ECDSA_P256_SHA256_ASN1(_) => Some(HashAlgorithm::Sha256),
+            CosignVerificationKey::RSA_PSS_SHA384(_)
+            | CosignVerificationKey::RSA_PKCS1_SHA384(_)
+            | CosignVerificationKey::ECDSA_P384_SHA384_ASN1(_) => Some(HashAlgorithm::Sha384),
+            CosignVerificationKey::RSA_PSS_SHA512(_)
+            | CosignVerificationKey::RSA_PKCS1_SHA512(_)
+            | CosignVerificationKey::ECDSA_P521_SHA512(_) => Some(HashAlgorithm::Sha512),
+            CosignVerificationKey::ED25519(_) => None,
+        }
+    }
+
+    /// The same key, verifying the signatures of the messages hashed with
+    /// `algorithm` instead of its own [`hash_algorithm`](Self::hash_algorithm).
+    ///
+    /// RSA keys keep their padding. The digest of ECDSA keys is tied to
+    /// their curve, hence only their own algorithm is accepted: use
+    /// [`verify_signature_with_hash_algorithm`](Self::verify_signature_with_hash_algorithm)
+    /// to verify signatures computed over another digest. Ed25519 keys don't
+    /// hash the messages.
+    pub fn with_hash_algorithm(self, algorithm: HashAlgorithm) -> Result<Self> {
+        if self.hash_algorithm() == Some(algorithm) {
+            return Ok(self);
+        }
+
+        let pss = matches!(
+            self,
+            CosignVerificationKey::RSA_PSS_SHA256(_)
+                | CosignVerificationKey::RSA_PSS_SHA384(_)
+                | CosignVerificationKey::RSA_PSS_SHA512(_)
+        );
+        let key = match &self {
+            CosignVerificationKey::RSA_PSS_SHA256(inner) => inner.as_ref().clone(),
+            CosignVerificationKey::RSA_PSS_SHA384(inner) => inner.as_ref().clone(),
+            CosignVerificationKey::RSA_PSS_SHA512(inner) => inner.as_ref().clone(),
+            CosignVerificationKey::RSA_PKCS1_SHA256(inner) => inner.as_ref().clone(),
+            CosignVerificationKey::RSA_PKCS1_SHA384(inner) => inner.as_ref().clone(),
+            CosignVerificationKey::RSA_PKCS1_SHA512(inner) => inner.as_ref().clone(),
+            CosignVerificationKey::ECDSA_P256_SHA256_ASN1(_)
+            | CosignVerificationKey::ECDSA_P384_SHA384_ASN1(_)
+            | CosignVerificationKey::ECDSA_P521_SHA512(_) => {
+                return Err(SigstoreError::PublicKeyUnsupportedAlgorithmError(format!(
+                    "the digest of the ECDSA key is tied to its curve, {algorithm:?} cannot be used"
+                )))
+            }
+            CosignVerificationKey::ED25519(_) => {
+                return Err(SigstoreError::PublicKeyUnsupportedAlgorithmError(
+                    "Ed25519 keys don't hash the messages".to_string(),
+                ))
+            }
+        };
+
+        Ok(match (pss, algorithm) {
+            (true, HashAlgorithm::Sha256) => {
+                CosignVerificationKey::RSA_PSS_SHA256(pss::VerifyingKey::new(key))
+            }
+            (true, HashAlgorithm::Sha384) => {
+                CosignVerificationKey::RSA_PSS_SHA384(pss::VerifyingKey::new(key))
+            }
+            (true, HashAlgorithm::Sha512) => {
+                CosignVerificationKey::RSA_PSS_SHA512(pss::VerifyingKey::new(key))
+            }
+            (false, HashAlgorithm::Sha256) => CosignVerificationKey::RSA_PKCS1_SHA256(
+                pkcs1v15::VerifyingKey::new_with_prefix(key),
+            ),
+            (false, HashAlgorithm::Sha384) => CosignVerificationKey::RSA_PKCS1_SHA384(
+                pkcs1v15::VerifyingKey::new_with_prefix(key),
+            ),
+            (false, HashAlgorithm::Sha512) => CosignVerificationKey::RSA_PKCS1_SHA512(
+                pkcs1v15::VerifyingKey::new_with_prefix(key),
+            ),
+        })
+    }
+
+    /// Verify the signature provided has been actually generated by the given key
+    /// when signing the message hashed with `algorithm`.
+    ///
+    /// [`verify_signature`](Self::verify_signature) uses the digest algorithm
+    /// of the key, SHA-256 for the RSA keys read from a certificate. The
+    /// signatures of some private CAs, or made with P-256 keys over a SHA-384
+    /// or SHA-512 digest, need another one. The algorithm of a certificate or
+    /// CRL signature can be found with
+    /// [`HashAlgorithm::from_signature_algorithm`]. Ed25519 keys are not
+    /// supported.
+    pub fn verify_signature_with_hash_algorithm(
+        &self,
+        signature: Signature,
+        msg: &[u8],
+        algorithm: HashAlgorithm,
+    ) -> Result<()> {
+        let digest = algorithm.digest(msg);
+        match self {
+            // The digest is truncated, or padded, to the size of the curve
+            CosignVerificationKey::ECDSA_P256_SHA256_ASN1(_)
+            | CosignVerificationKey::ECDSA_P384_SHA384_ASN1(_)
+            | CosignVerificationKey::ECDSA_P521_SHA512(_) => {
+                self.verify_prehash(signature, &digest)
+            }
+            _ => self
+                .clone()
+                .with_hash_algorithm(algorithm)?
+                .verify_prehash(signature, &digest),
+        }
+    }
+
+    /// Verify the signature provided has been actually generated by the given key
+    /// when signing the message whose digest is `digest`.
+    ///
+    /// The digest must have been computed with the [`hash_algorithm`](Self::hash_algorithm)
+    /// of the key. Ed25519 keys are not supported.
+    pub fn verify_prehash(&self, signature: Signature, digest: &[u8]) -> Result<()> {
+        let sig = decode_signature(signature)?;
+
+        match self {
+            CosignVerificationKey::RSA_PSS_SHA256(inner) => {
+                let sig = pss::Signature::try_from(sig.as_slice())?;
+                inner
+                    .verify_prehash(digest, &sig)
+                    .map_err(|_| SigstoreError::PublicKeyVerificationError)
+            }
+            CosignVerificationKey::RSA_PSS_SHA384(inner) => {
+                let sig = pss::Signature::try_from(sig.as_slice())?;
+                inner
+                    .verify_prehash(digest, &sig)
+                    .map_err(|_| SigstoreError::PublicKeyVerificationError)
+            }
+            CosignVerificationKey::RSA_PSS_SHA512(inner) => {
+                let sig = pss::Signature::try_from(sig.as_slice())?;
+                inner
+                    .verify_prehash(digest, &sig)
+                    .map_err(|_| SigstoreError::PublicKeyVerificationError)
+            }
+            CosignVerificationKey::RSA_PKCS1_SHA256(inner) => {
+                let sig = pkcs1v15::Signature::try_from(sig.as_slice())?;
+                inner
+                    .verify_prehash(digest, &sig)
+                    .map_err(|_| SigstoreError::PublicKeyVerificationError)
+            }
+            CosignVerificationKey::RSA_PKCS1_SHA384(inner) => {
+                let sig = pkcs1v15::Signature::try_from(sig.as_slice())?;
+                inner
+                    .verify_prehash(digest, &sig)
+                    .map_err(|_| SigstoreError::PublicKeyVerificationError)
+            }
+            CosignVerificationKey::RSA_PKCS1_SHA512(inner) => {
+                let sig = pkcs1v15::Signature::try_from(sig.as_slice())?;
+                inner
+                    .verify_prehash(digest, &sig)
+                    .map_err(|_| SigstoreError::PublicKeyVerificationError)
+            }
+            CosignVerificationKey::ECDSA_P256_SHA256_ASN1(inner) => {
+                let sig = ecdsa::Signature::from_der(&sig)?;
+                inner
+                    .verify_prehash(digest, &sig)
+                    .map_err(|_| SigstoreError::PublicKeyVerificationError)
+            }
+            CosignVerificationKey::ECDSA_P384_SHA384_ASN1(inner) => {
+                let sig = ecdsa::Signature::from_der(&sig)?;
+                inner
+                    .verify_prehash(digest, &sig)
+                    .map_err(|_| SigstoreError::PublicKeyVerificationError)
+            }
+            CosignVerificationKey::ECDSA_P521_SHA512(inner) => {
+                let sig = p521::ecdsa::Signature::from_der(&sig)
+                    .map_err(|_| SigstoreError::PublicKeyVerificationError)?;
+                p521::ecdsa::VerifyingKey::from_affine(*inner.as_affine())
+                    .and_then(|key| key.verify_prehash(digest, &sig))
+                    .map_err(|_| SigstoreError::PublicKeyVerificationError)
+            }
+            CosignVerificationKey::ED25519(_) => {
+                Err(SigstoreError::PublicKeyUnsupportedAlgorithmError(
+                    "Ed25519 signatures cannot be verified from a digest".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Verify the signature provided has been actually generated by the given key
+    /// when signing the message read from `reader`.
+    ///
+    /// The message is hashed while it is read, hence it's never loaded in
+    /// memory. The only exception are Ed25519 keys, whose signatures are
+    /// computed over the whole message.
+    pub fn verify_signature_from_reader<R: Read>(
+        &self,
+        signature: Signature,
+        mut reader: R,
+    ) -> Result<()> {
+        match self.hash_algorithm() {
+            Some(algorithm) => self.verify_prehash(signature, &algorithm.digest_reader(reader)?),
+            None => {
+                let mut msg = Vec::new();
+                reader.read_to_end(&mut msg)?;
+                self.verify_signature(signature, &msg)
+            }
+        }
+    }
+
+    /// Asynchronous version of [`verify_signature_from_reader`](Self::verify_signature_from_reader)
+    pub async fn verify_signature_from_async_reader<R: AsyncRead + Unpin>(
+        &self,
+        signature: Signature<'_>,
+        mut reader: R,
+    ) -> Result<()> {
+        match self.hash_algorithm() {
+            Some(algorithm) => {
+                let digest = algorithm.digest_async_reader(reader).await?;
+                self.verify_prehash(signature, &digest)
+            }
+            None => {
+                let mut msg = Vec::new();
+                reader.read_to_end(&mut msg).await?;
+                self.verify_signature(signature, &msg)
+            }
+        }
+    }
+
     /// Verify the signature provided has been actually generated by the given key
     /// when signing the provided message.
     pub fn verify_signature(&self, signature: Signature, msg: &[u8]) -> Result<()> {
-        let sig = match signature {
-            Signature::Raw(data) => data.to_owned(),
-            Signature::Base64Encoded(data) => BASE64_STD_ENGINE.decode(data)?,
-        };
+        let sig = decode_signature(signature)?;
 
         match self {
             CosignVerificationKey::RSA_PSS_SHA256(inner) => {
@@ -316,6 +717,13 @@ impl CosignVerificationKey {
                     .verify_digest(hasher, &sig)
                     .map_err(|_| SigstoreError::PublicKeyVerificationError)
             }
+            CosignVerificationKey::ECDSA_P521_SHA512(inner) => {
+                let sig = p521::ecdsa::Signature::from_der(&sig)
+                    .map_err(|_| SigstoreError::PublicKeyVerificationError)?;
+                p521::ecdsa::VerifyingKey::from_affine(*inner.as_affine())
+                    .and_then(|key| key.verify(msg, &sig))
+                    .map_err(|_| SigstoreError::PublicKeyVerificationError)
+            }
             CosignVerificationKey::ED25519(inner) => {
                 let sig = ed25519::Signature::from_slice(sig.as_slice())
                     .map_err(|_| SigstoreError::PublicKeyVerificationError)?;
@@ -325,6 +733,54 @@ impl CosignVerificationKey {
             }
         }
     }
+
+    /// Verify the signature provided, encoded in `format`, has been actually
+    /// generated by the given key when signing the provided message.
+    ///
+    /// The format matters only for ECDSA keys: the RSA and Ed25519 signatures
+    /// are always fixed size strings of bytes.
+    pub fn verify_signature_with_format(
+        &self,
+        signature: Signature,
+        msg: &[u8],
+        format: SignatureFormat,
+    ) -> Result<()> {
+        if format == SignatureFormat::Asn1Der {
+            return self.verify_signature(signature, msg);
+        }
+
+        let sig = decode_signature(signature)?;
+        let sig = match self {
+            CosignVerificationKey::ECDSA_P256_SHA256_ASN1(_) => {
+                ecdsa::Signature::<p256::NistP256>::try_from(sig.as_slice())?
+                    .to_der()
+                    .as_bytes()
+                    .to_vec()
+            }
+            CosignVerificationKey::ECDSA_P384_SHA384_ASN1(_) => {
+                ecdsa::Signature::<p384::NistP384>::try_from(sig.as_slice())?
+                    .to_der()
+                    .as_bytes()
+                    .to_vec()
+            }
+            CosignVerificationKey::ECDSA_P521_SHA512(_) => {
+                p521::ecdsa::Signature::try_from(sig.as_slice())
+                    .map_err(|_| SigstoreError::PublicKeyVerificationError)?
+                    .to_der()
+                    .as_bytes()
+                    .to_vec()
+            }
+            _ => sig,
+        };
+        self.verify_signature(Signature::Raw(&sig), msg)
+    }
+}
+
+fn decode_signature(signature: Signature) -> Result<Vec<u8>> {
+    Ok(match signature {
+        Signature::Raw(data) => data.to_owned(),
+        Signature::Base64Encoded(data) => BASE64_STD_ENGINE.decode(data)?,
+    })
 }
 
 #[cfg(test)]
@@ -514,6 +970,107 @@ DwIDAQAB
         Ok(())
     }
 
+    #[test]
+    fn verify_rsa_signatures_with_subject_public_key() -> anyhow::Result<()> {
+        use pkcs8::{AlgorithmIdentifier, EncodePublicKey};
+        use rsa::pkcs1::EncodeRsaPublicKey;
+        use signature::{RandomizedSigner, SignatureEncoding, Signer};
+
+        let msg = b"hello world";
+        let private_key = rsa::RsaPrivateKey::new(&mut rand::rngs::OsRng, 2048)?;
+        let public_key = private_key.to_public_key();
+
+        // rsaEncryption keys are used with PKCS1 padding
+        let spki_der = public_key.to_public_key_der()?;
+        let spki = SubjectPublicKeyInfo::from_der(spki_der.as_bytes())?;
+        let verification_key = CosignVerificationKey::try_from(&spki)?;
+        let signature =
+            pkcs1v15::SigningKey::<sha2::Sha256>::new_with_prefix(private_key.clone()).sign(msg);
+        assert!(verification_key
+            .verify_signature(Signature::Raw(&signature.to_vec()), msg)
+            .is_ok());
+
+        // id-RSASSA-PSS keys are used with PSS padding
+        let pkcs1_der = public_key.to_pkcs1_der()?;
+        let spki = SubjectPublicKeyInfo {
+            algorithm: AlgorithmIdentifier {
+                oid: ID_RSASSA_PSS,
+                parameters: None,
+            },
+            subject_public_key: pkcs1_der.as_bytes(),
+        };
+        let verification_key = CosignVerificationKey::try_from(&spki)?;
+        assert!(matches!(
+            verification_key,
+            CosignVerificationKey::RSA_PSS_SHA256(_)
+        ));
+        let signature = pss::BlindedSigningKey::<sha2::Sha256>::new(private_key)
+            .sign_with_rng(&mut rand::rngs::OsRng, msg);
+        assert!(verification_key
+            .verify_signature(Signature::Raw(&signature.to_vec()), msg)
+            .is_ok());
+        assert!(verification_key
+            .verify_signature(Signature::Raw(&signature.to_vec()), b"tampered")
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sign_and_verify_with_ecdsa_p521_key() -> anyhow::Result<()> {
+        use openssl::ec::{EcGroup, EcKey};
+        use openssl::hash::MessageDigest;
+        use openssl::nid::Nid;
+        use openssl::pkey::PKey;
+
+        let group = EcGroup::from_curve_name(Nid::SECP521R1)?;
+        let private_key = PKey::from_ec_key(EcKey::generate(&group)?)?;
+        let spki_der = private_key.public_key_to_der()?;
+
+        let msg = b"hello world";
+        let mut signer = openssl::sign::Signer::new(MessageDigest::sha512(), &private_key)?;
+        signer.update(msg)?;
+        let signature = signer.sign_to_vec()?;
+
+        // the curve is detected from the key parameters
+        let spki = SubjectPublicKeyInfo::from_der(&spki_der)?;
+        let verification_key = CosignVerificationKey::try_from(&spki)?;
+        assert!(matches!(
+            verification_key,
+            CosignVerificationKey::ECDSA_P521_SHA512(_)
+        ));
+        assert_eq!(
+            verification_key.hash_algorithm(),
+            Some(HashAlgorithm::Sha512)
+        );
+        assert!(verification_key
+            .verify_signature(Signature::Raw(&signature), msg)
+            .is_ok());
+        assert!(verification_key
+            .verify_prehash(
+                Signature::Raw(&signature),
+                &HashAlgorithm::Sha512.digest(msg)
+            )
+            .is_ok());
+        assert!(matches!(
+            verification_key.verify_signature(Signature::Raw(&signature), b"another message"),
+            Err(SigstoreError::PublicKeyVerificationError)
+        ));
+
+        // the key can be exported and read again
+        assert_eq!(verification_key.to_der()?, spki_der);
+        for key in [
+            CosignVerificationKey::from_der(&spki_der, &SigningScheme::ECDSA_P521_SHA512)?,
+            CosignVerificationKey::try_from_der(&spki_der)?,
+            CosignVerificationKey::try_from_pem(verification_key.to_pem()?.as_bytes())?,
+        ] {
+            assert!(matches!(key, CosignVerificationKey::ECDSA_P521_SHA512(_)));
+            assert!(key
+                .verify_signature(Signature::Raw(&signature), msg)
+                .is_ok());
+        }
+        Ok(())
+    }
+
     #[test]
     fn convert_ed25519_subject_public_key_to_cosign_verification_key() -> anyhow::Result<()> {
         let (private_key, public_key) = generate_ed25519_keypair();
@@ -567,4 +1124,205 @@ DwIDAQAB
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn verify_signature_from_reader() {
+        let msg = b"a message too large to be loaded in memory";
+
+        for scheme in [
+            SigningScheme::RSA_PSS_SHA512(2048),
+            SigningScheme::RSA_PKCS1_SHA384(2048),
+            SigningScheme::ECDSA_P256_SHA256_ASN1,
+            SigningScheme::ECDSA_P384_SHA384_ASN1,
+            SigningScheme::ED25519,
+        ] {
+            let signer = scheme.create_signer().expect("Cannot create signer");
+            let verification_key = signer.to_verification_key().unwrap();
+            let sig = signer.sign(msg).unwrap();
+
+            assert!(verification_key
+                .verify_signature_from_reader(Signature::Raw(&sig), &msg[..])
+                .is_ok());
+            assert!(verification_key
+                .verify_signature_from_async_reader(Signature::Raw(&sig), &msg[..])
+                .await
+                .is_ok());
+            assert!(matches!(
+                verification_key.verify_signature_from_reader(Signature::Raw(&sig), &b"hello"[..]),
+                Err(SigstoreError::PublicKeyVerificationError)
+            ));
+
+            match verification_key.hash_algorithm() {
+                Some(algorithm) => assert!(verification_key
+                    .verify_prehash(Signature::Raw(&sig), &algorithm.digest(msg))
+                    .is_ok()),
+                None => assert!(matches!(
+                    verification_key.verify_prehash(Signature::Raw(&sig), msg),
+                    Err(SigstoreError::PublicKeyUnsupportedAlgorithmError(_))
+                )),
+            }
+        }
+    }
+
+    #[test]
+    fn verify_signature_with_hash_algorithm() {
+        let msg = b"signed by a private CA";
+
+        // an RSA key read from a certificate assumes SHA-256
+        let signer = SigningScheme::RSA_PKCS1_SHA384(2048)
+            .create_signer()
+            .expect("Cannot create signer");
+        let sig = signer.sign(msg).unwrap();
+        let verification_key = signer
+            .to_verification_key()
+            .unwrap()
+            .with_hash_algorithm(HashAlgorithm::Sha256)
+            .unwrap();
+        assert!(verification_key
+            .verify_signature(Signature::Raw(&sig), msg)
+            .is_err());
+        assert!(verification_key
+            .verify_signature_with_hash_algorithm(Signature::Raw(&sig), msg, HashAlgorithm::Sha384)
+            .is_ok());
+
+        // a P-256 key signing a SHA-512 digest
+        let signing_key = p256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        let sig: p256::ecdsa::Signature = signature::hazmat::PrehashSigner::sign_prehash(
+            &signing_key,
+            &HashAlgorithm::Sha512.digest(msg),
+        )
+        .unwrap();
+        let verification_key =
+            CosignVerificationKey::ECDSA_P256_SHA256_ASN1(*signing_key.verifying_key());
+        let sig = sig.to_der();
+        assert!(verification_key
+            .verify_signature_with_hash_algorithm(
+                Signature::Raw(sig.as_bytes()),
+                msg,
+                HashAlgorithm::Sha512
+            )
+            .is_ok());
+        assert!(verification_key
+            .verify_signature(Signature::Raw(sig.as_bytes()), msg)
+            .is_err());
+        assert!(matches!(
+            verification_key.with_hash_algorithm(HashAlgorithm::Sha512),
+            Err(SigstoreError::PublicKeyUnsupportedAlgorithmError(_))
+        ));
+    }
+
+    #[test]
+    fn verify_signature_with_format() {
+        let msg = b"signed by a hardware token";
+
+        for scheme in [
+            SigningScheme::ECDSA_P256_SHA256_ASN1,
+            SigningScheme::ECDSA_P384_SHA384_ASN1,
+        ] {
+            let signer = scheme.create_signer().expect("Cannot create signer");
+            let verification_key = signer.to_verification_key().unwrap();
+            let der = signer.sign(msg).unwrap();
+            let raw = match &verification_key {
+                CosignVerificationKey::ECDSA_P256_SHA256_ASN1(_) => {
+                    ecdsa::Signature::<p256::NistP256>::from_der(&der)
+                        .unwrap()
+                        .to_vec()
+                }
+                _ => ecdsa::Signature::<p384::NistP384>::from_der(&der)
+                    .unwrap()
+                    .to_vec(),
+            };
+
+            assert!(verification_key
+                .verify_signature_with_format(Signature::Raw(&raw), msg, SignatureFormat::Raw)
+                .is_ok());
+            assert!(verification_key
+                .verify_signature_with_format(Signature::Raw(&der), msg, SignatureFormat::Asn1Der)
+                .is_ok());
+            assert!(verification_key
+                .verify_signature_with_format(Signature::Raw(&der), msg, SignatureFormat::Raw)
+                .is_err());
+        }
+
+        // Ed25519 signatures are always raw
+        let signer = SigningScheme::ED25519.create_signer().unwrap();
+        let sig = signer.sign(msg).unwrap();
+        assert!(signer
+            .to_verification_key()
+            .unwrap()
+            .verify_signature_with_format(Signature::Raw(&sig), msg, SignatureFormat::Raw)
+            .is_ok());
+    }
+
+    #[test]
+    fn key_id_of_verification_keys() {
+        for scheme in [
+            SigningScheme::RSA_PSS_SHA256(2048),
+            SigningScheme::ECDSA_P256_SHA256_ASN1,
+            SigningScheme::ECDSA_P384_SHA384_ASN1,
+            SigningScheme::ED25519,
+        ] {
+            let signer = scheme.create_signer().expect("Cannot create signer");
+            let der = signer.public_key_to_der().unwrap();
+            let verification_key = signer.to_verification_key().unwrap();
+
+            assert_eq!(verification_key.to_der().unwrap(), der);
+            assert_eq!(
+                verification_key.key_id_hex().unwrap(),
+                hex::encode(Sha256::digest(&der))
+            );
+            assert_eq!(
+                BASE64_STD_ENGINE
+                    .decode(verification_key.key_id_base64().unwrap())
+                    .unwrap(),
+                verification_key.key_id().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn export_verification_keys() {
+        for scheme in [
+            SigningScheme::RSA_PKCS1_SHA256(2048),
+            SigningScheme::ECDSA_P256_SHA256_ASN1,
+            SigningScheme::ED25519,
+        ] {
+            let signer = scheme.create_signer().expect("Cannot create signer");
+            let verification_key = signer.to_verification_key().unwrap();
+
+            let pem = verification_key.to_pem().unwrap();
+            assert_eq!(pem, signer.public_key_to_pem().unwrap());
+            let imported = CosignVerificationKey::try_from_pem(pem.as_bytes()).unwrap();
+            assert_eq!(
+                imported.to_der().unwrap(),
+                verification_key.to_der().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn verification_key_from_certificate() {
+        let ca_data = generate_certificate(None, CertGenerationOptions::default())
+            .expect("Cannot generate certificate");
+        let cert_pem = ca_data.cert.to_pem().unwrap();
+
+        let verification_key = CosignVerificationKey::from_certificate_pem(&cert_pem).unwrap();
+        assert!(matches!(
+            verification_key,
+            CosignVerificationKey::ECDSA_P256_SHA256_ASN1(_)
+        ));
+        assert_eq!(
+            verification_key.to_der().unwrap(),
+            ca_data
+                .cert
+                .public_key()
+                .unwrap()
+                .public_key_to_der()
+                .unwrap()
+        );
+        assert!(matches!(
+            CosignVerificationKey::from_certificate_der(b"not a certificate"),
+            Err(SigstoreError::X509Error(_))
+        ));
+    }
 }
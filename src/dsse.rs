@@ -0,0 +1,316 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The [Dead Simple Signing Envelope](https://github.com/secure-systems-lab/dsse).
+//!
+//! A DSSE envelope holds a payload of any type, together with any number of
+//! signatures of it. The signatures are computed over the
+//! [Pre-Authentication Encoding](pae) of the payload and of its type.
+//! The envelopes are used by in-toto attestations and by the `dsse` entries
+//! of Rekor.
+//!
+//! ```rust
+//! use sigstore::crypto::SigningScheme;
+//! use sigstore::dsse::{Envelope, EnvelopeVerifier};
+//!
+//! # fn doc() -> sigstore::errors::Result<()> {
+//! let alice = SigningScheme::default().create_signer()?;
+//! let bob = SigningScheme::default().create_signer()?;
+//!
+//! let mut envelope = Envelope::new("text/plain", b"hello");
+//! envelope.sign(&alice, Some("alice"))?;
+//! envelope.sign(&bob, Some("bob"))?;
+//!
+//! // Both alice and bob must have signed the envelope
+//! let payload = EnvelopeVerifier::new()
+//!     .with_key(Some("alice"), alice.to_verification_key()?)
+//!     .with_key(Some("bob"), bob.to_verification_key()?)
+//!     .with_threshold(2)
+//!     .verify(&envelope)?;
+//! assert_eq!(payload, b"hello");
+//! # Ok(())
+//! # }
+//! ```
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::crypto::{CosignVerificationKey, SigStoreSigner, Signature};
+use crate::errors::{Result, SigstoreError};
+
+pub use crate::portable::dsse::pae;
+
+/// A DSSE envelope
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Envelope {
+    /// The type of the payload, e.g. `application/vnd.in-toto+json` for
+    /// in-toto attestations
+    pub payload_type: String,
+    /// The base64 encoded payload
+    pub payload: String,
+    /// The signatures of the payload
+    pub signatures: Vec<EnvelopeSignature>,
+}
+
+/// The signature of a DSSE envelope
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EnvelopeSignature {
+    /// An optional hint about the key used to produce the signature
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub keyid: String,
+    /// The base64 encoded signature
+    pub sig: String,
+}
+
+impl Envelope {
+    /// Create an envelope holding `payload`, without any signature
+    pub fn new(payload_type: &str, payload: &[u8]) -> Self {
+        Envelope {
+            payload_type: payload_type.to_string(),
+            payload: BASE64_STD_ENGINE.encode(payload),
+            signatures: Vec::new(),
+        }
+    }
+
+    /// Parse a JSON encoded DSSE envelope
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        serde_json::from_slice(data)
+            .map_err(|e| SigstoreError::DsseError(format!("invalid envelope: {e}")))
+    }
+
+    /// The decoded payload of the envelope
+    pub fn decoded_payload(&self) -> Result<Vec<u8>> {
+        BASE64_STD_ENGINE
+            .decode(&self.payload)
+            .map_err(|e| SigstoreError::DsseError(format!("invalid payload encoding: {e}")))
+    }
+
+    /// The message signed by the signatures of the envelope
+    pub fn signed_message(&self) -> Result<Vec<u8>> {
+        Ok(pae(&self.payload_type, &self.decoded_payload()?))
+    }
+
+    /// Sign the payload with `signer` and add the signature to the
+    /// envelope. The `keyid` is a hint helping the verifiers to select the
+    /// key to use, it's not authenticated.
+    pub fn sign(&mut self, signer: &SigStoreSigner, keyid: Option<&str>) -> Result<()> {
+        let signature = signer.sign(&self.signed_message()?)?;
+        self.signatures.push(EnvelopeSignature {
+            keyid: keyid.unwrap_or_default().to_string(),
+            sig: BASE64_STD_ENGINE.encode(signature),
+        });
+        Ok(())
+    }
+}
+
+/// Verifier of DSSE envelopes, following the
+/// [DSSE protocol](https://github.com/secure-systems-lab/dsse/blob/master/protocol.md).
+///
+/// An envelope is accepted when at least `threshold` distinct keys have
+/// signed it, a single one by default. When both a signature and a key have
+/// a keyid, the key is used only for the signatures with the same keyid.
+///
+/// The keys are told apart by their [`key_id`](CosignVerificationKey::key_id):
+/// a key trusted under several keyids, or signing the envelope several
+/// times, counts once.
+#[derive(Debug, Clone)]
+pub struct EnvelopeVerifier {
+    keys: Vec<(Option<String>, CosignVerificationKey)>,
+    threshold: usize,
+}
+
+impl Default for EnvelopeVerifier {
+    fn default() -> Self {
+        EnvelopeVerifier {
+            keys: Vec::new(),
+            threshold: 1,
+        }
+    }
+}
+
+impl EnvelopeVerifier {
+    /// A verifier without any trusted key
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trust the signatures produced by `key`, identified by `keyid`
+    pub fn with_key(mut self, keyid: Option<&str>, key: CosignVerificationKey) -> Self {
+        self.keys.push((keyid.map(str::to_string), key));
+        self
+    }
+
+    /// Require the envelopes to be signed by at least `threshold` of the
+    /// trusted keys
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Verify the signatures of `envelope`, and return its decoded payload.
+    ///
+    /// The type of the payload is not checked, it's up to the caller to
+    /// ensure it's the expected one.
+    pub fn verify(&self, envelope: &Envelope) -> Result<Vec<u8>> {
+        let keys = self
+            .keys
+            .iter()
+            .map(|(keyid, key)| Ok((keyid, key, key.key_id()?)))
+            .collect::<Result<Vec<_>>>()?;
+        let distinct_keys = keys.iter().map(|(_, _, id)| id).collect::<HashSet<_>>();
+        if self.threshold == 0 || self.threshold > distinct_keys.len() {
+            return Err(SigstoreError::DsseError(format!(
+                "invalid threshold {} for {} distinct keys",
+                self.threshold,
+                distinct_keys.len()
+            )));
+        }
+
+        let payload = envelope.decoded_payload()?;
+        let message = pae(&envelope.payload_type, &payload);
+        // each signature is attributed to the first key verifying it
+        let verified_keys = envelope
+            .signatures
+            .iter()
+            .filter_map(|signature| {
+                keys.iter().find_map(|(keyid, key, id)| {
+                    let keyid_matches = match keyid {
+                        Some(keyid) if !signature.keyid.is_empty() => *keyid == signature.keyid,
+                        _ => true,
+                    };
+                    let verified = keyid_matches
+                        && key
+                            .verify_signature(
+                                Signature::Base64Encoded(signature.sig.as_bytes()),
+                                &message,
+                            )
+                            .is_ok();
+                    verified.then_some(id)
+                })
+            })
+            .collect::<HashSet<_>>()
+            .len();
+
+        if verified_keys < self.threshold {
+            return Err(SigstoreError::DsseError(format!(
+                "the envelope has been signed by {verified_keys} of the trusted keys, {} required",
+                self.threshold
+            )));
+        }
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::SigningScheme;
+
+    #[test]
+    fn pae_encoding() {
+        // Test vector of the DSSE protocol
+        assert_eq!(
+            pae("http://example.com/HelloWorld", b"hello world"),
+            b"DSSEv1 29 http://example.com/HelloWorld 11 hello world"
+        );
+    }
+
+    #[test]
+    fn verify_multi_signature_envelope() {
+        let alice = SigningScheme::default().create_signer().unwrap();
+        let bob = SigningScheme::ED25519.create_signer().unwrap();
+        let mallory = SigningScheme::default().create_signer().unwrap();
+
+        let mut envelope = Envelope::new("text/plain", b"hello");
+        envelope.sign(&alice, Some("alice")).unwrap();
+        envelope.sign(&bob, None).unwrap();
+        let envelope = Envelope::from_slice(&serde_json::to_vec(&envelope).unwrap()).unwrap();
+        assert_eq!(envelope.signatures[0].keyid, "alice");
+        assert!(envelope.signatures[1].keyid.is_empty());
+
+        let verifier = EnvelopeVerifier::new()
+            .with_key(Some("alice"), alice.to_verification_key().unwrap())
+            .with_key(Some("bob"), bob.to_verification_key().unwrap())
+            .with_key(None, mallory.to_verification_key().unwrap());
+        assert_eq!(verifier.verify(&envelope).unwrap(), b"hello");
+        assert!(verifier.clone().with_threshold(2).verify(&envelope).is_ok());
+        assert!(matches!(
+            verifier.clone().with_threshold(3).verify(&envelope),
+            Err(SigstoreError::DsseError(_))
+        ));
+        assert!(verifier.with_threshold(4).verify(&envelope).is_err());
+
+        let mut tampered = envelope;
+        tampered.payload_type = "application/json".to_string();
+        assert!(EnvelopeVerifier::new()
+            .with_key(None, alice.to_verification_key().unwrap())
+            .verify(&tampered)
+            .is_err());
+    }
+
+    #[test]
+    fn threshold_counts_distinct_keys() {
+        let alice = SigningScheme::default().create_signer().unwrap();
+        let mut envelope = Envelope::new("text/plain", b"hello");
+        envelope.sign(&alice, Some("alice")).unwrap();
+        envelope.sign(&alice, Some("alice-again")).unwrap();
+
+        // the same key, registered under two keyids
+        let key = alice.to_verification_key().unwrap();
+        let verifier = EnvelopeVerifier::new()
+            .with_key(Some("alice"), key.clone())
+            .with_key(Some("alice-again"), key.clone())
+            .with_threshold(2);
+        assert!(matches!(
+            verifier.verify(&envelope),
+            Err(SigstoreError::DsseError(_))
+        ));
+
+        // signing the envelope twice doesn't make up for another key
+        let bob = SigningScheme::default().create_signer().unwrap();
+        let mut envelope = Envelope::new("text/plain", b"hello");
+        envelope.sign(&alice, None).unwrap();
+        envelope.sign(&alice, None).unwrap();
+        assert!(EnvelopeVerifier::new()
+            .with_key(None, key)
+            .with_key(None, bob.to_verification_key().unwrap())
+            .with_threshold(2)
+            .verify(&envelope)
+            .is_err());
+    }
+
+    #[test]
+    fn keyid_selects_the_key() {
+        let alice = SigningScheme::default().create_signer().unwrap();
+        let mut envelope = Envelope::new("text/plain", b"hello");
+        envelope.sign(&alice, Some("alice")).unwrap();
+
+        let key = alice.to_verification_key().unwrap();
+        assert!(EnvelopeVerifier::new()
+            .with_key(Some("alice"), key.clone())
+            .verify(&envelope)
+            .is_ok());
+        assert!(EnvelopeVerifier::new()
+            .with_key(None, key.clone())
+            .verify(&envelope)
+            .is_ok());
+        assert!(EnvelopeVerifier::new()
+            .with_key(Some("bob"), key)
+            .verify(&envelope)
+            .is_err());
+    }
+}
@@ -36,6 +36,84 @@ pub struct SigstoreApplicationConstraintsError<'a> {
     pub unapplied_constraints: SignConstraintRefVec<'a>,
 }
 
+/// The reason why a signature layer failed verification
+#[cfg(feature = "cosign")]
+#[derive(Error, Debug)]
+pub enum LayerVerificationFailure {
+    /// The layer cannot be trusted: its signature, certificate, Rekor bundle
+    /// or expiry could not be verified
+    #[error("the layer cannot be trusted: {0}")]
+    Untrusted(SigstoreError),
+
+    /// The layer doesn't satisfy the constraint, described by its `Debug`
+    /// representation
+    #[error("{0} is not satisfied")]
+    UnsatisfiedConstraint(String),
+
+    /// The constraint failed to evaluate the layer
+    #[error("{constraint} failed: {error}")]
+    ConstraintError {
+        constraint: String,
+        error: SigstoreError,
+    },
+}
+
+/// The failures of a signature layer
+#[cfg(feature = "cosign")]
+#[derive(Debug)]
+pub struct LayerVerificationErrors {
+    /// The digest of the layer
+    pub layer_digest: String,
+    pub failures: Vec<LayerVerificationFailure>,
+}
+
+/// The failures of each signature layer of an image, none of them
+/// satisfying the verification constraints
+#[cfg(feature = "cosign")]
+#[derive(Debug, Default)]
+pub struct VerificationErrors {
+    pub layers: Vec<LayerVerificationErrors>,
+}
+
+#[cfg(feature = "cosign")]
+impl VerificationErrors {
+    /// Record the `failures` of the layer with `layer_digest`
+    pub fn push(&mut self, layer_digest: &str, failures: Vec<LayerVerificationFailure>) {
+        self.layers.push(LayerVerificationErrors {
+            layer_digest: layer_digest.to_string(),
+            failures,
+        });
+    }
+
+    /// Returns `true` when at least one layer could be trusted, hence the
+    /// verification failed because of the constraints
+    pub fn has_trusted_layer(&self) -> bool {
+        self.layers.iter().any(|layer| {
+            !layer
+                .failures
+                .iter()
+                .any(|failure| matches!(failure, LayerVerificationFailure::Untrusted(_)))
+        })
+    }
+}
+
+#[cfg(feature = "cosign")]
+impl std::fmt::Display for VerificationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No Signature Layer passed verification")?;
+        for layer in &self.layers {
+            write!(f, "\n  {}:", layer.layer_digest)?;
+            for failure in &layer.failures {
+                write!(f, "\n    - {failure}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "cosign")]
+impl std::error::Error for VerificationErrors {}
+
 pub type Result<T> = std::result::Result<T, SigstoreError>;
 
 #[derive(Error, Debug)]
@@ -106,6 +184,36 @@ pub enum SigstoreError {
     #[error("Certificate pool error: {0}")]
     CertificatePoolError(String),
 
+    #[error("Certificate revoked: {0}")]
+    CertificateRevokedError(String),
+
+    #[error("Cannot check the revocation status of the certificate: {0}")]
+    RevocationCheckError(String),
+
+    #[error("SCT verification failed: {0}")]
+    SctVerificationError(String),
+
+    #[error("RFC 3161 timestamp error: {0}")]
+    TimestampError(String),
+
+    #[error("KMS error: {0}")]
+    KmsError(String),
+
+    #[error("Invalid key reference: {0}")]
+    KeyRefError(String),
+
+    #[error("HTTP error: {0}")]
+    HttpError(String),
+
+    #[error("Trusted root error: {0}")]
+    TrustedRootError(String),
+
+    #[error("Trust material fetched at {fetched_at} is older than {max_age_seconds} seconds, it must be refreshed")]
+    StaleTrustMaterial {
+        fetched_at: String,
+        max_age_seconds: i64,
+    },
+
     #[error("Cannot fetch manifest of {image}: {error}")]
     RegistryFetchManifestError { image: String, error: String },
 
@@ -115,12 +223,61 @@ pub enum SigstoreError {
     #[error("Cannot pull {image}: {error}")]
     RegistryPullError { image: String, error: String },
 
+    #[error("Cannot list the referrers of {image}: {error}")]
+    RegistryReferrersError { image: String, error: String },
+
     #[error("Cannot push {image}: {error}")]
     RegistryPushError { image: String, error: String },
 
+    #[error("Cannot read the docker configuration: {0}")]
+    DockerConfigError(String),
+
+    #[error("Transfer of {image} aborted: {size} bytes exceed the budget of {limit} bytes")]
+    RegistryTransferBudgetExceeded {
+        image: String,
+        limit: u64,
+        size: u64,
+    },
+
+    #[error("Recording error: {0}")]
+    RecordingError(String),
+
+    #[error("Offline archive error: {0}")]
+    OfflineArchiveError(String),
+
+    #[error("DSSE error: {0}")]
+    DsseError(String),
+
+    #[error("Sigstore bundle error: {0}")]
+    SigstoreBundleError(String),
+
+    #[error("Attestation error: {0}")]
+    AttestationError(String),
+
+    #[error("Attestation digest mismatch: {0}")]
+    AttestationDigestMismatch(String),
+
+    #[error("Chain of custody broken at step {step}: {reason}")]
+    AttestationChainError { step: String, reason: String },
+
+    #[error("Builder {builder_id} is not trusted: {reason}")]
+    UntrustedBuilder { builder_id: String, reason: String },
+
+    #[error("Invalid predicate query {query}: {error}")]
+    PredicateQueryError { query: String, error: String },
+
     #[error("OCI reference not valid: {reference}")]
     OciReferenceNotValidError { reference: String },
 
+    #[error("Countersignature error: {0}")]
+    CountersignatureError(String),
+
+    #[error("COSE error: {0}")]
+    CoseError(String),
+
+    #[error("Rekor entry doesn't match the artifact: {0}")]
+    RekorEntryMismatchError(String),
+
     #[error("Layer doesn't have Sigstore media type")]
     SigstoreMediaTypeNotFoundError,
 
@@ -139,6 +296,63 @@ pub enum SigstoreError {
     #[error("No Signature Layer passed verification")]
     SigstoreNoVerifiedLayer,
 
+    #[cfg(feature = "cosign")]
+    #[error(transparent)]
+    VerificationErrors(VerificationErrors),
+
+    #[error("Only {} of the {threshold} required signers are satisfied: {satisfied:?}", satisfied.len())]
+    SigstoreThresholdNotMet {
+        threshold: usize,
+        satisfied: Vec<String>,
+    },
+
+    #[error("Rekor client error: {0}")]
+    RekorClientError(String),
+
+    #[error("Invalid Rekor checkpoint: {0}")]
+    RekorCheckpointError(String),
+
+    #[error("Rekor consistency proof verification failed: {0}")]
+    RekorConsistencyProofError(String),
+
+    #[error("Rekor inclusion proof verification failed: {0}")]
+    RekorInclusionProofError(String),
+
+    #[error("Unexpected Rekor log ID: expected {expected}, got {actual}")]
+    RekorLogIdMismatchError { expected: String, actual: String },
+
+    #[error("Rekor log rolled back: last verified size was {persisted_size}, current size is {current_size}")]
+    RekorLogRollbackError {
+        persisted_size: u64,
+        current_size: u64,
+    },
+
+    #[error("Rekor split view detected: the log is not consistent with the verified tree of size {tree_size}")]
+    RekorSplitViewError { tree_size: u64 },
+
+    #[error("Rekor log changed: last verified checkpoint is of {persisted_origin}, current one is of {current_origin}")]
+    RekorLogChangedError {
+        persisted_origin: String,
+        current_origin: String,
+    },
+
+    #[error("Evidence archive verification failed: {0}")]
+    EvidenceArchiveError(String),
+
+    #[error("Signing session error: {0}")]
+    SigningSessionError(String),
+
+    #[error("Publishing to the transparency log has been declined")]
+    TransparencyLogUploadDeclined,
+
+    #[cfg(feature = "rekor-sqlite")]
+    #[error(transparent)]
+    SqliteError(#[from] rusqlite::Error),
+
+    #[cfg(feature = "rekor-jar")]
+    #[error(transparent)]
+    ZipError(#[from] zip::result::ZipError),
+
     #[cfg(feature = "tuf")]
     #[error(transparent)]
     TufError(#[from] Box<tough::error::Error>),
@@ -152,9 +366,18 @@ pub enum SigstoreError {
     #[error("{0}")]
     UnexpectedError(String),
 
+    #[error("{0} is not supported by this cosign client")]
+    CosignCapabilityNotSupported(&'static str),
+
     #[error("{0}")]
     VerificationConstraintError(String),
 
+    #[error("Invalid verification policy: {0}")]
+    PolicyError(String),
+
+    #[error("Invalid SBOM: {0}")]
+    SbomError(String),
+
     #[error("{0}")]
     ApplyConstraintError(String),
 
@@ -167,6 +390,15 @@ pub enum SigstoreError {
     #[error("Failed to get id_token")]
     NoIDToken,
 
+    #[error("Device authorization failed: {0}")]
+    DeviceFlowError(String),
+
+    #[error("Cannot get ambient credentials: {0}")]
+    AmbientCredentialsError(String),
+
+    #[error("Invalid identity token: {0}")]
+    IdentityTokenError(String),
+
     #[error("Pkcs8 error : {0}")]
     PKCS8Error(String),
 
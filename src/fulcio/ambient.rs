@@ -0,0 +1,348 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Detection of the OIDC tokens made available by the environment.
+//!
+//! CI systems and cloud platforms give their workloads identity tokens,
+//! which can be exchanged for Fulcio certificates without any human
+//! interaction. The [`AmbientTokenProvider`] tries a chain of
+//! [`AmbientProvider`]s and uses the first one detected:
+//!
+//! ```rust,no_run
+//! use sigstore::fulcio::ambient::AmbientTokenProvider;
+//! use sigstore::fulcio::{FulcioClient, TokenProvider, FULCIO_ROOT};
+//! use url::Url;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! let fulcio = FulcioClient::new(
+//!     Url::parse(FULCIO_ROOT)?,
+//!     TokenProvider::Ambient(AmbientTokenProvider::from_env()),
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use openidconnect::core::CoreIdToken;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::debug;
+
+use super::token::SIGSTORE_AUDIENCE;
+use super::IdentityKind;
+use crate::errors::{Result, SigstoreError};
+
+/// The file where cosign looks for a projected service account token
+pub const DEFAULT_KUBERNETES_TOKEN_PATH: &str = "/var/run/sigstore/cosign/oidc-token";
+
+/// The host of the metadata server of Google Cloud
+pub const DEFAULT_GCE_METADATA_HOST: &str = "metadata.google.internal";
+
+const GCE_PRODUCT_NAME_PATH: &str = "/sys/class/dmi/id/product_name";
+
+/// A source of ambient identity tokens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AmbientProvider {
+    /// The token requested from GitHub Actions, the workflow needs the
+    /// `id-token: write` permission
+    GitHubActions,
+    /// The token exposed by GitLab CI inside of the `SIGSTORE_ID_TOKEN`
+    /// variable, declared with `id_tokens` by the pipeline
+    GitLabCi,
+    /// The token of the default service account, requested from the metadata
+    /// server of Google Cloud
+    GoogleCloud,
+    /// A JWT-SVID written to a file, like the ones written by the
+    /// spiffe-helper. The Workload API isn't used directly.
+    Spiffe(PathBuf),
+    /// A bound service account token, projected into the pod with the
+    /// `sigstore` audience
+    Kubernetes(PathBuf),
+}
+
+impl AmbientProvider {
+    /// The kind of identity certified for the tokens of the provider
+    pub fn identity_kind(&self) -> IdentityKind {
+        match self {
+            AmbientProvider::GoogleCloud => IdentityKind::Email,
+            _ => IdentityKind::Uri,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            AmbientProvider::GitHubActions => "GitHub Actions",
+            AmbientProvider::GitLabCi => "GitLab CI",
+            AmbientProvider::GoogleCloud => "Google Cloud",
+            AmbientProvider::Spiffe(_) => "SPIFFE",
+            AmbientProvider::Kubernetes(_) => "Kubernetes",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GitHubTokenResponse {
+    value: String,
+}
+
+/// Token provider using the first identity token found inside of the
+/// environment
+#[derive(Debug, Clone)]
+pub struct AmbientTokenProvider {
+    providers: Vec<AmbientProvider>,
+    audience: String,
+    vars: HashMap<String, String>,
+}
+
+impl AmbientTokenProvider {
+    /// Detect the tokens using the environment of the process
+    pub fn from_env() -> Self {
+        Self::from_vars(std::env::vars())
+    }
+
+    /// Detect the tokens using the given environment variables.
+    ///
+    /// The providers tried, in order, are GitHub Actions, GitLab CI,
+    /// Kubernetes, with the token found at
+    /// [`DEFAULT_KUBERNETES_TOKEN_PATH`], and Google Cloud.
+    pub fn from_vars<I, K, V>(vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        AmbientTokenProvider {
+            providers: vec![
+                AmbientProvider::GitHubActions,
+                AmbientProvider::GitLabCi,
+                AmbientProvider::Kubernetes(PathBuf::from(DEFAULT_KUBERNETES_TOKEN_PATH)),
+                AmbientProvider::GoogleCloud,
+            ],
+            audience: SIGSTORE_AUDIENCE.to_string(),
+            vars: vars
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .filter(|(_, v)| !v.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Replace the chain of providers
+    pub fn with_providers(self, providers: Vec<AmbientProvider>) -> Self {
+        Self {
+            providers,
+            audience: self.audience,
+            vars: self.vars,
+        }
+    }
+
+    /// Request tokens for a non-default audience, where the provider
+    /// allows it
+    pub fn with_audience(self, audience: &str) -> Self {
+        Self {
+            providers: self.providers,
+            audience: audience.to_string(),
+            vars: self.vars,
+        }
+    }
+
+    /// The first token found, together with the provider that issued it.
+    ///
+    /// The providers not detected are skipped, an error is returned when a
+    /// detected provider fails to give a token.
+    pub async fn detect_token(&self) -> Result<Option<(CoreIdToken, &AmbientProvider)>> {
+        for provider in &self.providers {
+            let token = match provider {
+                AmbientProvider::GitHubActions => self.github_actions_token().await,
+                AmbientProvider::GitLabCi => self.gitlab_ci_token(),
+                AmbientProvider::GoogleCloud => self.google_cloud_token().await,
+                AmbientProvider::Spiffe(path) | AmbientProvider::Kubernetes(path) => {
+                    file_token(path)
+                }
+            }
+            .map_err(|e| {
+                SigstoreError::AmbientCredentialsError(format!("{}: {e}", provider.name()))
+            })?;
+
+            if let Some(token) = token {
+                debug!(
+                    provider = provider.name(),
+                    "Found an ambient identity token"
+                );
+                let token = CoreIdToken::from_str(token.trim())
+                    .map_err(|e| SigstoreError::IdentityTokenError(e.to_string()))?;
+                return Ok(Some((token, provider)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Retrieve a token and the challenge-to-sign, fails when no token can
+    /// be found
+    pub async fn get_token(&self) -> Result<(CoreIdToken, String)> {
+        match self.detect_token().await? {
+            Some((token, provider)) => {
+                let challenge = provider.identity_kind().challenge(&token)?;
+                Ok((token, challenge))
+            }
+            None => Err(SigstoreError::AmbientCredentialsError(
+                "no identity token found inside of the environment".to_string(),
+            )),
+        }
+    }
+
+    fn var(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(String::as_str)
+    }
+
+    async fn github_actions_token(&self) -> std::result::Result<Option<String>, String> {
+        if self.var("GITHUB_ACTIONS") != Some("true") {
+            return Ok(None);
+        }
+        let (url, bearer) = match (
+            self.var("ACTIONS_ID_TOKEN_REQUEST_URL"),
+            self.var("ACTIONS_ID_TOKEN_REQUEST_TOKEN"),
+        ) {
+            (Some(url), Some(bearer)) => (url, bearer),
+            _ => return Err("the workflow doesn't have the id-token: write permission".to_string()),
+        };
+
+        let mut url = url::Url::parse(url).map_err(|e| e.to_string())?;
+        url.query_pairs_mut()
+            .append_pair("audience", &self.audience);
+        let response = reqwest::Client::new()
+            .get(url)
+            .bearer_auth(bearer)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?;
+        let token: GitHubTokenResponse = response.json().await.map_err(|e| e.to_string())?;
+        Ok(Some(token.value))
+    }
+
+    fn gitlab_ci_token(&self) -> std::result::Result<Option<String>, String> {
+        if self.var("GITLAB_CI") != Some("true") {
+            return Ok(None);
+        }
+        match self.var("SIGSTORE_ID_TOKEN") {
+            Some(token) => Ok(Some(token.to_string())),
+            None => Err("the job doesn't declare the SIGSTORE_ID_TOKEN id token".to_string()),
+        }
+    }
+
+    async fn google_cloud_token(&self) -> std::result::Result<Option<String>, String> {
+        let host = match self.var("GCE_METADATA_HOST") {
+            Some(host) => host,
+            None => {
+                let on_gce = std::fs::read_to_string(GCE_PRODUCT_NAME_PATH)
+                    .is_ok_and(|name| name.trim().starts_with("Google"));
+                if !on_gce {
+                    return Ok(None);
+                }
+                DEFAULT_GCE_METADATA_HOST
+            }
+        };
+
+        let mut url = url::Url::parse(&format!(
+            "http://{host}/computeMetadata/v1/instance/service-accounts/default/identity"
+        ))
+        .map_err(|e| e.to_string())?;
+        url.query_pairs_mut()
+            .append_pair("audience", &self.audience)
+            .append_pair("format", "full");
+        let response = reqwest::Client::new()
+            .get(url)
+            .header("Metadata-Flavor", "Google")
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?;
+        response.text().await.map(Some).map_err(|e| e.to_string())
+    }
+}
+
+fn file_token(path: &Path) -> std::result::Result<Option<String>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    std::fs::read_to_string(path)
+        .map(Some)
+        .map_err(|e| format!("cannot read {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fulcio::token::tests::token;
+    use serde_json::json;
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn detect_tokens() {
+        let raw_token = token(json!({
+            "iss": "https://gitlab.com",
+            "sub": "project_path:octocat/hello-world:ref_type:branch:ref:main",
+            "aud": "sigstore",
+            "exp": 4_102_444_800u64,
+            "iat": 1_700_000_000u64,
+        }))
+        .to_string();
+
+        let provider =
+            AmbientTokenProvider::from_vars([("GITLAB_CI", "true"), ("SIGSTORE_ID_TOKEN", "")])
+                .with_providers(vec![
+                    AmbientProvider::GitHubActions,
+                    AmbientProvider::GitLabCi,
+                ]);
+        assert!(matches!(
+            provider.detect_token().await,
+            Err(SigstoreError::AmbientCredentialsError(_))
+        ));
+
+        let provider = AmbientTokenProvider::from_vars([
+            ("GITLAB_CI", "true"),
+            ("SIGSTORE_ID_TOKEN", raw_token.as_str()),
+        ])
+        .with_providers(vec![
+            AmbientProvider::GitHubActions,
+            AmbientProvider::GitLabCi,
+        ]);
+        let (_, challenge) = provider.get_token().await.unwrap();
+        assert_eq!(
+            challenge,
+            "project_path:octocat/hello-world:ref_type:branch:ref:main"
+        );
+
+        let mut token_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(token_file, "{raw_token}").unwrap();
+        let provider = AmbientTokenProvider::from_vars(Vec::<(String, String)>::new())
+            .with_providers(vec![
+                AmbientProvider::Kubernetes(PathBuf::from("/does/not/exist")),
+                AmbientProvider::Spiffe(token_file.path().to_path_buf()),
+            ]);
+        let (_, found) = provider.detect_token().await.unwrap().unwrap();
+        assert_eq!(
+            *found,
+            AmbientProvider::Spiffe(token_file.path().to_path_buf())
+        );
+
+        let provider = AmbientTokenProvider::from_vars(Vec::<(String, String)>::new())
+            .with_providers(vec![AmbientProvider::GitHubActions]);
+        assert!(provider.detect_token().await.unwrap().is_none());
+        assert!(provider.get_token().await.is_err());
+    }
+}
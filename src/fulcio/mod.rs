@@ -1,16 +1,22 @@
+pub mod ambient;
 pub mod oauth;
+pub mod token;
 
 use crate::crypto::signing_key::SigStoreSigner;
 use crate::crypto::SigningScheme;
 use crate::errors::{Result, SigstoreError};
+use crate::fulcio::ambient::AmbientTokenProvider;
 use crate::fulcio::oauth::OauthTokenProvider;
+use crate::fulcio::token::{unverified_claims, IdentityToken, TokenValidator};
+use crate::http::HttpClient;
 use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
 use openidconnect::core::CoreIdToken;
-use reqwest::Body;
 use serde::ser::SerializeStruct;
 use serde::{Serialize, Serializer};
-use std::convert::{TryFrom, TryInto};
+use serde_json::Value;
+use std::convert::TryFrom;
 use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
 use url::Url;
 
 /// Default public Fulcio server root.
@@ -20,6 +26,7 @@ pub const FULCIO_ROOT: &str = "https://fulcio.sigstore.dev/";
 pub const SIGNING_CERT_PATH: &str = "api/v1/signingCert";
 
 const CONTENT_TYPE_HEADER_NAME: &str = "content-type";
+const AUTHORIZATION_HEADER_NAME: &str = "authorization";
 
 /// Fulcio certificate signing request
 ///
@@ -29,17 +36,11 @@ const CONTENT_TYPE_HEADER_NAME: &str = "content-type";
 #[serde(rename_all = "camelCase")]
 struct Csr {
     public_key: Option<PublicKey>,
+    /// The signed challenge. Despite its name, the field holds the proof of
+    /// possession of every kind of identity, see [`IdentityKind`].
     signed_email_address: Option<String>,
 }
 
-impl TryFrom<Csr> for Body {
-    type Error = serde_json::Error;
-
-    fn try_from(csr: Csr) -> std::result::Result<Self, Self::Error> {
-        Ok(Body::from(serde_json::to_string(&csr)?))
-    }
-}
-
 /// Internal newtype to control serde jsonification.
 #[derive(Debug)]
 struct PublicKey(String, SigningScheme);
@@ -54,9 +55,9 @@ impl Serialize for PublicKey {
         pk.serialize_field(
             "algorithm",
             match self.1 {
-                SigningScheme::ECDSA_P256_SHA256_ASN1 | SigningScheme::ECDSA_P384_SHA384_ASN1 => {
-                    "ecdsa"
-                }
+                SigningScheme::ECDSA_P256_SHA256_ASN1
+                | SigningScheme::ECDSA_P384_SHA384_ASN1
+                | SigningScheme::ECDSA_P521_SHA512 => "ecdsa",
                 SigningScheme::ED25519 => "ed25519",
                 SigningScheme::RSA_PSS_SHA256(_)
                 | SigningScheme::RSA_PSS_SHA384(_)
@@ -85,6 +86,60 @@ impl Display for FulcioCert {
     }
 }
 
+/// The kind of identity bound by Fulcio to the issued certificates.
+///
+/// The kind is set by the issuer of the identity token, see the
+/// `/api/v2/configuration` endpoint of Fulcio. It defines the claim of the
+/// token that must be signed, with the ephemeral key, to prove the
+/// possession of the key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdentityKind {
+    /// An email address, like the accounts of Google or GitHub. The `email`
+    /// claim is signed.
+    #[default]
+    Email,
+    /// A URI, like a SPIFFE ID, a CI workflow or a Kubernetes service
+    /// account. The `sub` claim is signed.
+    Uri,
+    /// A username, certified together with the domain of the issuer, like
+    /// `octocat!example.com`. The `sub` claim is signed.
+    Username,
+}
+
+impl IdentityKind {
+    /// The claim of the identity token signed to prove the possession of
+    /// the private key
+    pub fn challenge_claim(&self) -> &'static str {
+        match self {
+            IdentityKind::Email => "email",
+            IdentityKind::Uri | IdentityKind::Username => "sub",
+        }
+    }
+
+    /// The challenge to sign when requesting a certificate with `token`.
+    ///
+    /// **Note well:** the token is not verified, Fulcio does it.
+    pub fn challenge(&self, token: &CoreIdToken) -> Result<String> {
+        let claims = unverified_claims(token)?;
+        if *self == IdentityKind::Email
+            && claims.get("email_verified").and_then(Value::as_bool) == Some(false)
+        {
+            return Err(SigstoreError::IdentityTokenError(
+                "the email of the token is not verified".to_string(),
+            ));
+        }
+
+        let claim = self.challenge_claim();
+        claims
+            .get(claim)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| {
+                SigstoreError::IdentityTokenError(format!("the token doesn't have a {claim} claim"))
+            })
+    }
+}
+
 /// Provider for Fulcio token.
 #[allow(clippy::large_enum_variant)]
 pub enum TokenProvider {
@@ -115,14 +170,25 @@ pub enum TokenProvider {
     ///
     Static((CoreIdToken, String)),
     Oauth(OauthTokenProvider),
+    /// A provider of the token made available by the environment, like
+    /// the ones of the CI systems
+    Ambient(AmbientTokenProvider),
 }
 
 impl TokenProvider {
+    /// A static provider of `token`, signing the challenge required by the
+    /// given kind of identity
+    pub fn for_identity(token: CoreIdToken, identity_kind: IdentityKind) -> Result<Self> {
+        let challenge = identity_kind.challenge(&token)?;
+        Ok(TokenProvider::Static((token, challenge)))
+    }
+
     /// Retrieve a token and the challenge-to-sign from the provider.
     pub async fn get_token(&self) -> Result<(CoreIdToken, String)> {
         match self {
             TokenProvider::Static(inner) => Ok(inner.clone()),
             TokenProvider::Oauth(auth) => auth.get_token().await,
+            TokenProvider::Ambient(ambient) => ambient.get_token().await,
         }
     }
 }
@@ -132,6 +198,8 @@ impl TokenProvider {
 pub struct FulcioClient {
     root_url: Url,
     token_provider: TokenProvider,
+    token_validator: Option<TokenValidator>,
+    http_client: Arc<dyn HttpClient>,
 }
 
 impl FulcioClient {
@@ -145,6 +213,27 @@ impl FulcioClient {
         Self {
             root_url,
             token_provider,
+            token_validator: None,
+            http_client: Arc::new(reqwest::Client::new()),
+        }
+    }
+
+    /// Verify the identity tokens with `token_validator` before sending
+    /// them to Fulcio, to get clear errors instead of the rejections of
+    /// Fulcio.
+    pub fn with_token_validator(self, token_validator: TokenValidator) -> Self {
+        Self {
+            token_validator: Some(token_validator),
+            ..self
+        }
+    }
+
+    /// Send the requests with `http_client`, instead of a new
+    /// [`reqwest::Client`]
+    pub fn with_http_client(self, http_client: Arc<dyn HttpClient>) -> Self {
+        Self {
+            http_client,
+            ..self
         }
     }
 
@@ -158,6 +247,11 @@ impl FulcioClient {
         signing_scheme: SigningScheme,
     ) -> Result<(SigStoreSigner, FulcioCert)> {
         let (token, challenge) = self.token_provider.get_token().await?;
+        if let Some(token_validator) = &self.token_validator {
+            token_validator
+                .verify(&IdentityToken::try_from(token.clone())?)
+                .await?;
+        }
 
         let signer = signing_scheme.create_signer()?;
         let signature = signer.sign(challenge.as_bytes())?;
@@ -172,23 +266,77 @@ impl FulcioClient {
             signed_email_address: Some(signature),
         };
 
-        let csr = TryInto::<Body>::try_into(csr)?;
-
-        let client = reqwest::Client::new();
-        let response = client
-            .post(self.root_url.join(SIGNING_CERT_PATH)?)
+        let request = http::Request::post(self.root_url.join(SIGNING_CERT_PATH)?.as_str())
             .header(CONTENT_TYPE_HEADER_NAME, "application/json")
-            .bearer_auth(token.to_string())
-            .body(csr)
-            .send()
+            .header(
+                AUTHORIZATION_HEADER_NAME,
+                format!("Bearer {}", token.to_string()),
+            )
+            .body(serde_json::to_vec(&csr)?)
+            .map_err(|e| SigstoreError::HttpError(e.to_string()))?;
+        let response = self
+            .http_client
+            .execute(request)
             .await
             .map_err(|_| SigstoreError::SigstoreFulcioCertificatesNotProvidedError)?;
 
-        let cert = response
-            .text()
-            .await
+        let cert = String::from_utf8(response.into_body())
             .map_err(|_| SigstoreError::SigstoreFulcioCertificatesNotProvidedError)?;
 
         Ok((signer, FulcioCert(cert)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fulcio::token::tests::token;
+
+    #[test]
+    fn challenge_of_each_identity_kind() {
+        let token = token(serde_json::json!({
+            "iss": "https://token.actions.githubusercontent.com",
+            "sub": "repo:octocat/hello-world:ref:refs/heads/main",
+            "aud": "sigstore",
+            "exp": 4_102_444_800u64,
+            "iat": 1_700_000_000u64,
+        }));
+
+        assert_eq!(
+            IdentityKind::Uri.challenge(&token).unwrap(),
+            "repo:octocat/hello-world:ref:refs/heads/main"
+        );
+        assert_eq!(
+            IdentityKind::Username.challenge(&token).unwrap(),
+            "repo:octocat/hello-world:ref:refs/heads/main"
+        );
+        assert!(matches!(
+            IdentityKind::Email.challenge(&token),
+            Err(SigstoreError::IdentityTokenError(_))
+        ));
+    }
+
+    #[test]
+    fn reject_unverified_email() {
+        let token = token(serde_json::json!({
+            "iss": "https://oauth2.sigstore.dev/auth",
+            "sub": "CgYxMjM0NTYSJmh0dHBzOi8vZ2l0aHViLmNvbS9sb2dpbi9vYXV0aA",
+            "aud": "sigstore",
+            "exp": 4_102_444_800u64,
+            "iat": 1_700_000_000u64,
+            "email": "octocat@example.com",
+            "email_verified": false,
+        }));
+        assert!(IdentityKind::Email.challenge(&token).is_err());
+
+        match TokenProvider::for_identity(token, IdentityKind::Uri).unwrap() {
+            TokenProvider::Static((_, challenge)) => {
+                assert_eq!(
+                    challenge,
+                    "CgYxMjM0NTYSJmh0dHBzOi8vZ2l0aHViLmNvbS9sb2dpbi9vYXV0aA"
+                )
+            }
+            TokenProvider::Oauth(_) | TokenProvider::Ambient(_) => unreachable!(),
+        }
+    }
+}
@@ -1,7 +1,10 @@
 use crate::errors::Result;
 use crate::errors::SigstoreError;
+use crate::fulcio::IdentityKind;
+use crate::oauth::deviceflow::{DeviceAuthorization, DeviceAuthorize};
 use crate::oauth::openidflow::{OpenIDAuthorize, RedirectListener};
-use openidconnect::core::CoreIdToken;
+use openidconnect::core::{CoreIdToken, CoreIdTokenClaims};
+use std::sync::Arc;
 
 /// Default client id ("sigstore").
 pub const DEFAULT_CLIENT_ID: &str = "sigstore";
@@ -15,13 +18,33 @@ pub const DEFAULT_ISSUER: &str = "https://oauth2.sigstore.dev/auth";
 /// Default local redirect port (8080)
 pub const DEFAULT_REDIRECT_PORT: u32 = 8080;
 
+/// A trait that can be implemented to be told how the user completes a
+/// device authorization
+pub trait DeviceFlowListener: Send + Sync {
+    /// Invoked once the device authorization has been granted by the issuer.
+    ///
+    /// The user has to open
+    /// [`verification_uri`](DeviceAuthorization::verification_uri) on any
+    /// device and enter [`user_code`](DeviceAuthorization::user_code) there,
+    /// or simply open
+    /// [`verification_uri_complete`](DeviceAuthorization::verification_uri_complete)
+    /// when the issuer provides it. The token is polled for once this returns.
+    fn on_authorization(&self, authorization: &DeviceAuthorization);
+}
+
 /// Token provider that performs a human-involved OIDC flow to acquire a token id.
+///
+/// The user authorizes the request inside of a browser opened on the local
+/// machine, unless the device flow is enabled with
+/// [`with_device_flow`](OauthTokenProvider::with_device_flow).
 #[derive(Default)]
 pub struct OauthTokenProvider {
     client_id: Option<String>,
     client_secret: Option<String>,
     issuer: Option<String>,
     redirect_port: Option<u32>,
+    identity_kind: IdentityKind,
+    device_flow: Option<Arc<dyn DeviceFlowListener>>,
 }
 
 impl OauthTokenProvider {
@@ -32,6 +55,8 @@ impl OauthTokenProvider {
             client_secret: self.client_secret,
             issuer: self.issuer,
             redirect_port: self.redirect_port,
+            identity_kind: self.identity_kind,
+            device_flow: self.device_flow,
         }
     }
 
@@ -42,6 +67,8 @@ impl OauthTokenProvider {
             client_secret: Some(client_secret.to_string()),
             issuer: self.issuer,
             redirect_port: self.redirect_port,
+            identity_kind: self.identity_kind,
+            device_flow: self.device_flow,
         }
     }
 
@@ -52,6 +79,8 @@ impl OauthTokenProvider {
             client_secret: self.client_secret,
             issuer: Some(issuer.to_string()),
             redirect_port: self.redirect_port,
+            identity_kind: self.identity_kind,
+            device_flow: self.device_flow,
         }
     }
 
@@ -62,6 +91,37 @@ impl OauthTokenProvider {
             client_secret: self.client_secret,
             issuer: self.issuer,
             redirect_port: Some(port),
+            identity_kind: self.identity_kind,
+            device_flow: self.device_flow,
+        }
+    }
+
+    /// Set the kind of identity to certify, defaults to
+    /// [`IdentityKind::Email`].
+    pub fn with_identity_kind(self, identity_kind: IdentityKind) -> Self {
+        Self {
+            client_id: self.client_id,
+            client_secret: self.client_secret,
+            issuer: self.issuer,
+            redirect_port: self.redirect_port,
+            identity_kind,
+            device_flow: self.device_flow,
+        }
+    }
+
+    /// Use the device authorization grant instead of opening a browser:
+    /// the `listener` is handed a URL and a code, which the user has to
+    /// enter on any device.
+    /// Meant for headless environments, where no browser can be opened and
+    /// no local port can receive the redirect.
+    pub fn with_device_flow(self, listener: Arc<dyn DeviceFlowListener>) -> Self {
+        Self {
+            client_id: self.client_id,
+            client_secret: self.client_secret,
+            issuer: self.issuer,
+            redirect_port: self.redirect_port,
+            identity_kind: self.identity_kind,
+            device_flow: Some(listener),
         }
     }
 
@@ -72,17 +132,45 @@ impl OauthTokenProvider {
         )
     }
 
+    fn client_id(&self) -> &str {
+        self.client_id.as_deref().unwrap_or(DEFAULT_CLIENT_ID)
+    }
+
+    fn client_secret(&self) -> &str {
+        self.client_secret
+            .as_deref()
+            .unwrap_or(DEFAULT_CLIENT_SECRET)
+    }
+
+    fn issuer(&self) -> &str {
+        self.issuer.as_deref().unwrap_or(DEFAULT_ISSUER)
+    }
+
     /// Perform human-involved OIDC flow to acquire an id token, along with
-    /// the extracted email claim value for use in signed challenge with Fulcio.
+    /// the extracted challenge claim value (the email for email identities,
+    /// the subject otherwise) for use in signed challenge with Fulcio.
     pub async fn get_token(&self) -> Result<(CoreIdToken, String)> {
+        let (claims, id_token) = match &self.device_flow {
+            Some(listener) => self.device_flow_token(listener.as_ref()).await?,
+            None => self.browser_flow_token().await?,
+        };
+
+        match self.identity_kind {
+            IdentityKind::Email => match claims.email() {
+                Some(email) => Ok((id_token, email.to_string())),
+                None => Err(SigstoreError::NoIDToken),
+            },
+            IdentityKind::Uri | IdentityKind::Username => {
+                Ok((id_token, claims.subject().as_str().to_string()))
+            }
+        }
+    }
+
+    async fn browser_flow_token(&self) -> Result<(CoreIdTokenClaims, CoreIdToken)> {
         let oidc_url = OpenIDAuthorize::new(
-            self.client_id
-                .as_ref()
-                .unwrap_or(&DEFAULT_CLIENT_ID.to_string()),
-            self.client_secret
-                .as_ref()
-                .unwrap_or(&DEFAULT_CLIENT_SECRET.to_string()),
-            self.issuer.as_ref().unwrap_or(&DEFAULT_ISSUER.to_string()),
+            self.client_id(),
+            self.client_secret(),
+            self.issuer(),
             &self.redirect_url(),
         )
         .auth_url_async()
@@ -100,31 +188,30 @@ impl OauthTokenProvider {
         }
 
         let oidc_url = oidc_url?;
-        let result = RedirectListener::new(
+        RedirectListener::new(
             &format!(
                 "127.0.0.1:{}",
                 self.redirect_port.unwrap_or(DEFAULT_REDIRECT_PORT)
             ),
-            oidc_url.1.clone(), // client
-            oidc_url.2.clone(), // nonce
-            oidc_url.3,         // pkce_verifier
+            oidc_url.1, // client
+            oidc_url.2, // nonce
+            oidc_url.3, // pkce_verifier
         )
         .redirect_listener_async()
-        .await;
+        .await
+    }
 
-        if let Ok((_, id_token)) = result {
-            let verifier = oidc_url.1.id_token_verifier();
-            let nonce = &oidc_url.2;
+    async fn device_flow_token(
+        &self,
+        listener: &dyn DeviceFlowListener,
+    ) -> Result<(CoreIdTokenClaims, CoreIdToken)> {
+        let authorization =
+            DeviceAuthorize::new(self.client_id(), self.client_secret(), self.issuer())
+                .device_authorization_async()
+                .await?;
 
-            let claims = id_token.claims(&verifier, nonce);
-            if let Ok(claims) = claims {
-                if let Some(email) = claims.email() {
-                    let email = &**email;
-                    return Ok((id_token.clone(), email.clone()));
-                }
-            }
-        }
+        listener.on_authorization(&authorization);
 
-        Err(SigstoreError::NoIDToken)
+        authorization.poll_token_async().await
     }
 }
@@ -0,0 +1,353 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Inspection and pre-flight validation of OIDC identity tokens.
+//!
+//! Fulcio rejects invalid tokens with an opaque `401` response. Validating
+//! the token before contacting Fulcio gives clear errors instead, like
+//! "token expired" or "wrong audience":
+//!
+//! ```rust,no_run
+//! use sigstore::fulcio::token::{IdentityToken, TokenValidator};
+//! use std::str::FromStr;
+//!
+//! # async fn doc(raw_token: &str) -> sigstore::errors::Result<()> {
+//! let token = IdentityToken::from_str(raw_token)?;
+//! println!("issuer: {:?}, subject: {:?}", token.issuer(), token.subject());
+//!
+//! // check the claims, then the signature of the token with the keys
+//! // published by its issuer
+//! TokenValidator::default().verify(&token).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, TimeZone, Utc};
+use openidconnect::core::{CoreIdToken, CoreIdTokenVerifier, CoreProviderMetadata};
+use openidconnect::reqwest::async_http_client;
+use openidconnect::{ClaimsVerificationError, ClientId, IssuerUrl, Nonce};
+use serde_json::{Map, Value};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use crate::errors::{Result, SigstoreError};
+
+/// The audience of the tokens accepted by Fulcio
+pub const SIGSTORE_AUDIENCE: &str = "sigstore";
+
+/// An OIDC identity token, together with its claims.
+///
+/// **Note well:** the claims are parsed without verifying the token, use a
+/// [`TokenValidator`] before trusting them.
+#[derive(Debug, Clone)]
+pub struct IdentityToken {
+    token: CoreIdToken,
+    claims: Map<String, Value>,
+}
+
+impl IdentityToken {
+    /// The token itself
+    pub fn token(&self) -> &CoreIdToken {
+        &self.token
+    }
+
+    /// All the claims of the token
+    pub fn claims(&self) -> &Map<String, Value> {
+        &self.claims
+    }
+
+    /// The value of the string claim `name`
+    pub fn claim(&self, name: &str) -> Option<&str> {
+        self.claims.get(name).and_then(Value::as_str)
+    }
+
+    /// The `iss` claim
+    pub fn issuer(&self) -> Option<&str> {
+        self.claim("iss")
+    }
+
+    /// The `sub` claim
+    pub fn subject(&self) -> Option<&str> {
+        self.claim("sub")
+    }
+
+    /// The `email` claim
+    pub fn email(&self) -> Option<&str> {
+        self.claim("email")
+    }
+
+    /// The `aud` claim, which is either a string or a list of strings
+    pub fn audiences(&self) -> Vec<&str> {
+        match self.claims.get("aud") {
+            Some(Value::String(audience)) => vec![audience.as_str()],
+            Some(Value::Array(audiences)) => audiences.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// When the token expires, from the `exp` claim
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.timestamp("exp")
+    }
+
+    /// When the token was issued, from the `iat` claim
+    pub fn issued_at(&self) -> Option<DateTime<Utc>> {
+        self.timestamp("iat")
+    }
+
+    /// When the token becomes valid, from the `nbf` claim
+    pub fn not_before(&self) -> Option<DateTime<Utc>> {
+        self.timestamp("nbf")
+    }
+
+    fn timestamp(&self, name: &str) -> Option<DateTime<Utc>> {
+        let seconds = self.claims.get(name)?.as_f64()?;
+        Utc.timestamp_opt(seconds as i64, 0).single()
+    }
+}
+
+impl TryFrom<CoreIdToken> for IdentityToken {
+    type Error = SigstoreError;
+
+    fn try_from(token: CoreIdToken) -> Result<Self> {
+        let claims = unverified_claims(&token)?;
+        Ok(IdentityToken { token, claims })
+    }
+}
+
+impl FromStr for IdentityToken {
+    type Err = SigstoreError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let token = CoreIdToken::from_str(s.trim())
+            .map_err(|e| SigstoreError::IdentityTokenError(e.to_string()))?;
+        Self::try_from(token)
+    }
+}
+
+/// The claims of `token`, without verifying its signature
+pub(crate) fn unverified_claims(token: &CoreIdToken) -> Result<Map<String, Value>> {
+    let token = token.to_string();
+    let payload = token
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| SigstoreError::IdentityTokenError("the token is not a JWT".to_string()))?;
+    let claims: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload)?)?;
+    match claims {
+        Value::Object(claims) => Ok(claims),
+        _ => Err(SigstoreError::IdentityTokenError(
+            "the claims of the token are not a JSON object".to_string(),
+        )),
+    }
+}
+
+/// Validates identity tokens before they are sent to Fulcio
+#[derive(Debug, Clone)]
+pub struct TokenValidator {
+    audience: String,
+    issuer: Option<String>,
+}
+
+impl Default for TokenValidator {
+    fn default() -> Self {
+        TokenValidator {
+            audience: SIGSTORE_AUDIENCE.to_string(),
+            issuer: None,
+        }
+    }
+}
+
+impl TokenValidator {
+    /// Set a non-default audience, Fulcio instances other than the public
+    /// one can be configured to accept other audiences
+    pub fn with_audience(self, audience: &str) -> Self {
+        Self {
+            audience: audience.to_string(),
+            issuer: self.issuer,
+        }
+    }
+
+    /// Require the token to be issued by `issuer`
+    pub fn with_issuer(self, issuer: &str) -> Self {
+        Self {
+            audience: self.audience,
+            issuer: Some(issuer.to_string()),
+        }
+    }
+
+    /// Check the claims of `token`: its validity period, its audience and
+    /// its issuer. The signature of the token is not verified.
+    pub fn check(&self, token: &IdentityToken) -> Result<()> {
+        let now = Utc::now();
+        match token.expires_at() {
+            Some(expires_at) if expires_at <= now => {
+                return Err(token_error(format!("the token expired at {expires_at}")));
+            }
+            Some(_) => {}
+            None => return Err(token_error("the token doesn't have an expiration time")),
+        }
+        if let Some(not_before) = token.not_before() {
+            if not_before > now {
+                return Err(token_error(format!(
+                    "the token is not valid before {not_before}"
+                )));
+            }
+        }
+
+        let audiences = token.audiences();
+        if !audiences.contains(&self.audience.as_str()) {
+            return Err(token_error(format!(
+                "wrong audience: expected {}, the token is for {}",
+                self.audience,
+                audiences.join(", ")
+            )));
+        }
+
+        match (&self.issuer, token.issuer()) {
+            (_, None) => Err(token_error("the token doesn't have an issuer")),
+            (Some(expected), Some(issuer)) if expected != issuer => Err(token_error(format!(
+                "wrong issuer: expected {expected}, the token is issued by {issuer}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the claims of `token`, then verify its signature with the keys
+    /// of its issuer, found via OIDC discovery
+    pub async fn verify(&self, token: &IdentityToken) -> Result<()> {
+        self.check(token)?;
+
+        let issuer = token
+            .issuer()
+            .ok_or_else(|| token_error("the token doesn't have an issuer"))?;
+        let issuer = IssuerUrl::new(issuer.to_string())
+            .map_err(|e| token_error(format!("invalid issuer {issuer}: {e}")))?;
+        let provider_metadata =
+            CoreProviderMetadata::discover_async(issuer.clone(), async_http_client)
+                .await
+                .map_err(|e| {
+                    token_error(format!(
+                        "cannot discover the issuer {}: {e}",
+                        issuer.as_str()
+                    ))
+                })?;
+
+        let verifier = CoreIdTokenVerifier::new_public_client(
+            ClientId::new(self.audience.clone()),
+            issuer,
+            provider_metadata.jwks().clone(),
+        );
+        // the tokens given to Fulcio are not obtained with a nonce
+        let no_nonce = |_: Option<&Nonce>| Ok::<(), String>(());
+        token
+            .token()
+            .claims(&verifier, no_nonce)
+            .map_err(|e| match e {
+                ClaimsVerificationError::Expired(_) => token_error("the token expired"),
+                ClaimsVerificationError::InvalidAudience(msg) => {
+                    token_error(format!("wrong audience: {msg}"))
+                }
+                ClaimsVerificationError::SignatureVerification(e) => {
+                    token_error(format!("invalid token signature: {e}"))
+                }
+                e => token_error(e.to_string()),
+            })?;
+        Ok(())
+    }
+}
+
+fn token_error(msg: impl Into<String>) -> SigstoreError {
+    SigstoreError::IdentityTokenError(msg.into())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use serde_json::json;
+
+    pub(crate) fn token(claims: Value) -> CoreIdToken {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let signature = URL_SAFE_NO_PAD.encode("signature");
+        CoreIdToken::from_str(&format!("{header}.{claims}.{signature}")).unwrap()
+    }
+
+    fn identity_token(claims: Value) -> IdentityToken {
+        IdentityToken::try_from(token(claims)).unwrap()
+    }
+
+    #[test]
+    fn inspect_claims() {
+        let token = identity_token(json!({
+            "iss": "https://oauth2.sigstore.dev/auth",
+            "sub": "octocat",
+            "aud": ["sigstore", "other"],
+            "exp": 4_102_444_800u64,
+            "iat": 1_700_000_000u64,
+            "email": "octocat@example.com",
+        }));
+        assert_eq!(token.issuer(), Some("https://oauth2.sigstore.dev/auth"));
+        assert_eq!(token.subject(), Some("octocat"));
+        assert_eq!(token.email(), Some("octocat@example.com"));
+        assert_eq!(token.audiences(), vec!["sigstore", "other"]);
+        assert_eq!(token.issued_at().unwrap().timestamp(), 1_700_000_000);
+        assert!(token.not_before().is_none());
+        assert!(TokenValidator::default().check(&token).is_ok());
+        assert!(TokenValidator::default()
+            .with_issuer("https://oauth2.sigstore.dev/auth")
+            .check(&token)
+            .is_ok());
+    }
+
+    #[test]
+    fn reject_invalid_claims() {
+        let claims = json!({
+            "iss": "https://token.actions.githubusercontent.com",
+            "sub": "repo:octocat/hello-world:ref:refs/heads/main",
+            "aud": "sigstore",
+            "exp": 4_102_444_800u64,
+            "iat": 1_700_000_000u64,
+        });
+        let error_message = |claims: Value, validator: TokenValidator| match validator
+            .check(&identity_token(claims))
+        {
+            Err(SigstoreError::IdentityTokenError(msg)) => msg,
+            other => panic!("unexpected result {:?}", other),
+        };
+
+        let mut expired = claims.clone();
+        expired["exp"] = json!(1_600_000_000u64);
+        assert!(error_message(expired, TokenValidator::default()).starts_with("the token expired"));
+
+        let mut not_yet_valid = claims.clone();
+        not_yet_valid["nbf"] = json!(4_000_000_000u64);
+        assert!(error_message(not_yet_valid, TokenValidator::default())
+            .starts_with("the token is not valid before"));
+
+        assert_eq!(
+            error_message(
+                claims.clone(),
+                TokenValidator::default().with_audience("fulcio")
+            ),
+            "wrong audience: expected fulcio, the token is for sigstore"
+        );
+        assert!(error_message(
+            claims,
+            TokenValidator::default().with_issuer("https://oauth2.sigstore.dev/auth")
+        )
+        .starts_with("wrong issuer"));
+    }
+}
@@ -0,0 +1,209 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pluggable HTTP client of the network-facing modules.
+//!
+//! The Rekor and Fulcio clients send their requests through an
+//! [`HttpClient`], which is a [`reqwest::Client`] by default. Applications
+//! can give them their own implementation, wrapping a `hyper` client or
+//! adding retries and tracing, or share a single client between them so
+//! that the connection pool is reused across verifications:
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use sigstore::http::{HttpClient, HttpConfig};
+//! use sigstore::rekor::apis::configuration::Configuration;
+//!
+//! # fn doc() -> sigstore::errors::Result<()> {
+//! let http_client: Arc<dyn HttpClient> = Arc::new(
+//!     HttpConfig::default()
+//!         .with_proxy("http://proxy.example.com:3128")
+//!         .with_root_certificate(&std::fs::read("corporate-ca.pem")?)
+//!         .build()?,
+//! );
+//!
+//! let mut rekor_config = Configuration::default();
+//! rekor_config.http_client = Some(http_client.clone());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The OCI registries are reached through the client of `oci-distribution`,
+//! configured by [`crate::registry::ClientConfig`], and the TUF repository
+//! through the one of `tough`. Both honor the `HTTPS_PROXY` and `NO_PROXY`
+//! environment variables.
+
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::errors::{Result, SigstoreError};
+
+/// A request sent by an [`HttpClient`]
+pub type HttpRequest = http::Request<Vec<u8>>;
+
+/// A response received by an [`HttpClient`]
+pub type HttpResponse = http::Response<Vec<u8>>;
+
+/// A client sending HTTP requests.
+///
+/// The responses are returned whatever their status, only the failures to
+/// get a response are errors.
+#[async_trait(?Send)]
+pub trait HttpClient {
+    /// Send `request`, and return its response
+    async fn execute(&self, request: HttpRequest) -> Result<HttpResponse>;
+}
+
+impl std::fmt::Debug for dyn HttpClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("HttpClient")
+    }
+}
+
+#[async_trait(?Send)]
+impl HttpClient for reqwest::Client {
+    async fn execute(&self, request: HttpRequest) -> Result<HttpResponse> {
+        let request = reqwest::Request::try_from(request).map_err(http_error)?;
+        let response = reqwest::Client::execute(self, request)
+            .await
+            .map_err(http_error)?;
+
+        let mut builder = http::Response::builder().status(response.status());
+        if let Some(headers) = builder.headers_mut() {
+            *headers = response.headers().clone();
+        }
+        let body = response.bytes().await.map_err(http_error)?;
+        builder
+            .body(body.to_vec())
+            .map_err(|e| SigstoreError::HttpError(e.to_string()))
+    }
+}
+
+fn http_error(error: reqwest::Error) -> SigstoreError {
+    SigstoreError::HttpError(error.to_string())
+}
+
+/// Convert a request built with `reqwest` to one sent by an [`HttpClient`].
+/// Streamed bodies are not supported.
+pub(crate) fn from_reqwest_request(request: reqwest::Request) -> Result<HttpRequest> {
+    let mut builder = http::Request::builder()
+        .method(request.method().clone())
+        .uri(request.url().as_str());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = request.headers().clone();
+    }
+    let body = match request.body() {
+        Some(body) => body
+            .as_bytes()
+            .ok_or_else(|| {
+                SigstoreError::HttpError("streamed request bodies are not supported".to_string())
+            })?
+            .to_vec(),
+        None => Vec::new(),
+    };
+    builder
+        .body(body)
+        .map_err(|e| SigstoreError::HttpError(e.to_string()))
+}
+
+/// Settings of the [`reqwest::Client`] used as [`HttpClient`]
+#[derive(Debug, Clone, Default)]
+pub struct HttpConfig {
+    proxy: Option<String>,
+    root_certificates: Vec<Vec<u8>>,
+    timeout: Option<Duration>,
+    user_agent: Option<String>,
+}
+
+impl HttpConfig {
+    /// Send every request through the proxy at `url`, instead of the ones
+    /// set by the `HTTP_PROXY` and `HTTPS_PROXY` environment variables
+    pub fn with_proxy(mut self, url: &str) -> Self {
+        self.proxy = Some(url.to_string());
+        self
+    }
+
+    /// Trust the PEM-encoded certificate, in addition to the root
+    /// certificates of the system
+    pub fn with_root_certificate(mut self, pem: &[u8]) -> Self {
+        self.root_certificates.push(pem.to_vec());
+        self
+    }
+
+    /// Give up on the requests that are not answered within `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Send `user_agent` as `User-Agent` header
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// Build the client
+    pub fn build(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy).map_err(http_error)?);
+        }
+        for pem in &self.root_certificates {
+            builder = builder
+                .add_root_certificate(reqwest::Certificate::from_pem(pem).map_err(http_error)?);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        builder.build().map_err(http_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_reqwest_request() {
+        let request = reqwest::Client::new()
+            .post("https://rekor.sigstore.dev/api/v1/log/entries")
+            .header("content-type", "application/json")
+            .body("{}")
+            .build()
+            .unwrap();
+        let request = from_reqwest_request(request).unwrap();
+
+        assert_eq!(request.method(), http::Method::POST);
+        assert_eq!(
+            request.uri(),
+            "https://rekor.sigstore.dev/api/v1/log/entries"
+        );
+        assert_eq!(request.headers()["content-type"], "application/json");
+        assert_eq!(request.body(), b"{}");
+    }
+
+    #[test]
+    fn reject_invalid_proxy() {
+        assert!(matches!(
+            HttpConfig::default().with_proxy("not a url").build(),
+            Err(SigstoreError::HttpError(_))
+        ));
+    }
+}
@@ -241,27 +241,74 @@
 //! - `cosign-native-tls` and `cosign-rustls-tls`: Enables support for `cosign`, but one uses
 //! `native-tls` as underlying tls and the other uses `rustls-tls`.
 //!
+//! - `rekor-sqlite`: Enables the export of Rekor entries to a local SQLite database.
+//!
+//! - `rekor-jar`: Enables the verification of signed JARs recorded by Rekor.
+//!
+//! - `rekor-apk`: Enables the verification of Alpine packages recorded by Rekor.
+//!
+//! - `aws-kms`: Enables signing with keys stored inside of AWS KMS.
+//!
+//! - `gcp-kms-native-tls` and `gcp-kms-rustls-tls`: Enables signing with keys stored inside of
+//!   Google Cloud KMS, but one uses `native-tls` as underlying tls and the other uses `rustls-tls`.
+//!
+//! - `azure-kms-native-tls` and `azure-kms-rustls-tls`: Enables signing with keys stored inside of
+//!   Azure Key Vault, but one uses `native-tls` as underlying tls and the other uses `rustls-tls`.
+//!
+//! - `hashivault-native-tls` and `hashivault-rustls-tls`: Enables signing with keys stored by the
+//!   transit engine of HashiCorp Vault, but one uses `native-tls` as underlying tls and the other
+//!   uses `rustls-tls`.
+//!
+//! - `pkcs11`: Enables signing with keys stored inside of hardware tokens, like HSMs or YubiKeys,
+//!   through their PKCS#11 module.
+//!
+//! - `k8s`: Enables the `k8s://` key references, which read the keys stored inside of Kubernetes
+//!   secrets.
+//!
 //! - `cached-client`: Enables support for OCI registry client caching.
 //!
+//! - `policy-yaml`: Enables the YAML encoded verification policies of the `cosign::policy` module.
+//!
+//! - `blocking`: Enables the synchronous facades over the Rekor, Fulcio, cosign and TUF clients,
+//!   under the `blocking` module.
+//!
 //! - `test-registry`: Enables tests based on a temporary OCI registry.
 //! - `tuf`: Enables support for TUF to request for fulcio certs and rekor public key.
 
 #![forbid(unsafe_code)]
 #![warn(clippy::unwrap_used, clippy::panic)]
 
+extern crate alloc;
+
+pub mod bundle;
+
+pub mod cache;
+
 pub mod crypto;
 
+pub mod dsse;
+
 #[cfg(feature = "mock-client")]
 mod mock_client;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
 #[cfg(feature = "cosign")]
 pub mod cosign;
 
 pub mod errors;
 
+pub mod metrics;
+
+pub mod portable;
+
 #[cfg(feature = "fulcio")]
 pub mod fulcio;
 
+#[cfg(any(feature = "fulcio", feature = "rekor"))]
+pub mod http;
+
 #[cfg(feature = "oauth")]
 pub mod oauth;
 
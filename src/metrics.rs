@@ -0,0 +1,193 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Counters and timers about the verification activity.
+//!
+//! The host application can implement the [`MetricsRecorder`] trait to
+//! forward these events to its metrics system (Prometheus, StatsD,...).
+//! All the methods of the trait have an empty default implementation, hence
+//! only the interesting events have to be handled.
+//!
+//! The recorder can be registered with:
+//! * [`Watcher::with_metrics_recorder`](crate::cosign::watcher::Watcher::with_metrics_recorder):
+//!   to track the outcome of image verifications
+//! * [`LogAuditor::with_metrics_recorder`](crate::rekor::auditor::LogAuditor::with_metrics_recorder):
+//!   to track the lookups performed against the Rekor transparency log
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::errors::SigstoreError;
+
+/// Why a verification failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailureReason {
+    /// The signatures could not be fetched from the registry
+    Registry,
+    /// None of the signatures could be trusted
+    NoTrustedSignature,
+    /// The trusted signatures do not satisfy the verification constraints
+    ConstraintsNotSatisfied,
+    /// Any other error
+    Other,
+}
+
+impl FailureReason {
+    /// Classify the error raised during verification
+    pub fn from_error(error: &SigstoreError) -> Self {
+        match error {
+            SigstoreError::RegistryFetchManifestError { .. }
+            | SigstoreError::RegistryPullManifestError { .. }
+            | SigstoreError::RegistryPullError { .. }
+            | SigstoreError::RegistryTransferBudgetExceeded { .. } => FailureReason::Registry,
+            SigstoreError::SigstoreNoVerifiedLayer => FailureReason::NoTrustedSignature,
+            #[cfg(feature = "cosign")]
+            SigstoreError::VerificationErrors(errors) if errors.has_trusted_layer() => {
+                FailureReason::ConstraintsNotSatisfied
+            }
+            #[cfg(feature = "cosign")]
+            SigstoreError::VerificationErrors(_) => FailureReason::NoTrustedSignature,
+            SigstoreError::SigstoreThresholdNotMet { .. } => FailureReason::ConstraintsNotSatisfied,
+            _ => FailureReason::Other,
+        }
+    }
+
+    /// A short identifier, suitable as a metric label
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureReason::Registry => "registry",
+            FailureReason::NoTrustedSignature => "no_trusted_signature",
+            FailureReason::ConstraintsNotSatisfied => "constraints_not_satisfied",
+            FailureReason::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A trait that can be implemented to receive counters and timers about the
+/// verification activity
+pub trait MetricsRecorder: Send + Sync {
+    /// A verification has started
+    fn verification_attempted(&self) {}
+
+    /// A verification succeeded, after `elapsed` time
+    fn verification_succeeded(&self, _elapsed: Duration) {}
+
+    /// A verification failed, after `elapsed` time
+    fn verification_failed(&self, _reason: FailureReason, _elapsed: Duration) {}
+
+    /// A lookup against the transparency log has been performed
+    fn tlog_lookup(&self, _elapsed: Duration, _succeeded: bool) {}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// A recorder that keeps the counters in memory
+    #[derive(Default)]
+    pub(crate) struct CountingRecorder {
+        pub(crate) attempted: Mutex<u64>,
+        pub(crate) succeeded: Mutex<u64>,
+        pub(crate) failed: Mutex<HashMap<FailureReason, u64>>,
+        pub(crate) tlog_lookups: Mutex<u64>,
+    }
+
+    impl MetricsRecorder for CountingRecorder {
+        fn verification_attempted(&self) {
+            *self.attempted.lock().unwrap() += 1;
+        }
+
+        fn verification_succeeded(&self, _elapsed: Duration) {
+            *self.succeeded.lock().unwrap() += 1;
+        }
+
+        fn verification_failed(&self, reason: FailureReason, _elapsed: Duration) {
+            *self.failed.lock().unwrap().entry(reason).or_default() += 1;
+        }
+
+        fn tlog_lookup(&self, _elapsed: Duration, _succeeded: bool) {
+            *self.tlog_lookups.lock().unwrap() += 1;
+        }
+    }
+
+    #[test]
+    fn classify_errors() {
+        assert_eq!(
+            FailureReason::from_error(&SigstoreError::RegistryPullError {
+                image: "image".to_string(),
+                error: "error".to_string(),
+            }),
+            FailureReason::Registry
+        );
+        assert_eq!(
+            FailureReason::from_error(&SigstoreError::SigstoreNoVerifiedLayer),
+            FailureReason::NoTrustedSignature
+        );
+        assert_eq!(
+            FailureReason::from_error(&SigstoreError::NoIDToken),
+            FailureReason::Other
+        );
+    }
+
+    #[cfg(feature = "cosign")]
+    #[test]
+    fn classify_verification_errors() {
+        use crate::errors::{LayerVerificationFailure, VerificationErrors};
+
+        let mut errors = VerificationErrors::default();
+        errors.push(
+            "sha256:untrusted",
+            vec![LayerVerificationFailure::Untrusted(
+                SigstoreError::SigstoreRekorBundleNotFoundError,
+            )],
+        );
+        errors.push(
+            "sha256:trusted",
+            vec![LayerVerificationFailure::UnsatisfiedConstraint(
+                "AnnotationVerifier".to_string(),
+            )],
+        );
+        assert_eq!(
+            errors.to_string(),
+            "No Signature Layer passed verification\n  \
+             sha256:untrusted:\n    - the layer cannot be trusted: Rekor bundle missing\n  \
+             sha256:trusted:\n    - AnnotationVerifier is not satisfied"
+        );
+        assert_eq!(
+            FailureReason::from_error(&SigstoreError::VerificationErrors(errors)),
+            FailureReason::ConstraintsNotSatisfied
+        );
+
+        let mut errors = VerificationErrors::default();
+        errors.push(
+            "sha256:untrusted",
+            vec![LayerVerificationFailure::Untrusted(
+                SigstoreError::SigstoreRekorBundleNotFoundError,
+            )],
+        );
+        assert_eq!(
+            FailureReason::from_error(&SigstoreError::VerificationErrors(errors)),
+            FailureReason::NoTrustedSignature
+        );
+    }
+}
@@ -0,0 +1,356 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Retrieval of an OpenID Connect ID Token with the device authorization
+//! grant ([RFC 8628](https://www.rfc-editor.org/rfc/rfc8628)).
+//!
+//! This flow doesn't need a browser running on the same machine, nor a local
+//! port to receive the redirect: it's meant for headless environments like
+//! SSH sessions or containers. The user is shown a URL and a code, which can
+//! be entered on any other device:
+//!
+//! ```rust,no_run
+//! use sigstore::oauth::deviceflow::DeviceAuthorize;
+//!
+//! # async fn doc() -> sigstore::errors::Result<()> {
+//! let authorization = DeviceAuthorize::new("sigstore", "", "https://oauth2.sigstore.dev/auth")
+//!     .device_authorization_async()
+//!     .await?;
+//! println!(
+//!     "Open {} and enter the code {}",
+//!     authorization.verification_uri, authorization.user_code
+//! );
+//!
+//! // wait for the user to complete the authorization
+//! let (claims, id_token) = authorization.poll_token_async().await?;
+//! println!("Email {:?}", claims.email());
+//! # Ok(())
+//! # }
+//! ```
+
+use openidconnect::core::{
+    CoreAuthDisplay, CoreClaimName, CoreClaimType, CoreClientAuthMethod, CoreGrantType,
+    CoreIdToken, CoreIdTokenClaims, CoreIdTokenVerifier, CoreJsonWebKey, CoreJsonWebKeyType,
+    CoreJsonWebKeyUse, CoreJweContentEncryptionAlgorithm, CoreJweKeyManagementAlgorithm,
+    CoreJwsSigningAlgorithm, CoreResponseMode, CoreResponseType, CoreSubjectIdentifierType,
+    CoreTokenResponse,
+};
+use openidconnect::http::header::{HeaderValue, ACCEPT, CONTENT_TYPE};
+use openidconnect::http::{HeaderMap, Method};
+use openidconnect::reqwest::async_http_client;
+use openidconnect::{
+    AdditionalProviderMetadata, ClientId, HttpRequest, IssuerUrl, Nonce, ProviderMetadata,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::debug;
+use url::Url;
+
+use crate::errors::{Result, SigstoreError};
+
+/// The `grant_type` used to exchange a device code for a token
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// The polling interval used when the server doesn't provide one
+const DEFAULT_POLL_INTERVAL: u64 = 5;
+
+/// The device authorization endpoint isn't part of the OpenID Connect
+/// discovery document defined by the core specification
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct DeviceEndpointProviderMetadata {
+    device_authorization_endpoint: Option<Url>,
+}
+
+impl AdditionalProviderMetadata for DeviceEndpointProviderMetadata {}
+
+type DeviceProviderMetadata = ProviderMetadata<
+    DeviceEndpointProviderMetadata,
+    CoreAuthDisplay,
+    CoreClientAuthMethod,
+    CoreClaimName,
+    CoreClaimType,
+    CoreGrantType,
+    CoreJweContentEncryptionAlgorithm,
+    CoreJweKeyManagementAlgorithm,
+    CoreJwsSigningAlgorithm,
+    CoreJsonWebKeyType,
+    CoreJsonWebKeyUse,
+    CoreJsonWebKey,
+    CoreResponseMode,
+    CoreResponseType,
+    CoreSubjectIdentifierType,
+>;
+
+#[derive(Deserialize)]
+struct DeviceAuthorizationResponse {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    verification_uri_complete: Option<String>,
+    expires_in: u64,
+    interval: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+impl ErrorResponse {
+    fn into_error(self) -> SigstoreError {
+        let msg = match self.error_description {
+            Some(description) => format!("{}: {description}", self.error),
+            None => self.error,
+        };
+        SigstoreError::DeviceFlowError(msg)
+    }
+}
+
+/// Starts the device authorization flow against an OpenID Connect server
+#[derive(Debug)]
+pub struct DeviceAuthorize {
+    oidc_client_id: String,
+    oidc_client_secret: String,
+    oidc_issuer: String,
+}
+
+impl DeviceAuthorize {
+    /// Create a new DeviceAuthorize struct
+    ///
+    /// # Arguments
+    ///
+    /// * `client_id` - the client ID of the application
+    /// * `client_secret` - the client secret of the application, can be empty
+    ///   for public clients
+    /// * `issuer` - the URL of the OpenID Connect server
+    pub fn new(client_id: &str, client_secret: &str, issuer: &str) -> Self {
+        Self {
+            oidc_client_id: client_id.to_string(),
+            oidc_client_secret: client_secret.to_string(),
+            oidc_issuer: issuer.to_string(),
+        }
+    }
+
+    /// Discover the endpoints of the server and request a device code.
+    ///
+    /// The returned [`DeviceAuthorization`] holds the URL and the code to be
+    /// shown to the user.
+    pub async fn device_authorization_async(&self) -> Result<DeviceAuthorization> {
+        let issuer = IssuerUrl::new(self.oidc_issuer.to_owned())?;
+        let provider_metadata = DeviceProviderMetadata::discover_async(issuer, async_http_client)
+            .await
+            .map_err(|e| {
+                SigstoreError::DeviceFlowError(format!("cannot discover the issuer: {e}"))
+            })?;
+
+        let device_authorization_url = provider_metadata
+            .additional_metadata()
+            .device_authorization_endpoint
+            .clone()
+            .ok_or_else(|| {
+                SigstoreError::DeviceFlowError(
+                    "the issuer doesn't support the device authorization grant".to_string(),
+                )
+            })?;
+        let token_url = provider_metadata
+            .token_endpoint()
+            .ok_or_else(|| {
+                SigstoreError::DeviceFlowError("the issuer doesn't have a token endpoint".into())
+            })?
+            .url()
+            .clone();
+
+        let response: DeviceAuthorizationResponse = post_form(
+            device_authorization_url,
+            &self.form(&[("scope", "openid email")]),
+        )
+        .await?
+        .map_err(ErrorResponse::into_error)?;
+
+        let id_token_verifier = CoreIdTokenVerifier::new_public_client(
+            ClientId::new(self.oidc_client_id.clone()),
+            provider_metadata.issuer().clone(),
+            provider_metadata.jwks().clone(),
+        );
+
+        Ok(DeviceAuthorization {
+            user_code: response.user_code,
+            verification_uri: response.verification_uri,
+            verification_uri_complete: response.verification_uri_complete,
+            expires_in: Duration::from_secs(response.expires_in),
+            interval: Duration::from_secs(response.interval.unwrap_or(DEFAULT_POLL_INTERVAL)),
+            device_code: response.device_code,
+            token_url,
+            form: self.form(&[]),
+            id_token_verifier,
+        })
+    }
+
+    /// The parameters authenticating the client, followed by `params`
+    fn form(&self, params: &[(&str, &str)]) -> Vec<(String, String)> {
+        let mut form = vec![("client_id".to_string(), self.oidc_client_id.clone())];
+        if !self.oidc_client_secret.is_empty() {
+            form.push(("client_secret".to_string(), self.oidc_client_secret.clone()));
+        }
+        form.extend(
+            params
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+        );
+        form
+    }
+}
+
+/// A pending device authorization, waiting for the user to enter
+/// [`user_code`](DeviceAuthorization::user_code) at
+/// [`verification_uri`](DeviceAuthorization::verification_uri)
+pub struct DeviceAuthorization {
+    /// The code to be entered by the user
+    pub user_code: String,
+    /// The URL where the user enters the code
+    pub verification_uri: String,
+    /// The URL where the user authorizes the device without having to enter
+    /// the code, when supported by the server
+    pub verification_uri_complete: Option<String>,
+    /// How long the codes are valid
+    pub expires_in: Duration,
+    /// How often the token endpoint is polled
+    pub interval: Duration,
+    device_code: String,
+    token_url: Url,
+    form: Vec<(String, String)>,
+    id_token_verifier: CoreIdTokenVerifier<'static>,
+}
+
+impl DeviceAuthorization {
+    /// Poll the token endpoint until the user completes the authorization,
+    /// then verify the ID token returned by the server
+    pub async fn poll_token_async(self) -> Result<(CoreIdTokenClaims, CoreIdToken)> {
+        let mut form = self.form.clone();
+        form.push(("grant_type".to_string(), DEVICE_CODE_GRANT_TYPE.to_string()));
+        form.push(("device_code".to_string(), self.device_code.clone()));
+
+        let deadline = tokio::time::Instant::now() + self.expires_in;
+        let mut interval = self.interval;
+        let token_response = loop {
+            if tokio::time::Instant::now() >= deadline {
+                return Err(SigstoreError::DeviceFlowError(
+                    "the device code expired".to_string(),
+                ));
+            }
+            tokio::time::sleep(interval).await;
+
+            match post_form::<CoreTokenResponse>(self.token_url.clone(), &form).await? {
+                Ok(token_response) => break token_response,
+                Err(e) if e.error == "authorization_pending" => {
+                    debug!("Waiting for the user to authorize the device");
+                }
+                Err(e) if e.error == "slow_down" => {
+                    interval += Duration::from_secs(DEFAULT_POLL_INTERVAL);
+                }
+                Err(e) => return Err(e.into_error()),
+            }
+        };
+
+        let id_token = token_response
+            .extra_fields()
+            .id_token()
+            .ok_or(SigstoreError::NoIDToken)?;
+        // no nonce is sent with the device authorization grant
+        let no_nonce = |_: Option<&Nonce>| Ok::<(), String>(());
+        let claims = id_token
+            .claims(&self.id_token_verifier, no_nonce)
+            .map_err(|_| SigstoreError::ClaimsVerificationError)?;
+        Ok((claims.clone(), id_token.clone()))
+    }
+}
+
+/// POST `form` to `url`, the inner result holds the OAuth2 error returned
+/// by the server, if any
+async fn post_form<T: serde::de::DeserializeOwned>(
+    url: Url,
+    form: &[(String, String)],
+) -> Result<std::result::Result<T, ErrorResponse>> {
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(form)
+        .finish();
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/x-www-form-urlencoded"),
+    );
+    headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+
+    let response = async_http_client(HttpRequest {
+        url,
+        method: Method::POST,
+        headers,
+        body: body.into_bytes(),
+    })
+    .await
+    .map_err(|_| SigstoreError::ClaimsAccessPointError)?;
+
+    if response.status_code.is_success() {
+        Ok(Ok(serde_json::from_slice(&response.body)?))
+    } else {
+        match serde_json::from_slice(&response.body) {
+            Ok(error) => Ok(Err(error)),
+            Err(_) => Err(SigstoreError::DeviceFlowError(format!(
+                "unexpected response from the server: {}",
+                response.status_code
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_error_responses() {
+        let error: ErrorResponse = serde_json::from_str(
+            r#"{"error":"access_denied","error_description":"the user declined"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            error.into_error().to_string(),
+            "Device authorization failed: access_denied: the user declined"
+        );
+
+        let error: ErrorResponse = serde_json::from_str(r#"{"error":"expired_token"}"#).unwrap();
+        assert_eq!(
+            error.into_error().to_string(),
+            "Device authorization failed: expired_token"
+        );
+    }
+
+    #[test]
+    fn client_form() {
+        let public = DeviceAuthorize::new("sigstore", "", "https://oauth2.sigstore.dev/auth");
+        assert_eq!(
+            public.form(&[("scope", "openid email")]),
+            vec![
+                ("client_id".to_string(), "sigstore".to_string()),
+                ("scope".to_string(), "openid email".to_string()),
+            ]
+        );
+
+        let confidential =
+            DeviceAuthorize::new("sigstore", "secret", "https://oauth2.sigstore.dev/auth");
+        assert_eq!(confidential.form(&[]).len(), 2);
+    }
+}
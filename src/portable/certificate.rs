@@ -0,0 +1,35 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checks of the certificates that don't depend on the system clock.
+
+use core::time::Duration;
+use x509_cert::Certificate;
+
+use super::{Error, Result};
+
+/// Ensure `certificate` is valid at `time`, given as the time elapsed since
+/// the UNIX epoch, e.g. the time a signature was integrated into the
+/// transparency log
+pub fn check_validity_at(certificate: &Certificate, time: Duration) -> Result<()> {
+    let validity = &certificate.tbs_certificate.validity;
+    if time < validity.not_before.to_unix_duration() {
+        return Err(Error::InvalidCertificate("not yet valid"));
+    }
+    if time > validity.not_after.to_unix_duration() {
+        return Err(Error::InvalidCertificate("expired"));
+    }
+    Ok(())
+}
@@ -0,0 +1,32 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The [DSSE](https://github.com/secure-systems-lab/dsse) signing protocol.
+
+use alloc::vec::Vec;
+
+/// The Pre-Authentication Encoding of a DSSE payload, this is the message
+/// that is actually signed
+pub fn pae(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut message = alloc::format!(
+        "DSSEv1 {} {} {} ",
+        payload_type.len(),
+        payload_type,
+        payload.len()
+    )
+    .into_bytes();
+    message.extend_from_slice(payload);
+    message
+}
@@ -0,0 +1,204 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Merkle tree proofs, following
+//! [RFC 9162](https://www.rfc-editor.org/rfc/rfc9162#section-2.1).
+
+use alloc::vec::Vec;
+use sha2::{Digest, Sha256};
+
+use super::{Error, Result};
+
+const LEAF_HASH_PREFIX: u8 = 0;
+const NODE_HASH_PREFIX: u8 = 1;
+
+/// Compute the hash of a leaf of the Merkle tree
+pub fn hash_leaf(data: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_HASH_PREFIX]);
+    hasher.update(data);
+    hasher.finalize().to_vec()
+}
+
+/// Compute the hash of an interior node of the Merkle tree
+pub fn hash_children(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_HASH_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().to_vec()
+}
+
+/// Verify that the tree of size `new_size` with root `new_root` is an
+/// append-only extension of the tree of size `old_size` with root `old_root`.
+///
+/// `proof` is the consistency proof, ordered as described by RFC 9162.
+pub fn verify_consistency(
+    old_size: u64,
+    new_size: u64,
+    old_root: &[u8],
+    new_root: &[u8],
+    proof: &[Vec<u8>],
+) -> Result<()> {
+    let fail = |reason| Err(Error::InvalidProof(reason));
+
+    if old_size > new_size {
+        return fail("the old tree is bigger than the new one");
+    }
+    if old_size == new_size {
+        if !proof.is_empty() {
+            return fail("proof must be empty when the trees have the same size");
+        }
+        if old_root != new_root {
+            return fail("trees with the same size have different roots");
+        }
+        return Ok(());
+    }
+    if old_size == 0 {
+        // the empty tree is consistent with any tree
+        return Ok(());
+    }
+    if proof.is_empty() {
+        return fail("empty proof");
+    }
+
+    let mut path: Vec<&[u8]> = Vec::with_capacity(proof.len() + 1);
+    if old_size.is_power_of_two() {
+        path.push(old_root);
+    }
+    path.extend(proof.iter().map(|h| h.as_slice()));
+
+    let mut fn_ = old_size - 1;
+    let mut sn = new_size - 1;
+    while fn_ & 1 == 1 {
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+
+    let mut fr = path[0].to_vec();
+    let mut sr = path[0].to_vec();
+
+    for c in &path[1..] {
+        if sn == 0 {
+            return fail("proof is too long");
+        }
+        if fn_ & 1 == 1 || fn_ == sn {
+            fr = hash_children(c, &fr);
+            sr = hash_children(c, &sr);
+            while fn_ & 1 == 0 && fn_ != 0 {
+                fn_ >>= 1;
+                sn >>= 1;
+            }
+        } else {
+            sr = hash_children(&sr, c);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+
+    if sn != 0 {
+        return fail("proof is too short");
+    }
+    if fr != old_root {
+        return fail("computed old root does not match");
+    }
+    if sr != new_root {
+        return fail("computed new root does not match");
+    }
+    Ok(())
+}
+
+/// Verify that the leaf with hash `leaf_hash`, at `index`, is part of the
+/// tree of size `tree_size` with root `root`.
+///
+/// `proof` is the inclusion proof, ordered as described by RFC 9162.
+pub fn verify_inclusion(
+    index: u64,
+    tree_size: u64,
+    leaf_hash: &[u8],
+    root: &[u8],
+    proof: &[Vec<u8>],
+) -> Result<()> {
+    if index >= tree_size {
+        return Err(Error::InvalidProof("the leaf is outside of the tree"));
+    }
+
+    let mut fn_ = index;
+    let mut sn = tree_size - 1;
+    let mut r = leaf_hash.to_vec();
+    for p in proof {
+        if sn == 0 {
+            return Err(Error::InvalidProof("proof is too long"));
+        }
+        if fn_ & 1 == 1 || fn_ == sn {
+            r = hash_children(p, &r);
+            while fn_ & 1 == 0 && fn_ != 0 {
+                fn_ >>= 1;
+                sn >>= 1;
+            }
+        } else {
+            r = hash_children(&r, p);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+
+    if sn != 0 {
+        return Err(Error::InvalidProof("proof is too short"));
+    }
+    if r != root {
+        return Err(Error::InvalidProof("computed root does not match"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inclusion_proofs_are_verified() {
+        let leaves: Vec<Vec<u8>> = (0..3).map(|i| hash_leaf(&[i])).collect();
+        let left = hash_children(&leaves[0], &leaves[1]);
+        let root = hash_children(&left, &leaves[2]);
+
+        assert!(verify_inclusion(
+            0,
+            3,
+            &leaves[0],
+            &root,
+            &[leaves[1].clone(), leaves[2].clone()]
+        )
+        .is_ok());
+        assert!(verify_inclusion(
+            1,
+            3,
+            &leaves[1],
+            &root,
+            &[leaves[0].clone(), leaves[2].clone()]
+        )
+        .is_ok());
+        assert!(verify_inclusion(2, 3, &leaves[2], &root, &[left.clone()]).is_ok());
+
+        assert_eq!(
+            verify_inclusion(2, 3, &leaves[1], &root, &[left.clone()]),
+            Err(Error::InvalidProof("computed root does not match"))
+        );
+        assert_eq!(
+            verify_inclusion(3, 3, &leaves[2], &root, &[left]),
+            Err(Error::InvalidProof("the leaf is outside of the tree"))
+        );
+    }
+}
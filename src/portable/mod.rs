@@ -0,0 +1,67 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The verification core, usable without the standard library.
+//!
+//! The code of this module depends only on `core`, `alloc` and on crates
+//! supporting `no_std`. It holds the pure verification checks, which need
+//! neither the network nor the system clock:
+//!
+//! * [`dsse`]: the message signed by DSSE envelopes
+//! * [`merkle`]: the proofs of the transparency log
+//! * [`signature`]: ECDSA and Ed25519 signatures
+//! * [`certificate`]: the validity period of certificates, at a given time
+//!
+//! The rest of the crate is built on top of these checks. Constrained
+//! environments, like bootloaders and embedded update agents, can vendor
+//! this module inside of a `no_std + alloc` crate.
+//!
+//! **Note well:** this module is built as part of this crate, which
+//! requires `std`; nothing enforces the absence of `std` yet. Publishing the
+//! module as its own `no_std` crate is left to a later split of the crate.
+
+use core::fmt;
+
+pub mod certificate;
+pub mod dsse;
+pub mod merkle;
+pub mod signature;
+
+/// The errors of the verification core
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// A transparency log proof is invalid
+    InvalidProof(&'static str),
+    /// A signature doesn't match the message
+    InvalidSignature,
+    /// A key can't be parsed, or its type is not supported
+    UnsupportedKey,
+    /// A certificate is not valid at the given time
+    InvalidCertificate(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidProof(reason) => write!(f, "invalid proof: {reason}"),
+            Error::InvalidSignature => write!(f, "invalid signature"),
+            Error::UnsupportedKey => write!(f, "unsupported key"),
+            Error::InvalidCertificate(reason) => write!(f, "invalid certificate: {reason}"),
+        }
+    }
+}
+
+/// The result of the checks of the verification core
+pub type Result<T> = core::result::Result<T, Error>;
@@ -0,0 +1,111 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of the ECDSA and Ed25519 signatures.
+
+use ed25519_dalek::pkcs8::PublicKeyBytes;
+use pkcs8::DecodePublicKey;
+use sha2::{Digest, Sha256, Sha384};
+use signature::{DigestVerifier, Verifier};
+
+use super::{Error, Result};
+
+/// A public key able to verify signatures
+#[derive(Debug, Clone)]
+pub enum VerifyingKey {
+    /// ECDSA with the P-256 curve and SHA-256
+    EcdsaP256(p256::ecdsa::VerifyingKey),
+    /// ECDSA with the P-384 curve and SHA-384
+    EcdsaP384(p384::ecdsa::VerifyingKey),
+    /// Ed25519
+    Ed25519(ed25519_dalek::VerifyingKey),
+}
+
+impl VerifyingKey {
+    /// Parse a DER encoded `SubjectPublicKeyInfo`
+    pub fn from_public_key_der(der: &[u8]) -> Result<Self> {
+        if let Ok(key) = p256::ecdsa::VerifyingKey::from_public_key_der(der) {
+            Ok(VerifyingKey::EcdsaP256(key))
+        } else if let Ok(key) = p384::ecdsa::VerifyingKey::from_public_key_der(der) {
+            Ok(VerifyingKey::EcdsaP384(key))
+        } else {
+            let bytes =
+                PublicKeyBytes::from_public_key_der(der).map_err(|_| Error::UnsupportedKey)?;
+            ed25519_dalek::VerifyingKey::from_bytes(bytes.as_ref())
+                .map(VerifyingKey::Ed25519)
+                .map_err(|_| Error::UnsupportedKey)
+        }
+    }
+
+    /// Verify the raw `signature` of `msg`. ECDSA signatures are DER
+    /// encoded.
+    pub fn verify(&self, msg: &[u8], signature: &[u8]) -> Result<()> {
+        let verified = match self {
+            VerifyingKey::EcdsaP256(key) => p256::ecdsa::Signature::from_der(signature)
+                .and_then(|sig| key.verify_digest(Sha256::new_with_prefix(msg), &sig)),
+            VerifyingKey::EcdsaP384(key) => p384::ecdsa::Signature::from_der(signature)
+                .and_then(|sig| key.verify_digest(Sha384::new_with_prefix(msg), &sig)),
+            VerifyingKey::Ed25519(key) => {
+                ed25519::Signature::from_slice(signature).and_then(|sig| key.verify(msg, &sig))
+            }
+        };
+        verified.map_err(|_| Error::InvalidSignature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pkcs8::EncodePublicKey;
+    use signature::{DigestSigner, Signer};
+
+    #[test]
+    fn verify_signatures() {
+        let msg = b"hello world";
+
+        let signing_key = p256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        let sig: p256::ecdsa::Signature = signing_key.sign_digest(Sha256::new_with_prefix(msg));
+        let der = signing_key
+            .verifying_key()
+            .to_public_key_der()
+            .expect("cannot encode the public key");
+        let key = VerifyingKey::from_public_key_der(der.as_bytes()).expect("cannot parse the key");
+        assert!(matches!(key, VerifyingKey::EcdsaP256(_)));
+        assert!(key.verify(msg, sig.to_der().as_bytes()).is_ok());
+        assert_eq!(
+            key.verify(b"tampered", sig.to_der().as_bytes()),
+            Err(Error::InvalidSignature)
+        );
+
+        let signing_key = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+        let sig = signing_key.sign(msg);
+        let der = signing_key
+            .verifying_key()
+            .to_public_key_der()
+            .expect("cannot encode the public key");
+        let key = VerifyingKey::from_public_key_der(der.as_bytes()).expect("cannot parse the key");
+        assert!(matches!(key, VerifyingKey::Ed25519(_)));
+        assert!(key.verify(msg, &sig.to_bytes()).is_ok());
+        assert_eq!(
+            key.verify(b"tampered", &sig.to_bytes()),
+            Err(Error::InvalidSignature)
+        );
+
+        assert_eq!(
+            VerifyingKey::from_public_key_der(b"not a key").map(|_| ()),
+            Err(Error::UnsupportedKey)
+        );
+    }
+}
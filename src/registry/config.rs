@@ -18,14 +18,30 @@
 use serde::Serialize;
 use std::cmp::Ordering;
 use std::convert::From;
+use std::time::Duration;
 
-/// A method for authenticating to a registry
+/// The username used by the docker credential helpers to mark identity tokens
+pub const IDENTITY_TOKEN_USERNAME: &str = "<token>";
+
+/// A method for authenticating to a registry.
+///
+/// The credentials are presented to the token service advertised by the
+/// `WWW-Authenticate` challenge of the registry, like the ones of Docker
+/// Hub and GHCR, or directly to the registry when it asks for HTTP Basic
+/// authentication, like ECR does.
 #[derive(Serialize, Debug)]
 pub enum Auth {
     /// Access the registry anonymously
     Anonymous,
     /// Access the registry using HTTP Basic authentication
     Basic(String, String),
+    /// Access the registry with a token issued for it, like a GitHub
+    /// personal access token for GHCR or the password returned by
+    /// `aws ecr get-login-password` for ECR
+    Bearer(String),
+    /// Access the registry with the identity token, also known as refresh
+    /// token, stored by `docker login` inside of `config.json`
+    IdentityToken(String),
 }
 
 impl From<&Auth> for oci_distribution::secrets::RegistryAuth {
@@ -35,6 +51,16 @@ impl From<&Auth> for oci_distribution::secrets::RegistryAuth {
             Auth::Basic(username, pass) => {
                 oci_distribution::secrets::RegistryAuth::Basic(username.clone(), pass.clone())
             }
+            // the token services only accept HTTP Basic authentication from
+            // oci-distribution, the tokens are given as password like
+            // `docker login --password-stdin` does
+            Auth::Bearer(token) => {
+                oci_distribution::secrets::RegistryAuth::Basic(String::new(), token.clone())
+            }
+            Auth::IdentityToken(token) => oci_distribution::secrets::RegistryAuth::Basic(
+                IDENTITY_TOKEN_USERNAME.to_string(),
+                token.clone(),
+            ),
         }
     }
 }
@@ -43,6 +69,16 @@ impl From<&oci_distribution::secrets::RegistryAuth> for Auth {
     fn from(auth: &oci_distribution::secrets::RegistryAuth) -> Self {
         match auth {
             oci_distribution::secrets::RegistryAuth::Anonymous => Auth::Anonymous,
+            oci_distribution::secrets::RegistryAuth::Basic(username, pass)
+                if username == IDENTITY_TOKEN_USERNAME =>
+            {
+                Auth::IdentityToken(pass.clone())
+            }
+            oci_distribution::secrets::RegistryAuth::Basic(username, pass)
+                if username.is_empty() =>
+            {
+                Auth::Bearer(pass.clone())
+            }
             oci_distribution::secrets::RegistryAuth::Basic(username, pass) => {
                 Auth::Basic(username.clone(), pass.clone())
             }
@@ -124,6 +160,14 @@ impl From<&Certificate> for oci_distribution::client::Certificate {
 }
 
 /// A client configuration
+///
+/// ## Connection tuning
+///
+/// The connection-level settings (HTTP/2 preference, keep-alive, size of
+/// the idle connection pool) are applied to the HTTP clients built by this
+/// crate to reach the registries, like the one of the referrers API. The
+/// client of `oci-distribution` builds its own HTTP client, which ignores
+/// them.
 #[derive(Debug, Clone)]
 pub struct ClientConfig {
     /// Which protocol the client should use
@@ -139,6 +183,39 @@ pub struct ClientConfig {
     /// A list of extra root certificate to trust. This can be used to connect
     /// to servers using self-signed certificates
     pub extra_root_certificates: Vec<Certificate>,
+
+    /// Speak HTTP/2 with the registries right away, without negotiating it.
+    /// Defaults to false
+    ///
+    /// Only the requests of the referrers API honor it, `oci-distribution`
+    /// keeps negotiating the protocol of the other ones.
+    pub http2_prior_knowledge: bool,
+
+    /// Interval of the HTTP/2 keep-alive pings. Defaults to `None`, which
+    /// disables them
+    ///
+    /// Only sent over the connections of the referrers API.
+    pub http2_keep_alive_interval: Option<Duration>,
+
+    /// Interval of the TCP keep-alive probes. Defaults to `None`, which
+    /// disables them
+    ///
+    /// Only sent over the connections of the referrers API.
+    pub tcp_keepalive: Option<Duration>,
+
+    /// How long an idle connection is kept in the pool. Defaults to `None`,
+    /// which keeps it for 90 seconds
+    ///
+    /// Only the pool of the referrers API is affected, the one of
+    /// `oci-distribution` keeps its defaults.
+    pub pool_idle_timeout: Option<Duration>,
+
+    /// Maximum number of idle connections kept for each registry. Defaults
+    /// to `None`, which sets no limit
+    ///
+    /// Only the pool of the referrers API is affected, the one of
+    /// `oci-distribution` keeps its defaults.
+    pub pool_max_idle_per_host: Option<usize>,
 }
 
 impl Default for ClientConfig {
@@ -149,10 +226,42 @@ impl Default for ClientConfig {
             accept_invalid_hostnames: false,
             accept_invalid_certificates: false,
             extra_root_certificates: Vec::new(),
+            http2_prior_knowledge: false,
+            http2_keep_alive_interval: None,
+            tcp_keepalive: None,
+            pool_idle_timeout: None,
+            pool_max_idle_per_host: None,
         }
     }
 }
 
+impl ClientConfig {
+    /// Apply the connection-level settings to `builder`
+    pub(crate) fn tune_connections(
+        &self,
+        mut builder: reqwest::ClientBuilder,
+    ) -> reqwest::ClientBuilder {
+        if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        if let Some(interval) = self.http2_keep_alive_interval {
+            builder = builder.http2_keep_alive_interval(interval);
+        }
+        if let Some(interval) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(interval);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        builder
+    }
+}
+
+// `oci-distribution` does not allow the connection-level settings to be
+// configured, they are not forwarded.
 impl From<ClientConfig> for oci_distribution::client::ClientConfig {
     fn from(config: ClientConfig) -> Self {
         oci_distribution::client::ClientConfig {
@@ -196,3 +305,39 @@ impl From<oci_distribution::client::PushResponse> for PushResponse {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oci_distribution::secrets::RegistryAuth;
+
+    #[test]
+    fn convert_auth() {
+        for auth in [
+            Auth::Anonymous,
+            Auth::Basic("octocat".to_string(), "hunter2".to_string()),
+            Auth::Bearer("ghp_token".to_string()),
+            Auth::IdentityToken("refresh".to_string()),
+        ] {
+            let registry_auth: RegistryAuth = (&auth).into();
+            let converted: Auth = (&registry_auth).into();
+            assert_eq!(format!("{converted:?}"), format!("{auth:?}"));
+        }
+    }
+
+    #[test]
+    fn build_tuned_http_client() {
+        let config = ClientConfig {
+            http2_prior_knowledge: true,
+            http2_keep_alive_interval: Some(Duration::from_secs(30)),
+            tcp_keepalive: Some(Duration::from_secs(60)),
+            pool_idle_timeout: Some(Duration::from_secs(120)),
+            pool_max_idle_per_host: Some(4),
+            ..Default::default()
+        };
+        assert!(config
+            .tune_connections(reqwest::Client::builder())
+            .build()
+            .is_ok());
+    }
+}
@@ -0,0 +1,317 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Registry credentials stored by the docker CLI.
+//!
+//! The [`DockerConfig`] reads the `config.json` file of docker, the same one
+//! used by cosign, and finds the credentials of a registry like docker does:
+//!
+//! 1. the credential helper configured for the registry inside of
+//!    `credHelpers`
+//! 2. the credentials stored inside of `auths`
+//! 3. the credential store configured by `credsStore`
+//!
+//! Registries without credentials are accessed anonymously.
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::{ClientBuilder, CosignCapabilities};
+//! use sigstore::registry::docker_config::DockerConfig;
+//! use sigstore::registry::OciReference;
+//!
+//! # async fn doc() -> sigstore::errors::Result<()> {
+//! let image: OciReference = "ghcr.io/octocat/hello-world:latest".parse()?;
+//! let auth = DockerConfig::load()?.auth_for(image.registry())?;
+//!
+//! let mut client = ClientBuilder::default().build()?;
+//! let (cosign_image, manifest_digest) = client.triangulate(&image, &auth).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+use super::{Auth, IDENTITY_TOKEN_USERNAME};
+use crate::errors::{Result, SigstoreError};
+
+/// The key used by docker for the credentials of Docker Hub
+const DOCKER_HUB_KEY: &str = "https://index.docker.io/v1/";
+
+/// The message returned by the credential helpers when they don't hold
+/// credentials for the registry
+const HELPER_NOT_FOUND: &str = "credentials not found in native keychain";
+
+/// The contents of the `config.json` file of docker
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DockerConfig {
+    /// The credentials stored inside of the file, indexed by registry
+    #[serde(default)]
+    pub auths: HashMap<String, AuthEntry>,
+    /// The credential helper used for all the registries, like `desktop`
+    /// for `docker-credential-desktop`
+    #[serde(default)]
+    pub creds_store: Option<String>,
+    /// The credential helpers used for specific registries
+    #[serde(default)]
+    pub cred_helpers: HashMap<String, String>,
+}
+
+/// The credentials of a registry stored inside of `config.json`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuthEntry {
+    /// The base64 encoded `username:password` string
+    #[serde(default)]
+    pub auth: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// A refresh token, exchanged by the registry for access tokens
+    #[serde(default)]
+    pub identitytoken: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HelperCredentials {
+    username: String,
+    secret: String,
+}
+
+impl DockerConfig {
+    /// Load the configuration of the current user, from
+    /// `$DOCKER_CONFIG/config.json` or `~/.docker/config.json`.
+    ///
+    /// An empty configuration is returned when the file doesn't exist.
+    pub fn load() -> Result<Self> {
+        match default_path() {
+            Some(path) if path.exists() => Self::from_file(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Load the configuration stored at `path`
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path).map_err(|e| {
+            SigstoreError::DockerConfigError(format!("cannot read {}: {e}", path.display()))
+        })?;
+        Self::from_json(&raw)
+    }
+
+    /// Parse the contents of a `config.json` file
+    pub fn from_json(raw: &str) -> Result<Self> {
+        serde_json::from_str(raw)
+            .map_err(|e| SigstoreError::DockerConfigError(format!("cannot parse config: {e}")))
+    }
+
+    /// The credentials to use for `registry`, like `ghcr.io` or
+    /// `docker.io`. Credential helpers are invoked when configured.
+    pub fn auth_for(&self, registry: &str) -> Result<Auth> {
+        let registry = normalize_registry(registry);
+
+        if let Some(helper) = self.cred_helpers.get(registry) {
+            return helper_auth(helper, &server_url(registry));
+        }
+        if let Some(entry) = self.auth_entry(registry) {
+            return entry.to_auth();
+        }
+        match &self.creds_store {
+            Some(helper) => helper_auth(helper, &server_url(registry)),
+            None => Ok(Auth::Anonymous),
+        }
+    }
+
+    fn auth_entry(&self, registry: &str) -> Option<&AuthEntry> {
+        self.auths
+            .iter()
+            .find(|(key, _)| normalize_registry(key) == registry)
+            .map(|(_, entry)| entry)
+    }
+}
+
+impl AuthEntry {
+    fn to_auth(&self) -> Result<Auth> {
+        if let Some(token) = &self.identitytoken {
+            return Ok(Auth::IdentityToken(token.clone()));
+        }
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            return Ok(Auth::Basic(username.clone(), password.clone()));
+        }
+        match &self.auth {
+            Some(auth) => {
+                let decoded = String::from_utf8(BASE64_STD_ENGINE.decode(auth)?).map_err(|e| {
+                    SigstoreError::DockerConfigError(format!("invalid auth entry: {e}"))
+                })?;
+                let (username, password) = decoded.split_once(':').ok_or_else(|| {
+                    SigstoreError::DockerConfigError(
+                        "invalid auth entry: expected username:password".to_string(),
+                    )
+                })?;
+                Ok(Auth::Basic(username.to_string(), password.to_string()))
+            }
+            None => Ok(Auth::Anonymous),
+        }
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    match std::env::var_os("DOCKER_CONFIG") {
+        Some(dir) => Some(PathBuf::from(dir).join("config.json")),
+        None => std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".docker").join("config.json")),
+    }
+}
+
+/// Reduce the keys of `config.json`, which can be URLs, to the host of the
+/// registry. All the names of Docker Hub become `docker.io`.
+fn normalize_registry(registry: &str) -> &str {
+    let registry = registry
+        .trim_start_matches("https://")
+        .trim_start_matches("http://");
+    let registry = registry.split('/').next().unwrap_or(registry);
+    match registry {
+        "index.docker.io" | "registry-1.docker.io" => "docker.io",
+        _ => registry,
+    }
+}
+
+/// The server URL given to the credential helpers
+fn server_url(registry: &str) -> String {
+    match registry {
+        "docker.io" => DOCKER_HUB_KEY.to_string(),
+        _ => registry.to_string(),
+    }
+}
+
+/// Ask the `docker-credential-<helper>` binary the credentials of `server_url`
+fn helper_auth(helper: &str, server_url: &str) -> Result<Auth> {
+    let program = format!("docker-credential-{helper}");
+    debug!(%program, %server_url, "Invoking credential helper");
+    let helper_error = |msg: String| SigstoreError::DockerConfigError(format!("{program}: {msg}"));
+
+    let mut child = Command::new(&program)
+        .arg("get")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| helper_error(e.to_string()))?;
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| helper_error("cannot write to stdin".to_string()))?
+        .write_all(server_url.as_bytes())
+        .map_err(|e| helper_error(e.to_string()))?;
+    let output = child
+        .wait_with_output()
+        .map_err(|e| helper_error(e.to_string()))?;
+
+    if !output.status.success() {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if stdout.trim() == HELPER_NOT_FOUND {
+            return Ok(Auth::Anonymous);
+        }
+        return Err(helper_error(format!(
+            "{} {}",
+            stdout.trim(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    parse_helper_output(&output.stdout).map_err(helper_error)
+}
+
+fn parse_helper_output(stdout: &[u8]) -> std::result::Result<Auth, String> {
+    let credentials: HelperCredentials =
+        serde_json::from_slice(stdout).map_err(|e| format!("invalid output: {e}"))?;
+    if credentials.username == IDENTITY_TOKEN_USERNAME {
+        return Ok(Auth::IdentityToken(credentials.secret));
+    }
+    Ok(Auth::Basic(credentials.username, credentials.secret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credentials_from_config() {
+        let config = DockerConfig::from_json(
+            r#"{
+                "auths": {
+                    "https://index.docker.io/v1/": { "auth": "b2N0b2NhdDpodW50ZXIy" },
+                    "registry.local:5000": { "username": "admin", "password": "secret" },
+                    "quay.io": {},
+                    "myregistry.azurecr.io": { "auth": "", "identitytoken": "refresh" }
+                },
+                "credHelpers": {
+                    "123456789012.dkr.ecr.us-east-1.amazonaws.com": "ecr-login"
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            config.auth_for("docker.io").unwrap(),
+            Auth::Basic(user, pass) if user == "octocat" && pass == "hunter2"
+        ));
+        assert!(matches!(
+            config.auth_for("index.docker.io").unwrap(),
+            Auth::Basic(user, _) if user == "octocat"
+        ));
+        assert!(matches!(
+            config.auth_for("registry.local:5000").unwrap(),
+            Auth::Basic(user, pass) if user == "admin" && pass == "secret"
+        ));
+        assert!(matches!(
+            config.auth_for("quay.io").unwrap(),
+            Auth::Anonymous
+        ));
+        assert!(matches!(
+            config.auth_for("myregistry.azurecr.io").unwrap(),
+            Auth::IdentityToken(token) if token == "refresh"
+        ));
+        assert!(matches!(
+            config.auth_for("ghcr.io").unwrap(),
+            Auth::Anonymous
+        ));
+        assert_eq!(
+            config.cred_helpers["123456789012.dkr.ecr.us-east-1.amazonaws.com"],
+            "ecr-login"
+        );
+    }
+
+    #[test]
+    fn parse_credential_helper_output() {
+        let auth = parse_helper_output(
+            br#"{"ServerURL":"ghcr.io","Username":"octocat","Secret":"ghp_x"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            auth,
+            Auth::Basic(user, secret) if user == "octocat" && secret == "ghp_x"
+        ));
+        assert!(matches!(
+            parse_helper_output(br#"{"Username":"<token>","Secret":"refresh"}"#).unwrap(),
+            Auth::IdentityToken(token) if token == "refresh"
+        ));
+        assert!(parse_helper_output(b"not json").is_err());
+    }
+}
@@ -16,15 +16,33 @@
 pub mod config;
 pub use config::*;
 
+pub mod docker_config;
+
+pub mod progress;
+pub use progress::{ProgressListener, TransferBudget, TransferDirection, TransferProgress};
+
 #[cfg(feature = "cosign")]
 pub(crate) mod oci_client;
 #[cfg(feature = "cosign")]
 pub(crate) use oci_client::*;
 
+#[cfg(feature = "cosign")]
+pub mod recording;
+
+#[cfg(feature = "cosign")]
+pub mod referrers;
+#[cfg(feature = "cosign")]
+pub use referrers::Referrer;
+
+#[cfg(feature = "cosign")]
+pub(crate) mod storage_caching_client;
+#[cfg(feature = "cosign")]
+pub(crate) use storage_caching_client::StorageCachingClient;
+
 #[cfg(feature = "cosign")]
 pub mod oci_reference;
 #[cfg(feature = "cosign")]
-pub use oci_reference::OciReference;
+pub use oci_reference::{ImageReference, OciReference};
 
 #[cfg(all(feature = "cosign", feature = "cached-client"))]
 pub(crate) mod oci_caching_client;
@@ -65,4 +83,36 @@ pub(crate) trait ClientCapabilities {
         auth: &oci_distribution::secrets::RegistryAuth,
         manifest: Option<oci_distribution::manifest::OciImageManifest>,
     ) -> Result<oci_distribution::client::PushResponse>;
+
+    /// Pull the layer of `image` described by `descriptor`. By default the
+    /// whole image is pulled, and the layer is looked up among its layers.
+    async fn pull_layer(
+        &mut self,
+        image: &oci_distribution::Reference,
+        auth: &oci_distribution::secrets::RegistryAuth,
+        descriptor: &oci_distribution::manifest::OciDescriptor,
+    ) -> Result<oci_distribution::client::ImageLayer> {
+        self.pull(image, auth, vec![descriptor.media_type.as_str()])
+            .await?
+            .layers
+            .into_iter()
+            .find(|layer| layer.sha256_digest() == descriptor.digest)
+            .ok_or_else(|| crate::errors::SigstoreError::RegistryPullError {
+                image: image.whole(),
+                error: format!("layer {} not found", descriptor.digest),
+            })
+    }
+
+    /// Returns the artifacts referring to the manifest `image`, which must
+    /// have a digest. By default they are read through the referrers tag
+    /// schema only, see the [`referrers`] module.
+    #[cfg(feature = "cosign")]
+    async fn fetch_referrers(
+        &mut self,
+        image: &oci_distribution::Reference,
+        auth: &oci_distribution::secrets::RegistryAuth,
+        artifact_type: Option<&str>,
+    ) -> Result<Vec<Referrer>> {
+        referrers::referrers_from_tag_schema(self, image, auth, artifact_type).await
+    }
 }
@@ -13,6 +13,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::oci_client::pull_blob;
+use super::referrers::{referrers_from_tag_schema, Referrer, ReferrersApi};
 use super::ClientCapabilities;
 use crate::errors::{Result, SigstoreError};
 
@@ -31,6 +33,7 @@ use tracing::{debug, error};
 /// `mock_client` module.
 pub(crate) struct OciCachingClient {
     pub registry_client: oci_distribution::Client,
+    pub referrers_api: ReferrersApi,
 }
 
 #[cached(
@@ -314,4 +317,28 @@ impl ClientCapabilities for OciCachingClient {
                 error: e.to_string(),
             })
     }
+
+    /// The referrers are not cached: new signatures must be seen as soon as
+    /// they are attached
+    async fn pull_layer(
+        &mut self,
+        image: &oci_distribution::Reference,
+        auth: &oci_distribution::secrets::RegistryAuth,
+        descriptor: &oci_distribution::manifest::OciDescriptor,
+    ) -> Result<oci_distribution::client::ImageLayer> {
+        // blobs are not cached, the manifest pointing to them is
+        pull_blob(&mut self.registry_client, image, auth, descriptor).await
+    }
+
+    async fn fetch_referrers(
+        &mut self,
+        image: &oci_distribution::Reference,
+        auth: &oci_distribution::secrets::RegistryAuth,
+        artifact_type: Option<&str>,
+    ) -> Result<Vec<Referrer>> {
+        match self.referrers_api.fetch(image, auth, artifact_type).await? {
+            Some(referrers) => Ok(referrers),
+            None => referrers_from_tag_schema(self, image, auth, artifact_type).await,
+        }
+    }
 }
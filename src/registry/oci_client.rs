@@ -13,6 +13,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::referrers::{referrers_from_tag_schema, Referrer, ReferrersApi};
 use super::ClientCapabilities;
 use crate::errors::{Result, SigstoreError};
 
@@ -25,6 +26,7 @@ use async_trait::async_trait;
 /// `mock_client` module.
 pub(crate) struct OciClient {
     pub registry_client: oci_distribution::Client,
+    pub referrers_api: ReferrersApi,
 }
 
 #[async_trait(?Send)]
@@ -88,4 +90,56 @@ impl ClientCapabilities for OciClient {
                 error: e.to_string(),
             })
     }
+
+    async fn pull_layer(
+        &mut self,
+        image: &oci_distribution::Reference,
+        auth: &oci_distribution::secrets::RegistryAuth,
+        descriptor: &oci_distribution::manifest::OciDescriptor,
+    ) -> Result<oci_distribution::client::ImageLayer> {
+        pull_blob(&mut self.registry_client, image, auth, descriptor).await
+    }
+
+    async fn fetch_referrers(
+        &mut self,
+        image: &oci_distribution::Reference,
+        auth: &oci_distribution::secrets::RegistryAuth,
+        artifact_type: Option<&str>,
+    ) -> Result<Vec<Referrer>> {
+        match self.referrers_api.fetch(image, auth, artifact_type).await? {
+            Some(referrers) => Ok(referrers),
+            None => referrers_from_tag_schema(self, image, auth, artifact_type).await,
+        }
+    }
+}
+
+/// Pull the blob described by `descriptor`, authenticating against the
+/// registry first
+pub(crate) async fn pull_blob(
+    client: &mut oci_distribution::Client,
+    image: &oci_distribution::Reference,
+    auth: &oci_distribution::secrets::RegistryAuth,
+    descriptor: &oci_distribution::manifest::OciDescriptor,
+) -> Result<oci_distribution::client::ImageLayer> {
+    let pull_error =
+        |e: oci_distribution::errors::OciDistributionError| SigstoreError::RegistryPullError {
+            image: image.whole(),
+            error: e.to_string(),
+        };
+
+    client
+        .auth(image, auth, oci_distribution::RegistryOperation::Pull)
+        .await
+        .map_err(pull_error)?;
+    let mut data = Vec::new();
+    client
+        .pull_blob(image, &descriptor.digest, &mut data)
+        .await
+        .map_err(pull_error)?;
+
+    Ok(oci_distribution::client::ImageLayer::new(
+        data,
+        descriptor.media_type.clone(),
+        descriptor.annotations.clone(),
+    ))
 }
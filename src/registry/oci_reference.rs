@@ -18,11 +18,25 @@ use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
 /// `OciReference` provides a general type to represent any way of referencing images within an OCI registry.
+///
+/// All the forms accepted by `docker pull` are parsed:
+///
+/// * `busybox`, resolved to `docker.io/library/busybox:latest`
+/// * `registry.example.com:5000/team/app:v1`
+/// * `registry.example.com/team/app@sha256:<digest>`
+/// * `registry.example.com/team/app:v1@sha256:<digest>`, where the digest
+///   takes precedence over the tag, like it does for `docker pull`
+///
+/// Signatures are attached to a manifest digest, not to a tag: see
+/// [`Client::resolve_digest`](crate::cosign::Client::resolve_digest).
 #[derive(Debug, Clone, PartialEq)]
 pub struct OciReference {
     pub(crate) oci_reference: oci_distribution::Reference,
 }
 
+/// The reference of a container image, see [`OciReference`]
+pub type ImageReference = OciReference;
+
 impl FromStr for OciReference {
     type Err = SigstoreError;
 
@@ -82,6 +96,16 @@ impl OciReference {
     pub fn whole(&self) -> String {
         self.oci_reference.whole()
     }
+
+    /// Returns the reference of the manifest `digest` inside of the same
+    /// repository, e.g. the manifest a tag has been resolved to
+    pub fn pinned(&self, digest: &str) -> Self {
+        Self::with_digest(
+            self.registry().to_string(),
+            self.repository().to_string(),
+            digest.to_string(),
+        )
+    }
 }
 
 impl Display for OciReference {
@@ -89,3 +113,47 @@ impl Display for OciReference {
         self.oci_reference.fmt(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str = "sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b";
+
+    #[test]
+    fn parse_image_references() {
+        let image: ImageReference = "busybox".parse().unwrap();
+        assert_eq!(image.registry(), "docker.io");
+        assert_eq!(image.repository(), "library/busybox");
+        assert_eq!(image.tag(), Some("latest"));
+        assert_eq!(image.digest(), None);
+
+        let image: ImageReference = "registry.example.com:5000/team/app:v1".parse().unwrap();
+        assert_eq!(image.registry(), "registry.example.com:5000");
+        assert_eq!(image.repository(), "team/app");
+        assert_eq!(image.tag(), Some("v1"));
+
+        let image: ImageReference = format!("registry.example.com/team/app:v1@{DIGEST}")
+            .parse()
+            .unwrap();
+        assert_eq!(image.tag(), Some("v1"));
+        assert_eq!(image.digest(), Some(DIGEST));
+
+        assert!(matches!(
+            "registry.example.com/team/app@sha256:nothex".parse::<ImageReference>(),
+            Err(SigstoreError::OciReferenceNotValidError { .. })
+        ));
+    }
+
+    #[test]
+    fn pin_image_reference() {
+        let image: ImageReference = "registry.example.com/team/app:v1".parse().unwrap();
+        let pinned = image.pinned(DIGEST);
+        assert_eq!(pinned.tag(), None);
+        assert_eq!(pinned.digest(), Some(DIGEST));
+        assert_eq!(
+            pinned.whole(),
+            format!("registry.example.com/team/app@{DIGEST}")
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Progress reporting for data exchanged with OCI registries.
+//!
+//! Pulling and pushing signatures, attestations and SBOMs can involve
+//! large layers. Consumers can register a [`ProgressListener`] to be
+//! notified about the amount of data being transferred. This can be used
+//! to render progress bars, or to enforce transfer budgets: when a listener
+//! returns an error, the transfer is aborted and the error is propagated
+//! to the caller.
+//!
+//! Before any layer is transferred, listeners receive an announcement
+//! containing the total amount of bytes that are about to be exchanged,
+//! as described by the manifest. Then a notification is sent each time a
+//! layer has been transferred.
+//!
+//! Layers are pulled one by one, hence a listener error aborts the pull
+//! before the remaining layers are downloaded. Images are pushed at once
+//! instead: their layers are reported once the push has completed, and
+//! the errors of the listener no longer fail it.
+
+use std::sync::Arc;
+
+use crate::errors::{Result, SigstoreError};
+
+/// The direction of a transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferDirection {
+    /// Data is downloaded from the registry
+    Pull,
+    /// Data is uploaded to the registry
+    Push,
+}
+
+/// A snapshot of an ongoing transfer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferProgress {
+    /// The reference of the OCI object being transferred
+    pub reference: String,
+    /// Whether data is being downloaded or uploaded
+    pub direction: TransferDirection,
+    /// The digest of the layer that has just been transferred. This is
+    /// `None` for the announcement sent before any layer is transferred.
+    pub layer_digest: Option<String>,
+    /// The size of the layer that has just been transferred
+    pub layer_bytes: u64,
+    /// The amount of bytes transferred so far
+    pub transferred_bytes: u64,
+    /// The total amount of bytes of the transfer
+    pub total_bytes: u64,
+}
+
+impl TransferProgress {
+    /// Returns `true` when all the data has been transferred
+    pub fn is_complete(&self) -> bool {
+        self.transferred_bytes >= self.total_bytes
+    }
+}
+
+/// A trait that can be implemented to receive progress notifications
+pub trait ProgressListener: Send + Sync {
+    /// Invoked each time the transfer makes progress.
+    ///
+    /// Returning an error aborts the transfer.
+    fn on_progress(&self, progress: &TransferProgress) -> Result<()>;
+}
+
+/// A [`ProgressListener`] that refuses transfers larger than a given
+/// amount of bytes.
+///
+/// The check is performed using the total size announced before the
+/// transfer starts, hence no data is exchanged when the budget is exceeded.
+#[derive(Debug, Clone)]
+pub struct TransferBudget {
+    /// The maximum amount of bytes a single transfer can move
+    pub max_bytes: u64,
+}
+
+impl ProgressListener for TransferBudget {
+    fn on_progress(&self, progress: &TransferProgress) -> Result<()> {
+        if progress.total_bytes > self.max_bytes || progress.transferred_bytes > self.max_bytes {
+            return Err(SigstoreError::RegistryTransferBudgetExceeded {
+                image: progress.reference.clone(),
+                limit: self.max_bytes,
+                size: progress.total_bytes.max(progress.transferred_bytes),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Internal helper that keeps track of a transfer and forwards the
+/// notifications to the listener, if any.
+pub(crate) struct ProgressTracker<'a> {
+    listener: Option<&'a Arc<dyn ProgressListener>>,
+    reference: String,
+    direction: TransferDirection,
+    transferred_bytes: u64,
+    total_bytes: u64,
+}
+
+impl<'a> ProgressTracker<'a> {
+    pub(crate) fn new(
+        listener: Option<&'a Arc<dyn ProgressListener>>,
+        reference: String,
+        direction: TransferDirection,
+        total_bytes: u64,
+    ) -> Self {
+        ProgressTracker {
+            listener,
+            reference,
+            direction,
+            transferred_bytes: 0,
+            total_bytes,
+        }
+    }
+
+    /// Announce the transfer, before any data is exchanged
+    pub(crate) fn start(&self) -> Result<()> {
+        self.notify(None, 0)
+    }
+
+    /// Record the transfer of a layer
+    pub(crate) fn layer_transferred(&mut self, digest: &str, bytes: u64) -> Result<()> {
+        self.transferred_bytes += bytes;
+        self.notify(Some(digest.to_string()), bytes)
+    }
+
+    fn notify(&self, layer_digest: Option<String>, layer_bytes: u64) -> Result<()> {
+        match self.listener {
+            Some(listener) => listener.on_progress(&TransferProgress {
+                reference: self.reference.clone(),
+                direction: self.direction,
+                layer_digest,
+                layer_bytes,
+                transferred_bytes: self.transferred_bytes,
+                total_bytes: self.total_bytes,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingListener {
+        events: Mutex<Vec<TransferProgress>>,
+    }
+
+    impl ProgressListener for RecordingListener {
+        fn on_progress(&self, progress: &TransferProgress) -> Result<()> {
+            self.events.lock().unwrap().push(progress.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tracker_reports_each_layer() {
+        let recorder = Arc::new(RecordingListener::default());
+        let listener: Arc<dyn ProgressListener> = recorder.clone();
+        let mut tracker = ProgressTracker::new(
+            Some(&listener),
+            "registry.local/busybox:sig".to_string(),
+            TransferDirection::Pull,
+            30,
+        );
+
+        tracker.start().expect("start should not fail");
+        tracker
+            .layer_transferred("sha256:a", 10)
+            .expect("first layer should not fail");
+        tracker
+            .layer_transferred("sha256:b", 20)
+            .expect("second layer should not fail");
+
+        let events = recorder.events.lock().unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].layer_digest, None);
+        assert_eq!(events[0].transferred_bytes, 0);
+        assert_eq!(events[1].layer_digest, Some("sha256:a".to_string()));
+        assert_eq!(events[1].transferred_bytes, 10);
+        assert!(!events[1].is_complete());
+        assert_eq!(events[2].layer_bytes, 20);
+        assert_eq!(events[2].transferred_bytes, 30);
+        assert!(events[2].is_complete());
+    }
+
+    #[test]
+    fn tracker_without_listener() {
+        let mut tracker = ProgressTracker::new(
+            None,
+            "registry.local/busybox:sig".to_string(),
+            TransferDirection::Push,
+            10,
+        );
+        assert!(tracker.start().is_ok());
+        assert!(tracker.layer_transferred("sha256:a", 10).is_ok());
+    }
+
+    #[test]
+    fn transfer_budget_aborts_large_transfers() {
+        let listener: Arc<dyn ProgressListener> = Arc::new(TransferBudget { max_bytes: 100 });
+
+        let tracker = ProgressTracker::new(
+            Some(&listener),
+            "registry.local/busybox:sig".to_string(),
+            TransferDirection::Pull,
+            50,
+        );
+        assert!(tracker.start().is_ok());
+
+        let tracker = ProgressTracker::new(
+            Some(&listener),
+            "registry.local/busybox:sig".to_string(),
+            TransferDirection::Pull,
+            101,
+        );
+        let err = tracker.start().expect_err("budget should be exceeded");
+        assert!(matches!(
+            err,
+            SigstoreError::RegistryTransferBudgetExceeded {
+                limit: 100,
+                size: 101,
+                ..
+            }
+        ));
+    }
+}
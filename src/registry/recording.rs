@@ -0,0 +1,660 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Record and replay the remote data used during verification.
+//!
+//! A [`Recorder`] given to
+//! [`ClientBuilder::with_recorder`](crate::cosign::ClientBuilder::with_recorder)
+//! captures the trust material (Rekor key and Fulcio certificates, usually
+//! obtained from TUF) and all the responses returned by the OCI registry.
+//! The resulting [`Recording`] can be saved, then given to
+//! [`ClientBuilder::with_replay`](crate::cosign::ClientBuilder::with_replay)
+//! to build a client that performs exactly the same verification without
+//! reaching the network. This is useful to reproduce verification decisions
+//! during audits and bug reports.
+//!
+//! Rekor is not contacted during the verification of cosign signatures: the
+//! transparency log data is taken from the bundles stored inside of the
+//! registry, which are part of the recording.
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::ClientBuilder;
+//! use sigstore::registry::recording::{Recorder, Recording};
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! let recorder = Recorder::new();
+//! let client = ClientBuilder::default()
+//!     .with_recorder(recorder.clone())
+//!     .build()?;
+//!
+//! // ... verify some images with `client`
+//!
+//! std::fs::write("recording.json", recorder.recording().to_json()?)?;
+//!
+//! // later on, possibly on another machine
+//! let recording = Recording::from_json(&std::fs::read_to_string("recording.json")?)?;
+//! let client = ClientBuilder::default().with_replay(recording).build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use oci_distribution::manifest::{OciImageManifest, OciManifest};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+
+use super::{Certificate, CertificateEncoding, ClientCapabilities, Referrer};
+use crate::errors::{Result, SigstoreError};
+
+/// The version of the recording format produced by this crate
+pub const RECORDING_VERSION: &str = "v1";
+
+/// The remote data used during one or more verifications
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Recording {
+    version: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    rekor_pub_key: Option<String>,
+    #[serde(default)]
+    fulcio_certs: Vec<RecordedCertificate>,
+    #[serde(default)]
+    interactions: Vec<Interaction>,
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Recording {
+            version: RECORDING_VERSION.to_string(),
+            rekor_pub_key: None,
+            fulcio_certs: Vec::new(),
+            interactions: Vec::new(),
+        }
+    }
+}
+
+impl Recording {
+    /// Serialize the recording to JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self)
+            .map_err(|e| SigstoreError::RecordingError(format!("cannot serialize: {e}")))
+    }
+
+    /// Load a recording produced by [`Recording::to_json`]
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let recording: Recording = serde_json::from_str(raw)
+            .map_err(|e| SigstoreError::RecordingError(format!("cannot parse: {e}")))?;
+        if recording.version != RECORDING_VERSION {
+            return Err(SigstoreError::RecordingError(format!(
+                "unsupported version {}",
+                recording.version
+            )));
+        }
+        Ok(recording)
+    }
+
+    /// The PEM encoded Rekor public key used during the recording
+    pub fn rekor_pub_key(&self) -> Option<&str> {
+        self.rekor_pub_key.as_deref()
+    }
+
+    /// The Fulcio certificates used during the recording
+    pub fn fulcio_certs(&self) -> Result<Vec<Certificate>> {
+        self.fulcio_certs
+            .iter()
+            .map(Certificate::try_from)
+            .collect()
+    }
+
+    /// The number of registry interactions that have been recorded
+    pub fn len(&self) -> usize {
+        self.interactions.len()
+    }
+
+    /// Returns `true` when no registry interaction has been recorded
+    pub fn is_empty(&self) -> bool {
+        self.interactions.is_empty()
+    }
+}
+
+/// Captures the remote data used by a client. Clones of a `Recorder` share
+/// the same [`Recording`].
+#[derive(Clone, Default)]
+pub struct Recorder {
+    recording: Arc<Mutex<Recording>>,
+}
+
+impl Recorder {
+    /// Create a new `Recorder`, with an empty recording
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A copy of what has been recorded so far
+    pub fn recording(&self) -> Recording {
+        self.lock().clone()
+    }
+
+    pub(crate) fn set_trust_material(
+        &self,
+        rekor_pub_key: Option<&str>,
+        fulcio_certs: &[Certificate],
+    ) {
+        let mut recording = self.lock();
+        recording.rekor_pub_key = rekor_pub_key.map(|k| k.to_string());
+        recording.fulcio_certs = fulcio_certs.iter().map(RecordedCertificate::from).collect();
+    }
+
+    fn record(&self, interaction: Interaction) {
+        self.lock().interactions.push(interaction);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Recording> {
+        // a poisoned lock still holds a consistent recording: interactions
+        // are pushed atomically
+        self.recording
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct RecordedCertificate {
+    der: bool,
+    data: String,
+}
+
+impl From<&Certificate> for RecordedCertificate {
+    fn from(c: &Certificate) -> Self {
+        RecordedCertificate {
+            der: c.encoding == CertificateEncoding::Der,
+            data: BASE64_STD_ENGINE.encode(&c.data),
+        }
+    }
+}
+
+impl TryFrom<&RecordedCertificate> for Certificate {
+    type Error = SigstoreError;
+
+    fn try_from(c: &RecordedCertificate) -> Result<Self> {
+        Ok(Certificate {
+            encoding: if c.der {
+                CertificateEncoding::Der
+            } else {
+                CertificateEncoding::Pem
+            },
+            data: BASE64_STD_ENGINE.decode(&c.data)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+enum Outcome<T> {
+    Response(T),
+    Error(String),
+}
+
+impl<T: Clone> Outcome<T> {
+    fn new(result: &Result<T>) -> Self {
+        match result {
+            Ok(r) => Outcome::Response(r.clone()),
+            Err(e) => Outcome::Error(registry_error_message(e)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "request", rename_all = "camelCase")]
+enum Interaction {
+    FetchManifestDigest {
+        image: String,
+        outcome: Outcome<String>,
+    },
+    Pull {
+        image: String,
+        #[serde(rename = "acceptedMediaTypes")]
+        accepted_media_types: Vec<String>,
+        outcome: Outcome<RecordedImageData>,
+    },
+    PullManifest {
+        image: String,
+        outcome: Outcome<(OciManifest, String)>,
+    },
+    FetchReferrers {
+        image: String,
+        #[serde(
+            rename = "artifactType",
+            skip_serializing_if = "Option::is_none",
+            default
+        )]
+        artifact_type: Option<String>,
+        outcome: Outcome<Vec<Referrer>>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct RecordedBlob {
+    data: String,
+    media_type: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    annotations: Option<HashMap<String, String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RecordedImageData {
+    layers: Vec<RecordedBlob>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    digest: Option<String>,
+    config: RecordedBlob,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    manifest: Option<OciImageManifest>,
+}
+
+impl From<&oci_distribution::client::ImageData> for RecordedImageData {
+    fn from(data: &oci_distribution::client::ImageData) -> Self {
+        RecordedImageData {
+            layers: data
+                .layers
+                .iter()
+                .map(|l| RecordedBlob {
+                    data: BASE64_STD_ENGINE.encode(&l.data),
+                    media_type: l.media_type.clone(),
+                    annotations: l.annotations.clone(),
+                })
+                .collect(),
+            digest: data.digest.clone(),
+            config: RecordedBlob {
+                data: BASE64_STD_ENGINE.encode(&data.config.data),
+                media_type: data.config.media_type.clone(),
+                annotations: data.config.annotations.clone(),
+            },
+            manifest: data.manifest.clone(),
+        }
+    }
+}
+
+impl TryFrom<&RecordedImageData> for oci_distribution::client::ImageData {
+    type Error = SigstoreError;
+
+    fn try_from(data: &RecordedImageData) -> Result<Self> {
+        let layers = data
+            .layers
+            .iter()
+            .map(|l| {
+                Ok(oci_distribution::client::ImageLayer::new(
+                    BASE64_STD_ENGINE.decode(&l.data)?,
+                    l.media_type.clone(),
+                    l.annotations.clone(),
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(oci_distribution::client::ImageData {
+            layers,
+            digest: data.digest.clone(),
+            config: oci_distribution::client::Config::new(
+                BASE64_STD_ENGINE.decode(&data.config.data)?,
+                data.config.media_type.clone(),
+                data.config.annotations.clone(),
+            ),
+            manifest: data.manifest.clone(),
+        })
+    }
+}
+
+/// Extract the message of a registry error, so that replaying it produces
+/// exactly the same error
+fn registry_error_message(error: &SigstoreError) -> String {
+    match error {
+        SigstoreError::RegistryFetchManifestError { error, .. }
+        | SigstoreError::RegistryPullManifestError { error, .. }
+        | SigstoreError::RegistryPullError { error, .. }
+        | SigstoreError::RegistryReferrersError { error, .. } => error.clone(),
+        e => e.to_string(),
+    }
+}
+
+/// Wraps a registry client and records all its responses
+pub(crate) struct RecordingClient {
+    pub(crate) inner: Box<dyn ClientCapabilities>,
+    pub(crate) recorder: Recorder,
+}
+
+#[async_trait(?Send)]
+impl ClientCapabilities for RecordingClient {
+    async fn fetch_manifest_digest(
+        &mut self,
+        image: &oci_distribution::Reference,
+        auth: &oci_distribution::secrets::RegistryAuth,
+    ) -> Result<String> {
+        let result = self.inner.fetch_manifest_digest(image, auth).await;
+        self.recorder.record(Interaction::FetchManifestDigest {
+            image: image.whole(),
+            outcome: Outcome::new(&result),
+        });
+        result
+    }
+
+    async fn pull(
+        &mut self,
+        image: &oci_distribution::Reference,
+        auth: &oci_distribution::secrets::RegistryAuth,
+        accepted_media_types: Vec<&str>,
+    ) -> Result<oci_distribution::client::ImageData> {
+        let media_types: Vec<String> = accepted_media_types.iter().map(|m| m.to_string()).collect();
+        let result = self.inner.pull(image, auth, accepted_media_types).await;
+        self.recorder.record(Interaction::Pull {
+            image: image.whole(),
+            accepted_media_types: media_types,
+            outcome: match &result {
+                Ok(data) => Outcome::Response(RecordedImageData::from(data)),
+                Err(e) => Outcome::Error(registry_error_message(e)),
+            },
+        });
+        result
+    }
+
+    async fn pull_manifest(
+        &mut self,
+        image: &oci_distribution::Reference,
+        auth: &oci_distribution::secrets::RegistryAuth,
+    ) -> Result<(OciManifest, String)> {
+        let result = self.inner.pull_manifest(image, auth).await;
+        self.recorder.record(Interaction::PullManifest {
+            image: image.whole(),
+            outcome: Outcome::new(&result),
+        });
+        result
+    }
+
+    async fn push(
+        &mut self,
+        image_ref: &oci_distribution::Reference,
+        layers: &[oci_distribution::client::ImageLayer],
+        config: oci_distribution::client::Config,
+        auth: &oci_distribution::secrets::RegistryAuth,
+        manifest: Option<OciImageManifest>,
+    ) -> Result<oci_distribution::client::PushResponse> {
+        self.inner
+            .push(image_ref, layers, config, auth, manifest)
+            .await
+    }
+
+    async fn fetch_referrers(
+        &mut self,
+        image: &oci_distribution::Reference,
+        auth: &oci_distribution::secrets::RegistryAuth,
+        artifact_type: Option<&str>,
+    ) -> Result<Vec<Referrer>> {
+        let result = self.inner.fetch_referrers(image, auth, artifact_type).await;
+        self.recorder.record(Interaction::FetchReferrers {
+            image: image.whole(),
+            artifact_type: artifact_type.map(str::to_string),
+            outcome: Outcome::new(&result),
+        });
+        result
+    }
+}
+
+/// A registry client that serves the responses of a [`Recording`]
+pub(crate) struct ReplayClient {
+    pub(crate) recording: Recording,
+}
+
+impl ReplayClient {
+    fn not_recorded(request: &str, image: &oci_distribution::Reference) -> SigstoreError {
+        SigstoreError::RecordingError(format!(
+            "{request} of {} not found in the recording",
+            image.whole()
+        ))
+    }
+}
+
+#[async_trait(?Send)]
+impl ClientCapabilities for ReplayClient {
+    async fn fetch_manifest_digest(
+        &mut self,
+        image: &oci_distribution::Reference,
+        _auth: &oci_distribution::secrets::RegistryAuth,
+    ) -> Result<String> {
+        let whole = image.whole();
+        self.recording
+            .interactions
+            .iter()
+            .find_map(|i| match i {
+                Interaction::FetchManifestDigest { image, outcome } if *image == whole => {
+                    Some(match outcome {
+                        Outcome::Response(digest) => Ok(digest.clone()),
+                        Outcome::Error(error) => Err(SigstoreError::RegistryFetchManifestError {
+                            image: whole.clone(),
+                            error: error.clone(),
+                        }),
+                    })
+                }
+                _ => None,
+            })
+            .unwrap_or_else(|| Err(Self::not_recorded("manifest digest", image)))
+    }
+
+    async fn pull(
+        &mut self,
+        image: &oci_distribution::Reference,
+        _auth: &oci_distribution::secrets::RegistryAuth,
+        accepted_media_types: Vec<&str>,
+    ) -> Result<oci_distribution::client::ImageData> {
+        let whole = image.whole();
+        self.recording
+            .interactions
+            .iter()
+            .find_map(|i| match i {
+                Interaction::Pull {
+                    image,
+                    accepted_media_types: recorded_media_types,
+                    outcome,
+                } if *image == whole && *recorded_media_types == accepted_media_types => {
+                    Some(match outcome {
+                        Outcome::Response(data) => {
+                            oci_distribution::client::ImageData::try_from(data)
+                        }
+                        Outcome::Error(error) => Err(SigstoreError::RegistryPullError {
+                            image: whole.clone(),
+                            error: error.clone(),
+                        }),
+                    })
+                }
+                _ => None,
+            })
+            .unwrap_or_else(|| Err(Self::not_recorded("pull", image)))
+    }
+
+    async fn pull_manifest(
+        &mut self,
+        image: &oci_distribution::Reference,
+        _auth: &oci_distribution::secrets::RegistryAuth,
+    ) -> Result<(OciManifest, String)> {
+        let whole = image.whole();
+        self.recording
+            .interactions
+            .iter()
+            .find_map(|i| match i {
+                Interaction::PullManifest { image, outcome } if *image == whole => {
+                    Some(match outcome {
+                        Outcome::Response(response) => Ok(response.clone()),
+                        Outcome::Error(error) => Err(SigstoreError::RegistryPullManifestError {
+                            image: whole.clone(),
+                            error: error.clone(),
+                        }),
+                    })
+                }
+                _ => None,
+            })
+            .unwrap_or_else(|| Err(Self::not_recorded("manifest", image)))
+    }
+
+    async fn push(
+        &mut self,
+        image_ref: &oci_distribution::Reference,
+        _layers: &[oci_distribution::client::ImageLayer],
+        _config: oci_distribution::client::Config,
+        _auth: &oci_distribution::secrets::RegistryAuth,
+        _manifest: Option<OciImageManifest>,
+    ) -> Result<oci_distribution::client::PushResponse> {
+        Err(SigstoreError::RecordingError(format!(
+            "cannot push {} while replaying a recording",
+            image_ref.whole()
+        )))
+    }
+
+    async fn fetch_referrers(
+        &mut self,
+        image: &oci_distribution::Reference,
+        _auth: &oci_distribution::secrets::RegistryAuth,
+        artifact_type: Option<&str>,
+    ) -> Result<Vec<Referrer>> {
+        let whole = image.whole();
+        self.recording
+            .interactions
+            .iter()
+            .find_map(|i| match i {
+                Interaction::FetchReferrers {
+                    image,
+                    artifact_type: recorded_artifact_type,
+                    outcome,
+                } if *image == whole && recorded_artifact_type.as_deref() == artifact_type => {
+                    Some(match outcome {
+                        Outcome::Response(referrers) => Ok(referrers.clone()),
+                        Outcome::Error(error) => Err(SigstoreError::RegistryReferrersError {
+                            image: whole.clone(),
+                            error: error.clone(),
+                        }),
+                    })
+                }
+                _ => None,
+            })
+            .unwrap_or_else(|| Err(Self::not_recorded("referrers", image)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_client::test::MockOciClient;
+    use oci_distribution::client::{Config, ImageData, ImageLayer};
+    use oci_distribution::manifest::OciDescriptor;
+    use oci_distribution::secrets::RegistryAuth;
+
+    #[tokio::test]
+    async fn replay_recorded_interactions() {
+        let image: oci_distribution::Reference = "registry.local/busybox:latest".parse().unwrap();
+        let missing: oci_distribution::Reference = "registry.local/alpine:latest".parse().unwrap();
+        let layer = ImageLayer::new(
+            b"layer".to_vec(),
+            "application/vnd.dev.cosign.simplesigning.v1+json".to_string(),
+            Some([("key".to_string(), "value".to_string())].into()),
+        );
+        let manifest = OciImageManifest {
+            layers: vec![OciDescriptor {
+                digest: layer.sha256_digest(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let recorder = Recorder::new();
+        let mut client = RecordingClient {
+            inner: Box::new(MockOciClient {
+                fetch_manifest_digest_response: Some(Ok("sha256:digest".to_string())),
+                pull_response: Some(Ok(ImageData {
+                    layers: vec![layer.clone()],
+                    digest: None,
+                    config: Config::oci_v1(b"{}".to_vec(), None),
+                    manifest: None,
+                })),
+                pull_manifest_response: Some(Ok((
+                    OciManifest::Image(manifest),
+                    "sha256:manifest".to_string(),
+                ))),
+                push_response: None,
+            }),
+            recorder: recorder.clone(),
+        };
+
+        let auth = RegistryAuth::Anonymous;
+        client.fetch_manifest_digest(&image, &auth).await.unwrap();
+        client
+            .pull(&image, &auth, vec![&layer.media_type])
+            .await
+            .unwrap();
+        client.pull_manifest(&image, &auth).await.unwrap();
+
+        let recording = Recording::from_json(&recorder.recording().to_json().unwrap()).unwrap();
+        assert_eq!(recording.len(), 3);
+
+        let mut replay = ReplayClient { recording };
+        assert_eq!(
+            replay.fetch_manifest_digest(&image, &auth).await.unwrap(),
+            "sha256:digest"
+        );
+        let data = replay
+            .pull(&image, &auth, vec![&layer.media_type])
+            .await
+            .unwrap();
+        assert_eq!(data.layers[0].data, layer.data);
+        assert_eq!(data.layers[0].annotations, layer.annotations);
+        let (_, digest) = replay.pull_manifest(&image, &auth).await.unwrap();
+        assert_eq!(digest, "sha256:manifest");
+
+        assert!(matches!(
+            replay.fetch_manifest_digest(&missing, &auth).await,
+            Err(SigstoreError::RecordingError(_))
+        ));
+        assert!(replay.pull(&image, &auth, vec!["other"]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn replay_recorded_errors() {
+        let image: oci_distribution::Reference = "registry.local/busybox:latest".parse().unwrap();
+        let recorder = Recorder::new();
+        let mut client = RecordingClient {
+            inner: Box::new(MockOciClient {
+                fetch_manifest_digest_response: Some(Err(anyhow::anyhow!("unauthorized"))),
+                pull_response: None,
+                pull_manifest_response: None,
+                push_response: None,
+            }),
+            recorder: recorder.clone(),
+        };
+        let auth = RegistryAuth::Anonymous;
+        let recorded_error = client
+            .fetch_manifest_digest(&image, &auth)
+            .await
+            .unwrap_err();
+
+        let mut replay = ReplayClient {
+            recording: recorder.recording(),
+        };
+        let replayed_error = replay
+            .fetch_manifest_digest(&image, &auth)
+            .await
+            .unwrap_err();
+        assert_eq!(replayed_error.to_string(), recorded_error.to_string());
+    }
+}
@@ -0,0 +1,422 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Discovery of the artifacts attached to a manifest through the
+//! [referrers API](https://github.com/opencontainers/distribution-spec/blob/v1.1.0/spec.md#listing-referrers)
+//! of the OCI distribution spec 1.1.
+//!
+//! Registries that do not support the API answer `404`: the referrers are
+//! then read from the index tagged with the digest of the manifest, as
+//! described by the
+//! [referrers tag schema](https://github.com/opencontainers/distribution-spec/blob/v1.1.0/spec.md#referrers-tag-schema).
+
+use std::collections::HashMap;
+
+use oci_distribution::manifest::OciManifest;
+use oci_distribution::secrets::RegistryAuth;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use super::{CertificateEncoding, ClientCapabilities, ClientConfig, ClientProtocol};
+use crate::errors::{Result, SigstoreError};
+
+/// The media type of the indexes listing the referrers
+pub const OCI_IMAGE_INDEX_MEDIA_TYPE: &str = "application/vnd.oci.image.index.v1+json";
+
+/// The descriptor of an artifact referring to a manifest, like a signature
+/// or an attestation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Referrer {
+    /// The media type of the manifest of the artifact
+    pub media_type: String,
+    /// The digest of the manifest of the artifact
+    pub digest: String,
+    pub size: i64,
+    /// The type of the artifact. Unknown for the referrers found through
+    /// the referrers tag schema.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub artifact_type: Option<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty", default)]
+    pub annotations: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct ReferrersIndex {
+    #[serde(default)]
+    manifests: Vec<Referrer>,
+}
+
+/// Keep the referrers of the given artifact type. The referrers of unknown
+/// type are kept: the caller has to look at their content.
+fn filter_artifact_type(referrers: Vec<Referrer>, artifact_type: Option<&str>) -> Vec<Referrer> {
+    match artifact_type {
+        Some(expected) => referrers
+            .into_iter()
+            .filter(|r| r.artifact_type.as_deref().is_none_or(|t| t == expected))
+            .collect(),
+        None => referrers,
+    }
+}
+
+/// The tag of the index listing the referrers of `digest`, according to
+/// the referrers tag schema
+pub fn referrers_tag(digest: &str) -> String {
+    digest.replace(':', "-")
+}
+
+/// Read the referrers of `image` from the index of the referrers tag
+/// schema. A missing index means the manifest has no referrers.
+pub(crate) async fn referrers_from_tag_schema<C>(
+    client: &mut C,
+    image: &oci_distribution::Reference,
+    auth: &RegistryAuth,
+    artifact_type: Option<&str>,
+) -> Result<Vec<Referrer>>
+where
+    C: ClientCapabilities + ?Sized,
+{
+    let digest = image
+        .digest()
+        .ok_or_else(|| referrers_error(image, "missing digest"))?;
+    let index = oci_distribution::Reference::with_tag(
+        image.registry().to_string(),
+        image.repository().to_string(),
+        referrers_tag(digest),
+    );
+
+    let referrers = match client.pull_manifest(&index, auth).await {
+        Ok((OciManifest::ImageIndex(index), _)) => index
+            .manifests
+            .into_iter()
+            .map(|entry| Referrer {
+                media_type: entry.media_type,
+                digest: entry.digest,
+                size: entry.size,
+                artifact_type: None,
+                annotations: entry.annotations.unwrap_or_default(),
+            })
+            .collect(),
+        Ok((OciManifest::Image(_), _)) => Vec::new(),
+        Err(e) => {
+            debug!(error = ?e, index = %index, "no referrers index");
+            Vec::new()
+        }
+    };
+    Ok(filter_artifact_type(referrers, artifact_type))
+}
+
+/// Client of the referrers API.
+///
+/// The client of `oci-distribution` does not support the API, nor exposes
+/// the tokens it got from the registries: this client authenticates on its
+/// own, following the token authentication of the distribution spec.
+pub(crate) struct ReferrersApi {
+    http_client: reqwest::Client,
+    protocol: ClientProtocol,
+}
+
+impl ReferrersApi {
+    /// Create a client reaching the registries like the registry client
+    /// configured by `config`
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self> {
+        let mut builder = config.tune_connections(
+            reqwest::Client::builder()
+                .danger_accept_invalid_certs(config.accept_invalid_certificates),
+        );
+        for certificate in &config.extra_root_certificates {
+            let certificate = match certificate.encoding {
+                CertificateEncoding::Der => reqwest::Certificate::from_der(&certificate.data),
+                CertificateEncoding::Pem => reqwest::Certificate::from_pem(&certificate.data),
+            }
+            .map_err(|e| SigstoreError::HttpError(e.to_string()))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        Ok(ReferrersApi {
+            http_client: builder
+                .build()
+                .map_err(|e| SigstoreError::HttpError(e.to_string()))?,
+            protocol: config.protocol.clone(),
+        })
+    }
+
+    /// Returns the referrers of `image`, which must have a digest, or
+    /// `None` when the registry does not support the referrers API
+    pub(crate) async fn fetch(
+        &self,
+        image: &oci_distribution::Reference,
+        auth: &RegistryAuth,
+        artifact_type: Option<&str>,
+    ) -> Result<Option<Vec<Referrer>>> {
+        let digest = image
+            .digest()
+            .ok_or_else(|| referrers_error(image, "missing digest"))?;
+        let registry = image.resolve_registry();
+        let url = format!(
+            "{}://{registry}/v2/{}/referrers/{digest}",
+            scheme(&self.protocol, registry),
+            image.repository(),
+        );
+        let mut query = Vec::new();
+        if let Some(artifact_type) = artifact_type {
+            query.push(("artifactType", artifact_type));
+        }
+
+        let mut response = self
+            .get(&url, &query, None)
+            .await
+            .map_err(|e| referrers_error(image, e))?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            let authorization = match response
+                .headers()
+                .get(reqwest::header::WWW_AUTHENTICATE)
+                .and_then(|h| h.to_str().ok())
+            {
+                Some(challenge) => self.authorize(image, auth, challenge).await?,
+                None => None,
+            };
+            if let Some(authorization) = authorization {
+                response = self
+                    .get(&url, &query, Some(&authorization))
+                    .await
+                    .map_err(|e| referrers_error(image, e))?;
+            }
+        }
+
+        match response.status() {
+            reqwest::StatusCode::NOT_FOUND => {
+                debug!(image = %image, "referrers API not supported");
+                Ok(None)
+            }
+            status if status.is_success() => {
+                let index: ReferrersIndex = response
+                    .json()
+                    .await
+                    .map_err(|e| referrers_error(image, e))?;
+                Ok(Some(filter_artifact_type(index.manifests, artifact_type)))
+            }
+            status => Err(referrers_error(
+                image,
+                format!("unexpected status {status}"),
+            )),
+        }
+    }
+
+    async fn get(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+        authorization: Option<&str>,
+    ) -> reqwest::Result<reqwest::Response> {
+        let mut request = self
+            .http_client
+            .get(url)
+            .query(query)
+            .header(reqwest::header::ACCEPT, OCI_IMAGE_INDEX_MEDIA_TYPE);
+        if let Some(authorization) = authorization {
+            request = request.header(reqwest::header::AUTHORIZATION, authorization);
+        }
+        request.send().await
+    }
+
+    /// Answer the `WWW-Authenticate` challenge of the registry, returning
+    /// the value of the `Authorization` header to send
+    async fn authorize(
+        &self,
+        image: &oci_distribution::Reference,
+        auth: &RegistryAuth,
+        challenge: &str,
+    ) -> Result<Option<String>> {
+        let (scheme, params) = parse_challenge(challenge);
+        if scheme.eq_ignore_ascii_case("basic") {
+            return Ok(match auth {
+                RegistryAuth::Basic(username, password) => {
+                    Some(basic_authorization(username, password))
+                }
+                RegistryAuth::Anonymous => None,
+            });
+        }
+        if !scheme.eq_ignore_ascii_case("bearer") {
+            return Err(referrers_error(
+                image,
+                format!("unsupported authentication scheme {scheme}"),
+            ));
+        }
+
+        let realm = params
+            .get("realm")
+            .ok_or_else(|| referrers_error(image, "authentication challenge without realm"))?;
+        let scope = format!("repository:{}:pull", image.repository());
+        let mut query = vec![("scope", scope.as_str())];
+        if let Some(service) = params.get("service") {
+            query.push(("service", service.as_str()));
+        }
+
+        let mut request = self.http_client.get(realm).query(&query);
+        if let RegistryAuth::Basic(username, password) = auth {
+            request = request.basic_auth(username, Some(password));
+        }
+        let response = request
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| referrers_error(image, format!("authentication failed: {e}")))?;
+
+        // `access_token` is the OAuth 2 compatible name of `token`
+        #[derive(Deserialize)]
+        struct Token {
+            token: Option<String>,
+            access_token: Option<String>,
+        }
+        let token: Token = response
+            .json()
+            .await
+            .map_err(|e| referrers_error(image, format!("invalid token: {e}")))?;
+        let token = token
+            .token
+            .or(token.access_token)
+            .ok_or_else(|| referrers_error(image, "no token in the authentication response"))?;
+        Ok(Some(format!("Bearer {token}")))
+    }
+}
+
+/// The scheme used to reach `registry`, like the client of `oci-distribution`
+fn scheme(protocol: &ClientProtocol, registry: &str) -> &'static str {
+    match protocol {
+        ClientProtocol::Http => "http",
+        ClientProtocol::Https => "https",
+        ClientProtocol::HttpsExcept(exceptions) if exceptions.iter().any(|e| e == registry) => {
+            "http"
+        }
+        ClientProtocol::HttpsExcept(_) => "https",
+    }
+}
+
+fn basic_authorization(username: &str, password: &str) -> String {
+    use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+
+    format!(
+        "Basic {}",
+        BASE64_STD_ENGINE.encode(format!("{username}:{password}"))
+    )
+}
+
+/// Parse a `WWW-Authenticate` challenge like
+/// `Bearer realm="https://auth.example.com/token",service="registry.example.com"`
+fn parse_challenge(challenge: &str) -> (&str, HashMap<String, String>) {
+    let challenge = challenge.trim();
+    let (scheme, mut rest) = challenge.split_once(' ').unwrap_or((challenge, ""));
+
+    let mut params = HashMap::new();
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        let (key, value) = match rest.split_once('=') {
+            Some(kv) => kv,
+            None => break,
+        };
+        let (value, remaining) = match value.strip_prefix('"') {
+            Some(quoted) => match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            },
+            None => value.split_once(',').unwrap_or((value, "")),
+        };
+        params.insert(key.trim().to_ascii_lowercase(), value.to_string());
+        rest = remaining;
+    }
+    (scheme, params)
+}
+
+fn referrers_error(image: &oci_distribution::Reference, error: impl ToString) -> SigstoreError {
+    SigstoreError::RegistryReferrersError {
+        image: image.whole(),
+        error: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_authentication_challenges() {
+        let (scheme, params) = parse_challenge(
+            r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/busybox:pull,push""#,
+        );
+        assert_eq!(scheme, "Bearer");
+        assert_eq!(params["realm"], "https://auth.docker.io/token");
+        assert_eq!(params["service"], "registry.docker.io");
+        assert_eq!(params["scope"], "repository:library/busybox:pull,push");
+
+        let (scheme, params) = parse_challenge(r#"Basic realm=registry"#);
+        assert_eq!(scheme, "Basic");
+        assert_eq!(params["realm"], "registry");
+    }
+
+    #[test]
+    fn filter_referrers() {
+        let index: ReferrersIndex = serde_json::from_value(serde_json::json!({
+            "schemaVersion": 2,
+            "mediaType": OCI_IMAGE_INDEX_MEDIA_TYPE,
+            "manifests": [
+                {
+                    "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                    "digest": "sha256:a1",
+                    "size": 1024,
+                    "artifactType": "application/vnd.dev.cosign.artifact.sig.v1+json",
+                },
+                {
+                    "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                    "digest": "sha256:b2",
+                    "size": 2048,
+                    "artifactType": "application/spdx+json",
+                    "annotations": {"org.opencontainers.image.created": "2023-01-01T00:00:00Z"},
+                },
+                {
+                    "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                    "digest": "sha256:c3",
+                    "size": 512,
+                },
+            ],
+        }))
+        .unwrap();
+
+        let digests = |referrers: Vec<Referrer>| -> Vec<String> {
+            referrers.into_iter().map(|r| r.digest).collect()
+        };
+        assert_eq!(
+            digests(filter_artifact_type(
+                index.manifests.clone(),
+                Some("application/vnd.dev.cosign.artifact.sig.v1+json")
+            )),
+            vec!["sha256:a1", "sha256:c3"]
+        );
+        assert_eq!(
+            digests(filter_artifact_type(index.manifests, None)).len(),
+            3
+        );
+    }
+
+    #[test]
+    fn referrers_tag_of_digest() {
+        assert_eq!(
+            referrers_tag(
+                "sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b"
+            ),
+            "sha256-f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b"
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use olpc_cjson::CanonicalFormatter;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use super::recording::RecordedImageData;
+use super::{ClientCapabilities, Referrer};
+use crate::cache::CacheStorage;
+use crate::errors::Result;
+
+/// Wraps a registry client and caches its successful responses inside of a
+/// [`CacheStorage`].
+///
+/// The cache is an optimization: the responses are fetched from the
+/// registry when the storage can't be used.
+pub(crate) struct StorageCachingClient {
+    pub(crate) inner: Box<dyn ClientCapabilities>,
+    pub(crate) storage: Arc<dyn CacheStorage>,
+    pub(crate) ttl: Duration,
+}
+
+impl StorageCachingClient {
+    /// The key of a request, the credentials are hashed together with the
+    /// rest of the request
+    fn key(
+        request: &str,
+        image: &oci_distribution::Reference,
+        auth: &oci_distribution::secrets::RegistryAuth,
+        accepted_media_types: &[&str],
+    ) -> String {
+        let settings = json!({
+            "request": request,
+            "image": image.whole(),
+            "auth": super::config::Auth::from(auth),
+            "acceptedMediaTypes": accepted_media_types,
+        });
+        let mut buf = Vec::new();
+        let mut ser = serde_json::Serializer::with_formatter(&mut buf, CanonicalFormatter::new());
+        // serializing a JSON value can't fail
+        let _ = settings.serialize(&mut ser);
+        format!("registry-{request}-{}", hex::encode(Sha256::digest(&buf)))
+    }
+
+    fn lookup<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        match self.storage.get(key) {
+            Ok(Some(data)) => match serde_json::from_slice(&data) {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    warn!(key, error=?e, "Ignoring invalid cached registry response");
+                    None
+                }
+            },
+            Ok(None) => None,
+            Err(e) => {
+                warn!(key, error=?e, "Cannot read from the cache storage");
+                None
+            }
+        }
+    }
+
+    fn store<T: Serialize>(&self, key: &str, value: &T) {
+        let stored = serde_json::to_vec(value)
+            .map_err(Into::into)
+            .and_then(|data| self.storage.set(key, &data, Some(self.ttl)));
+        if let Err(e) = stored {
+            warn!(key, error=?e, "Cannot write to the cache storage");
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl ClientCapabilities for StorageCachingClient {
+    async fn fetch_manifest_digest(
+        &mut self,
+        image: &oci_distribution::Reference,
+        auth: &oci_distribution::secrets::RegistryAuth,
+    ) -> Result<String> {
+        let key = Self::key("digest", image, auth, &[]);
+        if let Some(digest) = self.lookup(&key) {
+            debug!(?image, "Got image digest from cache storage");
+            return Ok(digest);
+        }
+
+        let digest = self.inner.fetch_manifest_digest(image, auth).await?;
+        self.store(&key, &digest);
+        Ok(digest)
+    }
+
+    async fn pull(
+        &mut self,
+        image: &oci_distribution::Reference,
+        auth: &oci_distribution::secrets::RegistryAuth,
+        accepted_media_types: Vec<&str>,
+    ) -> Result<oci_distribution::client::ImageData> {
+        let key = Self::key("pull", image, auth, &accepted_media_types);
+        if let Some(data) = self.lookup::<RecordedImageData>(&key) {
+            if let Ok(data) = oci_distribution::client::ImageData::try_from(&data) {
+                debug!(?image, "Got image data from cache storage");
+                return Ok(data);
+            }
+        }
+
+        let data = self.inner.pull(image, auth, accepted_media_types).await?;
+        self.store(&key, &RecordedImageData::from(&data));
+        Ok(data)
+    }
+
+    async fn pull_manifest(
+        &mut self,
+        image: &oci_distribution::Reference,
+        auth: &oci_distribution::secrets::RegistryAuth,
+    ) -> Result<(oci_distribution::manifest::OciManifest, String)> {
+        let key = Self::key("manifest", image, auth, &[]);
+        if let Some(manifest) = self.lookup(&key) {
+            debug!(?image, "Got image manifest from cache storage");
+            return Ok(manifest);
+        }
+
+        let manifest = self.inner.pull_manifest(image, auth).await?;
+        self.store(&key, &manifest);
+        Ok(manifest)
+    }
+
+    async fn push(
+        &mut self,
+        image_ref: &oci_distribution::Reference,
+        layers: &[oci_distribution::client::ImageLayer],
+        config: oci_distribution::client::Config,
+        auth: &oci_distribution::secrets::RegistryAuth,
+        manifest: Option<oci_distribution::manifest::OciImageManifest>,
+    ) -> Result<oci_distribution::client::PushResponse> {
+        self.inner
+            .push(image_ref, layers, config, auth, manifest)
+            .await
+    }
+
+    /// The referrers are not cached: new signatures must be seen as soon as
+    /// they are attached
+    async fn fetch_referrers(
+        &mut self,
+        image: &oci_distribution::Reference,
+        auth: &oci_distribution::secrets::RegistryAuth,
+        artifact_type: Option<&str>,
+    ) -> Result<Vec<Referrer>> {
+        self.inner.fetch_referrers(image, auth, artifact_type).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::InMemoryCache;
+    use crate::mock_client::test::MockOciClient;
+    use crate::registry::Auth;
+
+    fn caching_client(
+        mock_client: MockOciClient,
+        storage: Arc<dyn CacheStorage>,
+    ) -> StorageCachingClient {
+        StorageCachingClient {
+            inner: Box::new(mock_client),
+            storage,
+            ttl: Duration::from_secs(60),
+        }
+    }
+
+    #[tokio::test]
+    async fn share_cached_responses() {
+        let storage: Arc<dyn CacheStorage> = Arc::new(InMemoryCache::default());
+        let image: oci_distribution::Reference = "docker.io/busybox:latest".parse().unwrap();
+        let auth = (&Auth::Anonymous).into();
+        let digest = "sha256:f3cfc9d0dbf931d3db4685ec659b7ac68e2a578219da4aae65427886e649b06b";
+
+        let mut client = caching_client(
+            MockOciClient {
+                fetch_manifest_digest_response: Some(Ok(digest.to_string())),
+                pull_response: None,
+                pull_manifest_response: None,
+                push_response: None,
+            },
+            storage.clone(),
+        );
+        assert_eq!(
+            client.fetch_manifest_digest(&image, &auth).await.unwrap(),
+            digest
+        );
+
+        // another replica, sharing the same storage, doesn't reach the
+        // registry
+        let mut replica = caching_client(
+            MockOciClient {
+                fetch_manifest_digest_response: None,
+                pull_response: None,
+                pull_manifest_response: None,
+                push_response: None,
+            },
+            storage.clone(),
+        );
+        assert_eq!(
+            replica.fetch_manifest_digest(&image, &auth).await.unwrap(),
+            digest
+        );
+
+        // the credentials are part of the key
+        let other_auth = (&Auth::Basic("user".to_string(), "password".to_string())).into();
+        assert!(replica
+            .fetch_manifest_digest(&image, &other_auth)
+            .await
+            .is_err());
+    }
+}
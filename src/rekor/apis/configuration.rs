@@ -8,8 +8,12 @@
  * Generated by: https://openapi-generator.tech
  */
 
+use std::sync::Arc;
+
 use reqwest;
 
+use crate::http::HttpClient;
+
 const VERSION: Option<&str> = option_env!("CARGO_PKG_VERSION");
 
 #[derive(Debug, Clone)]
@@ -21,6 +25,10 @@ pub struct Configuration {
     pub oauth_access_token: Option<String>,
     pub bearer_access_token: Option<String>,
     pub api_key: Option<ApiKey>,
+    /// Sends the requests built with `client`, instead of `client` itself.
+    /// Used to inject a custom HTTP client, or to share one with the other
+    /// modules.
+    pub http_client: Option<Arc<dyn HttpClient>>,
     // TODO: take an oauth2 token source, similar to the go one
 }
 
@@ -48,6 +56,7 @@ impl Default for Configuration {
             oauth_access_token: None,
             bearer_access_token: None,
             api_key: None,
+            http_client: None,
         }
     }
 }
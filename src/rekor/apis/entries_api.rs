@@ -59,14 +59,23 @@ pub struct LogEntries {
     entries: Vec<LogEntry>,
 }
 
-// TEMPORARY: Formats the returned response such that it can be read into a struct
-// TODO: Remove once upstream issue around dynamic top level key is resolved:
-// https://github.com/sigstore/rekor/issues/808
+/// Formats the returned response such that it can be read into a struct: Rekor
+/// returns the entries inside of an object keyed by their UUID, the UUID is
+/// moved inside of the entry.
+/// See <https://github.com/sigstore/rekor/issues/808>
 pub fn parse_response(local_var_content: String) -> String {
-    let uuid: &str = &local_var_content[1..82];
-    let rest: &str = &local_var_content[85..local_var_content.len() - 2];
-
-    "{\"uuid\":".to_string() + uuid + "\"," + rest
+    let entries: serde_json::Map<String, serde_json::Value> =
+        match serde_json::from_str(&local_var_content) {
+            Ok(entries) => entries,
+            Err(_) => return local_var_content,
+        };
+    match entries.into_iter().next() {
+        Some((uuid, serde_json::Value::Object(mut entry))) => {
+            entry.insert("uuid".to_string(), serde_json::Value::String(uuid));
+            serde_json::Value::Object(entry).to_string()
+        }
+        _ => local_var_content,
+    }
 }
 
 /// Creates an entry in the transparency log for a detached signature, public key, and content. Items can be included in the request or fetched by the server when URLs are specified.
@@ -88,10 +97,8 @@ pub async fn create_log_entry(
     local_var_req_builder = local_var_req_builder.json(&proposed_entry);
 
     let local_var_req = local_var_req_builder.build()?;
-    let local_var_resp = local_var_client.execute(local_var_req).await?;
-
-    let local_var_status = local_var_resp.status();
-    let local_var_content = local_var_resp.text().await?;
+    let (local_var_status, local_var_content) =
+        super::execute(configuration, local_var_req).await?;
 
     if !local_var_status.is_client_error() && !local_var_status.is_server_error() {
         LogEntry::from_str(&(parse_response(local_var_content))).map_err(Error::from)
@@ -125,10 +132,8 @@ pub async fn get_log_entry_by_index(
     }
 
     let local_var_req = local_var_req_builder.build()?;
-    let local_var_resp = local_var_client.execute(local_var_req).await?;
-
-    let local_var_status = local_var_resp.status();
-    let local_var_content = local_var_resp.text().await?;
+    let (local_var_status, local_var_content) =
+        super::execute(configuration, local_var_req).await?;
 
     if !local_var_status.is_client_error() && !local_var_status.is_server_error() {
         LogEntry::from_str(&(parse_response(local_var_content))).map_err(Error::from)
@@ -165,10 +170,8 @@ pub async fn get_log_entry_by_uuid(
     }
 
     let local_var_req = local_var_req_builder.build()?;
-    let local_var_resp = local_var_client.execute(local_var_req).await?;
-
-    let local_var_status = local_var_resp.status();
-    let local_var_content = local_var_resp.text().await?;
+    let (local_var_status, local_var_content) =
+        super::execute(configuration, local_var_req).await?;
 
     if !local_var_status.is_client_error() && !local_var_status.is_server_error() {
         LogEntry::from_str(&(parse_response(local_var_content))).map_err(Error::from)
@@ -202,10 +205,8 @@ pub async fn search_log_query(
     local_var_req_builder = local_var_req_builder.json(&entry);
 
     let local_var_req = local_var_req_builder.build()?;
-    let local_var_resp = local_var_client.execute(local_var_req).await?;
-
-    let local_var_status = local_var_resp.status();
-    let local_var_content = local_var_resp.text().await?;
+    let (local_var_status, local_var_content) =
+        super::execute(configuration, local_var_req).await?;
 
     if !local_var_status.is_client_error() && !local_var_status.is_server_error() {
         Ok(local_var_content)
@@ -220,3 +221,78 @@ pub async fn search_log_query(
         Err(Error::ResponseError(local_var_error))
     }
 }
+
+/// Searches the transparency log for the entries matching `query`, the
+/// entries are decoded like the ones returned by [`get_log_entry_by_uuid`]
+pub async fn search_log_query_entries(
+    configuration: &configuration::Configuration,
+    query: crate::rekor::models::SearchLogQuery,
+) -> Result<Vec<LogEntry>, Error<SearchLogQueryError>> {
+    let content = search_log_query(configuration, query).await?;
+    parse_log_entries(&content).map_err(Error::from)
+}
+
+/// Parses the entries returned by a search, each one inside of an object
+/// keyed by its UUID
+fn parse_log_entries(content: &str) -> Result<Vec<LogEntry>, serde_json::Error> {
+    serde_json::from_str::<Vec<serde_json::Value>>(content)?
+        .into_iter()
+        .map(|entry| LogEntry::from_str(&parse_response(entry.to_string())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rekor::models::log_entry::Body;
+    use crate::rekor::models::ProposedEntry;
+    use serde_json::json;
+
+    #[test]
+    fn parse_created_entry() {
+        let proposed_entry = ProposedEntry::hashedrekord(
+            &[0xab; 32],
+            b"signature",
+            "-----BEGIN PUBLIC KEY-----\n-----END PUBLIC KEY-----\n",
+        );
+        let body = base64::Engine::encode(
+            &base64::engine::general_purpose::STANDARD,
+            serde_json::to_vec(&proposed_entry).unwrap(),
+        );
+        // a UUID made of the ID of the tree and of the hash of the entry
+        let uuid = format!("{}{}", "24296fb24b8ad77a", "cd".repeat(32));
+        let response = json!({
+            uuid.clone(): {
+                "body": body,
+                "integratedTime": 1_700_000_000,
+                "logID": "c0d23d6ad406973f9559f3ba2d1ca01f84147d8ffc5b8445c224f98b9591801d",
+                "logIndex": 42,
+                "verification": {
+                    "signedEntryTimestamp": "MEUCIQ==",
+                },
+            }
+        });
+
+        let entry = LogEntry::from_str(&parse_response(
+            serde_json::to_string_pretty(&response).unwrap(),
+        ))
+        .unwrap();
+        assert_eq!(entry.uuid, uuid);
+        assert_eq!(entry.log_index, 42);
+        assert_eq!(entry.verification.signed_entry_timestamp, "MEUCIQ==");
+        match entry.body {
+            Body::hashedrekord(body) => {
+                assert_eq!(body.api_version, "0.0.1");
+                assert_eq!(body.spec["data"]["hash"]["value"], "ab".repeat(32));
+                assert_eq!(body.spec["signature"]["content"], "c2lnbmF0dXJl");
+            }
+            body => panic!("unexpected body {:?}", body),
+        }
+
+        let search_response = json!([response, response]).to_string();
+        let entries = parse_log_entries(&search_response).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].uuid, uuid);
+        assert!(parse_log_entries("{}").is_err());
+    }
+}
@@ -45,10 +45,8 @@ pub async fn search_index(
     local_var_req_builder = local_var_req_builder.json(&query);
 
     let local_var_req = local_var_req_builder.build()?;
-    let local_var_resp = local_var_client.execute(local_var_req).await?;
-
-    let local_var_status = local_var_resp.status();
-    let local_var_content = local_var_resp.text().await?;
+    let (local_var_status, local_var_content) =
+        super::execute(configuration, local_var_req).await?;
     if !local_var_status.is_client_error() && !local_var_status.is_server_error() {
         serde_json::from_str(&local_var_content).map_err(Error::from)
     } else {
@@ -27,6 +27,12 @@ pub enum Error<T> {
         source: std::io::Error,
     },
 
+    #[error("error in the HTTP client: {source}")]
+    Http {
+        #[from]
+        source: crate::errors::SigstoreError,
+    },
+
     #[error("error in response: status code {:?}", error_status(.0))]
     ResponseError(ResponseContent<T>),
 }
@@ -36,6 +42,28 @@ fn error_status<T>(response: &ResponseContent<T>) -> reqwest::StatusCode {
     response.status
 }
 
+/// Send `request` with the HTTP client of the configuration, and return the
+/// status and the body of the response
+async fn execute<T>(
+    configuration: &configuration::Configuration,
+    request: reqwest::Request,
+) -> Result<(reqwest::StatusCode, String), Error<T>> {
+    match &configuration.http_client {
+        Some(http_client) => {
+            let request = crate::http::from_reqwest_request(request)?;
+            let response = http_client.execute(request).await?;
+            Ok((
+                response.status(),
+                String::from_utf8_lossy(response.body()).into_owned(),
+            ))
+        }
+        None => {
+            let response = configuration.client.execute(request).await?;
+            Ok((response.status(), response.text().await?))
+        }
+    }
+}
+
 pub fn urlencode<T: AsRef<str>>(s: T) -> String {
     ::url::form_urlencoded::byte_serialize(s.as_ref().as_bytes()).collect()
 }
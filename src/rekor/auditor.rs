@@ -0,0 +1,391 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Auditing of the Rekor transparency log.
+//!
+//! The [`LogAuditor`] remembers the last checkpoint it verified, and each time
+//! [`LogAuditor::audit`] is invoked it fetches the latest checkpoint from
+//! Rekor and ensures the log only grew in an append-only fashion since then.
+//! A log that shrinks (rollback) or that presents two different trees of the
+//! same size (split view) is reported via an error, and the persisted
+//! checkpoint is left untouched.
+//!
+//! The last verified checkpoint is persisted via a [`CheckpointStore`]. The
+//! crate provides a [`FileCheckpointStore`] and an [`InMemoryCheckpointStore`];
+//! other storage backends can be plugged in by implementing the trait.
+//!
+//! ```rust,no_run
+//! use sigstore::crypto::CosignVerificationKey;
+//! use sigstore::rekor::apis::configuration::Configuration;
+//! use sigstore::rekor::auditor::{FileCheckpointStore, LogAuditor};
+//!
+//! #[tokio::main]
+//! pub async fn main() {
+//!   let rekor_pub_pem = std::fs::read("~/rekor.pub").expect("Cannot read rekor key");
+//!   let rekor_pub_key = CosignVerificationKey::try_from_pem(&rekor_pub_pem)
+//!     .expect("Cannot parse rekor key");
+//!
+//!   let mut auditor = LogAuditor::new(
+//!     Configuration::default(),
+//!     rekor_pub_key,
+//!     FileCheckpointStore::new("/var/lib/rekor-auditor/checkpoint"),
+//!   );
+//!
+//!   match auditor.audit().await {
+//!     Ok(checkpoint) => println!("log is consistent up to size {}", checkpoint.size),
+//!     Err(e) => eprintln!("log audit failed: {e}"),
+//!   }
+//! }
+//! ```
+
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use super::apis::{configuration::Configuration, tlog_api};
+use super::checkpoint::Checkpoint;
+use super::merkle::{decode_hashes, verify_consistency};
+use super::models::ConsistencyProof;
+use crate::crypto::CosignVerificationKey;
+use crate::errors::{Result, SigstoreError};
+use crate::metrics::MetricsRecorder;
+
+/// Storage used by the [`LogAuditor`] to persist the last verified checkpoint
+pub trait CheckpointStore {
+    /// Load the signed note of the last verified checkpoint, if any
+    fn load(&self) -> Result<Option<String>>;
+
+    /// Persist the signed note of the last verified checkpoint
+    fn save(&mut self, signed_note: &str) -> Result<()>;
+}
+
+/// A [`CheckpointStore`] that keeps the checkpoint inside of a file
+#[derive(Debug, Clone)]
+pub struct FileCheckpointStore {
+    path: PathBuf,
+}
+
+impl FileCheckpointStore {
+    /// Create a new store that persists the checkpoint at the given path
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        FileCheckpointStore {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+    fn load(&self) -> Result<Option<String>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(signed_note) => Ok(Some(signed_note)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&mut self, signed_note: &str) -> Result<()> {
+        // write to a temporary file first, to not corrupt the stored
+        // checkpoint when the process is interrupted
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, signed_note)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// A [`CheckpointStore`] that keeps the checkpoint in memory. Useful for
+/// tests and short lived processes.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryCheckpointStore {
+    signed_note: Option<String>,
+}
+
+impl CheckpointStore for InMemoryCheckpointStore {
+    fn load(&self) -> Result<Option<String>> {
+        Ok(self.signed_note.clone())
+    }
+
+    fn save(&mut self, signed_note: &str) -> Result<()> {
+        self.signed_note = Some(signed_note.to_string());
+        Ok(())
+    }
+}
+
+/// Ensures the Rekor log behaves in an append-only fashion
+pub struct LogAuditor<S: CheckpointStore> {
+    configuration: Configuration,
+    rekor_pub_key: CosignVerificationKey,
+    store: S,
+    metrics: Option<Arc<dyn MetricsRecorder>>,
+}
+
+impl<S: CheckpointStore> LogAuditor<S> {
+    /// Create a new `LogAuditor`.
+    ///
+    /// * `configuration`: how to reach the Rekor instance
+    /// * `rekor_pub_key`: the key used by Rekor to sign its checkpoints
+    /// * `store`: where the last verified checkpoint is persisted
+    pub fn new(
+        configuration: Configuration,
+        rekor_pub_key: CosignVerificationKey,
+        store: S,
+    ) -> Self {
+        LogAuditor {
+            configuration,
+            rekor_pub_key,
+            store,
+            metrics: None,
+        }
+    }
+
+    /// Report each lookup performed against Rekor to the given
+    /// [`MetricsRecorder`]
+    pub fn with_metrics_recorder(mut self, recorder: Arc<dyn MetricsRecorder>) -> Self {
+        self.metrics = Some(recorder);
+        self
+    }
+
+    /// The last verified checkpoint
+    pub fn last_checkpoint(&self) -> Result<Option<Checkpoint>> {
+        self.store
+            .load()?
+            .map(|note| Checkpoint::parse_and_verify(&note, &self.rekor_pub_key))
+            .transpose()
+    }
+
+    /// Fetch the latest checkpoint from Rekor and ensure it is consistent with
+    /// the last verified one. On success the new checkpoint is persisted and
+    /// returned.
+    pub async fn audit(&mut self) -> Result<Checkpoint> {
+        let start = Instant::now();
+        let log_info = tlog_api::get_log_info(&self.configuration).await;
+        self.record_lookup(start, log_info.is_ok());
+        let log_info = log_info.map_err(|e| SigstoreError::RekorClientError(e.to_string()))?;
+        let current =
+            Checkpoint::parse_and_verify(&log_info.signed_tree_head, &self.rekor_pub_key)?;
+
+        let previous = self.last_checkpoint()?;
+        let proof = match &previous {
+            Some(previous) if previous.size > 0 && previous.size < current.size => {
+                let last_size = i32::try_from(current.size).map_err(|_| {
+                    SigstoreError::RekorClientError(format!(
+                        "tree size {} is too big",
+                        current.size
+                    ))
+                })?;
+                let start = Instant::now();
+                let proof = tlog_api::get_log_proof(
+                    &self.configuration,
+                    last_size,
+                    Some(&previous.size.to_string()),
+                    log_info.tree_id.as_deref(),
+                )
+                .await;
+                self.record_lookup(start, proof.is_ok());
+                Some(proof.map_err(|e| SigstoreError::RekorClientError(e.to_string()))?)
+            }
+            _ => None,
+        };
+
+        verify_checkpoint_transition(previous.as_ref(), &current, proof.as_ref())?;
+
+        self.store.save(&current.signed_note)?;
+        info!(size = current.size, "rekor log audited");
+        Ok(current)
+    }
+
+    /// Audit the log every `interval`, invoking `on_failure` each time the
+    /// audit fails.
+    ///
+    /// The first audit happens immediately. This method never returns.
+    pub async fn run<F>(&mut self, interval: Duration, mut on_failure: F)
+    where
+        F: FnMut(&SigstoreError),
+    {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.audit().await {
+                warn!(error = %e, "rekor log audit failed");
+                on_failure(&e);
+            }
+        }
+    }
+
+    fn record_lookup(&self, start: Instant, succeeded: bool) {
+        if let Some(metrics) = &self.metrics {
+            metrics.tlog_lookup(start.elapsed(), succeeded);
+        }
+    }
+}
+
+/// Ensure the `current` checkpoint is an append-only extension of the
+/// `previous` one.
+///
+/// `proof` is the consistency proof between the two checkpoints. It's
+/// required only when the log grew.
+///
+/// Checkpoints of different logs, like the ones issued before and after
+/// the rotation of a log shard, cannot be compared: the transition is
+/// rejected with [`SigstoreError::RekorLogChangedError`].
+pub fn verify_checkpoint_transition(
+    previous: Option<&Checkpoint>,
+    current: &Checkpoint,
+    proof: Option<&ConsistencyProof>,
+) -> Result<()> {
+    let previous = match previous {
+        Some(previous) => previous,
+        None => return Ok(()),
+    };
+
+    if current.origin != previous.origin {
+        return Err(SigstoreError::RekorLogChangedError {
+            persisted_origin: previous.origin.clone(),
+            current_origin: current.origin.clone(),
+        });
+    }
+    if current.size < previous.size {
+        return Err(SigstoreError::RekorLogRollbackError {
+            persisted_size: previous.size,
+            current_size: current.size,
+        });
+    }
+    if current.size == previous.size {
+        if current.hash != previous.hash {
+            return Err(SigstoreError::RekorSplitViewError {
+                tree_size: current.size,
+            });
+        }
+        return Ok(());
+    }
+    if previous.size == 0 {
+        return Ok(());
+    }
+
+    let proof = proof.ok_or_else(|| {
+        SigstoreError::RekorConsistencyProofError("consistency proof not provided".to_string())
+    })?;
+    let hashes = decode_hashes(&proof.hashes)?;
+    verify_consistency(
+        previous.size,
+        current.size,
+        &previous.hash,
+        &current.hash,
+        &hashes,
+    )
+    .map_err(|e| {
+        warn!(error = %e, "consistency proof verification failed");
+        SigstoreError::RekorSplitViewError {
+            tree_size: previous.size,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::SigningScheme;
+    use crate::rekor::checkpoint::tests::sign_checkpoint;
+    use crate::rekor::merkle::tests::{consistency_proof, root};
+
+    fn leaves(n: usize) -> Vec<Vec<u8>> {
+        (0..n).map(|i| format!("entry {i}").into_bytes()).collect()
+    }
+
+    #[test]
+    fn checkpoint_transitions() {
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .expect("cannot create signer");
+        let all = leaves(10);
+        let checkpoint = |size: usize| {
+            Checkpoint::parse(&sign_checkpoint(&signer, size as u64, &root(&all[..size])))
+                .expect("cannot parse checkpoint")
+        };
+        let old = checkpoint(6);
+        let new = checkpoint(10);
+
+        // first audit
+        assert!(verify_checkpoint_transition(None, &new, None).is_ok());
+        // no changes
+        assert!(verify_checkpoint_transition(Some(&new), &new, None).is_ok());
+
+        // the log grew
+        let proof = ConsistencyProof::new(
+            hex::encode(&new.hash),
+            consistency_proof(6, &all).iter().map(hex::encode).collect(),
+        );
+        assert!(verify_checkpoint_transition(Some(&old), &new, Some(&proof)).is_ok());
+        assert!(matches!(
+            verify_checkpoint_transition(Some(&old), &new, None),
+            Err(SigstoreError::RekorConsistencyProofError(_))
+        ));
+
+        // rollback
+        assert!(matches!(
+            verify_checkpoint_transition(Some(&new), &old, None),
+            Err(SigstoreError::RekorLogRollbackError {
+                persisted_size: 10,
+                current_size: 6
+            })
+        ));
+
+        // split view: same size, different root
+        let mut forked_leaves = all.clone();
+        forked_leaves[3] = b"forked".to_vec();
+        let forked = Checkpoint::parse(&sign_checkpoint(&signer, 6, &root(&forked_leaves[..6])))
+            .expect("cannot parse checkpoint");
+        assert!(matches!(
+            verify_checkpoint_transition(Some(&forked), &old, None),
+            Err(SigstoreError::RekorSplitViewError { tree_size: 6 })
+        ));
+
+        // split view: the new tree doesn't extend the old one
+        assert!(matches!(
+            verify_checkpoint_transition(Some(&forked), &new, Some(&proof)),
+            Err(SigstoreError::RekorSplitViewError { tree_size: 6 })
+        ));
+
+        // the log shard rotated: the new log is smaller, but that's no rollback
+        let rotated = Checkpoint {
+            origin: "rekor.local - 5678".to_string(),
+            ..old.clone()
+        };
+        assert!(matches!(
+            verify_checkpoint_transition(Some(&new), &rotated, None),
+            Err(SigstoreError::RekorLogChangedError { .. })
+        ));
+        assert!(matches!(
+            verify_checkpoint_transition(Some(&old), &rotated, None),
+            Err(SigstoreError::RekorLogChangedError { .. })
+        ));
+    }
+
+    #[test]
+    fn file_checkpoint_store() {
+        let dir = tempfile::tempdir().expect("cannot create temp dir");
+        let mut store = FileCheckpointStore::new(dir.path().join("checkpoint"));
+
+        assert!(store.load().expect("cannot load").is_none());
+        store.save("checkpoint").expect("cannot save");
+        assert_eq!(
+            store.load().expect("cannot load"),
+            Some("checkpoint".to_string())
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parsing and verification of the checkpoints (signed tree heads) published
+//! by Rekor.
+//!
+//! Checkpoints use the [signed note](https://github.com/transparency-dev/formats/blob/main/log/README.md)
+//! format:
+//!
+//! ```text
+//! rekor.sigstore.dev - 2605736670972794746
+//! 21428036
+//! rs1YPY0ydGu4d0rfEoPoSYWL5zzxPjBxO5HuAAN8HhU=
+//! Timestamp: 1689748607742585419
+//!
+//! — rekor.sigstore.dev wNI9ajBFAiEA8OjwtP9lTdRWI2kOAmTjRb0s...
+//! ```
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+
+use crate::crypto::{CosignVerificationKey, Signature};
+use crate::errors::{Result, SigstoreError};
+
+/// Size of the key hint prepended to each note signature
+const KEY_HINT_SIZE: usize = 4;
+
+/// A Rekor checkpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// The identity of the log that produced the checkpoint
+    pub origin: String,
+    /// The number of entries of the log
+    pub size: u64,
+    /// The root hash of the Merkle tree
+    pub hash: Vec<u8>,
+    /// Optional extension lines, like the timestamp
+    pub other_content: Vec<String>,
+    /// The signatures of the note, as `(name, signature)` tuples. The
+    /// signature still includes the key hint.
+    pub signatures: Vec<(String, Vec<u8>)>,
+    /// The raw signed note this checkpoint has been parsed from
+    pub signed_note: String,
+}
+
+impl Checkpoint {
+    /// Parse a checkpoint, without verifying its signature
+    pub fn parse(signed_note: &str) -> Result<Self> {
+        let err = |reason: &str| SigstoreError::RekorCheckpointError(reason.to_string());
+
+        let (body, signatures) = match signed_note.split_once("\n\n") {
+            Some(parts) => parts,
+            None => return Err(err("missing separator between note and signatures")),
+        };
+
+        let mut lines = body.lines();
+        let origin = match lines.next() {
+            Some(origin) if !origin.is_empty() => origin.to_string(),
+            _ => return Err(err("missing origin")),
+        };
+        let size = match lines.next().map(|l| l.parse::<u64>()) {
+            Some(Ok(size)) => size,
+            _ => return Err(err("missing or invalid tree size")),
+        };
+        let hash = match lines.next().map(|l| BASE64_STD_ENGINE.decode(l)) {
+            Some(Ok(hash)) => hash,
+            _ => return Err(err("missing or invalid root hash")),
+        };
+        let other_content = lines.map(|l| l.to_string()).collect();
+
+        let signatures = signatures
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(|line| {
+                let line = match line.strip_prefix("\u{2014} ") {
+                    Some(line) => line,
+                    None => return Err(err("signature line must start with an em dash")),
+                };
+                let (name, signature) = match line.rsplit_once(' ') {
+                    Some(parts) => parts,
+                    None => return Err(err("malformed signature line")),
+                };
+                let signature = BASE64_STD_ENGINE.decode(signature)?;
+                if signature.len() <= KEY_HINT_SIZE {
+                    return Err(err("signature is too short"));
+                }
+                Ok((name.to_string(), signature))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if signatures.is_empty() {
+            return Err(err("checkpoint is not signed"));
+        }
+
+        Ok(Checkpoint {
+            origin,
+            size,
+            hash,
+            other_content,
+            signatures,
+            signed_note: signed_note.to_string(),
+        })
+    }
+
+    /// Parse a checkpoint and ensure it has been signed by the given key
+    pub fn parse_and_verify(
+        signed_note: &str,
+        rekor_pub_key: &CosignVerificationKey,
+    ) -> Result<Self> {
+        let checkpoint = Self::parse(signed_note)?;
+        checkpoint.verify_signature(rekor_pub_key)?;
+        Ok(checkpoint)
+    }
+
+    /// Ensure at least one of the signatures of the note has been produced
+    /// by the given key
+    pub fn verify_signature(&self, rekor_pub_key: &CosignVerificationKey) -> Result<()> {
+        let body = self.body();
+        let verified = self.signatures.iter().any(|(_, signature)| {
+            rekor_pub_key
+                .verify_signature(Signature::Raw(&signature[KEY_HINT_SIZE..]), body.as_bytes())
+                .is_ok()
+        });
+
+        if verified {
+            Ok(())
+        } else {
+            Err(SigstoreError::RekorCheckpointError(
+                "no valid signature found".to_string(),
+            ))
+        }
+    }
+
+    /// The signed part of the note, including the trailing newline
+    fn body(&self) -> String {
+        let (body, _) = self
+            .signed_note
+            .split_once("\n\n")
+            .expect("checkpoint has already been parsed");
+        format!("{body}\n")
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::crypto::{SigStoreSigner, SigningScheme};
+
+    /// Produce a signed note for the given tree
+    pub(crate) fn sign_checkpoint(signer: &SigStoreSigner, size: u64, hash: &[u8]) -> String {
+        let body = format!(
+            "rekor.local - 1234\n{}\n{}\n",
+            size,
+            BASE64_STD_ENGINE.encode(hash)
+        );
+        let mut signature = vec![0u8; KEY_HINT_SIZE];
+        signature.extend(signer.sign(body.as_bytes()).expect("cannot sign"));
+        format!(
+            "{}\n\u{2014} rekor.local {}\n",
+            body,
+            BASE64_STD_ENGINE.encode(signature)
+        )
+    }
+
+    #[test]
+    fn parse_and_verify_checkpoint() {
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .expect("cannot create signer");
+        let key = signer.to_verification_key().expect("cannot get key");
+        let note = sign_checkpoint(&signer, 42, &[1u8; 32]);
+
+        let checkpoint = Checkpoint::parse_and_verify(&note, &key).expect("invalid checkpoint");
+        assert_eq!(checkpoint.origin, "rekor.local - 1234");
+        assert_eq!(checkpoint.size, 42);
+        assert_eq!(checkpoint.hash, vec![1u8; 32]);
+        assert!(checkpoint.other_content.is_empty());
+        assert_eq!(checkpoint.signatures.len(), 1);
+
+        let other_signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .expect("cannot create signer");
+        let other_key = other_signer.to_verification_key().expect("cannot get key");
+        assert!(checkpoint.verify_signature(&other_key).is_err());
+
+        let tampered = note.replace("\n42\n", "\n43\n");
+        assert!(Checkpoint::parse_and_verify(&tampered, &key).is_err());
+    }
+
+    #[test]
+    fn parse_invalid_checkpoints() {
+        assert!(Checkpoint::parse("rekor.local\n42\n").is_err());
+        assert!(Checkpoint::parse(
+            "rekor.local\nnot-a-number\nAAAA\n\n\u{2014} rekor.local AAAAAAAA\n"
+        )
+        .is_err());
+        assert!(Checkpoint::parse("rekor.local\n42\nAAAA\n\n").is_err());
+    }
+}
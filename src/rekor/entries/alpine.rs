@@ -0,0 +1,309 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rekor `alpine` entries, recording Alpine packages (APK).
+//!
+//! An APK is the concatenation of three gzip streams: the signature, the
+//! control section and the data section. Verifying a package against its
+//! entry ensures that:
+//!
+//! * the package is the one recorded by the entry
+//! * the control section is signed by the RSA key of the entry
+//! * the `datahash` of the control section matches the data section, which
+//!   binds the contents of the package to the signature
+//! * the `.PKGINFO` of the package matches the recorded one
+//!
+//! ```rust,no_run
+//! use sigstore::rekor::entries::alpine::verify_entry;
+//! use sigstore::rekor::models::log_entry::LogEntry;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! # let (entry, apk): (LogEntry, Vec<u8>) = unimplemented!();
+//! let package = verify_entry(&entry.body, &apk)?;
+//! println!("verified {}-{}", package.name, package.version);
+//! # Ok(())
+//! # }
+//! ```
+
+use flate2::bufread::GzDecoder;
+use rsa::pkcs1v15;
+use rsa::pkcs8::DecodePublicKey;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use signature::Verifier;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::Read;
+
+use super::{check_hash, entry_spec, mismatch, spec_base64};
+use crate::errors::{Result, SigstoreError};
+use crate::rekor::models::log_entry::Body;
+
+/// The version of the `alpine` entry type understood by this module
+pub const ALPINE_API_VERSION: &str = "0.0.1";
+
+const PKGINFO_PATH: &str = ".PKGINFO";
+
+/// A verified Alpine package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApkPackage {
+    pub name: String,
+    pub version: String,
+    pub arch: String,
+    /// The `.PKGINFO` of the package. Only the first value of the keys
+    /// repeated by the package, like `depend`, is kept, as done by Rekor.
+    pub pkginfo: BTreeMap<String, String>,
+}
+
+/// Ensure the `alpine` entry `body` records the `apk` package, then verify
+/// the signature of the package with the RSA key of the entry.
+pub fn verify_entry(body: &Body, apk: &[u8]) -> Result<ApkPackage> {
+    let spec = entry_spec(body, "alpine")?;
+    check_hash(spec, &["package", "hash"], apk)?;
+
+    let streams = gzip_streams(apk)?;
+    let (signature, control, data) = match streams.as_slice() {
+        [signature, control, data] => (signature, control, data),
+        _ => return Err(apk_error("expected signature, control and data sections")),
+    };
+
+    // the name of the signature file tells the digest algorithm, e.g.
+    // `.SIGN.RSA256.builder@alpinelinux.org-4a6a0840.rsa.pub`
+    let (path, signature_bytes) = tar_files(&signature.1)?
+        .into_iter()
+        .find(|(path, _)| path.starts_with(".SIGN.RSA"))
+        .ok_or_else(|| apk_error("the package is not signed"))?;
+    let public_key_pem = spec_base64(spec, &["publicKey", "content"])?;
+    let public_key = rsa::RsaPublicKey::from_public_key_pem(std::str::from_utf8(&public_key_pem)?)
+        .map_err(|e| SigstoreError::InvalidKeyFormat {
+            error: e.to_string(),
+        })?;
+    let rsa_signature = pkcs1v15::Signature::try_from(signature_bytes.as_slice())?;
+    let verified = if path.starts_with(".SIGN.RSA256.") {
+        pkcs1v15::VerifyingKey::<Sha256>::new_with_prefix(public_key)
+            .verify(control.0, &rsa_signature)
+    } else if path.starts_with(".SIGN.RSA.") {
+        pkcs1v15::VerifyingKey::<Sha1>::new_with_prefix(public_key)
+            .verify(control.0, &rsa_signature)
+    } else {
+        return Err(apk_error(&format!("unsupported signature file {path}")));
+    };
+    verified.map_err(|_| SigstoreError::PublicKeyVerificationError)?;
+
+    let pkginfo = tar_files(&control.1)?
+        .into_iter()
+        .find(|(path, _)| path == PKGINFO_PATH)
+        .ok_or_else(|| apk_error("the control section doesn't have a .PKGINFO"))?;
+    let pkginfo = parse_pkginfo(std::str::from_utf8(&pkginfo.1)?);
+
+    let datahash = pkginfo
+        .get("datahash")
+        .ok_or_else(|| apk_error("the .PKGINFO doesn't have a datahash"))?;
+    if !hex::encode(Sha256::digest(data.0)).eq_ignore_ascii_case(datahash) {
+        return Err(apk_error("the datahash doesn't match the data section"));
+    }
+
+    if let Some(recorded) = spec.pointer("/package/pkginfo").and_then(|v| v.as_object()) {
+        for (key, value) in recorded {
+            let actual = pkginfo.get(key).map(String::as_str);
+            if actual != value.as_str() {
+                return Err(mismatch(format!(
+                    "the {key} of the .PKGINFO is {value}, the package has {}",
+                    actual.unwrap_or("none")
+                )));
+            }
+        }
+    }
+
+    let required = |key: &str| {
+        pkginfo
+            .get(key)
+            .cloned()
+            .ok_or_else(|| apk_error(&format!("the .PKGINFO doesn't have a {key}")))
+    };
+    Ok(ApkPackage {
+        name: required("pkgname")?,
+        version: required("pkgver")?,
+        arch: required("arch")?,
+        pkginfo,
+    })
+}
+
+fn apk_error(msg: &str) -> SigstoreError {
+    SigstoreError::RekorEntryMismatchError(format!("invalid APK package: {msg}"))
+}
+
+/// Split the concatenated gzip streams of `apk`, returns the compressed
+/// and the decompressed bytes of each stream
+fn gzip_streams(apk: &[u8]) -> Result<Vec<(&[u8], Vec<u8>)>> {
+    let mut streams = Vec::new();
+    let mut rest = apk;
+    while !rest.is_empty() {
+        let mut reader = rest;
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&mut reader).read_to_end(&mut decompressed)?;
+        let consumed = rest.len() - reader.len();
+        if consumed == 0 {
+            return Err(apk_error("invalid gzip stream"));
+        }
+        streams.push((&rest[..consumed], decompressed));
+        rest = reader;
+    }
+    Ok(streams)
+}
+
+/// The regular files of a tar archive. The archives of the signature and of
+/// the control sections don't have the end of archive marker.
+fn tar_files(archive: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut files = Vec::new();
+    for entry in tar::Archive::new(archive).entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.to_string_lossy().into_owned();
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        files.push((path, contents));
+    }
+    Ok(files)
+}
+
+/// Parse the `key = value` lines of a `.PKGINFO`
+fn parse_pkginfo(pkginfo: &str) -> BTreeMap<String, String> {
+    let mut values = BTreeMap::new();
+    for line in pkginfo.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            values
+                .entry(key.trim().to_string())
+                .or_insert_with(|| value.trim().to_string());
+        }
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{SigStoreSigner, SigningScheme};
+    use crate::rekor::models::AlpineAllOf;
+    use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+    use flate2::{write::GzEncoder, Compression};
+    use serde_json::json;
+    use std::io::Write;
+
+    fn gzip_tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *contents).unwrap();
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&builder.into_inner().unwrap()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn build_apk(signer: &SigStoreSigner, name: &str) -> Vec<u8> {
+        let data = gzip_tar(&[("usr/bin/hello", b"#!/bin/sh\necho hello\n")]);
+        let pkginfo = format!(
+            "# Generated by abuild\npkgname = {name}\npkgver = 2.12-r0\narch = x86_64\n\
+             depend = so:libc.musl-x86_64.so.1\ndepend = busybox\ndatahash = {}\n",
+            hex::encode(Sha256::digest(&data))
+        );
+        let control = gzip_tar(&[(PKGINFO_PATH, pkginfo.as_bytes())]);
+        let signature = gzip_tar(&[(
+            ".SIGN.RSA256.tests@sigstore-rs.dev.rsa.pub",
+            &signer.sign(&control).unwrap(),
+        )]);
+        [signature, control, data].concat()
+    }
+
+    fn logged_entry(apk: &[u8], public_key: &str) -> Body {
+        Body::alpine(AlpineAllOf::new(
+            ALPINE_API_VERSION.to_string(),
+            json!({
+                "package": {
+                    "hash": {"algorithm": "sha256", "value": hex::encode(Sha256::digest(apk))},
+                    "pkginfo": {"pkgname": "hello", "pkgver": "2.12-r0", "depend": "so:libc.musl-x86_64.so.1"}
+                },
+                "publicKey": {"content": BASE64_STD_ENGINE.encode(public_key)}
+            }),
+        ))
+    }
+
+    #[test]
+    fn verify_signed_package() {
+        let signer = SigningScheme::RSA_PKCS1_SHA256(2048)
+            .create_signer()
+            .unwrap();
+        let public_key = signer
+            .to_sigstore_keypair()
+            .unwrap()
+            .public_key_to_pem()
+            .unwrap();
+        let apk = build_apk(&signer, "hello");
+
+        let package = verify_entry(&logged_entry(&apk, &public_key), &apk).unwrap();
+        assert_eq!(package.name, "hello");
+        assert_eq!(package.version, "2.12-r0");
+        assert_eq!(package.arch, "x86_64");
+        assert_eq!(package.pkginfo["depend"], "so:libc.musl-x86_64.so.1");
+
+        // another package, signed by the same key
+        let other = build_apk(&signer, "goodbye");
+        let error = verify_entry(&logged_entry(&other, &public_key), &other)
+            .expect_err("wrong pkginfo accepted");
+        assert!(matches!(error, SigstoreError::RekorEntryMismatchError(_)));
+    }
+
+    #[test]
+    fn reject_tampered_data_section() {
+        let signer = SigningScheme::RSA_PKCS1_SHA256(2048)
+            .create_signer()
+            .unwrap();
+        let public_key = signer
+            .to_sigstore_keypair()
+            .unwrap()
+            .public_key_to_pem()
+            .unwrap();
+        let apk = build_apk(&signer, "hello");
+
+        // keep the signed control section, replace the data section
+        let streams = gzip_streams(&apk).unwrap();
+        let tampered = [
+            streams[0].0,
+            streams[1].0,
+            &gzip_tar(&[("usr/bin/hello", b"#!/bin/sh\nrm -rf /\n")]),
+        ]
+        .concat();
+        let error = verify_entry(&logged_entry(&tampered, &public_key), &tampered)
+            .expect_err("tampered data accepted");
+        assert!(matches!(error, SigstoreError::RekorEntryMismatchError(_)));
+    }
+
+    #[test]
+    fn parse_pkginfo_keeps_first_value() {
+        let pkginfo = parse_pkginfo("# comment\npkgname = hello\ndepend = a\ndepend = b\n");
+        assert_eq!(pkginfo.len(), 2);
+        assert_eq!(pkginfo["pkgname"], "hello");
+        assert_eq!(pkginfo["depend"], "a");
+    }
+}
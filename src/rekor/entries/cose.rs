@@ -0,0 +1,169 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rekor `cose` entries, recording [COSE_Sign1](crate::crypto::cose)
+//! envelopes.
+//!
+//! ```rust,no_run
+//! use sigstore::crypto::cose::CoseSign1;
+//! use sigstore::rekor::entries::cose::verify_entry;
+//! use sigstore::rekor::models::log_entry::LogEntry;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! # let (entry, envelope): (LogEntry, Vec<u8>) = unimplemented!();
+//! let envelope: CoseSign1 = verify_entry(&entry.body, &envelope, b"")?;
+//! let firmware = envelope.payload;
+//! # Ok(())
+//! # }
+//! ```
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use serde_json::{json, Value};
+
+use super::{check_hash, decode_public_key, entry_spec, mismatch, spec_base64};
+use crate::crypto::cose::CoseSign1;
+use crate::errors::Result;
+use crate::rekor::models::log_entry::Body;
+use crate::rekor::models::ProposedEntry;
+
+/// The version of the `cose` entry type produced and understood by this
+/// module
+pub const COSE_API_VERSION: &str = "0.0.1";
+
+/// Build the entry to be submitted to Rekor to record `envelope`, signed by
+/// the PEM encoded `public_key`. The `external_aad` is the one used when
+/// signing the envelope.
+pub fn proposed_entry(
+    envelope: &CoseSign1,
+    public_key: &str,
+    external_aad: &[u8],
+) -> ProposedEntry {
+    let mut data = json!({});
+    if !external_aad.is_empty() {
+        data["aad"] = Value::String(BASE64_STD_ENGINE.encode(external_aad));
+    }
+    ProposedEntry::Cose {
+        api_version: COSE_API_VERSION.to_string(),
+        spec: json!({
+            "message": BASE64_STD_ENGINE.encode(envelope.to_vec()),
+            "publicKey": BASE64_STD_ENGINE.encode(public_key),
+            "data": data,
+        }),
+    }
+}
+
+/// Ensure the `cose` entry `body` records the serialized `envelope`, then
+/// verify the envelope with the key of the entry.
+///
+/// Returns the verified envelope.
+pub fn verify_entry(body: &Body, envelope: &[u8], external_aad: &[u8]) -> Result<CoseSign1> {
+    let spec = entry_spec(body, "cose")?;
+    check_hash(spec, &["data", "envelopeHash"], envelope)?;
+
+    let cose = CoseSign1::from_slice(envelope)?;
+    let payload = cose
+        .payload
+        .as_ref()
+        .ok_or_else(|| mismatch("the envelope doesn't embed its payload".to_string()))?;
+    check_hash(spec, &["data", "payloadHash"], payload)?;
+
+    let recorded_aad = match spec.pointer("/data/aad") {
+        Some(_) => spec_base64(spec, &["data", "aad"])?,
+        None => Vec::new(),
+    };
+    if recorded_aad != external_aad {
+        return Err(mismatch(
+            "the external additional data is not the recorded one".to_string(),
+        ));
+    }
+
+    let (key, _) = decode_public_key(&spec_base64(spec, &["publicKey"])?)?;
+    cose.verify(&key, external_aad)?;
+    Ok(cose)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::SigningScheme;
+    use crate::errors::SigstoreError;
+    use crate::rekor::models::CoseAllOf;
+    use sha2::{Digest, Sha256};
+
+    /// Mimic the canonicalization performed by Rekor
+    fn logged_entry(envelope: &[u8], payload: &[u8], public_key: &str, aad: &[u8]) -> Body {
+        let mut data = json!({
+            "envelopeHash": {"algorithm": "sha256", "value": hex::encode(Sha256::digest(envelope))},
+            "payloadHash": {"algorithm": "sha256", "value": hex::encode(Sha256::digest(payload))},
+        });
+        if !aad.is_empty() {
+            data["aad"] = Value::String(BASE64_STD_ENGINE.encode(aad));
+        }
+        Body::cose(CoseAllOf::new(
+            COSE_API_VERSION.to_string(),
+            json!({"publicKey": BASE64_STD_ENGINE.encode(public_key), "data": data}),
+        ))
+    }
+
+    #[test]
+    fn verify_logged_envelope() {
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let public_key = signer
+            .to_sigstore_keypair()
+            .unwrap()
+            .public_key_to_pem()
+            .unwrap();
+        let envelope = CoseSign1::sign(&signer, b"firmware", b"device").unwrap();
+        let encoded = envelope.to_vec();
+
+        match proposed_entry(&envelope, &public_key, b"device") {
+            ProposedEntry::Cose { spec, .. } => {
+                assert_eq!(spec["message"], BASE64_STD_ENGINE.encode(&encoded));
+                assert_eq!(spec["data"]["aad"], BASE64_STD_ENGINE.encode(b"device"));
+            }
+            _ => panic!("not a cose entry"),
+        }
+
+        let body = logged_entry(&encoded, b"firmware", &public_key, b"device");
+        let verified = verify_entry(&body, &encoded, b"device").unwrap();
+        assert_eq!(verified.payload.as_deref(), Some(&b"firmware"[..]));
+
+        assert!(verify_entry(&body, &encoded, b"").is_err());
+
+        let other = CoseSign1::sign(&signer, b"malware", b"device")
+            .unwrap()
+            .to_vec();
+        let error = verify_entry(&body, &other, b"device").expect_err("other envelope accepted");
+        assert!(matches!(error, SigstoreError::RekorEntryMismatchError(_)));
+    }
+
+    #[test]
+    fn reject_envelope_signed_by_another_key() {
+        let signer = SigningScheme::ED25519.create_signer().unwrap();
+        let other_key = SigningScheme::ED25519
+            .create_signer()
+            .unwrap()
+            .to_sigstore_keypair()
+            .unwrap()
+            .public_key_to_pem()
+            .unwrap();
+        let encoded = CoseSign1::sign(&signer, b"firmware", b"").unwrap().to_vec();
+
+        let body = logged_entry(&encoded, b"firmware", &other_key, b"");
+        assert!(verify_entry(&body, &encoded, b"").is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use const_oid::ObjectIdentifier;
+use x509_cert::ext::pkix::{name::GeneralName, SubjectAltName};
+use x509_cert::Certificate;
+
+use crate::errors::{Result, SigstoreError};
+
+/// The extension holding the OIDC issuer inside of Fulcio certificates
+const ISSUER_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.1");
+
+/// The identity expected from the signer of an artifact, the counterpart of
+/// [`CertSubjectEmailVerifier`](crate::cosign::verification_constraint::CertSubjectEmailVerifier)
+/// and [`CertSubjectUrlVerifier`](crate::cosign::verification_constraint::CertSubjectUrlVerifier)
+/// for the artifacts recorded in Rekor with their certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityPolicy {
+    /// The email or the URI expected inside of the Subject Alternative Name
+    /// of the certificate
+    pub subject: String,
+    /// The OIDC issuer that authenticated the signer, not checked when `None`
+    pub issuer: Option<String>,
+}
+
+impl IdentityPolicy {
+    /// Ensure `certificate` has been issued to the expected identity
+    pub fn check(&self, certificate: &Certificate) -> Result<()> {
+        let subjects = certificate_subjects(certificate)?;
+        if !subjects.iter().any(|s| s == &self.subject) {
+            return Err(SigstoreError::VerificationConstraintError(format!(
+                "certificate issued to {}, expected {}",
+                subjects.join(", "),
+                self.subject
+            )));
+        }
+
+        if let Some(issuer) = &self.issuer {
+            let actual = certificate_issuer(certificate)?;
+            if actual.as_deref() != Some(issuer.as_str()) {
+                return Err(SigstoreError::VerificationConstraintError(format!(
+                    "certificate issued by {}, expected {issuer}",
+                    actual.unwrap_or_else(|| "an unknown issuer".to_string())
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The emails and URIs inside of the Subject Alternative Name of a
+/// certificate
+pub(crate) fn certificate_subjects(certificate: &Certificate) -> Result<Vec<String>> {
+    let san = certificate
+        .tbs_certificate
+        .get::<SubjectAltName>()
+        .map_err(|e| SigstoreError::X509Error(e.to_string()))?;
+    Ok(san
+        .map(|(_, san)| {
+            san.0
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::Rfc822Name(email) => Some(email.to_string()),
+                    GeneralName::UniformResourceIdentifier(uri) => Some(uri.to_string()),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// The OIDC issuer stored by Fulcio inside of a certificate
+fn certificate_issuer(certificate: &Certificate) -> Result<Option<String>> {
+    certificate
+        .tbs_certificate
+        .extensions
+        .iter()
+        .flatten()
+        .find(|ext| ext.extn_id == ISSUER_OID)
+        .map(|ext| {
+            String::from_utf8(ext.extn_value.to_vec()).map_err(|_| {
+                SigstoreError::X509Error("the issuer extension is not UTF8 compatible".to_string())
+            })
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::tests::{generate_certificate, CertGenerationOptions};
+    use pkcs8::der::Decode;
+
+    #[test]
+    fn check_certificate_identity() {
+        let ca = generate_certificate(None, CertGenerationOptions::default()).unwrap();
+        let issued = generate_certificate(Some(&ca), CertGenerationOptions::default()).unwrap();
+        let certificate_der = issued.cert.to_der().unwrap();
+        let certificate = Certificate::from_der(&certificate_der).unwrap();
+
+        let policy = IdentityPolicy {
+            subject: "tests@sigstore-rs.dev".to_string(),
+            issuer: None,
+        };
+        assert!(policy.check(&certificate).is_ok());
+
+        let policy = IdentityPolicy {
+            subject: "someone@example.com".to_string(),
+            issuer: None,
+        };
+        assert!(policy.check(&certificate).is_err());
+
+        // the test certificates don't have the issuer extension
+        let policy = IdentityPolicy {
+            subject: "tests@sigstore-rs.dev".to_string(),
+            issuer: Some("https://github.com/login/oauth".to_string()),
+        };
+        assert!(policy.check(&certificate).is_err());
+    }
+}
@@ -0,0 +1,454 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rekor `jar` entries, recording signed Java archives.
+//!
+//! Verifying a JAR against its entry ensures that:
+//!
+//! * the archive is the one recorded by the entry
+//! * the signature block of the archive is the one recorded by the entry
+//! * the [signed JAR](https://docs.oracle.com/en/java/javase/17/docs/specs/jar/jar.html#signed-jar-file)
+//!   digests are consistent: the signature file covers the manifest, which
+//!   covers all the files of the archive
+//! * the certificate of the signer satisfies the identity policies
+//!
+//! The PKCS #7 signature of the signature file is verified by Rekor when the
+//! entry is created. The archive hash binds the entry to this exact archive.
+//!
+//! ```rust,no_run
+//! use sigstore::rekor::entries::jar::verify_entry;
+//! use sigstore::rekor::entries::IdentityPolicy;
+//! use sigstore::rekor::models::log_entry::LogEntry;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! # let (entry, jar): (LogEntry, Vec<u8>) = unimplemented!();
+//! let release_pipeline = IdentityPolicy {
+//!     subject: "https://github.com/octocat/app/.github/workflows/release.yml@refs/heads/main"
+//!         .to_string(),
+//!     issuer: Some("https://token.actions.githubusercontent.com".to_string()),
+//! };
+//! let signer = verify_entry(&entry.body, &jar, &[release_pipeline])?;
+//! # Ok(())
+//! # }
+//! ```
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use pkcs8::der::Decode;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read};
+use x509_cert::Certificate;
+
+use super::{check_hash, decode_public_key, entry_spec, mismatch, spec_base64, IdentityPolicy};
+use crate::errors::{Result, SigstoreError};
+use crate::rekor::models::log_entry::Body;
+
+/// The version of the `jar` entry type understood by this module
+pub const JAR_API_VERSION: &str = "0.0.1";
+
+const MANIFEST_PATH: &str = "META-INF/MANIFEST.MF";
+
+/// The extensions of the signature block files
+const SIGNATURE_BLOCK_EXTENSIONS: [&str; 3] = ["RSA", "EC", "DSA"];
+
+/// The digest algorithms understood, in order of preference
+const DIGEST_ALGORITHMS: [&str; 3] = ["SHA-512", "SHA-384", "SHA-256"];
+
+/// The signer of a verified JAR
+#[derive(Debug, Clone)]
+pub struct JarSigner {
+    /// The name of the signer inside of the archive, e.g. `SIGNER` for
+    /// `META-INF/SIGNER.SF`
+    pub name: String,
+    /// The DER encoded certificate of the signer, as recorded by Rekor
+    pub certificate: Vec<u8>,
+}
+
+/// Ensure the `jar` entry `body` records `jar`, then verify the signature
+/// of the archive.
+///
+/// The certificate of the signer must satisfy at least one of the
+/// `identities`. The identity is not checked when `identities` is empty.
+pub fn verify_entry(body: &Body, jar: &[u8], identities: &[IdentityPolicy]) -> Result<JarSigner> {
+    let spec = entry_spec(body, "jar")?;
+    check_hash(spec, &["archive", "hash"], jar)?;
+
+    let (_, certificate) =
+        decode_public_key(&spec_base64(spec, &["signature", "publicKey", "content"])?)?;
+    let certificate = certificate.ok_or_else(|| {
+        mismatch("the entry doesn't record the certificate of the signer".to_string())
+    })?;
+    let signature_block = spec_base64(spec, &["signature", "content"])?;
+
+    let files = read_archive(jar)?;
+    let name = files
+        .iter()
+        .find_map(|(path, content)| {
+            signature_block_name(path).filter(|_| content == &signature_block)
+        })
+        .ok_or_else(|| {
+            mismatch("the archive doesn't contain the recorded signature block".to_string())
+        })?;
+
+    let manifest = files
+        .get(MANIFEST_PATH)
+        .ok_or_else(|| jar_error("the archive doesn't have a manifest"))?;
+    let signature_file = files
+        .get(&format!("META-INF/{name}.SF"))
+        .ok_or_else(|| jar_error(&format!("the signature file of {name} is missing")))?;
+    verify_digests(&files, manifest, signature_file)?;
+
+    let cert = Certificate::from_der(&certificate)
+        .map_err(|e| SigstoreError::PKCS8DerError(e.to_string()))?;
+    if !identities.is_empty() && !identities.iter().any(|i| i.check(&cert).is_ok()) {
+        return Err(SigstoreError::VerificationConstraintError(
+            "the signer of the archive doesn't satisfy any identity policy".to_string(),
+        ));
+    }
+
+    Ok(JarSigner { name, certificate })
+}
+
+/// Read all the files of the archive, indexed by path
+fn read_archive(jar: &[u8]) -> Result<BTreeMap<String, Vec<u8>>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(jar))?;
+    let mut files = BTreeMap::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        files.insert(file.name().to_string(), content);
+    }
+    Ok(files)
+}
+
+/// The name of the signer, when `path` is a signature block file
+fn signature_block_name(path: &str) -> Option<String> {
+    let file = path.strip_prefix("META-INF/")?;
+    let (name, extension) = file.rsplit_once('.')?;
+    if name.is_empty()
+        || name.contains('/')
+        || !SIGNATURE_BLOCK_EXTENSIONS
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case(extension))
+    {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// Files of `META-INF` that are not covered by the manifest
+fn is_signature_related(path: &str) -> bool {
+    match path.strip_prefix("META-INF/") {
+        Some(file) if !file.contains('/') => {
+            let upper = file.to_uppercase();
+            upper == "MANIFEST.MF"
+                || upper.ends_with(".SF")
+                || signature_block_name(path).is_some()
+                || upper.starts_with("SIG-")
+        }
+        _ => false,
+    }
+}
+
+/// A section of a manifest or of a signature file
+struct Section<'a> {
+    /// The bytes of the section, including the blank line ending it
+    raw: &'a [u8],
+    attributes: BTreeMap<String, String>,
+}
+
+impl Section<'_> {
+    fn name(&self) -> Option<&str> {
+        self.attributes.get("Name").map(String::as_str)
+    }
+
+    /// The digest attribute using the strongest algorithm understood
+    fn digest(&self, suffix: &str) -> Option<(&'static str, &str)> {
+        DIGEST_ALGORITHMS.iter().find_map(|algorithm| {
+            self.attributes
+                .get(&format!("{algorithm}-Digest{suffix}"))
+                .map(|value| (*algorithm, value.as_str()))
+        })
+    }
+}
+
+/// Split a manifest, or a signature file, into its sections. The first
+/// section is the main one.
+fn parse_sections(data: &[u8]) -> Result<Vec<Section<'_>>> {
+    let mut sections = Vec::new();
+    let mut start = 0;
+    let mut attributes: BTreeMap<String, String> = BTreeMap::new();
+    let mut last_key: Option<String> = None;
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let end = data[pos..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map(|i| pos + i + 1)
+            .unwrap_or(data.len());
+        let line = &data[pos..end];
+        let content = line.strip_suffix(b"\n").unwrap_or(line);
+        let content = content.strip_suffix(b"\r").unwrap_or(content);
+        pos = end;
+
+        if content.is_empty() {
+            if !attributes.is_empty() {
+                sections.push(Section {
+                    raw: &data[start..pos],
+                    attributes: std::mem::take(&mut attributes),
+                });
+            }
+            start = pos;
+            last_key = None;
+            continue;
+        }
+
+        let content = std::str::from_utf8(content)
+            .map_err(|_| jar_error("the manifest is not valid UTF-8"))?;
+        if let Some(continuation) = content.strip_prefix(' ') {
+            let key = last_key
+                .as_ref()
+                .ok_or_else(|| jar_error("unexpected continuation line in manifest"))?;
+            if let Some(value) = attributes.get_mut(key) {
+                value.push_str(continuation);
+            }
+        } else {
+            let (key, value) = content
+                .split_once(": ")
+                .ok_or_else(|| jar_error(&format!("invalid manifest line: {content}")))?;
+            attributes.insert(key.to_string(), value.to_string());
+            last_key = Some(key.to_string());
+        }
+    }
+    if !attributes.is_empty() {
+        sections.push(Section {
+            raw: &data[start..],
+            attributes,
+        });
+    }
+    Ok(sections)
+}
+
+/// Base64 encoded digest of `data`
+fn digest(algorithm: &str, data: &[u8]) -> String {
+    match algorithm {
+        "SHA-512" => BASE64_STD_ENGINE.encode(Sha512::digest(data)),
+        "SHA-384" => BASE64_STD_ENGINE.encode(Sha384::digest(data)),
+        _ => BASE64_STD_ENGINE.encode(Sha256::digest(data)),
+    }
+}
+
+/// Ensure the signature file covers the manifest, and the manifest covers
+/// all the files of the archive
+fn verify_digests(
+    files: &BTreeMap<String, Vec<u8>>,
+    manifest: &[u8],
+    signature_file: &[u8],
+) -> Result<()> {
+    let manifest_sections = parse_sections(manifest)?;
+    let signature_sections = parse_sections(signature_file)?;
+    let signature_main = signature_sections
+        .first()
+        .ok_or_else(|| jar_error("the signature file is empty"))?;
+
+    // the signature file covers either the whole manifest, or each one of
+    // its sections
+    let whole_manifest_signed = signature_main
+        .digest("-Manifest")
+        .map(|(algorithm, value)| digest(algorithm, manifest) == value)
+        .unwrap_or(false);
+
+    let mut signed_entries = BTreeMap::new();
+    for section in manifest_sections.iter().skip(1) {
+        if let Some(name) = section.name() {
+            signed_entries.insert(name.to_string(), section);
+        }
+    }
+
+    if !whole_manifest_signed {
+        for section in signature_sections.iter().skip(1) {
+            let name = section
+                .name()
+                .ok_or_else(|| jar_error("signature file section without name"))?;
+            let (algorithm, expected) = section
+                .digest("")
+                .ok_or_else(|| jar_error(&format!("no supported digest for {name}")))?;
+            let manifest_section = signed_entries
+                .get(name)
+                .ok_or_else(|| jar_error(&format!("{name} is not part of the manifest")))?;
+            if digest(algorithm, manifest_section.raw) != expected {
+                return Err(jar_error(&format!(
+                    "the manifest section of {name} has been modified"
+                )));
+            }
+        }
+        let signed: Vec<&str> = signature_sections
+            .iter()
+            .skip(1)
+            .filter_map(Section::name)
+            .collect();
+        signed_entries.retain(|name, _| signed.contains(&name.as_str()));
+    }
+
+    for (path, content) in files {
+        if is_signature_related(path) {
+            continue;
+        }
+        let section = signed_entries
+            .get(path)
+            .ok_or_else(|| jar_error(&format!("{path} is not signed")))?;
+        let (algorithm, expected) = section
+            .digest("")
+            .ok_or_else(|| jar_error(&format!("no supported digest for {path}")))?;
+        if digest(algorithm, content) != expected {
+            return Err(jar_error(&format!("{path} has been modified")));
+        }
+    }
+    Ok(())
+}
+
+fn jar_error(msg: &str) -> SigstoreError {
+    SigstoreError::RekorEntryMismatchError(format!("invalid signed JAR: {msg}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::tests::{generate_certificate, CertGenerationOptions};
+    use crate::rekor::models::JarAllOf;
+    use serde_json::json;
+    use std::io::Write;
+
+    const SIGNATURE_BLOCK: &[u8] = b"PKCS7 signature block";
+
+    fn manifest_section(name: &str, content: &[u8]) -> String {
+        format!(
+            "Name: {name}\r\nSHA-256-Digest: {}\r\n\r\n",
+            digest("SHA-256", content)
+        )
+    }
+
+    fn build_jar(files: &[(&str, &[u8])], extra: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut manifest = "Manifest-Version: 1.0\r\nCreated-By: test\r\n\r\n".to_string();
+        let mut signature_file = String::new();
+        for (name, content) in files {
+            let section = manifest_section(name, content);
+            signature_file.push_str(&format!(
+                "Name: {name}\r\nSHA-256-Digest: {}\r\n\r\n",
+                digest("SHA-256", section.as_bytes())
+            ));
+            manifest.push_str(&section);
+        }
+        let signature_file = format!(
+            "Signature-Version: 1.0\r\nSHA-256-Digest-Manifest: {}\r\n\r\n{signature_file}",
+            digest("SHA-256", manifest.as_bytes())
+        );
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let mut add = |name: &str, content: &[u8]| {
+            zip.start_file(name, zip::write::FileOptions::default())
+                .unwrap();
+            zip.write_all(content).unwrap();
+        };
+        add(MANIFEST_PATH, manifest.as_bytes());
+        add("META-INF/SIGNER.SF", signature_file.as_bytes());
+        add("META-INF/SIGNER.EC", SIGNATURE_BLOCK);
+        for (name, content) in files.iter().chain(extra) {
+            add(name, content);
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn logged_entry(jar: &[u8], certificate_pem: &[u8]) -> Body {
+        Body::jar(JarAllOf::new(
+            JAR_API_VERSION.to_string(),
+            json!({
+                "archive": {"hash": {"algorithm": "sha256", "value": hex::encode(Sha256::digest(jar))}},
+                "signature": {
+                    "content": BASE64_STD_ENGINE.encode(SIGNATURE_BLOCK),
+                    "publicKey": {"content": BASE64_STD_ENGINE.encode(certificate_pem)}
+                }
+            }),
+        ))
+    }
+
+    fn certificate_pem() -> Vec<u8> {
+        let ca = generate_certificate(None, CertGenerationOptions::default()).unwrap();
+        let issued = generate_certificate(Some(&ca), CertGenerationOptions::default()).unwrap();
+        issued.cert.to_pem().unwrap()
+    }
+
+    const FILES: [(&str, &[u8]); 2] = [
+        ("com/example/App.class", b"\xca\xfe\xba\xbe app"),
+        ("application.properties", b"debug=false"),
+    ];
+
+    #[test]
+    fn verify_signed_jar() {
+        let jar = build_jar(&FILES, &[]);
+        let body = logged_entry(&jar, &certificate_pem());
+
+        let signer = verify_entry(&body, &jar, &[]).unwrap();
+        assert_eq!(signer.name, "SIGNER");
+
+        let expected = IdentityPolicy {
+            subject: "tests@sigstore-rs.dev".to_string(),
+            issuer: None,
+        };
+        let other = IdentityPolicy {
+            subject: "someone@example.com".to_string(),
+            issuer: None,
+        };
+        assert!(verify_entry(&body, &jar, &[other.clone(), expected]).is_ok());
+        assert!(verify_entry(&body, &jar, &[other]).is_err());
+    }
+
+    #[test]
+    fn reject_other_archives() {
+        let jar = build_jar(&FILES, &[]);
+        let body = logged_entry(&jar, &certificate_pem());
+
+        let other = build_jar(&FILES[..1], &[]);
+        let error = verify_entry(&body, &other, &[]).expect_err("other archive accepted");
+        assert!(matches!(error, SigstoreError::RekorEntryMismatchError(_)));
+    }
+
+    #[test]
+    fn reject_unsigned_files() {
+        let jar = build_jar(&FILES, &[("com/example/Backdoor.class", b"evil")]);
+        // the entry matches the archive, but the archive is not fully signed
+        let body = logged_entry(&jar, &certificate_pem());
+        assert!(verify_entry(&body, &jar, &[]).is_err());
+    }
+
+    #[test]
+    fn parse_manifest_sections() {
+        let manifest = b"Manifest-Version: 1.0\r\n\r\nName: a/very/long/path/that/is\r\n /wrapped.class\r\nSHA-256-Digest: abc\r\n\r\n";
+        let sections = parse_sections(manifest).unwrap();
+        assert_eq!(sections.len(), 2);
+        assert_eq!(
+            sections[1].name(),
+            Some("a/very/long/path/that/is/wrapped.class")
+        );
+        assert_eq!(sections[1].digest(""), Some(("SHA-256", "abc")));
+        assert!(sections[1].raw.ends_with(b"\r\n\r\n"));
+        assert!(sections[1].raw.starts_with(b"Name: "));
+    }
+}
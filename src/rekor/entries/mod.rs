@@ -0,0 +1,154 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of artifacts against the Rekor entries recording them.
+//!
+//! Rekor validates the signature of an artifact when the entry is created,
+//! then stores only a summary of it: the hashes of the artifact and the key
+//! or certificate of the signer. The helpers of this module ensure an entry
+//! is really about the artifact being verified, and perform again the
+//! signature verification when possible.
+//!
+//! The entries must come from a trusted source: for example they have been
+//! fetched from the log and their inclusion has been verified, or they are
+//! part of a verified bundle.
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use pkcs8::der::Decode;
+use serde_json::Value;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::convert::TryFrom;
+use x509_cert::Certificate;
+
+use crate::crypto::CosignVerificationKey;
+use crate::errors::{Result, SigstoreError};
+use crate::rekor::models::log_entry::Body;
+
+#[cfg(feature = "rekor-apk")]
+pub mod alpine;
+pub mod cose;
+pub mod identity;
+pub use identity::IdentityPolicy;
+#[cfg(feature = "rekor-jar")]
+pub mod jar;
+mod pgp;
+pub mod rpm;
+pub mod tuf;
+
+/// The kind specific contents of `body`, ensuring it has the expected kind
+pub(crate) fn entry_spec<'a>(body: &'a Body, kind: &str) -> Result<&'a Value> {
+    if body.kind() != kind {
+        return Err(mismatch(format!(
+            "expected a {kind} entry, found a {} one",
+            body.kind()
+        )));
+    }
+    Ok(body.spec())
+}
+
+/// Look up the string at `path` inside of `spec`
+pub(crate) fn spec_str<'a>(spec: &'a Value, path: &[&str]) -> Result<&'a str> {
+    path.iter()
+        .try_fold(spec, |value, key| value.get(key))
+        .and_then(Value::as_str)
+        .ok_or_else(|| mismatch(format!("the entry doesn't have {}", path.join("."))))
+}
+
+/// Decode the base64 string at `path` inside of `spec`
+pub(crate) fn spec_base64(spec: &Value, path: &[&str]) -> Result<Vec<u8>> {
+    Ok(BASE64_STD_ENGINE.decode(spec_str(spec, path)?)?)
+}
+
+/// Ensure the hash object (`{"algorithm": ..., "value": ...}`) at `path`
+/// inside of `spec` is the digest of `data`
+pub(crate) fn check_hash(spec: &Value, path: &[&str], data: &[u8]) -> Result<()> {
+    let field = |name| {
+        let mut field = path.to_vec();
+        field.push(name);
+        field
+    };
+    let algorithm = spec_str(spec, &field("algorithm"))?;
+    let expected = spec_str(spec, &field("value"))?;
+    let actual = match algorithm {
+        "sha256" => hex::encode(Sha256::digest(data)),
+        "sha384" => hex::encode(Sha384::digest(data)),
+        "sha512" => hex::encode(Sha512::digest(data)),
+        _ => {
+            return Err(mismatch(format!(
+                "unsupported hash algorithm {algorithm} for {}",
+                path.join(".")
+            )))
+        }
+    };
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(mismatch(format!(
+            "{} is {algorithm}:{expected}, the artifact has {algorithm}:{actual}",
+            path.join(".")
+        )));
+    }
+    Ok(())
+}
+
+/// A key recorded by Rekor, either a PEM encoded public key or a PEM
+/// encoded certificate. The DER encoded certificate is returned too, if any.
+pub(crate) fn decode_public_key(
+    pem_data: &[u8],
+) -> Result<(CosignVerificationKey, Option<Vec<u8>>)> {
+    let pem = pem::parse(pem_data)?;
+    match pem.tag.as_str() {
+        "CERTIFICATE" => {
+            let cert = Certificate::from_der(&pem.contents)
+                .map_err(|e| SigstoreError::PKCS8DerError(e.to_string()))?;
+            let key =
+                CosignVerificationKey::try_from(&cert.tbs_certificate.subject_public_key_info)?;
+            Ok((key, Some(pem.contents)))
+        }
+        _ => Ok((CosignVerificationKey::try_from_der(&pem.contents)?, None)),
+    }
+}
+
+pub(crate) fn mismatch(reason: String) -> SigstoreError {
+    SigstoreError::RekorEntryMismatchError(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rekor::models::TufAllOf;
+    use serde_json::json;
+
+    #[test]
+    fn check_entry_hashes() {
+        let spec = json!({
+            "data": {
+                "hash": {
+                    "algorithm": "sha256",
+                    "value": "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+                }
+            }
+        });
+        assert!(check_hash(&spec, &["data", "hash"], b"hello").is_ok());
+        let error = check_hash(&spec, &["data", "hash"], b"world").expect_err("wrong data");
+        assert!(matches!(error, SigstoreError::RekorEntryMismatchError(_)));
+        assert!(check_hash(&spec, &["data", "other"], b"hello").is_err());
+    }
+
+    #[test]
+    fn check_entry_kind() {
+        let body = Body::tuf(TufAllOf::new("0.0.1".to_string(), json!({})));
+        assert!(entry_spec(&body, "tuf").is_ok());
+        assert!(entry_spec(&body, "rpm").is_err());
+    }
+}
@@ -0,0 +1,486 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The subset of [OpenPGP](https://www.rfc-editor.org/rfc/rfc4880) needed
+//! to verify detached signatures of packages.
+//!
+//! Only version 4 keys and signatures are supported, using RSA, ECDSA with
+//! P-256 or EdDSA with Ed25519.
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use rsa::{pkcs1v15, BigUint, RsaPublicKey};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::convert::TryInto;
+
+use crate::crypto::{CosignVerificationKey, Signature, SignatureFormat};
+use crate::errors::{Result, SigstoreError};
+
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_PUBLIC_SUBKEY: u8 = 14;
+
+const ALGORITHM_RSA: [u8; 3] = [1, 2, 3];
+const ALGORITHM_ECDSA: u8 = 19;
+const ALGORITHM_EDDSA: u8 = 22;
+
+const HASH_SHA256: u8 = 8;
+const HASH_SHA384: u8 = 9;
+const HASH_SHA512: u8 = 10;
+
+/// The OID of the P-256 curve, as encoded inside of key packets
+const OID_P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+/// The OID of Ed25519, as encoded inside of key packets
+const OID_ED25519: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xda, 0x47, 0x0f, 0x01];
+
+fn pgp_error(msg: &str) -> SigstoreError {
+    SigstoreError::RekorEntryMismatchError(format!("invalid OpenPGP data: {msg}"))
+}
+
+/// An OpenPGP packet
+struct Packet<'a> {
+    tag: u8,
+    body: &'a [u8],
+}
+
+/// Split `data` into packets
+fn packets(data: &[u8]) -> Result<Vec<Packet<'_>>> {
+    let mut reader = Reader::new(data);
+    let mut packets = Vec::new();
+    while !reader.is_empty() {
+        let header = reader.u8()?;
+        if header & 0x80 == 0 {
+            return Err(pgp_error("invalid packet header"));
+        }
+        let (tag, len) = if header & 0x40 != 0 {
+            let first = reader.u8()? as usize;
+            let len = match first {
+                0..=191 => first,
+                192..=223 => ((first - 192) << 8) + reader.u8()? as usize + 192,
+                255 => reader.u32()? as usize,
+                _ => return Err(pgp_error("partial body lengths are not supported")),
+            };
+            (header & 0x3f, len)
+        } else {
+            let len = match header & 0x03 {
+                0 => reader.u8()? as usize,
+                1 => reader.u16()? as usize,
+                2 => reader.u32()? as usize,
+                _ => return Err(pgp_error("indeterminate lengths are not supported")),
+            };
+            ((header >> 2) & 0x0f, len)
+        };
+        packets.push(Packet {
+            tag,
+            body: reader.take(len)?,
+        });
+    }
+    Ok(packets)
+}
+
+/// Remove the ASCII armor, when present
+fn dearmor(data: &[u8]) -> Result<Vec<u8>> {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) if text.trim_start().starts_with("-----BEGIN PGP") => text,
+        _ => return Ok(data.to_vec()),
+    };
+
+    let mut encoded = String::new();
+    let mut in_body = false;
+    let mut in_headers = false;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with("-----BEGIN PGP") {
+            in_headers = true;
+            continue;
+        }
+        if line.starts_with("-----END PGP") {
+            break;
+        }
+        if in_headers {
+            // the headers, like `Version`, end with a blank line
+            if line.is_empty() {
+                in_headers = false;
+                in_body = true;
+            } else if !line.contains(": ") {
+                in_headers = false;
+                in_body = true;
+                encoded.push_str(line);
+            }
+            continue;
+        }
+        if in_body && !line.starts_with('=') {
+            encoded.push_str(line);
+        }
+    }
+    Ok(BASE64_STD_ENGINE.decode(encoded)?)
+}
+
+/// A public key, or subkey, that can verify signatures
+pub(crate) struct PublicKey {
+    algorithm: u8,
+    material: KeyMaterial,
+}
+
+enum KeyMaterial {
+    Rsa { n: Vec<u8>, e: Vec<u8> },
+    EcdsaP256 { point: Vec<u8> },
+    Ed25519 { point: Vec<u8> },
+    Unsupported,
+}
+
+impl PublicKey {
+    fn parse(body: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(body);
+        if reader.u8()? != 4 {
+            return Err(pgp_error("only version 4 keys are supported"));
+        }
+        let _creation_time = reader.u32()?;
+        let algorithm = reader.u8()?;
+        let material = match algorithm {
+            a if ALGORITHM_RSA.contains(&a) => KeyMaterial::Rsa {
+                n: reader.mpi()?.to_vec(),
+                e: reader.mpi()?.to_vec(),
+            },
+            ALGORITHM_ECDSA | ALGORITHM_EDDSA => {
+                let oid_len = reader.u8()? as usize;
+                let oid = reader.take(oid_len)?;
+                let point = reader.mpi()?.to_vec();
+                match (algorithm, oid) {
+                    (ALGORITHM_ECDSA, OID_P256) => KeyMaterial::EcdsaP256 { point },
+                    (ALGORITHM_EDDSA, OID_ED25519) => KeyMaterial::Ed25519 { point },
+                    _ => KeyMaterial::Unsupported,
+                }
+            }
+            _ => KeyMaterial::Unsupported,
+        };
+        Ok(PublicKey {
+            algorithm,
+            material,
+        })
+    }
+}
+
+/// Parse all the keys and subkeys of a, possibly armored, transferable
+/// public key
+pub(crate) fn public_keys(data: &[u8]) -> Result<Vec<PublicKey>> {
+    let data = dearmor(data)?;
+    let keys = packets(&data)?
+        .into_iter()
+        .filter(|p| p.tag == TAG_PUBLIC_KEY || p.tag == TAG_PUBLIC_SUBKEY)
+        .map(|p| PublicKey::parse(p.body))
+        .collect::<Result<Vec<_>>>()?;
+    if keys.is_empty() {
+        return Err(pgp_error("no public key found"));
+    }
+    Ok(keys)
+}
+
+/// A version 4 signature packet
+struct SignaturePacket<'a> {
+    algorithm: u8,
+    hash_algorithm: u8,
+    /// The part of the packet covered by the signature
+    hashed: &'a [u8],
+    mpis: Vec<&'a [u8]>,
+}
+
+impl<'a> SignaturePacket<'a> {
+    fn parse(body: &'a [u8]) -> Result<Self> {
+        let mut reader = Reader::new(body);
+        if reader.u8()? != 4 {
+            return Err(pgp_error("only version 4 signatures are supported"));
+        }
+        let _signature_type = reader.u8()?;
+        let algorithm = reader.u8()?;
+        let hash_algorithm = reader.u8()?;
+        let hashed_len = reader.u16()? as usize;
+        reader.take(hashed_len)?;
+        let hashed = &body[..reader.pos];
+        let unhashed_len = reader.u16()? as usize;
+        reader.take(unhashed_len)?;
+        let _hash_prefix = reader.take(2)?;
+        let mut mpis = Vec::new();
+        while !reader.is_empty() {
+            mpis.push(reader.mpi()?);
+        }
+        Ok(SignaturePacket {
+            algorithm,
+            hash_algorithm,
+            hashed,
+            mpis,
+        })
+    }
+
+    /// The message actually signed: the data, followed by the hashed part
+    /// of the packet and by the trailer
+    fn signed_message(&self, data: &[u8]) -> Vec<u8> {
+        let mut message = Vec::with_capacity(data.len() + self.hashed.len() + 6);
+        message.extend_from_slice(data);
+        message.extend_from_slice(self.hashed);
+        message.extend_from_slice(&[0x04, 0xff]);
+        message.extend_from_slice(&(self.hashed.len() as u32).to_be_bytes());
+        message
+    }
+
+    fn verify(&self, key: &PublicKey, data: &[u8]) -> Result<()> {
+        if key.algorithm != self.algorithm
+            && !(ALGORITHM_RSA.contains(&key.algorithm) && ALGORITHM_RSA.contains(&self.algorithm))
+        {
+            return Err(SigstoreError::PublicKeyVerificationError);
+        }
+        let message = self.signed_message(data);
+
+        match &key.material {
+            KeyMaterial::Rsa { n, e } => {
+                let signature = self
+                    .mpis
+                    .first()
+                    .ok_or_else(|| pgp_error("missing RSA signature"))?;
+                let public_key =
+                    RsaPublicKey::new(BigUint::from_bytes_be(n), BigUint::from_bytes_be(e))?;
+                let verification_key = match self.hash_algorithm {
+                    HASH_SHA256 => CosignVerificationKey::RSA_PKCS1_SHA256(
+                        pkcs1v15::VerifyingKey::new_with_prefix(public_key),
+                    ),
+                    HASH_SHA384 => CosignVerificationKey::RSA_PKCS1_SHA384(
+                        pkcs1v15::VerifyingKey::new_with_prefix(public_key),
+                    ),
+                    HASH_SHA512 => CosignVerificationKey::RSA_PKCS1_SHA512(
+                        pkcs1v15::VerifyingKey::new_with_prefix(public_key),
+                    ),
+                    _ => return Err(pgp_error("unsupported hash algorithm")),
+                };
+                let signature = left_pad(signature, n.len())?;
+                verification_key.verify_signature(Signature::Raw(&signature), &message)
+            }
+            KeyMaterial::EcdsaP256 { point } => {
+                if self.hash_algorithm != HASH_SHA256 {
+                    return Err(pgp_error("ECDSA P-256 signatures must use SHA-256"));
+                }
+                let raw = self.fixed_size_signature(32)?;
+                let verification_key = CosignVerificationKey::ECDSA_P256_SHA256_ASN1(
+                    ecdsa::VerifyingKey::from_sec1_bytes(point)?,
+                );
+                verification_key.verify_signature_with_format(
+                    Signature::Raw(&raw),
+                    &message,
+                    SignatureFormat::Raw,
+                )
+            }
+            KeyMaterial::Ed25519 { point } => {
+                // the point is prefixed by 0x40, the native encoding
+                let point: &[u8; 32] = point
+                    .strip_prefix(&[0x40])
+                    .and_then(|p| p.try_into().ok())
+                    .ok_or_else(|| pgp_error("invalid Ed25519 key"))?;
+                let verification_key =
+                    CosignVerificationKey::ED25519(ed25519_dalek::VerifyingKey::from_bytes(point)?);
+                // EdDSA signs the digest of the message
+                let digest = match self.hash_algorithm {
+                    HASH_SHA256 => Sha256::digest(&message).to_vec(),
+                    HASH_SHA384 => Sha384::digest(&message).to_vec(),
+                    HASH_SHA512 => Sha512::digest(&message).to_vec(),
+                    _ => return Err(pgp_error("unsupported hash algorithm")),
+                };
+                let raw = self.fixed_size_signature(32)?;
+                verification_key.verify_signature(Signature::Raw(&raw), &digest)
+            }
+            KeyMaterial::Unsupported => Err(pgp_error("unsupported key algorithm")),
+        }
+    }
+
+    /// Concatenate the `r` and `s` values of the signature
+    fn fixed_size_signature(&self, size: usize) -> Result<Vec<u8>> {
+        match self.mpis.as_slice() {
+            [r, s] => Ok([left_pad(r, size)?, left_pad(s, size)?].concat()),
+            _ => Err(pgp_error("the signature must have two values")),
+        }
+    }
+}
+
+fn left_pad(value: &[u8], size: usize) -> Result<Vec<u8>> {
+    if value.len() > size {
+        return Err(pgp_error("value too large"));
+    }
+    let mut padded = vec![0; size - value.len()];
+    padded.extend_from_slice(value);
+    Ok(padded)
+}
+
+/// Verify the detached `signature` of `data`, which must be produced by one
+/// of the `keys`
+pub(crate) fn verify_detached(keys: &[PublicKey], signature: &[u8], data: &[u8]) -> Result<()> {
+    let signature = dearmor(signature)?;
+    let packets = packets(&signature)?;
+    let signature = packets
+        .iter()
+        .find(|p| p.tag == TAG_SIGNATURE)
+        .ok_or_else(|| pgp_error("no signature found"))?;
+    let signature = SignaturePacket::parse(signature.body)?;
+
+    if keys.iter().any(|key| signature.verify(key, data).is_ok()) {
+        Ok(())
+    } else {
+        Err(SigstoreError::PublicKeyVerificationError)
+    }
+}
+
+/// Sequential reader of big endian values
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| pgp_error("unexpected end of data"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// A multiprecision integer, prefixed by its length in bits
+    fn mpi(&mut self) -> Result<&'a [u8]> {
+        let bits = self.u16()? as usize;
+        self.take(bits.div_ceil(8))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::crypto::SigningScheme;
+
+    fn mpi(value: &[u8]) -> Vec<u8> {
+        let value = match value.iter().position(|b| *b != 0) {
+            Some(i) => &value[i..],
+            None => &[],
+        };
+        let bits = match value.first() {
+            Some(first) => value.len() * 8 - first.leading_zeros() as usize,
+            None => 0,
+        };
+        [&(bits as u16).to_be_bytes()[..], value].concat()
+    }
+
+    fn packet(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0xc0 | tag, 0xff];
+        packet.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        packet.extend_from_slice(body);
+        packet
+    }
+
+    /// An Ed25519 key able to produce OpenPGP signatures
+    pub(crate) struct TestKey {
+        signer: crate::crypto::SigStoreSigner,
+        point: Vec<u8>,
+    }
+
+    impl TestKey {
+        pub(crate) fn new() -> Self {
+            let signer = SigningScheme::ED25519.create_signer().unwrap();
+            let der = signer
+                .to_sigstore_keypair()
+                .unwrap()
+                .public_key_to_der()
+                .unwrap();
+            let point = [&[0x40][..], &der[der.len() - 32..]].concat();
+            TestKey { signer, point }
+        }
+
+        /// The armored transferable public key
+        pub(crate) fn armored_public_key(&self) -> String {
+            let mut body = vec![4, 0, 0, 0, 0, ALGORITHM_EDDSA, OID_ED25519.len() as u8];
+            body.extend_from_slice(OID_ED25519);
+            body.extend_from_slice(&mpi(&self.point));
+            let encoded = BASE64_STD_ENGINE.encode(packet(TAG_PUBLIC_KEY, &body));
+            format!(
+                "-----BEGIN PGP PUBLIC KEY BLOCK-----\nVersion: test\n\n{encoded}\n=abcd\n-----END PGP PUBLIC KEY BLOCK-----\n"
+            )
+        }
+
+        /// A binary detached signature of `data`
+        pub(crate) fn sign(&self, data: &[u8]) -> Vec<u8> {
+            let hashed = vec![4, 0, ALGORITHM_EDDSA, HASH_SHA256, 0, 0];
+            let packet_without_signature = SignaturePacket {
+                algorithm: ALGORITHM_EDDSA,
+                hash_algorithm: HASH_SHA256,
+                hashed: &hashed,
+                mpis: Vec::new(),
+            };
+            let digest = Sha256::digest(packet_without_signature.signed_message(data));
+            let signature = self.signer.sign(&digest).unwrap();
+
+            let mut body = hashed;
+            body.extend_from_slice(&[0, 0]);
+            body.extend_from_slice(&digest[..2]);
+            body.extend_from_slice(&mpi(&signature[..32]));
+            body.extend_from_slice(&mpi(&signature[32..]));
+            packet(TAG_SIGNATURE, &body)
+        }
+    }
+
+    #[test]
+    fn verify_ed25519_signature() {
+        let key = TestKey::new();
+        let keys = public_keys(key.armored_public_key().as_bytes()).unwrap();
+        let signature = key.sign(b"package header");
+
+        assert!(verify_detached(&keys, &signature, b"package header").is_ok());
+        assert!(verify_detached(&keys, &signature, b"other header").is_err());
+
+        let other_keys = public_keys(TestKey::new().armored_public_key().as_bytes()).unwrap();
+        assert!(verify_detached(&other_keys, &signature, b"package header").is_err());
+    }
+
+    #[test]
+    fn parse_packet_lengths() {
+        // old format, one byte length
+        let data = [0x88, 0x02, 0xaa, 0xbb, 0xc0 | 6, 192, 0];
+        assert!(packets(&data).is_err());
+        let parsed = packets(&data[..4]).unwrap();
+        assert_eq!(parsed[0].tag, TAG_SIGNATURE);
+        assert_eq!(parsed[0].body, &[0xaa, 0xbb]);
+        // new format, two bytes length: 192 bytes
+        let mut data = vec![0xc0 | 6, 192, 0];
+        data.extend_from_slice(&[0; 192]);
+        assert_eq!(packets(&data).unwrap()[0].body.len(), 192);
+    }
+}
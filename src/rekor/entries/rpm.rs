@@ -0,0 +1,380 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rekor `rpm` entries, recording RPM packages signed with OpenPGP.
+//!
+//! Verifying a package against its entry ensures that:
+//!
+//! * the package is the one recorded by the entry
+//! * the header of the package is signed by the OpenPGP key of the entry
+//! * the header digest stored inside of the signature header, and the
+//!   payload digest stored inside of the header, match the package
+//! * the name, version, release and architecture of the package are the
+//!   recorded ones
+//!
+//! ```rust,no_run
+//! use sigstore::rekor::entries::rpm::verify_entry;
+//! use sigstore::rekor::models::log_entry::LogEntry;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! # let (entry, rpm): (LogEntry, Vec<u8>) = unimplemented!();
+//! let package = verify_entry(&entry.body, &rpm)?;
+//! println!("verified {}-{}-{}.{}", package.name, package.version, package.release, package.arch);
+//! # Ok(())
+//! # }
+//! ```
+
+use sha2::{Digest, Sha256, Sha512};
+
+use super::{check_hash, entry_spec, mismatch, pgp, spec_base64};
+use crate::errors::{Result, SigstoreError};
+use crate::rekor::models::log_entry::Body;
+
+/// The version of the `rpm` entry type understood by this module
+pub const RPM_API_VERSION: &str = "0.0.1";
+
+const LEAD_SIZE: usize = 96;
+const LEAD_MAGIC: [u8; 4] = [0xed, 0xab, 0xee, 0xdb];
+const HEADER_MAGIC: [u8; 3] = [0x8e, 0xad, 0xe8];
+
+const SIGTAG_DSA: u32 = 267;
+const SIGTAG_RSA: u32 = 268;
+const SIGTAG_SHA256: u32 = 273;
+
+const RPMTAG_NAME: u32 = 1000;
+const RPMTAG_VERSION: u32 = 1001;
+const RPMTAG_RELEASE: u32 = 1002;
+const RPMTAG_EPOCH: u32 = 1003;
+const RPMTAG_ARCH: u32 = 1022;
+const RPMTAG_PAYLOADDIGEST: u32 = 5092;
+const RPMTAG_PAYLOADDIGESTALGO: u32 = 5093;
+
+const TYPE_INT32: u32 = 4;
+const TYPE_STRING: u32 = 6;
+const TYPE_BIN: u32 = 7;
+const TYPE_STRING_ARRAY: u32 = 8;
+const TYPE_I18NSTRING: u32 = 9;
+
+/// The digest algorithms of the payload, using the OpenPGP numbering
+const PAYLOAD_DIGEST_SHA256: u32 = 8;
+const PAYLOAD_DIGEST_SHA512: u32 = 10;
+
+/// A verified RPM package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpmPackage {
+    pub name: String,
+    pub epoch: Option<u32>,
+    pub version: String,
+    pub release: String,
+    pub arch: String,
+}
+
+/// Ensure the `rpm` entry `body` records the `rpm` package, then verify the
+/// signature of the package with the OpenPGP key of the entry.
+pub fn verify_entry(body: &Body, rpm: &[u8]) -> Result<RpmPackage> {
+    let spec = entry_spec(body, "rpm")?;
+    check_hash(spec, &["package", "hash"], rpm)?;
+
+    if rpm.len() < LEAD_SIZE || rpm[..4] != LEAD_MAGIC {
+        return Err(rpm_error("not an RPM package"));
+    }
+    let (signature_header, end) = Header::parse(rpm, LEAD_SIZE)?;
+    // the signature header is padded to 8 bytes
+    let header_start = (end + 7) & !7;
+    let (header, payload_start) = Header::parse(rpm, header_start)?;
+    let payload = &rpm[payload_start..];
+
+    let signature = signature_header
+        .bin(SIGTAG_RSA)
+        .or_else(|| signature_header.bin(SIGTAG_DSA))
+        .ok_or_else(|| rpm_error("the package doesn't have a header signature"))?;
+    let keys = pgp::public_keys(&spec_base64(spec, &["publicKey", "content"])?)?;
+    pgp::verify_detached(&keys, signature, header.raw)?;
+
+    if let Some(expected) = signature_header.string(SIGTAG_SHA256) {
+        if !hex::encode(Sha256::digest(header.raw)).eq_ignore_ascii_case(expected) {
+            return Err(rpm_error("the header digest doesn't match"));
+        }
+    }
+
+    // the payload is covered by the signature via its digest
+    let payload_digest = header
+        .string(RPMTAG_PAYLOADDIGEST)
+        .ok_or_else(|| rpm_error("the header doesn't have the payload digest"))?;
+    let actual = match header.int32(RPMTAG_PAYLOADDIGESTALGO) {
+        Some(PAYLOAD_DIGEST_SHA256) => hex::encode(Sha256::digest(payload)),
+        Some(PAYLOAD_DIGEST_SHA512) => hex::encode(Sha512::digest(payload)),
+        _ => return Err(rpm_error("unsupported payload digest algorithm")),
+    };
+    if !actual.eq_ignore_ascii_case(payload_digest) {
+        return Err(rpm_error("the payload digest doesn't match"));
+    }
+
+    let required = |tag| {
+        header
+            .string(tag)
+            .map(str::to_string)
+            .ok_or_else(|| rpm_error(&format!("the header doesn't have tag {tag}")))
+    };
+    let package = RpmPackage {
+        name: required(RPMTAG_NAME)?,
+        epoch: header.int32(RPMTAG_EPOCH),
+        version: required(RPMTAG_VERSION)?,
+        release: required(RPMTAG_RELEASE)?,
+        arch: required(RPMTAG_ARCH)?,
+    };
+
+    // the entry records some of the headers as well
+    let epoch = package.epoch.map(|e| e.to_string());
+    for (key, value) in [
+        ("Name", Some(&package.name)),
+        ("Epoch", epoch.as_ref()),
+        ("Version", Some(&package.version)),
+        ("Release", Some(&package.release)),
+        ("Arch", Some(&package.arch)),
+    ] {
+        if let Some(recorded) = spec.pointer(&format!("/package/headers/{key}")) {
+            if recorded.as_str() != value.map(String::as_str) {
+                return Err(mismatch(format!(
+                    "the {key} header is {recorded}, the package has {}",
+                    value.map(String::as_str).unwrap_or("none")
+                )));
+            }
+        }
+    }
+
+    Ok(package)
+}
+
+fn rpm_error(msg: &str) -> SigstoreError {
+    SigstoreError::RekorEntryMismatchError(format!("invalid RPM package: {msg}"))
+}
+
+struct IndexEntry {
+    tag: u32,
+    kind: u32,
+    offset: usize,
+    count: usize,
+}
+
+/// A header structure, used both by the signature and the main header
+struct Header<'a> {
+    /// The bytes of the header, from its magic to the end of its store
+    raw: &'a [u8],
+    index: Vec<IndexEntry>,
+    store: &'a [u8],
+}
+
+impl<'a> Header<'a> {
+    /// Parse the header starting at `start`, returns the header and the
+    /// position of its end
+    fn parse(data: &'a [u8], start: usize) -> Result<(Self, usize)> {
+        let u32_at = |pos: usize| -> Result<usize> {
+            data.get(pos..pos + 4)
+                .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+                .ok_or_else(|| rpm_error("truncated header"))
+        };
+
+        if data.get(start..start + 3) != Some(&HEADER_MAGIC[..]) {
+            return Err(rpm_error("invalid header magic"));
+        }
+        let entries = u32_at(start + 8)?;
+        let store_size = u32_at(start + 12)?;
+        let index_start = start + 16;
+        let store_start = entries
+            .checked_mul(16)
+            .and_then(|len| len.checked_add(index_start))
+            .ok_or_else(|| rpm_error("invalid header size"))?;
+        let end = store_start
+            .checked_add(store_size)
+            .filter(|end| *end <= data.len())
+            .ok_or_else(|| rpm_error("truncated header"))?;
+
+        let mut index = Vec::with_capacity(entries);
+        for i in 0..entries {
+            let pos = index_start + i * 16;
+            index.push(IndexEntry {
+                tag: u32_at(pos)? as u32,
+                kind: u32_at(pos + 4)? as u32,
+                offset: u32_at(pos + 8)?,
+                count: u32_at(pos + 12)?,
+            });
+        }
+
+        Ok((
+            Header {
+                raw: &data[start..end],
+                index,
+                store: &data[store_start..end],
+            },
+            end,
+        ))
+    }
+
+    fn entry(&self, tag: u32) -> Option<&IndexEntry> {
+        self.index.iter().find(|e| e.tag == tag)
+    }
+
+    /// A binary value
+    fn bin(&self, tag: u32) -> Option<&'a [u8]> {
+        let entry = self.entry(tag).filter(|e| e.kind == TYPE_BIN)?;
+        self.store
+            .get(entry.offset..entry.offset.checked_add(entry.count)?)
+    }
+
+    /// A string value. The first item is returned for arrays.
+    fn string(&self, tag: u32) -> Option<&'a str> {
+        let entry = self
+            .entry(tag)
+            .filter(|e| [TYPE_STRING, TYPE_STRING_ARRAY, TYPE_I18NSTRING].contains(&e.kind))?;
+        let value = self.store.get(entry.offset..)?;
+        let len = value.iter().position(|b| *b == 0)?;
+        std::str::from_utf8(&value[..len]).ok()
+    }
+
+    /// An integer value. The first item is returned for arrays.
+    fn int32(&self, tag: u32) -> Option<u32> {
+        let entry = self.entry(tag).filter(|e| e.kind == TYPE_INT32)?;
+        let b = self.store.get(entry.offset..entry.offset.checked_add(4)?)?;
+        Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rekor::entries::pgp::tests::TestKey;
+    use crate::rekor::models::RpmAllOf;
+    use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+    use serde_json::json;
+
+    fn build_header(entries: &[(u32, u32, Vec<u8>)]) -> Vec<u8> {
+        let mut index = Vec::new();
+        let mut store = Vec::new();
+        for (tag, kind, value) in entries {
+            while *kind == TYPE_INT32 && store.len() % 4 != 0 {
+                store.push(0);
+            }
+            let count = match *kind {
+                TYPE_BIN => value.len(),
+                _ => 1,
+            };
+            for field in [*tag, *kind, store.len() as u32, count as u32] {
+                index.extend_from_slice(&field.to_be_bytes());
+            }
+            store.extend_from_slice(value);
+        }
+        let mut header = vec![0x8e, 0xad, 0xe8, 0x01, 0, 0, 0, 0];
+        header.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        header.extend_from_slice(&(store.len() as u32).to_be_bytes());
+        header.extend_from_slice(&index);
+        header.extend_from_slice(&store);
+        header
+    }
+
+    fn string(value: &str) -> Vec<u8> {
+        [value.as_bytes(), &[0]].concat()
+    }
+
+    fn build_rpm(key: &TestKey, payload: &[u8]) -> Vec<u8> {
+        let header = build_header(&[
+            (RPMTAG_NAME, TYPE_STRING, string("hello")),
+            (RPMTAG_VERSION, TYPE_STRING, string("2.12")),
+            (RPMTAG_RELEASE, TYPE_STRING, string("1.fc38")),
+            (RPMTAG_ARCH, TYPE_STRING, string("x86_64")),
+            (
+                RPMTAG_PAYLOADDIGEST,
+                TYPE_STRING_ARRAY,
+                string(&hex::encode(Sha256::digest(payload))),
+            ),
+            (
+                RPMTAG_PAYLOADDIGESTALGO,
+                TYPE_INT32,
+                PAYLOAD_DIGEST_SHA256.to_be_bytes().to_vec(),
+            ),
+        ]);
+        let signature_header = build_header(&[
+            (SIGTAG_RSA, TYPE_BIN, key.sign(&header)),
+            (
+                SIGTAG_SHA256,
+                TYPE_STRING,
+                string(&hex::encode(Sha256::digest(&header))),
+            ),
+        ]);
+
+        let mut rpm = LEAD_MAGIC.to_vec();
+        rpm.resize(LEAD_SIZE, 0);
+        rpm.extend_from_slice(&signature_header);
+        rpm.resize((rpm.len() + 7) & !7, 0);
+        rpm.extend_from_slice(&header);
+        rpm.extend_from_slice(payload);
+        rpm
+    }
+
+    fn logged_entry(rpm: &[u8], key: &TestKey, name: &str) -> Body {
+        Body::rpm(RpmAllOf::new(
+            RPM_API_VERSION.to_string(),
+            json!({
+                "package": {
+                    "hash": {"algorithm": "sha256", "value": hex::encode(Sha256::digest(rpm))},
+                    "headers": {"Name": name, "Version": "2.12"}
+                },
+                "publicKey": {"content": BASE64_STD_ENGINE.encode(key.armored_public_key())}
+            }),
+        ))
+    }
+
+    #[test]
+    fn verify_signed_package() {
+        let key = TestKey::new();
+        let rpm = build_rpm(&key, b"compressed cpio archive");
+        let body = logged_entry(&rpm, &key, "hello");
+
+        let package = verify_entry(&body, &rpm).unwrap();
+        assert_eq!(
+            package,
+            RpmPackage {
+                name: "hello".to_string(),
+                epoch: None,
+                version: "2.12".to_string(),
+                release: "1.fc38".to_string(),
+                arch: "x86_64".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn reject_tampered_packages() {
+        let key = TestKey::new();
+        let rpm = build_rpm(&key, b"compressed cpio archive");
+
+        // the payload has been replaced, the entry has been forged for it
+        let mut tampered = rpm.clone();
+        let len = tampered.len();
+        tampered[len - 1] ^= 0xff;
+        let body = logged_entry(&tampered, &key, "hello");
+        assert!(verify_entry(&body, &tampered).is_err());
+
+        // the package is signed by another key
+        let other_key = TestKey::new();
+        let body = logged_entry(&rpm, &other_key, "hello");
+        assert!(verify_entry(&body, &rpm).is_err());
+
+        // the recorded headers don't match
+        let body = logged_entry(&rpm, &key, "goodbye");
+        let error = verify_entry(&body, &rpm).expect_err("wrong name accepted");
+        assert!(matches!(error, SigstoreError::RekorEntryMismatchError(_)));
+    }
+}
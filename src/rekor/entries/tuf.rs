@@ -0,0 +1,348 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rekor `tuf` entries, recording the metadata of a
+//! [TUF](https://theupdateframework.io/) repository together with the root
+//! metadata trusted to sign it.
+//!
+//! Repository operators record their metadata with [`proposed_entry`], then
+//! clients ensure the metadata they fetched is the logged one with
+//! [`verify_entry`]:
+//!
+//! ```rust,no_run
+//! use sigstore::rekor::entries::tuf::verify_entry;
+//! use sigstore::rekor::models::log_entry::LogEntry;
+//!
+//! # fn main() -> sigstore::errors::Result<()> {
+//! # let (entry, targets_json): (LogEntry, Vec<u8>) = unimplemented!();
+//! let metadata = verify_entry(&entry.body, &targets_json)?;
+//! println!("{} version {} has been logged", metadata.role, metadata.version);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Only the top-level roles, which are listed by the root metadata, can be
+//! verified.
+
+use olpc_cjson::CanonicalFormatter;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+
+use super::{entry_spec, mismatch};
+use crate::crypto::{CosignVerificationKey, Signature, SigningScheme};
+use crate::errors::{Result, SigstoreError};
+use crate::rekor::models::log_entry::Body;
+use crate::rekor::models::ProposedEntry;
+
+/// The version of the `tuf` entry type produced and understood by this
+/// module
+pub const TUF_API_VERSION: &str = "0.0.1";
+
+/// The TUF metadata recorded by an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TufMetadata {
+    /// The role of the metadata, e.g. `root` or `targets`
+    pub role: String,
+    pub version: u64,
+    pub expires: String,
+}
+
+/// Build the entry to be submitted to Rekor to record the signed
+/// `metadata`, verified by the `root` metadata.
+pub fn proposed_entry(metadata: &Value, root: &Value) -> ProposedEntry {
+    ProposedEntry::Tuf {
+        api_version: TUF_API_VERSION.to_string(),
+        spec: json!({
+            "metadata": {"content": metadata},
+            "root": {"content": root},
+        }),
+    }
+}
+
+/// Ensure the `tuf` entry `body` records the JSON `metadata`, then verify
+/// the signatures of the metadata with the keys of the recorded root.
+///
+/// The metadata are compared once canonicalized, as done by TUF when
+/// signing them.
+pub fn verify_entry(body: &Body, metadata: &[u8]) -> Result<TufMetadata> {
+    let spec = entry_spec(body, "tuf")?;
+    let fetched: Value = serde_json::from_slice(metadata)?;
+    let recorded = spec
+        .pointer("/metadata/content")
+        .ok_or_else(|| mismatch("the entry doesn't have metadata".to_string()))?;
+    if canonical_json(&fetched)? != canonical_json(recorded)? {
+        return Err(mismatch("the metadata is not the recorded one".to_string()));
+    }
+
+    let root = spec
+        .pointer("/root/content")
+        .ok_or_else(|| mismatch("the entry doesn't have root metadata".to_string()))?;
+    verify_signatures(&fetched, root)?;
+
+    Ok(TufMetadata {
+        role: signed_str(&fetched, "_type")?.to_string(),
+        version: fetched
+            .pointer("/signed/version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| tuf_error("the metadata doesn't have a version"))?,
+        expires: signed_str(&fetched, "expires")?.to_string(),
+    })
+}
+
+fn tuf_error(msg: &str) -> SigstoreError {
+    SigstoreError::RekorEntryMismatchError(format!("invalid TUF metadata: {msg}"))
+}
+
+fn signed_str<'a>(metadata: &'a Value, field: &str) -> Result<&'a str> {
+    metadata
+        .pointer(&format!("/signed/{field}"))
+        .and_then(Value::as_str)
+        .ok_or_else(|| tuf_error(&format!("the metadata doesn't have {field}")))
+}
+
+fn canonical_json(value: &Value) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let mut ser = serde_json::Serializer::with_formatter(&mut buf, CanonicalFormatter::new());
+    value.serialize(&mut ser)?;
+    Ok(buf)
+}
+
+/// Ensure the threshold of the role of `metadata` is reached, using the keys
+/// of the `root` metadata
+fn verify_signatures(metadata: &Value, root: &Value) -> Result<()> {
+    let role = signed_str(metadata, "_type")?;
+    let role_keys = root
+        .pointer(&format!("/signed/roles/{role}"))
+        .ok_or_else(|| tuf_error(&format!("the root doesn't have the {role} role")))?;
+    let keyids: Vec<&str> = role_keys
+        .get("keyids")
+        .and_then(Value::as_array)
+        .map(|ids| ids.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let threshold = role_keys
+        .get("threshold")
+        .and_then(Value::as_u64)
+        .filter(|t| *t > 0)
+        .ok_or_else(|| tuf_error(&format!("invalid threshold for the {role} role")))?;
+
+    let message = canonical_json(&metadata["signed"])?;
+    let mut verified = BTreeSet::new();
+    for signature in metadata
+        .get("signatures")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let keyid = match signature.get("keyid").and_then(Value::as_str) {
+            Some(keyid) if keyids.contains(&keyid) && !verified.contains(keyid) => keyid,
+            _ => continue,
+        };
+        // unknown keys and unsupported schemes don't count towards the
+        // threshold
+        let key = match root
+            .pointer(&format!("/signed/keys/{keyid}"))
+            .map(verification_key)
+        {
+            Some(Ok(key)) => key,
+            _ => continue,
+        };
+        let sig = signature
+            .get("sig")
+            .and_then(Value::as_str)
+            .and_then(|sig| hex::decode(sig).ok())
+            .unwrap_or_default();
+        if key.verify_signature(Signature::Raw(&sig), &message).is_ok() {
+            verified.insert(keyid);
+        }
+    }
+
+    if (verified.len() as u64) < threshold {
+        return Err(tuf_error(&format!(
+            "{} valid signatures for the {role} role, the threshold is {threshold}",
+            verified.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Build a verification key from a TUF key
+fn verification_key(key: &Value) -> Result<CosignVerificationKey> {
+    let public = key
+        .pointer("/keyval/public")
+        .and_then(Value::as_str)
+        .ok_or_else(|| tuf_error("the key doesn't have a public value"))?;
+    match key.get("scheme").and_then(Value::as_str) {
+        Some("ed25519") => {
+            let point: [u8; 32] = hex::decode(public)
+                .ok()
+                .and_then(|p| p.as_slice().try_into().ok())
+                .ok_or_else(|| tuf_error("invalid ed25519 key"))?;
+            Ok(CosignVerificationKey::ED25519(
+                ed25519_dalek::VerifyingKey::from_bytes(&point)?,
+            ))
+        }
+        Some("ecdsa-sha2-nistp256") => CosignVerificationKey::from_pem(
+            public.as_bytes(),
+            &SigningScheme::ECDSA_P256_SHA256_ASN1,
+        ),
+        // the key size is only used when generating keys
+        Some("rsassa-pss-sha256") => {
+            CosignVerificationKey::from_pem(public.as_bytes(), &SigningScheme::RSA_PSS_SHA256(0))
+        }
+        scheme => Err(tuf_error(&format!("unsupported key scheme {scheme:?}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::SigStoreSigner;
+    use crate::rekor::models::TufAllOf;
+
+    struct Keys {
+        ed25519: SigStoreSigner,
+        ecdsa: SigStoreSigner,
+    }
+
+    impl Keys {
+        fn new() -> Self {
+            Keys {
+                ed25519: SigningScheme::ED25519.create_signer().unwrap(),
+                ecdsa: SigningScheme::ECDSA_P256_SHA256_ASN1
+                    .create_signer()
+                    .unwrap(),
+            }
+        }
+
+        fn root(&self) -> Value {
+            let ed25519_der = self
+                .ed25519
+                .to_sigstore_keypair()
+                .unwrap()
+                .public_key_to_der()
+                .unwrap();
+            let ecdsa_pem = self
+                .ecdsa
+                .to_sigstore_keypair()
+                .unwrap()
+                .public_key_to_pem()
+                .unwrap();
+            let signed = json!({
+                "_type": "root",
+                "spec_version": "1.0",
+                "version": 1,
+                "expires": "2030-01-01T00:00:00Z",
+                "consistent_snapshot": true,
+                "keys": {
+                    "aaaa": {"keytype": "ed25519", "scheme": "ed25519",
+                             "keyval": {"public": hex::encode(&ed25519_der[ed25519_der.len() - 32..])}},
+                    "bbbb": {"keytype": "ecdsa-sha2-nistp256", "scheme": "ecdsa-sha2-nistp256",
+                             "keyval": {"public": ecdsa_pem}},
+                },
+                "roles": {
+                    "root": {"keyids": ["aaaa"], "threshold": 1},
+                    "targets": {"keyids": ["aaaa", "bbbb"], "threshold": 2},
+                },
+            });
+            self.sign(signed, &[("aaaa", &self.ed25519)])
+        }
+
+        fn sign(&self, signed: Value, signers: &[(&str, &SigStoreSigner)]) -> Value {
+            let message = canonical_json(&signed).unwrap();
+            let signatures: Vec<Value> = signers
+                .iter()
+                .map(|(keyid, signer)| {
+                    json!({"keyid": keyid, "sig": hex::encode(signer.sign(&message).unwrap())})
+                })
+                .collect();
+            json!({"signed": signed, "signatures": signatures})
+        }
+    }
+
+    fn targets(version: u64) -> Value {
+        json!({
+            "_type": "targets",
+            "spec_version": "1.0",
+            "version": version,
+            "expires": "2030-01-01T00:00:00Z",
+            "targets": {"app.tar.gz": {"length": 4, "hashes": {"sha256": "abcd"}}},
+        })
+    }
+
+    fn logged_entry(metadata: &Value, root: &Value) -> Body {
+        match proposed_entry(metadata, root) {
+            ProposedEntry::Tuf { api_version, spec } => Body::tuf(TufAllOf::new(api_version, spec)),
+            _ => panic!("not a tuf entry"),
+        }
+    }
+
+    #[test]
+    fn verify_logged_metadata() {
+        let keys = Keys::new();
+        let root = keys.root();
+        let metadata = keys.sign(
+            targets(3),
+            &[("aaaa", &keys.ed25519), ("bbbb", &keys.ecdsa)],
+        );
+        let body = logged_entry(&metadata, &root);
+
+        // the fetched metadata doesn't need to be canonicalized
+        let fetched = serde_json::to_vec_pretty(&metadata).unwrap();
+        assert_eq!(
+            verify_entry(&body, &fetched).unwrap(),
+            TufMetadata {
+                role: "targets".to_string(),
+                version: 3,
+                expires: "2030-01-01T00:00:00Z".to_string(),
+            }
+        );
+
+        let root_body = logged_entry(&root, &root);
+        let verified = verify_entry(&root_body, &serde_json::to_vec(&root).unwrap()).unwrap();
+        assert_eq!(verified.role, "root");
+
+        let other = keys.sign(
+            targets(4),
+            &[("aaaa", &keys.ed25519), ("bbbb", &keys.ecdsa)],
+        );
+        let error = verify_entry(&body, &serde_json::to_vec(&other).unwrap())
+            .expect_err("other metadata accepted");
+        assert!(matches!(error, SigstoreError::RekorEntryMismatchError(_)));
+    }
+
+    #[test]
+    fn reject_metadata_below_threshold() {
+        let keys = Keys::new();
+        let root = keys.root();
+
+        // the same key twice doesn't reach the threshold
+        let metadata = keys.sign(
+            targets(3),
+            &[("aaaa", &keys.ed25519), ("aaaa", &keys.ed25519)],
+        );
+        let body = logged_entry(&metadata, &root);
+        assert!(verify_entry(&body, &serde_json::to_vec(&metadata).unwrap()).is_err());
+
+        // a key signing on behalf of another one
+        let metadata = keys.sign(
+            targets(3),
+            &[("aaaa", &keys.ed25519), ("bbbb", &keys.ed25519)],
+        );
+        let body = logged_entry(&metadata, &root);
+        assert!(verify_entry(&body, &serde_json::to_vec(&metadata).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,325 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of the Merkle tree proofs produced by Rekor.
+//!
+//! The algorithms follow [RFC 9162](https://www.rfc-editor.org/rfc/rfc9162#section-2.1).
+
+use std::convert::TryFrom;
+
+use super::checkpoint::Checkpoint;
+use super::models::log_entry::LogEntry;
+use crate::errors::{Result, SigstoreError};
+
+pub use crate::portable::merkle::{hash_children, hash_leaf};
+
+/// Decode the hex encoded hashes returned by the Rekor API
+pub fn decode_hashes(hashes: &[String]) -> Result<Vec<Vec<u8>>> {
+    hashes
+        .iter()
+        .map(|h| {
+            hex::decode(h).map_err(|e| {
+                SigstoreError::RekorConsistencyProofError(format!("invalid hash {h}: {e}"))
+            })
+        })
+        .collect()
+}
+
+/// Verify that the tree of size `new_size` with root `new_root` is an
+/// append-only extension of the tree of size `old_size` with root `old_root`.
+///
+/// `proof` is the consistency proof returned by Rekor, ordered as described
+/// by RFC 9162.
+pub fn verify_consistency(
+    old_size: u64,
+    new_size: u64,
+    old_root: &[u8],
+    new_root: &[u8],
+    proof: &[Vec<u8>],
+) -> Result<()> {
+    crate::portable::merkle::verify_consistency(old_size, new_size, old_root, new_root, proof)
+        .map_err(|e| SigstoreError::RekorConsistencyProofError(e.to_string()))
+}
+
+/// Verify that the leaf with hash `leaf_hash`, at `index`, is part of the
+/// tree of size `tree_size` with root `root`.
+///
+/// `proof` is the inclusion proof returned by Rekor, ordered from the leaf
+/// to the root as described by RFC 9162.
+pub fn verify_inclusion(
+    index: u64,
+    tree_size: u64,
+    leaf_hash: &[u8],
+    root: &[u8],
+    proof: &[Vec<u8>],
+) -> Result<()> {
+    crate::portable::merkle::verify_inclusion(index, tree_size, leaf_hash, root, proof)
+        .map_err(|e| SigstoreError::RekorInclusionProofError(e.to_string()))
+}
+
+/// Verify the inclusion proof returned by Rekor together with `entry`.
+///
+/// **Note well:** the root of the proof is taken from the response of Rekor,
+/// use [`verify_log_entry_inclusion_at`] to check it against a verified
+/// checkpoint.
+pub fn verify_log_entry_inclusion(entry: &LogEntry) -> Result<()> {
+    let err = |msg: String| SigstoreError::RekorInclusionProofError(msg);
+    let proof = entry
+        .verification
+        .inclusion_proof
+        .as_ref()
+        .ok_or_else(|| err("the entry doesn't have an inclusion proof".to_string()))?;
+
+    let index = u64::try_from(proof.log_index)
+        .map_err(|_| err(format!("invalid log index {}", proof.log_index)))?;
+    let tree_size = u64::try_from(proof.tree_size)
+        .map_err(|_| err(format!("invalid tree size {}", proof.tree_size)))?;
+    let root = hex::decode(&proof.root_hash)
+        .map_err(|e| err(format!("invalid root hash {}: {e}", proof.root_hash)))?;
+    let hashes = proof
+        .hashes
+        .iter()
+        .map(|h| hex::decode(h).map_err(|e| err(format!("invalid hash {h}: {e}"))))
+        .collect::<Result<Vec<_>>>()?;
+    if entry.raw_body.is_empty() {
+        return Err(err("the raw body of the entry is missing".to_string()));
+    }
+
+    verify_inclusion(
+        index,
+        tree_size,
+        &hash_leaf(&entry.raw_body),
+        &root,
+        &hashes,
+    )
+}
+
+/// Verify the inclusion proof of `entry`, ensuring the proof is about the
+/// tree described by `checkpoint`.
+///
+/// The signature of the checkpoint must have been verified already.
+pub fn verify_log_entry_inclusion_at(entry: &LogEntry, checkpoint: &Checkpoint) -> Result<()> {
+    let proof = entry.verification.inclusion_proof.as_ref().ok_or_else(|| {
+        SigstoreError::RekorInclusionProofError(
+            "the entry doesn't have an inclusion proof".to_string(),
+        )
+    })?;
+    if u64::try_from(proof.tree_size) != Ok(checkpoint.size)
+        || hex::encode(&checkpoint.hash) != proof.root_hash.to_lowercase()
+    {
+        return Err(SigstoreError::RekorInclusionProofError(format!(
+            "the proof is not about the tree of size {} of the checkpoint",
+            checkpoint.size
+        )));
+    }
+    verify_log_entry_inclusion(entry)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    fn largest_power_of_two_smaller_than(n: usize) -> usize {
+        let mut k = 1;
+        while k << 1 < n {
+            k <<= 1;
+        }
+        k
+    }
+
+    /// Compute the Merkle Tree Hash of the given leaves, as defined by RFC 9162
+    pub(crate) fn root(leaves: &[Vec<u8>]) -> Vec<u8> {
+        match leaves.len() {
+            0 => Sha256::digest([]).to_vec(),
+            1 => hash_leaf(&leaves[0]),
+            n => {
+                let k = largest_power_of_two_smaller_than(n);
+                hash_children(&root(&leaves[..k]), &root(&leaves[k..]))
+            }
+        }
+    }
+
+    fn subproof(m: usize, leaves: &[Vec<u8>], complete: bool) -> Vec<Vec<u8>> {
+        let n = leaves.len();
+        if m == n {
+            return if complete { vec![] } else { vec![root(leaves)] };
+        }
+        let k = largest_power_of_two_smaller_than(n);
+        if m <= k {
+            let mut proof = subproof(m, &leaves[..k], complete);
+            proof.push(root(&leaves[k..]));
+            proof
+        } else {
+            let mut proof = subproof(m - k, &leaves[k..], false);
+            proof.push(root(&leaves[..k]));
+            proof
+        }
+    }
+
+    /// Generate a consistency proof between the first `m` leaves and all the leaves
+    pub(crate) fn consistency_proof(m: usize, leaves: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        subproof(m, leaves, true)
+    }
+
+    /// Generate the inclusion proof of the leaf at `index`
+    pub(crate) fn inclusion_proof(index: usize, leaves: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        let n = leaves.len();
+        if n == 1 {
+            return vec![];
+        }
+        let k = largest_power_of_two_smaller_than(n);
+        if index < k {
+            let mut proof = inclusion_proof(index, &leaves[..k]);
+            proof.push(root(&leaves[k..]));
+            proof
+        } else {
+            let mut proof = inclusion_proof(index - k, &leaves[k..]);
+            proof.push(root(&leaves[..k]));
+            proof
+        }
+    }
+
+    fn leaves(n: usize) -> Vec<Vec<u8>> {
+        (0..n).map(|i| format!("entry {i}").into_bytes()).collect()
+    }
+
+    #[test]
+    fn consistency_proofs_are_verified() {
+        let all = leaves(13);
+        for new_size in 1..=all.len() {
+            for old_size in 1..=new_size {
+                let proof = consistency_proof(old_size, &all[..new_size]);
+                let old_root = root(&all[..old_size]);
+                let new_root = root(&all[..new_size]);
+                assert!(
+                    verify_consistency(
+                        old_size as u64,
+                        new_size as u64,
+                        &old_root,
+                        &new_root,
+                        &proof
+                    )
+                    .is_ok(),
+                    "consistency between {} and {} failed",
+                    old_size,
+                    new_size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn inclusion_proofs_are_verified() {
+        let all = leaves(13);
+        for size in 1..=all.len() {
+            let tree_root = root(&all[..size]);
+            for index in 0..size {
+                let proof = inclusion_proof(index, &all[..size]);
+                assert!(
+                    verify_inclusion(
+                        index as u64,
+                        size as u64,
+                        &hash_leaf(&all[index]),
+                        &tree_root,
+                        &proof
+                    )
+                    .is_ok(),
+                    "inclusion of {} in {} failed",
+                    index,
+                    size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn consistency_proof_detects_forks() {
+        let all = leaves(7);
+        let mut forked = leaves(7);
+        forked[2] = b"tampered".to_vec();
+
+        let proof = consistency_proof(4, &all);
+        let forked_root = root(&forked[..4]);
+        let new_root = root(&all);
+
+        assert!(verify_consistency(4, 7, &forked_root, &new_root, &proof).is_err());
+        assert!(verify_consistency(7, 4, &new_root, &forked_root, &proof).is_err());
+        assert!(verify_consistency(4, 4, &forked_root, &root(&all[..4]), &[]).is_err());
+    }
+
+    #[test]
+    fn log_entry_inclusion_is_verified() {
+        use crate::rekor::models::log_entry::{Body, InclusionProof, Verification};
+
+        // Not canonical on purpose: the leaf is the hash of the body as
+        // returned by Rekor, not of its re-serialization
+        let raw_body = br#"{"kind": "hashedrekord", "apiVersion": "0.0.1",
+            "spec": {"data": {"hash": {"algorithm": "sha256", "value": "ab"}}}}"#
+            .to_vec();
+        let mut entry = LogEntry {
+            body: Body::hashedrekord(crate::rekor::models::HashedrekordAllOf::new(
+                "0.0.1".to_string(),
+                serde_json::json!({"data": {"hash": {"algorithm": "sha256", "value": "ab"}}}),
+            )),
+            raw_body: raw_body.clone(),
+            ..Default::default()
+        };
+        let mut all = leaves(2);
+        all.push(raw_body);
+        let tree_root = root(&all);
+        let proof = InclusionProof {
+            hashes: vec![hex::encode(root(&all[..2]))],
+            log_index: 2,
+            root_hash: hex::encode(&tree_root),
+            tree_size: 3,
+        };
+        entry.verification = Verification {
+            inclusion_proof: Some(proof.clone()),
+            signed_entry_timestamp: String::new(),
+        };
+        assert!(verify_log_entry_inclusion(&entry).is_ok());
+
+        let checkpoint = Checkpoint {
+            origin: "rekor.sigstore.dev".to_string(),
+            size: 3,
+            hash: tree_root,
+            other_content: vec![],
+            signatures: vec![],
+            signed_note: String::new(),
+        };
+        assert!(verify_log_entry_inclusion_at(&entry, &checkpoint).is_ok());
+        let other_checkpoint = Checkpoint {
+            size: 4,
+            ..checkpoint
+        };
+        assert!(verify_log_entry_inclusion_at(&entry, &other_checkpoint).is_err());
+
+        let mut without_raw_body = entry.clone();
+        without_raw_body.raw_body.clear();
+        assert!(verify_log_entry_inclusion(&without_raw_body).is_err());
+
+        entry.verification.inclusion_proof = Some(InclusionProof {
+            log_index: 1,
+            ..proof
+        });
+        assert!(matches!(
+            verify_log_entry_inclusion(&entry),
+            Err(SigstoreError::RekorInclusionProofError(_))
+        ));
+        entry.verification.inclusion_proof = None;
+        assert!(verify_log_entry_inclusion(&entry).is_err());
+    }
+}
@@ -87,5 +87,94 @@
 //!
 
 pub mod apis;
+pub mod auditor;
+pub mod checkpoint;
+pub mod entries;
+pub mod merkle;
 pub mod models;
+pub mod public_key;
+#[cfg(feature = "rekor-sqlite")]
+pub mod sqlite_export;
 type TreeSize = i64;
+
+use crate::errors::{Result, SigstoreError};
+use checkpoint::Checkpoint;
+use models::ConsistencyProof;
+
+/// Verify that the tree described by the `new_sth` checkpoint is an
+/// append-only extension of the one described by `old_sth`, detecting
+/// truncations and forks of the log.
+///
+/// `proof` is the consistency proof returned by Rekor between the sizes of
+/// the two checkpoints. The signatures of the checkpoints must have been
+/// verified already.
+pub fn verify_consistency(
+    old_sth: &Checkpoint,
+    new_sth: &Checkpoint,
+    proof: &ConsistencyProof,
+) -> Result<()> {
+    if new_sth.size > old_sth.size && hex::encode(&new_sth.hash) != proof.root_hash.to_lowercase() {
+        return Err(SigstoreError::RekorConsistencyProofError(format!(
+            "the proof is not about the tree of size {}",
+            new_sth.size
+        )));
+    }
+    auditor::verify_checkpoint_transition(Some(old_sth), new_sth, Some(proof))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rekor::merkle::tests::{consistency_proof, root};
+
+    fn checkpoint(leaves: &[Vec<u8>]) -> Checkpoint {
+        Checkpoint {
+            origin: "rekor.sigstore.dev - 2605736670972794746".to_string(),
+            size: leaves.len() as u64,
+            hash: root(leaves),
+            other_content: vec![],
+            signatures: vec![],
+            signed_note: String::new(),
+        }
+    }
+
+    #[test]
+    fn verify_consistency_between_checkpoints() {
+        let leaves: Vec<Vec<u8>> = (0..7).map(|i| vec![i]).collect();
+        let old_sth = checkpoint(&leaves[..3]);
+        let new_sth = checkpoint(&leaves);
+        let proof = ConsistencyProof::new(
+            hex::encode(&new_sth.hash),
+            consistency_proof(3, &leaves)
+                .iter()
+                .map(hex::encode)
+                .collect(),
+        );
+        assert!(verify_consistency(&old_sth, &new_sth, &proof).is_ok());
+
+        // a fork of the log
+        let mut forked = leaves.clone();
+        forked[1] = vec![42];
+        let forked_sth = checkpoint(&forked);
+        let forked_proof = ConsistencyProof::new(
+            hex::encode(&forked_sth.hash),
+            consistency_proof(3, &forked)
+                .iter()
+                .map(hex::encode)
+                .collect(),
+        );
+        assert!(verify_consistency(&old_sth, &forked_sth, &forked_proof).is_err());
+
+        // a proof about another tree
+        assert!(matches!(
+            verify_consistency(&old_sth, &forked_sth, &proof),
+            Err(SigstoreError::RekorConsistencyProofError(_))
+        ));
+
+        // a truncation of the log
+        assert!(matches!(
+            verify_consistency(&new_sth, &old_sth, &proof),
+            Err(SigstoreError::RekorLogRollbackError { .. })
+        ));
+    }
+}
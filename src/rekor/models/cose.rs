@@ -0,0 +1,34 @@
+/*
+ * Rekor
+ *
+ * Rekor is a cryptographically secure, immutable transparency log for signed software releases.
+ *
+ * The version of the OpenAPI document: 0.0.1
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use serde::{Deserialize, Serialize};
+
+/// Cose : COSE object
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Cose {
+    #[serde(rename = "kind")]
+    pub kind: String,
+    #[serde(rename = "apiVersion")]
+    pub api_version: String,
+    #[serde(rename = "spec")]
+    pub spec: serde_json::Value,
+}
+
+impl Cose {
+    /// COSE object
+    pub fn new(kind: String, api_version: String, spec: serde_json::Value) -> Cose {
+        Cose {
+            kind,
+            api_version,
+            spec,
+        }
+    }
+}
@@ -0,0 +1,100 @@
+/*
+ * Rekor
+ *
+ * Rekor is a cryptographically secure, immutable transparency log for signed software releases.
+ *
+ * The version of the OpenAPI document: 0.0.1
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use serde::{Deserialize, Serialize};
+
+use super::hashedrekord::Hash;
+use super::proposed_entry::EntrySpec;
+use crate::dsse::Envelope;
+use crate::errors::Result;
+
+/// Dsse : DSSE envelope
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Dsse {
+    #[serde(rename = "kind")]
+    pub kind: String,
+    #[serde(rename = "apiVersion")]
+    pub api_version: String,
+    #[serde(rename = "spec")]
+    pub spec: serde_json::Value,
+}
+
+impl Dsse {
+    /// DSSE envelope
+    pub fn new(kind: String, api_version: String, spec: serde_json::Value) -> Dsse {
+        Dsse {
+            kind,
+            api_version,
+            spec,
+        }
+    }
+}
+
+/// The `spec` of the `dsse` entries: a DSSE envelope, with any payload
+/// type. The envelope is proposed together with the keys verifying it,
+/// Rekor records its hashes and its signatures.
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proposed_content: Option<ProposedContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub envelope_hash: Option<Hash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload_hash: Option<Hash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signatures: Option<Vec<EnvelopeSignature>>,
+}
+
+impl Spec {
+    /// The spec of a proposed entry
+    ///
+    /// * `envelope`: the JSON encoded DSSE envelope
+    /// * `verifiers`: the base64 encoded PEM public keys, or certificates,
+    ///   verifying the signatures of the envelope
+    pub fn new(envelope: String, verifiers: Vec<String>) -> Spec {
+        Spec {
+            proposed_content: Some(ProposedContent {
+                envelope,
+                verifiers,
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// The spec of a proposed entry recording `envelope`, whose signatures
+    /// are verified by `verifiers`
+    pub fn from_envelope(envelope: &Envelope, verifiers: Vec<String>) -> Result<Spec> {
+        Ok(Spec::new(serde_json::to_string(envelope)?, verifiers))
+    }
+}
+
+impl EntrySpec for Spec {
+    const KIND: &'static str = "dsse";
+    const API_VERSION: &'static str = "0.0.1";
+}
+
+/// The envelope submitted to Rekor, never part of the recorded entries
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProposedContent {
+    pub envelope: String,
+    pub verifiers: Vec<String>,
+}
+
+/// A signature of the envelope, with the base64 encoded key or certificate
+/// that verified it
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvelopeSignature {
+    pub signature: String,
+    pub verifier: String,
+}
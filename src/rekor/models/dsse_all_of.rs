@@ -0,0 +1,25 @@
+/*
+ * Rekor
+ *
+ * Rekor is a cryptographically secure, immutable transparency log for signed software releases.
+ *
+ * The version of the OpenAPI document: 0.0.1
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct DsseAllOf {
+    #[serde(rename = "apiVersion")]
+    pub api_version: String,
+    #[serde(rename = "spec")]
+    pub spec: serde_json::Value,
+}
+
+impl DsseAllOf {
+    pub fn new(api_version: String, spec: serde_json::Value) -> DsseAllOf {
+        DsseAllOf { api_version, spec }
+    }
+}
@@ -11,6 +11,7 @@
 use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
 use serde::{Deserialize, Serialize};
 
+use super::proposed_entry::EntrySpec;
 use crate::errors::SigstoreError;
 
 /// Hashedrekord : Hashed Rekord object
@@ -51,6 +52,11 @@ impl Spec {
     }
 }
 
+impl EntrySpec for Spec {
+    const KIND: &'static str = "hashedrekord";
+    const API_VERSION: &'static str = "0.0.1";
+}
+
 /// Stores the signature format, signature of the artifact and the PublicKey struct
 #[derive(Default, Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
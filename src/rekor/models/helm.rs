@@ -10,6 +10,9 @@
 
 use serde::{Deserialize, Serialize};
 
+use super::hashedrekord::{Hash, PublicKey};
+use super::proposed_entry::EntrySpec;
+
 /// Helm : Helm chart
 
 #[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
@@ -32,3 +35,61 @@ impl Helm {
         }
     }
 }
+
+/// The `spec` of the `helm` entries: a Helm chart signed by its provenance
+/// file
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spec {
+    /// The PGP public key of the signer
+    pub public_key: PublicKey,
+    pub chart: Chart,
+}
+
+impl Spec {
+    pub fn new(public_key: PublicKey, chart: Chart) -> Spec {
+        Spec { public_key, chart }
+    }
+}
+
+impl EntrySpec for Spec {
+    const KIND: &'static str = "helm";
+    const API_VERSION: &'static str = "0.0.1";
+}
+
+/// The chart, identified by the hash recorded by Rekor, and its provenance
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Chart {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<Hash>,
+    pub provenance: Provenance,
+}
+
+/// The provenance file of the chart: its base64 encoded content, or the
+/// signature extracted from it by Rekor
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Provenance {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ProvenanceSignature>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+impl Provenance {
+    /// The provenance file itself, `content` is base64 encoded
+    pub fn from_content(content: String) -> Provenance {
+        Provenance {
+            signature: None,
+            content: Some(content),
+        }
+    }
+}
+
+/// The base64 encoded signature of the provenance file
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvenanceSignature {
+    pub content: String,
+}
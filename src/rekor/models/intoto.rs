@@ -10,6 +10,9 @@
 
 use serde::{Deserialize, Serialize};
 
+use super::hashedrekord::Hash;
+use super::proposed_entry::EntrySpec;
+
 /// Intoto : Intoto object
 
 #[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
@@ -32,3 +35,53 @@ impl Intoto {
         }
     }
 }
+
+/// The `spec` of the `intoto` entries: an in-toto attestation wrapped inside
+/// of a DSSE envelope. Rekor stores only the hashes of the envelope and of
+/// its payload.
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spec {
+    pub content: Content,
+    /// The base64 encoded public key, or certificate, of the signer
+    pub public_key: String,
+}
+
+impl Spec {
+    pub fn new(content: Content, public_key: String) -> Spec {
+        Spec {
+            content,
+            public_key,
+        }
+    }
+}
+
+impl EntrySpec for Spec {
+    const KIND: &'static str = "intoto";
+    const API_VERSION: &'static str = "0.0.1";
+}
+
+/// The envelope, when proposing an entry, and the hashes recorded by Rekor
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Content {
+    /// The JSON encoded DSSE envelope
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub envelope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<Hash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload_hash: Option<Hash>,
+}
+
+impl Content {
+    /// The content of a proposed entry, `envelope` is the JSON encoded DSSE
+    /// envelope
+    pub fn from_envelope(envelope: String) -> Content {
+        Content {
+            envelope: Some(envelope),
+            hash: None,
+            payload_hash: None,
+        }
+    }
+}
@@ -11,6 +11,9 @@
 /// Jar : Java Archive (JAR)
 use serde::{Deserialize, Serialize};
 
+use super::hashedrekord::{Hash, PublicKey};
+use super::proposed_entry::EntrySpec;
+
 #[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub struct Jar {
     #[serde(rename = "kind")]
@@ -31,3 +34,56 @@ impl Jar {
         }
     }
 }
+
+/// The `spec` of the `jar` entries: a signed Java archive
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spec {
+    /// The signature extracted from the archive by Rekor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<Signature>,
+    pub archive: Archive,
+}
+
+impl Spec {
+    pub fn new(archive: Archive) -> Spec {
+        Spec {
+            signature: None,
+            archive,
+        }
+    }
+}
+
+impl EntrySpec for Spec {
+    const KIND: &'static str = "jar";
+    const API_VERSION: &'static str = "0.0.1";
+}
+
+/// The base64 encoded PKCS7 signature of the archive, together with the
+/// certificate of the signer
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Signature {
+    pub content: String,
+    pub public_key: PublicKey,
+}
+
+/// The archive: its hash, or its base64 encoded content
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Archive {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<Hash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+impl Archive {
+    /// The archive itself, `content` is base64 encoded
+    pub fn from_content(content: String) -> Archive {
+        Archive {
+            hash: None,
+            content: Some(content),
+        }
+    }
+}
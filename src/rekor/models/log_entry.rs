@@ -1,15 +1,17 @@
 use crate::errors::SigstoreError;
 use crate::rekor::TreeSize;
 use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use olpc_cjson::CanonicalFormatter;
 
+use serde::de::{DeserializeOwned, Error as _};
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Error, Value};
+use serde_json::{Error, Value};
 use std::collections::HashMap;
 use std::str::FromStr;
 
 use super::{
-    AlpineAllOf, HashedrekordAllOf, HelmAllOf, IntotoAllOf, JarAllOf, RekordAllOf, Rfc3161AllOf,
-    RpmAllOf, TufAllOf,
+    AlpineAllOf, CoseAllOf, DsseAllOf, HashedrekordAllOf, HelmAllOf, IntotoAllOf, JarAllOf,
+    RekordAllOf, Rfc3161AllOf, RpmAllOf, TufAllOf,
 };
 
 /// Stores the response returned by Rekor after making a new entry
@@ -20,26 +22,51 @@ pub struct LogEntry {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub attestation: Option<Attestation>,
     pub body: Body,
+    /// The body exactly as returned by Rekor, once base64 decoded. These are
+    /// the bytes covered by the signed entry timestamp and hashed into the
+    /// leaf of the log, re-serializing [`body`](LogEntry::body) is not
+    /// guaranteed to reproduce them.
+    #[serde(skip)]
+    pub raw_body: Vec<u8>,
     pub integrated_time: i64,
     pub log_i_d: String,
     pub log_index: i64,
     pub verification: Verification,
 }
 
+impl LogEntry {
+    /// The body of the entry the signed entry timestamp is computed over.
+    ///
+    /// These are the [`raw_body`](LogEntry::raw_body) bytes when known,
+    /// otherwise `body` is canonicalized the way Rekor does.
+    pub fn canonicalized_body(&self) -> Result<Vec<u8>, Error> {
+        if !self.raw_body.is_empty() {
+            return Ok(self.raw_body.clone());
+        }
+        let mut body = Vec::new();
+        let mut ser = serde_json::Serializer::with_formatter(&mut body, CanonicalFormatter::new());
+        self.body.serialize(&mut ser)?;
+        Ok(body)
+    }
+}
+
 impl FromStr for LogEntry {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut log_entry_map = serde_json::from_str::<HashMap<&str, Value>>(s)?;
-        log_entry_map.entry("body").and_modify(|body| {
-            let decoded_body = serde_json::to_value(
-                decode_body(body.as_str().expect("Failed to parse Body"))
-                    .expect("Failed to decode Body"),
-            )
-            .expect("Serialization failed");
-            *body = json!(decoded_body);
-        });
+        let mut raw_body = Vec::new();
+        if let Some(body) = log_entry_map.get_mut("body") {
+            let encoded = body
+                .as_str()
+                .ok_or_else(|| Error::custom("the body of the entry is not a string"))?;
+            let (decoded, decoded_body) = decode_body(encoded).map_err(Error::custom)?;
+            *body = serde_json::to_value(decoded_body)?;
+            raw_body = decoded;
+        }
         let log_entry_str = serde_json::to_string(&log_entry_map)?;
-        Ok(serde_json::from_str::<LogEntry>(&log_entry_str).expect("Serialization failed"))
+        let mut log_entry = serde_json::from_str::<LogEntry>(&log_entry_str)?;
+        log_entry.raw_body = raw_body;
+        Ok(log_entry)
     }
 }
 
@@ -48,6 +75,8 @@ impl FromStr for LogEntry {
 #[allow(non_camel_case_types)]
 pub enum Body {
     alpine(AlpineAllOf),
+    cose(CoseAllOf),
+    dsse(DsseAllOf),
     helm(HelmAllOf),
     jar(JarAllOf),
     rfc3161(Rfc3161AllOf),
@@ -58,15 +87,75 @@ pub enum Body {
     rekord(RekordAllOf),
 }
 
+impl Body {
+    /// The kind of the entry, e.g. `hashedrekord`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Body::alpine(_) => "alpine",
+            Body::cose(_) => "cose",
+            Body::dsse(_) => "dsse",
+            Body::helm(_) => "helm",
+            Body::jar(_) => "jar",
+            Body::rfc3161(_) => "rfc3161",
+            Body::rpm(_) => "rpm",
+            Body::tuf(_) => "tuf",
+            Body::intoto(_) => "intoto",
+            Body::hashedrekord(_) => "hashedrekord",
+            Body::rekord(_) => "rekord",
+        }
+    }
+
+    /// The version of the entry kind
+    pub fn api_version(&self) -> &str {
+        match self {
+            Body::alpine(b) => &b.api_version,
+            Body::cose(b) => &b.api_version,
+            Body::dsse(b) => &b.api_version,
+            Body::helm(b) => &b.api_version,
+            Body::jar(b) => &b.api_version,
+            Body::rfc3161(b) => &b.api_version,
+            Body::rpm(b) => &b.api_version,
+            Body::tuf(b) => &b.api_version,
+            Body::intoto(b) => &b.api_version,
+            Body::hashedrekord(b) => &b.api_version,
+            Body::rekord(b) => &b.api_version,
+        }
+    }
+
+    /// The kind specific contents of the entry
+    pub fn spec(&self) -> &Value {
+        match self {
+            Body::alpine(b) => &b.spec,
+            Body::cose(b) => &b.spec,
+            Body::dsse(b) => &b.spec,
+            Body::helm(b) => &b.spec,
+            Body::jar(b) => &b.spec,
+            Body::rfc3161(b) => &b.spec,
+            Body::rpm(b) => &b.spec,
+            Body::tuf(b) => &b.spec,
+            Body::intoto(b) => &b.spec,
+            Body::hashedrekord(b) => &b.spec,
+            Body::rekord(b) => &b.spec,
+        }
+    }
+
+    /// The kind specific contents of the entry, deserialized into `T`, like
+    /// a [`hashedrekord::Spec`](super::hashedrekord::Spec)
+    pub fn typed_spec<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        T::deserialize(self.spec())
+    }
+}
+
 impl Default for Body {
     fn default() -> Self {
         Self::hashedrekord(Default::default())
     }
 }
 
-fn decode_body(s: &str) -> Result<Body, SigstoreError> {
+fn decode_body(s: &str) -> Result<(Vec<u8>, Body), SigstoreError> {
     let decoded = BASE64_STD_ENGINE.decode(s)?;
-    serde_json::from_slice(&decoded).map_err(SigstoreError::from)
+    let body = serde_json::from_slice(&decoded)?;
+    Ok((decoded, body))
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -96,3 +185,51 @@ pub struct InclusionProof {
     pub root_hash: String,
     pub tree_size: TreeSize,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rekor::models::hashedrekord;
+    use serde_json::json;
+
+    fn log_entry(body: &str) -> String {
+        json!({
+            "uuid": "362f8ecba72f4326",
+            "body": body,
+            "integratedTime": 1_700_000_000,
+            "logID": "c0d23d6ad406973f9559f3ba2d1ca01f84147d8ffc5b8445c224f98b9591801d",
+            "logIndex": 7,
+            "verification": {"signedEntryTimestamp": "MEUCIQ=="},
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn decode_typed_body() {
+        let body = json!({
+            "kind": "hashedrekord",
+            "apiVersion": "0.0.1",
+            "spec": {
+                "signature": {
+                    "content": "c2lnbmF0dXJl",
+                    "publicKey": {"content": "a2V5"},
+                },
+                "data": {"hash": {"algorithm": "sha256", "value": "abcd"}},
+            },
+        });
+        let entry =
+            LogEntry::from_str(&log_entry(&BASE64_STD_ENGINE.encode(body.to_string()))).unwrap();
+        assert_eq!(entry.raw_body, body.to_string().into_bytes());
+        assert_eq!(entry.body.kind(), "hashedrekord");
+        let spec: hashedrekord::Spec = entry.body.typed_spec().unwrap();
+        assert_eq!(spec.signature.content, "c2lnbmF0dXJl");
+        assert_eq!(spec.signature.public_key.decode().unwrap(), "key");
+        assert_eq!(spec.data.hash.value, "abcd");
+    }
+
+    #[test]
+    fn reject_invalid_body() {
+        assert!(LogEntry::from_str(&log_entry("not base64!")).is_err());
+        assert!(LogEntry::from_str(&log_entry(&BASE64_STD_ENGINE.encode("{}"))).is_err());
+    }
+}
@@ -7,7 +7,28 @@
 *
 * Generated by: https://openapi-generator.tech
 */
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::hashedrekord::{AlgorithmKind, Data, Hash, PublicKey, Signature, Spec};
+
+/// The typed `spec` of a kind of entries, like a
+/// [`hashedrekord::Spec`](super::hashedrekord::Spec).
+///
+/// The specs of the recorded entries can be read with
+/// [`Body::typed_spec`](super::log_entry::Body::typed_spec).
+pub trait EntrySpec: Serialize + DeserializeOwned {
+    /// The kind of the entries, e.g. `hashedrekord`
+    const KIND: &'static str;
+    /// The version of the kind of entries described by the spec
+    const API_VERSION: &'static str;
+}
+
+/// The version of the `hashedrekord` entries created by
+/// [`ProposedEntry::hashedrekord`]
+pub const HASHEDREKORD_API_VERSION: &str = "0.0.1";
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "kind")]
@@ -19,6 +40,20 @@ pub enum ProposedEntry {
         #[serde(rename = "spec")]
         spec: serde_json::Value,
     },
+    #[serde(rename = "cose")]
+    Cose {
+        #[serde(rename = "apiVersion")]
+        api_version: String,
+        #[serde(rename = "spec")]
+        spec: serde_json::Value,
+    },
+    #[serde(rename = "dsse")]
+    Dsse {
+        #[serde(rename = "apiVersion")]
+        api_version: String,
+        #[serde(rename = "spec")]
+        spec: serde_json::Value,
+    },
     #[serde(rename = "hashedrekord")]
     Hashedrekord {
         #[serde(rename = "apiVersion")]
@@ -76,3 +111,98 @@ pub enum ProposedEntry {
         spec: serde_json::Value,
     },
 }
+
+impl ProposedEntry {
+    /// The entry of the kind described by `spec`
+    pub fn from_spec<S: EntrySpec>(spec: &S) -> serde_json::Result<Self> {
+        serde_json::from_value(json!({
+            "kind": S::KIND,
+            "apiVersion": S::API_VERSION,
+            "spec": spec,
+        }))
+    }
+
+    /// A `hashedrekord` entry, recording the signature of an artifact.
+    ///
+    /// * `digest`: the SHA-256 digest of the artifact
+    /// * `signature`: the signature of the artifact
+    /// * `verification_material`: the PEM encoded public key, or certificate,
+    ///   of the signer
+    pub fn hashedrekord(digest: &[u8], signature: &[u8], verification_material: &str) -> Self {
+        ProposedEntry::Hashedrekord {
+            api_version: HASHEDREKORD_API_VERSION.to_string(),
+            spec: Spec::new(
+                Signature::new(
+                    BASE64_STD_ENGINE.encode(signature),
+                    PublicKey::new(BASE64_STD_ENGINE.encode(verification_material)),
+                ),
+                Data::new(Hash::new(AlgorithmKind::sha256, hex::encode(digest))),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rekor::models::log_entry::Body;
+    use crate::rekor::models::{dsse, helm, intoto, jar, rekord, rpm};
+
+    fn assert_round_trip<S: EntrySpec + PartialEq + std::fmt::Debug>(spec: S) {
+        let entry = ProposedEntry::from_spec(&spec).unwrap();
+        let json = serde_json::to_value(&entry).unwrap();
+        assert_eq!(json["kind"], S::KIND);
+        assert_eq!(json["apiVersion"], S::API_VERSION);
+
+        let body: Body = serde_json::from_value(json).unwrap();
+        assert_eq!(body.kind(), S::KIND);
+        assert_eq!(body.typed_spec::<S>().unwrap(), spec);
+    }
+
+    #[test]
+    fn typed_specs() {
+        let hash = Hash::new(AlgorithmKind::sha256, "ab".repeat(32));
+        let public_key = PublicKey::new("a2V5".to_string());
+
+        assert_round_trip(rekord::Spec::new(
+            rekord::Signature::new(
+                rekord::SignatureFormat::Minisign,
+                "c2lnbmF0dXJl".to_string(),
+                public_key.clone(),
+            ),
+            rekord::Data::from_hash(hash.clone()),
+        ));
+        assert_round_trip(intoto::Spec::new(
+            intoto::Content::from_envelope("{}".to_string()),
+            "a2V5".to_string(),
+        ));
+        assert_round_trip(dsse::Spec::new("{}".to_string(), vec!["a2V5".to_string()]));
+        assert_round_trip(jar::Spec::new(jar::Archive::from_content(
+            "amFy".to_string(),
+        )));
+        assert_round_trip(rpm::Spec::new(
+            public_key.clone(),
+            rpm::Package::from_content("cnBt".to_string()),
+        ));
+        assert_round_trip(helm::Spec::new(
+            public_key,
+            helm::Chart {
+                hash: Some(hash),
+                provenance: helm::Provenance::from_content("cHJvdg==".to_string()),
+            },
+        ));
+
+        let entry = ProposedEntry::hashedrekord(&[0xab; 32], b"signature", "key");
+        assert_eq!(
+            ProposedEntry::from_spec(&Spec::new(
+                Signature::new(
+                    "c2lnbmF0dXJl".to_string(),
+                    PublicKey::new("a2V5".to_string())
+                ),
+                Data::new(Hash::new(AlgorithmKind::sha256, "ab".repeat(32))),
+            ))
+            .unwrap(),
+            entry
+        );
+    }
+}
@@ -11,6 +11,9 @@
 /// Rekord : Rekord object
 use serde::{Deserialize, Serialize};
 
+use super::hashedrekord::{Hash, PublicKey};
+use super::proposed_entry::EntrySpec;
+
 #[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub struct Rekord {
     #[serde(rename = "kind")]
@@ -31,3 +34,82 @@ impl Rekord {
         }
     }
 }
+
+/// The `spec` of the `rekord` entries: a detached signature of an
+/// artifact, verified by Rekor when the entry is created
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spec {
+    pub signature: Signature,
+    pub data: Data,
+}
+
+impl Spec {
+    pub fn new(signature: Signature, data: Data) -> Spec {
+        Spec { signature, data }
+    }
+}
+
+impl EntrySpec for Spec {
+    const KIND: &'static str = "rekord";
+    const API_VERSION: &'static str = "0.0.1";
+}
+
+/// Stores the format of the signature, the base64 encoded signature and the
+/// public key of the signer
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Signature {
+    pub format: SignatureFormat,
+    pub content: String,
+    pub public_key: PublicKey,
+}
+
+impl Signature {
+    pub fn new(format: SignatureFormat, content: String, public_key: PublicKey) -> Signature {
+        Signature {
+            format,
+            content,
+            public_key,
+        }
+    }
+}
+
+/// The formats of the signatures accepted by the `rekord` entries
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureFormat {
+    #[default]
+    X509,
+    Pgp,
+    Minisign,
+    Ssh,
+}
+
+/// The signed artifact: its hash, or its base64 encoded content
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Data {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<Hash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+impl Data {
+    /// The artifact identified by its hash
+    pub fn from_hash(hash: Hash) -> Data {
+        Data {
+            hash: Some(hash),
+            content: None,
+        }
+    }
+
+    /// The artifact itself, `content` is base64 encoded
+    pub fn from_content(content: String) -> Data {
+        Data {
+            hash: None,
+            content: Some(content),
+        }
+    }
+}
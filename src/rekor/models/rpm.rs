@@ -10,6 +10,9 @@
 
 use serde::{Deserialize, Serialize};
 
+use super::hashedrekord::{Hash, PublicKey};
+use super::proposed_entry::EntrySpec;
+
 /// Rpm : RPM package
 
 #[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
@@ -32,3 +35,50 @@ impl Rpm {
         }
     }
 }
+
+/// The `spec` of the `rpm` entries: a signed RPM package
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spec {
+    /// The PGP public key of the signer
+    pub public_key: PublicKey,
+    pub package: Package,
+}
+
+impl Spec {
+    pub fn new(public_key: PublicKey, package: Package) -> Spec {
+        Spec {
+            public_key,
+            package,
+        }
+    }
+}
+
+impl EntrySpec for Spec {
+    const KIND: &'static str = "rpm";
+    const API_VERSION: &'static str = "0.0.1";
+}
+
+/// The package: its hash, or its base64 encoded content. Rekor records the
+/// headers of the package too.
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Package {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<Hash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub headers: Option<std::collections::BTreeMap<String, String>>,
+}
+
+impl Package {
+    /// The package itself, `content` is base64 encoded
+    pub fn from_content(content: String) -> Package {
+        Package {
+            hash: None,
+            content: Some(content),
+            headers: None,
+        }
+    }
+}
@@ -28,4 +28,63 @@ impl SearchIndex {
             hash: None,
         }
     }
+
+    /// Search the entries of the artifact with the given SHA-256 `digest`
+    pub fn with_sha256_digest(self, digest: &[u8]) -> SearchIndex {
+        SearchIndex {
+            hash: Some(format!("sha256:{}", hex::encode(digest))),
+            ..self
+        }
+    }
+
+    /// Search the entries signed by `email`, found inside of the
+    /// certificates of the signers
+    pub fn with_email(self, email: &str) -> SearchIndex {
+        SearchIndex {
+            email: Some(email.to_string()),
+            ..self
+        }
+    }
+
+    /// Search the entries signed with the given public key, `content` is the
+    /// key encoded in the given `format`, e.g. a PEM encoded key or
+    /// certificate for [`Format::X509`](crate::rekor::models::search_index_public_key::Format::X509)
+    pub fn with_public_key(
+        self,
+        format: crate::rekor::models::search_index_public_key::Format,
+        content: &[u8],
+    ) -> SearchIndex {
+        use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+
+        let mut public_key =
+            crate::rekor::models::search_index_public_key::SearchIndexPublicKey::new(format);
+        public_key.content = Some(BASE64_STD_ENGINE.encode(content));
+        SearchIndex {
+            public_key: Some(public_key),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rekor::models::search_index_public_key::Format;
+    use serde_json::json;
+
+    #[test]
+    fn build_queries() {
+        let query = SearchIndex::new()
+            .with_sha256_digest(&[0xab; 32])
+            .with_email("octocat@example.com")
+            .with_public_key(Format::X509, b"key");
+        assert_eq!(
+            serde_json::to_value(&query).unwrap(),
+            json!({
+                "hash": format!("sha256:{}", "ab".repeat(32)),
+                "email": "octocat@example.com",
+                "publicKey": {"format": "x509", "content": "a2V5"},
+            })
+        );
+    }
 }
@@ -0,0 +1,185 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Discovery of the public key of a Rekor instance.
+//!
+//! The key is fetched from the `/api/v1/log/publicKey` endpoint, together
+//! with the metadata of the tree returned by `/api/v1/log`. Both are cached
+//! by the [`RekorKeyProvider`], and the key can be pinned by its log ID to
+//! guard against a compromised or impersonated instance:
+//!
+//! ```rust,no_run
+//! use sigstore::cosign::ClientBuilder;
+//! use sigstore::rekor::apis::configuration::Configuration;
+//! use sigstore::rekor::public_key::RekorKeyProvider;
+//!
+//! # async fn doc() -> sigstore::errors::Result<()> {
+//! let provider = RekorKeyProvider::new(Configuration::default()).with_expected_log_id(
+//!     "c0d23d6ad406973f9559f3ba2d1ca01f84147d8ffc5b8445c224f98b9591801d",
+//! );
+//! let rekor_key = provider.public_key().await?;
+//! let client = ClientBuilder::default()
+//!     .with_rekor_pub_key(&rekor_key.pem)
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! **Note well:** without a pinned log ID the key is trusted on first use,
+//! prefer the keys distributed via [TUF](crate::tuf) when possible.
+
+use sha2::{Digest, Sha256};
+use std::sync::Mutex;
+
+use super::apis::configuration::Configuration;
+use super::apis::{pubkey_api, tlog_api};
+use super::models::LogInfo;
+use crate::crypto::CosignVerificationKey;
+use crate::errors::{Result, SigstoreError};
+
+/// The public key of a Rekor instance
+#[derive(Debug, Clone)]
+pub struct RekorPublicKey {
+    /// The PEM encoded key
+    pub pem: String,
+    /// The key, ready to verify the signed entry timestamps and the
+    /// checkpoints of the log
+    pub key: CosignVerificationKey,
+    /// The ID of the log: the hex encoded SHA-256 digest of the DER encoded
+    /// key
+    pub log_id: String,
+    /// The metadata of the tree at the time the key was fetched
+    pub log_info: LogInfo,
+}
+
+impl RekorPublicKey {
+    /// Parse the PEM encoded `pem` key of the log described by `log_info`
+    pub fn new(pem: &str, log_info: LogInfo) -> Result<Self> {
+        let der = pem::parse(pem)?.contents;
+        Ok(RekorPublicKey {
+            pem: pem.to_string(),
+            key: CosignVerificationKey::try_from_der(&der)?,
+            log_id: log_id(&der),
+            log_info,
+        })
+    }
+}
+
+/// The log ID of the Rekor instance using the DER encoded `public_key`
+pub fn log_id(public_key: &[u8]) -> String {
+    hex::encode(Sha256::digest(public_key))
+}
+
+/// Fetches, and caches, the public key of a Rekor instance
+pub struct RekorKeyProvider {
+    configuration: Configuration,
+    expected_log_id: Option<String>,
+    cached: Mutex<Option<RekorPublicKey>>,
+}
+
+impl RekorKeyProvider {
+    /// Fetch the key of the Rekor instance of `configuration`
+    pub fn new(configuration: Configuration) -> Self {
+        RekorKeyProvider {
+            configuration,
+            expected_log_id: None,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Reject the key unless its log ID is `log_id`
+    pub fn with_expected_log_id(self, log_id: &str) -> Self {
+        RekorKeyProvider {
+            configuration: self.configuration,
+            expected_log_id: Some(log_id.to_lowercase()),
+            cached: self.cached,
+        }
+    }
+
+    /// The public key of the instance, fetched on the first call
+    pub async fn public_key(&self) -> Result<RekorPublicKey> {
+        if let Some(cached) = self.lock().as_ref() {
+            return Ok(cached.clone());
+        }
+
+        let public_key = self.fetch().await?;
+        *self.lock() = Some(public_key.clone());
+        Ok(public_key)
+    }
+
+    /// Forget the cached key, the next call to
+    /// [`public_key`](RekorKeyProvider::public_key) fetches it again
+    pub fn clear(&self) {
+        *self.lock() = None;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<RekorPublicKey>> {
+        // a panic while holding the lock can't leave the cache inconsistent
+        self.cached
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    async fn fetch(&self) -> Result<RekorPublicKey> {
+        let log_info = tlog_api::get_log_info(&self.configuration)
+            .await
+            .map_err(|e| SigstoreError::RekorClientError(e.to_string()))?;
+        let pem = pubkey_api::get_public_key(&self.configuration, log_info.tree_id.as_deref())
+            .await
+            .map_err(|e| SigstoreError::RekorClientError(e.to_string()))?;
+        let public_key = RekorPublicKey::new(&pem, log_info)?;
+        self.check_log_id(&public_key)?;
+        Ok(public_key)
+    }
+
+    fn check_log_id(&self, public_key: &RekorPublicKey) -> Result<()> {
+        match &self.expected_log_id {
+            Some(expected) if *expected != public_key.log_id => {
+                Err(SigstoreError::RekorLogIdMismatchError {
+                    expected: expected.clone(),
+                    actual: public_key.log_id.clone(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::tests::REKOR_PUB_KEY;
+
+    #[test]
+    fn pin_log_id() {
+        let public_key = RekorPublicKey::new(REKOR_PUB_KEY, LogInfo::default()).unwrap();
+        // the ID of the public instance of Rekor
+        assert_eq!(
+            public_key.log_id,
+            "c0d23d6ad406973f9559f3ba2d1ca01f84147d8ffc5b8445c224f98b9591801d"
+        );
+
+        let provider = RekorKeyProvider::new(Configuration::default())
+            .with_expected_log_id(&public_key.log_id.to_uppercase());
+        assert!(provider.check_log_id(&public_key).is_ok());
+
+        let provider =
+            RekorKeyProvider::new(Configuration::default()).with_expected_log_id(&"00".repeat(32));
+        assert!(matches!(
+            provider.check_log_id(&public_key),
+            Err(SigstoreError::RekorLogIdMismatchError { .. })
+        ));
+    }
+}
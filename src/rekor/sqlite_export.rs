@@ -0,0 +1,391 @@
+//
+// Copyright 2023 The Sigstore Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export of Rekor entries to a local SQLite database.
+//!
+//! The [`SqliteExporter`] stores the entries fetched from Rekor, together with
+//! the details extracted from their bodies (artifact digest, signing
+//! identity, public key), inside of an indexed SQLite database. This allows
+//! incident responders to answer questions like "what did identity X sign
+//! last month" without having access to Rekor.
+//!
+//! This module requires the `rekor-sqlite` feature.
+//!
+//! ```rust,no_run
+//! use sigstore::rekor::apis::{configuration::Configuration, entries_api};
+//! use sigstore::rekor::sqlite_export::SqliteExporter;
+//!
+//! #[tokio::main]
+//! pub async fn main() {
+//!   let configuration = Configuration::default();
+//!   let entry = entries_api::get_log_entry_by_index(&configuration, 99)
+//!     .await
+//!     .expect("Cannot fetch entry");
+//!
+//!   let mut exporter = SqliteExporter::open("rekor.db").expect("Cannot open database");
+//!   exporter.export(&[entry]).expect("Cannot export entry");
+//!
+//!   for entry in exporter
+//!     .entries_signed_by("user@example.com", None, None)
+//!     .expect("Cannot query database")
+//!   {
+//!     println!("{} signed {:?}", entry.identity.unwrap_or_default(), entry.artifact_digest);
+//!   }
+//! }
+//! ```
+
+use std::path::Path;
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STD_ENGINE, Engine as _};
+use chrono::{DateTime, Utc};
+use pkcs8::der::Decode;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde_json::Value;
+use x509_cert::ext::pkix::{name::GeneralName, SubjectAltName};
+use x509_cert::Certificate;
+
+use super::models::log_entry::LogEntry;
+use crate::errors::Result;
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS entries (
+    uuid TEXT PRIMARY KEY NOT NULL,
+    log_index INTEGER NOT NULL,
+    log_id TEXT NOT NULL,
+    integrated_time INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    api_version TEXT NOT NULL,
+    artifact_digest TEXT,
+    identity TEXT,
+    public_key TEXT,
+    body TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS entries_identity ON entries (identity, integrated_time);
+CREATE INDEX IF NOT EXISTS entries_artifact_digest ON entries (artifact_digest);
+CREATE INDEX IF NOT EXISTS entries_integrated_time ON entries (integrated_time);
+"#;
+
+/// Where to look for the artifact digest inside of the entry spec
+const DIGEST_POINTERS: [&str; 4] = [
+    "/data/hash",
+    "/content/hash",
+    "/package/hash",
+    "/archive/hash",
+];
+
+/// Where to look for the signer public key or certificate inside of the entry spec
+const PUBLIC_KEY_POINTERS: [&str; 3] = [
+    "/signature/publicKey/content",
+    "/publicKey",
+    "/content/envelope/signatures/0/publicKey",
+];
+
+/// A Rekor entry stored inside of the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportedEntry {
+    /// The UUID of the entry
+    pub uuid: String,
+    /// The index of the entry inside of the log
+    pub log_index: i64,
+    /// The ID of the log
+    pub log_id: String,
+    /// When the entry has been added to the log, as a UNIX timestamp
+    pub integrated_time: i64,
+    /// The kind of the entry, e.g. `hashedrekord`
+    pub kind: String,
+    /// The version of the entry kind
+    pub api_version: String,
+    /// The digest of the artifact, in the `<algorithm>:<hex value>` format
+    pub artifact_digest: Option<String>,
+    /// The identity of the signer, taken from the Subject Alternative Name
+    /// of the signing certificate. `None` when the entry has been signed with
+    /// a plain public key.
+    pub identity: Option<String>,
+    /// The PEM encoded public key or certificate of the signer
+    pub public_key: Option<String>,
+    /// The decoded body of the entry, serialized as JSON
+    pub body: String,
+}
+
+impl ExportedEntry {
+    /// Extract the searchable details of a Rekor entry
+    pub fn from_log_entry(entry: &LogEntry) -> Result<Self> {
+        let spec = entry.body.spec();
+
+        let artifact_digest = DIGEST_POINTERS
+            .iter()
+            .filter_map(|p| spec.pointer(p))
+            .find_map(|hash| {
+                let algorithm = hash.get("algorithm")?.as_str()?;
+                let value = hash.get("value")?.as_str()?;
+                Some(format!("{algorithm}:{value}"))
+            });
+
+        let public_key = PUBLIC_KEY_POINTERS
+            .iter()
+            .filter_map(|p| spec.pointer(p).and_then(Value::as_str))
+            .find_map(|content| {
+                let decoded = BASE64_STD_ENGINE.decode(content).ok()?;
+                String::from_utf8(decoded).ok()
+            });
+        let identity = public_key.as_deref().and_then(certificate_identity);
+
+        Ok(ExportedEntry {
+            uuid: entry.uuid.clone(),
+            log_index: entry.log_index,
+            log_id: entry.log_i_d.clone(),
+            integrated_time: entry.integrated_time,
+            kind: entry.body.kind().to_string(),
+            api_version: entry.body.api_version().to_string(),
+            artifact_digest,
+            identity,
+            public_key,
+            body: serde_json::to_string(&entry.body)?,
+        })
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(ExportedEntry {
+            uuid: row.get("uuid")?,
+            log_index: row.get("log_index")?,
+            log_id: row.get("log_id")?,
+            integrated_time: row.get("integrated_time")?,
+            kind: row.get("kind")?,
+            api_version: row.get("api_version")?,
+            artifact_digest: row.get("artifact_digest")?,
+            identity: row.get("identity")?,
+            public_key: row.get("public_key")?,
+            body: row.get("body")?,
+        })
+    }
+}
+
+/// Extract the email or URI stored inside of the Subject Alternative Name
+/// of a PEM encoded certificate. Returns `None` for plain public keys.
+fn certificate_identity(pem_data: &str) -> Option<String> {
+    let pem = pem::parse(pem_data).ok()?;
+    if pem.tag != "CERTIFICATE" {
+        return None;
+    }
+    let cert = Certificate::from_der(&pem.contents).ok()?;
+    let (_, san) = cert.tbs_certificate.get::<SubjectAltName>().ok()??;
+    san.0.iter().find_map(|name| match name {
+        GeneralName::Rfc822Name(email) => Some(email.to_string()),
+        GeneralName::UniformResourceIdentifier(uri) => Some(uri.to_string()),
+        _ => None,
+    })
+}
+
+/// Stores Rekor entries inside of a SQLite database
+pub struct SqliteExporter {
+    conn: Connection,
+}
+
+impl SqliteExporter {
+    /// Open the database at the given path, creating it when it doesn't exist
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Create a database that lives only in memory
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(SqliteExporter { conn })
+    }
+
+    /// Store the given entries. Entries that are already part of the database
+    /// are updated.
+    ///
+    /// Returns the number of entries written.
+    pub fn export(&mut self, entries: &[LogEntry]) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO entries
+                 (uuid, log_index, log_id, integrated_time, kind, api_version,
+                  artifact_digest, identity, public_key, body)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+            for entry in entries {
+                let e = ExportedEntry::from_log_entry(entry)?;
+                stmt.execute(params![
+                    e.uuid,
+                    e.log_index,
+                    e.log_id,
+                    e.integrated_time,
+                    e.kind,
+                    e.api_version,
+                    e.artifact_digest,
+                    e.identity,
+                    e.public_key,
+                    e.body,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(entries.len())
+    }
+
+    /// Look up an entry by its UUID
+    pub fn entry(&self, uuid: &str) -> Result<Option<ExportedEntry>> {
+        let entry = self
+            .conn
+            .query_row(
+                "SELECT * FROM entries WHERE uuid = ?1",
+                params![uuid],
+                ExportedEntry::from_row,
+            )
+            .optional()?;
+        Ok(entry)
+    }
+
+    /// Find the entries signed by the given identity, optionally restricted
+    /// to the given time window. Entries are sorted by integration time.
+    pub fn entries_signed_by(
+        &self,
+        identity: &str,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Vec<ExportedEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT * FROM entries
+             WHERE identity = ?1 AND integrated_time >= ?2 AND integrated_time <= ?3
+             ORDER BY integrated_time",
+        )?;
+        let entries = stmt
+            .query_map(
+                params![
+                    identity,
+                    since.map(|t| t.timestamp()).unwrap_or(i64::MIN),
+                    until.map(|t| t.timestamp()).unwrap_or(i64::MAX),
+                ],
+                ExportedEntry::from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    /// Find the entries referring to the artifact with the given digest,
+    /// expressed in the `<algorithm>:<hex value>` format
+    pub fn entries_for_artifact(&self, digest: &str) -> Result<Vec<ExportedEntry>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT * FROM entries WHERE artifact_digest = ?1 ORDER BY integrated_time")?;
+        let entries = stmt
+            .query_map(params![digest], ExportedEntry::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    /// Access the underlying connection, to perform arbitrary queries
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+    use std::str::FromStr;
+
+    use crate::crypto::tests::{generate_certificate, CertGenerationOptions, PUBLIC_KEY};
+
+    fn build_log_entry(uuid: &str, integrated_time: i64, public_PUBLIC_KEY: &str) -> LogEntry {
+        let body = json!({
+            "apiVersion": "0.0.1",
+            "kind": "hashedrekord",
+            "spec": {
+                "data": {
+                    "hash": {
+                        "algorithm": "sha256",
+                        "value": format!("digest-of-{uuid}"),
+                    }
+                },
+                "signature": {
+                    "content": "c2lnbmF0dXJl",
+                    "publicKey": {
+                        "content": BASE64_STD_ENGINE.encode(public_PUBLIC_KEY),
+                    }
+                }
+            }
+        });
+        let entry = json!({
+            "uuid": uuid,
+            "body": BASE64_STD_ENGINE.encode(body.to_string()),
+            "integratedTime": integrated_time,
+            "logID": "c0d23d6ad406973f9559f3ba2d1ca01f84147d8ffc5b8445c224f98b9591801d",
+            "logIndex": 1,
+            "verification": {
+                "signedEntryTimestamp": "",
+            }
+        });
+        LogEntry::from_str(&entry.to_string()).expect("cannot parse log entry")
+    }
+
+    #[test]
+    fn export_and_query() -> anyhow::Result<()> {
+        let ca_data = generate_certificate(None, CertGenerationOptions::default())?;
+        let issued_cert = generate_certificate(
+            Some(&ca_data),
+            CertGenerationOptions {
+                subject_email: Some("user@example.com".to_string()),
+                ..Default::default()
+            },
+        )?;
+        let cert_pem = String::from_utf8(issued_cert.cert.to_pem()?)?;
+
+        let entries = vec![
+            build_log_entry("a", 1_000, &cert_pem),
+            build_log_entry("b", 2_000, &cert_pem),
+            build_log_entry("c", 3_000, PUBLIC_KEY),
+        ];
+
+        let mut exporter = SqliteExporter::open_in_memory()?;
+        assert_eq!(exporter.export(&entries)?, 3);
+        // exporting the same entries again must not create duplicates
+        assert_eq!(exporter.export(&entries)?, 3);
+
+        let signed = exporter.entries_signed_by("user@example.com", None, None)?;
+        assert_eq!(
+            signed.iter().map(|e| e.uuid.as_str()).collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+
+        let since = Utc.timestamp_opt(1_500, 0).unwrap();
+        let signed = exporter.entries_signed_by("user@example.com", Some(since), None)?;
+        assert_eq!(signed.len(), 1);
+        assert_eq!(signed[0].uuid, "b");
+        assert_eq!(signed[0].kind, "hashedrekord");
+
+        let entry = exporter.entry("c")?.expect("entry not found");
+        assert_eq!(entry.identity, None);
+        assert_eq!(entry.public_key.as_deref(), Some(PUBLIC_KEY));
+        assert_eq!(entry.artifact_digest.as_deref(), Some("sha256:digest-of-c"));
+
+        assert_eq!(
+            exporter.entries_for_artifact("sha256:digest-of-a")?.len(),
+            1
+        );
+        assert!(exporter.entry("missing")?.is_none());
+
+        Ok(())
+    }
+}
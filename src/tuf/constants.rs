@@ -19,11 +19,12 @@ use regex::Regex;
 lazy_static! {
     pub(crate) static ref SIGSTORE_FULCIO_CERT_TARGET_REGEX: Regex =
         Regex::new(r#"fulcio(_v\d+)?\.crt\.pem"#).expect("cannot compile regexp");
+    pub(crate) static ref SIGSTORE_CTFE_PUB_KEY_TARGET_REGEX: Regex =
+        Regex::new(r#"^ctfe(_\d+)?\.pub$"#).expect("cannot compile regexp");
 }
 
-pub(crate) const SIGSTORE_METADATA_BASE: &str = "https://sigstore-tuf-root.storage.googleapis.com/";
-pub(crate) const SIGSTORE_TARGET_BASE: &str =
-    "https://sigstore-tuf-root.storage.googleapis.com/targets";
+pub(crate) const SIGSTORE_METADATA_BASE: &str = "https://tuf-repo-cdn.sigstore.dev/";
+pub(crate) const SIGSTORE_TARGET_BASE: &str = "https://tuf-repo-cdn.sigstore.dev/targets";
 
 pub(crate) const SIGSTORE_REKOR_PUB_KEY_TARGET: &str = "rekor.pub";
 
@@ -185,4 +186,13 @@ mod test {
     fn check_fulcio_regex(#[case] input: &str, #[case] matches: bool) {
         assert_eq!(SIGSTORE_FULCIO_CERT_TARGET_REGEX.is_match(input), matches);
     }
+
+    #[rstest]
+    #[case("ctfe.pub", true)]
+    #[case("ctfe_2022.pub", true)]
+    #[case("ctfe-2022.pub", false)]
+    #[case("rekor.pub", false)]
+    fn check_ctfe_regex(#[case] input: &str, #[case] matches: bool) {
+        assert_eq!(SIGSTORE_CTFE_PUB_KEY_TARGET_REGEX.is_match(input), matches);
+    }
 }
@@ -16,10 +16,13 @@
 //! Helper Structs to interact with the Sigstore TUF repository.
 //!
 //! The main interaction point is [`SigstoreRepository`], which fetches Rekor's
-//! public key and Fulcio's certificate.
+//! public key, Fulcio's certificate and the public keys of the certificate
+//! transparency logs from `tuf-repo-cdn.sigstore.dev`.
 //!
 //! These can later be given to [`cosign::ClientBuilder`](crate::cosign::ClientBuilder)
-//! to enable Fulcio and Rekor integrations.
+//! to enable Fulcio and Rekor integrations. Converting the repository into a
+//! [`TrustedRoot`] provides the keys of the CT logs too, which are required
+//! to verify the SCT of the certificates issued by Fulcio.
 //!
 //! # Example
 //!
@@ -30,11 +33,15 @@
 //! use sigstore::tuf::SigstoreRepository;
 //! use sigstore::cosign;
 //!
+//! use sigstore::crypto::trusted_root::TrustedRoot;
+//! use std::convert::TryFrom;
+//!
 //! let repo = SigstoreRepository::fetch(None)
 //!     .expect("Error while building SigstoreRepository");
+//! let trusted_root = TrustedRoot::try_from(&repo)
+//!     .expect("Error while building TrustedRoot");
 //! let client = cosign::ClientBuilder::default()
-//!     .with_rekor_pub_key(repo.rekor_pub_key())
-//!     .with_fulcio_certs(repo.fulcio_certs())
+//!     .with_trusted_root(trusted_root)
 //!     .build()
 //!     .expect("Error while building cosign client");
 //! ```
@@ -47,7 +54,10 @@
 //! special handling when invoked inside of an async context. Please refer to the
 //! [method docs](SigstoreRepository::fetch) for more details.
 //!
+use chrono::{DateTime, Utc};
+use std::convert::TryFrom;
 use std::path::Path;
+use std::sync::Arc;
 
 mod constants;
 use constants::*;
@@ -55,13 +65,19 @@ use constants::*;
 mod repository_helper;
 use repository_helper::RepositoryHelper;
 
+use crate::cache::{CacheStorage, FilesystemCache};
+use crate::crypto::trusted_root::{TrustedRoot, ValidityPeriod};
+
 use super::errors::{Result, SigstoreError};
 
-/// Securely fetches Rekor public key and Fulcio certificates from Sigstore's TUF repository
+/// Securely fetches Rekor public key, Fulcio certificates and CT log keys
+/// from Sigstore's TUF repository
 #[derive(Clone)]
 pub struct SigstoreRepository {
     rekor_pub_key: String,
     fulcio_certs: Vec<crate::registry::Certificate>,
+    ctfe_pub_keys: Vec<String>,
+    fetched_at: DateTime<Utc>,
 }
 
 impl SigstoreRepository {
@@ -70,7 +86,7 @@ impl SigstoreRepository {
     /// ## Parameters
     ///
     /// * `checkout_dir`: path to a local directory where Rekor's public
-    /// key and Fulcio's certificates can be found
+    ///   key, Fulcio's certificates and the CT log keys can be found
     ///
     /// ## Behaviour
     ///
@@ -83,7 +99,7 @@ impl SigstoreRepository {
     /// The update process happens using the TUF protocol.
     ///
     /// When `checkout_dir` is specified, this method will look for the
-    /// Fulcio and Rekor files inside of this directory. It will then compare the
+    /// Fulcio, Rekor and CT log files inside of this directory. It will then compare the
     /// checksums of these local files with the ones reported inside of the
     /// TUF repository metadata.
     ///
@@ -122,6 +138,24 @@ impl SigstoreRepository {
     ///
     /// This of course has a performance hit when used inside of an async function.
     pub fn fetch(checkout_dir: Option<&Path>) -> Result<Self> {
+        Self::fetch_from(
+            checkout_dir.map(|dir| Arc::new(FilesystemCache::new(dir)) as Arc<dyn CacheStorage>),
+        )
+    }
+
+    /// Fetch relevant information from the remote Sigstore TUF repository,
+    /// like [`SigstoreRepository::fetch`] does, caching Rekor's public key
+    /// and Fulcio's certificates inside of `cache`.
+    ///
+    /// The cached files are reused as long as their checksums match the ones
+    /// reported by the TUF repository metadata. Using a
+    /// [`FilesystemCache`] is equivalent to giving a `checkout_dir` to
+    /// [`SigstoreRepository::fetch`].
+    pub fn fetch_with_cache(cache: Arc<dyn CacheStorage>) -> Result<Self> {
+        Self::fetch_from(Some(cache))
+    }
+
+    fn fetch_from(cache: Option<Arc<dyn CacheStorage>>) -> Result<Self> {
         let metadata_base = url::Url::parse(SIGSTORE_METADATA_BASE).map_err(|_| {
             SigstoreError::UnexpectedError(String::from("Cannot convert metadata_base to URL"))
         })?;
@@ -129,12 +163,8 @@ impl SigstoreRepository {
             SigstoreError::UnexpectedError(String::from("Cannot convert target_base to URL"))
         })?;
 
-        let repository_helper = RepositoryHelper::new(
-            SIGSTORE_ROOT.as_bytes(),
-            metadata_base,
-            target_base,
-            checkout_dir,
-        )?;
+        let repository_helper =
+            RepositoryHelper::new(SIGSTORE_ROOT.as_bytes(), metadata_base, target_base, cache)?;
 
         let fulcio_certs = repository_helper.fulcio_certs()?;
 
@@ -146,9 +176,23 @@ impl SigstoreRepository {
             })
         })??;
 
+        let ctfe_pub_keys = repository_helper
+            .ctfe_pub_keys()?
+            .into_iter()
+            .map(|data| {
+                String::from_utf8(data).map_err(|e| {
+                    SigstoreError::UnexpectedError(format!(
+                        "Cannot parse CT log public key obtained from TUF repository: {e}",
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
         Ok(SigstoreRepository {
             rekor_pub_key,
             fulcio_certs,
+            ctfe_pub_keys,
+            fetched_at: Utc::now(),
         })
     }
 
@@ -161,4 +205,97 @@ impl SigstoreRepository {
     pub fn fulcio_certs(&self) -> &[crate::registry::Certificate] {
         &self.fulcio_certs
     }
+
+    /// PEM encoded public keys of the certificate transparency logs where
+    /// Fulcio publishes its certificates
+    pub fn ctfe_pub_keys(&self) -> &[String] {
+        &self.ctfe_pub_keys
+    }
+
+    /// When the TUF metadata and the targets have been fetched. This can be
+    /// given to [`ClientBuilder::with_freshness_policy`](crate::cosign::ClientBuilder::with_freshness_policy)
+    /// to prevent verifying against stale trust material.
+    pub fn fetched_at(&self) -> DateTime<Utc> {
+        self.fetched_at
+    }
+}
+
+impl TryFrom<&SigstoreRepository> for TrustedRoot {
+    type Error = SigstoreError;
+
+    /// Build a `TrustedRoot` holding Rekor's public key, Fulcio's
+    /// certificates and the keys of the CT logs of `repo`. The TUF targets
+    /// don't carry any validity window, hence all the material is considered
+    /// to be always valid.
+    fn try_from(repo: &SigstoreRepository) -> Result<Self> {
+        let mut trusted_root = TrustedRoot::new();
+        trusted_root.add_rekor_pub_key(repo.rekor_pub_key(), ValidityPeriod::always())?;
+        for key in repo.ctfe_pub_keys() {
+            trusted_root.add_ct_log_pub_key(key, ValidityPeriod::always())?;
+        }
+        for cert in repo.fulcio_certs() {
+            trusted_root
+                .add_fulcio_cert_chain(std::slice::from_ref(cert), ValidityPeriod::always());
+        }
+        Ok(trusted_root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cosign::signature_layers::CertificateSignature;
+    use crate::cosign::tests::{FULCIO_CRT_2_PEM, REKOR_PUB_KEY};
+    use crate::crypto::certificate_transparency::tests::{CTFE_2022_PUB_KEY, FULCIO_LEAF_CERT};
+    use crate::crypto::certificate_transparency::SctPolicy;
+    use crate::registry::{Certificate, CertificateEncoding};
+
+    fn repository(ctfe_pub_keys: Vec<String>) -> SigstoreRepository {
+        SigstoreRepository {
+            rekor_pub_key: REKOR_PUB_KEY.to_string(),
+            fulcio_certs: vec![Certificate {
+                encoding: CertificateEncoding::Pem,
+                data: FULCIO_CRT_2_PEM.as_bytes().to_vec(),
+            }],
+            ctfe_pub_keys,
+            fetched_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn trusted_root_verifies_sct_with_the_default_policy() {
+        // the SCT of the certificate has been issued at this time
+        let integrated_time = 1678707561;
+
+        let repo = repository(vec![CTFE_2022_PUB_KEY.to_string()]);
+        let trusted_root = TrustedRoot::try_from(&repo).unwrap();
+        assert!(trusted_root.has_ct_logs());
+        assert_eq!(trusted_root.rekor_pub_keys_at(integrated_time).len(), 1);
+
+        let cert_pool = trusted_root
+            .fulcio_cert_pool_at(integrated_time)
+            .unwrap()
+            .unwrap();
+        assert!(CertificateSignature::from_certificate(
+            FULCIO_LEAF_CERT.as_bytes(),
+            &cert_pool,
+            integrated_time,
+            Some(&trusted_root),
+            SctPolicy::default(),
+        )
+        .is_ok());
+
+        // without the keys of the CT logs the certificate is rejected
+        let trusted_root = TrustedRoot::try_from(&repository(Vec::new())).unwrap();
+        assert!(matches!(
+            CertificateSignature::from_certificate(
+                FULCIO_LEAF_CERT.as_bytes(),
+                &cert_pool,
+                integrated_time,
+                Some(&trusted_root),
+                SctPolicy::default(),
+            ),
+            Err(SigstoreError::SctVerificationError(_))
+        ));
+    }
 }
@@ -14,20 +14,23 @@
 // limitations under the License.
 
 use sha2::{Digest, Sha256};
-use std::fs;
 use std::io::Read;
-use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tough::{RepositoryLoader, TargetName};
 use url::Url;
 
 use super::{
+    super::cache::CacheStorage,
     super::errors::{Result, SigstoreError},
-    constants::{SIGSTORE_FULCIO_CERT_TARGET_REGEX, SIGSTORE_REKOR_PUB_KEY_TARGET},
+    constants::{
+        SIGSTORE_CTFE_PUB_KEY_TARGET_REGEX, SIGSTORE_FULCIO_CERT_TARGET_REGEX,
+        SIGSTORE_REKOR_PUB_KEY_TARGET,
+    },
 };
 
 pub(crate) struct RepositoryHelper {
     repository: tough::Repository,
-    checkout_dir: Option<PathBuf>,
+    cache: Option<Arc<dyn CacheStorage>>,
 }
 
 impl RepositoryHelper {
@@ -35,7 +38,7 @@ impl RepositoryHelper {
         root: R,
         metadata_base: Url,
         target_base: Url,
-        checkout_dir: Option<&Path>,
+        cache: Option<Arc<dyn CacheStorage>>,
     ) -> Result<Self>
     where
         R: Read,
@@ -45,10 +48,7 @@ impl RepositoryHelper {
             .load()
             .map_err(Box::new)?;
 
-        Ok(Self {
-            repository,
-            checkout_dir: checkout_dir.map(|s| s.to_owned()),
-        })
+        Ok(Self { repository, cache })
     }
 
     /// Fetch Fulcio certificates from the given TUF repository or reuse
@@ -60,15 +60,10 @@ impl RepositoryHelper {
         let mut certs = vec![];
 
         for fulcio_target_name in &fulcio_target_names {
-            let local_fulcio_path = self
-                .checkout_dir
-                .as_ref()
-                .map(|d| Path::new(d).join(fulcio_target_name.raw()));
-
             let cert_data = fetch_target_or_reuse_local_cache(
                 &self.repository,
                 fulcio_target_name,
-                local_fulcio_path.as_ref(),
+                self.cache.as_deref(),
             )?;
             certs.push(crate::registry::Certificate {
                 data: cert_data,
@@ -79,12 +74,34 @@ impl RepositoryHelper {
     }
 
     fn fulcio_cert_target_names(&self) -> Vec<TargetName> {
+        self.target_names_matching(&SIGSTORE_FULCIO_CERT_TARGET_REGEX)
+    }
+
+    /// Fetch the public keys of the certificate transparency logs used by
+    /// Fulcio from the given TUF repository or reuse the local cache if its
+    /// contents are not outdated.
+    ///
+    /// The contents of the local cache are updated when they are outdated.
+    pub(crate) fn ctfe_pub_keys(&self) -> Result<Vec<Vec<u8>>> {
+        self.target_names_matching(&SIGSTORE_CTFE_PUB_KEY_TARGET_REGEX)
+            .iter()
+            .map(|target_name| {
+                fetch_target_or_reuse_local_cache(
+                    &self.repository,
+                    target_name,
+                    self.cache.as_deref(),
+                )
+            })
+            .collect()
+    }
+
+    fn target_names_matching(&self, regex: &regex::Regex) -> Vec<TargetName> {
         self.repository
             .targets()
             .signed
             .targets_iter()
             .filter_map(|(target_name, _target)| {
-                if SIGSTORE_FULCIO_CERT_TARGET_REGEX.is_match(target_name.raw()) {
+                if regex.is_match(target_name.raw()) {
                     Some(target_name.clone())
                 } else {
                     None
@@ -100,15 +117,10 @@ impl RepositoryHelper {
     pub(crate) fn rekor_pub_key(&self) -> Result<Vec<u8>> {
         let rekor_target_name = TargetName::new(SIGSTORE_REKOR_PUB_KEY_TARGET).map_err(Box::new)?;
 
-        let local_rekor_path = self
-            .checkout_dir
-            .as_ref()
-            .map(|d| Path::new(d).join(SIGSTORE_REKOR_PUB_KEY_TARGET));
-
         fetch_target_or_reuse_local_cache(
             &self.repository,
             &rekor_target_name,
-            local_rekor_path.as_ref(),
+            self.cache.as_deref(),
         )
     }
 }
@@ -118,40 +130,34 @@ impl RepositoryHelper {
 ///
 /// * `repository`: TUF repository holding the file
 /// * `target_name`: TUF representation of the file to be downloaded
-/// * `local_file`: location where the file should be downloaded
+/// * `cache`: storage where the file should be cached, using the name of
+///   the target as key
 ///
-/// This function will reuse the local copy of the file if contents
+/// This function will reuse the cached copy of the file if contents
 /// didn't change.
-/// This check is done by comparing the digest of the local file, if found,
+/// This check is done by comparing the digest of the cached file, if found,
 /// with the digest reported inside of the TUF repository metadata.
 ///
-/// **Note well:** the `local_file` is updated whenever its contents are
+/// **Note well:** the cached file is updated whenever its contents are
 /// outdated.
 fn fetch_target_or_reuse_local_cache(
     repository: &tough::Repository,
     target_name: &TargetName,
-    local_file: Option<&PathBuf>,
+    cache: Option<&dyn CacheStorage>,
 ) -> Result<Vec<u8>> {
-    let (local_file_outdated, local_file_contents) = if let Some(path) = local_file {
-        is_local_file_outdated(repository, target_name, path)
-    } else {
-        Ok((true, None))
-    }?;
-
-    let data = if local_file_outdated {
-        let data = fetch_target(repository, target_name)?;
-        if let Some(path) = local_file {
-            // update the local file to have latest data from the TUF repo
-            fs::write(path, data.clone())?;
+    if let Some(cache) = cache {
+        if let Some(data) = cache.get(target_name.raw())? {
+            if !is_cached_data_outdated(repository, target_name, &data)? {
+                return Ok(data);
+            }
         }
-        data
-    } else {
-        local_file_contents
-            .expect("local file contents to not be 'None'")
-            .as_bytes()
-            .to_owned()
-    };
+    }
 
+    let data = fetch_target(repository, target_name)?;
+    if let Some(cache) = cache {
+        // update the cache to have latest data from the TUF repo
+        cache.set(target_name.raw(), &data, None)?;
+    }
     Ok(data)
 }
 
@@ -169,13 +175,13 @@ fn fetch_target(repository: &tough::Repository, target_name: &TargetName) -> Res
     }
 }
 
-/// Compares the checksum of a local file, with the digest reported inside of
+/// Compares the checksum of cached data, with the digest reported inside of
 /// TUF repository metadata
-fn is_local_file_outdated(
+fn is_cached_data_outdated(
     repository: &tough::Repository,
     target_name: &TargetName,
-    local_file: &Path,
-) -> Result<(bool, Option<String>)> {
+    data: &[u8],
+) -> Result<bool> {
     let target = repository
         .targets()
         .signed
@@ -183,20 +189,9 @@ fn is_local_file_outdated(
         .get(target_name)
         .ok_or_else(|| SigstoreError::TufTargetNotFoundError(target_name.raw().to_string()))?;
 
-    if local_file.exists() {
-        let data = fs::read_to_string(local_file)?;
-        let local_checksum = Sha256::digest(data.clone());
-        let expected_digest: Vec<u8> = target.hashes.sha256.to_vec();
-
-        if local_checksum.as_slice() == expected_digest.as_slice() {
-            // local data is not outdated
-            Ok((false, Some(data)))
-        } else {
-            Ok((true, None))
-        }
-    } else {
-        Ok((true, None))
-    }
+    let local_checksum = Sha256::digest(data);
+    let expected_digest: Vec<u8> = target.hashes.sha256.to_vec();
+    Ok(local_checksum.as_slice() != expected_digest.as_slice())
 }
 
 /// Gets the goods from a read and makes a Vec
@@ -210,6 +205,8 @@ fn read_to_end<R: Read>(mut reader: R) -> Result<Vec<u8>> {
 mod tests {
     use super::super::constants::*;
     use super::*;
+    use crate::cache::FilesystemCache;
+    use std::fs;
     use std::path::PathBuf;
     use tempfile::TempDir;
 
@@ -265,7 +262,7 @@ mod tests {
         let repository = local_tuf_repo().expect("Local TUF repo should not fail");
         let helper = RepositoryHelper {
             repository,
-            checkout_dir: None,
+            cache: None,
         };
 
         let mut actual = helper.fulcio_certs().expect("fulcio certs cannot be read");
@@ -307,6 +304,21 @@ mod tests {
             actual, expected,
             "The rekor key read from the TUF repository is not what was expected"
         );
+
+        let actual = helper.ctfe_pub_keys().expect("ctfe keys cannot be read");
+        let expected = fs::read(
+            test_data()
+                .join("repository")
+                .join("targets")
+                .join("ctfe.pub"),
+        )
+        .expect("cannot read ctfe key from test data");
+
+        assert_eq!(
+            actual,
+            vec![expected],
+            "The ctfe keys read from the TUF repository are not what was expected"
+        );
     }
 
     #[test]
@@ -316,7 +328,7 @@ mod tests {
         let repository = local_tuf_repo().expect("Local TUF repo should not fail");
         let helper = RepositoryHelper {
             repository,
-            checkout_dir: Some(cache_dir.path().to_path_buf()),
+            cache: Some(Arc::new(FilesystemCache::new(cache_dir.path()))),
         };
 
         let mut actual = helper.fulcio_certs().expect("fulcio certs cannot be read");
@@ -373,7 +385,7 @@ mod tests {
         let repository = local_tuf_repo().expect("Local TUF repo should not fail");
         let helper = RepositoryHelper {
             repository,
-            checkout_dir: Some(cache_dir.path().to_path_buf()),
+            cache: Some(Arc::new(FilesystemCache::new(cache_dir.path()))),
         };
 
         let mut actual = helper.fulcio_certs().expect("fulcio certs cannot be read");